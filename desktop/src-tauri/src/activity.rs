@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::Path;
+
+use crate::sync::{read_mapping, sha256_hex};
+
+pub(crate) use diregram_sync_core::activity::*;
+
+#[tauri::command]
+pub async fn sync_file_activity(vault_path: String, rel_path: String) -> Result<FileActivity, String> {
+  let rel = crate::revisions::checked_rel(&rel_path)?;
+  let abs = crate::normalize::local_path(Path::new(&vault_path), &rel);
+  let mapped_hash = read_mapping(&vault_path)?.and_then(|m| m.files.get(&rel).map(|f| f.local_hash.clone()));
+  let activity = with_state(&vault_path, |state, _| state.files.get(&rel).cloned())
+    .flatten()
+    .unwrap_or_default();
+  let has_conflict = crate::conflicts::list(&vault_path).iter().any(|c| c.path == rel);
+
+  let badge = match (fs::read(&abs).ok(), mapped_hash) {
+    _ if has_conflict => FileSyncBadge::Conflict,
+    (None, Some(_)) => FileSyncBadge::Missing,
+    (_, None) => FileSyncBadge::LocalOnly,
+    (Some(bytes), Some(hash)) if sha256_hex(&bytes) == hash => FileSyncBadge::Synced,
+    (Some(_), Some(_)) => FileSyncBadge::Modified,
+  };
+  let mut conflicts = activity.conflicts;
+  conflicts.reverse();
+  Ok(FileActivity {
+    path: rel,
+    badge,
+    last_local_edit: mtime_iso(&abs),
+    last_push_at: activity.last_push_at,
+    last_pull_at: activity.last_pull_at,
+    conflicts,
+  })
+}
+
+/// The most recent push, pull or conflict of each note, newest first.
+#[tauri::command]
+pub async fn sync_recent_activity(vault_path: String, limit: Option<u32>) -> Result<Vec<RecentActivity>, String> {
+  Ok(recent(&vault_path, limit.unwrap_or(50) as usize))
+}
@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::Manager;
+
+use crate::notes::note_rel;
+use crate::sync::{append_event, now_iso, write_atomic, SyncEvent};
+use crate::vaults::RegisteredVault;
+
+const FILE_NAME: &str = "api.json";
+/// Secure storage key of the bearer token, in the default profile.
+const TOKEN_KEY: &str = "local-api-token";
+const DEFAULT_PORT: u16 = 27_183;
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+/// Request line plus headers.
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+/// The whole request must arrive within this, however slowly it trickles in.
+const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+/// Connections served at once; further ones wait in the listen backlog.
+const MAX_CONNECTIONS: usize = 8;
+const SEARCH_LIMIT: usize = 50;
+
+static DIR: OnceCell<PathBuf> = OnceCell::new();
+/// Stop flag and thread of the running server.
+type Server = (Arc<AtomicBool>, JoinHandle<()>);
+
+static SERVER: Lazy<Mutex<Option<Server>>> = Lazy::new(|| Mutex::new(None));
+
+/// `<app config>/api.json`. The token is kept in secure storage, not here.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+struct LocalApiConfigV1 {
+  version: u32,
+  enabled: bool,
+  port: u16,
+}
+
+impl Default for LocalApiConfigV1 {
+  fn default() -> Self {
+    Self {
+      version: 1,
+      enabled: false,
+      port: DEFAULT_PORT,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct LocalApiStatus {
+  pub enabled: bool,
+  pub port: u16,
+  /// `http://127.0.0.1:<port>` while the server runs.
+  pub url: Option<String>,
+  /// Sent as `Authorization: Bearer <token>` by clients.
+  pub token: Option<String>,
+}
+
+struct Request {
+  method: String,
+  path: String,
+  query: HashMap<String, String>,
+  headers: HashMap<String, String>,
+  body: Vec<u8>,
+}
+
+struct Response {
+  status: &'static str,
+  content_type: &'static str,
+  body: String,
+}
+
+impl Response {
+  fn json(status: &'static str, body: Value) -> Self {
+    Self {
+      status,
+      content_type: "application/json",
+      body: body.to_string(),
+    }
+  }
+
+  fn error(status: &'static str, message: &str) -> Self {
+    Self::json(status, json!({ "error": message }))
+  }
+}
+
+fn file_path() -> Option<PathBuf> {
+  DIR.get().map(|d| d.join(FILE_NAME))
+}
+
+fn load() -> LocalApiConfigV1 {
+  file_path()
+    .and_then(|p| fs::read_to_string(p).ok())
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+fn save(config: &LocalApiConfigV1) -> Result<(), String> {
+  let path = file_path().ok_or("the app config folder is not known yet")?;
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+  }
+  let text = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+  write_atomic(&path, text).map_err(|e| e.to_string())
+}
+
+fn new_token() -> Result<String, String> {
+  let mut bytes = [0u8; 32];
+  getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+  let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+  crate::secrets::set(None, TOKEN_KEY, &token)?;
+  Ok(token)
+}
+
+fn token() -> Result<String, String> {
+  match crate::secrets::get(None, TOKEN_KEY)? {
+    Some(t) => Ok(t),
+    None => new_token(),
+  }
+}
+
+/// Compares without leaking where the first difference is.
+fn same_token(a: &str, b: &str) -> bool {
+  a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// One read that gives up at `deadline`; `None` on timeout, error or a closed connection.
+fn read_until(stream: &mut TcpStream, buf: &mut [u8], deadline: Instant) -> Option<usize> {
+  let left = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero())?;
+  stream.set_read_timeout(Some(left)).ok()?;
+  stream.read(buf).ok().filter(|&n| n > 0)
+}
+
+fn read_request(stream: &mut TcpStream, deadline: Instant) -> Option<Request> {
+  let mut data = Vec::new();
+  let mut buf = [0u8; 8192];
+  let head_end = loop {
+    let n = read_until(stream, &mut buf, deadline)?;
+    data.extend_from_slice(&buf[..n]);
+    if let Some(i) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+      break i;
+    }
+    if data.len() > MAX_HEAD_BYTES {
+      return None;
+    }
+  };
+  if head_end > MAX_HEAD_BYTES {
+    return None;
+  }
+  let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+  let mut lines = head.lines();
+  let mut first = lines.next()?.split_whitespace();
+  let method = first.next()?.to_string();
+  let url = Url::parse(&format!("http://127.0.0.1{}", first.next()?)).ok()?;
+  let headers: HashMap<String, String> = lines
+    .filter_map(|l| l.split_once(':'))
+    .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+    .collect();
+  let len: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+  if len > MAX_BODY_BYTES {
+    return None;
+  }
+  let mut body = data[head_end + 4..].to_vec();
+  while body.len() < len {
+    let n = read_until(stream, &mut buf, deadline)?;
+    body.extend_from_slice(&buf[..n]);
+  }
+  body.truncate(len);
+  Some(Request {
+    method,
+    path: crate::links::percent_decode(url.path()),
+    query: url.query_pairs().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+    headers,
+    body,
+  })
+}
+
+/// The registered vault a request names with `?vault=` (its name or path), or the only one.
+fn vault_for<'v>(req: &Request, vaults: &'v [RegisteredVault]) -> Result<&'v RegisteredVault, Response> {
+  match req.query.get("vault") {
+    Some(v) => vaults
+      .iter()
+      .find(|r| &r.name == v || &r.vault_path == v)
+      .ok_or_else(|| Response::error("404 Not Found", "no registered vault by that name or path")),
+    None if vaults.len() == 1 => Ok(&vaults[0]),
+    None if vaults.is_empty() => Err(Response::error("404 Not Found", "no vault is registered")),
+    None => Err(Response::error("400 Bad Request", "several vaults are registered; pass ?vault=<name or path>")),
+  }
+}
+
+fn list_notes(vault_path: &str) -> Response {
+  let list: Vec<Value> = crate::notes::list(vault_path)
+    .into_iter()
+    .map(|(abs, rel)| {
+      let meta = fs::metadata(&abs).ok();
+      let modified = meta.as_ref().and_then(|m| m.modified().ok()).map(|t| DateTime::<Utc>::from(t).to_rfc3339());
+      json!({ "path": rel, "bytes": meta.map(|m| m.len()).unwrap_or(0), "modified": modified })
+    })
+    .collect();
+  Response::json("200 OK", Value::Array(list))
+}
+
+fn read_note(vault_path: &str, rel: &str) -> Response {
+  let Some(rel) = note_rel(rel) else {
+    return Response::error("400 Bad Request", "not a note path");
+  };
+  match fs::read(Path::new(vault_path).join(&rel)) {
+    Ok(bytes) => Response {
+      status: "200 OK",
+      content_type: "text/markdown; charset=utf-8",
+      body: crate::encoding::decode(&bytes).0,
+    },
+    Err(_) => Response::error("404 Not Found", "no such note"),
+  }
+}
+
+#[derive(Deserialize)]
+struct WriteNote {
+  path: String,
+  content: String,
+  /// Replace a note that exists already instead of answering 409.
+  #[serde(default)]
+  overwrite: bool,
+}
+
+/// Writes a note into the vault; a watched vault pushes it like any other edit.
+fn write_note(vault_path: &str, body: &[u8]) -> Response {
+  let note: WriteNote = match serde_json::from_slice(body) {
+    Ok(n) => n,
+    Err(e) => return Response::error("400 Bad Request", &e.to_string()),
+  };
+  let Some(rel) = note_rel(&note.path) else {
+    return Response::error("400 Bad Request", "only .md notes outside hidden folders, resources/ and rag/ can be written");
+  };
+  let target = Path::new(vault_path).join(&rel);
+  if target.exists() && !note.overwrite {
+    return Response::error("409 Conflict", "the note exists; pass \"overwrite\": true to replace it");
+  }
+  let written = target
+    .parent()
+    .map(fs::create_dir_all)
+    .unwrap_or(Ok(()))
+    .and_then(|_| write_atomic(&target, note.content.as_bytes()));
+  if let Err(e) = written {
+    return Response::error("500 Internal Server Error", &e.to_string());
+  }
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "api_write".to_string(),
+      path: rel.clone(),
+      detail: "Written through the local API.".to_string(),
+    },
+  );
+  Response::json("201 Created", json!({ "path": rel }))
+}
+
+/// Saves a web clip into the vault's clip folder. The push (and ingest) runs after the answer, so
+/// the clipper isn't kept waiting and other requests aren't held up.
+fn clip(vault_path: &str, body: &[u8]) -> Response {
+  let clip: crate::clip::Clip = match serde_json::from_slice(body) {
+    Ok(c) => c,
+    Err(e) => return Response::error("400 Bad Request", &e.to_string()),
+  };
+  match crate::clip::write(vault_path, &clip) {
+    Ok(rel) => {
+      let (vp, r) = (vault_path.to_string(), rel.clone());
+      tauri::async_runtime::spawn(async move { crate::clip::push(&vp, &r).await });
+      Response::json("201 Created", json!({ "path": rel }))
+    }
+    Err(e) => Response::error("400 Bad Request", &e),
+  }
+}
+
+/// Lines of notes containing `q` (case-insensitive), in path order.
+fn search(vault_path: &str, q: &str) -> Response {
+  if q.trim().is_empty() {
+    return Response::error("400 Bad Request", "q is required");
+  }
+  let hits = crate::notes::search(vault_path, q, SEARCH_LIMIT);
+  Response::json("200 OK", serde_json::to_value(hits).unwrap_or_default())
+}
+
+fn route(req: &Request, token: &str, vaults: &[RegisteredVault]) -> Response {
+  let bearer = req.headers.get("authorization").and_then(|v| v.strip_prefix("Bearer ")).unwrap_or("");
+  if !same_token(bearer, token) {
+    return Response::error("401 Unauthorized", "missing or wrong bearer token");
+  }
+  let vault = match vault_for(req, vaults) {
+    Ok(v) => v,
+    Err(res) => return res,
+  };
+  let vault_path = vault.vault_path.as_str();
+  match (req.method.as_str(), req.path.as_str()) {
+    ("GET", "/notes") => list_notes(vault_path),
+    ("POST", "/notes") => write_note(vault_path, &req.body),
+    ("GET", p) if p.starts_with("/notes/") => read_note(vault_path, &p["/notes/".len()..]),
+    ("POST", "/clip") => clip(vault_path, &req.body),
+    ("GET", "/search") => search(vault_path, req.query.get("q").map(String::as_str).unwrap_or("")),
+    ("GET", "/sync/status") => {
+      let status = crate::status::snapshot().into_iter().find(|s| s.vault_path == vault_path);
+      Response::json("200 OK", json!({ "vault": vault.name, "vault_path": vault_path, "status": status }))
+    }
+    _ => Response::error("404 Not Found", "no such endpoint"),
+  }
+}
+
+fn respond(stream: &mut TcpStream, res: Response) {
+  let _ = write!(
+    stream,
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    res.status,
+    res.content_type,
+    res.body.len(),
+    res.body
+  );
+}
+
+/// Answers one connection. The request must arrive within `REQUEST_DEADLINE`.
+fn serve(mut stream: TcpStream, token: &str) {
+  let _ = stream.set_nonblocking(false);
+  let _ = stream.set_write_timeout(Some(REQUEST_DEADLINE));
+  let res = match read_request(&mut stream, Instant::now() + REQUEST_DEADLINE) {
+    Some(req) => route(&req, token, &crate::vaults::registered()),
+    None => Response::error("400 Bad Request", "malformed, oversized or too slow request"),
+  };
+  respond(&mut stream, res);
+}
+
+/// Serves on `127.0.0.1:<port>` until stopped, each connection on its own thread (at most
+/// `MAX_CONNECTIONS` at once), so a slow client can't hold up the others.
+fn start(port: u16, token: String) -> Result<SocketAddr, String> {
+  stop();
+  let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("could not listen on port {}: {}", port, e))?;
+  listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+  let addr = listener.local_addr().map_err(|e| e.to_string())?;
+  let stopped = Arc::new(AtomicBool::new(false));
+  let flag = stopped.clone();
+  let token: Arc<str> = token.into();
+  let thread = std::thread::spawn(move || {
+    let active = Arc::new(AtomicUsize::new(0));
+    while !flag.load(Ordering::SeqCst) {
+      if active.load(Ordering::SeqCst) >= MAX_CONNECTIONS {
+        std::thread::sleep(Duration::from_millis(20));
+        continue;
+      }
+      let stream = match listener.accept() {
+        Ok((s, _)) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+          std::thread::sleep(Duration::from_millis(100));
+          continue;
+        }
+        Err(_) => continue,
+      };
+      active.fetch_add(1, Ordering::SeqCst);
+      let (active, token) = (active.clone(), token.clone());
+      std::thread::spawn(move || {
+        serve(stream, &token);
+        active.fetch_sub(1, Ordering::SeqCst);
+      });
+    }
+  });
+  if let Ok(mut g) = SERVER.lock() {
+    *g = Some((stopped, thread));
+  }
+  tracing::info!(%addr, "serving the local API");
+  Ok(addr)
+}
+
+fn stop() {
+  let running = SERVER.lock().ok().and_then(|mut g| g.take());
+  if let Some((stopped, thread)) = running {
+    stopped.store(true, Ordering::SeqCst);
+    let _ = thread.join();
+  }
+}
+
+fn status(config: &LocalApiConfigV1) -> Result<LocalApiStatus, String> {
+  let running = SERVER.lock().map(|g| g.is_some()).unwrap_or(false);
+  Ok(LocalApiStatus {
+    enabled: config.enabled,
+    port: config.port,
+    url: running.then(|| format!("http://127.0.0.1:{}", config.port)),
+    token: if config.enabled { Some(token()?) } else { None },
+  })
+}
+
+/// Starts the API if it was left on. Call from setup.
+pub(crate) fn install(app: &tauri::AppHandle) {
+  if let Ok(dir) = app.path().app_config_dir() {
+    let _ = DIR.set(dir);
+  }
+  let config = load();
+  if !config.enabled {
+    return;
+  }
+  if let Err(e) = token().and_then(|t| start(config.port, t)) {
+    tracing::warn!(error = %e, "could not start the local API");
+  }
+}
+
+#[tauri::command]
+pub async fn local_api_status() -> Result<LocalApiStatus, String> {
+  status(&load())
+}
+
+/// Turns the localhost API on or off. The token is created the first time it is turned on.
+#[tauri::command]
+pub async fn local_api_configure(enabled: bool, port: Option<u16>) -> Result<LocalApiStatus, String> {
+  let mut config = load();
+  config.version = 1;
+  config.enabled = enabled;
+  if let Some(p) = port {
+    if p == 0 {
+      return Err("port must be greater than 0".to_string());
+    }
+    config.port = p;
+  }
+  save(&config)?;
+  if enabled {
+    start(config.port, token()?)?;
+  } else {
+    stop();
+  }
+  status(&config)
+}
+
+/// Replaces the token; clients using the old one are refused from now on.
+#[tauri::command]
+pub async fn local_api_rotate_token() -> Result<LocalApiStatus, String> {
+  let config = load();
+  let token = new_token()?;
+  if config.enabled {
+    start(config.port, token)?;
+  }
+  status(&config)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `raw` sent over a loopback connection, as the server reads it.
+  fn parsed(raw: Vec<u8>, deadline: Duration) -> Option<Request> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = std::thread::spawn(move || {
+      let mut c = TcpStream::connect(addr).unwrap();
+      let _ = c.write_all(&raw);
+      // Held open so only the server's deadline ends a short request.
+      std::thread::sleep(deadline + Duration::from_millis(200));
+    });
+    let (mut stream, _) = listener.accept().unwrap();
+    let req = read_request(&mut stream, Instant::now() + deadline);
+    client.join().unwrap();
+    req
+  }
+
+  #[test]
+  fn same_token_compares_whole_tokens() {
+    assert!(same_token("abc123", "abc123"));
+    assert!(!same_token("abc123", "abc124"));
+    assert!(!same_token("abc123", "abc12"));
+    assert!(!same_token("", "abc123"));
+  }
+
+  #[test]
+  fn parses_request_line_headers_and_body() {
+    let raw = b"POST /notes/Daily%20Notes/a.md?vault=Work HTTP/1.1\r\n\
+      Authorization: Bearer t\r\nContent-Length: 5\r\n\r\nhello";
+    let req = parsed(raw.to_vec(), Duration::from_secs(2)).expect("request");
+    assert_eq!(req.method, "POST");
+    assert_eq!(req.path, "/notes/Daily Notes/a.md");
+    assert_eq!(req.query.get("vault").map(String::as_str), Some("Work"));
+    assert_eq!(req.headers.get("authorization").map(String::as_str), Some("Bearer t"));
+    assert_eq!(req.body, b"hello");
+  }
+
+  #[test]
+  fn rejects_oversized_heads() {
+    let mut raw = b"GET /notes HTTP/1.1\r\nX-Pad: ".to_vec();
+    raw.resize(MAX_HEAD_BYTES + 1024, b'a');
+    raw.extend_from_slice(b"\r\n\r\n");
+    assert!(parsed(raw, Duration::from_secs(2)).is_none());
+  }
+
+  #[test]
+  fn gives_up_on_requests_past_the_deadline() {
+    let started = Instant::now();
+    assert!(parsed(b"GET /notes HTTP/1.1\r\nAuthor".to_vec(), Duration::from_millis(300)).is_none());
+    assert!(started.elapsed() < Duration::from_secs(2));
+  }
+
+  #[test]
+  fn note_paths_stay_inside_the_vault() {
+    assert_eq!(note_rel("Notes/a.md").as_deref(), Some("Notes/a.md"));
+    let rejected = [
+      "../secret.md",
+      "Notes/../../secret.md",
+      "./a.md",
+      ".obsidian/a.md",
+      "resources/a.md",
+      "rag/a.md",
+      "a.txt",
+      "",
+    ];
+    for rel in rejected {
+      assert_eq!(note_rel(rel), None, "{}", rel);
+    }
+  }
+}
@@ -0,0 +1,327 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+use crate::sync::{
+  append_event, fetch_project_remote_ids, mapping_path, now_iso, read_mapping, to_rel_posix, write_mapping, SupabaseAuth,
+  SyncEvent,
+};
+
+pub(crate) const ARCHIVE_MANIFEST_NAME: &str = "manifest.json";
+pub(crate) const ARCHIVE_FORMAT: &str = "diregram-vault-archive";
+pub(crate) const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveManifest {
+  pub format: String,
+  pub format_version: u32,
+  pub app_version: String,
+  pub created_at: String,
+  pub source_vault_path: String,
+  pub mapping_version: Option<u32>,
+  pub project_folder_id: Option<String>,
+  pub mapping_updated_at: Option<String>,
+  pub last_pull_at: Option<String>,
+  pub include_rag: bool,
+  pub include_trash: bool,
+  pub files: u32,
+  pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ArchiveProgress {
+  pub vault_path: String,
+  pub done: u32,
+  pub total: u32,
+  pub path: String,
+}
+
+fn should_archive(rel: &str, include_rag: bool, include_trash: bool) -> bool {
+  if !include_trash && (rel == ".diregram/trash" || rel.starts_with(".diregram/trash/")) {
+    return false;
+  }
+  if !include_rag && (rel == "rag" || rel.starts_with("rag/")) {
+    return false;
+  }
+  true
+}
+
+pub(crate) fn collect_archive_files(
+  root: &Path,
+  include_rag: bool,
+  include_trash: bool,
+  exclude: Option<&Path>,
+) -> Vec<(PathBuf, String)> {
+  let mut out: Vec<(PathBuf, String)> = Vec::new();
+  for entry in WalkDir::new(root)
+    .follow_links(false)
+    .sort_by_file_name()
+    .into_iter()
+    .filter_map(Result::ok)
+  {
+    if !entry.file_type().is_file() {
+      continue;
+    }
+    let p = entry.path();
+    if exclude.map(|x| x == p).unwrap_or(false) {
+      continue;
+    }
+    let Some(rel) = to_rel_posix(root, p) else { continue };
+    if !should_archive(&rel, include_rag, include_trash) {
+      continue;
+    }
+    out.push((p.to_path_buf(), rel));
+  }
+  out
+}
+
+/// Writes `files` plus a manifest into a zip at `dest`. Shared by manual export and scheduled backups.
+pub(crate) fn write_vault_archive(
+  vault_path: &str,
+  dest: &Path,
+  files: &[(PathBuf, String)],
+  include_rag: bool,
+  include_trash: bool,
+  mut on_progress: impl FnMut(u32, u32, &str),
+) -> Result<ArchiveManifest, String> {
+  if let Some(parent) = dest.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  // Write to a temp name first so a crash never leaves a truncated archive under the final name.
+  let tmp = dest.with_extension("zip.partial");
+  let out = File::create(&tmp).map_err(|e| e.to_string())?;
+  let mut zip = zip::ZipWriter::new(out);
+  let options = SimpleFileOptions::default()
+    .compression_method(CompressionMethod::Deflated)
+    .large_file(true);
+
+  let total = files.len() as u32;
+  let mut bytes: u64 = 0;
+  for (i, (abs, rel)) in files.iter().enumerate() {
+    zip.start_file(rel.as_str(), options).map_err(|e| e.to_string())?;
+    let mut f = File::open(abs).map_err(|e| format!("{}: {}", rel, e))?;
+    bytes += io::copy(&mut f, &mut zip).map_err(|e| format!("{}: {}", rel, e))?;
+    on_progress(i as u32 + 1, total, rel);
+  }
+
+  let mapping = read_mapping(vault_path).ok().flatten();
+  let manifest = ArchiveManifest {
+    format: ARCHIVE_FORMAT.to_string(),
+    format_version: ARCHIVE_FORMAT_VERSION,
+    app_version: env!("CARGO_PKG_VERSION").to_string(),
+    created_at: now_iso(),
+    source_vault_path: vault_path.to_string(),
+    mapping_version: mapping.as_ref().map(|m| m.version),
+    project_folder_id: mapping.as_ref().map(|m| m.project_folder_id.clone()),
+    mapping_updated_at: mapping.as_ref().map(|m| m.updated_at.clone()),
+    last_pull_at: mapping.as_ref().map(|m| m.last_pull_at.clone()),
+    include_rag,
+    include_trash,
+    files: total,
+    bytes,
+  };
+  let manifest_text = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+  zip.start_file(ARCHIVE_MANIFEST_NAME, options).map_err(|e| e.to_string())?;
+  io::Write::write_all(&mut zip, manifest_text.as_bytes()).map_err(|e| e.to_string())?;
+  zip.finish().map_err(|e| e.to_string())?;
+
+  fs::rename(&tmp, dest).map_err(|e| e.to_string())?;
+  Ok(manifest)
+}
+
+#[tauri::command]
+pub async fn vault_export_archive(
+  app: tauri::AppHandle,
+  vault_path: String,
+  dest_path: String,
+  include_rag: bool,
+  include_trash: bool,
+) -> Result<ArchiveManifest, String> {
+  let root = Path::new(&vault_path);
+  if !root.exists() {
+    return Err("vault_path does not exist".to_string());
+  }
+  if dest_path.trim().is_empty() {
+    return Err("dest_path is required".to_string());
+  }
+  let dest = PathBuf::from(dest_path.trim());
+  let files = collect_archive_files(root, include_rag, include_trash, Some(&dest));
+
+  let manifest = write_vault_archive(&vault_path, &dest, &files, include_rag, include_trash, |done, total, rel| {
+    // Throttle events for large vaults: first, last and every 50th file.
+    if done == 1 || done == total || done % 50 == 0 {
+      let _ = app.emit(
+        "vault://archive_progress",
+        ArchiveProgress {
+          vault_path: vault_path.clone(),
+          done,
+          total,
+          path: rel.to_string(),
+        },
+      );
+    }
+  })?;
+
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "archive_export".to_string(),
+      path: String::new(),
+      detail: format!("Exported {} files ({} bytes) to {}", manifest.files, manifest.bytes, dest.display()),
+    },
+  );
+  Ok(manifest)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ArchiveImportSummary {
+  pub files: u32,
+  pub project_folder_id: Option<String>,
+  pub relinked: bool,
+  /// Mapping entries dropped because their remote rows no longer exist (they re-upload on next push).
+  pub stale_files: u32,
+  pub stale_resources: u32,
+}
+
+pub(crate) fn read_archive_manifest(archive: &mut zip::ZipArchive<File>) -> Result<ArchiveManifest, String> {
+  let mut entry = archive
+    .by_name(ARCHIVE_MANIFEST_NAME)
+    .map_err(|_| "archive has no manifest.json (not a Diregram vault archive)".to_string())?;
+  let mut text = String::new();
+  io::Read::read_to_string(&mut entry, &mut text).map_err(|e| e.to_string())?;
+  let manifest: ArchiveManifest = serde_json::from_str(&text).map_err(|e| format!("invalid manifest.json: {}", e))?;
+  if manifest.format != ARCHIVE_FORMAT {
+    return Err(format!("unsupported archive format: {}", manifest.format));
+  }
+  if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+    return Err(format!(
+      "archive format version {} is newer than this app supports ({})",
+      manifest.format_version, ARCHIVE_FORMAT_VERSION
+    ));
+  }
+  Ok(manifest)
+}
+
+/// Unpacks every entry except the manifest into `dest`, rejecting paths that escape it.
+pub(crate) fn extract_archive(
+  archive: &mut zip::ZipArchive<File>,
+  dest: &Path,
+  mut on_progress: impl FnMut(u32, u32, &str),
+) -> Result<u32, String> {
+  let total = archive.len() as u32;
+  let mut files: u32 = 0;
+  for i in 0..archive.len() {
+    let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+    if entry.name() == ARCHIVE_MANIFEST_NAME {
+      continue;
+    }
+    let Some(rel) = entry.enclosed_name() else {
+      return Err(format!("archive entry escapes the vault: {}", entry.name()));
+    };
+    let target = dest.join(&rel);
+    if entry.is_dir() {
+      fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+      continue;
+    }
+    if let Some(parent) = target.parent() {
+      fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut out = File::create(&target).map_err(|e| format!("{}: {}", target.display(), e))?;
+    io::copy(&mut entry, &mut out).map_err(|e| format!("{}: {}", target.display(), e))?;
+    files += 1;
+    on_progress(i as u32 + 1, total, &rel.to_string_lossy());
+  }
+  Ok(files)
+}
+
+#[tauri::command]
+pub async fn vault_import_archive(
+  app: tauri::AppHandle,
+  archive_path: String,
+  dest_vault_path: String,
+  relink: Option<bool>,
+  auth: Option<SupabaseAuth>,
+) -> Result<ArchiveImportSummary, String> {
+  let dest = Path::new(&dest_vault_path);
+  if dest_vault_path.trim().is_empty() {
+    return Err("dest_vault_path is required".to_string());
+  }
+  if dest.exists() && fs::read_dir(dest).map_err(|e| e.to_string())?.next().is_some() {
+    return Err("dest_vault_path must be empty (restore never overwrites an existing vault)".to_string());
+  }
+  let file = File::open(&archive_path).map_err(|e| format!("cannot open archive: {}", e))?;
+  let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("cannot read archive: {}", e))?;
+  let manifest = read_archive_manifest(&mut archive)?;
+
+  fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+  let files = extract_archive(&mut archive, dest, |done, total, rel| {
+    if done == 1 || done == total || done % 50 == 0 {
+      let _ = app.emit(
+        "vault://archive_progress",
+        ArchiveProgress {
+          vault_path: dest_vault_path.clone(),
+          done,
+          total,
+          path: rel.to_string(),
+        },
+      );
+    }
+  })?;
+
+  let mut summary = ArchiveImportSummary {
+    files,
+    project_folder_id: manifest.project_folder_id.clone(),
+    ..Default::default()
+  };
+
+  let _mapping = crate::lock::mapping(&dest_vault_path).await;
+  if let Some(mut mapping) = read_mapping(&dest_vault_path)? {
+    if !relink.unwrap_or(false) {
+      // Keep the old link for reference but leave the restored vault unlinked.
+      let restored = mapping_path(&dest_vault_path);
+      fs::rename(&restored, restored.with_file_name("sync.imported.json")).map_err(|e| e.to_string())?;
+    } else {
+      let mut auth = auth.ok_or_else(|| "auth is required to relink a restored vault".to_string())?;
+      let client = reqwest::Client::new();
+      let Some((file_ids, resource_ids)) =
+        fetch_project_remote_ids(&client, &mut auth, &mapping.project_folder_id).await?
+      else {
+        return Err(format!(
+          "Project {} no longer exists remotely; restored files are on disk but the vault was not relinked.",
+          mapping.project_folder_id
+        ));
+      };
+      let before_files = mapping.files.len();
+      let before_resources = mapping.resources.len();
+      mapping.files.retain(|_, fm| file_ids.contains(&fm.file_id));
+      mapping.resources.retain(|_, rm| resource_ids.contains(&rm.resource_id));
+      summary.stale_files = (before_files - mapping.files.len()) as u32;
+      summary.stale_resources = (before_resources - mapping.resources.len()) as u32;
+      mapping.vault_path = dest_vault_path.clone();
+      mapping.updated_at = now_iso();
+      write_mapping(&dest_vault_path, &mapping)?;
+      summary.relinked = true;
+    }
+  }
+
+  let _ = append_event(
+    &dest_vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "archive_import".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Restored {} files from {} (exported {}). Relinked: {}. Stale mapping entries dropped: {} files, {} resources.",
+        files, archive_path, manifest.created_at, summary.relinked, summary.stale_files, summary.stale_resources
+      ),
+    },
+  );
+  Ok(summary)
+}
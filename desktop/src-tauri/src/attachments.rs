@@ -0,0 +1,11 @@
+use crate::config::{read_config, write_config, AttachmentConfig};
+
+/// Replaces the vault's attachment settings. They apply from the next push; switching `backend`
+/// uploads every attachment to the new store then.
+#[tauri::command]
+pub async fn attachments_configure(vault_path: String, config: AttachmentConfig) -> Result<AttachmentConfig, String> {
+  let mut cfg = read_config(&vault_path)?;
+  cfg.attachments = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
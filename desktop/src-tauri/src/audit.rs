@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+
+use crate::events::{entry_hash, read_entries, Actor, AuditEntry};
+use crate::sync::{append_event, now_iso, sha256_hex, write_atomic, SyncEvent};
+
+/// Secure-storage key of this device's export signing key (PKCS#8, base64).
+const SIGNING_KEY: &str = "audit.signing_key";
+
+/// Where the hash chain of an export doesn't hold.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChainBreak {
+  /// Index into `entries`.
+  pub index: usize,
+  pub ts: String,
+  pub reason: String,
+}
+
+/// The exported log. Written as-is to `dest`; `<dest>.sig` signs its exact bytes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditExport {
+  pub version: u32,
+  pub vault_path: String,
+  pub exported_at: String,
+  pub exported_by: Actor,
+  pub since: Option<String>,
+  /// Hash the first exported entry chains from; ties the export to the log before `since`.
+  pub anchor: String,
+  pub last_hash: String,
+  /// Entries written before chaining existed; they are exported but prove nothing.
+  pub unchained: usize,
+  pub breaks: Vec<ChainBreak>,
+  pub entries: Vec<AuditEntry>,
+}
+
+/// `<dest>.sig`: Ed25519 signature over the export file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditSignature {
+  pub algorithm: String,
+  pub public_key: String,
+  pub sha256: String,
+  pub signature: String,
+  pub device_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditExportSummary {
+  pub dest: String,
+  pub signature_path: String,
+  pub entries: usize,
+  pub breaks: Vec<ChainBreak>,
+  pub last_hash: String,
+}
+
+/// This device's signing key, created on first export.
+fn signing_key() -> Result<Ed25519KeyPair, String> {
+  if let Some(b64) = crate::secrets::get(None, SIGNING_KEY)? {
+    let pkcs8 = STANDARD.decode(b64.trim()).map_err(|e| e.to_string())?;
+    return Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|e| format!("stored audit signing key is invalid: {}", e));
+  }
+  let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|e| e.to_string())?;
+  crate::secrets::set(None, SIGNING_KEY, &STANDARD.encode(pkcs8.as_ref()))?;
+  Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(|e| e.to_string())
+}
+
+/// Checks each chained entry's hash and its link to the one before it.
+fn verify(entries: &[AuditEntry]) -> (usize, Vec<ChainBreak>) {
+  let mut unchained = 0;
+  let mut breaks = Vec::new();
+  let mut last: Option<&str> = None;
+  for (index, entry) in entries.iter().enumerate() {
+    let (Some(actor), Some(prev), Some(hash)) = (&entry.actor, &entry.prev, &entry.hash) else {
+      unchained += 1;
+      continue;
+    };
+    let mut fail = |reason: &str| {
+      breaks.push(ChainBreak {
+        index,
+        ts: entry.ev.ts.clone(),
+        reason: reason.to_string(),
+      })
+    };
+    if entry_hash(prev, &entry.ev, actor) != *hash {
+      fail("entry was modified (hash mismatch)");
+    }
+    if let Some(last) = last {
+      if last != prev {
+        fail("entries before this one were removed, reordered or modified");
+      }
+    }
+    last = Some(hash);
+  }
+  (unchained, breaks)
+}
+
+fn sig_path(dest: &Path) -> PathBuf {
+  let name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+  dest.with_file_name(format!("{}.sig", name))
+}
+
+/// Exports the vault's event log from `since` (RFC 3339; everything when empty) to `dest`, with its
+/// hash chain checked, and signs it with this device's key into `<dest>.sig`.
+#[tauri::command]
+pub async fn audit_export(vault_path: String, since: Option<String>, dest: String) -> Result<AuditExportSummary, String> {
+  let since = since.filter(|s| !s.trim().is_empty());
+  let since_ts = match since.as_deref() {
+    Some(s) => Some(chrono::DateTime::parse_from_rfc3339(s).map_err(|_| format!("invalid since timestamp: {}", s))?),
+    None => None,
+  };
+  let all = read_entries(&vault_path, since.as_deref())?;
+  // Chain checks run over everything read, so a break just before `since` still shows.
+  let start = all
+    .iter()
+    .position(|e| match (since_ts, chrono::DateTime::parse_from_rfc3339(&e.ev.ts)) {
+      (Some(s), Ok(ts)) => ts >= s,
+      _ => true,
+    })
+    .unwrap_or(all.len());
+  let (_, all_breaks) = verify(&all);
+  let entries = all[start..].to_vec();
+  let (unchained, _) = verify(&entries);
+  let breaks: Vec<ChainBreak> = all_breaks
+    .into_iter()
+    .filter(|b| b.index >= start)
+    .map(|b| ChainBreak { index: b.index - start, ..b })
+    .collect();
+
+  let export = AuditExport {
+    version: 1,
+    vault_path: vault_path.clone(),
+    exported_at: now_iso(),
+    exported_by: Actor::current(),
+    since,
+    anchor: entries.first().and_then(|e| e.prev.clone()).unwrap_or_default(),
+    last_hash: entries.iter().rev().find_map(|e| e.hash.clone()).unwrap_or_default(),
+    unchained,
+    breaks: breaks.clone(),
+    entries,
+  };
+  let bytes = serde_json::to_vec_pretty(&export).map_err(|e| e.to_string())?;
+  let key = signing_key()?;
+  let signature = AuditSignature {
+    algorithm: "ed25519".to_string(),
+    public_key: STANDARD.encode(key.public_key().as_ref()),
+    sha256: sha256_hex(&bytes),
+    signature: STANDARD.encode(key.sign(&bytes).as_ref()),
+    device_id: export.exported_by.device_id.clone(),
+  };
+  let dest_path = PathBuf::from(&dest);
+  let sig = sig_path(&dest_path);
+  write_atomic(&dest_path, &bytes).map_err(|e| e.to_string())?;
+  let sig_text = serde_json::to_string_pretty(&signature).map_err(|e| e.to_string())?;
+  write_atomic(&sig, sig_text).map_err(|e| e.to_string())?;
+
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "audit_export".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Exported {} event(s) to {} ({} chain break(s)); sha256 {}.",
+        export.entries.len(),
+        dest,
+        breaks.len(),
+        signature.sha256
+      ),
+    },
+  );
+  Ok(AuditExportSummary {
+    dest,
+    signature_path: sig.to_string_lossy().to_string(),
+    entries: export.entries.len(),
+    breaks,
+    last_hash: export.last_hash,
+  })
+}
@@ -0,0 +1,219 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::Emitter;
+
+use crate::deeplink::DeepLinkRoute;
+use crate::sync::{persist_auth_tokens, refresh_access_token, SupabaseAuth};
+
+pub(crate) use diregram_sync_core::auth::*;
+
+/// How long the user has to finish signing in in the browser.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+const DEEP_LINK_REDIRECT: &str = "diregram://auth/callback";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginRedirect {
+  /// `diregram://auth/callback`; needs the scheme registered with the OS.
+  #[default]
+  DeepLink,
+  /// `http://127.0.0.1:<port>/callback`; works in dev builds without scheme registration.
+  Loopback,
+}
+
+#[derive(Debug, Deserialize)]
+struct PkceTokenResponse {
+  access_token: String,
+  refresh_token: String,
+  user: PkceUser,
+}
+
+#[derive(Debug, Deserialize)]
+struct PkceUser {
+  id: String,
+}
+
+fn pkce_pair() -> Result<(String, String), String> {
+  let mut bytes = [0u8; 32];
+  getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+  let verifier = URL_SAFE_NO_PAD.encode(bytes);
+  let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+  Ok((verifier, challenge))
+}
+
+fn authorize_url(supabase_url: &str, provider: &str, redirect_to: &str, challenge: &str) -> Result<Url, String> {
+  let mut url = Url::parse(&format!("{}/auth/v1/authorize", supabase_url.trim_end_matches('/'))).map_err(|e| e.to_string())?;
+  url
+    .query_pairs_mut()
+    .append_pair("provider", provider)
+    .append_pair("redirect_to", redirect_to)
+    .append_pair("code_challenge", challenge)
+    .append_pair("code_challenge_method", "s256");
+  Ok(url)
+}
+
+fn code_from_route(route: DeepLinkRoute) -> Result<String, String> {
+  match route {
+    DeepLinkRoute::AuthCallback { error: Some(e), .. } => Err(format!("sign-in failed: {}", e)),
+    DeepLinkRoute::AuthCallback { code: Some(code), .. } => Ok(code),
+    _ => Err("sign-in callback did not include an authorization code".to_string()),
+  }
+}
+
+fn respond(mut stream: &TcpStream, body: &str) {
+  let _ = write!(
+    stream,
+    "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    body.len(),
+    body
+  );
+}
+
+/// Serves the loopback redirect until a `/callback` request arrives (browsers also ask for a favicon).
+fn wait_for_loopback(listener: TcpListener) -> Result<String, String> {
+  listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+  let deadline = Instant::now() + LOGIN_TIMEOUT;
+  while Instant::now() < deadline {
+    let mut stream = match listener.accept() {
+      Ok((s, _)) => s,
+      Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+        std::thread::sleep(Duration::from_millis(200));
+        continue;
+      }
+      Err(e) => return Err(e.to_string()),
+    };
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let mut buf = [0u8; 8192];
+    let n = stream.read(&mut buf).unwrap_or(0);
+    let head = String::from_utf8_lossy(&buf[..n]);
+    let target = head.lines().next().and_then(|l| l.split_whitespace().nth(1)).unwrap_or("");
+    let Ok(url) = Url::parse(&format!("http://127.0.0.1{}", target)) else { continue };
+    if url.path() != "/callback" {
+      respond(&stream, "");
+      continue;
+    }
+    let param = |k: &str| url.query_pairs().find(|(key, _)| key == k).map(|(_, v)| v.to_string());
+    let route = DeepLinkRoute::AuthCallback {
+      code: param("code"),
+      access_token: None,
+      refresh_token: None,
+      error: param("error_description").or_else(|| param("error")),
+    };
+    respond(&stream, "<html><body>Signed in to Diregram. You can close this tab.</body></html>");
+    return code_from_route(route);
+  }
+  Err("sign-in timed out".to_string())
+}
+
+async fn exchange_code(supabase_url: &str, anon_key: &str, code: &str, verifier: &str) -> Result<PkceTokenResponse, String> {
+  let url = format!("{}/auth/v1/token?grant_type=pkce", supabase_url.trim_end_matches('/'));
+  let client = reqwest::Client::new();
+  let res = client
+    .post(url)
+    .header("apikey", anon_key)
+    .header("content-type", "application/json")
+    .json(&serde_json::json!({ "auth_code": code, "code_verifier": verifier }))
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+  if !res.status().is_success() {
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    return Err(format!("code exchange failed: HTTP {} {}", status, body));
+  }
+  res.json().await.map_err(|e| e.to_string())
+}
+
+/// Runs the whole OAuth sign-in natively: opens the provider page in the system browser, waits for
+/// the redirect, exchanges the code with the PKCE verifier and stores the session in the keychain.
+#[tauri::command]
+pub async fn auth_login_start(
+  supabase_url: String,
+  supabase_anon_key: String,
+  provider: String,
+  redirect: Option<LoginRedirect>,
+  profile: Option<String>,
+) -> Result<SupabaseAuth, String> {
+  let (verifier, challenge) = pkce_pair()?;
+
+  let code = match redirect.unwrap_or_default() {
+    LoginRedirect::DeepLink => {
+      let rx = crate::deeplink::await_auth_callback();
+      let url = authorize_url(&supabase_url, &provider, DEEP_LINK_REDIRECT, &challenge)?;
+      crate::tray::open_with_os(url.as_str());
+      let route = tauri::async_runtime::spawn_blocking(move || rx.recv_timeout(LOGIN_TIMEOUT))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|_| "sign-in timed out or was superseded by another attempt".to_string())?;
+      code_from_route(route)?
+    }
+    LoginRedirect::Loopback => {
+      let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+      let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+      let redirect_to = format!("http://127.0.0.1:{}/callback", port);
+      let url = authorize_url(&supabase_url, &provider, &redirect_to, &challenge)?;
+      crate::tray::open_with_os(url.as_str());
+      tauri::async_runtime::spawn_blocking(move || wait_for_loopback(listener))
+        .await
+        .map_err(|e| e.to_string())??
+    }
+  };
+
+  let tokens = exchange_code(&supabase_url, &supabase_anon_key, &code, &verifier).await?;
+  persist_auth_tokens(profile.as_deref(), &tokens.access_token, &tokens.refresh_token)?;
+  let auth = SupabaseAuth {
+    supabase_url,
+    supabase_anon_key,
+    access_token: tokens.access_token,
+    refresh_token: Some(tokens.refresh_token),
+    owner_id: tokens.user.id,
+    profile,
+  };
+  crate::status::update_session_tokens(&auth);
+  remember(&auth);
+  Ok(auth)
+}
+
+async fn refresh_if_due(app: &tauri::AppHandle, client: &reqwest::Client) {
+  let Some(mut auth) = SESSION.lock().ok().and_then(|g| g.clone()) else { return };
+  let due = jwt_exp(&auth.access_token)
+    .map(|exp| exp - Utc::now().timestamp() <= REFRESH_AHEAD_SECS)
+    .unwrap_or(false);
+  if !due {
+    return;
+  }
+  // Persists and calls `store_tokens` on success.
+  let Err(e) = refresh_access_token(client, &mut auth).await else { return };
+  if !is_permanent_refresh_error(&e) {
+    return;
+  }
+  // Forget the session so this isn't reported every tick; the next sign-in re-arms it.
+  if let Ok(mut guard) = SESSION.lock() {
+    if guard.as_ref().map(|s| s.access_token == auth.access_token).unwrap_or(false) {
+      *guard = None;
+    }
+  }
+  let _ = app.emit("auth://expired", AuthExpiredEvent { reason: e });
+}
+
+/// Refreshes the remembered session a few minutes before it expires. Called once from setup.
+pub(crate) fn start_refresh_daemon(app: &tauri::AppHandle) {
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    let client = reqwest::Client::new();
+    loop {
+      tokio::time::sleep(REFRESH_CHECK_INTERVAL).await;
+      refresh_if_due(&app, &client).await;
+    }
+  });
+}
@@ -0,0 +1,48 @@
+use tauri::Manager;
+
+use crate::sync::{load_auth_tokens, SupabaseAuth};
+
+pub(crate) use diregram_sync_core::autosync::*;
+
+async fn resume_one(runtime: &crate::scheduler::SyncRuntime, v: &AutosyncVaultV1) -> Result<(), String> {
+  let (access_token, refresh_token) =
+    load_auth_tokens(v.profile.as_deref())?.ok_or_else(|| "no saved session; sign in to resume sync".to_string())?;
+  let auth = SupabaseAuth {
+    supabase_url: v.supabase_url.clone(),
+    supabase_anon_key: v.supabase_anon_key.clone(),
+    access_token,
+    refresh_token: Some(refresh_token),
+    owner_id: v.owner_id.clone(),
+    profile: v.profile.clone(),
+  };
+  if v.watch {
+    crate::sync::start_watch(runtime, &v.vault_path, &v.project_folder_id, &auth, true).await?;
+  }
+  if let Some(settings) = &v.pull {
+    crate::sync::start_pull(runtime, &v.vault_path, &v.project_folder_id, &auth, settings, true).await?;
+  }
+  Ok(())
+}
+
+/// Restarts the watchers and pollers recorded in `autosync.json`. Call from setup, after the
+/// sync runtime is managed. When launched with `BACKGROUND_ARG`, the main window is hidden too.
+pub(crate) fn install(app: &tauri::AppHandle) {
+  if let Ok(dir) = app.path().app_config_dir() {
+    set_dir(dir);
+  }
+  if std::env::args().any(|a| a == BACKGROUND_ARG) {
+    if let Some(w) = app.get_webview_window("main") {
+      let _ = w.hide();
+    }
+  }
+  let app = app.clone();
+  tauri::async_runtime::spawn(async move {
+    let runtime = app.state::<crate::scheduler::SyncRuntime>();
+    for v in load().vaults {
+      match resume_one(&runtime, &v).await {
+        Ok(()) => log(&v.vault_path, "sync_resumed", "Resumed background sync at launch.".to_string()),
+        Err(e) => log(&v.vault_path, "sync_resume_failed", format!("Could not resume sync at launch: {}", e)),
+      }
+    }
+  });
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::archive::{collect_archive_files, vault_import_archive, write_vault_archive, ArchiveImportSummary};
+use crate::config::{read_config, write_config, BackupConfig};
+use crate::sync::{append_event, now_iso, sha256_hex, SupabaseAuth, SyncEvent};
+
+static BACKUP_STATE: Lazy<Mutex<HashMap<String, BackupState>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct BackupState {
+  stop_tx: oneshot::Sender<()>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupEntry {
+  pub name: String,
+  pub path: String,
+  pub bytes: u64,
+  pub created_at: String,
+}
+
+/// Snapshots from different vaults can share one backup directory, so names carry a
+/// short hash of the vault path: `diregram-backup-<hash>-<YYYYmmddTHHMMSSZ>.zip`.
+fn backup_prefix(vault_path: &str) -> String {
+  format!("diregram-backup-{}-", &sha256_hex(vault_path.as_bytes())[..8])
+}
+
+fn backup_dir_for(vault_path: &str, cfg: &BackupConfig) -> Result<PathBuf, String> {
+  let dir = cfg
+    .backup_dir
+    .as_ref()
+    .map(|s| s.trim())
+    .filter(|s| !s.is_empty())
+    .ok_or_else(|| "backup_dir is not configured".to_string())?;
+  let dir = PathBuf::from(dir);
+  let vault = fs::canonicalize(vault_path).map_err(|e| e.to_string())?;
+  fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  let resolved = fs::canonicalize(&dir).map_err(|e| e.to_string())?;
+  if resolved.starts_with(&vault) {
+    return Err("backup_dir must be outside the vault".to_string());
+  }
+  Ok(dir)
+}
+
+fn list_backups_in(dir: &Path, prefix: &str) -> Vec<BackupEntry> {
+  let Ok(rd) = fs::read_dir(dir) else { return Vec::new() };
+  let mut out: Vec<BackupEntry> = rd
+    .filter_map(Result::ok)
+    .filter_map(|e| {
+      let name = e.file_name().to_string_lossy().to_string();
+      let stamp = name.strip_prefix(prefix)?.strip_suffix(".zip")?.to_string();
+      let bytes = e.metadata().map(|m| m.len()).unwrap_or(0);
+      let created_at = chrono::NaiveDateTime::parse_from_str(&stamp, "%Y%m%dT%H%M%SZ")
+        .map(|dt| dt.and_utc().to_rfc3339())
+        .unwrap_or(stamp);
+      Some(BackupEntry {
+        name,
+        path: e.path().to_string_lossy().to_string(),
+        bytes,
+        created_at,
+      })
+    })
+    .collect();
+  // Timestamps sort lexically, newest first.
+  out.sort_by(|a, b| b.name.cmp(&a.name));
+  out
+}
+
+fn run_backup(vault_path: &str) -> Result<BackupEntry, String> {
+  let cfg = read_config(vault_path)?.backup;
+  let dir = backup_dir_for(vault_path, &cfg)?;
+  let prefix = backup_prefix(vault_path);
+  let name = format!("{}{}.zip", prefix, Utc::now().format("%Y%m%dT%H%M%SZ"));
+  let dest = dir.join(&name);
+
+  let files = collect_archive_files(Path::new(vault_path), cfg.include_rag, false, None);
+  let manifest = write_vault_archive(vault_path, &dest, &files, cfg.include_rag, false, |_, _, _| {})?;
+
+  let mut pruned = 0u32;
+  for old in list_backups_in(&dir, &prefix).iter().skip(cfg.retention.max(1) as usize) {
+    if fs::remove_file(&old.path).is_ok() {
+      pruned += 1;
+    }
+  }
+
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "backup".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Backup {} written ({} files, {} bytes). Old backups pruned: {}.",
+        name, manifest.files, manifest.bytes, pruned
+      ),
+    },
+  );
+  let bytes = fs::metadata(&dest).map(|m| m.len()).unwrap_or(0);
+  Ok(BackupEntry {
+    name,
+    path: dest.to_string_lossy().to_string(),
+    bytes,
+    created_at: manifest.created_at,
+  })
+}
+
+#[tauri::command]
+pub async fn backup_get_config(vault_path: String) -> Result<BackupConfig, String> {
+  Ok(read_config(&vault_path)?.backup)
+}
+
+#[tauri::command]
+pub async fn backup_set_config(vault_path: String, backup: BackupConfig) -> Result<BackupConfig, String> {
+  if backup.interval_minutes == 0 {
+    return Err("interval_minutes must be at least 1".to_string());
+  }
+  if backup.retention == 0 {
+    return Err("retention must be at least 1".to_string());
+  }
+  backup_dir_for(&vault_path, &backup)?;
+  let mut cfg = read_config(&vault_path)?;
+  cfg.backup = backup.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(backup)
+}
+
+/// Starts the scheduler for a vault. Interval and retention are re-read from config before each
+/// run, so editing them does not require a restart.
+#[tauri::command]
+pub async fn backup_start(vault_path: String) -> Result<(), String> {
+  let mut guard = BACKUP_STATE.lock().map_err(|_| "backup state lock poisoned".to_string())?;
+  if guard.contains_key(&vault_path) {
+    return Err("backup scheduler already running for this vault".to_string());
+  }
+  let cfg = read_config(&vault_path)?.backup;
+  backup_dir_for(&vault_path, &cfg)?;
+  let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+  let vp = vault_path.clone();
+
+  tauri::async_runtime::spawn(async move {
+    loop {
+      let minutes = read_config(&vp).map(|c| c.backup.interval_minutes).unwrap_or(360).max(1);
+      tokio::select! {
+        _ = &mut stop_rx => break,
+        _ = tokio::time::sleep(Duration::from_secs(minutes * 60)) => {}
+      }
+      let enabled = read_config(&vp).map(|c| c.backup.enabled).unwrap_or(false);
+      if !enabled {
+        continue;
+      }
+      // A backup under way when the app quits is waited for rather than left half-written.
+      let backup = crate::scheduler::run_task(async {
+        let vp = vp.clone();
+        tauri::async_runtime::spawn_blocking(move || run_backup(&vp))
+          .await
+          .map_err(|e| e.to_string())
+          .and_then(|res| res)
+      });
+      if let Err(e) = backup.await.and_then(|res| res) {
+        let _ = append_event(
+          &vp,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "backup_error".to_string(),
+            path: String::new(),
+            detail: e,
+          },
+        );
+      }
+    }
+  });
+
+  guard.insert(vault_path, BackupState { stop_tx });
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn backup_stop(vault_path: Option<String>) -> Result<(), String> {
+  let mut guard = BACKUP_STATE.lock().map_err(|_| "backup state lock poisoned".to_string())?;
+  match vault_path {
+    Some(vp) => {
+      if let Some(st) = guard.remove(&vp) {
+        let _ = st.stop_tx.send(());
+      }
+    }
+    None => {
+      for (_, st) in guard.drain() {
+        let _ = st.stop_tx.send(());
+      }
+    }
+  }
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn backup_run_now(vault_path: String) -> Result<BackupEntry, String> {
+  tauri::async_runtime::spawn_blocking(move || run_backup(&vault_path))
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn backup_list(vault_path: String) -> Result<Vec<BackupEntry>, String> {
+  let cfg = read_config(&vault_path)?.backup;
+  let dir = backup_dir_for(&vault_path, &cfg)?;
+  Ok(list_backups_in(&dir, &backup_prefix(&vault_path)))
+}
+
+/// Restores a snapshot into a new, empty folder. The live vault is never overwritten.
+#[tauri::command]
+pub async fn backup_restore(
+  app: tauri::AppHandle,
+  vault_path: String,
+  backup_name: String,
+  dest_vault_path: String,
+  relink: Option<bool>,
+  auth: Option<SupabaseAuth>,
+) -> Result<ArchiveImportSummary, String> {
+  let cfg = read_config(&vault_path)?.backup;
+  let dir = backup_dir_for(&vault_path, &cfg)?;
+  let entry = list_backups_in(&dir, &backup_prefix(&vault_path))
+    .into_iter()
+    .find(|b| b.name == backup_name)
+    .ok_or_else(|| format!("backup not found: {}", backup_name))?;
+
+  let summary = vault_import_archive(app, entry.path.clone(), dest_vault_path.clone(), relink, auth).await?;
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "backup_restore".to_string(),
+      path: String::new(),
+      detail: format!("Restored backup {} into {} ({} files).", entry.name, dest_vault_path, summary.files),
+    },
+  );
+  Ok(summary)
+}
@@ -0,0 +1,21 @@
+use crate::sync::{
+  SupabaseAuth, SyncSummary,
+};
+
+pub(crate) use diregram_sync_core::bootstrap::*;
+
+/// One-shot "clone project to disk" for a new vault folder, with the vault lock held. Progress is
+/// emitted as `sync://bootstrap_progress`.
+#[tauri::command]
+pub async fn vault_bootstrap(
+  vault_path: String,
+  project_folder_id: String,
+  auth: SupabaseAuth,
+  options: Option<BootstrapOptions>,
+) -> Result<SyncSummary, String> {
+  let vault = crate::engine::VaultSync::new(vault_path.clone(), project_folder_id, auth);
+  crate::lock::acquire(&vault_path)?;
+  let res = vault.bootstrap(&options.unwrap_or_default()).await;
+  crate::lock::release(&vault_path);
+  res
+}
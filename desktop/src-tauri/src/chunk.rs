@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::Path;
+
+use crate::config::read_config;
+use crate::sync::{
+  read_mapping, write_jsonl,
+};
+
+pub(crate) use diregram_sync_core::chunk::*;
+
+#[tauri::command]
+pub async fn rag_chunk_vault(vault_path: String, options: Option<ChunkOptions>) -> Result<ChunkSummary, String> {
+  let opts = match options {
+    Some(o) => o,
+    None => read_config(&vault_path)?.rag.chunking,
+  };
+  let (chunks, files) = chunk_vault(&vault_path, &opts)?;
+  let out = chunks_path(&vault_path);
+  write_jsonl(&out, &chunks)?;
+  Ok(ChunkSummary {
+    files,
+    chunks: chunks.len() as u32,
+    output_path: out.to_string_lossy().to_string(),
+  })
+}
+
+/// Finds a RAG chunk (by id, from the export or the local chunk file) in the current contents of
+/// its note, for "jump to source" from search results.
+#[tauri::command]
+pub async fn rag_resolve_anchor(vault_path: String, chunk_id: String) -> Result<ChunkLocation, String> {
+  let chunk = find_chunk(&vault_path, &chunk_id).ok_or_else(|| format!("no chunk {}", chunk_id))?;
+  let mapping = read_mapping(&vault_path)?;
+  let rel_path = chunk
+    .rel_path
+    .clone()
+    .or_else(|| {
+      let m = mapping.as_ref()?;
+      match (&chunk.file_id, &chunk.resource_id) {
+        (Some(id), _) => m.files.iter().find(|(_, f)| &f.file_id == id).map(|(rel, _)| rel.clone()),
+        (None, Some(id)) => m.resources.iter().find(|(_, r)| &r.resource_id == id).map(|(rel, _)| rel.clone()),
+        (None, None) => None,
+      }
+    })
+    .ok_or("the chunk's file is not in this vault")?;
+  let bytes = fs::read(Path::new(&vault_path).join(&rel_path)).map_err(|_| format!("{} no longer exists", rel_path))?;
+  let content = crate::encoding::decode(&bytes).0;
+  let opts = read_config(&vault_path)?.rag.chunking;
+  let (start, end, method) =
+    locate(&content, &rel_path, &chunk, &opts).ok_or_else(|| format!("the chunk's text is no longer in {}", rel_path))?;
+  Ok(ChunkLocation {
+    rel_path,
+    line_start: start + 1,
+    line_end: end + 1,
+    method: method.to_string(),
+  })
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::engine::VaultSync;
+use crate::sync::{read_mapping, SupabaseAuth};
+
+/// First argument that switches the binary into CLI mode instead of starting the app.
+const CLI_ARG: &str = "--cli";
+
+const USAGE: &str = "usage: diregram-sync --cli <command> [options]
+
+commands:
+  sync pull   --vault <dir> --project <id> [--force]   pull remote changes once
+  import      --vault <dir> --project <id>             push local changes once
+  clone       --vault <dir> --project <id>             download the project into an empty folder
+  rag export  --vault <dir> --project <id>             export the project's knowledge base
+  status      --vault <dir>... [--json]                mapping, lock and recent activity
+  mcp         --vault <dir>                            serve the vault to an MCP client (e.g. Claude
+                                                       Desktop) over stdin/stdout
+
+  --metrics-file <file>  after a sync, import or rag command, write its counters there in the
+                         Prometheus text format (for node_exporter's textfile collector)
+
+auth (sync, import, clone, rag):
+  --session <file>   JSON with supabase_url, supabase_anon_key, access_token, refresh_token and
+                     owner_id; rewritten with the new tokens when they are refreshed
+  or the DIREGRAM_SUPABASE_URL, DIREGRAM_SUPABASE_ANON_KEY, DIREGRAM_ACCESS_TOKEN,
+  DIREGRAM_REFRESH_TOKEN and DIREGRAM_OWNER_ID environment variables (not written back)
+  DIREGRAM_DEVICE_ID names this machine in the device registry, written rows and the event log;
+  without it the CLI uses the id the desktop app keeps in its config folder (created if missing)
+
+exit status: 0 on success, 1 when the command failed or reported errors, 2 on bad usage";
+
+struct Args {
+  words: Vec<String>,
+  options: HashMap<String, Vec<String>>,
+  flags: Vec<String>,
+}
+
+impl Args {
+  fn parse(args: &[String]) -> Result<Self, String> {
+    let mut out = Args {
+      words: Vec::new(),
+      options: HashMap::new(),
+      flags: Vec::new(),
+    };
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+      match a.as_str() {
+        "--force" | "--json" => out.flags.push(a.clone()),
+        "--vault" | "--project" | "--session" | "--metrics-file" => {
+          let v = it.next().ok_or_else(|| format!("{} needs a value", a))?;
+          out.options.entry(a.clone()).or_default().push(v.clone());
+        }
+        _ if a.starts_with("--") => return Err(format!("unknown option {}", a)),
+        _ => out.words.push(a.clone()),
+      }
+    }
+    Ok(out)
+  }
+
+  fn one(&self, name: &str) -> Result<String, String> {
+    match self.options.get(name).map(|v| v.as_slice()) {
+      Some([v]) => Ok(v.clone()),
+      Some(_) => Err(format!("{} given more than once", name)),
+      None => Err(format!("missing {}", name)),
+    }
+  }
+
+  fn flag(&self, name: &str) -> bool {
+    self.flags.iter().any(|f| f == name)
+  }
+}
+
+/// Where the CLI's session came from; a session file gets refreshed tokens written back so the
+/// next run (e.g. from cron) doesn't use a rotated-out refresh token.
+enum AuthSource {
+  File(PathBuf),
+  Env,
+}
+
+fn load_auth(args: &Args) -> Result<(SupabaseAuth, AuthSource), String> {
+  if args.options.contains_key("--session") {
+    let path = PathBuf::from(args.one("--session")?);
+    let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let auth: SupabaseAuth = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    return Ok((auth, AuthSource::File(path)));
+  }
+  let var = |name: &str| std::env::var(name).map_err(|_| format!("missing --session or {}", name));
+  let auth = SupabaseAuth {
+    supabase_url: var("DIREGRAM_SUPABASE_URL")?,
+    supabase_anon_key: var("DIREGRAM_SUPABASE_ANON_KEY")?,
+    access_token: var("DIREGRAM_ACCESS_TOKEN")?,
+    refresh_token: std::env::var("DIREGRAM_REFRESH_TOKEN").ok(),
+    owner_id: var("DIREGRAM_OWNER_ID")?,
+    profile: None,
+  };
+  Ok((auth, AuthSource::Env))
+}
+
+fn save_session(path: &Path, auth: &SupabaseAuth) -> Result<(), String> {
+  let tmp = path.with_extension("json.tmp");
+  let text = serde_json::to_string_pretty(auth).map_err(|e| e.to_string())?;
+  fs::write(&tmp, text).map_err(|e| e.to_string())?;
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600));
+  }
+  fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize)]
+struct CliVaultStatus {
+  vault_path: String,
+  project_folder_id: Option<String>,
+  last_pull_at: Option<String>,
+  files: usize,
+  resources: usize,
+  /// Remote deletions held back by the mass-delete guard.
+  pending_mass_delete: usize,
+  lock: crate::lock::VaultLockStatus,
+  recent: Vec<crate::activity::RecentActivity>,
+}
+
+async fn vault_status(vault_path: String) -> Result<CliVaultStatus, String> {
+  let mapping = read_mapping(&vault_path)?;
+  Ok(CliVaultStatus {
+    project_folder_id: mapping.as_ref().map(|m| m.project_folder_id.clone()),
+    last_pull_at: mapping
+      .as_ref()
+      .map(|m| m.last_pull_at.clone())
+      .filter(|s| !s.trim().is_empty()),
+    files: mapping.as_ref().map(|m| m.files.len()).unwrap_or(0),
+    resources: mapping.as_ref().map(|m| m.resources.len()).unwrap_or(0),
+    pending_mass_delete: mapping
+      .as_ref()
+      .and_then(|m| m.pending_mass_delete.as_ref())
+      .map(|p| p.files.len())
+      .unwrap_or(0),
+    lock: crate::lock::vault_lock_status(vault_path.clone()).await?,
+    recent: crate::activity::sync_recent_activity(vault_path.clone(), Some(10)).await?,
+    vault_path,
+  })
+}
+
+fn print_status(s: &CliVaultStatus) {
+  println!("{}", s.vault_path);
+  match &s.project_folder_id {
+    Some(p) => println!("  project: {}", p),
+    None => println!("  not set up for sync"),
+  }
+  println!("  last pull: {}", s.last_pull_at.as_deref().unwrap_or("never"));
+  println!("  files: {}, resources: {}", s.files, s.resources);
+  if s.pending_mass_delete > 0 {
+    println!("  {} remote deletions held back (resolve them in the app)", s.pending_mass_delete);
+  }
+  match &s.lock.lock {
+    Some(l) if !s.lock.held_by_us => println!(
+      "  locked by pid {} on {}{}",
+      l.pid,
+      l.hostname,
+      if s.lock.stale { " (stale)" } else { "" }
+    ),
+    _ => {}
+  }
+  for a in &s.recent {
+    println!("  {} {} {}", a.ts, a.kind, a.path);
+  }
+}
+
+fn print_summary(what: &str, s: &crate::sync::SyncSummary, json: bool) {
+  if json {
+    println!("{}", serde_json::to_string_pretty(s).unwrap_or_default());
+    return;
+  }
+  println!(
+    "{}: {} created, {} updated, {} deleted, {} skipped",
+    what, s.files_created, s.files_updated, s.files_deleted, s.files_skipped
+  );
+  for w in &s.warnings {
+    println!("warning: {}", w);
+  }
+  for e in &s.errors {
+    eprintln!("error: {}", e);
+  }
+}
+
+/// Runs one sync command against a vault with the vault lock held, as the app's pollers do, so it
+/// can't race a running app.
+async fn run_locked(args: &Args) -> Result<bool, String> {
+  let vault_path = args.one("--vault")?;
+  let project_folder_id = args.one("--project")?;
+  let (auth, source) = load_auth(args)?;
+  let json = args.flag("--json");
+  let vault = VaultSync::new(vault_path.clone(), project_folder_id, auth.clone());
+  let mut events = vault.events();
+
+  crate::lock::acquire(&vault_path)?;
+  let op = async {
+    let words: Vec<&str> = args.words.iter().map(|w| w.as_str()).collect();
+    match words.as_slice() {
+      ["sync", "pull"] => vault.pull_once(args.flag("--force")).await.map(|s| Some(("pull", s))),
+      ["import"] => vault.push_all().await.map(|s| Some(("push", s))),
+      ["clone"] => vault.bootstrap(&Default::default()).await.map(|s| Some(("pull", s))),
+      ["rag", "export"] => vault.rag_export().await.map(|_| None),
+      _ => Err(USAGE.to_string()),
+    }
+  };
+  tokio::pin!(op);
+  // Without --json, show what happens file by file as it is logged.
+  let res = loop {
+    tokio::select! {
+      res = &mut op => break res,
+      Some(ev) = events.next(), if !json => println!("{} {} {}", ev.kind, ev.path, ev.detail),
+    }
+  };
+  crate::lock::release(&vault_path);
+  while let Some(ev) = events.try_next() {
+    if !json {
+      println!("{} {} {}", ev.kind, ev.path, ev.detail);
+    }
+  }
+  let res = res.map(|summary| match summary {
+    Some((what, s)) => {
+      print_summary(what, &s, json);
+      s.errors.is_empty()
+    }
+    None => true,
+  });
+
+  let latest = vault.auth();
+  if latest.refresh_token != auth.refresh_token || latest.access_token != auth.access_token {
+    match source {
+      AuthSource::File(path) => save_session(&path, &latest)?,
+      AuthSource::Env => eprintln!("note: the session was refreshed; the refresh token in DIREGRAM_REFRESH_TOKEN may no longer work"),
+    }
+  }
+  res
+}
+
+async fn run(args: Args) -> Result<bool, String> {
+  if args.words.first().map(|w| w.as_str()) == Some("status") {
+    let vaults = args.options.get("--vault").cloned().unwrap_or_default();
+    if vaults.is_empty() {
+      return Err("missing --vault".to_string());
+    }
+    let mut out = Vec::new();
+    for v in vaults {
+      out.push(vault_status(v).await?);
+    }
+    if args.flag("--json") {
+      println!("{}", serde_json::to_string_pretty(&out).map_err(|e| e.to_string())?);
+    } else {
+      out.iter().for_each(print_status);
+    }
+    return Ok(true);
+  }
+  if args.words.first().map(|w| w.as_str()) == Some("mcp") {
+    crate::mcp::serve(&args.one("--vault")?)?;
+    return Ok(true);
+  }
+  let res = run_locked(&args).await;
+  if args.options.contains_key("--metrics-file") {
+    let path = PathBuf::from(args.one("--metrics-file")?);
+    crate::sync::write_atomic(&path, crate::metrics::render()).map_err(|e| format!("{}: {}", path.display(), e))?;
+  }
+  res
+}
+
+/// Runs the CLI when the process was started with `--cli`, returning its exit code; `None` means
+/// start the app as usual. Nothing here needs a window or the app's managed state, so it works on
+/// headless machines (cron, systemd).
+pub(crate) fn main_if_requested() -> Option<i32> {
+  let argv: Vec<String> = std::env::args().skip(1).collect();
+  if argv.first().map(|a| a.as_str()) != Some(CLI_ARG) {
+    return None;
+  }
+  let args = match Args::parse(&argv[1..]) {
+    Ok(a) if !a.words.is_empty() => a,
+    Ok(_) => {
+      eprintln!("{}", USAGE);
+      return Some(2);
+    }
+    Err(e) => {
+      eprintln!("{}\n\n{}", e, USAGE);
+      return Some(2);
+    }
+  };
+  crate::device::install_cli();
+  match tauri::async_runtime::block_on(run(args)) {
+    Ok(true) => Some(0),
+    Ok(false) => Some(1),
+    Err(e) if e == USAGE => {
+      eprintln!("{}", USAGE);
+      Some(2)
+    }
+    Err(e) => {
+      eprintln!("error: {}", e);
+      Some(1)
+    }
+  }
+}
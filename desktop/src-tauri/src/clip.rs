@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use crate::config::{read_config, write_config, ClipConfig};
+
+pub(crate) use diregram_sync_core::clip::*;
+
+/// Pushes the new note right away when the vault has a sync session, then, if configured, starts
+/// a RAG ingest of just that file. Returns once the push is done; the ingest runs on.
+pub(crate) async fn push(vault_path: &str, rel: &str) -> (bool, bool) {
+  let Some(s) = crate::status::sessions().into_iter().find(|s| s.vault_path == vault_path) else {
+    return (false, false);
+  };
+  let auth = crate::auth::latest(&s.auth);
+  let abs = Path::new(vault_path).join(rel);
+  let res = crate::scheduler::run_task(crate::sync::sync_one_path(vault_path, &s.project_folder_id, &auth, &abs))
+    .await
+    .and_then(|res| res);
+  crate::notify::report_background_result(vault_path, "push", res.as_ref().map(|_| ()).map_err(|e| e.as_str()));
+  if res.is_err() {
+    return (false, false);
+  }
+  let Ok(cfg) = read_config(vault_path) else { return (true, false) };
+  // With ingest on push, the queue re-indexes the clip along with the rest.
+  if !cfg.clip.ingest || cfg.rag.ingest_on_push {
+    return (true, false);
+  }
+  let req = match crate::rag_queue::request(vault_path, vec![rel.to_string()]) {
+    Ok(r) => r,
+    Err(e) => {
+      tracing::info!(vault = %vault_path, error = %e, "not indexing the clip");
+      return (true, false);
+    }
+  };
+  let vp = vault_path.to_string();
+  tauri::async_runtime::spawn(async move { crate::rag_queue::run(&vp, req).await });
+  (true, true)
+}
+
+/// Saves a clip into the vault's clip folder and pushes it.
+#[tauri::command]
+pub async fn clip_capture(
+  vault_path: String,
+  title: String,
+  markdown: String,
+  source_url: Option<String>,
+  tags: Option<Vec<String>>,
+) -> Result<ClipResult, String> {
+  let clip = Clip {
+    title,
+    markdown,
+    source_url,
+    tags: tags.unwrap_or_default(),
+  };
+  let path = write(&vault_path, &clip)?;
+  let (pushed, ingest_started) = push(&vault_path, &path).await;
+  Ok(ClipResult {
+    path,
+    pushed,
+    ingest_started,
+  })
+}
+
+#[tauri::command]
+pub async fn clip_configure(vault_path: String, config: ClipConfig) -> Result<ClipConfig, String> {
+  if clip_folder(&config).is_none() {
+    return Err("folder must be a visible folder inside the vault, outside resources/ and rag/".to_string());
+  }
+  let mut cfg = read_config(&vault_path)?;
+  cfg.clip = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
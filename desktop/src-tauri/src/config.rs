@@ -0,0 +1,50 @@
+use std::fs;
+
+use serde_json::Value;
+
+pub(crate) use diregram_sync_core::config::*;
+
+/// The vault's settings, with defaults filled in for everything the file leaves out.
+#[tauri::command]
+pub async fn config_get(vault_path: String) -> Result<VaultConfigV1, String> {
+  read_config(&vault_path)
+}
+
+/// Checks `config` (or, without it, the vault's config file) against the schema: type errors,
+/// unknown settings and out-of-range values. Empty when it is valid.
+#[tauri::command]
+pub async fn config_validate(vault_path: String, config: Option<Value>) -> Result<Vec<ConfigIssue>, String> {
+  let value = match config {
+    Some(value) => value,
+    None => {
+      let p = config_path(&vault_path);
+      if !p.exists() {
+        return Ok(Vec::new());
+      }
+      let text = fs::read_to_string(&p).map_err(|e| e.to_string())?;
+      match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(e) => return Ok(vec![issue("", format!("not valid JSON: {}", e))]),
+      }
+    }
+  };
+  Ok(match parse(&value) {
+    Ok(cfg) => validate(&vault_path, &cfg),
+    Err(issues) => issues,
+  })
+}
+
+/// Merges `patch` into the vault's settings (`{"watch": {"mode": "scan"}}` changes just that) and
+/// saves them if the result is valid. Running watchers and pollers use them from then on.
+#[tauri::command]
+pub async fn config_set(vault_path: String, patch: Value) -> Result<VaultConfigV1, String> {
+  let mut value = serde_json::to_value(read_config(&vault_path)?).map_err(|e| e.to_string())?;
+  merge(&mut value, patch);
+  let cfg = parse(&value).map_err(|issues| describe(&issues))?;
+  let issues = validate(&vault_path, &cfg);
+  if !issues.is_empty() {
+    return Err(describe(&issues));
+  }
+  write_config(&vault_path, &cfg)?;
+  Ok(cfg)
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::Path;
+
+use crate::normalize::safe_rel_path;
+use crate::sync::{
+  append_event, now_iso, read_mapping, sync_one_path, SupabaseAuth, SyncEvent,
+};
+
+pub(crate) use diregram_sync_core::conflicts::*;
+
+#[tauri::command]
+pub async fn conflict_list(vault_path: String) -> Result<Vec<ConflictEntry>, String> {
+  Ok(list(&vault_path))
+}
+
+/// Applies the chosen side to the note, removes the conflict copy and pushes both changes.
+#[tauri::command]
+pub async fn conflict_resolve(
+  vault_path: String,
+  path: String,
+  strategy: ConflictStrategy,
+  auth: SupabaseAuth,
+) -> Result<(), String> {
+  let conflict_rel = safe_rel_path(&path).ok_or_else(|| format!("invalid vault path: {}", path))?;
+  let (original_rel, _) = parse_conflict_path(&conflict_rel).ok_or_else(|| format!("{} is not a conflict copy", conflict_rel))?;
+  let mapping = read_mapping(&vault_path)?.ok_or_else(|| "vault is not linked (missing .diregram/sync.json)".to_string())?;
+
+  let root = Path::new(&vault_path);
+  let conflict_abs = crate::normalize::local_path(root, &conflict_rel);
+  let original_abs = crate::normalize::local_path(root, &original_rel);
+  let (content, label) = match strategy {
+    ConflictStrategy::TakeLocal => (None, "local"),
+    ConflictStrategy::TakeRemote => (Some(fs::read(&conflict_abs).map_err(|e| e.to_string())?), "remote"),
+    ConflictStrategy::MergedContent(text) => (Some(text.into_bytes()), "merged"),
+  };
+  if let Some(content) = content {
+    if let Some(parent) = original_abs.parent() {
+      fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    crate::sync::write_atomic(&original_abs, content).map_err(|e| e.to_string())?;
+  }
+  fs::remove_file(&conflict_abs).map_err(|e| e.to_string())?;
+
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "conflict_resolved".to_string(),
+      path: original_rel,
+      detail: format!("Kept the {} version; removed {}.", label, conflict_rel),
+    },
+  );
+
+  let auth = crate::auth::latest(&auth);
+  sync_one_path(&vault_path, &mapping.project_folder_id, &auth, &original_abs).await
+}
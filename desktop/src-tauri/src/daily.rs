@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use chrono::{Local, NaiveDate};
+use tokio::sync::oneshot;
+
+use crate::config::{read_config, write_config, DailyNoteConfig};
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+pub(crate) use diregram_sync_core::daily::*;
+
+/// Pushes right away when the vault has a sync session; otherwise the next push picks it up.
+async fn push(vault_path: &str, rel: &str) {
+  let Some(s) = crate::status::sessions().into_iter().find(|s| s.vault_path == vault_path) else { return };
+  let auth = crate::auth::latest(&s.auth);
+  let abs = Path::new(vault_path).join(rel);
+  let res = crate::scheduler::run_task(crate::sync::sync_one_path(vault_path, &s.project_folder_id, &auth, &abs))
+    .await
+    .and_then(|res| res);
+  crate::notify::report_background_result(vault_path, "push", res.as_ref().map(|_| ()).map_err(|e| e.as_str()));
+}
+
+/// One scheduler tick: creates today's note once its time has passed. `done` remembers the last
+/// date handled, so a note the user deletes isn't recreated the same day.
+async fn tick(vault_path: &str, done: &mut Option<NaiveDate>) {
+  let Ok(cfg) = read_config(vault_path).map(|c| c.daily_note) else { return };
+  if !cfg.enabled {
+    return;
+  }
+  let now = Local::now();
+  let today = now.date_naive();
+  if *done == Some(today) || parse_time(&cfg.time).map(|t| now.time() < t).unwrap_or(true) {
+    return;
+  }
+  *done = Some(today);
+  match ensure_note(vault_path, &cfg, today) {
+    Ok((rel, true)) => {
+      push(vault_path, &rel).await;
+      if cfg.notify {
+        crate::notify::notify(
+          vault_path,
+          crate::notify::NotifyKind::DailyNote,
+          "Daily note",
+          &format!("{} is ready.", rel),
+          Some(&rel),
+        );
+      }
+    }
+    Ok((_, false)) => {}
+    Err(e) => {
+      let _ = append_event(
+        vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "daily_note_error".to_string(),
+          path: String::new(),
+          detail: e,
+        },
+      );
+    }
+  }
+}
+
+/// Starts the scheduler for a vault if daily notes are enabled and it isn't running yet.
+pub(crate) fn ensure_scheduler(vault_path: &str) {
+  let enabled = read_config(vault_path).map(|c| c.daily_note.enabled).unwrap_or(false);
+  let Ok(mut guard) = DAILY_STATE.lock() else { return };
+  if !enabled {
+    if let Some(st) = guard.remove(vault_path) {
+      let _ = st.stop_tx.send(());
+    }
+    return;
+  }
+  if guard.contains_key(vault_path) {
+    return;
+  }
+  let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+  let vp = vault_path.to_string();
+  tauri::async_runtime::spawn(async move {
+    let mut done = None;
+    loop {
+      tick(&vp, &mut done).await;
+      tokio::select! {
+        _ = &mut stop_rx => break,
+        _ = tokio::time::sleep(DAILY_CHECK_INTERVAL) => {}
+      }
+    }
+  });
+  guard.insert(vault_path.to_string(), DailyState { stop_tx });
+}
+
+/// Saves the daily note settings and starts or stops the scheduler to match.
+#[tauri::command]
+pub async fn daily_note_configure(vault_path: String, config: DailyNoteConfig) -> Result<DailyNoteConfig, String> {
+  validate(&config)?;
+  let mut cfg = read_config(&vault_path)?;
+  cfg.daily_note = config.clone();
+  write_config(&vault_path, &cfg)?;
+  ensure_scheduler(&vault_path);
+  Ok(config)
+}
+
+/// Creates today's note now if needed (regardless of the scheduled time) and returns its
+/// vault-relative path for the UI to open.
+#[tauri::command]
+pub async fn daily_note_open_today(vault_path: String) -> Result<String, String> {
+  let cfg = read_config(&vault_path)?.daily_note;
+  validate(&cfg)?;
+  let (rel, created) = ensure_note(&vault_path, &cfg, Local::now().date_naive())?;
+  if created {
+    push(&vault_path, &rel).await;
+  }
+  Ok(rel)
+}
@@ -0,0 +1,180 @@
+use std::sync::{mpsc, Mutex};
+
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::normalize::safe_rel_path;
+use crate::sync::persist_auth_tokens;
+
+/// Must match `plugins.deep-link.desktop.schemes` in tauri.conf.json.
+const SCHEME: &str = "diregram";
+
+/// Routes that arrive before the frontend has subscribed (e.g. the link that launched the app).
+static PENDING: Lazy<Mutex<Vec<DeepLinkRoute>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static FRONTEND_READY: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+/// A native sign-in (`auth_login_start`) waiting for its callback; it holds the PKCE verifier.
+static LOGIN_WAITER: Lazy<Mutex<Option<mpsc::Sender<DeepLinkRoute>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Emitted as `deeplink://navigate`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DeepLinkRoute {
+  /// `diregram://open?file=<rel>&project=<id>`
+  Open {
+    file: String,
+    project_folder_id: Option<String>,
+  },
+  /// `diregram://sync?project=<id>`
+  Sync { project_folder_id: String },
+  /// `diregram://auth/callback?code=...` (PKCE) or `diregram://auth/callback#access_token=...`
+  AuthCallback {
+    code: Option<String>,
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    error: Option<String>,
+  },
+}
+
+fn query_param(url: &Url, key: &str) -> Option<String> {
+  url
+    .query_pairs()
+    .find(|(k, _)| k == key)
+    .map(|(_, v)| v.trim().to_string())
+    .filter(|v| !v.is_empty())
+}
+
+/// Magic links put the session in the fragment; it is form-encoded like a query string.
+fn fragment_param(url: &Url, key: &str) -> Option<String> {
+  let mut as_query = url.clone();
+  as_query.set_query(url.fragment());
+  query_param(&as_query, key)
+}
+
+fn parse_auth_callback(url: &Url) -> DeepLinkRoute {
+  let param = |key: &str| query_param(url, key).or_else(|| fragment_param(url, key));
+  DeepLinkRoute::AuthCallback {
+    code: query_param(url, "code"),
+    access_token: fragment_param(url, "access_token"),
+    refresh_token: fragment_param(url, "refresh_token"),
+    error: param("error_description").or_else(|| param("error")),
+  }
+}
+
+pub(crate) fn parse(url: &Url) -> Result<DeepLinkRoute, String> {
+  if url.scheme() != SCHEME {
+    return Err(format!("unsupported link scheme: {}", url.scheme()));
+  }
+  let host = url.host_str().unwrap_or("");
+  let path = url.path().trim_matches('/');
+  match (host, path) {
+    ("open", "") => {
+      let file = query_param(url, "file").ok_or_else(|| "open link is missing ?file=".to_string())?;
+      let file = safe_rel_path(&file).ok_or_else(|| format!("invalid file path in link: {}", file))?;
+      Ok(DeepLinkRoute::Open {
+        file,
+        project_folder_id: query_param(url, "project"),
+      })
+    }
+    ("sync", "") => Ok(DeepLinkRoute::Sync {
+      project_folder_id: query_param(url, "project").ok_or_else(|| "sync link is missing ?project=".to_string())?,
+    }),
+    ("auth", "callback") => Ok(parse_auth_callback(url)),
+    // Sign-in links issued before the routed scheme only carried the code or tokens.
+    _ if query_param(url, "code").is_some() || fragment_param(url, "access_token").is_some() => {
+      Ok(parse_auth_callback(url))
+    }
+    _ => Err(format!("unknown link: {}://{}/{}", SCHEME, host, path)),
+  }
+}
+
+/// Claims the next auth callback. Replaces (and thereby cancels) any earlier waiter.
+pub(crate) fn await_auth_callback() -> mpsc::Receiver<DeepLinkRoute> {
+  let (tx, rx) = mpsc::channel();
+  if let Ok(mut guard) = LOGIN_WAITER.lock() {
+    *guard = Some(tx);
+  }
+  rx
+}
+
+/// Side effects that don't need the UI, then hands the route to the frontend.
+fn dispatch(app: &tauri::AppHandle, route: DeepLinkRoute) {
+  if let DeepLinkRoute::AuthCallback { .. } = route {
+    let waiter = LOGIN_WAITER.lock().ok().and_then(|mut g| g.take());
+    if let Some(tx) = waiter {
+      let _ = tx.send(route);
+      return;
+    }
+  }
+  match &route {
+    DeepLinkRoute::AuthCallback {
+      access_token: Some(access),
+      refresh_token: Some(refresh),
+      ..
+    } => {
+      match crate::auth::link_session(access, refresh) {
+        Some(auth) => {
+          if let Err(e) = persist_auth_tokens(auth.profile.as_deref(), access, refresh) {
+            tracing::warn!(error = %e, "could not save session from sign-in link");
+          }
+          crate::auth::store_tokens(&auth);
+        }
+        // Another account (or none signed in): the frontend signs in from the route itself.
+        None => tracing::info!("sign-in link is not for the remembered session; left to the app"),
+      }
+    }
+    DeepLinkRoute::Sync { project_folder_id } => {
+      let sessions: Vec<_> = crate::status::sessions()
+        .into_iter()
+        .filter(|s| s.project_folder_id == *project_folder_id)
+        .collect();
+      if !sessions.is_empty() {
+        crate::tray::run_sessions(sessions);
+      }
+    }
+    _ => {}
+  }
+
+  // Hold the flag while queueing so a concurrent `deeplink_take_pending` can't miss this route.
+  let Ok(ready) = FRONTEND_READY.lock() else { return };
+  if *ready {
+    let _ = app.emit("deeplink://navigate", route);
+  } else if let Ok(mut pending) = PENDING.lock() {
+    pending.push(route);
+  }
+}
+
+fn handle_urls(app: &tauri::AppHandle, urls: Vec<Url>) {
+  for url in urls {
+    // Malformed or unknown links are dropped; there is nowhere useful to report them.
+    if let Ok(route) = parse(&url) {
+      dispatch(app, route);
+    }
+  }
+  if let Some(w) = app.get_webview_window("main") {
+    let _ = w.show();
+    let _ = w.set_focus();
+  }
+}
+
+/// Subscribes to incoming links and routes the one the app was launched with, if any.
+pub(crate) fn install(app: &tauri::AppHandle) {
+  let handle = app.clone();
+  app.deep_link().on_open_url(move |event| {
+    handle_urls(&handle, event.urls());
+  });
+  if let Ok(Some(urls)) = app.deep_link().get_current() {
+    handle_urls(app, urls);
+  }
+}
+
+/// Called once the frontend listens for `deeplink://navigate`; returns what arrived before that.
+#[tauri::command]
+pub async fn deeplink_take_pending() -> Result<Vec<DeepLinkRoute>, String> {
+  let mut ready = FRONTEND_READY.lock().map_err(|_| "deeplink state lock poisoned".to_string())?;
+  *ready = true;
+  let mut pending = PENDING.lock().map_err(|_| "deeplink state lock poisoned".to_string())?;
+  Ok(std::mem::take(&mut *pending))
+}
@@ -0,0 +1,23 @@
+use crate::sync::{
+  read_mapping, SyncSummary,
+};
+
+pub(crate) use diregram_sync_core::delete_guard::*;
+
+#[tauri::command]
+pub async fn sync_mass_delete_pending(vault_path: String) -> Result<Option<PendingMassDeleteV1>, String> {
+  Ok(read_mapping(&vault_path)?.and_then(|m| m.pending_mass_delete))
+}
+
+/// Settles a held deletion. `confirm` archives the notes as a normal pull would have; otherwise
+/// they stay and are unlinked, so the next push uploads them again.
+#[tauri::command]
+pub async fn sync_mass_delete_resolve(vault_path: String, confirm: bool) -> Result<SyncSummary, String> {
+  crate::lock::acquire(&vault_path)?;
+  let res = {
+    let _mapping = crate::lock::mapping(&vault_path).await;
+    resolve(&vault_path, confirm)
+  };
+  crate::lock::release(&vault_path);
+  res
+}
@@ -0,0 +1,68 @@
+use tauri::Manager;
+
+use crate::sync::{rest_base, send_with_refresh, SupabaseAuth};
+
+pub(crate) use diregram_sync_core::device::*;
+
+/// Sets where the device file lives. Call once, from setup, before anything logs events.
+pub(crate) fn install(app: &tauri::AppHandle) {
+  if let Ok(dir) = app.path().app_config_dir() {
+    set_dir(dir);
+  }
+}
+
+/// The account's devices, most recently seen first.
+#[tauri::command]
+pub async fn devices_list(auth: SupabaseAuth) -> Result<Vec<DeviceInfo>, String> {
+  let client = reqwest::Client::new();
+  let mut auth = auth;
+  let url = format!(
+    "{}/devices?select=device_id,name,platform,app_version,first_seen_at,last_seen_at,revoked_at&order=last_seen_at.desc",
+    rest_base(&auth)
+  );
+  let mut rows: Vec<DeviceInfo> = send_with_refresh(
+    &client,
+    &mut auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("device list failed: HTTP {}", res.status()));
+        }
+        res.json().await.map_err(|e| e.to_string())
+      })
+    },
+  )
+  .await?;
+  let me = id();
+  for d in &mut rows {
+    d.current = d.device_id == me;
+  }
+  Ok(rows)
+}
+
+/// Signs a device out remotely by ending its session; it stays listed as revoked.
+#[tauri::command]
+pub async fn device_revoke(auth: SupabaseAuth, device_id: String) -> Result<(), String> {
+  if device_id == id() {
+    return Err("this is the current device; sign out instead".to_string());
+  }
+  let client = reqwest::Client::new();
+  let mut auth = auth;
+  let url = format!("{}/rpc/revoke_device", rest_base(&auth));
+  let body = serde_json::json!({ "p_device_id": device_id });
+  send_with_refresh(
+    &client,
+    &mut auth,
+    || client.post(url.clone()).json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("device revoke failed: HTTP {}", res.status()));
+        }
+        Ok(())
+      })
+    },
+  )
+  .await
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+use crate::revisions::{checked_rel, fetch_remote_text, read_local_revision};
+use crate::sync::SupabaseAuth;
+
+/// Unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffLineKind {
+  Equal,
+  Delete,
+  Insert,
+}
+
+/// One line of a hunk. Line numbers are 1-based; a deleted line has no `new_line` and an inserted
+/// one no `old_line`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffLine {
+  pub kind: DiffLineKind,
+  pub old_line: Option<usize>,
+  pub new_line: Option<usize>,
+  pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiffHunk {
+  pub old_start: usize,
+  pub old_lines: usize,
+  pub new_start: usize,
+  pub new_lines: usize,
+  pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileDiff {
+  pub path: String,
+  pub left: String,
+  pub right: String,
+  /// Empty when both sides are identical.
+  pub hunks: Vec<DiffHunk>,
+}
+
+pub(crate) fn hunks(old: &str, new: &str) -> Vec<DiffHunk> {
+  let diff = TextDiff::configure().timeout(crate::delta::DIFF_TIMEOUT).diff_lines(old, new);
+  diff
+    .grouped_ops(CONTEXT_LINES)
+    .iter()
+    .map(|group| {
+      let (first, last) = (&group[0], &group[group.len() - 1]);
+      let old_range = first.old_range().start..last.old_range().end;
+      let new_range = first.new_range().start..last.new_range().end;
+      let lines = group
+        .iter()
+        .flat_map(|op| diff.iter_changes(op))
+        .map(|change| DiffLine {
+          kind: match change.tag() {
+            ChangeTag::Equal => DiffLineKind::Equal,
+            ChangeTag::Delete => DiffLineKind::Delete,
+            ChangeTag::Insert => DiffLineKind::Insert,
+          },
+          old_line: change.old_index().map(|i| i + 1),
+          new_line: change.new_index().map(|i| i + 1),
+          text: change.value().trim_end_matches(['\n', '\r']).to_string(),
+        })
+        .collect();
+      DiffHunk {
+        old_start: old_range.start + 1,
+        old_lines: old_range.len(),
+        new_start: new_range.start + 1,
+        new_lines: new_range.len(),
+        lines,
+      }
+    })
+    .collect()
+}
+
+/// Resolves a revision reference: `local` (the file on disk), `local:<ts>` (a local revision),
+/// `remote` (the synced row as it is now) or `remote:<rev>`.
+async fn resolve(
+  client: &reqwest::Client,
+  auth: &mut Option<SupabaseAuth>,
+  vault_path: &str,
+  rel_path: &str,
+  reference: &str,
+) -> Result<String, String> {
+  let (side, id) = match reference.split_once(':') {
+    Some((side, id)) => (side, Some(id)),
+    None => (reference, None),
+  };
+  match (side, id) {
+    ("local", None) => fs::read_to_string(crate::normalize::local_path(Path::new(vault_path), rel_path)).map_err(|e| e.to_string()),
+    ("local", Some(ts)) => read_local_revision(vault_path, rel_path, ts),
+    ("remote", id) => {
+      let rev = match id {
+        Some(id) => Some(id.parse::<i64>().map_err(|_| format!("invalid remote revision: {}", id))?),
+        None => None,
+      };
+      let auth = auth.as_mut().ok_or_else(|| "sign in to compare remote revisions".to_string())?;
+      fetch_remote_text(client, auth, vault_path, rel_path, rev).await
+    }
+    _ => Err(format!("invalid revision reference: {}", reference)),
+  }
+}
+
+#[tauri::command]
+pub async fn vault_diff(
+  vault_path: String,
+  path: String,
+  left_rev: String,
+  right_rev: String,
+  auth: Option<SupabaseAuth>,
+) -> Result<FileDiff, String> {
+  let rel = checked_rel(&path)?;
+  let client = reqwest::Client::new();
+  let mut auth = auth.as_ref().map(crate::auth::latest);
+  let left = resolve(&client, &mut auth, &vault_path, &rel, &left_rev).await?;
+  let right = resolve(&client, &mut auth, &vault_path, &rel, &right_rev).await?;
+  Ok(FileDiff {
+    path: rel,
+    hunks: hunks(&left, &right),
+    left: left_rev,
+    right: right_rev,
+  })
+}
@@ -0,0 +1,32 @@
+use std::collections::BTreeMap;
+
+use crate::config::{read_config, write_config, SyncMode};
+
+/// Sets whether the vault syncs both ways, only takes remote changes or only sends its own. Takes
+/// effect on the next push or pull.
+#[tauri::command]
+pub async fn sync_set_mode(vault_path: String, mode: SyncMode) -> Result<SyncMode, String> {
+  let mut cfg = read_config(&vault_path)?;
+  cfg.mode = mode;
+  write_config(&vault_path, &cfg)?;
+  Ok(mode)
+}
+
+/// Sets (or with `None`, clears) the mode of the files in `folder`, overriding the vault's.
+#[tauri::command]
+pub async fn sync_set_folder_mode(
+  vault_path: String,
+  folder: String,
+  mode: Option<SyncMode>,
+) -> Result<BTreeMap<String, SyncMode>, String> {
+  let folder = crate::normalize::safe_rel_path(&folder)
+    .map(|f| f.trim_end_matches('/').to_string())
+    .ok_or_else(|| format!("{} must be a folder inside the vault", folder))?;
+  let mut cfg = read_config(&vault_path)?;
+  match mode {
+    Some(mode) => cfg.folder_modes.insert(folder, mode),
+    None => cfg.folder_modes.remove(&folder),
+  };
+  write_config(&vault_path, &cfg)?;
+  Ok(cfg.folder_modes)
+}
@@ -0,0 +1,407 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::normalize::safe_rel_path;
+use crate::sync::{
+  diregram_dir, is_ignored_rel, is_markdown_path, now_iso, read_mapping, sha256_hex, to_rel_posix, trash_dir, SupabaseAuth,
+  SyncMappingV1,
+};
+
+/// How long the watcher probe waits for its own write to be reported.
+const WATCH_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DoctorStatus {
+  Ok,
+  Warning,
+  Error,
+  /// Could not be checked (e.g. signed out, or not applicable on this platform).
+  Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DoctorCheck {
+  pub id: String,
+  pub status: DoctorStatus,
+  pub summary: String,
+  /// What the user can do about it, when there is something to do.
+  pub fix: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VaultDoctorReport {
+  pub vault_path: String,
+  pub checked_at: String,
+  /// Worst status among the checks.
+  pub status: DoctorStatus,
+  pub checks: Vec<DoctorCheck>,
+}
+
+fn check(id: &str, status: DoctorStatus, summary: impl Into<String>, fix: Option<&str>) -> DoctorCheck {
+  DoctorCheck {
+    id: id.to_string(),
+    status,
+    summary: summary.into(),
+    fix: fix.map(str::to_string),
+  }
+}
+
+fn check_mapping(vault_path: &str) -> (DoctorCheck, Option<SyncMappingV1>) {
+  let mapping = match read_mapping(vault_path) {
+    Ok(Some(m)) => m,
+    Ok(None) => {
+      return (
+        check(
+          "mapping",
+          DoctorStatus::Warning,
+          "Vault is not linked to a project yet.",
+          Some("Link the vault to a project to start syncing."),
+        ),
+        None,
+      )
+    }
+    Err(e) => {
+      return (
+        check(
+          "mapping",
+          DoctorStatus::Error,
+          format!("Sync mapping can't be read: {}", e),
+          Some("Restore .diregram/sync.json from a backup, or unlink and relink the vault."),
+        ),
+        None,
+      )
+    }
+  };
+
+  let mut problems = Vec::new();
+  if mapping.project_folder_id.is_empty() {
+    problems.push("no project folder id".to_string());
+  }
+  let bad_paths = mapping.files.keys().filter(|p| safe_rel_path(p).as_deref() != Some(p.as_str())).count();
+  if bad_paths > 0 {
+    problems.push(format!("{} file path(s) outside the vault", bad_paths));
+  }
+  let mut seen: HashMap<&str, u32> = HashMap::new();
+  for m in mapping.files.values() {
+    *seen.entry(m.file_id.as_str()).or_default() += 1;
+  }
+  let duplicated = seen.values().filter(|n| **n > 1).count();
+  if duplicated > 0 {
+    problems.push(format!("{} remote file(s) mapped to more than one path", duplicated));
+  }
+  let unknown_folders = mapping
+    .files
+    .values()
+    .filter(|m| !m.folder_id.is_empty() && !mapping.folders.values().any(|f| *f == m.folder_id))
+    .count();
+  if unknown_folders > 0 {
+    problems.push(format!("{} file(s) in folders the mapping doesn't know", unknown_folders));
+  }
+
+  let c = if problems.is_empty() {
+    check(
+      "mapping",
+      DoctorStatus::Ok,
+      format!("{} file(s) and {} folder(s) mapped.", mapping.files.len(), mapping.folders.len()),
+      None,
+    )
+  } else {
+    check(
+      "mapping",
+      DoctorStatus::Error,
+      format!("Sync mapping is inconsistent: {}.", problems.join("; ")),
+      Some("Unlink and relink the vault; files are matched to their remote copies again by path."),
+    )
+  };
+  (c, Some(mapping))
+}
+
+fn check_writable(vault_path: &str) -> DoctorCheck {
+  let dir = diregram_dir(vault_path);
+  let probe = dir.join(".doctor-probe");
+  let res = fs::create_dir_all(&dir)
+    .and_then(|_| fs::write(&probe, b"ok"))
+    .and_then(|_| fs::remove_file(&probe));
+  match res {
+    Ok(()) => check("metadata_writable", DoctorStatus::Ok, ".diregram is writable.", None),
+    Err(e) => check(
+      "metadata_writable",
+      DoctorStatus::Error,
+      format!("Can't write to {}: {}", dir.display(), e),
+      Some("Check the folder's permissions and free disk space."),
+    ),
+  }
+}
+
+/// Watches `.diregram` and waits for a probe write to be reported.
+fn probe_watcher(vault_path: &str) -> Result<bool, String> {
+  let dir = diregram_dir(vault_path);
+  fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  let (tx, rx) = mpsc::channel();
+  let mut watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
+    let _ = tx.send(res.is_ok());
+  })
+  .map_err(|e| e.to_string())?;
+  watcher.watch(&dir, RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+  let probe = dir.join(".doctor-watch-probe");
+  fs::write(&probe, b"ok").map_err(|e| e.to_string())?;
+  let seen = rx.recv_timeout(WATCH_PROBE_TIMEOUT).unwrap_or(false);
+  let _ = fs::remove_file(&probe);
+  Ok(seen)
+}
+
+#[cfg(target_os = "linux")]
+fn inotify_limit() -> Option<u64> {
+  fs::read_to_string("/proc/sys/fs/inotify/max_user_watches").ok()?.trim().parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn inotify_limit() -> Option<u64> {
+  None
+}
+
+fn check_watcher(vault_path: &str) -> DoctorCheck {
+  match probe_watcher(vault_path) {
+    Ok(true) => {}
+    Ok(false) => {
+      return check(
+        "watcher",
+        DoctorStatus::Error,
+        "File watcher didn't report a change; local edits won't be pushed automatically.",
+        Some("Network drives and some sandboxed folders don't support file events. Move the vault to a local disk or push manually."),
+      )
+    }
+    Err(e) => {
+      return check(
+        "watcher",
+        DoctorStatus::Error,
+        format!("File watcher can't start: {}", e),
+        Some("On Linux, raise fs.inotify.max_user_instances; otherwise push manually."),
+      )
+    }
+  }
+  let Some(limit) = inotify_limit() else {
+    return check("watcher", DoctorStatus::Ok, "File watcher is working.", None);
+  };
+  // Recursive inotify watches use one watch per directory.
+  let dirs = WalkDir::new(vault_path)
+    .into_iter()
+    .filter_map(Result::ok)
+    .filter(|e| e.file_type().is_dir())
+    .count() as u64;
+  if dirs * 2 > limit {
+    check(
+      "watcher",
+      DoctorStatus::Warning,
+      format!("Vault has {} folders; the inotify limit is {} watches shared by every app.", dirs, limit),
+      Some("Raise fs.inotify.max_user_watches (e.g. `sysctl fs.inotify.max_user_watches=524288`)."),
+    )
+  } else {
+    check(
+      "watcher",
+      DoctorStatus::Ok,
+      format!("File watcher is working ({} folders, inotify limit {}).", dirs, limit),
+      None,
+    )
+  }
+}
+
+/// Auth validity and clock skew, both from one request to the auth server.
+async fn check_server(auth: Option<&SupabaseAuth>) -> Vec<DoctorCheck> {
+  let Some(auth) = auth else {
+    return vec![
+      check("auth", DoctorStatus::Skipped, "Not signed in.", Some("Sign in to sync this vault.")),
+      check("clock_skew", DoctorStatus::Skipped, "Needs a signed-in session.", None),
+    ];
+  };
+  let auth = crate::auth::latest(auth);
+  let client = reqwest::Client::new();
+  let url = format!("{}/auth/v1/user", auth.supabase_url.trim_end_matches('/'));
+  let res = client
+    .get(url)
+    .header("apikey", &auth.supabase_anon_key)
+    .bearer_auth(&auth.access_token)
+    .send()
+    .await;
+  let res = match res {
+    Ok(res) => res,
+    Err(e) => {
+      return vec![
+        check("auth", DoctorStatus::Warning, format!("Server unreachable: {}", e), Some("Check your network connection.")),
+        check("clock_skew", DoctorStatus::Skipped, "Server unreachable.", None),
+      ]
+    }
+  };
+
+  let skew = crate::clock::skew_from(&res);
+  let clock = match skew {
+    Some(s) if s.abs() > crate::clock::MAX_CLOCK_SKEW_SECS => check(
+      "clock_skew",
+      DoctorStatus::Warning,
+      format!("This computer's clock is {}s {} the server.", s.abs(), if s > 0 { "ahead of" } else { "behind" }),
+      Some("Turn on automatic time sync in your system settings."),
+    ),
+    Some(s) => check("clock_skew", DoctorStatus::Ok, format!("Clock is within {}s of the server.", s.abs()), None),
+    None => check("clock_skew", DoctorStatus::Skipped, "Server didn't report its time.", None),
+  };
+
+  let status = res.status();
+  let auth_check = if status.is_success() {
+    check("auth", DoctorStatus::Ok, "Signed in.", None)
+  } else if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+    check(
+      "auth",
+      DoctorStatus::Error,
+      "Session is no longer valid.",
+      Some("Sign in again; syncing stops until you do."),
+    )
+  } else {
+    check("auth", DoctorStatus::Warning, format!("Auth server returned HTTP {}.", status), None)
+  };
+  vec![auth_check, clock]
+}
+
+/// Local changes the next push would upload: edited or new notes, and mapped notes deleted locally.
+fn check_pending(vault_path: &str, mapping: Option<&SyncMappingV1>) -> DoctorCheck {
+  let Some(mapping) = mapping else {
+    return check("pending", DoctorStatus::Skipped, "Vault is not linked.", None);
+  };
+  let root = Path::new(vault_path);
+  let mut changed = 0u32;
+  let mut added = 0u32;
+  let mut present = 0usize;
+  for entry in WalkDir::new(root)
+    .follow_links(false)
+    .into_iter()
+    .filter_entry(|e| e.file_name() != ".diregram")
+    .filter_map(Result::ok)
+  {
+    if !entry.file_type().is_file() {
+      continue;
+    }
+    let Some(rel) = to_rel_posix(root, entry.path()) else { continue };
+    if is_ignored_rel(&rel) {
+      continue;
+    }
+    match mapping.files.get(&rel) {
+      Some(m) => {
+        present += 1;
+        if fs::read(entry.path()).map(|b| sha256_hex(&b) != m.local_hash).unwrap_or(false) {
+          changed += 1;
+        }
+      }
+      None if is_markdown_path(entry.path()) => added += 1,
+      None => {}
+    }
+  }
+  let deleted = mapping.files.len().saturating_sub(present);
+  let total = changed as usize + added as usize + deleted;
+  let summary = format!("{} edited, {} new, {} deleted locally since the last sync.", changed, added, deleted);
+  if total == 0 {
+    check("pending", DoctorStatus::Ok, "Everything local has been pushed.", None)
+  } else if crate::status::is_paused() {
+    check("pending", DoctorStatus::Warning, summary, Some("Sync is paused; resume it to push these changes."))
+  } else {
+    check("pending", DoctorStatus::Ok, summary, None)
+  }
+}
+
+fn check_conflicts(vault_path: &str) -> DoctorCheck {
+  let conflicts = crate::conflicts::list(vault_path);
+  let orphaned = conflicts.iter().filter(|c| !c.original_exists).count();
+  if orphaned > 0 {
+    check(
+      "conflicts",
+      DoctorStatus::Warning,
+      format!("{} conflict copies whose note no longer exists ({} unresolved in total).", orphaned, conflicts.len()),
+      Some("Review them in the conflicts list and keep or delete each copy."),
+    )
+  } else if !conflicts.is_empty() {
+    check(
+      "conflicts",
+      DoctorStatus::Warning,
+      format!("{} unresolved conflict(s).", conflicts.len()),
+      Some("Resolve them from the conflicts list."),
+    )
+  } else {
+    check("conflicts", DoctorStatus::Ok, "No unresolved conflicts.", None)
+  }
+}
+
+fn check_trash(vault_path: &str) -> DoctorCheck {
+  let (files, bytes) = crate::trash::dir_size(&trash_dir(vault_path));
+  let max = crate::config::read_config(vault_path).ok().and_then(|c| c.trash.max_bytes);
+  let summary = format!("Trash holds {} file(s), {} KiB.", files, bytes / 1024);
+  match max {
+    Some(max) if bytes > max => check(
+      "trash",
+      DoctorStatus::Warning,
+      format!("{} That's over the {} KiB limit.", summary, max / 1024),
+      Some("Empty the trash, or lower the retention settings."),
+    ),
+    _ => check("trash", DoctorStatus::Ok, summary, None),
+  }
+}
+
+fn check_links(vault_path: &str) -> DoctorCheck {
+  let report = crate::linkcheck::check(vault_path);
+  if report.broken.is_empty() {
+    return check(
+      "links",
+      DoctorStatus::Ok,
+      format!("All {} link(s) in {} note(s) resolve.", report.links, report.notes),
+      None,
+    );
+  }
+  let notes: HashSet<&str> = report.broken.iter().map(|b| b.source.as_str()).collect();
+  check(
+    "links",
+    DoctorStatus::Warning,
+    format!("{} broken link(s) in {} note(s).", report.broken.len(), notes.len()),
+    Some("Open the link check to fix them; most come with a suggested target or a trash copy to restore."),
+  )
+}
+
+/// Runs every health check on a vault. Checks never fail the command; problems are reported as
+/// entries with a suggested fix.
+#[tauri::command]
+pub async fn vault_doctor(vault_path: String, auth: Option<SupabaseAuth>) -> Result<VaultDoctorReport, String> {
+  if !Path::new(&vault_path).is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let (mapping_check, mapping) = check_mapping(&vault_path);
+  let mut checks = vec![mapping_check, check_writable(&vault_path)];
+  let vp = vault_path.clone();
+  checks.push(
+    tauri::async_runtime::spawn_blocking(move || check_watcher(&vp))
+      .await
+      .map_err(|e| e.to_string())?,
+  );
+  checks.extend(check_server(auth.as_ref()).await);
+  checks.push(check_pending(&vault_path, mapping.as_ref()));
+  checks.push(check_conflicts(&vault_path));
+  checks.push(check_trash(&vault_path));
+  checks.push(check_links(&vault_path));
+
+  let status = if checks.iter().any(|c| c.status == DoctorStatus::Error) {
+    DoctorStatus::Error
+  } else if checks.iter().any(|c| c.status == DoctorStatus::Warning) {
+    DoctorStatus::Warning
+  } else {
+    DoctorStatus::Ok
+  };
+  Ok(VaultDoctorReport {
+    vault_path,
+    checked_at: now_iso(),
+    status,
+    checks,
+  })
+}
@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{looks_like_text_utf8, sha256_hex};
+
+const DEFAULT_THRESHOLD: f64 = 0.8;
+/// Notes shorter than this (in words) are too short to call near-duplicates.
+const MIN_NEAR_WORDS: usize = 20;
+/// Words per shingle.
+const SHINGLE: usize = 3;
+/// MinHash signature length, as `BANDS` bands of `ROWS` rows for finding candidate pairs.
+const BANDS: usize = 16;
+const ROWS: usize = 4;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMode {
+  /// Files with identical bytes.
+  Exact,
+  /// Notes whose words mostly overlap.
+  Near,
+  #[default]
+  All,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DuplicateCluster {
+  /// `exact` or `near`.
+  pub kind: String,
+  /// 1 for exact duplicates; for near ones, the lowest similarity (0-1) linking the cluster.
+  pub similarity: f64,
+  /// Vault-relative, sorted.
+  pub paths: Vec<String>,
+}
+
+/// FNV-1a, so shingle hashes don't depend on the process.
+fn fnv(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn mix(mut x: u64) -> u64 {
+  x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+  x ^ (x >> 31)
+}
+
+/// Hashed word shingles of a note, ignoring frontmatter, case, punctuation and spacing. `None`
+/// for notes too short to compare.
+fn shingles(text: &str) -> Option<HashSet<u64>> {
+  let body = text
+    .strip_prefix("---\n")
+    .and_then(|rest| rest.find("\n---").map(|end| &rest[end + 4..]))
+    .unwrap_or(text)
+    .to_lowercase();
+  let words: Vec<&str> = body.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+  if words.len() < MIN_NEAR_WORDS {
+    return None;
+  }
+  Some(words.windows(SHINGLE).map(|w| fnv(w.join(" ").as_bytes())).collect())
+}
+
+fn signature(set: &HashSet<u64>) -> Vec<u64> {
+  (0..BANDS * ROWS)
+    .map(|i| set.iter().map(|h| mix(h ^ mix(i as u64 + 1))).min().unwrap_or(0))
+    .collect()
+}
+
+fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+  let shared = a.intersection(b).count();
+  shared as f64 / (a.len() + b.len() - shared).max(1) as f64
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+  let mut root = i;
+  while parent[root] != root {
+    root = parent[root];
+  }
+  parent[i] = root;
+  root
+}
+
+/// Clusters of distinct contents (indexes into `sets`) at least `threshold` similar, each with the
+/// lowest similarity that links it. Candidates come from MinHash banding, then are checked exactly.
+fn near_clusters(sets: &[HashSet<u64>], threshold: f64) -> Vec<(Vec<usize>, f64)> {
+  let sigs: Vec<Vec<u64>> = sets.iter().map(signature).collect();
+  let mut candidates: HashSet<(usize, usize)> = HashSet::new();
+  for band in 0..BANDS {
+    let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+    for (i, sig) in sigs.iter().enumerate() {
+      buckets.entry(&sig[band * ROWS..(band + 1) * ROWS]).or_default().push(i);
+    }
+    for members in buckets.values().filter(|m| m.len() > 1) {
+      for (n, a) in members.iter().enumerate() {
+        for b in &members[n + 1..] {
+          candidates.insert((*a, *b));
+        }
+      }
+    }
+  }
+
+  let mut parent: Vec<usize> = (0..sets.len()).collect();
+  let mut lowest: HashMap<usize, f64> = HashMap::new();
+  let mut links: Vec<(usize, usize, f64)> = Vec::new();
+  for (a, b) in candidates {
+    let sim = jaccard(&sets[a], &sets[b]);
+    if sim >= threshold {
+      links.push((a, b, sim));
+      let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+      parent[ra] = rb;
+    }
+  }
+  for (a, _, sim) in links {
+    let root = find(&mut parent, a);
+    let low = lowest.entry(root).or_insert(1.0);
+    *low = low.min(sim);
+  }
+  let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+  for i in 0..sets.len() {
+    let root = find(&mut parent, i);
+    groups.entry(root).or_default().push(i);
+  }
+  groups
+    .into_iter()
+    .filter(|(_, members)| members.len() > 1)
+    .map(|(root, members)| (members, lowest.get(&root).copied().unwrap_or(1.0)))
+    .collect()
+}
+
+/// Duplicate files in the vault, `resources/` included: identical files and, for text, notes
+/// whose wording is at least `threshold` (0-1) the same.
+fn find_duplicates(vault_path: &str, mode: DuplicateMode, threshold: f64) -> Result<Vec<DuplicateCluster>, String> {
+  let root = Path::new(vault_path);
+  if !root.is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  // Content hash -> paths, and the hash and shingles of each distinct text.
+  let mut by_hash: HashMap<String, Vec<String>> = HashMap::new();
+  let mut text_hashes: Vec<String> = Vec::new();
+  let mut sets: Vec<HashSet<u64>> = Vec::new();
+  for (abs, rel) in crate::site::collect(root, vault_path) {
+    let Ok(bytes) = fs::read(&abs) else { continue };
+    if bytes.iter().all(|b| b.is_ascii_whitespace()) {
+      continue;
+    }
+    let hash = sha256_hex(&bytes);
+    let seen = by_hash.contains_key(&hash);
+    by_hash.entry(hash.clone()).or_default().push(rel);
+    if mode != DuplicateMode::Exact && !seen && looks_like_text_utf8(&bytes) {
+      if let Some(set) = shingles(&crate::encoding::decode(&bytes).0) {
+        text_hashes.push(hash);
+        sets.push(set);
+      }
+    }
+  }
+
+  let mut out = Vec::new();
+  if mode != DuplicateMode::Near {
+    for paths in by_hash.values().filter(|p| p.len() > 1) {
+      let mut paths = paths.clone();
+      paths.sort();
+      out.push(DuplicateCluster {
+        kind: "exact".to_string(),
+        similarity: 1.0,
+        paths,
+      });
+    }
+  }
+  if mode != DuplicateMode::Exact {
+    let threshold = threshold.clamp(0.0, 1.0);
+    for (members, similarity) in near_clusters(&sets, threshold) {
+      // Exact copies of a near-duplicate are listed with it.
+      let mut paths: Vec<String> = members.iter().flat_map(|i| by_hash[&text_hashes[*i]].iter().cloned()).collect();
+      paths.sort();
+      out.push(DuplicateCluster {
+        kind: "near".to_string(),
+        similarity,
+        paths,
+      });
+    }
+  }
+  out.sort_by(|a, b| (a.kind.as_str(), &a.paths).cmp(&(b.kind.as_str(), &b.paths)));
+  Ok(out)
+}
+
+/// Exact and near-duplicate files, so they can be cleaned up before they crowd the RAG index.
+#[tauri::command]
+pub async fn vault_find_duplicates(
+  vault_path: String,
+  mode: Option<DuplicateMode>,
+  threshold: Option<f64>,
+) -> Result<Vec<DuplicateCluster>, String> {
+  find_duplicates(&vault_path, mode.unwrap_or_default(), threshold.unwrap_or(DEFAULT_THRESHOLD))
+}
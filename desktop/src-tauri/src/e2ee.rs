@@ -0,0 +1,39 @@
+use crate::config::{read_config, write_config};
+
+pub(crate) use diregram_sync_core::e2ee::*;
+
+#[tauri::command]
+pub async fn e2ee_status(vault_path: String) -> Result<E2eeStatus, String> {
+  let project_folder_id = project_of(&vault_path)?;
+  status_for(&vault_path, &project_folder_id)
+}
+
+/// Derives the vault key from `passphrase` and stores it in the keyring. Also how a second device
+/// joins: entering the same passphrase yields the same key.
+#[tauri::command]
+pub async fn e2ee_unlock(vault_path: String, passphrase: String) -> Result<E2eeStatus, String> {
+  if passphrase.chars().count() < 8 {
+    return Err("passphrase must be at least 8 characters".to_string());
+  }
+  let project_folder_id = project_of(&vault_path)?;
+  let pid = project_folder_id.clone();
+  let key = tokio::task::spawn_blocking(move || derive_key(&passphrase, &pid))
+    .await
+    .map_err(|e| e.to_string())??;
+  store_key(&project_folder_id, key)?;
+  status_for(&vault_path, &project_folder_id)
+}
+
+/// Turns encryption of pushed content on or off. Existing remote rows are re-encrypted (or
+/// decrypted) only when next pushed; pulled rows are always decrypted when a key is present.
+#[tauri::command]
+pub async fn e2ee_set_enabled(vault_path: String, enabled: bool) -> Result<E2eeStatus, String> {
+  let project_folder_id = project_of(&vault_path)?;
+  if enabled && load_key(&project_folder_id)?.is_none() {
+    return Err("set a passphrase with e2ee_unlock before enabling encryption".to_string());
+  }
+  let mut config = read_config(&vault_path)?;
+  config.e2ee.enabled = enabled;
+  write_config(&vault_path, &config)?;
+  status_for(&vault_path, &project_folder_id)
+}
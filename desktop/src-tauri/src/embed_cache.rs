@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{diregram_dir, write_jsonl};
+
+/// One cached embedding: the vector (little-endian f32s, base64) the model gave a chunk's text.
+#[derive(Serialize, Deserialize)]
+struct Row {
+  model: String,
+  hash: String,
+  embedding: String,
+}
+
+fn cache_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("embeddings.jsonl")
+}
+
+/// Embeddings already computed for the vault's chunks, keyed by chunk hash, so an ingest only
+/// embeds chunks that are new or changed. Saving keeps just the entries this ingest used, which
+/// drops deleted chunks and other models.
+pub(crate) struct EmbeddingCache {
+  vault_path: String,
+  model: String,
+  entries: HashMap<String, Vec<f32>>,
+  used: HashSet<String>,
+  /// Chunks served from the cache, and distinct texts that had to be embedded, since `load`.
+  pub(crate) reused: u32,
+  pub(crate) computed: u32,
+}
+
+impl EmbeddingCache {
+  /// The cached embeddings for `model`; a missing or unreadable cache is an empty one.
+  pub(crate) fn load(vault_path: &str, model: &str) -> Self {
+    let entries = fs::read_to_string(cache_path(vault_path))
+      .unwrap_or_default()
+      .lines()
+      .filter_map(|l| serde_json::from_str::<Row>(l).ok())
+      .filter(|r| r.model == model)
+      .filter_map(|r| {
+        let bytes = STANDARD.decode(&r.embedding).ok()?;
+        let v = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        Some((r.hash, v))
+      })
+      .collect();
+    Self {
+      vault_path: vault_path.to_string(),
+      model: model.to_string(),
+      entries,
+      used: HashSet::new(),
+      reused: 0,
+      computed: 0,
+    }
+  }
+
+  pub(crate) fn contains(&self, hash: &str) -> bool {
+    self.entries.contains_key(hash)
+  }
+
+  pub(crate) fn get(&mut self, hash: &str) -> Option<Vec<f32>> {
+    let v = self.entries.get(hash)?.clone();
+    self.used.insert(hash.to_string());
+    Some(v)
+  }
+
+  pub(crate) fn insert(&mut self, hash: &str, embedding: Vec<f32>) {
+    self.used.insert(hash.to_string());
+    self.entries.insert(hash.to_string(), embedding);
+  }
+
+  pub(crate) fn save(&self) -> Result<(), String> {
+    let mut hashes: Vec<&String> = self.used.iter().filter(|h| self.entries.contains_key(*h)).collect();
+    hashes.sort();
+    let rows: Vec<Row> = hashes
+      .into_iter()
+      .map(|h| Row {
+        model: self.model.clone(),
+        hash: h.clone(),
+        embedding: STANDARD.encode(self.entries[h].iter().flat_map(|f| f.to_le_bytes()).collect::<Vec<u8>>()),
+      })
+      .collect();
+    write_jsonl(&cache_path(&self.vault_path), &rows)
+  }
+}
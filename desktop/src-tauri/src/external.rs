@@ -0,0 +1,913 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::links::{dir_of, percent_decode, percent_encode, relative, resolve, rewrite_targets};
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+/// Where imported attachments go, below a folder named like the import's.
+const ATTACHMENTS_DIR: &str = "resources/attachments";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExternalFormat {
+  /// Notion's "Markdown & CSV" or "HTML" export zip.
+  Notion,
+  /// An Evernote `.enex` export.
+  Evernote,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ExternalImportSummary {
+  pub notes: u32,
+  pub attachments: u32,
+  /// Links between imported notes and attachments pointed at their new paths.
+  pub links_rewritten: u32,
+  /// Entries left out, with why.
+  pub skipped: Vec<String>,
+}
+
+/// One note or attachment of an export, by its path inside the export.
+struct Item {
+  source: String,
+  bytes: Vec<u8>,
+}
+
+// ---------------------------------------------------------------------------------------------
+// HTML / ENML to markdown
+
+fn decode_entities(s: &str) -> String {
+  if !s.contains('&') {
+    return s.to_string();
+  }
+  let mut out = String::with_capacity(s.len());
+  let mut rest = s;
+  while let Some(i) = rest.find('&') {
+    out.push_str(&rest[..i]);
+    rest = &rest[i..];
+    let Some(end) = rest[..rest.len().min(12)].find(';') else {
+      out.push('&');
+      rest = &rest[1..];
+      continue;
+    };
+    let name = &rest[1..end];
+    let decoded = match name {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      "nbsp" => Some(' '),
+      _ => name
+        .strip_prefix("#x")
+        .or_else(|| name.strip_prefix("#X"))
+        .and_then(|h| u32::from_str_radix(h, 16).ok())
+        .or_else(|| name.strip_prefix('#').and_then(|d| d.parse().ok()))
+        .and_then(char::from_u32),
+    };
+    match decoded {
+      Some(c) => {
+        out.push(c);
+        rest = &rest[end + 1..];
+      }
+      None => {
+        out.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+enum Token<'a> {
+  Text(&'a str),
+  /// Lowercased name, attributes (lowercased names, decoded values).
+  Open(String, HashMap<String, String>),
+  Close(String),
+}
+
+fn parse_attrs(s: &str) -> HashMap<String, String> {
+  let mut attrs = HashMap::new();
+  let mut rest = s.trim();
+  while !rest.is_empty() {
+    let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+    let name = rest[..name_end].to_lowercase();
+    rest = rest[name_end..].trim_start();
+    let mut value = String::new();
+    if let Some(after) = rest.strip_prefix('=') {
+      let after = after.trim_start();
+      let (v, next) = match after.chars().next() {
+        Some(q @ ('"' | '\'')) => {
+          let end = after[1..].find(q).map(|e| e + 1).unwrap_or(after.len());
+          (&after[1..end], after.get(end + 1..).unwrap_or(""))
+        }
+        _ => {
+          let end = after.find(char::is_whitespace).unwrap_or(after.len());
+          (&after[..end], &after[end..])
+        }
+      };
+      value = decode_entities(v);
+      rest = next.trim_start();
+    }
+    if !name.is_empty() {
+      attrs.insert(name, value);
+    }
+  }
+  attrs
+}
+
+fn tokens(html: &str) -> Vec<Token<'_>> {
+  let mut out = Vec::new();
+  let mut rest = html;
+  while !rest.is_empty() {
+    let Some(lt) = rest.find('<') else {
+      out.push(Token::Text(rest));
+      break;
+    };
+    if lt > 0 {
+      out.push(Token::Text(&rest[..lt]));
+    }
+    rest = &rest[lt..];
+    // Comments, doctypes, processing instructions and CDATA markers carry nothing to keep.
+    let skip_to = if rest.starts_with("<!--") {
+      rest.find("-->").map(|e| e + 3)
+    } else if rest.starts_with("<!") || rest.starts_with("<?") {
+      rest.find('>').map(|e| e + 1)
+    } else {
+      None
+    };
+    if let Some(end) = skip_to {
+      rest = &rest[end..];
+      continue;
+    }
+    let Some(gt) = rest.find('>') else {
+      out.push(Token::Text(rest));
+      break;
+    };
+    let inner = rest[1..gt].trim();
+    rest = &rest[gt + 1..];
+    if let Some(name) = inner.strip_prefix('/') {
+      out.push(Token::Close(name.trim().to_lowercase()));
+      continue;
+    }
+    let self_closing = inner.ends_with('/');
+    let inner = inner.trim_end_matches('/');
+    let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+    let name = inner[..name_end].to_lowercase();
+    out.push(Token::Open(name.clone(), parse_attrs(&inner[name_end..])));
+    if self_closing {
+      out.push(Token::Close(name));
+    }
+  }
+  out
+}
+
+/// Markdown for an HTML page or an Evernote note. `media` renders `<en-media>` from its attributes.
+fn html_to_markdown(html: &str, media: &dyn Fn(&HashMap<String, String>) -> Option<String>) -> String {
+  // Each open blockquote collects into its own buffer, prefixed with `> ` when it closes.
+  let mut buffers: Vec<String> = vec![String::new()];
+  // Open lists: numbered?, items so far.
+  let mut lists: Vec<(bool, u32)> = Vec::new();
+  let mut links: Vec<Option<String>> = Vec::new();
+  let mut hidden: u32 = 0;
+  let mut pre = false;
+  let mut row_cells = 0;
+  let mut table_rows = 0;
+
+  fn buf(buffers: &mut [String]) -> &mut String {
+    buffers.last_mut().expect("root buffer")
+  }
+  fn block(b: &mut String, blank: bool) {
+    while b.ends_with(' ') {
+      b.pop();
+    }
+    if b.is_empty() {
+      return;
+    }
+    let want = if blank { "\n\n" } else { "\n" };
+    while !b.ends_with(want) {
+      b.push('\n');
+    }
+  }
+
+  for tok in tokens(html) {
+    match tok {
+      Token::Text(t) => {
+        if hidden > 0 {
+          continue;
+        }
+        let text = decode_entities(t);
+        let b = buf(&mut buffers);
+        if pre {
+          b.push_str(&text);
+          continue;
+        }
+        for c in text.chars() {
+          if c.is_whitespace() {
+            if !b.is_empty() && !b.ends_with([' ', '\n']) {
+              b.push(' ');
+            }
+          } else {
+            b.push(c);
+          }
+        }
+      }
+      Token::Open(name, attrs) => {
+        if matches!(name.as_str(), "head" | "style" | "script" | "title") {
+          hidden += 1;
+        }
+        if hidden > 0 {
+          continue;
+        }
+        let b = buf(&mut buffers);
+        match name.as_str() {
+          "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            block(b, true);
+            let level = name[1..].parse::<usize>().unwrap_or(1);
+            b.push_str(&format!("{} ", "#".repeat(level)));
+          }
+          // Evernote puts each line in its own div.
+          "p" | "div" => block(b, true),
+          "section" | "article" | "header" | "footer" | "en-note" | "figure" => block(b, false),
+          "br" => b.push('\n'),
+          "hr" => {
+            block(b, true);
+            b.push_str("---\n\n");
+          }
+          "strong" | "b" => b.push_str("**"),
+          "em" | "i" => b.push('*'),
+          "s" | "del" | "strike" => b.push_str("~~"),
+          "code" if !pre => b.push('`'),
+          "pre" => {
+            block(b, true);
+            b.push_str("```\n");
+            pre = true;
+          }
+          "a" => {
+            let href = attrs.get("href").filter(|h| !h.is_empty()).cloned();
+            if href.is_some() {
+              b.push('[');
+            }
+            links.push(href);
+          }
+          "img" => {
+            if let Some(src) = attrs.get("src") {
+              let alt = attrs.get("alt").map(String::as_str).unwrap_or("");
+              b.push_str(&format!("![{}]({})", alt, percent_encode_spaces(src)));
+            }
+          }
+          "en-media" => {
+            if let Some(md) = media(&attrs) {
+              b.push_str(&md);
+            }
+          }
+          "en-todo" => {
+            let done = attrs.get("checked").map(|c| c == "true").unwrap_or(false);
+            b.push_str(if done { "- [x] " } else { "- [ ] " });
+          }
+          "input" if attrs.get("type").map(|t| t == "checkbox").unwrap_or(false) => {
+            b.push_str(if attrs.contains_key("checked") { "[x] " } else { "[ ] " });
+          }
+          "ul" | "ol" => {
+            if lists.is_empty() {
+              block(b, true);
+            }
+            lists.push((name == "ol", 0));
+          }
+          "li" => {
+            block(b, false);
+            let depth = lists.len().max(1) - 1;
+            let marker = match lists.last_mut() {
+              Some((true, n)) => {
+                *n += 1;
+                format!("{}. ", n)
+              }
+              _ => "- ".to_string(),
+            };
+            b.push_str(&format!("{}{}", "  ".repeat(depth), marker));
+          }
+          "blockquote" => {
+            block(b, true);
+            buffers.push(String::new());
+          }
+          "table" => {
+            block(b, true);
+            table_rows = 0;
+          }
+          "tr" => {
+            block(b, false);
+            b.push('|');
+            row_cells = 0;
+          }
+          "td" | "th" => {
+            b.push(' ');
+            row_cells += 1;
+          }
+          _ => {}
+        }
+      }
+      Token::Close(name) => {
+        if matches!(name.as_str(), "head" | "style" | "script" | "title") {
+          hidden = hidden.saturating_sub(1);
+          continue;
+        }
+        if hidden > 0 {
+          continue;
+        }
+        if name == "blockquote" && buffers.len() > 1 {
+          let inner = buffers.pop().unwrap_or_default();
+          let b = buf(&mut buffers);
+          for line in inner.trim().lines() {
+            b.push_str(&format!("> {}\n", line).replace("> \n", ">\n"));
+          }
+          b.push('\n');
+          continue;
+        }
+        let b = buf(&mut buffers);
+        match name.as_str() {
+          "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "div" => block(b, true),
+          "section" | "article" | "header" | "footer" | "figure" => block(b, false),
+          "strong" | "b" => b.push_str("**"),
+          "em" | "i" => b.push('*'),
+          "s" | "del" | "strike" => b.push_str("~~"),
+          "code" if !pre => b.push('`'),
+          "pre" => {
+            if !b.ends_with('\n') {
+              b.push('\n');
+            }
+            b.push_str("```\n\n");
+            pre = false;
+          }
+          "a" => {
+            if let Some(Some(href)) = links.pop() {
+              b.push_str(&format!("]({})", percent_encode_spaces(&href)));
+            }
+          }
+          "ul" | "ol" => {
+            lists.pop();
+            if lists.is_empty() {
+              block(b, true);
+            }
+          }
+          "td" | "th" => b.push_str(" |"),
+          "tr" => {
+            table_rows += 1;
+            // Markdown tables need a rule after the first row, header or not.
+            if table_rows == 1 {
+              b.push_str(&format!("\n|{}", " --- |".repeat(row_cells.max(1))));
+            }
+            b.push('\n');
+          }
+          "table" => block(b, true),
+          _ => {}
+        }
+      }
+    }
+  }
+
+  while buffers.len() > 1 {
+    let inner = buffers.pop().unwrap_or_default();
+    buf(&mut buffers).push_str(&inner);
+  }
+  let text = buffers.pop().unwrap_or_default();
+  let mut out = String::new();
+  let mut blank = 0;
+  for line in text.lines() {
+    let line = line.trim_end();
+    if line.is_empty() {
+      blank += 1;
+      continue;
+    }
+    if !out.is_empty() {
+      out.push_str(if blank > 0 { "\n\n" } else { "\n" });
+    }
+    blank = 0;
+    out.push_str(line);
+  }
+  out.push('\n');
+  out
+}
+
+/// Spaces and parentheses would end a markdown link target.
+fn percent_encode_spaces(s: &str) -> String {
+  s.replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
+
+// ---------------------------------------------------------------------------------------------
+// Paths
+
+/// Notion appends a 32-hex-digit id to every page and folder name: `Roadmap 1a2b…9f`.
+fn strip_notion_id(stem: &str) -> &str {
+  match stem.rsplit_once(' ') {
+    Some((name, id)) if !name.is_empty() && id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()) => name,
+    _ => stem,
+  }
+}
+
+/// `rel` made unique among `taken` and files already in the vault, by numbering the name.
+fn unique(root: &Path, rel: String, taken: &mut HashSet<String>) -> String {
+  let (stem, ext) = match rel.rfind('.') {
+    Some(i) if i > rel.rfind('/').map(|s| s + 1).unwrap_or(0) => (rel[..i].to_string(), rel[i..].to_string()),
+    _ => (rel.clone(), String::new()),
+  };
+  let mut candidate = rel;
+  let mut n = 2;
+  while taken.contains(&candidate) || crate::normalize::local_path(root, &candidate).exists() {
+    candidate = format!("{} {}{}", stem, n, ext);
+    n += 1;
+  }
+  taken.insert(candidate.clone());
+  candidate
+}
+
+/// Vault path for a path inside the export: ids dropped and every name made safe.
+fn clean_path(source: &str, notion: bool) -> String {
+  source
+    .split('/')
+    .filter(|s| !s.is_empty() && *s != "." && *s != "..")
+    .map(|seg| {
+      let (stem, ext) = match seg.rfind('.') {
+        Some(i) if i > 0 && !seg[i..].contains(' ') => seg.split_at(i),
+        _ => (seg, ""),
+      };
+      let stem = if notion { strip_notion_id(stem) } else { stem };
+      crate::names::local_name(&format!("{}{}", stem, ext), false)
+    })
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+fn is_note(source: &str) -> bool {
+  let lower = source.to_lowercase();
+  lower.ends_with(".md") || lower.ends_with(".html") || lower.ends_with(".htm")
+}
+
+fn note_rel(dest: &str, cleaned: &str) -> String {
+  let stem = cleaned
+    .strip_suffix(".html")
+    .or_else(|| cleaned.strip_suffix(".htm"))
+    .or_else(|| cleaned.strip_suffix(".md"))
+    .unwrap_or(cleaned);
+  format!("{}/{}.md", dest, stem)
+}
+
+// ---------------------------------------------------------------------------------------------
+// Notion
+
+fn read_zip<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, prefix: &str, items: &mut Vec<Item>) -> Result<(), String> {
+  for i in 0..archive.len() {
+    let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+    if entry.is_dir() {
+      continue;
+    }
+    let name = entry.name().replace('\\', "/");
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes).map_err(|e| format!("{}: {}", name, e))?;
+    // Large workspaces come as a zip of zips.
+    if name.to_lowercase().ends_with(".zip") && prefix.is_empty() {
+      let mut inner = zip::ZipArchive::new(std::io::Cursor::new(bytes)).map_err(|e| format!("{}: {}", name, e))?;
+      read_zip(&mut inner, &name, items)?;
+      continue;
+    }
+    items.push(Item { source: name, bytes });
+  }
+  Ok(())
+}
+
+/// Notes and attachments of a Notion export, with links between them pointed at their new paths.
+fn plan_notion(root: &Path, archive_path: &Path, dest: &str, summary: &mut ExternalImportSummary) -> Result<Vec<(String, Vec<u8>)>, String> {
+  let file = File::open(archive_path).map_err(|e| e.to_string())?;
+  let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("cannot read archive: {}", e))?;
+  let mut items = Vec::new();
+  read_zip(&mut archive, "", &mut items)?;
+
+  let mut taken = HashSet::new();
+  let mut new_paths: HashMap<String, String> = HashMap::new();
+  for item in &items {
+    let cleaned = clean_path(&item.source, true);
+    if cleaned.is_empty() {
+      summary.skipped.push(format!("{}: no usable name", item.source));
+      continue;
+    }
+    let rel = if is_note(&item.source) {
+      note_rel(dest, &cleaned)
+    } else {
+      format!("{}/{}/{}", ATTACHMENTS_DIR, dest, cleaned)
+    };
+    new_paths.insert(crate::normalize::nfc(&item.source), unique(root, rel, &mut taken));
+  }
+
+  let mut out = Vec::new();
+  for item in items {
+    let Some(rel) = new_paths.get(&crate::normalize::nfc(&item.source)).cloned() else { continue };
+    if !is_note(&item.source) {
+      out.push((rel, item.bytes));
+      summary.attachments += 1;
+      continue;
+    }
+    let (text, _) = crate::encoding::decode(&item.bytes);
+    let lower = item.source.to_lowercase();
+    let markdown = if lower.ends_with(".html") || lower.ends_with(".htm") {
+      html_to_markdown(&text, &|_| None)
+    } else {
+      text
+    };
+    let source_dir = dir_of(&item.source);
+    let new_dir = dir_of(&rel);
+    let markdown = rewrite_targets(&markdown, |target| {
+      let first = target.split('/').next().unwrap_or("");
+      if target.starts_with('#') || first.contains(':') {
+        return None;
+      }
+      let (path, anchor) = target.split_at(target.find('#').unwrap_or(target.len()));
+      let source = resolve(&source_dir, &percent_decode(path))?;
+      let to = new_paths.get(&crate::normalize::nfc(&source))?;
+      summary.links_rewritten += 1;
+      Some(format!("{}{}", percent_encode(&relative(&new_dir, to)), anchor))
+    });
+    out.push((rel, markdown.into_bytes()));
+    summary.notes += 1;
+  }
+  Ok(out)
+}
+
+// ---------------------------------------------------------------------------------------------
+// Evernote
+
+/// Text between `<tag ...>` and `</tag>` for each occurrence, outermost first.
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+  let open = format!("<{}", tag);
+  let close = format!("</{}>", tag);
+  let mut out = Vec::new();
+  let mut rest = xml;
+  while let Some(start) = rest.find(&open) {
+    let after = &rest[start + open.len()..];
+    // `<tag>` or `<tag attr=…>`, not `<tagname>`.
+    if !after.starts_with(['>', ' ', '\n', '\t', '/']) {
+      rest = after;
+      continue;
+    }
+    let Some(gt) = after.find('>') else { break };
+    let body = &after[gt + 1..];
+    let Some(end) = body.find(&close) else { break };
+    out.push(&body[..end]);
+    rest = &body[end + close.len()..];
+  }
+  out
+}
+
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+  elements(xml, tag).first().map(|t| {
+    let t = t.trim();
+    let t = t.strip_prefix("<![CDATA[").and_then(|t| t.strip_suffix("]]>")).unwrap_or(t);
+    decode_entities(t)
+  })
+}
+
+fn extension_for(mime: &str) -> &'static str {
+  match mime {
+    "image/png" => "png",
+    "image/jpeg" => "jpg",
+    "image/gif" => "gif",
+    "image/webp" => "webp",
+    "image/svg+xml" => "svg",
+    "application/pdf" => "pdf",
+    "text/plain" => "txt",
+    _ => "bin",
+  }
+}
+
+/// MD5, which ENEX uses to tie `<en-media hash=…>` to its resource.
+fn md5_hex(data: &[u8]) -> String {
+  const S: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20,
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15,
+    21,
+  ];
+  let k: Vec<u32> = (0..64).map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32).collect();
+  let mut h: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+  let mut msg = data.to_vec();
+  msg.push(0x80);
+  while msg.len() % 64 != 56 {
+    msg.push(0);
+  }
+  msg.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+  for chunk in msg.chunks(64) {
+    let m: Vec<u32> = chunk.chunks(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect();
+    let [mut a, mut b, mut c, mut d] = h;
+    for i in 0..64 {
+      let (f, g) = match i / 16 {
+        0 => ((b & c) | (!b & d), i),
+        1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+        2 => (b ^ c ^ d, (3 * i + 5) % 16),
+        _ => (c ^ (b | !d), (7 * i) % 16),
+      };
+      let f = f.wrapping_add(a).wrapping_add(k[i]).wrapping_add(m[g]);
+      a = d;
+      d = c;
+      c = b;
+      b = b.wrapping_add(f.rotate_left(S[i]));
+    }
+    h = [h[0].wrapping_add(a), h[1].wrapping_add(b), h[2].wrapping_add(c), h[3].wrapping_add(d)];
+  }
+  h.iter().flat_map(|w| w.to_le_bytes()).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Notes of an ENEX file, each with its resources beside it under the attachments folder.
+fn plan_evernote(root: &Path, archive_path: &Path, dest: &str, summary: &mut ExternalImportSummary) -> Result<Vec<(String, Vec<u8>)>, String> {
+  let bytes = fs::read(archive_path).map_err(|e| e.to_string())?;
+  let (xml, _) = crate::encoding::decode(&bytes);
+  let notes = elements(&xml, "note");
+  if notes.is_empty() && !xml.contains("<en-export") {
+    return Err("not an Evernote export (no <en-export>)".to_string());
+  }
+
+  let mut taken = HashSet::new();
+  let mut out = Vec::new();
+  for (i, note) in notes.iter().enumerate() {
+    let title = element_text(note, "title").filter(|t| !t.trim().is_empty()).unwrap_or_else(|| format!("Note {}", i + 1));
+    let name = crate::names::local_name(title.trim(), false);
+    let rel = unique(root, format!("{}/{}.md", dest, name), &mut taken);
+    let note_dir = dir_of(&rel);
+
+    // Resources by MD5, written next to each other in a folder named after the note.
+    let mut media: HashMap<String, (String, String)> = HashMap::new();
+    for res in elements(note, "resource") {
+      let Some(data) = elements(res, "data").first().map(|d| d.split_whitespace().collect::<String>()) else { continue };
+      let Ok(data) = STANDARD.decode(data.as_bytes()) else {
+        summary.skipped.push(format!("{}: a resource isn't valid base64", title));
+        continue;
+      };
+      let mime = element_text(res, "mime").unwrap_or_default();
+      let hash = md5_hex(&data);
+      let file_name = element_text(res, "file-name")
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| format!("{}.{}", &hash[..12], extension_for(&mime)));
+      let stem = rel.strip_suffix(".md").unwrap_or(&rel);
+      let stem = stem.strip_prefix(&format!("{}/", dest)).unwrap_or(stem);
+      let res_rel = unique(
+        root,
+        format!("{}/{}/{}/{}", ATTACHMENTS_DIR, dest, stem, crate::names::local_name(&file_name, false)),
+        &mut taken,
+      );
+      media.insert(hash, (res_rel.clone(), mime));
+      out.push((res_rel, data));
+      summary.attachments += 1;
+    }
+
+    let content = element_text(note, "content").unwrap_or_default();
+    let render_media = |attrs: &HashMap<String, String>| -> Option<String> {
+      let (path, mime) = media.get(attrs.get("hash")?.as_str())?;
+      let href = percent_encode(&relative(&note_dir, path));
+      let label = path.rsplit('/').next().unwrap_or(path);
+      Some(if mime.starts_with("image/") {
+        format!("![{}]({})", label, href)
+      } else {
+        format!("[{}]({})", label, href)
+      })
+    };
+    let body = html_to_markdown(&content, &render_media);
+    summary.links_rewritten += media.len() as u32;
+
+    let mut front = Vec::new();
+    if let Some(created) = element_text(note, "created") {
+      front.push(format!("created: {}", serde_json::json!(created)));
+    }
+    let tags: Vec<String> = elements(note, "tag").iter().map(|t| decode_entities(t.trim())).collect();
+    if !tags.is_empty() {
+      front.push(format!("tags: {}", serde_json::json!(tags)));
+    }
+    let markdown = if front.is_empty() {
+      body
+    } else {
+      format!("---\n{}\n---\n{}", front.join("\n"), body)
+    };
+    out.push((rel, markdown.into_bytes()));
+    summary.notes += 1;
+  }
+  Ok(out)
+}
+
+// ---------------------------------------------------------------------------------------------
+
+pub(crate) fn import(
+  vault_path: &str,
+  archive_path: &Path,
+  format: ExternalFormat,
+  dest_subfolder: &str,
+) -> Result<ExternalImportSummary, String> {
+  let root = Path::new(vault_path);
+  if !root.is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let dest = clean_path(dest_subfolder.trim(), false);
+  if dest.is_empty() {
+    return Err("dest_subfolder is required".to_string());
+  }
+  if crate::sync::is_ignored_rel(&dest) || dest.starts_with('.') {
+    return Err(format!("{} is reserved; pick another dest_subfolder", dest));
+  }
+
+  let mut summary = ExternalImportSummary::default();
+  let files = match format {
+    ExternalFormat::Notion => plan_notion(root, archive_path, &dest, &mut summary)?,
+    ExternalFormat::Evernote => plan_evernote(root, archive_path, &dest, &mut summary)?,
+  };
+  for (rel, bytes) in files {
+    let abs = crate::normalize::local_path(root, &rel);
+    if let Some(parent) = abs.parent() {
+      fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&abs, bytes).map_err(|e| format!("{}: {}", rel, e))?;
+  }
+  Ok(summary)
+}
+
+/// Migrates a Notion export zip or an Evernote `.enex` into `dest_subfolder` of the vault: notes
+/// become markdown, links between them follow the new paths, and attachments go under
+/// `resources/attachments/<dest_subfolder>/`. Existing files are never overwritten. The vault's
+/// watcher (or its next push) uploads the result like any other local change.
+#[tauri::command]
+pub async fn vault_import_external(
+  vault_path: String,
+  archive_path: String,
+  format: ExternalFormat,
+  dest_subfolder: String,
+) -> Result<ExternalImportSummary, String> {
+  let summary = import(&vault_path, Path::new(archive_path.trim()), format, &dest_subfolder)?;
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "external_import".to_string(),
+      path: dest_subfolder.clone(),
+      detail: format!(
+        "Imported {} notes and {} attachments from {} ({} links rewritten, {} skipped).",
+        summary.notes,
+        summary.attachments,
+        archive_path,
+        summary.links_rewritten,
+        summary.skipped.len()
+      ),
+    },
+  );
+  Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+  use std::fs;
+  use std::io::Write;
+  use std::path::PathBuf;
+
+  use base64::engine::general_purpose::STANDARD;
+  use base64::Engine;
+  use zip::write::SimpleFileOptions;
+
+  use super::{clean_path, decode_entities, html_to_markdown, import, md5_hex, ExternalFormat};
+
+  const ROADMAP_ID: &str = "0123456789abcdef0123456789abcdef";
+  const NEXT_ID: &str = "fedcba9876543210fedcba9876543210";
+
+  fn vault(label: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("diregram-external-{}-{}", label, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("vault")).unwrap();
+    dir
+  }
+
+  #[test]
+  fn md5_matches_known_digests() {
+    assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(md5_hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(md5_hex(&[b'a'; 64]), "014842d480b571495a4a0363793f7367");
+  }
+
+  #[test]
+  fn entities_decode_and_unknown_ones_stay() {
+    assert_eq!(decode_entities("a &amp; &lt;b&gt; &#233;&#x41; &bogus; & end"), "a & <b> éA &bogus; & end");
+  }
+
+  #[test]
+  fn html_becomes_markdown() {
+    let html = "<html><head><title>Hidden</title></head><body><h2>Plan</h2><p>Do <b>this</b> and \
+      <a href=\"a b.md\">that</a></p><ul><li>one</li><li>two<ol><li>sub</li></ol></li></ul>\
+      <blockquote><p>quoted</p></blockquote><pre><code>let x = 1;\n</code></pre></body></html>";
+    assert_eq!(
+      html_to_markdown(html, &|_| None),
+      "## Plan\n\nDo **this** and [that](a%20b.md)\n\n- one\n- two\n  1. sub\n\n> quoted\n\n```\nlet x = 1;\n```\n"
+    );
+  }
+
+  #[test]
+  fn notion_ids_and_unsafe_names_are_cleaned() {
+    assert_eq!(
+      clean_path(&format!("Roadmap {}/Q1: goals {}.md", ROADMAP_ID, NEXT_ID), true),
+      "Roadmap/Q1_ goals.md"
+    );
+    assert_eq!(clean_path(&format!("../a/./Roadmap {}.md", ROADMAP_ID), false), format!("a/Roadmap {}.md", ROADMAP_ID));
+  }
+
+  #[test]
+  fn notion_import_rewrites_links_and_never_overwrites() {
+    let dir = vault("notion");
+    let archive = dir.join("export.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let files: [(String, Vec<u8>); 3] = [
+      (
+        format!("Roadmap {}.md", ROADMAP_ID),
+        format!(
+          "# Roadmap\n\nSee [Next](Roadmap%20{0}/Next%20{1}.html) and ![](Roadmap%20{0}/chart.png).\n",
+          ROADMAP_ID, NEXT_ID
+        )
+        .into_bytes(),
+      ),
+      (
+        format!("Roadmap {}/Next {}.html", ROADMAP_ID, NEXT_ID),
+        format!(
+          "<html><head><title>Next</title></head><body><h1>Next</h1><p>Back to \
+           <a href=\"../Roadmap%20{}.md\">Roadmap</a></p></body></html>",
+          ROADMAP_ID
+        )
+        .into_bytes(),
+      ),
+      (format!("Roadmap {}/chart.png", ROADMAP_ID), vec![0x89, b'P', b'N', b'G']),
+    ];
+    for (name, bytes) in &files {
+      zip.start_file(name.as_str(), options).unwrap();
+      zip.write_all(bytes).unwrap();
+    }
+    zip.finish().unwrap();
+    let vp = dir.join("vault");
+    let vp = vp.to_str().unwrap();
+
+    let summary = import(vp, &archive, ExternalFormat::Notion, "Notion").unwrap();
+    assert_eq!((summary.notes, summary.attachments, summary.links_rewritten), (2, 1, 3));
+    let roadmap = fs::read_to_string(dir.join("vault/Notion/Roadmap.md")).unwrap();
+    assert_eq!(
+      roadmap,
+      "# Roadmap\n\nSee [Next](Roadmap/Next.md) and ![](../resources/attachments/Notion/Roadmap/chart.png).\n"
+    );
+    let next = fs::read_to_string(dir.join("vault/Notion/Roadmap/Next.md")).unwrap();
+    assert_eq!(next, "# Next\n\nBack to [Roadmap](../Roadmap.md)\n");
+    assert!(dir.join("vault/resources/attachments/Notion/Roadmap/chart.png").exists());
+
+    // Importing again numbers the new files instead of replacing the first import.
+    import(vp, &archive, ExternalFormat::Notion, "Notion").unwrap();
+    assert_eq!(fs::read_to_string(dir.join("vault/Notion/Roadmap.md")).unwrap(), roadmap);
+    assert!(dir.join("vault/Notion/Roadmap 2.md").exists());
+    assert!(import(vp, &archive, ExternalFormat::Notion, "resources").is_err());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn evernote_import_writes_resources_beside_the_note() {
+    let dir = vault("enex");
+    let image = vec![0x89, b'P', b'N', b'G', 1, 2, 3];
+    let enex = format!(
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<en-export><note><title>Trip &amp; plans</title>\
+       <content><![CDATA[<?xml version=\"1.0\"?><en-note><div>Pack <b>light</b></div>\
+       <en-media hash=\"{}\" type=\"image/png\"/><div><en-todo checked=\"true\"/>Tickets</div></en-note>]]></content>\
+       <created>20240101T000000Z</created><tag>travel</tag><resource><data encoding=\"base64\">{}</data>\
+       <mime>image/png</mime><resource-attributes><file-name>map.png</file-name></resource-attributes></resource>\
+       </note></en-export>",
+      md5_hex(&image),
+      STANDARD.encode(&image)
+    );
+    let archive = dir.join("notes.enex");
+    fs::write(&archive, enex).unwrap();
+    let vp = dir.join("vault");
+    let vp = vp.to_str().unwrap();
+
+    let summary = import(vp, &archive, ExternalFormat::Evernote, "Evernote").unwrap();
+    assert_eq!((summary.notes, summary.attachments, summary.links_rewritten), (1, 1, 1));
+    let note = fs::read_to_string(dir.join("vault/Evernote/Trip & plans.md")).unwrap();
+    assert_eq!(
+      note,
+      "---\ncreated: \"20240101T000000Z\"\ntags: [\"travel\"]\n---\nPack **light**\n\n\
+       ![map.png](../resources/attachments/Evernote/Trip%20&%20plans/map.png)\n\n- [x] Tickets\n"
+    );
+    let res = dir.join("vault/resources/attachments/Evernote/Trip & plans/map.png");
+    assert_eq!(fs::read(res).unwrap(), image);
+
+    fs::write(&archive, "<html>not an export</html>").unwrap();
+    assert!(import(vp, &archive, ExternalFormat::Evernote, "Evernote").is_err());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn media_without_a_resource_is_dropped() {
+    let media = HashMap::from([("hash".to_string(), "missing".to_string())]);
+    let render = |attrs: &HashMap<String, String>| (attrs == &media).then(|| "![x](x.png)".to_string());
+    assert_eq!(html_to_markdown("<div>a<en-media hash=\"other\"/></div>", &render), "a\n");
+    assert_eq!(html_to_markdown("<div>a<en-media hash=\"missing\"/></div>", &render), "a![x](x.png)\n");
+  }
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use crate::config::{read_config, write_config, GitConfig};
+
+pub(crate) use diregram_sync_core::git::*;
+
+/// Turns snapshots on or off for the vault. They start with the next pull or import.
+#[tauri::command]
+pub async fn git_configure(vault_path: String, config: GitConfig) -> Result<GitConfig, String> {
+  let mut cfg = read_config(&vault_path)?;
+  cfg.git = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
+
+/// Branch and uncommitted changes of the vault's repository (`git status`, limited to the vault).
+#[tauri::command]
+pub async fn git_status(vault_path: String) -> Result<GitStatus, String> {
+  if !PathBuf::from(&vault_path).is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  if !is_repository(&vault_path) {
+    return Ok(GitStatus::default());
+  }
+  let out = git(&vault_path, &["status", "--porcelain=v1", "-z", "--branch", "--", ".", EXCLUDE])?;
+  let mut status = GitStatus {
+    repository: true,
+    ..Default::default()
+  };
+  let mut entries = out.split('\0').filter(|e| !e.is_empty());
+  while let Some(entry) = entries.next() {
+    if let Some(branch) = entry.strip_prefix("## ") {
+      // `main...origin/main [ahead 1]`, or `No commits yet on main`.
+      let name = branch.strip_prefix("No commits yet on ").unwrap_or(branch);
+      status.branch = name.split("...").next().map(|b| b.split(' ').next().unwrap_or(b).to_string());
+      continue;
+    }
+    if entry.len() < 4 {
+      continue;
+    }
+    let code = entry[..2].to_string();
+    // Renames and copies are followed by their original path.
+    if code.starts_with('R') || code.starts_with('C') {
+      entries.next();
+    }
+    status.changes.push(GitChange {
+      status: code,
+      path: entry[3..].to_string(),
+    });
+  }
+  Ok(status)
+}
+
+/// The latest commits touching the vault, newest first (`git log`, 50 by default).
+#[tauri::command]
+pub async fn git_history(vault_path: String, limit: Option<u32>) -> Result<Vec<GitCommit>, String> {
+  if !is_repository(&vault_path) {
+    return Ok(Vec::new());
+  }
+  // Unborn branches have no log to show.
+  if git(&vault_path, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err() {
+    return Ok(Vec::new());
+  }
+  let n = format!("-n{}", limit.unwrap_or(50).max(1));
+  let out = git(&vault_path, &["log", &n, "--format=%H%x1f%an%x1f%aI%x1f%s", "--", "."])?;
+  Ok(
+    out
+      .lines()
+      .filter_map(|line| {
+        let mut f = line.split('\x1f');
+        Some(GitCommit {
+          hash: f.next()?.to_string(),
+          author: f.next()?.to_string(),
+          date: f.next()?.to_string(),
+          subject: f.next().unwrap_or("").to_string(),
+        })
+      })
+      .collect(),
+  )
+}
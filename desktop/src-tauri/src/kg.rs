@@ -0,0 +1,442 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::chunk::Fences;
+use crate::sync::{mapping_path, read_mapping};
+
+const FIND_LIMIT: usize = 100;
+/// Names shorter than this are too likely to be ordinary words.
+const MIN_MENTION_CHARS: usize = 3;
+/// Neighbourhoods and paths stop this many hops out, however far the caller asks.
+const MAX_DEPTH: u32 = 6;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KgEntity {
+  pub id: String,
+  pub entity_type: String,
+  #[serde(default)]
+  pub file_id: Option<String>,
+  #[serde(default)]
+  pub data: Value,
+  /// What the entity is called: files have a `name`, headings a `text`.
+  #[serde(default)]
+  pub label: Option<String>,
+  /// Vault-relative path of `file_id`, when it has been synced.
+  #[serde(default)]
+  pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KgEdge {
+  pub id: String,
+  pub edge_type: String,
+  pub src: String,
+  pub dst: String,
+  #[serde(default)]
+  pub data: Value,
+}
+
+/// Where an entity of the graph shows up in a note.
+#[derive(Debug, Serialize, Clone)]
+pub struct KgMention {
+  pub entity_id: String,
+  pub name: String,
+  pub entity_type: String,
+  /// Span in the note's text, in UTF-16 code units as the editor counts them; `end` is exclusive.
+  pub start: usize,
+  pub end: usize,
+}
+
+/// Some entities of the graph and the edges between them.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct KgSubgraph {
+  pub entities: Vec<KgEntity>,
+  pub edges: Vec<KgEdge>,
+}
+
+/// The knowledge graph a RAG export left in the vault (`rag/kg_entities.jsonl`,
+/// `rag/kg_edges.jsonl`).
+pub(crate) struct Graph {
+  entities: Vec<KgEntity>,
+  edges: Vec<KgEdge>,
+  by_id: HashMap<String, usize>,
+  /// Entity id -> the edges touching it, either way.
+  adjacent: HashMap<String, Vec<usize>>,
+}
+
+/// Modification times of the files a loaded graph was built from.
+type Stamp = Vec<Option<SystemTime>>;
+type Cached = (Stamp, Arc<Graph>);
+
+static GRAPHS: Lazy<Mutex<HashMap<String, Cached>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn sources(vault_path: &str) -> [PathBuf; 3] {
+  let rag = Path::new(vault_path).join("rag");
+  [rag.join("kg_entities.jsonl"), rag.join("kg_edges.jsonl"), mapping_path(vault_path)]
+}
+
+/// Rows of one export file; a missing file is an empty table, unreadable lines are skipped.
+fn read_jsonl<T: for<'de> Deserialize<'de>>(path: &Path) -> Vec<T> {
+  fs::read_to_string(path)
+    .map(|text| text.lines().filter_map(|l| serde_json::from_str(l).ok()).collect())
+    .unwrap_or_default()
+}
+
+impl Graph {
+  /// The vault's graph, re-read only when the export (or the mapping) changed since last time.
+  pub(crate) fn load(vault_path: &str) -> Result<Arc<Graph>, String> {
+    let files = sources(vault_path);
+    let stamp: Stamp = files.iter().map(|p| fs::metadata(p).and_then(|m| m.modified()).ok()).collect();
+    if stamp[0].is_none() {
+      return Err("the vault has no knowledge graph export (rag/kg_entities.jsonl); run a RAG export first".to_string());
+    }
+    let mut g = GRAPHS.lock().map_err(|_| "kg cache lock poisoned".to_string())?;
+    if let Some((s, graph)) = g.get(vault_path) {
+      if *s == stamp {
+        return Ok(graph.clone());
+      }
+    }
+    let graph = Arc::new(Self::build(vault_path, read_jsonl(&files[0]), read_jsonl(&files[1])));
+    g.insert(vault_path.to_string(), (stamp, graph.clone()));
+    Ok(graph)
+  }
+
+  fn build(vault_path: &str, mut entities: Vec<KgEntity>, edges: Vec<KgEdge>) -> Self {
+    let paths: HashMap<String, String> = read_mapping(vault_path)
+      .ok()
+      .flatten()
+      .map(|m| m.files.into_iter().map(|(rel, f)| (f.file_id, rel)).collect())
+      .unwrap_or_default();
+    for e in &mut entities {
+      e.label = ["name", "title", "label", "text"]
+        .iter()
+        .find_map(|k| e.data[k].as_str())
+        .map(str::to_string);
+      e.path = e.file_id.as_ref().and_then(|f| paths.get(f)).cloned();
+    }
+    let by_id = entities.iter().enumerate().map(|(i, e)| (e.id.clone(), i)).collect();
+    let mut adjacent: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+      adjacent.entry(edge.src.clone()).or_default().push(i);
+      if edge.dst != edge.src {
+        adjacent.entry(edge.dst.clone()).or_default().push(i);
+      }
+    }
+    Self {
+      entities,
+      edges,
+      by_id,
+      adjacent,
+    }
+  }
+
+  pub(crate) fn get(&self, id: &str) -> Option<&KgEntity> {
+    self.by_id.get(id).map(|i| &self.entities[*i])
+  }
+
+  /// The entity with id `wanted`, or else the first whose label is `wanted` (case-insensitive).
+  pub(crate) fn find(&self, wanted: &str) -> Option<&KgEntity> {
+    let wanted = wanted.trim();
+    self.get(wanted).or_else(|| {
+      let lower = wanted.to_lowercase();
+      self
+        .entities
+        .iter()
+        .find(|e| e.label.as_deref().map(str::to_lowercase).as_deref() == Some(lower.as_str()))
+    })
+  }
+
+  /// Entities whose id is `query` or whose label contains it (case-insensitive), exact labels
+  /// first, then labels starting with it; optionally only of one type.
+  pub(crate) fn search(&self, query: &str, entity_type: Option<&str>, limit: usize) -> Vec<&KgEntity> {
+    let q = query.trim().to_lowercase();
+    let mut hits: Vec<(u8, String, &KgEntity)> = self
+      .entities
+      .iter()
+      .filter(|e| entity_type.map(|t| e.entity_type == t).unwrap_or(true))
+      .filter_map(|e| {
+        let label = e.label.as_deref().unwrap_or("").to_lowercase();
+        let rank = if e.id.to_lowercase() == q || label == q {
+          0
+        } else if label.starts_with(&q) {
+          1
+        } else if label.contains(&q) {
+          2
+        } else {
+          return None;
+        };
+        Some((rank, label, e))
+      })
+      .collect();
+    hits.sort_by(|a, b| (a.0, &a.1, &a.2.id).cmp(&(b.0, &b.1, &b.2.id)));
+    hits.into_iter().take(limit).map(|(_, _, e)| e).collect()
+  }
+
+  /// Edges touching `id` (optionally of one type), with the entity at the other end.
+  pub(crate) fn edges_of<'a>(&'a self, id: &'a str, edge_type: Option<&'a str>) -> impl Iterator<Item = (&'a KgEdge, &'a str)> + 'a {
+    self
+      .adjacent
+      .get(id)
+      .into_iter()
+      .flatten()
+      .map(|i| &self.edges[*i])
+      .filter(move |e| edge_type.map(|t| e.edge_type == t).unwrap_or(true))
+      .map(move |e| (e, if e.src == id { e.dst.as_str() } else { e.src.as_str() }))
+  }
+
+  /// Rows for `ids`, in order; ids the export has no entity for get a bare placeholder.
+  fn entities_for<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> Vec<KgEntity> {
+    ids
+      .into_iter()
+      .map(|id| {
+        self.get(id).cloned().unwrap_or_else(|| KgEntity {
+          id: id.to_string(),
+          entity_type: String::new(),
+          file_id: None,
+          data: Value::Null,
+          label: None,
+          path: None,
+        })
+      })
+      .collect()
+  }
+
+  /// Everything within `depth` edges of `id`, following edges either way; `id` comes first.
+  pub(crate) fn neighborhood(&self, id: &str, depth: u32, edge_type: Option<&str>) -> KgSubgraph {
+    let mut seen: HashSet<&str> = HashSet::from([id]);
+    let mut order = vec![id];
+    let mut edges: Vec<usize> = Vec::new();
+    let mut frontier = vec![id];
+    for _ in 0..depth.min(MAX_DEPTH) {
+      let mut next = Vec::new();
+      for at in frontier {
+        for i in self.adjacent.get(at).into_iter().flatten() {
+          let e = &self.edges[*i];
+          if edge_type.map(|t| e.edge_type != t).unwrap_or(false) {
+            continue;
+          }
+          edges.push(*i);
+          let other = if e.src == at { e.dst.as_str() } else { e.src.as_str() };
+          if seen.insert(other) {
+            order.push(other);
+            next.push(other);
+          }
+        }
+      }
+      frontier = next;
+    }
+    edges.sort_unstable();
+    edges.dedup();
+    KgSubgraph {
+      entities: self.entities_for(order),
+      edges: edges.into_iter().map(|i| self.edges[i].clone()).collect(),
+    }
+  }
+
+  /// A shortest chain of edges (either way) from `a` to `b`: its entities from `a` to `b` and the
+  /// edges between them, in order.
+  pub(crate) fn path_between(&self, a: &str, b: &str) -> Option<KgSubgraph> {
+    // Entity -> the edge it was reached by, and from where.
+    let mut came_from: HashMap<&str, Option<(usize, &str)>> = HashMap::from([(a, None)]);
+    let mut queue = VecDeque::from([(a, 0u32)]);
+    while let Some((at, hops)) = queue.pop_front() {
+      if at == b {
+        break;
+      }
+      if hops >= MAX_DEPTH {
+        continue;
+      }
+      for i in self.adjacent.get(at).into_iter().flatten() {
+        let e = &self.edges[*i];
+        let other = if e.src == at { e.dst.as_str() } else { e.src.as_str() };
+        if !came_from.contains_key(other) {
+          came_from.insert(other, Some((*i, at)));
+          queue.push_back((other, hops + 1));
+        }
+      }
+    }
+    came_from.get(b)?;
+    let mut ids = vec![b];
+    let mut edges = Vec::new();
+    let mut at = b;
+    while let Some(Some((edge, from))) = came_from.get(at) {
+      edges.push(self.edges[*edge].clone());
+      ids.push(*from);
+      at = *from;
+    }
+    ids.reverse();
+    edges.reverse();
+    Some(KgSubgraph {
+      entities: self.entities_for(ids),
+      edges,
+    })
+  }
+}
+
+/// The `heading:<slug>:<occurrence>` chunk anchor of a heading entity, from its data or else its
+/// id (`heading:<file id>:<slug>:<occurrence>`).
+fn heading_anchor(e: &KgEntity) -> Option<String> {
+  if let (Some(slug), Some(occ)) = (e.data["slug"].as_str(), e.data["occurrence"].as_u64()) {
+    return Some(format!("heading:{}:{}", slug, occ));
+  }
+  let mut parts = e.id.rsplitn(3, ':');
+  let (occ, slug) = (parts.next()?, parts.next()?);
+  occ.parse::<u32>().ok().map(|occ| format!("heading:{}:{}", slug, occ))
+}
+
+/// Case-folded characters, one per input character so indexes carry over.
+fn fold(s: &str) -> Vec<char> {
+  s.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect()
+}
+
+impl Graph {
+  /// Spans of `text` naming an entity. The note's own headings (`file_id`) are found by chunk
+  /// anchor, so they follow a heading that moved; other entities by whole-word, case-insensitive
+  /// matches of their name outside code, longest name first.
+  pub(crate) fn mentions(&self, text: &str, file_id: Option<&str>) -> Vec<KgMention> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    // (line, first char, chars, entity)
+    let mut spans: Vec<(usize, usize, usize, &KgEntity)> = Vec::new();
+    if let Some(fid) = file_id {
+      let anchors: HashMap<String, usize> = crate::chunk::heading_anchors(text).into_iter().collect();
+      for e in self.entities.iter().filter(|e| e.entity_type == "noteHeading" && e.file_id.as_deref() == Some(fid)) {
+        let Some(&n) = heading_anchor(e).and_then(|a| anchors.get(&a)) else { continue };
+        let line = lines[n];
+        let body = line.trim().trim_start_matches('#').trim();
+        let Some(at) = line.find(body).filter(|_| !body.is_empty()) else { continue };
+        spans.push((n, line[..at].chars().count(), body.chars().count(), e));
+      }
+    }
+
+    let mut names: Vec<(Vec<char>, &KgEntity)> = self
+      .entities
+      .iter()
+      .filter(|e| e.entity_type != "noteHeading" && !(e.entity_type == "file" && file_id.is_some() && e.file_id.as_deref() == file_id))
+      .filter_map(|e| {
+        let label = e.label.as_deref()?;
+        let name = if e.entity_type == "file" { label.strip_suffix(".md").unwrap_or(label) } else { label };
+        let name = fold(name.trim());
+        (name.len() >= MIN_MENTION_CHARS).then_some((name, e))
+      })
+      .collect();
+    names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+
+    let mut fences = Fences::default();
+    for (n, line) in lines.iter().enumerate() {
+      if fences.toggles(line) || fences.inside() {
+        continue;
+      }
+      let chars = fold(line);
+      // Code spans and headings already found are off limits.
+      let mut taken: Vec<bool> = Vec::with_capacity(chars.len());
+      let mut in_code = false;
+      for c in &chars {
+        if *c == '`' {
+          in_code = !in_code;
+        }
+        taken.push(in_code || *c == '`');
+      }
+      for (_, start, len, _) in spans.iter().filter(|s| s.0 == n) {
+        taken[*start..start + len].iter_mut().for_each(|t| *t = true);
+      }
+      for (name, e) in &names {
+        let mut i = 0;
+        while i + name.len() <= chars.len() {
+          let end = i + name.len();
+          let bounded = (i == 0 || !chars[i - 1].is_alphanumeric()) && (end == chars.len() || !chars[end].is_alphanumeric());
+          if bounded && chars[i..end] == name[..] && !taken[i..end].contains(&true) {
+            taken[i..end].iter_mut().for_each(|t| *t = true);
+            spans.push((n, i, name.len(), e));
+            i = end;
+          } else {
+            i += 1;
+          }
+        }
+      }
+    }
+
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for line in &lines {
+      line_starts.push(offset);
+      offset += line.encode_utf16().count() + 1;
+    }
+    let utf16 = |n: usize, chars: usize| -> usize {
+      line_starts[n] + lines[n].chars().take(chars).map(char::len_utf16).sum::<usize>()
+    };
+    let mut out: Vec<KgMention> = spans
+      .into_iter()
+      .map(|(n, start, len, e)| KgMention {
+        entity_id: e.id.clone(),
+        name: e.label.clone().unwrap_or_default(),
+        entity_type: e.entity_type.clone(),
+        start: utf16(n, start),
+        end: utf16(n, start + len),
+      })
+      .collect();
+    out.sort_by_key(|m| m.start);
+    out
+  }
+}
+
+fn find_or_err<'a>(graph: &'a Graph, wanted: &str) -> Result<&'a KgEntity, String> {
+  graph.find(wanted).ok_or_else(|| format!("no entity with id or name {}", wanted.trim()))
+}
+
+/// The entity and everything within `depth` edges of it (default 1).
+#[tauri::command]
+pub async fn kg_neighbors(
+  vault_path: String,
+  entity_id: String,
+  depth: Option<u32>,
+  edge_type: Option<String>,
+) -> Result<KgSubgraph, String> {
+  let graph = Graph::load(&vault_path)?;
+  let id = find_or_err(&graph, &entity_id)?.id.clone();
+  Ok(graph.neighborhood(&id, depth.unwrap_or(1), edge_type.as_deref()))
+}
+
+#[tauri::command]
+pub async fn kg_find_entities(
+  vault_path: String,
+  query: String,
+  entity_type: Option<String>,
+  limit: Option<usize>,
+) -> Result<Vec<KgEntity>, String> {
+  let graph = Graph::load(&vault_path)?;
+  let entity_type = entity_type.filter(|t| !t.trim().is_empty());
+  Ok(
+    graph
+      .search(&query, entity_type.as_deref(), limit.unwrap_or(FIND_LIMIT).max(1))
+      .into_iter()
+      .cloned()
+      .collect(),
+  )
+}
+
+/// A shortest path between two entities, or `None` when they aren't connected.
+#[tauri::command]
+pub async fn kg_path_between(vault_path: String, a: String, b: String) -> Result<Option<KgSubgraph>, String> {
+  let graph = Graph::load(&vault_path)?;
+  let a = find_or_err(&graph, &a)?.id.clone();
+  let b = find_or_err(&graph, &b)?.id.clone();
+  Ok(graph.path_between(&a, &b))
+}
+
+/// Entities mentioned in a note, for the editor to underline and link to the graph view. Follows
+/// the latest RAG export and the note as it is on disk.
+#[tauri::command]
+pub async fn kg_mentions_for_file(vault_path: String, rel_path: String) -> Result<Vec<KgMention>, String> {
+  let rel = crate::notes::note_rel(&rel_path).ok_or("not a note path")?;
+  let bytes = fs::read(Path::new(&vault_path).join(&rel)).map_err(|e| e.to_string())?;
+  let graph = Graph::load(&vault_path)?;
+  let file_id = read_mapping(&vault_path)?.and_then(|m| m.files.get(&rel).map(|f| f.file_id.clone()));
+  Ok(graph.mentions(&crate::encoding::decode(&bytes).0, file_id.as_deref()))
+}
@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::chunk::Fences;
+use crate::links::{dir_of, percent_decode, percent_encode, relative, resolve, rewrite_targets, SCHEME};
+use crate::notes::wiki_links;
+use crate::sync::{is_markdown_path, read_mapping};
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BrokenLink {
+  /// Note the link is in, and its 1-based line.
+  pub source: String,
+  pub line: usize,
+  /// The target as written; for wiki-links, what is inside `[[...]]`.
+  pub raw: String,
+  /// `wiki`, `link` (markdown link or image) or `file_id` (`nexus://file/<id>`).
+  pub kind: String,
+  /// Vault path the link points at, when it names one.
+  pub target: Option<String>,
+  /// Trash path (`trash_rel`) of the missing file, if it was archived there.
+  pub in_trash: Option<String>,
+  /// What to write instead: the same kind of link to the existing file with the closest name.
+  pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct LinkReport {
+  pub notes: u32,
+  pub links: u32,
+  pub broken: Vec<BrokenLink>,
+}
+
+fn file_name(rel: &str) -> &str {
+  rel.rsplit('/').next().unwrap_or(rel)
+}
+
+fn note_stem(rel: &str) -> &str {
+  rel.strip_suffix(".md").or_else(|| rel.strip_suffix(".markdown")).unwrap_or(rel)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.chars().enumerate() {
+    let mut diag = row[0];
+    row[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let next = (diag + usize::from(ca != *cb)).min(row[j] + 1).min(row[j + 1] + 1);
+      diag = row[j + 1];
+      row[j + 1] = next;
+    }
+  }
+  row[b.len()]
+}
+
+/// What a vault's links can point at.
+struct Targets {
+  files: HashSet<String>,
+  /// Lowercased note path and name without `.md`, and file path and name with their extension ->
+  /// vault path, for wiki-links. The first (sorted) file wins a shared name.
+  wiki: HashMap<String, String>,
+  by_id: HashMap<String, String>,
+  /// Original path -> trash path of the newest copy in the trash.
+  trash: HashMap<String, String>,
+}
+
+impl Targets {
+  fn load(vault_path: &str) -> Self {
+    let mut rels: Vec<String> = crate::site::collect(Path::new(vault_path), vault_path).into_iter().map(|(_, rel)| rel).collect();
+    rels.sort();
+    let mut wiki = HashMap::new();
+    for rel in &rels {
+      let mut keys = vec![rel.to_lowercase(), file_name(rel).to_lowercase()];
+      if is_markdown_path(Path::new(rel)) {
+        keys.push(note_stem(rel).to_lowercase());
+        keys.push(file_name(note_stem(rel)).to_lowercase());
+      }
+      for k in keys {
+        wiki.entry(k).or_insert_with(|| rel.clone());
+      }
+    }
+    let by_id = read_mapping(vault_path)
+      .ok()
+      .flatten()
+      .map(|m| m.files.into_iter().map(|(rel, f)| (f.file_id, rel)).collect())
+      .unwrap_or_default();
+    let mut trash = HashMap::new();
+    for e in crate::trash::entries(vault_path) {
+      trash.entry(e.rel_path).or_insert(e.trash_rel);
+    }
+    Self {
+      files: rels.into_iter().collect(),
+      wiki,
+      by_id,
+      trash,
+    }
+  }
+
+  /// The existing file whose name is closest to `name` (of the same kind: notes for notes, the
+  /// same extension otherwise), if it is close enough to be a typo.
+  fn closest(&self, name: &str) -> Option<&String> {
+    let wanted = name.to_lowercase();
+    let wanted_note = is_markdown_path(Path::new(&wanted)) || !file_name(&wanted).contains('.');
+    let wanted_stem = if wanted_note { note_stem(&wanted) } else { &wanted };
+    let ext = |s: &str| file_name(s).rsplit_once('.').map(|(_, e)| e.to_string());
+    self
+      .files
+      .iter()
+      .filter(|rel| {
+        if wanted_note {
+          is_markdown_path(Path::new(rel.as_str()))
+        } else {
+          ext(&rel.to_lowercase()) == ext(&wanted)
+        }
+      })
+      .map(|rel| {
+        let have = rel.to_lowercase();
+        let have = if wanted_note { note_stem(file_name(&have)).to_string() } else { file_name(&have).to_string() };
+        (levenshtein(file_name(wanted_stem), &have), rel)
+      })
+      .filter(|(d, _)| {
+        let len = file_name(wanted_stem).chars().count();
+        *d <= (len / 3).max(2).min(len.saturating_sub(1))
+      })
+      .min()
+      .map(|(_, rel)| rel)
+  }
+
+  /// Trash path of a missing wiki-link target, matched by name.
+  fn trashed_by_name(&self, name: &str) -> Option<&String> {
+    let wanted = name.to_lowercase();
+    self
+      .trash
+      .iter()
+      .filter(|(rel, _)| {
+        let rel = rel.to_lowercase();
+        rel == wanted || file_name(&rel) == wanted || note_stem(&rel) == wanted || file_name(note_stem(&rel)) == wanted
+      })
+      .map(|(_, t)| t)
+      .min()
+  }
+}
+
+/// Wiki-links, markdown links and images in the vault's notes that point at files that aren't
+/// there, with the trash copy and a closest-name fix when there is one.
+pub(crate) fn check(vault_path: &str) -> LinkReport {
+  let targets = Targets::load(vault_path);
+  let mut report = LinkReport::default();
+  for (abs, source) in crate::notes::list(vault_path) {
+    let Ok(bytes) = std::fs::read(&abs) else { continue };
+    let text = crate::encoding::decode(&bytes).0;
+    report.notes += 1;
+    let dir = dir_of(&source);
+    let broken = |line: usize, raw: &str, kind: &str, target: Option<String>, in_trash: Option<&String>, suggestion: Option<String>| BrokenLink {
+      source: source.clone(),
+      line,
+      raw: raw.to_string(),
+      kind: kind.to_string(),
+      target,
+      in_trash: in_trash.cloned(),
+      suggestion,
+    };
+
+    let mut fences = Fences::default();
+    for (i, line) in text.lines().enumerate() {
+      if fences.toggles(line) || fences.inside() {
+        continue;
+      }
+
+      let mut raws = Vec::new();
+      rewrite_targets(line, |raw| {
+        raws.push(raw.to_string());
+        None
+      });
+      for raw in raws {
+        let path = raw.split('#').next().unwrap_or("");
+        let first = raw.split('/').next().unwrap_or("");
+        if let Some(id) = raw.strip_prefix(SCHEME) {
+          report.links += 1;
+          let target = targets.by_id.get(id.split('#').next().unwrap_or(id));
+          if !target.map(|t| targets.files.contains(t)).unwrap_or(false) {
+            let in_trash = target.and_then(|t| targets.trash.get(t));
+            report.broken.push(broken(i + 1, &raw, "file_id", target.cloned(), in_trash, None));
+          }
+          continue;
+        }
+        if path.is_empty() || raw.starts_with('/') || first.contains(':') {
+          continue;
+        }
+        report.links += 1;
+        let resolved = resolve(&dir, &percent_decode(path)).map(|r| crate::normalize::nfc(&r));
+        let exists = resolved
+          .as_ref()
+          .map(|r| targets.files.contains(r) || targets.files.contains(&format!("{}.md", r)))
+          .unwrap_or(false);
+        if !exists {
+          let in_trash = resolved.as_ref().and_then(|r| targets.trash.get(r).or_else(|| targets.trash.get(&format!("{}.md", r))));
+          let suggestion = resolved
+            .as_deref()
+            .and_then(|r| targets.closest(file_name(r)))
+            .map(|rel| format!("{}{}", percent_encode(&relative(&dir, rel)), &raw[path.len()..]));
+          report.broken.push(broken(i + 1, &raw, "link", resolved, in_trash, suggestion));
+        }
+      }
+
+      for inner in wiki_links(line) {
+        let name = inner.split('|').next().unwrap_or("").split('#').next().unwrap_or("").trim();
+        if name.is_empty() {
+          continue;
+        }
+        report.links += 1;
+        let key = name.to_lowercase();
+        if targets.wiki.contains_key(&key) || targets.wiki.contains_key(key.trim_end_matches(".md")) {
+          continue;
+        }
+        let suggestion = targets.closest(name).map(|rel| {
+          let fixed = if is_markdown_path(Path::new(rel)) { note_stem(file_name(rel)) } else { file_name(rel) };
+          format!("{}{}", fixed, &inner[inner.find(['#', '|']).unwrap_or(inner.len())..])
+        });
+        report.broken.push(broken(i + 1, &inner, "wiki", None, targets.trashed_by_name(name), suggestion));
+      }
+    }
+  }
+  report
+}
+
+/// Links in the vault's notes that lead nowhere, with suggested fixes.
+#[tauri::command]
+pub async fn vault_check_links(vault_path: String) -> Result<LinkReport, String> {
+  if !Path::new(&vault_path).is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  Ok(check(&vault_path))
+}
@@ -0,0 +1,13 @@
+use crate::config::{read_config, write_config, LinkConfig};
+
+pub(crate) use diregram_sync_core::links::*;
+
+/// Turns link rewriting on or off for the vault. Notes are converted as they are next pushed or
+/// pulled.
+#[tauri::command]
+pub async fn sync_links_configure(vault_path: String, config: LinkConfig) -> Result<LinkConfig, String> {
+  let mut cfg = read_config(&vault_path)?;
+  cfg.links = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
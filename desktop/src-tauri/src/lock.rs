@@ -0,0 +1,32 @@
+use std::fs::{self};
+
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+pub(crate) use diregram_sync_core::lock::*;
+
+#[tauri::command]
+pub async fn vault_lock_status(vault_path: String) -> Result<VaultLockStatus, String> {
+  let lock = read_lock(&vault_path);
+  Ok(VaultLockStatus {
+    held_by_us: lock.as_ref().map(is_ours).unwrap_or(false),
+    stale: lock.as_ref().map(|l| !is_ours(l) && is_stale(l)).unwrap_or(false),
+    lock,
+  })
+}
+
+/// Recovery for a lock left behind by a crashed process or another machine that is gone.
+#[tauri::command]
+pub async fn vault_lock_break(vault_path: String) -> Result<(), String> {
+  let Some(lock) = read_lock(&vault_path) else { return Ok(()) };
+  fs::remove_file(lock_path(&vault_path)).map_err(|e| e.to_string())?;
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "lock_break".to_string(),
+      path: ".diregram/lock".to_string(),
+      detail: format!("Lock held by pid {} on {} was broken manually", lock.pid, lock.hostname),
+    },
+  );
+  Ok(())
+}
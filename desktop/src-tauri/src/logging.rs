@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
+use tauri::Manager;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+const LOG_PREFIX: &str = "diregram-sync";
+const LOG_SUFFIX: &str = "log";
+/// Daily files kept; older ones are deleted as the log rotates.
+const KEEP_FILES: usize = 7;
+
+static DIR: OnceCell<PathBuf> = OnceCell::new();
+/// Keeps the background writer alive; dropping it flushes buffered lines.
+static GUARD: Lazy<Mutex<Option<WorkerGuard>>> = Lazy::new(|| Mutex::new(None));
+
+/// One line of the app log, as `logs_get_recent` returns it.
+#[derive(Debug, Serialize, Clone)]
+pub struct LogEntry {
+  pub ts: String,
+  pub level: String,
+  pub target: String,
+  pub message: String,
+  /// Vault the line was logged for, from its own fields or the enclosing sync span.
+  pub vault: Option<String>,
+  /// Remaining structured fields.
+  pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+fn level_rank(level: &str) -> Option<u8> {
+  match level.to_ascii_uppercase().as_str() {
+    "ERROR" => Some(0),
+    "WARN" => Some(1),
+    "INFO" => Some(2),
+    "DEBUG" => Some(3),
+    "TRACE" => Some(4),
+    _ => None,
+  }
+}
+
+/// Writes JSON lines to `<app data>/logs/diregram-sync.<date>.log`, rotated daily, plus readable
+/// output on stderr in debug builds. Call first in setup so later setup steps are logged too.
+/// The CLI doesn't call this: its output is the log.
+pub(crate) fn install(app: &tauri::AppHandle) {
+  let Ok(dir) = app.path().app_data_dir().map(|d| d.join("logs")) else { return };
+  let appender = match RollingFileAppender::builder()
+    .rotation(Rotation::DAILY)
+    .filename_prefix(LOG_PREFIX)
+    .filename_suffix(LOG_SUFFIX)
+    .max_log_files(KEEP_FILES)
+    .build(&dir)
+  {
+    Ok(a) => a,
+    Err(e) => {
+      eprintln!("could not open log folder {}: {}", dir.display(), e);
+      return;
+    }
+  };
+  let (writer, guard) = tracing_appender::non_blocking(appender);
+  let file_layer = tracing_subscriber::fmt::layer()
+    .json()
+    .with_current_span(true)
+    .with_span_list(false)
+    .with_writer(writer);
+  let stderr_layer = cfg!(debug_assertions).then(|| tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+  if tracing_subscriber::registry()
+    .with(LevelFilter::INFO)
+    .with(file_layer)
+    .with(stderr_layer)
+    .try_init()
+    .is_ok()
+  {
+    let _ = DIR.set(dir);
+    if let Ok(mut g) = GUARD.lock() {
+      *g = Some(guard);
+    }
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "app started");
+  }
+}
+
+/// Writes out buffered lines. Call on exit; nothing is logged to the file afterwards.
+pub(crate) fn flush() {
+  if let Ok(mut g) = GUARD.lock() {
+    g.take();
+  }
+}
+
+pub(crate) fn log_dir() -> Option<PathBuf> {
+  DIR.get().cloned()
+}
+
+/// Shows the log folder in the file manager, e.g. to attach logs to a bug report.
+pub(crate) fn open_folder() {
+  if let Some(dir) = log_dir() {
+    crate::tray::open_with_os(&dir.to_string_lossy());
+  }
+}
+
+fn parse_line(line: &str) -> Option<LogEntry> {
+  let mut v: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line).ok()?;
+  let text = |v: Option<serde_json::Value>| v.and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+  let mut fields = match v.remove("fields") {
+    Some(serde_json::Value::Object(f)) => f,
+    _ => serde_json::Map::new(),
+  };
+  let span_vault = v.get("span").and_then(|s| s.get("vault")).and_then(|s| s.as_str()).map(str::to_string);
+  Some(LogEntry {
+    ts: text(v.remove("timestamp")),
+    level: text(v.remove("level")),
+    target: text(v.remove("target")),
+    message: text(fields.remove("message")),
+    vault: fields.remove("vault").and_then(|s| s.as_str().map(str::to_string)).or(span_vault),
+    fields,
+  })
+}
+
+/// Latest log lines at `level` (default `info`) or more severe, oldest first.
+#[tauri::command]
+pub async fn logs_get_recent(level: Option<String>, limit: Option<u32>) -> Result<Vec<LogEntry>, String> {
+  let min = match level.as_deref() {
+    Some(l) => level_rank(l).ok_or_else(|| format!("unknown log level: {}", l))?,
+    None => 2,
+  };
+  let limit = limit.unwrap_or(200).max(1) as usize;
+  let Some(dir) = log_dir() else { return Ok(Vec::new()) };
+
+  // Dated names sort chronologically; read newest first until `limit` lines are found.
+  let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+    .map_err(|e| e.to_string())?
+    .filter_map(Result::ok)
+    .map(|e| e.path())
+    .filter(|p| {
+      p.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with(LOG_PREFIX) && n.ends_with(LOG_SUFFIX))
+        .unwrap_or(false)
+    })
+    .collect();
+  files.sort();
+  let mut out: Vec<LogEntry> = Vec::new();
+  for path in files.iter().rev() {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    for entry in text.lines().rev().filter_map(parse_line) {
+      if level_rank(&entry.level).map(|r| r <= min).unwrap_or(false) {
+        out.push(entry);
+        if out.len() == limit {
+          break;
+        }
+      }
+    }
+    if out.len() == limit {
+      break;
+    }
+  }
+  out.reverse();
+  Ok(out)
+}
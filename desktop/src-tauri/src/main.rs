@@ -1,9 +1,87 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-const KEYCHAIN_SERVICE: &str = "com.diregram.sync";
-
 mod sync;
 mod rag;
+mod chunk;
+mod rag_direct;
+mod embed_cache;
+mod rag_queue;
+mod archive;
+mod config;
+mod backup;
+mod trash;
+mod notify;
+mod status;
+mod tray;
+mod throttle;
+mod policy;
+mod lock;
+mod deeplink;
+mod auth;
+mod secrets;
+mod secret_file;
+mod e2ee;
+mod revisions;
+mod diff;
+mod conflicts;
+mod doctor;
+mod links;
+mod attachments;
+mod s3;
+mod site;
+mod obsidian;
+mod external;
+mod git;
+mod mirror;
+mod api;
+mod mcp;
+mod kg;
+mod duplicates;
+mod linkcheck;
+mod tags;
+mod smartfolders;
+mod routing;
+mod direction;
+mod sharing;
+mod presence;
+mod open_files;
+mod shred;
+mod device;
+mod audit;
+mod clip;
+mod daily;
+mod activity;
+mod delete_guard;
+mod scheduler;
+mod autosync;
+mod cli;
+mod logging;
+mod metrics;
+mod panics;
+mod scan;
+mod vaults;
+mod projects;
+mod bootstrap;
+
+// Engine modules the app uses as they are; the others have app modules above for their commands.
+use diregram_sync_core::{
+  clock,
+  codec,
+  delta,
+  echo,
+  encoding,
+  engine,
+  events,
+  folders,
+  host,
+  names,
+  nexusdoc,
+  normalize,
+  notes,
+  symlinks,
+  volume,
+};
+
 use sync::{
   sync_init,
   sync_initial_import,
@@ -16,37 +94,73 @@ use sync::{
   vault_write_text_file,
   sync_watch_start,
   sync_watch_stop,
+  sync_pause_all,
+  sync_resume_all,
 };
-use rag::rag_ingest_jwt;
-use tauri::{Manager, WindowEvent};
-use tauri::menu::MenuBuilder;
-use tauri::tray::TrayIconBuilder;
-
-#[tauri::command]
-fn secure_storage_set(key: String, value: String) -> Result<(), String> {
-  let entry = keyring::Entry::new(KEYCHAIN_SERVICE, &key).map_err(|e| e.to_string())?;
-  entry.set_password(&value).map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn secure_storage_get(key: String) -> Result<Option<String>, String> {
-  let entry = keyring::Entry::new(KEYCHAIN_SERVICE, &key).map_err(|e| e.to_string())?;
-  match entry.get_password() {
-    Ok(v) => Ok(Some(v)),
-    Err(keyring::Error::NoEntry) => Ok(None),
-    Err(e) => Err(e.to_string()),
-  }
-}
-
-#[tauri::command]
-fn secure_storage_remove(key: String) -> Result<(), String> {
-  let entry = keyring::Entry::new(KEYCHAIN_SERVICE, &key).map_err(|e| e.to_string())?;
-  match entry.delete_credential() {
-    Ok(()) => Ok(()),
-    Err(keyring::Error::NoEntry) => Ok(()),
-    Err(e) => Err(e.to_string()),
-  }
-}
+use rag::{rag_configure, rag_ingest_cancel, rag_ingest_jwt};
+use rag_queue::rag_ingest_status;
+use chunk::{rag_chunk_vault, rag_resolve_anchor};
+use rag_direct::rag_ingest_direct;
+use archive::{vault_export_archive, vault_import_archive};
+use site::vault_export_html;
+use obsidian::vault_export_obsidian;
+use external::vault_import_external;
+use git::{git_configure, git_history, git_status};
+use mirror::{mirror_configure, mirror_run_now, mirror_set_webdav_password};
+use backup::{backup_get_config, backup_list, backup_restore, backup_run_now, backup_set_config, backup_start, backup_stop};
+use status::sync_status;
+use throttle::sync_set_bandwidth_limit;
+use policy::sync_set_policy_override;
+use lock::{vault_lock_break, vault_lock_status};
+use deeplink::deeplink_take_pending;
+use auth::auth_login_start;
+use e2ee::{e2ee_set_enabled, e2ee_status, e2ee_unlock};
+use secrets::{
+  profile_delete,
+  profile_list,
+  secure_storage_get,
+  secure_storage_list_keys,
+  secure_storage_remove,
+  secure_storage_set,
+  secure_storage_set_passphrase,
+  secure_storage_status,
+  secure_storage_unlock,
+};
+use notify::{notification_get_prefs, notification_set_prefs};
+use revisions::{vault_fetch_remote_revision, vault_list_remote_revisions, vault_list_revisions, vault_restore_revision};
+use diff::vault_diff;
+use conflicts::{conflict_list, conflict_resolve};
+use doctor::vault_doctor;
+use daily::{daily_note_configure, daily_note_open_today};
+use activity::{sync_file_activity, sync_recent_activity};
+use delete_guard::{sync_mass_delete_pending, sync_mass_delete_resolve};
+use logging::logs_get_recent;
+use metrics::sync_metrics;
+use scan::sync_watch_configure;
+use links::sync_links_configure;
+use attachments::attachments_configure;
+use s3::attachments_set_s3_secret;
+use api::{local_api_configure, local_api_rotate_token, local_api_status};
+use clip::{clip_capture, clip_configure};
+use duplicates::vault_find_duplicates;
+use linkcheck::vault_check_links;
+use tags::{tags_configure, tags_files, tags_list};
+use smartfolders::{smart_folder_define, smart_folder_delete, smart_folder_evaluate, smart_folder_list};
+use routing::routing_configure;
+use direction::{sync_set_folder_mode, sync_set_mode};
+use sharing::sync_file_access;
+use open_files::sync_set_open_files;
+use shred::shred_configure;
+use audit::audit_export;
+use device::{device_revoke, devices_list};
+use config::{config_get, config_set, config_validate};
+use presence::{presence_list, presence_subscribe, presence_track, presence_unsubscribe};
+use kg::{kg_find_entities, kg_mentions_for_file, kg_neighbors, kg_path_between};
+use bootstrap::vault_bootstrap;
+use projects::{projects_list, sync_relink};
+use vaults::{vaults_list, vaults_register, vaults_start_all, vaults_stop_all, vaults_unregister};
+use trash::{trash_list, trash_purge, trash_restore, trash_restore_and_relink};
+use tauri::{Manager, RunEvent, WindowEvent};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -56,33 +170,25 @@ pub fn run() {
     .plugin(tauri_plugin_shell::init())
     .plugin(tauri_plugin_autostart::init(
       tauri_plugin_autostart::MacosLauncher::LaunchAgent,
-      None,
+      Some(vec![autosync::BACKGROUND_ARG]),
     ))
+    .plugin(tauri_plugin_notification::init())
     .setup(|app| {
       let handle = app.handle();
-      let menu = MenuBuilder::new(handle)
-        .text("show", "Show")
-        .separator()
-        .text("quit", "Quit")
-        .build()?;
-
-      let icon = handle.default_window_icon().cloned().unwrap();
-      TrayIconBuilder::new()
-        .icon(icon)
-        .menu(&menu)
-        .on_menu_event(|app, event| match event.id.as_ref() {
-          "show" => {
-            if let Some(w) = app.get_webview_window("main") {
-              let _ = w.show();
-              let _ = w.set_focus();
-            }
-          }
-          "quit" => {
-            std::process::exit(0);
-          }
-          _ => {}
-        })
-        .build(handle)?;
+      logging::install(handle);
+      device::install(handle);
+      panics::install();
+      secret_file::install(handle);
+      notify::install(handle);
+      tray::build(handle)?;
+      policy::start();
+      deeplink::install(handle);
+      auth::start_refresh_daemon(handle);
+      app.manage(scheduler::SyncRuntime::start());
+      vaults::install(handle);
+      autosync::install(handle);
+      api::install(handle);
+      metrics::serve_from_env();
 
       Ok(())
     })
@@ -92,11 +198,23 @@ pub fn run() {
         let _ = window.hide();
         api.prevent_close();
       }
+      if let WindowEvent::Focused(true) = event {
+        // Clicking a sync notification activates the app; route the UI to the file it was about.
+        notify::deliver_focus_target(window.app_handle());
+        // The user is looking at the app again; don't leave them waiting on an idle poll interval.
+        window.app_handle().state::<scheduler::SyncRuntime>().wake();
+      }
     })
     .invoke_handler(tauri::generate_handler![
       secure_storage_set,
       secure_storage_get,
       secure_storage_remove,
+      secure_storage_list_keys,
+      profile_list,
+      profile_delete,
+      secure_storage_status,
+      secure_storage_unlock,
+      secure_storage_set_passphrase,
       sync_init,
       sync_initial_import,
       sync_watch_start,
@@ -108,13 +226,132 @@ pub fn run() {
       rag_export_once,
       vault_ensure_dir,
       vault_write_text_file,
-      rag_ingest_jwt
+      rag_ingest_jwt,
+      rag_configure,
+      rag_ingest_status,
+      rag_ingest_cancel,
+      rag_chunk_vault,
+      rag_resolve_anchor,
+      rag_ingest_direct,
+      vault_export_archive,
+      vault_export_html,
+      vault_export_obsidian,
+      vault_import_external,
+      git_configure,
+      git_status,
+      git_history,
+      mirror_configure,
+      mirror_set_webdav_password,
+      mirror_run_now,
+      attachments_configure,
+      attachments_set_s3_secret,
+      local_api_status,
+      local_api_configure,
+      local_api_rotate_token,
+      clip_capture,
+      clip_configure,
+      kg_neighbors,
+      kg_find_entities,
+      kg_path_between,
+      kg_mentions_for_file,
+      vault_find_duplicates,
+      vault_check_links,
+      tags_list,
+      tags_files,
+      tags_configure,
+      smart_folder_define,
+      smart_folder_delete,
+      smart_folder_list,
+      smart_folder_evaluate,
+      routing_configure,
+      sync_set_mode,
+      sync_set_folder_mode,
+      sync_file_access,
+      presence_subscribe,
+      presence_unsubscribe,
+      presence_list,
+      presence_track,
+      sync_set_open_files,
+      shred_configure,
+      audit_export,
+      devices_list,
+      device_revoke,
+      config_get,
+      config_set,
+      config_validate,
+      vault_import_archive,
+      backup_get_config,
+      backup_set_config,
+      backup_start,
+      backup_stop,
+      backup_run_now,
+      backup_list,
+      backup_restore,
+      trash_list,
+      trash_restore,
+      trash_purge,
+      trash_restore_and_relink,
+      notification_get_prefs,
+      notification_set_prefs,
+      sync_status,
+      sync_pause_all,
+      sync_resume_all,
+      sync_set_bandwidth_limit,
+      sync_set_policy_override,
+      vault_lock_status,
+      vault_lock_break,
+      deeplink_take_pending,
+      auth_login_start,
+      e2ee_status,
+      e2ee_unlock,
+      e2ee_set_enabled,
+      vault_list_revisions,
+      vault_restore_revision,
+      vault_list_remote_revisions,
+      vault_fetch_remote_revision,
+      vault_diff,
+      conflict_list,
+      conflict_resolve,
+      vault_doctor,
+      daily_note_configure,
+      daily_note_open_today,
+      sync_file_activity,
+      sync_recent_activity,
+      sync_mass_delete_pending,
+      sync_mass_delete_resolve,
+      logs_get_recent,
+      sync_metrics,
+      sync_watch_configure,
+      sync_links_configure,
+      vaults_register,
+      vaults_list,
+      vaults_unregister,
+      vaults_start_all,
+      vaults_stop_all,
+      projects_list,
+      sync_relink,
+      vault_bootstrap
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| match event {
+      // Exits not started from the tray (e.g. Cmd+Q) wait for sync too.
+      RunEvent::ExitRequested { api, .. } if !app.state::<scheduler::SyncRuntime>().quitting() => {
+        api.prevent_exit();
+        scheduler::SyncRuntime::quit(app);
+      }
+      RunEvent::Exit => {
+        tauri::async_runtime::block_on(app.state::<scheduler::SyncRuntime>().shutdown());
+        logging::flush();
+      }
+      _ => {}
+    });
 }
 
 fn main() {
+  if let Some(code) = cli::main_if_requested() {
+    std::process::exit(code);
+  }
   run();
 }
 
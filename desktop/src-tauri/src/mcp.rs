@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::kg::{Graph, KgEntity};
+use crate::notes::{note_rel, LinkIndex};
+
+/// Protocol revisions this server speaks, newest first.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+const SEARCH_LIMIT: usize = 50;
+const RECENT_LIMIT: usize = 50;
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+fn tools() -> Value {
+  json!([
+    {
+      "name": "search_notes",
+      "description": "Lines of the user's notes containing the query (case-insensitive), with note path and line number.",
+      "inputSchema": {
+        "type": "object",
+        "properties": {
+          "query": { "type": "string" },
+          "limit": { "type": "integer", "minimum": 1, "description": "At most this many lines (default 50)." }
+        },
+        "required": ["query"]
+      }
+    },
+    {
+      "name": "get_note",
+      "description": "The markdown of one note, by vault-relative path, and the notes linking to it.",
+      "inputSchema": {
+        "type": "object",
+        "properties": { "path": { "type": "string", "description": "e.g. `Projects/Plan.md`" } },
+        "required": ["path"]
+      }
+    },
+    {
+      "name": "get_kg_neighbors",
+      "description": "Entities connected to one entity of the exported knowledge graph, with the edges between them. Needs a RAG export in the vault.",
+      "inputSchema": {
+        "type": "object",
+        "properties": {
+          "entity": { "type": "string", "description": "Entity id, or a name matched case-insensitively." },
+          "edge_type": { "type": "string", "description": "Only follow edges of this type." }
+        },
+        "required": ["entity"]
+      }
+    },
+    {
+      "name": "list_recent_changes",
+      "description": "Notes most recently pushed, pulled or in conflict, newest first.",
+      "inputSchema": {
+        "type": "object",
+        "properties": { "limit": { "type": "integer", "minimum": 1, "description": "Default 50." } }
+      }
+    }
+  ])
+}
+
+fn get_note(vault_path: &str, args: &Value) -> Result<Value, String> {
+  let path = args["path"].as_str().ok_or("path is required")?;
+  let rel = note_rel(path).ok_or("not a note path")?;
+  let bytes = fs::read(Path::new(vault_path).join(&rel)).map_err(|_| format!("no note at {}", rel))?;
+  let backlinks = LinkIndex::build(vault_path).backlinks(&rel);
+  Ok(json!({ "path": rel, "content": crate::encoding::decode(&bytes).0, "backlinks": backlinks }))
+}
+
+fn get_kg_neighbors(vault_path: &str, args: &Value) -> Result<Value, String> {
+  let wanted = args["entity"].as_str().ok_or("entity is required")?;
+  let graph = Graph::load(vault_path)?;
+  let entity = graph.find(wanted).ok_or_else(|| format!("no entity with id or name {}", wanted.trim()))?;
+  let describe = |e: &KgEntity| json!({ "id": e.id, "entity_type": e.entity_type, "label": e.label, "path": e.path });
+  let neighbors: Vec<Value> = graph
+    .edges_of(&entity.id, args["edge_type"].as_str())
+    .map(|(edge, other)| {
+      let direction = if edge.src == entity.id { "out" } else { "in" };
+      let other = graph.get(other).map(describe).unwrap_or_else(|| json!({ "id": other }));
+      json!({ "direction": direction, "edge_type": edge.edge_type, "entity": other })
+    })
+    .collect();
+  Ok(json!({ "entity": describe(entity), "neighbors": neighbors }))
+}
+
+fn limit(args: &Value, default: usize) -> usize {
+  args["limit"].as_u64().map(|n| n.max(1) as usize).unwrap_or(default)
+}
+
+fn call_tool(vault_path: &str, name: &str, args: &Value) -> Option<Result<Value, String>> {
+  Some(match name {
+    "search_notes" => match args["query"].as_str().filter(|q| !q.trim().is_empty()) {
+      Some(q) => Ok(json!(crate::notes::search(vault_path, q, limit(args, SEARCH_LIMIT)))),
+      None => Err("query is required".to_string()),
+    },
+    "get_note" => get_note(vault_path, args),
+    "get_kg_neighbors" => get_kg_neighbors(vault_path, args),
+    "list_recent_changes" => Ok(json!(crate::activity::recent(vault_path, limit(args, RECENT_LIMIT)))),
+    _ => return None,
+  })
+}
+
+/// The response to one request, or `None` for notifications.
+fn handle(vault_path: &str, msg: &Value) -> Option<Value> {
+  let id = msg.get("id")?.clone();
+  let params = &msg["params"];
+  let result = match msg["method"].as_str().unwrap_or("") {
+    "initialize" => {
+      let asked = params["protocolVersion"].as_str().unwrap_or("");
+      let version = PROTOCOL_VERSIONS.iter().find(|v| **v == asked).unwrap_or(&PROTOCOL_VERSIONS[0]);
+      Ok(json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "diregram", "version": env!("CARGO_PKG_VERSION") },
+      }))
+    }
+    "ping" => Ok(json!({})),
+    "tools/list" => Ok(json!({ "tools": tools() })),
+    "tools/call" => {
+      let name = params["name"].as_str().unwrap_or("");
+      match call_tool(vault_path, name, &params["arguments"]) {
+        // Tool failures are results the model gets to read, not protocol errors.
+        Some(Ok(v)) => Ok(json!({
+          "content": [{ "type": "text", "text": serde_json::to_string_pretty(&v).unwrap_or_default() }],
+          "isError": false,
+        })),
+        Some(Err(e)) => Ok(json!({ "content": [{ "type": "text", "text": e }], "isError": true })),
+        None => Err((INVALID_PARAMS, format!("unknown tool {}", name))),
+      }
+    }
+    m => Err((METHOD_NOT_FOUND, format!("unknown method {}", m))),
+  };
+  Some(match result {
+    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+    Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+  })
+}
+
+/// Serves the vault to an MCP client over stdio (newline-delimited JSON-RPC) until stdin closes.
+/// Everything is read from disk per call, so a running app's syncs show up without a restart.
+pub(crate) fn serve(vault_path: &str) -> Result<(), String> {
+  if !Path::new(vault_path).is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let stdin = std::io::stdin();
+  let mut stdout = std::io::stdout();
+  for line in stdin.lock().lines() {
+    let line = line.map_err(|e| e.to_string())?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let reply = match serde_json::from_str::<Value>(&line) {
+      Ok(msg) => handle(vault_path, &msg),
+      Err(e) => Some(json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": e.to_string() } })),
+    };
+    if let Some(reply) = reply {
+      writeln!(stdout, "{}", reply).map_err(|e| e.to_string())?;
+      stdout.flush().map_err(|e| e.to_string())?;
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,6 @@
+pub(crate) use diregram_sync_core::metrics::*;
+
+#[tauri::command]
+pub async fn sync_metrics() -> Result<SyncMetrics, String> {
+  Ok(snapshot())
+}
@@ -0,0 +1,29 @@
+use crate::config::{read_config, write_config, MirrorConfig};
+
+pub(crate) use diregram_sync_core::mirror::*;
+
+/// Replaces the vault's mirror targets. Files are mirrored from the next sync on.
+#[tauri::command]
+pub async fn mirror_configure(vault_path: String, config: MirrorConfig) -> Result<MirrorConfig, String> {
+  let mut cfg = read_config(&vault_path)?;
+  cfg.mirror = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
+
+/// Stores (or with `None`, forgets) the password of the WebDAV target at `url`.
+#[tauri::command]
+pub fn mirror_set_webdav_password(url: String, password: Option<String>) -> Result<(), String> {
+  let profile = crate::auth::current_profile();
+  let key = format!("{}{}", PASSWORD_PREFIX, url.trim_end_matches('/'));
+  match password {
+    Some(p) => crate::secrets::set(profile.as_deref(), &key, &p),
+    None => crate::secrets::remove(profile.as_deref(), &key),
+  }
+}
+
+/// Mirrors now instead of after the next sync, e.g. after adding a target.
+#[tauri::command]
+pub async fn mirror_run_now(vault_path: String) -> Result<Vec<MirrorOutcome>, String> {
+  run(&vault_path).await
+}
@@ -0,0 +1,79 @@
+use once_cell::sync::OnceCell;
+use tauri::{Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::config::{read_config, write_config, NotificationConfig};
+
+pub(crate) use diregram_sync_core::notify::*;
+
+/// Background sync runs on plain threads without an `AppHandle`; keep one around for the host and
+/// the app's own background jobs.
+static APP: OnceCell<tauri::AppHandle> = OnceCell::new();
+
+/// The engine's host in the desktop app: events go to the windows, notifications to the OS, and
+/// background jobs to the scheduler.
+struct DesktopHost(tauri::AppHandle);
+
+impl crate::host::Host for DesktopHost {
+  fn emit(&self, event: &str, payload: serde_json::Value) {
+    let _ = self.0.emit(event, payload);
+  }
+
+  fn notify(&self, title: &str, body: &str) {
+    if let Err(e) = self.0.notification().builder().title(title).body(body).show() {
+      tracing::warn!(error = %e, "could not show notification");
+    }
+  }
+
+  fn pushed(&self, vault_path: &str, rel_path: &str) {
+    crate::rag_queue::note_push(vault_path, rel_path);
+  }
+
+  fn reload_config(&self, vault_path: &str) {
+    if let Some(runtime) = self.0.try_state::<crate::scheduler::SyncRuntime>() {
+      runtime.reload_config(vault_path.to_string());
+    }
+  }
+
+  fn stop_account(
+    &self,
+    supabase_url: String,
+    owner_id: String,
+    except: String,
+  ) -> crate::host::BoxFuture<'_, Result<crate::host::Stopped, String>> {
+    let runtime = self.0.state::<crate::scheduler::SyncRuntime>();
+    Box::pin(async move { runtime.stop_account(supabase_url, owner_id, except).await })
+  }
+}
+
+pub(crate) fn install(app: &tauri::AppHandle) {
+  let _ = APP.set(app.clone());
+  crate::host::install(DesktopHost(app.clone()));
+}
+
+pub(crate) fn app() -> Option<&'static tauri::AppHandle> {
+  APP.get()
+}
+
+/// Called when the main window gains focus; forwards a recent notification's target to the UI.
+pub(crate) fn deliver_focus_target(app: &tauri::AppHandle) {
+  let Some(target) = take_focus_target() else { return };
+  if let Some(w) = app.get_webview_window("main") {
+    let _ = w.show();
+    let _ = w.set_focus();
+  }
+  let _ = app.emit("notify://focus_file", target);
+}
+
+#[tauri::command]
+pub async fn notification_get_prefs(vault_path: String) -> Result<NotificationConfig, String> {
+  Ok(read_config(&vault_path)?.notifications)
+}
+
+#[tauri::command]
+pub async fn notification_set_prefs(vault_path: String, prefs: NotificationConfig) -> Result<NotificationConfig, String> {
+  let mut cfg = read_config(&vault_path)?;
+  cfg.notifications = prefs.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(prefs)
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::site::{body, collect, prepare_dest, write};
+use crate::sync::{append_event, is_markdown_path, now_iso, read_mapping, SyncEvent};
+
+/// Left in the export so a later one knows it may replace it.
+const OBSIDIAN_MARKER: &str = ".diregram-obsidian";
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ObsidianExportOptions {
+  /// Add `diregram-id`, `diregram-kind` and, for renamed notes, `aliases` to each note's frontmatter.
+  pub frontmatter: bool,
+  /// Turn nexus-doc blocks into collapsed callouts; otherwise they stay code blocks.
+  pub callouts: bool,
+}
+
+impl Default for ObsidianExportOptions {
+  fn default() -> Self {
+    Self {
+      frontmatter: true,
+      callouts: true,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ObsidianExportSummary {
+  pub notes: u32,
+  pub files: u32,
+  pub callouts: u32,
+}
+
+/// `.obsidian` settings the export starts from: markdown links with relative paths (what the
+/// sync's link rewriting produces), links kept up to date on rename, and sync internals hidden.
+fn obsidian_config() -> Vec<(&'static str, Value)> {
+  vec![
+    (
+      ".obsidian/app.json",
+      json!({
+        "useMarkdownLinks": true,
+        "newLinkFormat": "relative",
+        "alwaysUpdateLinksOnRename": true,
+        "showFrontmatter": true,
+        "userIgnoreFilters": [".diregram/", "rag/"],
+      }),
+    ),
+    (
+      ".obsidian/core-plugins.json",
+      json!(["file-explorer", "global-search", "switcher", "graph", "backlink", "outgoing-link", "tag-pane", "page-preview", "outline"]),
+    ),
+  ]
+}
+
+/// `markdown` with `fields` (already YAML, e.g. `key: "value"`) added to its frontmatter, keeping
+/// keys it already has.
+fn with_frontmatter(markdown: &str, fields: &[(&str, String)]) -> String {
+  let (existing, rest) = match markdown.strip_prefix("---\n").and_then(|r| r.find("\n---\n").map(|e| (r, e))) {
+    Some((r, e)) => (&r[..e], body(markdown)),
+    None => ("", markdown),
+  };
+  let missing: Vec<String> = fields
+    .iter()
+    .filter(|(k, _)| !existing.lines().any(|l| l.starts_with(&format!("{}:", k))))
+    .map(|(k, v)| format!("{}: {}", k, v))
+    .collect();
+  if missing.is_empty() {
+    return markdown.to_string();
+  }
+  let mut yaml = existing.to_string();
+  for line in missing {
+    if !yaml.is_empty() {
+      yaml.push('\n');
+    }
+    yaml.push_str(&line);
+  }
+  format!("---\n{}\n---\n{}", yaml, rest)
+}
+
+/// The nexus-doc block as a collapsed callout holding the same JSON, so Obsidian shows it folded
+/// instead of as a wall of code. `None` when there is no (valid) block.
+fn to_callout(markdown: &str) -> Option<String> {
+  let (before, json, after) = crate::nexusdoc::split(markdown)?;
+  let v: Value = serde_json::from_str(json).ok()?;
+  let kind = v.get("kind").and_then(|k| k.as_str()).unwrap_or("document");
+  let pretty = serde_json::to_string_pretty(&v).ok()?;
+  let mut callout = format!("> [!abstract]- Diregram {}\n> ```json\n", kind);
+  for line in pretty.lines() {
+    callout.push_str(&format!("> {}\n", line));
+  }
+  callout.push_str("> ```");
+  Some(format!("{}{}{}", before, callout, after))
+}
+
+pub(crate) fn export(vault_path: &str, dest: &Path, options: &ObsidianExportOptions) -> Result<ObsidianExportSummary, String> {
+  let root = Path::new(vault_path);
+  if !root.is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  if dest.starts_with(root) {
+    return Err("dest_dir must be outside the vault, or the export would be synced as notes".to_string());
+  }
+  let mapping = read_mapping(vault_path).ok().flatten();
+  let files = collect(root, vault_path);
+  // Where each remote file ended up, for turning `nexus://file/<id>` links into relative ones.
+  let targets: HashMap<String, String> = mapping
+    .as_ref()
+    .map(|m| m.files.iter().map(|(rel, fm)| (fm.file_id.clone(), rel.clone())).collect())
+    .unwrap_or_default();
+
+  prepare_dest(dest, OBSIDIAN_MARKER)?;
+  let mut summary = ObsidianExportSummary::default();
+  for (abs, rel) in &files {
+    let bytes = fs::read(abs).map_err(|e| format!("{}: {}", rel, e))?;
+    if !is_markdown_path(abs) {
+      write(dest, rel, &bytes)?;
+      summary.files += 1;
+      continue;
+    }
+    let (markdown, _) = crate::encoding::decode(&bytes);
+    let mut note = crate::links::to_local(&markdown, rel, &targets);
+    if options.callouts {
+      if let Some(converted) = to_callout(&note) {
+        note = converted;
+        summary.callouts += 1;
+      }
+    }
+    if options.frontmatter {
+      if let Some(m) = &mapping {
+        let mut fields = Vec::new();
+        if let Some(fm) = m.files.get(rel) {
+          fields.push(("diregram-id", json!(fm.file_id).to_string()));
+          fields.push(("diregram-kind", json!(fm.kind).to_string()));
+        }
+        if let Some(remote) = m.remote_names.get(rel) {
+          let alias = remote.strip_suffix(".md").unwrap_or(remote);
+          fields.push(("aliases", json!([alias]).to_string()));
+        }
+        note = with_frontmatter(&note, &fields);
+      }
+    }
+    write(dest, rel, note.as_bytes())?;
+    summary.notes += 1;
+  }
+  for (rel, value) in obsidian_config() {
+    let text = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+    write(dest, rel, text.as_bytes())?;
+  }
+  Ok(summary)
+}
+
+/// Writes a copy of the vault that opens cleanly in Obsidian: frontmatter with the remote ids,
+/// nexus-doc blocks as callouts, remote links as relative ones and a `.obsidian` folder with
+/// defaults. The vault itself is left as it is.
+#[tauri::command]
+pub async fn vault_export_obsidian(
+  vault_path: String,
+  dest_dir: String,
+  options: Option<ObsidianExportOptions>,
+) -> Result<ObsidianExportSummary, String> {
+  if dest_dir.trim().is_empty() {
+    return Err("dest_dir is required".to_string());
+  }
+  let dest = PathBuf::from(dest_dir.trim());
+  let summary = export(&vault_path, &dest, &options.unwrap_or_default())?;
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "obsidian_export".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Exported {} notes and {} files to {} ({} nexus-doc blocks as callouts).",
+        summary.notes,
+        summary.files,
+        dest.display(),
+        summary.callouts
+      ),
+    },
+  );
+  Ok(summary)
+}
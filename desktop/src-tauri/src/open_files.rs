@@ -0,0 +1,17 @@
+use std::collections::HashSet;
+
+pub(crate) use diregram_sync_core::open_files::*;
+
+/// Replaces the list of files the editor has open in `vault_path` (absolute or vault-relative paths).
+/// An empty list stops the notifications for the vault.
+#[tauri::command]
+pub async fn sync_set_open_files(vault_path: String, paths: Vec<String>) -> Result<(), String> {
+  let open: HashSet<String> = paths.iter().map(|p| normalize(&vault_path, p)).filter(|p| !p.is_empty()).collect();
+  let mut guard = OPEN.lock().map_err(|_| "open files lock poisoned".to_string())?;
+  if open.is_empty() {
+    guard.remove(&vault_path);
+  } else {
+    guard.insert(vault_path, open);
+  }
+  Ok(())
+}
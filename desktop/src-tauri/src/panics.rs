@@ -0,0 +1,72 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+/// Logs every panic with its backtrace before the default hook runs. Call once, from setup, after
+/// logging is installed. Panics in sync jobs are also reported per vault (`report`) by the
+/// scheduler, which catches them.
+pub(crate) fn install() {
+  let previous = std::panic::take_hook();
+  std::panic::set_hook(Box::new(move |info| {
+    let thread = std::thread::current();
+    let location = info.location().map(|l| l.to_string()).unwrap_or_default();
+    tracing::error!(
+      thread = thread.name().unwrap_or("unnamed"),
+      location = %location,
+      backtrace = %Backtrace::force_capture(),
+      "panic: {}",
+      message(info.payload())
+    );
+    previous(info);
+  }));
+}
+
+/// The text a panic was raised with.
+pub(crate) fn message(payload: &(dyn Any + Send)) -> String {
+  if let Some(s) = payload.downcast_ref::<&str>() {
+    s.to_string()
+  } else if let Some(s) = payload.downcast_ref::<String>() {
+    s.clone()
+  } else {
+    "unknown panic".to_string()
+  }
+}
+
+/// Records a crashed background `op` ("pull", "push" or "watch") for the vault: a `panic` event,
+/// an error status in place of the run that never finished, and a notification.
+pub(crate) fn report(vault_path: &str, op: &str, detail: &str, retry_in: Duration) {
+  let text = format!(
+    "Background {} crashed ({}); restarting it in {}s.",
+    op,
+    detail,
+    retry_in.as_secs().max(1)
+  );
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "panic".to_string(),
+      path: String::new(),
+      detail: text.clone(),
+    },
+  );
+  crate::status::finish(vault_path, op, Err(&format!("sync crashed: {}", detail)));
+  crate::notify::notify(vault_path, crate::notify::NotifyKind::Crash, "Diregram sync crashed", &text, None);
+}
+
+/// Runs `fut`, turning a panic inside it into `Err(message)` so the job that ran it can be
+/// restarted instead of silently dying with its task.
+pub(crate) async fn catch_panic<F: Future>(fut: F) -> Result<F::Output, String> {
+  let mut fut = Box::pin(fut);
+  std::future::poll_fn(move |cx| match std::panic::catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(cx))) {
+    Ok(Poll::Ready(v)) => Poll::Ready(Ok(v)),
+    Ok(Poll::Pending) => Poll::Pending,
+    Err(payload) => Poll::Ready(Err(message(&*payload))),
+  })
+  .await
+}
@@ -0,0 +1,22 @@
+pub(crate) use diregram_sync_core::policy::*;
+
+/// Starts the background check. Called once from setup.
+pub(crate) fn start() {
+  tauri::async_runtime::spawn(async {
+    loop {
+      // Detection runs OS tools; keep their waits off the async workers.
+      let _ = tauri::async_runtime::spawn_blocking(refresh).await;
+      tokio::time::sleep(POLICY_CHECK_INTERVAL).await;
+    }
+  });
+}
+
+#[tauri::command]
+pub async fn sync_set_policy_override(overridden: bool) -> Result<PolicyDecision, String> {
+  {
+    let mut guard = POLICY.lock().map_err(|_| "policy lock poisoned".to_string())?;
+    *guard = decide(guard.metered, guard.power_saver, overridden);
+  }
+  crate::status::notify_changed();
+  Ok(current())
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::Emitter;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::sync::{read_mapping, SupabaseAuth};
+
+/// Phoenix drops sockets that stay silent for 60s.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+const RECONNECT_MIN: Duration = Duration::from_secs(2);
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+
+/// A teammate (or another device of this account) that has a file of the project open.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PresenceEntry {
+  pub user_id: String,
+  /// Device id, so the same account on another machine still shows.
+  #[serde(default)]
+  pub device: Option<String>,
+  #[serde(default)]
+  pub file_id: Option<String>,
+  /// Vault-relative path of `file_id`, when the subscribing vault has it mapped.
+  #[serde(default)]
+  pub path: Option<String>,
+  #[serde(default)]
+  pub online_at: Option<String>,
+}
+
+/// Emitted as `presence://changed` whenever someone joins, leaves or switches files.
+#[derive(Debug, Serialize, Clone)]
+pub struct PresenceChanged {
+  pub project_folder_id: String,
+  pub entries: Vec<PresenceEntry>,
+}
+
+struct Channel {
+  vault_path: String,
+  /// The subscribing account; its own entry from this device is left out of the list.
+  owner_id: String,
+  /// Presence key -> metas, as last reported by the server.
+  state: HashMap<String, Vec<PresenceEntry>>,
+  /// What this device announces; resent after every reconnect.
+  tracked: Option<Value>,
+  outbox: mpsc::UnboundedSender<Value>,
+  task: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// Project folder id -> live presence channel.
+static CHANNELS: Lazy<Mutex<HashMap<String, Channel>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn topic(project_folder_id: &str) -> String {
+  format!("realtime:presence-{}", project_folder_id)
+}
+
+fn socket_url(auth: &SupabaseAuth) -> String {
+  let base = auth.supabase_url.trim_end_matches('/');
+  let base = base.strip_prefix("https://").map(|s| format!("wss://{}", s)).unwrap_or_else(|| {
+    base.strip_prefix("http://").map(|s| format!("ws://{}", s)).unwrap_or_else(|| base.to_string())
+  });
+  format!("{}/realtime/v1/websocket?apikey={}&vsn=1.0.0", base, auth.supabase_anon_key)
+}
+
+fn frame(topic: &str, event: &str, payload: Value, r: u64) -> Value {
+  json!({ "topic": topic, "event": event, "payload": payload, "ref": r.to_string() })
+}
+
+fn metas(v: &Value) -> Vec<PresenceEntry> {
+  v.get("metas")
+    .and_then(|m| m.as_array())
+    .map(|a| a.iter().filter_map(|m| serde_json::from_value(m.clone()).ok()).collect())
+    .unwrap_or_default()
+}
+
+/// Others' entries, with paths resolved against the vault's mapping.
+fn entries_of(project_folder_id: &str) -> Vec<PresenceEntry> {
+  let Ok(guard) = CHANNELS.lock() else { return Vec::new() };
+  let Some(ch) = guard.get(project_folder_id) else { return Vec::new() };
+  let paths: HashMap<String, String> = read_mapping(&ch.vault_path)
+    .ok()
+    .flatten()
+    .map(|m| m.files.into_iter().map(|(rel, f)| (f.file_id, rel)).collect())
+    .unwrap_or_default();
+  let mut out: Vec<PresenceEntry> = ch
+    .state
+    .values()
+    .flatten()
+    .filter(|e| e.user_id != ch.owner_id || e.device.as_deref() != Some(crate::device::id().as_str()))
+    .map(|e| {
+      let mut e = e.clone();
+      e.path = e.file_id.as_ref().and_then(|id| paths.get(id).cloned());
+      e
+    })
+    .collect();
+  out.sort_by(|a, b| (&a.path, &a.user_id).cmp(&(&b.path, &b.user_id)));
+  out
+}
+
+fn apply(project_folder_id: &str, event: &str, payload: &Value) -> bool {
+  let Ok(mut guard) = CHANNELS.lock() else { return false };
+  let Some(ch) = guard.get_mut(project_folder_id) else { return false };
+  let before = ch.state.clone();
+  match event {
+    "presence_state" => {
+      ch.state = payload
+        .as_object()
+        .map(|o| o.iter().map(|(k, v)| (k.clone(), metas(v))).collect())
+        .unwrap_or_default();
+    }
+    "presence_diff" => {
+      for (k, _) in payload.get("leaves").and_then(|v| v.as_object()).into_iter().flatten() {
+        ch.state.remove(k);
+      }
+      for (k, v) in payload.get("joins").and_then(|v| v.as_object()).into_iter().flatten() {
+        ch.state.insert(k.clone(), metas(v));
+      }
+    }
+    _ => return false,
+  }
+  ch.state != before
+}
+
+fn emit_changed(project_folder_id: &str) {
+  let Some(app) = crate::notify::app() else { return };
+  let _ = app.emit(
+    "presence://changed",
+    PresenceChanged {
+      project_folder_id: project_folder_id.to_string(),
+      entries: entries_of(project_folder_id),
+    },
+  );
+}
+
+/// One socket session: join, re-track, then relay frames until the socket drops.
+async fn session(
+  project_folder_id: &str,
+  auth: &SupabaseAuth,
+  outbox: &mut mpsc::UnboundedReceiver<Value>,
+) -> Result<(), String> {
+  let (ws, _) = tokio_tungstenite::connect_async(socket_url(auth)).await.map_err(|e| e.to_string())?;
+  let (mut tx, mut rx) = ws.split();
+  let topic = topic(project_folder_id);
+  let mut r = 1u64;
+  let join = json!({
+    "config": {
+      "broadcast": { "self": false },
+      "presence": { "key": format!("{}:{}", auth.owner_id, crate::device::id()) },
+    },
+    "access_token": auth.access_token,
+  });
+  tx.send(Message::Text(frame(&topic, "phx_join", join, r).to_string().into()))
+    .await
+    .map_err(|e| e.to_string())?;
+  let tracked = CHANNELS.lock().ok().and_then(|g| g.get(project_folder_id).and_then(|c| c.tracked.clone()));
+  if let Some(meta) = tracked {
+    r += 1;
+    let track = json!({ "type": "presence", "event": "track", "payload": meta });
+    tx.send(Message::Text(frame(&topic, "presence", track, r).to_string().into()))
+      .await
+      .map_err(|e| e.to_string())?;
+  }
+  let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+  loop {
+    tokio::select! {
+      _ = heartbeat.tick() => {
+        r += 1;
+        tx.send(Message::Text(frame("phoenix", "heartbeat", json!({}), r).to_string().into()))
+          .await
+          .map_err(|e| e.to_string())?;
+      }
+      Some(payload) = outbox.recv() => {
+        r += 1;
+        tx.send(Message::Text(frame(&topic, "presence", payload, r).to_string().into()))
+          .await
+          .map_err(|e| e.to_string())?;
+      }
+      msg = rx.next() => {
+        let text = match msg {
+          Some(Ok(Message::Text(t))) => t,
+          Some(Ok(Message::Close(_))) | None => return Err("presence socket closed".to_string()),
+          Some(Ok(_)) => continue,
+          Some(Err(e)) => return Err(e.to_string()),
+        };
+        let Ok(v) = serde_json::from_str::<Value>(text.as_str()) else { continue };
+        if v.get("topic").and_then(|t| t.as_str()) != Some(topic.as_str()) {
+          continue;
+        }
+        let event = v.get("event").and_then(|e| e.as_str()).unwrap_or("");
+        if event == "phx_close" || event == "phx_error" {
+          return Err(format!("presence channel {}", event));
+        }
+        if apply(project_folder_id, event, v.get("payload").unwrap_or(&Value::Null)) {
+          emit_changed(project_folder_id);
+        }
+      }
+    }
+  }
+}
+
+async fn run(project_folder_id: String, auth: SupabaseAuth, mut outbox: mpsc::UnboundedReceiver<Value>) {
+  let mut backoff = RECONNECT_MIN;
+  loop {
+    // Tokens rotate while the channel is open; join with the freshest ones.
+    let auth = crate::auth::latest(&auth);
+    let started = Instant::now();
+    let res = session(&project_folder_id, &auth, &mut outbox).await;
+    // Whoever was present is unknown until the next `presence_state`.
+    let cleared = CHANNELS
+      .lock()
+      .ok()
+      .and_then(|mut g| g.get_mut(&project_folder_id).map(|ch| !std::mem::take(&mut ch.state).is_empty()))
+      .unwrap_or(false);
+    if cleared {
+      emit_changed(&project_folder_id);
+    }
+    if let Err(e) = res {
+      tracing::debug!(project_folder_id = %project_folder_id, error = %e, "presence socket dropped");
+    }
+    // A channel that stayed up a while is not flapping; retry quickly.
+    if started.elapsed() > RECONNECT_MAX {
+      backoff = RECONNECT_MIN;
+    }
+    tokio::time::sleep(backoff).await;
+    backoff = (backoff * 2).min(RECONNECT_MAX);
+  }
+}
+
+/// Joins the project's presence channel. Idempotent; `presence://changed` fires as people come and go.
+#[tauri::command]
+pub async fn presence_subscribe(vault_path: String, project_folder_id: String, auth: SupabaseAuth) -> Result<(), String> {
+  let mut guard = CHANNELS.lock().map_err(|_| "presence lock poisoned".to_string())?;
+  if let Some(ch) = guard.get_mut(&project_folder_id) {
+    ch.vault_path = vault_path;
+    ch.owner_id = auth.owner_id;
+    return Ok(());
+  }
+  let (outbox, rx) = mpsc::unbounded_channel();
+  let owner_id = auth.owner_id.clone();
+  let task = tauri::async_runtime::spawn(run(project_folder_id.clone(), auth, rx));
+  guard.insert(
+    project_folder_id,
+    Channel {
+      vault_path,
+      owner_id,
+      state: HashMap::new(),
+      tracked: None,
+      outbox,
+      task,
+    },
+  );
+  Ok(())
+}
+
+#[tauri::command]
+pub async fn presence_unsubscribe(project_folder_id: String) -> Result<(), String> {
+  let removed = CHANNELS.lock().map_err(|_| "presence lock poisoned".to_string())?.remove(&project_folder_id);
+  if let Some(ch) = removed {
+    ch.task.abort();
+  }
+  Ok(())
+}
+
+/// Who else has which file of the project open, as of the last presence update.
+#[tauri::command]
+pub async fn presence_list(project_folder_id: String) -> Result<Vec<PresenceEntry>, String> {
+  Ok(entries_of(&project_folder_id))
+}
+
+/// Announces the file this device is editing (`None` when it closes it) to the project's teammates.
+#[tauri::command]
+pub async fn presence_track(project_folder_id: String, auth: SupabaseAuth, file_id: Option<String>) -> Result<(), String> {
+  let mut guard = CHANNELS.lock().map_err(|_| "presence lock poisoned".to_string())?;
+  let ch = guard
+    .get_mut(&project_folder_id)
+    .ok_or_else(|| "not subscribed to this project's presence".to_string())?;
+  let meta = serde_json::to_value(PresenceEntry {
+    user_id: auth.owner_id,
+    device: Some(crate::device::id()),
+    file_id,
+    path: None,
+    online_at: Some(crate::sync::now_iso()),
+  })
+  .map_err(|e| e.to_string())?;
+  ch.tracked = Some(meta.clone());
+  let _ = ch.outbox.send(json!({ "type": "presence", "event": "track", "payload": meta }));
+  Ok(())
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scheduler::SyncRuntime;
+use crate::sync::{
+  append_event, archive_file_to_trash, checksum_matches, compute_subtree_folder_ids, conflict_copy_path,
+  fetch_file_meta_in_folders, fetch_files_updated_since, local_file_rel, local_folder_rel, now_iso, read_mapping, sha256_hex, write_mapping,
+  write_synced, FileMappingV1, FolderNode, RemoteFileMetaRow, SupabaseAuth, SyncEvent, SyncMappingV1,
+};
+
+/// A top-level folder the account can link a vault to.
+#[derive(Debug, Serialize, Clone)]
+pub struct ProjectSummary {
+  pub id: String,
+  pub name: String,
+  /// Files and folders anywhere below the project.
+  pub file_count: u64,
+  pub folder_count: u64,
+  /// Newest `updated_at` of the project, its folders and files.
+  pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RelinkStrategy {
+  /// Keep the vault's files and link those the new project also has (same path) to its copies.
+  /// Where the contents differ, the remote version is written beside the local file, which is
+  /// pushed over it. Everything else is pushed or pulled as new.
+  Migrate,
+  /// Move the vault's synced notes to the trash; the next pull fills the vault from the new
+  /// project. Notes never synced stay and are pushed.
+  Reset,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RelinkReport {
+  pub project_folder_id: String,
+  /// Local files linked to a remote file with the same path.
+  pub linked: u32,
+  /// Of those, files whose remote version differed and was written beside them.
+  pub conflicts: u32,
+  /// Notes moved to the trash by `reset`.
+  pub archived: u32,
+}
+
+fn newest(a: Option<String>, b: Option<&String>) -> Option<String> {
+  match (a, b) {
+    (Some(a), Some(b)) if crate::clock::is_after(b, &a) => Some(b.clone()),
+    (a, b) => a.or_else(|| b.cloned()),
+  }
+}
+
+/// The account's projects (folders without a parent), newest first, for the vault link picker.
+#[tauri::command]
+pub async fn projects_list(auth: SupabaseAuth) -> Result<Vec<ProjectSummary>, String> {
+  let client = reqwest::Client::new();
+  let mut auth = auth;
+  let folders = crate::folders::fetch_account(&client, &mut auth).await?;
+  let mut project_of: HashMap<String, String> = HashMap::new();
+  let mut projects: Vec<ProjectSummary> = Vec::new();
+  for p in folders.iter().filter(|f| f.parent_id.is_none()) {
+    let subtree = compute_subtree_folder_ids(&p.id, &folders);
+    projects.push(ProjectSummary {
+      id: p.id.clone(),
+      name: p.name.clone(),
+      file_count: 0,
+      folder_count: subtree.len().saturating_sub(1) as u64,
+      updated_at: None,
+    });
+    for id in subtree {
+      project_of.insert(id, p.id.clone());
+    }
+  }
+  let index: HashMap<String, usize> = projects.iter().enumerate().map(|(i, p)| (p.id.clone(), i)).collect();
+  for f in &folders {
+    if let Some(&i) = project_of.get(&f.id).and_then(|p| index.get(p)) {
+      projects[i].updated_at = newest(projects[i].updated_at.take(), f.updated_at.as_ref());
+    }
+  }
+
+  let folder_ids: Vec<String> = project_of.keys().cloned().collect();
+  for file in fetch_file_meta_in_folders(&client, &mut auth, &folder_ids).await? {
+    let Some(&i) = file.folder_id.as_ref().and_then(|f| project_of.get(f)).and_then(|p| index.get(p)) else {
+      continue;
+    };
+    projects[i].file_count += 1;
+    projects[i].updated_at = newest(projects[i].updated_at.take(), file.updated_at.as_ref());
+  }
+  projects.sort_by(|a, b| match (&a.updated_at, &b.updated_at) {
+    (Some(x), Some(y)) if x != y => {
+      if crate::clock::is_after(x, y) {
+        std::cmp::Ordering::Less
+      } else {
+        std::cmp::Ordering::Greater
+      }
+    }
+    _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+  });
+  Ok(projects)
+}
+
+/// The mapping a vault starts from when linked to `project_folder_id`: what the vault knew about
+/// its old project is dropped, the settings it carried are kept.
+fn fresh_mapping(old: &SyncMappingV1, project_folder_id: &str) -> SyncMappingV1 {
+  let now = now_iso();
+  let mut folders = HashMap::new();
+  folders.insert(String::new(), project_folder_id.to_string());
+  SyncMappingV1 {
+    project_folder_id: project_folder_id.to_string(),
+    updated_at: now,
+    last_pull_at: String::new(),
+    last_rag_export_at: String::new(),
+    last_rag_ingest: None,
+    folders,
+    files: HashMap::new(),
+    resources: HashMap::new(),
+    trashed: HashMap::new(),
+    pending_mass_delete: None,
+    remote_head: None,
+    remote_names: HashMap::new(),
+    attachments: HashMap::new(),
+    objects: HashMap::new(),
+    attachment_store: None,
+    routed: HashMap::new(),
+    ..old.clone()
+  }
+}
+
+/// Links local files to the new project's files at the same paths.
+async fn link_by_path(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  mapping: &mut SyncMappingV1,
+  report: &mut RelinkReport,
+) -> Result<(), String> {
+  let project_folder_id = mapping.project_folder_id.clone();
+  let folders = crate::folders::fetch_subtree(client, auth, &project_folder_id).await?;
+  let folder_ids = compute_subtree_folder_ids(&project_folder_id, &folders);
+  let folders_by_id: HashMap<String, FolderNode> = folders.into_iter().map(|f| (f.id.clone(), f)).collect();
+  for id in &folder_ids {
+    if let Some(rel) = crate::names::local_folder(mapping, &project_folder_id, id, &folders_by_id) {
+      mapping.folders.insert(rel, id.clone());
+    }
+  }
+
+  let root = Path::new(vault_path);
+  let scope = crate::vaults::SyncScope::load(vault_path);
+  let rows = fetch_files_updated_since(client, auth, &folder_ids, "1970-01-01T00:00:00Z").await?;
+  let metas: Vec<RemoteFileMetaRow> = rows
+    .iter()
+    .map(|r| RemoteFileMetaRow {
+      id: r.id.clone(),
+      name: r.name.clone(),
+      folder_id: r.folder_id.clone(),
+      updated_at: r.updated_at.clone(),
+    })
+    .collect();
+  for rf in rows {
+    let folder_id = rf.folder_id.clone().unwrap_or_else(|| project_folder_id.clone());
+    if !folders_by_id.contains_key(&folder_id) && folder_id != project_folder_id {
+      continue;
+    }
+    let folder_rel = local_folder_rel(mapping, &project_folder_id, &folder_id, &folders_by_id);
+    let rel = local_file_rel(&folder_rel, &folder_id, &rf.id, &rf.name, &project_folder_id, &metas);
+    let abs_path = crate::normalize::local_path(root, &rel);
+    // Remote-only files are left for the next pull, as are rows that didn't download intact.
+    let Ok(local_bytes) = fs::read(&abs_path) else { continue };
+    if !scope.admits(&rel) || !checksum_matches(&rf) {
+      continue;
+    }
+    let (remote_content, _) = crate::codec::decode(&project_folder_id, rf.content.as_deref().unwrap_or(""))?;
+    let local_hash = sha256_hex(&local_bytes);
+    let remote_hash = sha256_hex(remote_content.as_bytes());
+    if local_hash != remote_hash {
+      let conflict_path = conflict_copy_path(&abs_path, "conflict");
+      write_synced(&conflict_path, &remote_content).map_err(|e| e.to_string())?;
+      let _ = append_event(
+        vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "conflict".to_string(),
+          path: rel.clone(),
+          detail: format!("Relinked to a project whose copy differs. Wrote {}", conflict_path.display()),
+        },
+      );
+      report.conflicts += 1;
+    }
+    crate::names::remember(mapping, &rel, &rf.name);
+    let rev = rf.rev.unwrap_or(0);
+    // Recorded as last synced at the remote version, so a differing local file is pushed over it.
+    mapping.files.insert(
+      rel,
+      FileMappingV1 {
+        owner_id: rf.owner_id.clone(),
+        ..FileMappingV1::synced(
+          rf.id.clone(),
+          folder_id,
+          rf.kind.clone().unwrap_or_else(|| "note".to_string()),
+          remote_hash,
+          rf.updated_at.clone().unwrap_or_default(),
+          rev,
+        )
+      },
+    );
+    report.linked += 1;
+  }
+  Ok(())
+}
+
+async fn relink(
+  vault_path: &str,
+  project_folder_id: &str,
+  strategy: RelinkStrategy,
+  auth: SupabaseAuth,
+) -> Result<RelinkReport, String> {
+  let old = read_mapping(vault_path)?.ok_or_else(|| "This vault isn't linked to a project yet.".to_string())?;
+  if old.project_folder_id == project_folder_id {
+    return Err("This vault is already linked to that project.".to_string());
+  }
+  let mut mapping = fresh_mapping(&old, project_folder_id);
+  let mut report = RelinkReport {
+    project_folder_id: project_folder_id.to_string(),
+    ..Default::default()
+  };
+  match strategy {
+    RelinkStrategy::Migrate => {
+      let client = reqwest::Client::new();
+      let mut auth = auth;
+      link_by_path(&client, &mut auth, vault_path, &mut mapping, &mut report).await?;
+    }
+    RelinkStrategy::Reset => {
+      for rel in old.files.keys().chain(old.resources.keys()) {
+        if archive_file_to_trash(vault_path, rel)?.is_some() {
+          report.archived += 1;
+        }
+      }
+    }
+  }
+  write_mapping(vault_path, &mapping)?;
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "relink".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Relinked from project {} to {} ({:?}): {} linked, {} conflicts, {} archived.",
+        old.project_folder_id, project_folder_id, strategy, report.linked, report.conflicts, report.archived
+      ),
+    },
+  );
+  Ok(report)
+}
+
+/// Links the vault to another project, replacing `.diregram/sync.json` as `strategy` says.
+/// The vault's background sync is stopped first; start it again for the new project.
+#[tauri::command]
+pub async fn sync_relink(
+  scheduler: tauri::State<'_, SyncRuntime>,
+  vault_path: String,
+  new_project_folder_id: String,
+  strategy: RelinkStrategy,
+  auth: SupabaseAuth,
+) -> Result<RelinkReport, String> {
+  if new_project_folder_id.trim().is_empty() {
+    return Err("new_project_folder_id is required".to_string());
+  }
+  for _ in 0..scheduler.stop_vault(vault_path.clone()).await? {
+    crate::lock::release(&vault_path);
+  }
+  crate::status::end_session(&vault_path);
+  crate::autosync::forget(&vault_path);
+
+  crate::lock::acquire(&vault_path)?;
+  let res = {
+    let _mapping = crate::lock::mapping(&vault_path).await;
+    relink(&vault_path, &new_project_folder_id, strategy, auth).await
+  };
+  crate::lock::release(&vault_path);
+  if res.is_ok() {
+    crate::vaults::relinked(&vault_path, &new_project_folder_id);
+  }
+  res
+}
@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::{chunk_vault, ChunkOptions, LocalChunk};
+use crate::embed_cache::EmbeddingCache;
+use crate::sync::{
+  append_event, fetch_one_rag_project, now_iso, rest_base, send_with_refresh, sha256_hex, SupabaseAuth, SyncEvent,
+};
+
+const EMBED_BATCH_SIZE: usize = 48;
+const UPSERT_BATCH_SIZE: usize = 200;
+const EMBED_MAX_CHARS: usize = 8000;
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RagDirectIngestRequest {
+  pub vault_path: String,
+  pub project_folder_id: String,
+  pub auth: SupabaseAuth,
+  pub openai_api_key: String,
+  #[serde(default)]
+  pub embedding_model: Option<String>,
+  #[serde(default)]
+  pub chunk_options: Option<ChunkOptions>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RagDirectIngestSummary {
+  pub chunks: u32,
+  pub entities: u32,
+  pub edges: u32,
+  /// Chunks from files that have no remote row yet (push first to include them).
+  pub skipped_unmapped: u32,
+  pub public_project_id: String,
+  /// Chunks whose embedding came from the cache (unchanged since an earlier ingest), and texts
+  /// sent to the embedding model.
+  #[serde(default)]
+  pub embeddings_reused: u32,
+  #[serde(default)]
+  pub embeddings_computed: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+  data: Vec<EmbeddingRow>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingRow {
+  embedding: Vec<f32>,
+}
+
+async fn embed_texts(
+  client: &reqwest::Client,
+  api_key: &str,
+  model: &str,
+  texts: &[String],
+) -> Result<Vec<Vec<f32>>, String> {
+  let input: Vec<String> = texts.iter().map(|t| t.chars().take(EMBED_MAX_CHARS).collect()).collect();
+  let res = crate::throttle::send(
+    client
+      .post("https://api.openai.com/v1/embeddings")
+      .timeout(std::time::Duration::from_secs(60))
+      .header("authorization", format!("Bearer {}", api_key))
+      .json(&serde_json::json!({ "model": model, "input": input })),
+  )
+  .await
+  .map_err(|e| format!("embeddings request failed: {}", e))?;
+  let status = res.status();
+  if !status.is_success() {
+    let text = res.text().await.unwrap_or_default();
+    return Err(format!("OpenAI embeddings failed (HTTP {}): {}", status.as_u16(), text));
+  }
+  let json: EmbeddingResponse = res.json().await.map_err(|e| e.to_string())?;
+  if json.data.len() != input.len() {
+    return Err("OpenAI embeddings: unexpected response shape".to_string());
+  }
+  Ok(json.data.into_iter().map(|d| d.embedding).collect())
+}
+
+/// Embeddings for `chunks`, in order. Only texts the cache doesn't have go to the model, each once.
+async fn embed_chunks(
+  client: &reqwest::Client,
+  api_key: &str,
+  model: &str,
+  chunks: &[LocalChunk],
+  cache: &mut EmbeddingCache,
+) -> Result<Vec<Vec<f32>>, String> {
+  let mut seen: HashSet<&str> = HashSet::new();
+  let missing: Vec<&LocalChunk> = chunks
+    .iter()
+    .filter(|c| !cache.contains(&c.hash) && seen.insert(c.hash.as_str()))
+    .collect();
+  cache.reused = chunks.iter().filter(|c| !seen.contains(c.hash.as_str())).count() as u32;
+  for batch in missing.chunks(EMBED_BATCH_SIZE) {
+    let texts: Vec<String> = batch.iter().map(|c| c.text.clone()).collect();
+    let out = embed_texts(client, api_key, model, &texts).await?;
+    for (c, emb) in batch.iter().zip(out) {
+      cache.insert(&c.hash, emb);
+      cache.computed += 1;
+    }
+  }
+  Ok(chunks.iter().map(|c| cache.get(&c.hash).unwrap_or_default()).collect())
+}
+
+async fn upsert_rows(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  table: &str,
+  rows: &[serde_json::Value],
+) -> Result<(), String> {
+  let mut url = reqwest::Url::parse(&format!("{}/{}", rest_base(auth), table)).map_err(|e| e.to_string())?;
+  url.query_pairs_mut().append_pair("on_conflict", "owner_id,id");
+  let table_name = table.to_string();
+  for batch in rows.chunks(UPSERT_BATCH_SIZE) {
+    send_with_refresh(
+      client,
+      auth,
+      || {
+        client
+          .post(url.clone())
+          .header("Prefer", "resolution=merge-duplicates,return=minimal")
+          .json(&batch)
+      },
+      |res| {
+        let table_name = table_name.clone();
+        Box::pin(async move {
+          if !res.status().is_success() {
+            let status = res.status();
+            let text = res.text().await.unwrap_or_default();
+            return Err(format!("{} upsert failed: HTTP {}: {}", table_name, status, text));
+          }
+          Ok(())
+        })
+      },
+    )
+    .await?;
+  }
+  Ok(())
+}
+
+async fn delete_project_rows(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  table: &str,
+  project_folder_id: &str,
+) -> Result<(), String> {
+  let mut url = reqwest::Url::parse(&format!("{}/{}", rest_base(auth), table)).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("owner_id", &format!("eq.{}", auth.owner_id));
+    q.append_pair("project_folder_id", &format!("eq.{}", project_folder_id));
+  }
+  let table_name = table.to_string();
+  send_with_refresh(
+    client,
+    auth,
+    || client.delete(url.clone()),
+    |res| {
+      let table_name = table_name.clone();
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("{} delete failed: HTTP {}", table_name, res.status()));
+        }
+        Ok(())
+      })
+    },
+  )
+  .await
+}
+
+/// Builds the minimal file/heading graph the server export would produce for plain notes.
+fn graph_rows_from_chunks(
+  chunks: &[LocalChunk],
+  owner_id: &str,
+  project_folder_id: &str,
+) -> (Vec<serde_json::Value>, Vec<serde_json::Value>) {
+  let mut entities: Vec<serde_json::Value> = Vec::new();
+  let mut edges: Vec<serde_json::Value> = Vec::new();
+  let mut seen_files: HashSet<String> = HashSet::new();
+
+  for c in chunks {
+    let Some(file_id) = c.file_id.as_ref() else { continue };
+    let fid = format!("file:{}", file_id);
+    if seen_files.insert(file_id.clone()) {
+      let name = c.rel_path.rsplit('/').next().unwrap_or(&c.rel_path).to_string();
+      entities.push(serde_json::json!({
+        "owner_id": owner_id,
+        "id": fid,
+        "project_folder_id": project_folder_id,
+        "entity_type": "file",
+        "file_id": file_id,
+        "data": { "type": "entity", "id": fid, "entityType": "file", "fileId": file_id, "name": name, "kind": c.file_kind },
+      }));
+    }
+
+    // Anchors look like `heading:<slug>:<occurrence>` or `heading:<slug>:<occurrence>:chunk:<n>`.
+    let parts: Vec<&str> = c.anchor.split(':').collect();
+    if parts.len() < 3 || parts[0] != "heading" {
+      continue;
+    }
+    if parts.len() >= 5 && parts[3] == "chunk" && parts[4] != "1" {
+      continue;
+    }
+    let hid = format!("heading:{}:{}:{}", file_id, parts[1], parts[2]);
+    let heading_text = c.text.lines().next().unwrap_or("").trim_start_matches('#').trim().to_string();
+    entities.push(serde_json::json!({
+      "owner_id": owner_id,
+      "id": hid,
+      "project_folder_id": project_folder_id,
+      "entity_type": "noteHeading",
+      "file_id": file_id,
+      "data": {
+        "type": "entity",
+        "id": hid,
+        "entityType": "noteHeading",
+        "fileId": file_id,
+        "text": heading_text,
+        "slug": parts[1],
+        "occurrence": parts[2].parse::<u32>().unwrap_or(1),
+        "lineIndex": c.line_start,
+      },
+    }));
+    let eid = format!("edge:file_has_heading:{}->{}", fid, hid);
+    edges.push(serde_json::json!({
+      "owner_id": owner_id,
+      "id": eid,
+      "project_folder_id": project_folder_id,
+      "edge_type": "file_has_heading",
+      "src": fid,
+      "dst": hid,
+      "data": { "type": "edge", "id": eid, "edgeType": "file_has_heading", "src": fid, "dst": hid },
+    }));
+  }
+  (entities, edges)
+}
+
+/// Ingests the vault straight into `rag_chunks` / `kg_*` via PostgREST, for self-hosters
+/// without the Vercel API. Embeddings use the caller's OpenAI key, and the signed-in user
+/// must be allowed to write those tables (the stock RLS policies only allow the service role).
+#[tauri::command]
+pub async fn rag_ingest_direct(req: RagDirectIngestRequest) -> Result<RagDirectIngestSummary, String> {
+  if req.project_folder_id.trim().is_empty() {
+    return Err("project_folder_id is required".to_string());
+  }
+  let api_key = req.openai_api_key.trim().to_string();
+  if api_key.is_empty() {
+    return Err("openai_api_key is required for direct ingest".to_string());
+  }
+  let model = req
+    .embedding_model
+    .clone()
+    .map(|m| m.trim().to_string())
+    .filter(|m| !m.is_empty())
+    .unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string());
+  let project_folder_id = req.project_folder_id.trim().to_string();
+  let opts = match req.chunk_options.clone() {
+    Some(o) => o,
+    None => crate::config::read_config(&req.vault_path)?.rag.chunking,
+  };
+
+  let (all_chunks, _files) = chunk_vault(&req.vault_path, &opts)?;
+  let total = all_chunks.len();
+  let chunks: Vec<LocalChunk> = all_chunks
+    .into_iter()
+    .filter(|c| c.file_id.is_some() || c.resource_id.is_some())
+    .collect();
+  let skipped_unmapped = (total - chunks.len()) as u32;
+
+  let client = reqwest::Client::new();
+  let mut auth = req.auth.clone();
+  let owner_id = auth.owner_id.clone();
+
+  // Embed everything before touching the tables so a failure leaves the old index intact. What
+  // was embedded is cached even then, so a retry doesn't pay for it again.
+  let mut cache = EmbeddingCache::load(&req.vault_path, &model);
+  let embedded = embed_chunks(&client, &api_key, &model, &chunks, &mut cache).await;
+  if let Err(e) = cache.save() {
+    tracing::warn!(error = %e, "could not save the embedding cache");
+  }
+  let embeddings = embedded?;
+
+  let chunk_rows: Vec<serde_json::Value> = chunks
+    .iter()
+    .zip(embeddings.iter())
+    .map(|(c, emb)| {
+      serde_json::json!({
+        "owner_id": owner_id,
+        "id": c.id,
+        "project_folder_id": project_folder_id,
+        "file_id": c.file_id,
+        "resource_id": c.resource_id,
+        "file_kind": c.file_kind,
+        "anchor": c.anchor,
+        "text": c.text,
+        "embedding": emb,
+        "metadata": {
+          "fileId": c.file_id,
+          "fileKind": c.file_kind,
+          "anchor": c.anchor,
+          "resourceId": c.resource_id,
+          "projectFolderId": project_folder_id,
+          "source": "desktop-direct",
+        },
+      })
+    })
+    .collect();
+  let (entity_rows, edge_rows) = graph_rows_from_chunks(&chunks, &owner_id, &project_folder_id);
+
+  for table in ["rag_chunks", "kg_entities", "kg_edges"] {
+    delete_project_rows(&client, &mut auth, table, &project_folder_id).await?;
+  }
+  upsert_rows(&client, &mut auth, "kg_entities", &entity_rows).await?;
+  upsert_rows(&client, &mut auth, "kg_edges", &edge_rows).await?;
+  upsert_rows(&client, &mut auth, "rag_chunks", &chunk_rows).await?;
+
+  let public_id = match fetch_one_rag_project(&client, &mut auth, &project_folder_id).await? {
+    Some(rp) => rp.public_id,
+    None => format!("rag_{}", &sha256_hex(format!("{}:{}", project_folder_id, now_iso()).as_bytes())[..20]),
+  };
+  let mut url = reqwest::Url::parse(&format!("{}/rag_projects", rest_base(&auth))).map_err(|e| e.to_string())?;
+  url.query_pairs_mut().append_pair("on_conflict", "owner_id,project_folder_id");
+  let project_row = serde_json::json!({
+    "owner_id": owner_id,
+    "project_folder_id": project_folder_id,
+    "public_id": public_id,
+    "updated_at": now_iso(),
+  });
+  send_with_refresh(
+    &client,
+    &mut auth,
+    || {
+      client
+        .post(url.clone())
+        .header("Prefer", "resolution=merge-duplicates,return=minimal")
+        .json(&project_row)
+    },
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("rag_projects upsert failed: HTTP {}", res.status()));
+        }
+        Ok(())
+      })
+    },
+  )
+  .await?;
+
+  let summary = RagDirectIngestSummary {
+    chunks: chunk_rows.len() as u32,
+    entities: entity_rows.len() as u32,
+    edges: edge_rows.len() as u32,
+    skipped_unmapped,
+    public_project_id: public_id,
+    embeddings_reused: cache.reused,
+    embeddings_computed: cache.computed,
+  };
+  let _ = append_event(
+    &req.vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "rag_ingest_direct".to_string(),
+      path: "rag/".to_string(),
+      detail: format!(
+        "Direct ingest. Chunks: {}, entities: {}, edges: {}. Skipped (unmapped): {}. Embeddings reused: {}, computed: {}.",
+        summary.chunks,
+        summary.entities,
+        summary.edges,
+        summary.skipped_unmapped,
+        summary.embeddings_reused,
+        summary.embeddings_computed
+      ),
+    },
+  );
+  Ok(summary)
+}
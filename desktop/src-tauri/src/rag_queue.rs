@@ -0,0 +1,259 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::config::{read_config, RagConfig};
+use crate::rag::{RagIngestRequest, RagIngestRunning};
+use crate::sync::{append_event, now_iso, read_mapping, RagIngestRunV1, SyncEvent};
+
+/// Due times are re-checked at least this often, so a push that makes an ingest due right away
+/// doesn't wait for the sleep of an earlier one.
+const RECHECK: Duration = Duration::from_secs(1);
+
+/// What the ingest policy is waiting to do for a vault.
+#[derive(Default)]
+struct Queue {
+  /// Notes to re-index on their own (`ingest_on_push`), once `files_due`.
+  rels: BTreeSet<String>,
+  files_due: Option<Instant>,
+  /// Notes pushed since the last whole-project ingest, and when the next one is due
+  /// (`auto_ingest`).
+  changes: u32,
+  project_due: Option<Instant>,
+  /// A worker task is waiting for the due times.
+  worker: bool,
+}
+
+impl Queue {
+  fn next_due(&self) -> Option<Instant> {
+    self.files_due.into_iter().chain(self.project_due).min()
+  }
+}
+
+static QUEUES: Lazy<Mutex<HashMap<String, Queue>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RagIngestStatus {
+  /// Notes waiting to be re-indexed on their own.
+  pub queued: Vec<String>,
+  /// Notes pushed since the last whole-project ingest.
+  pub changes_since_ingest: u32,
+  /// When the next automatic ingest starts, if one is scheduled.
+  pub next_ingest_at: Option<String>,
+  pub running: Option<RagIngestRunning>,
+  pub last_ingest: Option<RagIngestRunV1>,
+}
+
+/// A RAG ingest of `rel_paths` (the whole project when empty), with the vault's sync session and
+/// `rag.api_base_url`.
+pub(crate) fn request(vault_path: &str, rel_paths: Vec<String>) -> Result<RagIngestRequest, String> {
+  let base = read_config(vault_path)?
+    .rag
+    .api_base_url
+    .filter(|u| !u.trim().is_empty())
+    .ok_or("rag.api_base_url is not set")?;
+  let s = crate::status::sessions()
+    .into_iter()
+    .find(|s| s.vault_path == vault_path)
+    .ok_or("the vault is not syncing")?;
+  Ok(RagIngestRequest {
+    project_folder_id: s.project_folder_id.clone(),
+    access_token: crate::auth::latest(&s.auth).access_token,
+    api_base_url: base,
+    openai_api_key: None,
+    vault_path: Some(vault_path.to_string()),
+    resume: false,
+    chunk_limit: None,
+    concurrency: None,
+    file_ids: Vec::new(),
+    rel_paths,
+  })
+}
+
+/// Runs a background ingest, logging how it went.
+pub(crate) async fn run(vault_path: &str, req: RagIngestRequest) -> Result<(), String> {
+  let what = match req.rel_paths.as_slice() {
+    [] => "the project".to_string(),
+    [one] => one.clone(),
+    many => format!("{} notes", many.len()),
+  };
+  let res = crate::scheduler::run_task(crate::rag::rag_ingest_jwt(req))
+    .await
+    .and_then(|res| res)
+    .map(|_| ());
+  let (kind, detail) = match &res {
+    Ok(()) => ("rag_ingest", format!("Re-indexed {}.", what)),
+    Err(e) => ("rag_ingest_error", format!("Re-indexing {} failed: {}", what, e)),
+  };
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: kind.to_string(),
+      path: String::new(),
+      detail,
+    },
+  );
+  res
+}
+
+fn last_run(vault_path: &str) -> Option<RagIngestRunV1> {
+  read_mapping(vault_path).ok().flatten().and_then(|m| m.last_rag_ingest)
+}
+
+/// Updates the vault's queue and starts its worker if something became due and none is waiting.
+fn update(vault_path: &str, f: impl FnOnce(&mut Queue)) {
+  let start = {
+    let Ok(mut g) = QUEUES.lock() else { return };
+    let q = g.entry(vault_path.to_string()).or_insert_with(|| Queue {
+      // Earlier pushes count too; activity already has the one `note_push` is adding.
+      changes: crate::activity::pushed_since(vault_path, last_run(vault_path).map(|r| r.started_at).as_deref())
+        .saturating_sub(1),
+      ..Default::default()
+    });
+    f(q);
+    let start = q.next_due().is_some() && !q.worker;
+    q.worker |= start;
+    start
+  };
+  if start {
+    let vp = vault_path.to_string();
+    tauri::async_runtime::spawn(async move { drain(&vp).await });
+  }
+}
+
+/// Counts a pushed note towards the vault's ingest policy (`rag` in its config).
+pub(crate) fn note_push(vault_path: &str, rel_path: &str) {
+  let Ok(cfg) = read_config(vault_path).map(|c| c.rag) else { return };
+  if !cfg.ingest_on_push && !cfg.auto_ingest {
+    return;
+  }
+  let now = Instant::now();
+  update(vault_path, |q| {
+    if cfg.ingest_on_push {
+      q.rels.insert(rel_path.to_string());
+      q.files_due = Some(now + Duration::from_secs(cfg.ingest_debounce_seconds.max(1)));
+    }
+    if cfg.auto_ingest {
+      q.changes += 1;
+      q.project_due = Some(if q.changes >= cfg.auto_ingest_after_changes.max(1) {
+        now
+      } else {
+        now + Duration::from_secs(cfg.auto_ingest_quiet_minutes.max(1) * 60)
+      });
+    }
+  });
+}
+
+enum Job {
+  Project,
+  Files(Vec<String>),
+}
+
+/// The next due job, or `None` when nothing is scheduled any more (the worker then leaves).
+async fn next_job(vault_path: &str) -> Option<Job> {
+  loop {
+    let now = Instant::now();
+    {
+      let mut g = QUEUES.lock().ok()?;
+      let q = g.get_mut(vault_path)?;
+      match q.next_due() {
+        None => {
+          q.worker = false;
+          return None;
+        }
+        Some(due) if due > now => {}
+        // A whole-project ingest covers the single notes too.
+        Some(_) if q.project_due.map(|d| d <= now).unwrap_or(false) => {
+          q.project_due = None;
+          q.files_due = None;
+          q.rels.clear();
+          q.changes = 0;
+          return Some(Job::Project);
+        }
+        Some(_) => {
+          q.files_due = None;
+          return Some(Job::Files(std::mem::take(&mut q.rels).into_iter().collect()));
+        }
+      }
+    }
+    tokio::time::sleep(RECHECK).await;
+  }
+}
+
+async fn drain(vault_path: &str) {
+  while let Some(job) = next_job(vault_path).await {
+    let rels = match job {
+      Job::Project => Vec::new(),
+      // Resources and notes deleted meanwhile have nothing to index.
+      Job::Files(rels) => {
+        let mapped = read_mapping(vault_path).ok().flatten().map(|m| m.files).unwrap_or_default();
+        let rels: Vec<String> = rels.into_iter().filter(|r| mapped.contains_key(r)).collect();
+        if rels.is_empty() {
+          continue;
+        }
+        rels
+      }
+    };
+    let req = match request(vault_path, rels.clone()) {
+      Ok(r) => r,
+      Err(e) => {
+        tracing::info!(vault = %vault_path, error = %e, "skipping automatic ingest");
+        continue;
+      }
+    };
+    let res = run(vault_path, req).await;
+    // Another ingest of the project was running; try again once pushes are quiet again.
+    if matches!(&res, Err(e) if e.contains("already running")) {
+      let cfg: RagConfig = read_config(vault_path).map(|c| c.rag).unwrap_or_default();
+      let now = Instant::now();
+      if let Ok(mut g) = QUEUES.lock() {
+        if let Some(q) = g.get_mut(vault_path) {
+          if rels.is_empty() {
+            q.project_due = Some(now + Duration::from_secs(cfg.auto_ingest_quiet_minutes.max(1) * 60));
+          } else {
+            q.rels.extend(rels);
+            q.files_due = Some(now + Duration::from_secs(cfg.ingest_debounce_seconds.max(1)));
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Where the vault's background ingests stand: what is queued, what runs, and how the last
+/// whole-project ingest went.
+#[tauri::command]
+pub async fn rag_ingest_status(vault_path: String) -> Result<RagIngestStatus, String> {
+  let mapping = read_mapping(&vault_path)?;
+  let last_ingest = mapping.as_ref().and_then(|m| m.last_rag_ingest.clone());
+  let running = mapping.as_ref().and_then(|m| crate::rag::running(&m.project_folder_id));
+  let now = Instant::now();
+  let queued = QUEUES.lock().ok().and_then(|g| {
+    g.get(&vault_path).map(|q| {
+      let at = q.next_due().map(|due| {
+        let wait = chrono::Duration::from_std(due.saturating_duration_since(now)).unwrap_or_default();
+        (chrono::Utc::now() + wait).to_rfc3339()
+      });
+      (q.rels.iter().cloned().collect::<Vec<_>>(), q.changes, at)
+    })
+  });
+  let (queued, changes_since_ingest, next_ingest_at) = match queued {
+    Some(q) => q,
+    None => (
+      Vec::new(),
+      crate::activity::pushed_since(&vault_path, last_ingest.as_ref().map(|r| r.started_at.as_str())),
+      None,
+    ),
+  };
+  Ok(RagIngestStatus {
+    queued,
+    changes_since_ingest,
+    next_ingest_at,
+    running,
+    last_ingest,
+  })
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+
+use crate::sync::{append_event, now_iso, SupabaseAuth, SyncEvent};
+
+pub(crate) use diregram_sync_core::revisions::*;
+
+#[tauri::command]
+pub async fn vault_list_revisions(vault_path: String, path: String) -> Result<Vec<RevisionEntry>, String> {
+  let rel = checked_rel(&path)?;
+  let mut out = Vec::new();
+  for (ts, p) in list(&vault_path, &rel).into_iter().rev() {
+    let created_at = NaiveDateTime::parse_from_str(&ts, REVISION_FORMAT)
+      .map(|t| t.and_utc().to_rfc3339())
+      .unwrap_or_else(|_| ts.clone());
+    out.push(RevisionEntry {
+      ts,
+      created_at,
+      bytes: fs::metadata(&p).map(|m| m.len()).unwrap_or(0),
+    });
+  }
+  Ok(out)
+}
+
+/// Writes a revision back over the file; the watcher then pushes it like any local edit. The
+/// current content is recorded first, so a restore can itself be undone.
+#[tauri::command]
+pub async fn vault_restore_revision(vault_path: String, path: String, ts: String) -> Result<(), String> {
+  let rel = checked_rel(&path)?;
+  let bytes = read_local_revision(&vault_path, &rel, &ts)?;
+  let target = crate::normalize::local_path(Path::new(&vault_path), &rel);
+  if let Ok(current) = fs::read(&target) {
+    record(&vault_path, &rel, &current);
+  }
+  if let Some(parent) = target.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  crate::sync::write_atomic(&target, &bytes).map_err(|e| e.to_string())?;
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "revision_restore".to_string(),
+      path: rel,
+      detail: format!("Restored local revision {}.", ts),
+    },
+  );
+  Ok(())
+}
+
+/// Remote history of a synced note, newest first: the current row followed by the previous
+/// versions the server keeps in `file_revisions`.
+#[tauri::command]
+pub async fn vault_list_remote_revisions(
+  vault_path: String,
+  path: String,
+  auth: SupabaseAuth,
+) -> Result<Vec<RemoteRevisionEntry>, String> {
+  let rel = checked_rel(&path)?;
+  let (_, file_id) = remote_ids(&vault_path, &rel)?;
+  let client = reqwest::Client::new();
+  let mut auth = crate::auth::latest(&auth);
+  let mut out = Vec::new();
+  for row in revision_rows(&client, &mut auth, "files", "rev,updated_at,content", &file_id, None).await? {
+    out.push(RemoteRevisionEntry {
+      rev: row.rev,
+      updated_at: row.updated_at,
+      bytes: row.content.map(|c| c.len() as u64).unwrap_or(0),
+      current: true,
+    });
+  }
+  for row in revision_rows(&client, &mut auth, "file_revisions", "rev,updated_at,bytes", &file_id, None).await? {
+    out.push(RemoteRevisionEntry {
+      rev: row.rev,
+      updated_at: row.updated_at,
+      bytes: row.bytes.unwrap_or(0),
+      current: false,
+    });
+  }
+  Ok(out)
+}
+
+#[tauri::command]
+pub async fn vault_fetch_remote_revision(
+  vault_path: String,
+  path: String,
+  rev: Option<i64>,
+  auth: SupabaseAuth,
+) -> Result<String, String> {
+  let rel = checked_rel(&path)?;
+  let client = reqwest::Client::new();
+  let mut auth = crate::auth::latest(&auth);
+  fetch_remote_text(&client, &mut auth, &vault_path, &rel, rev).await
+}
@@ -0,0 +1,25 @@
+use std::collections::BTreeMap;
+
+use crate::config::{read_config, write_config, RoutingConfig};
+
+pub(crate) use diregram_sync_core::routing::*;
+
+/// Sets which kinds of pulled files go to which folder. Files already pulled move on the next
+/// pull that touches them.
+#[tauri::command]
+pub async fn routing_configure(vault_path: String, config: RoutingConfig) -> Result<RoutingConfig, String> {
+  let mut kinds = BTreeMap::new();
+  for (kind, dir) in config.kinds {
+    let kind = kind.trim().to_string();
+    if kind.is_empty() {
+      return Err("kind is empty".to_string());
+    }
+    let dir = route_folder(&dir).ok_or_else(|| format!("{} must be a visible folder inside the vault, outside resources/ and rag/", dir))?;
+    kinds.insert(kind, dir);
+  }
+  let config = RoutingConfig { kinds };
+  let mut cfg = read_config(&vault_path)?;
+  cfg.routing = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
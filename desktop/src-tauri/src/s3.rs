@@ -0,0 +1,12 @@
+pub(crate) use diregram_sync_core::s3::*;
+
+/// Stores (or with `None`, forgets) the secret key paired with an S3 access key id.
+#[tauri::command]
+pub fn attachments_set_s3_secret(access_key_id: String, secret_access_key: Option<String>) -> Result<(), String> {
+  let profile = crate::auth::current_profile();
+  let key = format!("{}{}", SECRET_PREFIX, access_key_id.trim());
+  match secret_access_key {
+    Some(s) => crate::secrets::set(profile.as_deref(), &key, &s),
+    None => crate::secrets::remove(profile.as_deref(), &key),
+  }
+}
@@ -0,0 +1,15 @@
+use crate::config::{read_config, write_config, WatchConfig};
+
+pub(crate) use diregram_sync_core::scan::*;
+
+/// Saves how the vault is watched. A running sync watcher switches over right away.
+#[tauri::command]
+pub async fn sync_watch_configure(vault_path: String, config: WatchConfig) -> Result<WatchConfig, String> {
+  if config.scan_interval_secs < MIN_SCAN_INTERVAL.as_secs() {
+    return Err(format!("scan_interval_secs must be at least {}", MIN_SCAN_INTERVAL.as_secs()));
+  }
+  let mut cfg = read_config(&vault_path)?;
+  cfg.watch = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
@@ -0,0 +1,1015 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+use crate::config::{WatchConfig, WatchStrategy};
+use crate::engine::VaultSync;
+use crate::status::WatchMode;
+use crate::sync::{background_sync_suspended, is_atomic_tmp, sync_key, SupabaseAuth, SyncSummary};
+
+/// Filesystem events within this window of each other are pushed together (one save often
+/// reports several events: temp write, rename, metadata).
+const PUSH_DEBOUNCE: Duration = Duration::from_millis(200);
+/// While sync is paused, due jobs are re-checked this often so they run soon after resuming.
+const PAUSED_RECHECK: Duration = Duration::from_secs(1);
+/// How long quitting waits for in-flight pulls and pushes before exiting anyway.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+/// A job that crashed is restarted after this, doubling per consecutive crash up to the max.
+const CRASH_RESTART_MIN: Duration = Duration::from_secs(5);
+const CRASH_RESTART_MAX: Duration = Duration::from_secs(5 * 60);
+/// A watcher that failed is recreated after this, doubling per attempt; after
+/// `WATCH_RETRIES` failed attempts the vault is rescanned periodically instead.
+const WATCH_RETRY_MIN: Duration = Duration::from_secs(2);
+const WATCH_RETRIES: u32 = 5;
+/// How often the folder of an unavailable vault is checked for its return.
+const UNAVAILABLE_RECHECK: Duration = Duration::from_secs(5);
+
+/// The app's background sync state, registered with `app.manage()` and handed to commands as
+/// `tauri::State`. One task on the async runtime owns every vault's remote polling and local
+/// watching; commands talk to it through here.
+pub struct SyncRuntime {
+  tx: mpsc::UnboundedSender<Command>,
+  /// Set once quitting has started; the exit it ends with must not start another shutdown.
+  quitting: AtomicBool,
+}
+
+/// Emitted as `sync://shutting_down` when the app starts quitting, so the UI can show that sync
+/// is finishing.
+#[derive(Debug, Serialize, Clone)]
+pub struct ShuttingDown {
+  /// Pulls, pushes and other sync runs (see `run_task`) being waited for.
+  pub in_flight: usize,
+}
+
+struct PullJob {
+  vault_path: String,
+  project_folder_id: String,
+  auth: SupabaseAuth,
+  force: Option<bool>,
+  min_wait: Duration,
+  max_wait: Duration,
+  wait: Duration,
+  due: Instant,
+  running: bool,
+  /// Started at launch from `autosync`; the frontend's own start takes it over.
+  resumed: bool,
+  /// Consecutive runs that panicked.
+  crashes: u32,
+}
+
+struct WatchJob {
+  vault_path: String,
+  project_folder_id: String,
+  auth: SupabaseAuth,
+  /// `None` while the watcher is torn down after an error, or for good in scan mode.
+  watcher: Option<notify::RecommendedWatcher>,
+  /// Path of the change to push, once `due`.
+  trigger: Option<PathBuf>,
+  due: Option<Instant>,
+  running: bool,
+  resumed: bool,
+  crashes: u32,
+  mode: WatchMode,
+  /// Failed watcher recreations in a row, and when to try the next one.
+  watch_failures: u32,
+  rewatch_at: Option<Instant>,
+  /// The vault's `watch` config the job runs with, and the time between scans it sets.
+  config: WatchConfig,
+  scan_interval: Duration,
+}
+
+pub(crate) struct PullOptions {
+  pub min_wait: Duration,
+  pub max_wait: Duration,
+  pub force: Option<bool>,
+  pub resumed: bool,
+}
+
+enum Command {
+  StartPull {
+    vault_path: String,
+    project_folder_id: String,
+    auth: SupabaseAuth,
+    opts: PullOptions,
+    reply: oneshot::Sender<Result<(), String>>,
+  },
+  StopPulls {
+    reply: oneshot::Sender<Vec<String>>,
+  },
+  StartWatch {
+    vault_path: String,
+    project_folder_id: String,
+    auth: SupabaseAuth,
+    resumed: bool,
+    reply: oneshot::Sender<Result<(), String>>,
+  },
+  StopWatches {
+    reply: oneshot::Sender<Vec<String>>,
+  },
+  StopVault {
+    vault_path: String,
+    reply: oneshot::Sender<usize>,
+  },
+  /// Drops one account's jobs. `done` fires once the runs they had in flight have finished, except
+  /// the one under `except`, which is the caller's own.
+  StopAccount {
+    supabase_url: String,
+    owner_id: String,
+    except: String,
+    reply: oneshot::Sender<(Vec<String>, oneshot::Receiver<()>)>,
+  },
+  /// The vault's config was written; re-apply what running jobs read once.
+  ReloadConfig {
+    vault_path: String,
+  },
+  Wake,
+  PullDone {
+    key: String,
+    changed: Option<bool>,
+    /// The panic message, if the run crashed.
+    crashed: Option<String>,
+  },
+  PushDone {
+    key: String,
+    crashed: Option<String>,
+  },
+  /// A run started outside the scheduler (see `run_task`) begins; refused while shutting down.
+  TaskStart {
+    reply: oneshot::Sender<Result<(), String>>,
+  },
+  TaskDone,
+  Shutdown {
+    /// The vaults that had jobs, and how many runs are still in flight.
+    reply: oneshot::Sender<(Vec<String>, usize)>,
+    /// Sent once the last in-flight run has finished.
+    drained: oneshot::Sender<()>,
+  },
+}
+
+/// `min` doubled per earlier attempt, capped at `max`. `attempt` counts from 1.
+fn restart_delay(min: Duration, max: Duration, attempt: u32) -> Duration {
+  min.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(max)
+}
+
+fn crash_restart(crashes: u32) -> Duration {
+  restart_delay(CRASH_RESTART_MIN, CRASH_RESTART_MAX, crashes)
+}
+
+/// Errors after which the watcher delivers nothing more (watch limit reached, the watched
+/// folder gone or its volume unmounted), so it has to be recreated.
+fn fatal_watch_error(e: &notify::Error) -> bool {
+  matches!(
+    e.kind,
+    notify::ErrorKind::MaxFilesWatch | notify::ErrorKind::PathNotFound | notify::ErrorKind::WatchNotFound | notify::ErrorKind::Io(_)
+  )
+}
+
+fn log_watch(vault_path: &str, kind: &str, detail: String) {
+  let _ = crate::sync::append_event(
+    vault_path,
+    &crate::sync::SyncEvent {
+      ts: crate::sync::now_iso(),
+      kind: kind.to_string(),
+      path: String::new(),
+      detail,
+    },
+  );
+}
+
+/// Interval growth per pull that found nothing new.
+fn backoff(wait: Duration, max: Duration) -> Duration {
+  (wait * 3 / 2).min(max)
+}
+
+fn pull_found_changes(s: &SyncSummary) -> bool {
+  s.files_created + s.files_updated + s.files_deleted + s.resources_deleted > 0 || !s.warnings.is_empty()
+}
+
+/// Events that must not trigger a push.
+fn ignorable(vault_path: &str, event: &notify::Event) -> bool {
+  // Our own bookkeeping (mapping, events, lock heartbeat).
+  event.paths.iter().all(|p| p.components().any(|c| c.as_os_str() == ".diregram"))
+    // Temp files of our own atomic writes, and our own pull/export writes (the rename is reported
+    // with the temp path too) still holding what we wrote.
+    || (!event.paths.is_empty() && event.paths.iter().all(|p| is_atomic_tmp(p) || crate::echo::is_echo(p)))
+    // Links the symlink policy excludes would only trigger a push that skips them.
+    || event
+      .paths
+      .iter()
+      .all(|p| p.starts_with(vault_path) && crate::symlinks::check_write(vault_path, p).is_err())
+}
+
+struct Scheduler {
+  tx: mpsc::UnboundedSender<Command>,
+  fs_tx: mpsc::UnboundedSender<(String, notify::Result<notify::Event>)>,
+  pulls: HashMap<String, PullJob>,
+  watches: HashMap<String, WatchJob>,
+  /// Spawned pull and push runs, and `run_task` runs, that haven't reported back yet.
+  in_flight: usize,
+  /// Set while shutting down; no new jobs are accepted.
+  drained: Option<oneshot::Sender<()>>,
+  /// Runs of stopped jobs that `StopAccount` callers are waiting out.
+  stopping: Vec<(Vec<Run>, oneshot::Sender<()>)>,
+}
+
+/// An in-flight run, by job key.
+#[derive(PartialEq, Eq)]
+enum Run {
+  Pull(String),
+  Push(String),
+}
+
+impl Scheduler {
+  fn next_due(&self) -> Option<Instant> {
+    let pulls = self.pulls.values().filter(|j| !j.running).map(|j| j.due);
+    let pushes = self.watches.values().filter(|j| !j.running).filter_map(|j| j.due);
+    let rewatches = self.watches.values().filter_map(|j| j.rewatch_at);
+    pulls.chain(pushes).chain(rewatches).min()
+  }
+
+  fn handle(&mut self, cmd: Command) {
+    match cmd {
+      Command::StartPull { reply, .. } | Command::StartWatch { reply, .. } | Command::TaskStart { reply }
+        if self.drained.is_some() =>
+      {
+        let _ = reply.send(Err("sync is shutting down".to_string()));
+      }
+      Command::StartPull {
+        vault_path,
+        project_folder_id,
+        auth,
+        opts,
+        reply,
+      } => {
+        let _ = reply.send(self.start_pull(vault_path, project_folder_id, auth, opts));
+      }
+      Command::StopPulls { reply } => {
+        let _ = reply.send(self.pulls.drain().map(|(_, j)| j.vault_path).collect());
+      }
+      Command::StartWatch {
+        vault_path,
+        project_folder_id,
+        auth,
+        resumed,
+        reply,
+      } => {
+        let _ = reply.send(self.start_watch(vault_path, project_folder_id, auth, resumed));
+      }
+      Command::StopWatches { reply } => {
+        let _ = reply.send(self.watches.drain().map(|(_, j)| j.vault_path).collect());
+      }
+      Command::StopVault { vault_path, reply } => {
+        let jobs = self.pulls.len() + self.watches.len();
+        self.pulls.retain(|_, j| j.vault_path != vault_path);
+        self.watches.retain(|_, j| j.vault_path != vault_path);
+        let _ = reply.send(jobs - self.pulls.len() - self.watches.len());
+      }
+      Command::StopAccount {
+        supabase_url,
+        owner_id,
+        except,
+        reply,
+      } => {
+        let ours = |auth: &SupabaseAuth| auth.supabase_url == supabase_url && auth.owner_id == owner_id;
+        let mut vaults = Vec::new();
+        let mut runs = Vec::new();
+        self.pulls.retain(|key, j| {
+          if !ours(&j.auth) {
+            return true;
+          }
+          vaults.push(j.vault_path.clone());
+          if j.running && *key != except {
+            runs.push(Run::Pull(key.clone()));
+          }
+          false
+        });
+        self.watches.retain(|key, j| {
+          if !ours(&j.auth) {
+            return true;
+          }
+          vaults.push(j.vault_path.clone());
+          if j.running {
+            runs.push(Run::Push(key.clone()));
+          }
+          false
+        });
+        vaults.sort();
+        vaults.dedup();
+        let (done, done_rx) = oneshot::channel();
+        if runs.is_empty() {
+          let _ = done.send(());
+        } else {
+          self.stopping.push((runs, done));
+        }
+        let _ = reply.send((vaults, done_rx));
+      }
+      Command::ReloadConfig { vault_path } => self.reload_config(&vault_path),
+      Command::Wake => {
+        let now = Instant::now();
+        for job in self.pulls.values_mut() {
+          job.wait = job.min_wait;
+          job.due = now;
+        }
+      }
+      Command::PullDone { key, changed, crashed } => {
+        self.in_flight -= 1;
+        self.finished(&Run::Pull(key.clone()));
+        // The job may have been stopped while this run was in flight.
+        let Some(job) = self.pulls.get_mut(&key) else { return };
+        job.running = false;
+        let vault_path = job.vault_path.clone();
+        if let Some(detail) = crashed {
+          job.crashes += 1;
+          let wait = crash_restart(job.crashes);
+          crate::panics::report(&job.vault_path, "pull", &detail, wait);
+          job.due = Instant::now() + wait;
+        } else {
+          job.crashes = 0;
+          job.wait = match changed {
+            Some(true) => job.min_wait,
+            Some(false) => backoff(job.wait, job.max_wait),
+            None => job.wait,
+          };
+          job.due = Instant::now() + job.wait;
+        }
+        // A run that failed because the vault went away pauses the vault's other jobs too.
+        self.check_volume(&vault_path, Instant::now());
+      }
+      Command::PushDone { key, crashed } => {
+        self.in_flight -= 1;
+        self.finished(&Run::Push(key.clone()));
+        let Some(job) = self.watches.get_mut(&key) else { return };
+        job.running = false;
+        if job.mode == WatchMode::Scan {
+          job.trigger.get_or_insert_with(|| PathBuf::from(&job.vault_path));
+          job.due = Some(Instant::now() + job.scan_interval);
+        }
+        match crashed {
+          Some(detail) => {
+            job.crashes += 1;
+            let wait = crash_restart(job.crashes);
+            crate::panics::report(&job.vault_path, "push", &detail, wait);
+            // The change that triggered it was taken; push the whole vault on restart.
+            job.trigger.get_or_insert_with(|| PathBuf::from(&job.vault_path));
+            job.due = Some(Instant::now() + wait);
+          }
+          None => job.crashes = 0,
+        }
+        let vault_path = job.vault_path.clone();
+        self.check_volume(&vault_path, Instant::now());
+      }
+      Command::TaskStart { reply } => {
+        self.in_flight += 1;
+        let _ = reply.send(Ok(()));
+      }
+      Command::TaskDone => self.in_flight -= 1,
+      Command::Shutdown { reply, drained } => {
+        let vaults = self.stop_all();
+        let _ = reply.send((vaults, self.in_flight));
+        self.drained = Some(drained);
+      }
+    }
+  }
+
+  /// Tells `StopAccount` callers waiting on `run` once none of theirs are left.
+  fn finished(&mut self, run: &Run) {
+    for (runs, _) in self.stopping.iter_mut() {
+      runs.retain(|r| r != run);
+    }
+    let (done, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.stopping).into_iter().partition(|(runs, _)| runs.is_empty());
+    self.stopping = waiting;
+    for (_, tx) in done {
+      let _ = tx.send(());
+    }
+  }
+
+  /// Drops every job (stopping the watchers) and returns the vaults they ran for. Changes still
+  /// waiting out the push debounce are pushed first rather than lost.
+  fn stop_all(&mut self) -> Vec<String> {
+    self.pulls.clear();
+    let now = Instant::now();
+    for job in self.watches.values_mut() {
+      if job.trigger.is_some() {
+        job.due = Some(now);
+      }
+    }
+    self.run_due();
+    let mut vaults: Vec<String> = self.watches.drain().map(|(_, j)| j.vault_path).collect();
+    vaults.sort();
+    vaults.dedup();
+    vaults
+  }
+
+  fn start_pull(&mut self, vault_path: String, project_folder_id: String, auth: SupabaseAuth, opts: PullOptions) -> Result<(), String> {
+    let key = sync_key(&vault_path, &project_folder_id);
+    if let Some(job) = self.pulls.get_mut(&key) {
+      if !job.resumed || opts.resumed {
+        return Err("remote poller already running for this project".to_string());
+      }
+      job.auth = auth;
+      job.force = opts.force;
+      job.min_wait = opts.min_wait;
+      job.max_wait = opts.max_wait.max(opts.min_wait);
+      job.wait = job.min_wait;
+      job.resumed = false;
+      return Ok(());
+    }
+    crate::lock::acquire(&vault_path)?;
+    self.pulls.insert(
+      key,
+      PullJob {
+        vault_path,
+        project_folder_id,
+        auth,
+        force: opts.force,
+        min_wait: opts.min_wait,
+        max_wait: opts.max_wait.max(opts.min_wait),
+        wait: opts.min_wait,
+        due: Instant::now(),
+        running: false,
+        resumed: opts.resumed,
+        crashes: 0,
+      },
+    );
+    Ok(())
+  }
+
+  fn start_watch(
+    &mut self,
+    vault_path: String,
+    project_folder_id: String,
+    auth: SupabaseAuth,
+    resumed: bool,
+  ) -> Result<(), String> {
+    let key = sync_key(&vault_path, &project_folder_id);
+    if let Some(job) = self.watches.get_mut(&key) {
+      if !job.resumed || resumed {
+        return Err("sync watcher already running for this project".to_string());
+      }
+      job.auth = auth;
+      job.resumed = false;
+      return Ok(());
+    }
+    crate::lock::acquire(&vault_path)?;
+    let config = crate::config::read_config(&vault_path).map(|c| c.watch).unwrap_or_default();
+    // Baseline for telling later hand edits of the config apart.
+    crate::config::remember(&vault_path);
+    let scan_interval = crate::scan::scan_interval(&config);
+    let (watcher, mode, degraded) = match self.open_watch(&key, &vault_path, &config) {
+      Ok(opened) => opened,
+      Err(e) => {
+        crate::lock::release(&vault_path);
+        return Err(e);
+      }
+    };
+    let scanning = mode == WatchMode::Scan;
+    crate::status::set_watch_mode(&vault_path, Some(mode), degraded);
+    self.watches.insert(
+      key,
+      WatchJob {
+        trigger: scanning.then(|| PathBuf::from(&vault_path)),
+        due: scanning.then(Instant::now),
+        vault_path,
+        project_folder_id,
+        auth,
+        watcher,
+        running: false,
+        resumed,
+        crashes: 0,
+        mode,
+        watch_failures: 0,
+        rewatch_at: None,
+        config,
+        scan_interval,
+      },
+    );
+    Ok(())
+  }
+
+  /// How `config` has the vault watched: a filesystem watcher, or periodic scans (with the reason
+  /// when they stand in for a watcher).
+  fn open_watch(
+    &self,
+    key: &str,
+    vault_path: &str,
+    config: &WatchConfig,
+  ) -> Result<(Option<notify::RecommendedWatcher>, WatchMode, Option<String>), String> {
+    // Watchers only see changes made through this machine on network drives; scan those instead.
+    let network_fs = match config.mode {
+      WatchStrategy::Auto => crate::scan::network_filesystem(vault_path),
+      _ => None,
+    };
+    if config.mode == WatchStrategy::Scan || network_fs.is_some() {
+      let degraded = network_fs.map(|fs| {
+        format!(
+          "The vault is on a network drive ({}), which reports no file changes; rescanning every {}s instead.",
+          fs,
+          crate::scan::scan_interval(config).as_secs()
+        )
+      });
+      return Ok((None, WatchMode::Scan, degraded));
+    }
+    Ok((Some(self.watch(key, vault_path)?), WatchMode::Events, None))
+  }
+
+  /// Applies the vault's current `watch` config to its running watchers. A job whose settings
+  /// changed is set up again and catches up with a full push; an unreadable config changes nothing.
+  fn reload_config(&mut self, vault_path: &str) {
+    let Ok(config) = crate::config::read_config(vault_path).map(|c| c.watch) else { return };
+    let keys: Vec<String> = self
+      .watches
+      .iter()
+      .filter(|(_, j)| j.vault_path == vault_path && j.config != config)
+      .map(|(k, _)| k.clone())
+      .collect();
+    for key in keys {
+      let opened = self.open_watch(&key, vault_path, &config);
+      let Some(job) = self.watches.get_mut(&key) else { continue };
+      job.config = config.clone();
+      job.scan_interval = crate::scan::scan_interval(&config);
+      job.watch_failures = 0;
+      job.rewatch_at = None;
+      let (watcher, mode, degraded) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+          job.mode = WatchMode::Events;
+          self.watch_failed(&key, &e);
+          continue;
+        }
+      };
+      job.watcher = watcher;
+      job.mode = mode;
+      job.trigger = Some(PathBuf::from(vault_path));
+      job.due = Some(Instant::now());
+      crate::status::set_watch_mode(vault_path, Some(mode), degraded);
+      let detail = match mode {
+        WatchMode::Events => "Watch settings changed; watching for file changes.".to_string(),
+        WatchMode::Scan => format!("Watch settings changed; rescanning every {}s.", job.scan_interval.as_secs()),
+      };
+      log_watch(vault_path, "watch_reconfigured", detail);
+    }
+  }
+
+  /// A filesystem watcher that feeds `on_fs_event` under `key`.
+  fn watch(&self, key: &str, vault_path: &str) -> Result<notify::RecommendedWatcher, String> {
+    let fs_tx = self.fs_tx.clone();
+    let event_key = key.to_string();
+    let mut w = notify::recommended_watcher(move |res| {
+      let _ = fs_tx.send((event_key.clone(), res));
+    })
+    .map_err(|e| e.to_string())?;
+    w.watch(Path::new(vault_path), RecursiveMode::Recursive).map_err(|e| e.to_string())?;
+    Ok(w)
+  }
+
+  /// Tears down a watcher that stopped delivering events and schedules its recreation. Once
+  /// `WATCH_RETRIES` recreations have failed, switches the vault to periodic rescans.
+  fn watch_failed(&mut self, key: &str, reason: &str) {
+    let Some(job) = self.watches.get_mut(key) else { return };
+    job.watcher = None;
+    // The watched folder itself is gone: not a watcher problem, the vault is paused until it's back.
+    if !crate::volume::available(&job.vault_path) {
+      let vault_path = job.vault_path.clone();
+      self.check_volume(&vault_path, Instant::now());
+      return;
+    }
+    job.watch_failures += 1;
+    if job.watch_failures > WATCH_RETRIES {
+      job.mode = WatchMode::Scan;
+      job.rewatch_at = None;
+      job.trigger = Some(PathBuf::from(&job.vault_path));
+      job.due = Some(Instant::now());
+      let degraded = format!(
+        "The file watcher keeps failing ({}); rescanning every {}s instead.",
+        reason,
+        job.scan_interval.as_secs()
+      );
+      log_watch(&job.vault_path, "watch_degraded", degraded.clone());
+      crate::status::set_watch_mode(&job.vault_path, Some(WatchMode::Scan), Some(degraded));
+      return;
+    }
+    let wait = restart_delay(WATCH_RETRY_MIN, WATCH_RETRY_MIN * 32, job.watch_failures);
+    job.rewatch_at = Some(Instant::now() + wait);
+    log_watch(
+      &job.vault_path,
+      "watch_error",
+      format!("File watcher failed ({}); recreating it in {}s.", reason, wait.as_secs()),
+    );
+  }
+
+  /// Pauses the vault's jobs while its folder is unavailable (e.g. the drive was unplugged) and
+  /// resumes them once it is back, with a full push for changes made meanwhile.
+  fn check_volume(&mut self, vault_path: &str, now: Instant) {
+    if crate::volume::available(vault_path) {
+      if !crate::volume::mark_available(vault_path) {
+        return;
+      }
+      for job in self.pulls.values_mut().filter(|j| j.vault_path == vault_path && !j.running) {
+        job.due = now;
+      }
+      for job in self.watches.values_mut().filter(|j| j.vault_path == vault_path) {
+        if job.mode == WatchMode::Events {
+          job.rewatch_at = Some(now);
+        }
+        job.trigger = Some(PathBuf::from(vault_path));
+        job.due = Some(now);
+      }
+      return;
+    }
+    crate::volume::mark_unavailable(vault_path);
+    let recheck = now + UNAVAILABLE_RECHECK;
+    for job in self.pulls.values_mut().filter(|j| j.vault_path == vault_path && !j.running) {
+      job.due = recheck;
+    }
+    for job in self.watches.values_mut().filter(|j| j.vault_path == vault_path) {
+      job.watcher = None;
+      job.rewatch_at = None;
+      job.trigger = Some(PathBuf::from(vault_path));
+      if !job.running {
+        job.due = Some(recheck);
+      }
+    }
+  }
+
+  /// Recreates watchers whose retry time has come. Changes made while a vault was unwatched are
+  /// caught up with a full push.
+  fn rewatch_due(&mut self, now: Instant) {
+    let due: Vec<(String, String)> = self
+      .watches
+      .iter()
+      .filter(|(_, j)| j.rewatch_at.map(|t| t <= now).unwrap_or(false))
+      .map(|(k, j)| (k.clone(), j.vault_path.clone()))
+      .collect();
+    for (key, vault_path) in due {
+      match self.watch(&key, &vault_path) {
+        Ok(w) => {
+          let Some(job) = self.watches.get_mut(&key) else { continue };
+          job.watcher = Some(w);
+          job.rewatch_at = None;
+          job.trigger = Some(PathBuf::from(&vault_path));
+          job.due = Some(now);
+          log_watch(&vault_path, "watch_restored", "File watcher recreated.".to_string());
+        }
+        Err(e) => self.watch_failed(&key, &e),
+      }
+    }
+  }
+
+  /// Handling a filesystem event panicked: replace the watcher and push the whole vault once the
+  /// restart delay is over, so changes seen in between aren't lost.
+  fn restart_watch(&mut self, key: &str, detail: &str) {
+    let Some(job) = self.watches.get_mut(key) else { return };
+    job.crashes += 1;
+    let wait = crash_restart(job.crashes);
+    crate::panics::report(&job.vault_path, "watch", detail, wait);
+    let restart_at = Instant::now() + wait;
+    if job.mode == WatchMode::Events {
+      job.watcher = None;
+      job.rewatch_at = Some(restart_at);
+    }
+    job.trigger = Some(PathBuf::from(&job.vault_path));
+    job.due = Some(restart_at);
+  }
+
+  fn on_fs_event(&mut self, key: String, res: notify::Result<notify::Event>) {
+    let Some(job) = self.watches.get_mut(&key) else { return };
+    // Still queued from a watcher that has been torn down.
+    if job.watcher.is_none() {
+      return;
+    }
+    let event = match res {
+      Ok(event) => {
+        // Delivering again, so earlier failures were transient.
+        job.watch_failures = 0;
+        event
+      }
+      Err(e) if fatal_watch_error(&e) => return self.watch_failed(&key, &e.to_string()),
+      Err(e) => {
+        tracing::warn!(vault = %job.vault_path, error = %e, "watcher error");
+        return;
+      }
+    };
+    // The backend dropped events (e.g. inotify queue overflow); only a full push catches up.
+    if event.need_rescan() {
+      job.trigger = Some(PathBuf::from(&job.vault_path));
+      job.due = Some(Instant::now() + PUSH_DEBOUNCE);
+      return;
+    }
+    // Hand edits of the config apply right away; the app's own writes already did.
+    if event.paths.iter().any(|p| p.ends_with(".diregram/config.json")) {
+      crate::config::reload_if_edited(&job.vault_path);
+    }
+    if ignorable(&job.vault_path, &event) {
+      return;
+    }
+    let trigger = event
+      .paths
+      .into_iter()
+      .next()
+      .unwrap_or_else(|| Path::new(&job.vault_path).to_path_buf());
+    job.trigger = Some(trigger);
+    job.due = Some(Instant::now() + PUSH_DEBOUNCE);
+  }
+
+  fn run_due(&mut self) {
+    let now = Instant::now();
+    let suspended = background_sync_suspended();
+    let vaults: HashSet<String> = self
+      .pulls
+      .values()
+      .map(|j| j.vault_path.clone())
+      .chain(self.watches.values().map(|j| j.vault_path.clone()))
+      .collect();
+    for vault_path in vaults {
+      self.check_volume(&vault_path, now);
+    }
+    self.rewatch_due(now);
+    // Scans see no events, so hand edits of the config are looked for as they run.
+    let scanned: HashSet<String> = self
+      .watches
+      .values()
+      .filter(|j| j.mode == WatchMode::Scan)
+      .map(|j| j.vault_path.clone())
+      .collect();
+    for vault_path in scanned {
+      crate::config::reload_if_edited(&vault_path);
+    }
+
+    for (key, job) in self.pulls.iter_mut() {
+      if job.running || job.due > now {
+        continue;
+      }
+      if suspended {
+        job.due = now + job.wait;
+        continue;
+      }
+      job.running = true;
+      self.in_flight += 1;
+      let tx = self.tx.clone();
+      let key = key.clone();
+      let (vault_path, project_folder_id) = (job.vault_path.clone(), job.project_folder_id.clone());
+      let auth = crate::auth::latest(&job.auth);
+      let force = job.force.unwrap_or(false);
+      tauri::async_runtime::spawn(async move {
+        let run = VaultSync::new(vault_path.clone(), project_folder_id, auth);
+        let (changed, crashed) = match crate::panics::catch_panic(run.pull_once(force)).await {
+          Ok(res) => {
+            crate::notify::report_background_result(&vault_path, "pull", res.as_ref().map(|_| ()).map_err(|e| e.as_str()));
+            (res.as_ref().ok().map(pull_found_changes), None)
+          }
+          Err(panic) => (None, Some(panic)),
+        };
+        let _ = tx.send(Command::PullDone { key, changed, crashed });
+      });
+    }
+
+    for (key, job) in self.watches.iter_mut() {
+      if job.running || job.due.map(|d| d > now).unwrap_or(true) {
+        continue;
+      }
+      // Changes seen while paused are pushed in one go on resume.
+      if suspended {
+        job.due = Some(now + PAUSED_RECHECK);
+        continue;
+      }
+      let Some(trigger) = job.trigger.take() else {
+        job.due = None;
+        continue;
+      };
+      job.due = None;
+      job.running = true;
+      self.in_flight += 1;
+      let tx = self.tx.clone();
+      let key = key.clone();
+      let (vault_path, project_folder_id) = (job.vault_path.clone(), job.project_folder_id.clone());
+      let auth = crate::auth::latest(&job.auth);
+      let scanning = job.mode == WatchMode::Scan;
+      tauri::async_runtime::spawn(async move {
+        let run = VaultSync::new(vault_path.clone(), project_folder_id, auth);
+        let push = async {
+          // Scans push only when something changed, so an idle vault doesn't cost a sync per interval.
+          if scanning {
+            let vp = vault_path.clone();
+            if !tauri::async_runtime::spawn_blocking(move || crate::scan::detect_changes(&vp)).await.unwrap_or(true) {
+              return None;
+            }
+          }
+          Some(run.push_path(&trigger).await)
+        };
+        let crashed = match crate::panics::catch_panic(push).await {
+          Ok(Some(res)) => {
+            crate::notify::report_background_result(&vault_path, "push", res.as_ref().map(|_| ()).map_err(|e| e.as_str()));
+            None
+          }
+          Ok(None) => None,
+          Err(panic) => Some(panic),
+        };
+        let _ = tx.send(Command::PushDone { key, crashed });
+      });
+    }
+  }
+}
+
+/// Reports a `run_task` run as finished when dropped, so one that panics or is cancelled still
+/// lets shutdown go ahead.
+struct TaskGuard(mpsc::UnboundedSender<Command>);
+
+impl Drop for TaskGuard {
+  fn drop(&mut self) {
+    let _ = self.0.send(Command::TaskDone);
+  }
+}
+
+/// Runs a sync job started outside the scheduler (tray "Sync now", a clip or daily note push, a
+/// background ingest) as one of its in-flight runs, so quitting waits for it like for a pull.
+/// Refused once quitting has started. Without the app (the CLI) the job just runs.
+pub(crate) async fn run_task<F: Future>(job: F) -> Result<F::Output, String> {
+  let Some(runtime) = crate::notify::app().and_then(|app| app.try_state::<SyncRuntime>()) else {
+    return Ok(job.await);
+  };
+  let tx = runtime.tx.clone();
+  runtime.ask(|reply| Command::TaskStart { reply }).await??;
+  let _done = TaskGuard(tx);
+  Ok(job.await)
+}
+
+impl SyncRuntime {
+  /// Spawns the scheduler task. Call once, from setup.
+  pub(crate) fn start() -> Self {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Command>();
+    let (fs_tx, mut fs_rx) = mpsc::unbounded_channel();
+    let mut sched = Scheduler {
+      tx: tx.clone(),
+      fs_tx,
+      pulls: HashMap::new(),
+      watches: HashMap::new(),
+      in_flight: 0,
+      drained: None,
+      stopping: Vec::new(),
+    };
+    tauri::async_runtime::spawn(async move {
+      loop {
+        let next = sched.next_due();
+        tokio::select! {
+          cmd = rx.recv() => match cmd {
+            Some(cmd) => sched.handle(cmd),
+            None => break,
+          },
+          Some((key, res)) = fs_rx.recv() => {
+            let handled = std::panic::catch_unwind(AssertUnwindSafe(|| sched.on_fs_event(key.clone(), res)));
+            if let Err(payload) = handled {
+              sched.restart_watch(&key, &crate::panics::message(&*payload));
+            }
+          }
+          _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => sched.run_due(),
+        }
+        let queued = sched.watches.values().filter(|j| j.trigger.is_some()).count();
+        crate::metrics::set_queue(queued, sched.in_flight);
+        if sched.in_flight == 0 {
+          if let Some(drained) = sched.drained.take() {
+            let _ = drained.send(());
+            break;
+          }
+        }
+      }
+    });
+    Self {
+      tx,
+      quitting: AtomicBool::new(false),
+    }
+  }
+
+  async fn ask<T>(&self, cmd: impl FnOnce(oneshot::Sender<T>) -> Command) -> Result<T, String> {
+    let (reply, rx) = oneshot::channel();
+    self.tx.send(cmd(reply)).map_err(|_| "sync scheduler stopped".to_string())?;
+    rx.await.map_err(|_| "sync scheduler stopped".to_string())
+  }
+
+  pub(crate) async fn start_pull(
+    &self,
+    vault_path: String,
+    project_folder_id: String,
+    auth: SupabaseAuth,
+    opts: PullOptions,
+  ) -> Result<(), String> {
+    self
+      .ask(|reply| Command::StartPull {
+        vault_path,
+        project_folder_id,
+        auth,
+        opts,
+        reply,
+      })
+      .await?
+  }
+
+  /// Stops every poller; returns the vaults they ran for.
+  pub(crate) async fn stop_pulls(&self) -> Result<Vec<String>, String> {
+    self.ask(|reply| Command::StopPulls { reply }).await
+  }
+
+  pub(crate) async fn start_watch(
+    &self,
+    vault_path: String,
+    project_folder_id: String,
+    auth: SupabaseAuth,
+    resumed: bool,
+  ) -> Result<(), String> {
+    self
+      .ask(|reply| Command::StartWatch {
+        vault_path,
+        project_folder_id,
+        auth,
+        resumed,
+        reply,
+      })
+      .await?
+  }
+
+  /// Stops every watcher; returns the vaults they ran for.
+  pub(crate) async fn stop_watches(&self) -> Result<Vec<String>, String> {
+    self.ask(|reply| Command::StopWatches { reply }).await
+  }
+
+  /// Stops the vault's poller and watcher; returns how many jobs were stopped.
+  pub(crate) async fn stop_vault(&self, vault_path: String) -> Result<usize, String> {
+    self.ask(|reply| Command::StopVault { vault_path, reply }).await
+  }
+
+  /// Stops the pollers and watchers syncing with one account; returns their vaults, and a receiver
+  /// that resolves once their in-flight runs (other than the pull under `except`) have finished.
+  pub(crate) async fn stop_account(
+    &self,
+    supabase_url: String,
+    owner_id: String,
+    except: String,
+  ) -> Result<(Vec<String>, oneshot::Receiver<()>), String> {
+    self
+      .ask(|reply| Command::StopAccount {
+        supabase_url,
+        owner_id,
+        except,
+        reply,
+      })
+      .await
+  }
+
+  /// Re-applies the vault's config to its running jobs. Called whenever the config is written.
+  pub(crate) fn reload_config(&self, vault_path: String) {
+    let _ = self.tx.send(Command::ReloadConfig { vault_path });
+  }
+
+  /// Pulls every polled vault now and resets their backoff, e.g. when the window gains focus
+  /// and the user expects fresh data.
+  pub(crate) fn wake(&self) {
+    let _ = self.tx.send(Command::Wake);
+  }
+
+  /// Stops all polling and watching for good, pushes changes still waiting out the debounce, waits
+  /// up to `SHUTDOWN_GRACE` for in-flight runs, then persists state and releases the vault locks
+  /// so the next launch (or another device's app) doesn't have to wait for them to go stale.
+  /// Does nothing when already shut down.
+  pub(crate) async fn shutdown(&self) {
+    let (drained, drained_rx) = oneshot::channel();
+    let Ok((vaults, in_flight)) = self.ask(|reply| Command::Shutdown { reply, drained }).await else {
+      return;
+    };
+    if let Some(app) = crate::notify::app() {
+      let _ = app.emit("sync://shutting_down", ShuttingDown { in_flight });
+    }
+    let _ = tokio::time::timeout(SHUTDOWN_GRACE, drained_rx).await;
+    for vault_path in vaults {
+      crate::activity::flush(&vault_path);
+      crate::lock::release(&vault_path);
+    }
+    crate::status::clear_sessions();
+  }
+
+  /// Quits the app once sync has shut down. Use instead of exiting directly.
+  pub(crate) fn quit(app: &tauri::AppHandle) {
+    let runtime = app.state::<SyncRuntime>();
+    if runtime.quitting.swap(true, Ordering::SeqCst) {
+      return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+      app.state::<SyncRuntime>().shutdown().await;
+      app.exit(0);
+    });
+  }
+
+  /// Whether `quit` has started; the exit request it ends with should go through.
+  pub(crate) fn quitting(&self) -> bool {
+    self.quitting.load(Ordering::SeqCst)
+  }
+}
@@ -0,0 +1,9 @@
+use tauri::Manager;
+
+pub(crate) use diregram_sync_core::secret_file::*;
+
+pub(crate) fn install(app: &tauri::AppHandle) {
+  if let Ok(dir) = app.path().app_config_dir() {
+    set_dir(dir);
+  }
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::Ordering;
+
+use crate::secret_file;
+use diregram_sync_core::KEYCHAIN_SERVICE;
+
+pub(crate) use diregram_sync_core::secrets::*;
+
+#[tauri::command]
+pub fn secure_storage_set(key: String, value: String, profile: Option<String>) -> Result<(), String> {
+  set(profile.as_deref(), &key, &value)
+}
+
+#[tauri::command]
+pub fn secure_storage_get(key: String, profile: Option<String>) -> Result<Option<String>, String> {
+  get(profile.as_deref(), &key)
+}
+
+#[tauri::command]
+pub fn secure_storage_remove(key: String, profile: Option<String>) -> Result<(), String> {
+  remove(profile.as_deref(), &key)
+}
+
+/// Keys written through `secure_storage_set` since profiles were introduced; older entries in the
+/// default profile show up once they are next saved.
+#[tauri::command]
+pub fn secure_storage_list_keys(profile: Option<String>) -> Result<Vec<String>, String> {
+  let profile = normalize_profile(profile.as_deref())?;
+  let mut keys = read_list(&service_for(&profile), INDEX_KEY)?;
+  keys.sort();
+  Ok(keys)
+}
+
+#[tauri::command]
+pub fn profile_list() -> Result<Vec<String>, String> {
+  let mut profiles = read_list(KEYCHAIN_SERVICE, PROFILES_KEY)?;
+  profiles.sort();
+  profiles.insert(0, DEFAULT_PROFILE.to_string());
+  Ok(profiles)
+}
+
+/// Removes every secret in the profile. The default profile is emptied but always remains.
+#[tauri::command]
+pub fn profile_delete(profile: String) -> Result<(), String> {
+  let profile = normalize_profile(Some(&profile))?;
+  let service = service_for(&profile);
+  for key in read_list(&service, INDEX_KEY)? {
+    raw_remove(&service, &key)?;
+  }
+  raw_remove(&service, INDEX_KEY)?;
+  if profile != DEFAULT_PROFILE {
+    let mut profiles = read_list(KEYCHAIN_SERVICE, PROFILES_KEY)?;
+    profiles.retain(|p| *p != profile);
+    write_list(KEYCHAIN_SERVICE, PROFILES_KEY, &profiles)?;
+  }
+  Ok(())
+}
+
+#[tauri::command]
+pub fn secure_storage_status() -> Result<SecureStorageStatus, String> {
+  let file = USE_FILE.load(Ordering::SeqCst);
+  Ok(SecureStorageStatus {
+    backend: if file { "file" } else { "keyring" }.to_string(),
+    passphrase_protected: file && secret_file::passphrase_protected(),
+    locked: file && secret_file::locked(),
+  })
+}
+
+/// Only meaningful for the file fallback; the OS keyring handles its own unlocking.
+#[tauri::command]
+pub fn secure_storage_unlock(passphrase: String) -> Result<(), String> {
+  secret_file::unlock(&passphrase)
+}
+
+/// Protects the file fallback with a passphrase instead of the machine-derived key (`None` reverts).
+#[tauri::command]
+pub fn secure_storage_set_passphrase(passphrase: Option<String>) -> Result<(), String> {
+  secret_file::set_passphrase(passphrase.as_deref())
+}
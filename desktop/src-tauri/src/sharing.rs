@@ -0,0 +1,20 @@
+use crate::sync::read_mapping;
+
+pub(crate) use diregram_sync_core::sharing::*;
+
+/// Owner and view-only status of every synced file, sorted by path.
+#[tauri::command]
+pub async fn sync_file_access(vault_path: String) -> Result<Vec<FileAccess>, String> {
+  let mapping = read_mapping(&vault_path)?.ok_or_else(|| "vault is not linked to a project".to_string())?;
+  let mut out: Vec<FileAccess> = mapping
+    .files
+    .into_iter()
+    .map(|(path, fm)| FileAccess {
+      path,
+      owner_id: fm.owner_id,
+      read_only: fm.read_only,
+    })
+    .collect();
+  out.sort_by(|a, b| a.path.cmp(&b.path));
+  Ok(out)
+}
@@ -0,0 +1,46 @@
+use crate::config::{read_config, write_config, ShredConfig};
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+pub(crate) use diregram_sync_core::shred::*;
+
+#[tauri::command]
+pub async fn shred_configure(vault_path: String, config: ShredConfig) -> Result<ShredConfig, String> {
+  let globs: Vec<String> = config
+    .globs
+    .iter()
+    .map(|g| g.trim().trim_start_matches('/').to_string())
+    .filter(|g| !g.is_empty())
+    .collect();
+  if let Some(g) = globs.iter().find(|g| g.split('/').any(|part| part == "..")) {
+    return Err(format!("{} must stay inside the vault", g));
+  }
+  let config = ShredConfig {
+    enabled: config.enabled,
+    globs,
+    passes: config.passes.clamp(1, MAX_PASSES),
+  };
+  let mut cfg = read_config(&vault_path)?;
+  let was = cfg.shred.enabled || !cfg.shred.globs.is_empty();
+  cfg.shred = config.clone();
+  write_config(&vault_path, &cfg)?;
+  let now = config.enabled || !config.globs.is_empty();
+  if was || now {
+    let detail = if !now {
+      "Secure wipe turned off; deleted files are archived to the trash again.".to_string()
+    } else if config.enabled {
+      format!("Secure wipe on for the whole vault ({} pass(es)).", config.passes)
+    } else {
+      format!("Secure wipe on for {} ({} pass(es)).", config.globs.join(", "), config.passes)
+    };
+    let _ = append_event(
+      &vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "shred_config".to_string(),
+        path: String::new(),
+        detail,
+      },
+    );
+  }
+  Ok(config)
+}
@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+pub(crate) use diregram_sync_core::site::*;
+
+/// Renders the vault's notes to a static HTML site in `dest_dir`: one page per note in the same
+/// folders, wiki-links and note links pointing between pages, and an index with search that works
+/// offline.
+#[tauri::command]
+pub async fn vault_export_html(
+  vault_path: String,
+  dest_dir: String,
+  options: Option<HtmlExportOptions>,
+) -> Result<HtmlExportSummary, String> {
+  if dest_dir.trim().is_empty() {
+    return Err("dest_dir is required".to_string());
+  }
+  let dest = PathBuf::from(dest_dir.trim());
+  let summary = export(&vault_path, &dest, &options.unwrap_or_default())?;
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "html_export".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Exported {} pages and {} assets to {} ({} broken links).",
+        summary.pages,
+        summary.assets,
+        dest.display(),
+        summary.broken_links
+      ),
+    },
+  );
+  Ok(summary)
+}
@@ -0,0 +1,43 @@
+use std::collections::BTreeMap;
+
+use crate::config::{read_config, write_config, SmartFolderQuery};
+
+pub(crate) use diregram_sync_core::smartfolders::*;
+
+/// Saves (or replaces) the smart folder `name` in the vault's config.
+#[tauri::command]
+pub async fn smart_folder_define(vault_path: String, name: String, query: SmartFolderQuery) -> Result<SmartFolderQuery, String> {
+  let name = name.trim().to_string();
+  if name.is_empty() {
+    return Err("name is empty".to_string());
+  }
+  let query = normalize(query)?;
+  let mut cfg = read_config(&vault_path)?;
+  cfg.smart_folders.insert(name, query.clone());
+  write_config(&vault_path, &cfg)?;
+  Ok(query)
+}
+
+#[tauri::command]
+pub async fn smart_folder_delete(vault_path: String, name: String) -> Result<(), String> {
+  let mut cfg = read_config(&vault_path)?;
+  if cfg.smart_folders.remove(name.trim()).is_none() {
+    return Err(format!("no smart folder named {}", name));
+  }
+  write_config(&vault_path, &cfg)
+}
+
+#[tauri::command]
+pub async fn smart_folder_list(vault_path: String) -> Result<BTreeMap<String, SmartFolderQuery>, String> {
+  Ok(read_config(&vault_path)?.smart_folders)
+}
+
+/// The vault files currently in the smart folder `name`.
+#[tauri::command]
+pub async fn smart_folder_evaluate(vault_path: String, name: String) -> Result<Vec<String>, String> {
+  let query = read_config(&vault_path)?
+    .smart_folders
+    .remove(name.trim())
+    .ok_or_else(|| format!("no smart folder named {}", name))?;
+  evaluate(&vault_path, &query)
+}
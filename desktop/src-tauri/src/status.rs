@@ -0,0 +1,11 @@
+pub(crate) use diregram_sync_core::status::*;
+
+#[tauri::command]
+pub async fn sync_status() -> Result<SyncStatusReport, String> {
+  Ok(SyncStatusReport {
+    paused: is_paused(),
+    vaults: snapshot(),
+    throughput: crate::throttle::report(),
+    policy: crate::policy::current(),
+  })
+}
@@ -0,0 +1,47 @@
+use std::collections::BTreeMap;
+
+use crate::config::{read_config, write_config, TagConfig};
+
+pub(crate) use diregram_sync_core::tags::*;
+
+/// Every tag in the vault with the number of notes carrying it, most used first.
+#[tauri::command]
+pub async fn tags_list(vault_path: String) -> Result<Vec<TagCount>, String> {
+  let index = refresh(&vault_path)?;
+  let mut counts: BTreeMap<&str, u32> = BTreeMap::new();
+  for tag in index.files.values().flat_map(|f| &f.tags) {
+    *counts.entry(tag).or_default() += 1;
+  }
+  let mut out: Vec<TagCount> = counts
+    .into_iter()
+    .map(|(tag, files)| TagCount {
+      tag: tag.to_string(),
+      files,
+    })
+    .collect();
+  out.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.tag.cmp(&b.tag)));
+  Ok(out)
+}
+
+/// Notes tagged `tag` or a tag nested under it (`tag/...`), sorted.
+#[tauri::command]
+pub async fn tags_files(vault_path: String, tag: String) -> Result<Vec<String>, String> {
+  let wanted = normalize(&tag).ok_or("not a valid tag")?;
+  let index = refresh(&vault_path)?;
+  Ok(
+    index
+      .files
+      .into_iter()
+      .filter(|(_, f)| f.tags.iter().any(|t| under(t, &wanted)))
+      .map(|(rel, _)| rel)
+      .collect(),
+  )
+}
+
+#[tauri::command]
+pub async fn tags_configure(vault_path: String, config: TagConfig) -> Result<TagConfig, String> {
+  let mut cfg = read_config(&vault_path)?;
+  cfg.tags = config.clone();
+  write_config(&vault_path, &cfg)?;
+  Ok(config)
+}
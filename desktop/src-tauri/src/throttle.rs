@@ -0,0 +1,19 @@
+pub(crate) use diregram_sync_core::throttle::*;
+
+/// Caps apply process-wide to sync and RAG transfers until changed; the UI re-applies its saved
+/// setting on startup.
+#[tauri::command]
+pub async fn sync_set_bandwidth_limit(limit: BandwidthLimit) -> Result<ThroughputReport, String> {
+  let to_bytes = |kbps: Option<u64>| kbps.filter(|k| *k > 0).map(|k| k * 1024);
+  {
+    let mut up = UPLOAD.lock().map_err(|_| "bandwidth state lock poisoned".to_string())?;
+    up.limit_bytes_per_sec = to_bytes(limit.upload_kbps);
+    up.next_free = None;
+  }
+  {
+    let mut down = DOWNLOAD.lock().map_err(|_| "bandwidth state lock poisoned".to_string())?;
+    down.limit_bytes_per_sec = to_bytes(limit.download_kbps);
+    down.next_free = None;
+  }
+  Ok(report())
+}
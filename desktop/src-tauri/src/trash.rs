@@ -0,0 +1,110 @@
+use std::fs;
+
+use chrono::Utc;
+
+use crate::sync::{
+  append_event, now_iso, read_mapping, relink_restored_file, write_mapping, SupabaseAuth,
+  SyncEvent,
+};
+
+pub(crate) use diregram_sync_core::trash::*;
+
+#[tauri::command]
+pub async fn trash_list(vault_path: String) -> Result<Vec<TrashEntry>, String> {
+  Ok(entries(&vault_path))
+}
+
+#[tauri::command]
+pub async fn trash_restore(vault_path: String, trash_rel: String) -> Result<String, String> {
+  let _mapping = crate::lock::mapping(&vault_path).await;
+  let (src, batch, rel) = plan_restore(&vault_path, &trash_rel)?;
+  move_out_of_trash(&vault_path, &src, &batch, &rel)?;
+  // A plain restore is pushed as a new file; the old remote id no longer applies.
+  forget_trashed(&vault_path, |k| k != trash_rel);
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "trash_restore".to_string(),
+      path: rel.clone(),
+      detail: format!("Restored from .diregram/trash/{}", trash_rel),
+    },
+  );
+  Ok(rel)
+}
+
+#[tauri::command]
+pub async fn trash_purge(vault_path: String, older_than_days: Option<u32>) -> Result<TrashPurgeSummary, String> {
+  let _mapping = crate::lock::mapping(&vault_path).await;
+  let days = older_than_days.unwrap_or(0);
+  let cutoff = Utc::now().naive_utc() - chrono::Duration::days(days as i64);
+  let names: Vec<String> = list_batches(&vault_path)
+    .into_iter()
+    .filter(|b| days == 0 || b.archived.map(|t| t < cutoff).unwrap_or(false))
+    .map(|b| b.name)
+    .collect();
+  let summary = purge_batches(&vault_path, &names);
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "trash_purge".to_string(),
+      path: ".diregram/trash/".to_string(),
+      detail: format!("Purged {} trashed files ({} bytes).", summary.files, summary.bytes),
+    },
+  );
+  Ok(summary)
+}
+
+/// Restores a file that a remote delete archived and pushes it back under its old remote id
+/// when possible, so it does not come back as a brand new file.
+#[tauri::command]
+pub async fn trash_restore_and_relink(
+  vault_path: String,
+  project_folder_id: String,
+  trash_rel: String,
+  auth: SupabaseAuth,
+) -> Result<TrashRelinkResult, String> {
+  let _mapping = crate::lock::mapping(&vault_path).await;
+  let mut mapping = read_mapping(&vault_path)?.ok_or_else(|| "vault is not linked (missing .diregram/sync.json)".to_string())?;
+  if mapping.project_folder_id != project_folder_id {
+    return Err("mapping project_folder_id mismatch".to_string());
+  }
+  let (src, batch, rel) = plan_restore(&vault_path, &trash_rel)?;
+  let bytes = fs::read(&src).map_err(|e| e.to_string())?;
+  let prior = mapping.trashed.get(&trash_rel).cloned();
+
+  let client = reqwest::Client::new();
+  let mut auth = auth;
+  let (fm, reused) = relink_restored_file(&client, &mut auth, &mut mapping, &rel, &bytes, prior.as_ref()).await?;
+  let file_id = fm.file_id.clone();
+
+  // Record the mapping before the file reappears so the watcher sees it as already pushed.
+  mapping.files.insert(rel.clone(), fm);
+  mapping.trashed.remove(&trash_rel);
+  mapping.updated_at = now_iso();
+  write_mapping(&vault_path, &mapping)?;
+  move_out_of_trash(&vault_path, &src, &batch, &rel)?;
+
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "trash_restore".to_string(),
+      path: rel.clone(),
+      detail: if reused {
+        format!("Restored from .diregram/trash/{} and relinked to remote file {}", trash_rel, file_id)
+      } else {
+        format!(
+          "Restored from .diregram/trash/{}; original remote id unavailable, pushed as new file {}",
+          trash_rel, file_id
+        )
+      },
+    },
+  );
+  Ok(TrashRelinkResult {
+    rel_path: rel,
+    file_id,
+    reused_file_id: reused,
+  })
+}
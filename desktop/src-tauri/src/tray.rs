@@ -0,0 +1,190 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tauri::image::Image;
+use tauri::menu::{MenuBuilder, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{Emitter, Manager};
+
+use crate::status::{self, SyncState};
+use crate::sync::{sync_initial_import, sync_pull_once};
+
+const TRAY_ID: &str = "main";
+/// Re-render at least this often so "Last pull: N min ago" stays current.
+const TRAY_REFRESH: Duration = Duration::from_secs(30);
+
+fn format_ago(ts: &str) -> String {
+  let Ok(t) = DateTime::parse_from_rfc3339(ts) else { return "unknown".to_string() };
+  let secs = (Utc::now() - t.with_timezone(&Utc)).num_seconds().max(0);
+  match secs {
+    0..=59 => "just now".to_string(),
+    60..=3599 => format!("{} min ago", secs / 60),
+    3600..=86399 => format!("{} h ago", secs / 3600),
+    _ => format!("{} d ago", secs / 86400),
+  }
+}
+
+/// Paints a status dot in the bottom-right corner of the app icon. Idle uses the plain icon.
+fn status_icon(base: &Image<'_>, state: SyncState) -> Image<'static> {
+  let (w, h) = (base.width(), base.height());
+  let mut rgba = base.rgba().to_vec();
+  let color: Option<[u8; 3]> = match state {
+    SyncState::Idle => None,
+    SyncState::Syncing => Some([0x3b, 0x82, 0xf6]),
+    SyncState::Error => Some([0xef, 0x44, 0x44]),
+    SyncState::Offline | SyncState::Unavailable => Some([0x9c, 0xa3, 0xaf]),
+    SyncState::Paused | SyncState::ManualOnly => Some([0xf5, 0x9e, 0x0b]),
+  };
+  if let Some([r, g, b]) = color {
+    let radius = (w.min(h) as f32) * 0.22;
+    let (cx, cy) = (w as f32 - radius - 1.0, h as f32 - radius - 1.0);
+    for y in 0..h {
+      for x in 0..w {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        if dx * dx + dy * dy <= radius * radius {
+          let i = ((y * w + x) * 4) as usize;
+          rgba[i..i + 4].copy_from_slice(&[r, g, b, 0xff]);
+        }
+      }
+    }
+  }
+  Image::new_owned(rgba, w, h)
+}
+
+/// Hands a folder or URL to the OS default handler (file manager / browser).
+pub(crate) fn open_with_os(target: &str) {
+  #[cfg(target_os = "macos")]
+  let cmd = "open";
+  #[cfg(target_os = "windows")]
+  let cmd = "explorer";
+  #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+  let cmd = "xdg-open";
+  if let Err(e) = std::process::Command::new(cmd).arg(target).spawn() {
+    tracing::warn!(error = %e, target, "could not open with the OS handler");
+  }
+}
+
+fn show_main(app: &tauri::AppHandle) {
+  if let Some(w) = app.get_webview_window("main") {
+    let _ = w.show();
+    let _ = w.set_focus();
+  }
+}
+
+/// Runs a push then a pull for every vault with an active watcher/poller.
+fn sync_now(app: &tauri::AppHandle) {
+  let sessions = status::sessions();
+  if sessions.is_empty() {
+    // Nothing running in the background yet; the UI is where sync gets started.
+    show_main(app);
+    return;
+  }
+  run_sessions(sessions);
+}
+
+/// Pushes then pulls each session in the background, as a run quitting waits for.
+pub(crate) fn run_sessions(sessions: Vec<status::SyncSession>) {
+  tauri::async_runtime::spawn(async move {
+    for s in sessions {
+      // Failures are logged by the sync spans and shown as the vault's status.
+      let _ = crate::scheduler::run_task(async {
+        let _ = sync_initial_import(s.vault_path.clone(), s.project_folder_id.clone(), s.auth.clone()).await;
+        let _ = sync_pull_once(s.vault_path, s.project_folder_id, s.auth, None).await;
+      })
+      .await;
+    }
+  });
+}
+
+fn toggle_pause(app: &tauri::AppHandle) {
+  let paused = !status::is_paused();
+  status::set_paused(paused);
+  let _ = app.emit("tray://sync_paused", paused);
+}
+
+fn open_vault_folder() {
+  let latest = status::snapshot().into_iter().max_by(|a, b| a.updated_at.cmp(&b.updated_at));
+  if let Some(v) = latest {
+    open_with_os(&v.vault_path);
+  }
+}
+
+pub(crate) fn build(handle: &tauri::AppHandle) -> tauri::Result<()> {
+  let status_item = MenuItem::with_id(handle, "status", "Status: Idle", false, None::<&str>)?;
+  let last_pull_item = MenuItem::with_id(handle, "last_pull", "Last pull: never", false, None::<&str>)?;
+  let pause_item = MenuItem::with_id(handle, "pause_sync", "Pause sync", true, None::<&str>)?;
+  let menu = MenuBuilder::new(handle)
+    .text("show", "Show")
+    .separator()
+    .item(&status_item)
+    .item(&last_pull_item)
+    .separator()
+    .text("sync_now", "Sync now")
+    .item(&pause_item)
+    .text("open_vault", "Open vault folder")
+    .text("open_logs", "Open log folder")
+    .separator()
+    .text("quit", "Quit")
+    .build()?;
+
+  // A build without a bundled icon runs without the tray rather than failing to start.
+  let Some(icon) = handle.default_window_icon().cloned() else {
+    tracing::warn!("no default window icon; the tray is not shown");
+    return Ok(());
+  };
+  TrayIconBuilder::with_id(TRAY_ID)
+    .icon(icon)
+    .tooltip("Diregram Sync")
+    .menu(&menu)
+    .on_menu_event(|app, event| match event.id.as_ref() {
+      "show" => show_main(app),
+      "sync_now" => sync_now(app),
+      "pause_sync" => toggle_pause(app),
+      "open_vault" => open_vault_folder(),
+      "open_logs" => crate::logging::open_folder(),
+      // Lets in-flight syncs finish first.
+      "quit" => crate::scheduler::SyncRuntime::quit(app),
+      _ => {}
+    })
+    .build(handle)?;
+
+  let rx = status::subscribe();
+  let app = handle.clone();
+  std::thread::spawn(move || {
+    let Some(base) = app.default_window_icon().map(|i| Image::new_owned(i.rgba().to_vec(), i.width(), i.height())) else {
+      return;
+    };
+    let mut rendered: Option<(String, String)> = None;
+    while let Ok(()) | Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(TRAY_REFRESH) {
+      // Collapse bursts (the poller reports every few seconds per vault).
+      while rx.try_recv().is_ok() {}
+
+      let (state, last_pull) = status::aggregate();
+      let policy = crate::policy::current();
+      let label = if state == SyncState::ManualOnly {
+        format!("{} ({})", state.label(), policy.reason)
+      } else {
+        state.label().to_string()
+      };
+      let pull_text = match last_pull {
+        Some(ts) => format!("Last pull: {}", format_ago(&ts)),
+        None => "Last pull: never".to_string(),
+      };
+      if rendered.as_ref().map(|(l, p)| *l == label && *p == pull_text).unwrap_or(false) {
+        continue;
+      }
+      if rendered.as_ref().map(|(l, _)| *l != label).unwrap_or(true) {
+        if let Some(tray) = app.tray_by_id(TRAY_ID) {
+          let _ = tray.set_icon(Some(status_icon(&base, state)));
+          let _ = tray.set_tooltip(Some(format!("Diregram Sync: {}", label)));
+        }
+        let _ = status_item.set_text(format!("Status: {}", label));
+        let _ = pause_item.set_text(if state == SyncState::Paused { "Resume sync" } else { "Pause sync" });
+      }
+      let _ = last_pull_item.set_text(&pull_text);
+      rendered = Some((label, pull_text));
+    }
+  });
+  Ok(())
+}
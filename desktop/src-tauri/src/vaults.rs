@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use tauri::Manager;
+
+use crate::autosync::PullSettingsV1;
+use crate::scheduler::SyncRuntime;
+use crate::sync::{now_iso, SupabaseAuth};
+
+pub(crate) use diregram_sync_core::vaults::*;
+
+/// Sets where the registry lives. Call once, from setup.
+pub(crate) fn install(app: &tauri::AppHandle) {
+  if let Ok(dir) = app.path().app_config_dir() {
+    set_dir(dir);
+  }
+}
+
+async fn start_one(runtime: &SyncRuntime, v: &RegisteredVault) -> Result<(), String> {
+  let auth = crate::auth::latest(&saved_auth(&v.account)?);
+  let s = &v.settings;
+  if s.watch {
+    crate::sync::start_watch(runtime, &v.vault_path, &v.project_folder_id, &auth, false).await?;
+    crate::autosync::note_watch(&v.vault_path, &v.project_folder_id, &auth);
+  }
+  if s.pull {
+    let pull = PullSettingsV1 {
+      interval_ms: s.pull_interval_ms,
+      max_interval_ms: s.max_pull_interval_ms,
+      force: None,
+    };
+    crate::sync::start_pull(runtime, &v.vault_path, &v.project_folder_id, &auth, &pull, false).await?;
+    crate::autosync::note_pull(&v.vault_path, &v.project_folder_id, &auth, pull);
+  }
+  Ok(())
+}
+
+async fn stop_one(runtime: &SyncRuntime, vault_path: &str) -> Result<(), String> {
+  // Each job held its own reference to the vault lock.
+  for _ in 0..runtime.stop_vault(vault_path.to_string()).await? {
+    crate::lock::release(vault_path);
+  }
+  crate::status::end_session(vault_path);
+  crate::autosync::forget(vault_path);
+  Ok(())
+}
+
+/// Adds a vault to the registry, or updates its name and settings. A vault is linked to one
+/// project; `sync_relink` moves it to another.
+#[tauri::command]
+pub async fn vaults_register(
+  vault_path: String,
+  project_folder_id: String,
+  auth: SupabaseAuth,
+  name: Option<String>,
+  settings: Option<VaultSettings>,
+) -> Result<RegisteredVault, String> {
+  if !Path::new(&vault_path).is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  if project_folder_id.trim().is_empty() {
+    return Err("project_folder_id is required".to_string());
+  }
+  let settings = settings.map(normalize_settings).transpose()?;
+  let account = VaultAccount {
+    supabase_url: auth.supabase_url.clone(),
+    supabase_anon_key: auth.supabase_anon_key.clone(),
+    owner_id: auth.owner_id.clone(),
+    profile: auth.profile.clone(),
+  };
+  let name = name.filter(|n| !n.trim().is_empty()).unwrap_or_else(|| {
+    Path::new(&vault_path)
+      .file_name()
+      .map(|n| n.to_string_lossy().to_string())
+      .unwrap_or_else(|| vault_path.clone())
+  });
+  update(|r| {
+    if let Some(v) = r.vaults.iter_mut().find(|v| v.vault_path == vault_path) {
+      if v.project_folder_id != project_folder_id {
+        return Err("This vault is registered with a different Diregram project.".to_string());
+      }
+      v.name = name;
+      v.account = account;
+      if let Some(s) = settings {
+        v.settings = s;
+      }
+      return Ok(v.clone());
+    }
+    let v = RegisteredVault {
+      vault_path: vault_path.clone(),
+      project_folder_id: project_folder_id.clone(),
+      name,
+      account,
+      settings: settings.unwrap_or_default(),
+      registered_at: now_iso(),
+    };
+    r.vaults.push(v.clone());
+    Ok(v)
+  })
+}
+
+#[tauri::command]
+pub async fn vaults_list() -> Result<VaultList, String> {
+  let statuses = crate::status::snapshot();
+  let vaults = load()
+    .vaults
+    .into_iter()
+    .map(|vault| VaultListEntry {
+      status: statuses.iter().find(|s| s.vault_path == vault.vault_path).cloned(),
+      vault,
+    })
+    .collect();
+  let (state, last_pull_at) = crate::status::aggregate();
+  Ok(VaultList {
+    vaults,
+    state,
+    last_pull_at,
+  })
+}
+
+/// Stops the vault's sync and removes it from the registry. Its files and mapping are untouched.
+#[tauri::command]
+pub async fn vaults_unregister(scheduler: tauri::State<'_, SyncRuntime>, vault_path: String) -> Result<(), String> {
+  stop_one(&scheduler, &vault_path).await?;
+  update(|r| {
+    r.vaults.retain(|v| v.vault_path != vault_path);
+    Ok(())
+  })
+}
+
+/// Starts watching and polling every registered vault, as its settings say. Vaults already
+/// running report that as their error.
+#[tauri::command]
+pub async fn vaults_start_all(scheduler: tauri::State<'_, SyncRuntime>) -> Result<Vec<VaultRunResult>, String> {
+  let mut out = Vec::new();
+  for v in load().vaults {
+    let error = start_one(&scheduler, &v).await.err();
+    out.push(VaultRunResult {
+      vault_path: v.vault_path,
+      error,
+    });
+  }
+  Ok(out)
+}
+
+#[tauri::command]
+pub async fn vaults_stop_all(scheduler: tauri::State<'_, SyncRuntime>) -> Result<Vec<VaultRunResult>, String> {
+  let mut out = Vec::new();
+  for v in load().vaults {
+    let error = stop_one(&scheduler, &v.vault_path).await.err();
+    out.push(VaultRunResult {
+      vault_path: v.vault_path,
+      error,
+    });
+  }
+  Ok(out)
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{diregram_dir, now_iso};
+
+/// Conflicts remembered per note; older ones are still in events.jsonl.
+const MAX_CONFLICTS_PER_FILE: usize = 20;
+
+/// Per-vault activity, loaded on first use and written out alongside the sync mapping.
+static ACTIVITY: Lazy<Mutex<HashMap<String, (ActivityStateV1, bool)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConflictRecord {
+  pub ts: String,
+  pub detail: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FileActivityV1 {
+  pub last_push_at: Option<String>,
+  pub last_pull_at: Option<String>,
+  pub conflicts: Vec<ConflictRecord>,
+}
+
+/// `.diregram/activity.json`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ActivityStateV1 {
+  version: u32,
+  pub files: HashMap<String, FileActivityV1>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSyncBadge {
+  /// Local file matches what was last synced.
+  Synced,
+  /// Edited locally since the last sync.
+  Modified,
+  /// Has an unresolved conflict copy.
+  Conflict,
+  /// Never synced.
+  LocalOnly,
+  /// Synced before but no longer on disk.
+  Missing,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileActivity {
+  pub path: String,
+  pub badge: FileSyncBadge,
+  pub last_local_edit: Option<String>,
+  pub last_push_at: Option<String>,
+  pub last_pull_at: Option<String>,
+  /// Newest first.
+  pub conflicts: Vec<ConflictRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecentActivity {
+  pub path: String,
+  /// `push`, `pull` or `conflict`.
+  pub kind: String,
+  pub ts: String,
+}
+
+fn activity_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("activity.json")
+}
+
+fn load(vault_path: &str) -> ActivityStateV1 {
+  fs::read_to_string(activity_path(vault_path))
+    .ok()
+    .and_then(|t| serde_json::from_str(&t).ok())
+    .unwrap_or(ActivityStateV1 {
+      version: 1,
+      files: HashMap::new(),
+    })
+}
+
+pub fn with_state<T>(vault_path: &str, f: impl FnOnce(&mut ActivityStateV1, &mut bool) -> T) -> Option<T> {
+  let mut guard = ACTIVITY.lock().ok()?;
+  let (state, dirty) = guard.entry(vault_path.to_string()).or_insert_with(|| (load(vault_path), false));
+  Some(f(state, dirty))
+}
+
+fn update(vault_path: &str, rel_path: &str, f: impl FnOnce(&mut FileActivityV1)) {
+  with_state(vault_path, |state, dirty| {
+    f(state.files.entry(rel_path.to_string()).or_default());
+    *dirty = true;
+  });
+}
+
+pub fn note_push(vault_path: &str, rel_path: &str) {
+  update(vault_path, rel_path, |a| a.last_push_at = Some(now_iso()));
+  crate::host::pushed(vault_path, rel_path);
+}
+
+pub fn note_pull(vault_path: &str, rel_path: &str) {
+  update(vault_path, rel_path, |a| a.last_pull_at = Some(now_iso()));
+}
+
+pub fn note_conflict(vault_path: &str, rel_path: &str, detail: &str) {
+  crate::metrics::note_conflict();
+  update(vault_path, rel_path, |a| {
+    a.conflicts.push(ConflictRecord {
+      ts: now_iso(),
+      detail: detail.to_string(),
+    });
+    let excess = a.conflicts.len().saturating_sub(MAX_CONFLICTS_PER_FILE);
+    a.conflicts.drain(..excess);
+  });
+}
+
+/// Notes pushed after `since` (RFC 3339), or ever.
+pub fn pushed_since(vault_path: &str, since: Option<&str>) -> u32 {
+  let since = since.and_then(|s| DateTime::parse_from_rfc3339(s).ok());
+  with_state(vault_path, |state, _| {
+    state
+      .files
+      .values()
+      .filter_map(|a| a.last_push_at.as_deref())
+      .filter(|ts| match (since, DateTime::parse_from_rfc3339(ts)) {
+        (Some(since), Ok(ts)) => ts > since,
+        _ => true,
+      })
+      .count() as u32
+  })
+  .unwrap_or(0)
+}
+
+/// Writes pending activity to disk. Called with every mapping write, so both stay in step.
+pub fn flush(vault_path: &str) {
+  let Some(Some(text)) = with_state(vault_path, |state, dirty| {
+    if !*dirty {
+      return None;
+    }
+    *dirty = false;
+    serde_json::to_string_pretty(state).ok()
+  }) else {
+    return;
+  };
+  let p = activity_path(vault_path);
+  let _ = crate::sync::write_atomic(&p, text);
+}
+
+pub fn mtime_iso(p: &Path) -> Option<String> {
+  let modified = fs::metadata(p).and_then(|m| m.modified()).ok()?;
+  Some(DateTime::<Utc>::from(modified).to_rfc3339())
+}
+
+/// The most recent push, pull or conflict of each note, newest first.
+pub fn recent(vault_path: &str, limit: usize) -> Vec<RecentActivity> {
+  let mut out: Vec<RecentActivity> = with_state(vault_path, |state, _| {
+    state
+      .files
+      .iter()
+      .filter_map(|(path, a)| {
+        [
+          ("push", a.last_push_at.as_ref()),
+          ("pull", a.last_pull_at.as_ref()),
+          ("conflict", a.conflicts.last().map(|c| &c.ts)),
+        ]
+        .into_iter()
+        .filter_map(|(kind, ts)| ts.map(|ts| (kind, ts)))
+        .max_by(|a, b| a.1.cmp(b.1))
+        .map(|(kind, ts)| RecentActivity {
+          path: path.clone(),
+          kind: kind.to_string(),
+          ts: ts.clone(),
+        })
+      })
+      .collect()
+  })
+  .unwrap_or_default();
+  out.sort_by(|a, b| b.ts.cmp(&a.ts));
+  out.truncate(limit);
+  out
+}
+
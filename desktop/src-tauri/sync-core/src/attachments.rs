@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{read_config, AttachmentBackend, AttachmentConfig};
+use crate::mirror::BoxFuture;
+use crate::sync::{
+  append_event, diregram_dir, now_iso, send_with_refresh, sha256_hex, SupabaseAuth, SyncEvent, SyncMappingV1,
+  SyncSummary,
+};
+
+/// Storage bucket holding attachment objects, under `<project_folder_id>/<sha256>`.
+const BUCKET: &str = "vault-attachments";
+/// Label of the project's own storage; mappings without a recorded store used it.
+const SUPABASE_STORE: &str = "supabase";
+
+/// Where attachment objects are kept remotely, per the vault's `attachments.backend`.
+pub trait ObjectStore: Send {
+  /// Recorded in the mapping, so switching stores uploads every object again.
+  fn label(&self) -> String;
+  /// Stores an object; one stored already (e.g. by another device) counts as stored.
+  fn put<'a>(&'a mut self, sha256: &'a str, bytes: Vec<u8>) -> BoxFuture<'a, Result<(), String>>;
+  /// Deletes an object; one already gone counts as deleted.
+  fn delete<'a>(&'a mut self, sha256: &'a str) -> BoxFuture<'a, Result<(), String>>;
+}
+
+/// The object a synced attachment's current content is stored as.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttachmentMappingV1 {
+  pub sha256: String,
+  pub size: u64,
+}
+
+/// A stored object and how many attachments in the vault have its content.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ObjectRefV1 {
+  pub refs: u32,
+  /// Present in the project's storage, uploaded by this vault or found there already.
+  #[serde(default)]
+  pub uploaded: bool,
+}
+
+/// Which files a push treats as attachments, per the vault's `attachments` config. Loaded once per
+/// push.
+pub struct AttachmentFilter {
+  config: Option<AttachmentConfig>,
+}
+
+impl AttachmentFilter {
+  pub fn load(vault_path: &str) -> Self {
+    Self {
+      config: read_config(vault_path).ok().map(|c| c.attachments).filter(|a| a.enabled),
+    }
+  }
+
+  pub fn enabled(&self) -> bool {
+    self.config.is_some()
+  }
+
+  pub fn matches(&self, path: &Path) -> bool {
+    let Some(cfg) = &self.config else { return false };
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    cfg.extensions.contains(&ext)
+      && fs::metadata(path).map(|m| m.len() <= cfg.max_bytes).unwrap_or(false)
+  }
+}
+
+/// Local copy of an object, sharded by the first two hex digits like git's.
+fn object_path(vault_path: &str, sha256: &str) -> PathBuf {
+  diregram_dir(vault_path).join("objects").join(&sha256[..2]).join(sha256)
+}
+
+/// Stores `bytes` under their hash unless an object with that hash is there already.
+fn store(vault_path: &str, sha256: &str, bytes: &[u8]) -> Result<(), String> {
+  let p = object_path(vault_path, sha256);
+  if p.exists() {
+    return Ok(());
+  }
+  if let Some(parent) = p.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  let tmp = p.with_extension("tmp");
+  fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+  fs::rename(&tmp, &p).map_err(|e| e.to_string())
+}
+
+fn object_url(auth: &SupabaseAuth, project_folder_id: &str, sha256: &str) -> String {
+  format!(
+    "{}/storage/v1/object/{}/{}/{}",
+    auth.supabase_url.trim_end_matches('/'),
+    BUCKET,
+    project_folder_id,
+    sha256
+  )
+}
+
+async fn upload(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+  sha256: &str,
+  bytes: Vec<u8>,
+) -> Result<(), String> {
+  let url = object_url(auth, project_folder_id, sha256);
+  send_with_refresh(
+    client,
+    auth,
+    || {
+      client
+        .post(&url)
+        .header("Content-Type", "application/octet-stream")
+        .header("x-upsert", "false")
+        .body(bytes.clone())
+    },
+    |res| {
+      Box::pin(async move {
+        let status = res.status();
+        if status.is_success() || status == reqwest::StatusCode::CONFLICT {
+          return Ok(());
+        }
+        // Older storage versions report an existing object as a 400 with a 409 inside.
+        let body = res.text().await.unwrap_or_default();
+        if status == reqwest::StatusCode::BAD_REQUEST && (body.contains("\"409\"") || body.contains("Duplicate")) {
+          return Ok(());
+        }
+        Err(format!("attachment upload failed: HTTP {}", status))
+      })
+    },
+  )
+  .await
+}
+
+async fn delete_remote(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+  sha256: &str,
+) -> Result<(), String> {
+  let url = object_url(auth, project_folder_id, sha256);
+  send_with_refresh(
+    client,
+    auth,
+    || client.delete(&url),
+    |res| {
+      Box::pin(async move {
+        if res.status().is_success() || res.status() == reqwest::StatusCode::NOT_FOUND {
+          Ok(())
+        } else {
+          Err(format!("attachment delete failed: HTTP {}", res.status()))
+        }
+      })
+    },
+  )
+  .await
+}
+
+/// The project's Supabase Storage, with the sync's session.
+struct SupabaseStore<'s> {
+  client: &'s reqwest::Client,
+  auth: &'s mut SupabaseAuth,
+  project_folder_id: &'s str,
+}
+
+impl ObjectStore for SupabaseStore<'_> {
+  fn label(&self) -> String {
+    SUPABASE_STORE.to_string()
+  }
+
+  fn put<'a>(&'a mut self, sha256: &'a str, bytes: Vec<u8>) -> BoxFuture<'a, Result<(), String>> {
+    Box::pin(upload(self.client, self.auth, self.project_folder_id, sha256, bytes))
+  }
+
+  fn delete<'a>(&'a mut self, sha256: &'a str) -> BoxFuture<'a, Result<(), String>> {
+    Box::pin(delete_remote(self.client, self.auth, self.project_folder_id, sha256))
+  }
+}
+
+fn open_store<'s>(
+  client: &'s reqwest::Client,
+  auth: &'s mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &'s str,
+) -> Result<Box<dyn ObjectStore + 's>, String> {
+  let cfg = read_config(vault_path)?.attachments;
+  match cfg.backend {
+    AttachmentBackend::Supabase => Ok(Box::new(SupabaseStore {
+      client,
+      auth,
+      project_folder_id,
+    })),
+    AttachmentBackend::S3 => {
+      let s3 = cfg.s3.as_ref().ok_or("attachments.backend is s3 but attachments.s3 is not set")?;
+      Ok(Box::new(crate::s3::S3Store::open(s3, project_folder_id)?))
+    }
+  }
+}
+
+fn acquire(mapping: &mut SyncMappingV1, sha256: &str) {
+  mapping.objects.entry(sha256.to_string()).or_default().refs += 1;
+}
+
+fn release(mapping: &mut SyncMappingV1, sha256: &str) {
+  if let Some(obj) = mapping.objects.get_mut(sha256) {
+    obj.refs = obj.refs.saturating_sub(1);
+  }
+}
+
+/// Syncs the attachments found by a push walk (vault-relative path -> file). Each distinct content
+/// is stored and uploaded once however many paths have it; attachments gone from paths the walk
+/// covered give up their reference. Objects left without references are then removed locally and
+/// remotely.
+#[allow(clippy::too_many_arguments)]
+pub async fn push(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &str,
+  mapping: &mut SyncMappingV1,
+  local: &HashMap<String, PathBuf>,
+  walked: impl Fn(&str) -> bool,
+  summary: &mut SyncSummary,
+) -> Result<(), String> {
+  let mut remote = match open_store(client, auth, vault_path, project_folder_id) {
+    Ok(s) => s,
+    Err(e) => {
+      summary.errors.push(format!("Attachments not synced: {}", e));
+      return Ok(());
+    }
+  };
+  let label = remote.label();
+  if mapping.attachment_store.as_deref().unwrap_or(SUPABASE_STORE) != label {
+    // Copies left in the previous store are not deleted.
+    for obj in mapping.objects.values_mut() {
+      obj.uploaded = false;
+    }
+    mapping.attachment_store = (label != SUPABASE_STORE).then_some(label);
+  }
+  for (rel, path) in local {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let sha256 = sha256_hex(&bytes);
+    let prev = mapping.attachments.get(rel).map(|a| a.sha256.clone());
+    let changed = prev.as_deref() != Some(sha256.as_str());
+    if changed {
+      store(vault_path, &sha256, &bytes)?;
+      acquire(mapping, &sha256);
+      if let Some(prev) = prev {
+        release(mapping, &prev);
+      }
+      mapping.attachments.insert(
+        rel.clone(),
+        AttachmentMappingV1 {
+          sha256: sha256.clone(),
+          size: bytes.len() as u64,
+        },
+      );
+    }
+    if mapping.objects.get(&sha256).map(|o| o.uploaded).unwrap_or(false) {
+      if changed {
+        summary.attachments_deduped += 1;
+      }
+      continue;
+    }
+    match remote.put(&sha256, bytes).await {
+      Ok(()) => {
+        if let Some(obj) = mapping.objects.get_mut(&sha256) {
+          obj.uploaded = true;
+        }
+        summary.attachments_uploaded += 1;
+        let _ = append_event(
+          vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "attachment_push".to_string(),
+            path: rel.clone(),
+            detail: format!("Uploaded object {}", sha256),
+          },
+        );
+      }
+      // Left marked as not uploaded, so the next push retries it.
+      Err(e) => summary.errors.push(format!("Attachment upload failed for {}: {}", rel, e)),
+    }
+  }
+
+  let gone: Vec<String> = mapping
+    .attachments
+    .keys()
+    .filter(|rel| !local.contains_key(*rel) && walked(rel))
+    .cloned()
+    .collect();
+  for rel in gone {
+    if let Some(a) = mapping.attachments.remove(&rel) {
+      release(mapping, &a.sha256);
+    }
+  }
+
+  gc(remote.as_mut(), vault_path, mapping, summary).await;
+  Ok(())
+}
+
+/// Removes objects no attachment refers to any more. Runs after every path of the push has taken
+/// or given up its reference, so content that only moved between paths is kept.
+async fn gc(
+  remote: &mut dyn ObjectStore,
+  vault_path: &str,
+  mapping: &mut SyncMappingV1,
+  summary: &mut SyncSummary,
+) {
+  let unreferenced: Vec<(String, bool)> = mapping
+    .objects
+    .iter()
+    .filter(|(_, o)| o.refs == 0)
+    .map(|(sha, o)| (sha.clone(), o.uploaded))
+    .collect();
+  for (sha256, uploaded) in unreferenced {
+    if uploaded {
+      if let Err(e) = remote.delete(&sha256).await {
+        // Kept in the mapping so the next push tries again.
+        summary.errors.push(format!("Attachment cleanup failed for object {}: {}", sha256, e));
+        continue;
+      }
+    }
+    let p = object_path(vault_path, &sha256);
+    let _ = fs::remove_file(&p);
+    // Only succeeds once the shard is empty.
+    if let Some(shard) = p.parent() {
+      let _ = fs::remove_dir(shard);
+    }
+    mapping.objects.remove(&sha256);
+  }
+}
+
@@ -0,0 +1,100 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::SupabaseAuth;
+
+pub const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Refresh this long before the access token's `exp`.
+pub const REFRESH_AHEAD_SECS: i64 = 5 * 60;
+
+/// The session background tasks use. Every refresh lands here, so a watcher started an hour ago
+/// doesn't keep retrying with the access (and rotated-out refresh) token it was started with.
+pub static SESSION: Lazy<Mutex<Option<SupabaseAuth>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuthExpiredEvent {
+  pub reason: String,
+}
+
+/// Makes `auth` the session the refresh daemon keeps alive.
+pub fn remember(auth: &SupabaseAuth) {
+  if let Ok(mut guard) = SESSION.lock() {
+    *guard = Some(auth.clone());
+  }
+}
+
+/// Both sessions belong to the same account on the same project.
+pub fn same_account(a: &SupabaseAuth, b: &SupabaseAuth) -> bool {
+  a.owner_id == b.owner_id && a.supabase_url == b.supabase_url
+}
+
+/// The freshest tokens for the same account as `auth` (which may be a stale copy held by a loop).
+pub fn latest(auth: &SupabaseAuth) -> SupabaseAuth {
+  match SESSION.lock().ok().and_then(|g| g.clone()) {
+    Some(s) if same_account(&s, auth) => s,
+    _ => auth.clone(),
+  }
+}
+
+/// Drops the remembered session if it belongs to the same account as `auth`, so the refresh daemon
+/// stops renewing it.
+pub fn forget(auth: &SupabaseAuth) {
+  if let Ok(mut guard) = SESSION.lock() {
+    if guard.as_ref().is_some_and(|s| same_account(s, auth)) {
+      *guard = None;
+    }
+  }
+}
+
+/// Profile of the remembered session, for tokens that arrive without one (auth deep links).
+pub fn current_profile() -> Option<String> {
+  SESSION.lock().ok().and_then(|g| g.as_ref().and_then(|s| s.profile.clone()))
+}
+
+/// Records tokens obtained outside the daemon (401 retry, auth deep link) for `auth`'s account.
+/// Sessions of other accounts keep their own tokens.
+pub fn store_tokens(auth: &SupabaseAuth) {
+  if let Ok(mut guard) = SESSION.lock() {
+    if let Some(s) = guard.as_mut().filter(|s| same_account(s, auth)) {
+      s.access_token = auth.access_token.clone();
+      s.refresh_token = auth.refresh_token.clone();
+    }
+  }
+  crate::status::update_session_tokens(auth);
+}
+
+/// The remembered session with the tokens of an auth deep link, if the link signs in the same
+/// user. `None` when nothing is remembered or the link is for another account.
+pub fn link_session(access_token: &str, refresh_token: &str) -> Option<SupabaseAuth> {
+  let mut auth = SESSION.lock().ok().and_then(|g| g.clone())?;
+  if jwt_claim(access_token, "sub")?.as_str()? != auth.owner_id {
+    return None;
+  }
+  auth.access_token = access_token.to_string();
+  auth.refresh_token = Some(refresh_token.to_string());
+  Some(auth)
+}
+
+/// A claim of a JWT, without verifying it (we only need to know when to refresh, and whose it is).
+fn jwt_claim(token: &str, name: &str) -> Option<serde_json::Value> {
+  let payload = token.split('.').nth(1)?;
+  let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+  let mut claims: serde_json::Value = serde_json::from_slice(&bytes).ok()?;
+  Some(claims.get_mut(name)?.take())
+}
+
+/// `exp` claim of a JWT.
+pub fn jwt_exp(token: &str) -> Option<i64> {
+  jwt_claim(token, "exp")?.as_i64()
+}
+
+/// Network hiccups are retried on the next tick; a rejected refresh token is not.
+pub fn is_permanent_refresh_error(err: &str) -> bool {
+  err.contains("missing refresh_token") || (err.contains("token refresh failed") && err.contains("HTTP 4"))
+}
+
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{append_event, now_iso, write_atomic, SupabaseAuth, SyncEvent};
+
+const FILE_NAME: &str = "autosync.json";
+/// Passed by the autostart entry; the app then starts in the tray without showing its window.
+pub const BACKGROUND_ARG: &str = "--background";
+
+static DIR: OnceCell<PathBuf> = OnceCell::new();
+/// Serializes read-modify-write cycles on the file.
+static FILE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// `<app config>/autosync.json`: what the frontend last asked to sync, so a launch (e.g. at login)
+/// resumes it before any window is opened. Tokens are not stored here; they are read from the
+/// auth profile's secure-storage slot.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AutosyncConfigV1 {
+  version: u32,
+  pub vaults: Vec<AutosyncVaultV1>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AutosyncVaultV1 {
+  pub vault_path: String,
+  pub project_folder_id: String,
+  pub supabase_url: String,
+  pub supabase_anon_key: String,
+  pub owner_id: String,
+  #[serde(default)]
+  pub profile: Option<String>,
+  #[serde(default)]
+  pub watch: bool,
+  #[serde(default)]
+  pub pull: Option<PullSettingsV1>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PullSettingsV1 {
+  pub interval_ms: u64,
+  pub max_interval_ms: u64,
+  #[serde(default)]
+  pub force: Option<bool>,
+}
+
+fn file_path() -> Option<PathBuf> {
+  DIR.get().map(|d| d.join(FILE_NAME))
+}
+
+pub fn load() -> AutosyncConfigV1 {
+  file_path()
+    .and_then(|p| fs::read_to_string(p).ok())
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+fn update(f: impl FnOnce(&mut AutosyncConfigV1)) {
+  let Some(path) = file_path() else { return };
+  let Ok(_guard) = FILE_LOCK.lock() else { return };
+  let mut config = load();
+  config.version = 1;
+  f(&mut config);
+  config.vaults.retain(|v| v.watch || v.pull.is_some());
+  if let Some(dir) = path.parent() {
+    let _ = fs::create_dir_all(dir);
+  }
+  if let Ok(text) = serde_json::to_string_pretty(&config) {
+    if let Err(e) = write_atomic(&path, text) {
+      tracing::warn!(error = %e, "could not save {}", FILE_NAME);
+    }
+  }
+}
+
+/// The entry for a vault/project, created from `auth` (whose account it then syncs with).
+fn entry<'a>(
+  config: &'a mut AutosyncConfigV1,
+  vault_path: &str,
+  project_folder_id: &str,
+  auth: &SupabaseAuth,
+) -> &'a mut AutosyncVaultV1 {
+  let i = match config
+    .vaults
+    .iter()
+    .position(|v| v.vault_path == vault_path && v.project_folder_id == project_folder_id)
+  {
+    Some(i) => i,
+    None => {
+      config.vaults.push(AutosyncVaultV1 {
+        vault_path: vault_path.to_string(),
+        project_folder_id: project_folder_id.to_string(),
+        supabase_url: String::new(),
+        supabase_anon_key: String::new(),
+        owner_id: String::new(),
+        profile: None,
+        watch: false,
+        pull: None,
+      });
+      config.vaults.len() - 1
+    }
+  };
+  let v = &mut config.vaults[i];
+  v.supabase_url = auth.supabase_url.clone();
+  v.supabase_anon_key = auth.supabase_anon_key.clone();
+  v.owner_id = auth.owner_id.clone();
+  v.profile = auth.profile.clone();
+  v
+}
+
+pub fn note_watch(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth) {
+  update(|c| entry(c, vault_path, project_folder_id, auth).watch = true);
+}
+
+pub fn note_pull(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth, settings: PullSettingsV1) {
+  update(|c| entry(c, vault_path, project_folder_id, auth).pull = Some(settings));
+}
+
+/// The user stopped watching; don't resume it next launch. Quitting doesn't call this.
+pub fn forget_watches() {
+  update(|c| c.vaults.iter_mut().for_each(|v| v.watch = false));
+}
+
+pub fn forget_pulls() {
+  update(|c| c.vaults.iter_mut().for_each(|v| v.pull = None));
+}
+
+/// Nothing of this account resumes next launch; other accounts' vaults are kept.
+pub fn forget_account(supabase_url: &str, owner_id: &str) {
+  update(|c| c.vaults.retain(|v| v.supabase_url != supabase_url || v.owner_id != owner_id));
+}
+
+pub fn forget(vault_path: &str) {
+  update(|c| c.vaults.retain(|v| v.vault_path != vault_path));
+}
+
+pub fn log(vault_path: &str, kind: &str, detail: String) {
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: kind.to_string(),
+      path: String::new(),
+      detail,
+    },
+  );
+}
+
+/// Sets where `autosync.json` lives. Call once, at startup.
+pub fn set_dir(dir: PathBuf) {
+  let _ = DIR.set(dir);
+}
+
@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::sync::{diregram_dir, sha256_hex};
+
+/// Content-addressed copies of note text, named by their sha256 (the key the sync mapping stores as
+/// `base_hash`), so identical versions are only kept once.
+fn blob_path(vault_path: &str, hash: &str) -> PathBuf {
+  diregram_dir(vault_path).join("blobs").join(hash)
+}
+
+pub fn put(vault_path: &str, text: &str) -> Result<String, String> {
+  let hash = sha256_hex(text.as_bytes());
+  let p = blob_path(vault_path, &hash);
+  if !p.exists() {
+    if let Some(parent) = p.parent() {
+      fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = p.with_extension("tmp");
+    fs::write(&tmp, text).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, &p).map_err(|e| e.to_string())?;
+  }
+  Ok(hash)
+}
+
+pub fn get(vault_path: &str, hash: &str) -> Option<String> {
+  if hash.is_empty() {
+    return None;
+  }
+  fs::read_to_string(blob_path(vault_path, hash)).ok()
+}
+
+pub fn remove(vault_path: &str, hash: &str) {
+  if !hash.is_empty() {
+    let _ = fs::remove_file(blob_path(vault_path, hash));
+  }
+}
@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{config_path, write_config, VaultConfigV1};
+use crate::sync::{
+  append_event, compute_subtree_folder_ids, init_mapping, now_iso, write_mapping, FolderNode,
+  SupabaseAuth, SyncEvent, SyncSummary,
+};
+use crate::tempfiles::{IGNORE_FILE, LEGACY_IGNORE_FILE};
+
+const IGNORE_TEMPLATE: &str = "\
+# File-name patterns Diregram never pushes, one per line. `*` matches any run of characters and
+# `?` a single one, e.g. `*.draft.md`. Editor swap/lock files and partial downloads are always
+# skipped.
+";
+
+/// Vaults being bootstrapped; their pulls report progress.
+static ACTIVE: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct BootstrapOptions {
+  /// Write a commented `.diregramignore` at the vault root, unless one (or a legacy
+  /// `.nexusmapignore`) exists.
+  pub seed_ignore_file: bool,
+  /// Write `.diregram/config.json` with the defaults, unless one exists.
+  pub seed_config: bool,
+}
+
+impl Default for BootstrapOptions {
+  fn default() -> Self {
+    Self {
+      seed_ignore_file: true,
+      seed_config: true,
+    }
+  }
+}
+
+/// Emitted as `sync://bootstrap_progress`. `phase` is `folders`, `files` or `resources`.
+#[derive(Debug, Serialize, Clone)]
+pub struct BootstrapProgress {
+  pub vault_path: String,
+  pub phase: String,
+  pub done: usize,
+  pub total: usize,
+  pub path: String,
+}
+
+/// Reports a step of a bootstrap's pull. Does nothing for vaults that aren't being bootstrapped.
+pub fn progress(vault_path: &str, phase: &str, done: usize, total: usize, path: &str) {
+  if !ACTIVE.lock().map(|a| a.contains(vault_path)).unwrap_or(false) {
+    return;
+  }
+  // Throttled for large projects: first, last and every 25th item.
+  if done != 1 && done != total && !done.is_multiple_of(25) {
+    return;
+  }
+  crate::host::emit(
+    "sync://bootstrap_progress",
+    BootstrapProgress {
+      vault_path: vault_path.to_string(),
+      phase: phase.to_string(),
+      done,
+      total,
+      path: path.to_string(),
+    },
+  );
+}
+
+/// Creates the project's folders in the vault and records them in the mapping, so empty folders
+/// show up too and the pull finds every folder already linked.
+async fn create_folders(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth) -> Result<u32, String> {
+  let _mapping = crate::lock::mapping(vault_path).await;
+  let client = reqwest::Client::new();
+  let mut auth = auth.clone();
+  let mut mapping = init_mapping(vault_path, project_folder_id, false, false)?;
+  let folders = crate::folders::fetch_folders(&client, &mut auth, vault_path, project_folder_id).await?;
+  let folder_ids = compute_subtree_folder_ids(project_folder_id, &folders);
+  let folders_by_id: HashMap<String, FolderNode> = folders.into_iter().map(|f| (f.id.clone(), f)).collect();
+  let scope = crate::vaults::SyncScope::load(vault_path);
+  let root = Path::new(vault_path);
+
+  let mut created = 0;
+  for (i, id) in folder_ids.iter().enumerate() {
+    let Some(rel) = crate::names::local_folder(&mut mapping, project_folder_id, id, &folders_by_id) else { continue };
+    progress(vault_path, "folders", i + 1, folder_ids.len(), &rel);
+    if rel.is_empty() || !scope.admits_dir(&rel) {
+      continue;
+    }
+    let abs = crate::normalize::local_path(root, &rel);
+    if !abs.is_dir() {
+      fs::create_dir_all(&abs).map_err(|e| e.to_string())?;
+      created += 1;
+    }
+    mapping.folders.insert(rel, id.clone());
+  }
+  write_mapping(vault_path, &mapping)?;
+  Ok(created)
+}
+
+fn seed(vault_path: &str, options: &BootstrapOptions) -> Result<(), String> {
+  let ignore = Path::new(vault_path).join(IGNORE_FILE);
+  if options.seed_ignore_file && !ignore.exists() && !Path::new(vault_path).join(LEGACY_IGNORE_FILE).exists() {
+    fs::write(&ignore, IGNORE_TEMPLATE).map_err(|e| e.to_string())?;
+  }
+  if options.seed_config && !config_path(vault_path).exists() {
+    write_config(vault_path, &VaultConfigV1::default())?;
+  }
+  Ok(())
+}
+
+/// Clones a project into an empty folder: links it, creates the folder tree, seeds the ignore
+/// file and config, and pulls every file and resource. Running it again on a vault already linked
+/// to the project just catches up.
+pub async fn bootstrap(
+  vault_path: &str,
+  project_folder_id: &str,
+  auth: &SupabaseAuth,
+  options: &BootstrapOptions,
+) -> Result<SyncSummary, String> {
+  if vault_path.trim().is_empty() {
+    return Err("vault_path is required".to_string());
+  }
+  fs::create_dir_all(vault_path).map_err(|e| e.to_string())?;
+  if let Ok(mut active) = ACTIVE.lock() {
+    active.insert(vault_path.to_string());
+  }
+  let res = async {
+    let folders_created = create_folders(vault_path, project_folder_id, auth).await?;
+    seed(vault_path, options)?;
+    let mut summary =
+      crate::sync::sync_pull_once(vault_path.to_string(), project_folder_id.to_string(), auth.clone(), None).await?;
+    summary.folders_created += folders_created;
+    Ok::<_, String>(summary)
+  }
+  .await;
+  if let Ok(mut active) = ACTIVE.lock() {
+    active.remove(vault_path);
+  }
+
+  let summary = res?;
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "bootstrap".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Cloned project {}. Folders created: {}, files: {}. Errors: {}.",
+        project_folder_id,
+        summary.folders_created,
+        summary.files_created,
+        summary.errors.len()
+      ),
+    },
+  );
+  Ok(summary)
+}
+
@@ -0,0 +1,736 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::sync::{
+  detect_kind, diregram_dir, is_extensionless_path, is_ignored_rel, is_markdown_path, looks_like_text_utf8, read_mapping,
+  sha256_hex, to_rel_posix,
+};
+
+const DEFAULT_MAX_CHARS: usize = 2200;
+const DEFAULT_OVERLAP_CHARS: usize = 200;
+const MIN_MAX_CHARS: usize = 200;
+const DEFAULT_MAX_TOKENS: usize = 300;
+const DEFAULT_OVERLAP_TOKENS: usize = 40;
+const MIN_MAX_TOKENS: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+  /// Sections split at headings, their paragraphs packed up to `max_chars`.
+  #[default]
+  Heading,
+  /// Every paragraph of a section on its own (long ones still split at `max_chars`).
+  Paragraph,
+  /// Runs of lines holding about `max_tokens` words each, overlapping by `overlap_tokens`,
+  /// regardless of headings.
+  Fixed,
+}
+
+/// How notes are cut into chunks. Stored per vault as `rag.chunking` in `.diregram/config.json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChunkOptions {
+  #[serde(default)]
+  pub strategy: ChunkStrategy,
+  #[serde(default = "default_max_chars")]
+  pub max_chars: usize,
+  #[serde(default = "default_overlap_chars")]
+  pub overlap_chars: usize,
+  /// For `fixed`; words stand in for tokens.
+  #[serde(default = "default_max_tokens")]
+  pub max_tokens: usize,
+  #[serde(default = "default_overlap_tokens")]
+  pub overlap_tokens: usize,
+  /// Code fences become chunks of their own, never split or mixed with prose.
+  #[serde(default)]
+  pub code_blocks: bool,
+}
+
+fn default_max_chars() -> usize {
+  DEFAULT_MAX_CHARS
+}
+
+fn default_overlap_chars() -> usize {
+  DEFAULT_OVERLAP_CHARS
+}
+
+fn default_max_tokens() -> usize {
+  DEFAULT_MAX_TOKENS
+}
+
+fn default_overlap_tokens() -> usize {
+  DEFAULT_OVERLAP_TOKENS
+}
+
+impl Default for ChunkOptions {
+  fn default() -> Self {
+    Self {
+      strategy: ChunkStrategy::default(),
+      max_chars: DEFAULT_MAX_CHARS,
+      overlap_chars: DEFAULT_OVERLAP_CHARS,
+      max_tokens: DEFAULT_MAX_TOKENS,
+      overlap_tokens: DEFAULT_OVERLAP_TOKENS,
+      code_blocks: false,
+    }
+  }
+}
+
+/// One chunk record in `.diregram/chunks.jsonl`. Field names follow the server's
+/// embeddings export so the file can be fed to the ingest pipeline unchanged.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalChunk {
+  #[serde(rename = "type")]
+  pub record_type: String,
+  pub id: String,
+  pub file_id: Option<String>,
+  pub resource_id: Option<String>,
+  pub file_kind: String,
+  pub anchor: String,
+  pub text: String,
+  pub rel_path: String,
+  /// 0-based, inclusive line range of the chunk body in the source file.
+  pub line_start: usize,
+  pub line_end: usize,
+  pub hash: String,
+}
+
+/// Where a chunk's text is in the vault now.
+#[derive(Debug, Serialize, Clone)]
+pub struct ChunkLocation {
+  pub rel_path: String,
+  /// 1-based, inclusive.
+  pub line_start: usize,
+  pub line_end: usize,
+  /// `exact` (the chunk is unchanged), `fuzzy` (most of its lines are still together) or
+  /// `section` (only its heading is left; the range is that section).
+  pub method: String,
+}
+
+/// A chunk of the RAG export (`rag/rag_chunks.jsonl`) or of `.diregram/chunks.jsonl`, as far as
+/// finding it again goes.
+#[derive(Deserialize)]
+pub struct ChunkRef {
+  pub id: String,
+  #[serde(default, alias = "fileId")]
+  pub file_id: Option<String>,
+  #[serde(default, alias = "resourceId")]
+  pub resource_id: Option<String>,
+  #[serde(default)]
+  pub anchor: Option<String>,
+  pub text: String,
+  #[serde(default, alias = "relPath")]
+  pub rel_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChunkSummary {
+  pub files: u32,
+  pub chunks: u32,
+  pub output_path: String,
+}
+
+pub fn chunks_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("chunks.jsonl")
+}
+
+fn slugify_heading(text: &str) -> String {
+  let mut out = String::new();
+  let mut dash = false;
+  for ch in text.trim().to_lowercase().chars() {
+    if ch.is_alphanumeric() {
+      out.push(ch);
+      dash = false;
+    } else if !dash {
+      out.push('-');
+      dash = true;
+    }
+  }
+  let slug = out.trim_matches('-').to_string();
+  if slug.is_empty() {
+    "section".to_string()
+  } else {
+    slug
+  }
+}
+
+fn parse_heading(line: &str) -> Option<(usize, String)> {
+  let t = line.trim_start();
+  let level = t.chars().take_while(|c| *c == '#').count();
+  if level == 0 || level > 6 {
+    return None;
+  }
+  let rest = &t[level..];
+  if !rest.starts_with(char::is_whitespace) {
+    return None;
+  }
+  let text = rest.trim();
+  if text.is_empty() {
+    return None;
+  }
+  Some((level, text.to_string()))
+}
+
+struct Section {
+  anchor: String,
+  heading_line: Option<String>,
+  /// (line index, text) of the body lines.
+  lines: Vec<(usize, String)>,
+}
+
+/// The opening run of a code fence on `line`: three or more backticks or tildes (a backtick
+/// fence's info string can't hold backticks).
+pub fn fence_marker(line: &str) -> Option<(char, usize)> {
+  let line = line.trim_start();
+  let ch = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+  let len = line.len() - line.trim_start_matches(ch).len();
+  let info = &line[len..];
+  (len >= 3 && !(ch == '`' && info.contains('`'))).then_some((ch, len))
+}
+
+/// Code fences seen line by line. A fence closes only on a line of its own marker character at
+/// least as long as the opening run, so a ``` inside a ~~~~ block is just code.
+#[derive(Default)]
+pub struct Fences {
+  open: Option<(char, usize)>,
+}
+
+impl Fences {
+  /// Takes the next line; true when it opens or closes a fence.
+  pub fn toggles(&mut self, line: &str) -> bool {
+    match self.open {
+      Some((ch, len)) => {
+        let closes = fence_marker(line).is_some_and(|(c, n)| c == ch && n >= len)
+          && line.trim_start().trim_start_matches(ch).trim().is_empty();
+        if closes {
+          self.open = None;
+        }
+        closes
+      }
+      None => {
+        self.open = fence_marker(line);
+        self.open.is_some()
+      }
+    }
+  }
+
+  pub fn inside(&self) -> bool {
+    self.open.is_some()
+  }
+}
+
+/// Splits markdown at headings (outside code fences). Text before the first heading
+/// becomes an `intro` section.
+fn split_sections(markdown: &str) -> Vec<Section> {
+  let mut sections: Vec<Section> = vec![Section {
+    anchor: "intro".to_string(),
+    heading_line: None,
+    lines: Vec::new(),
+  }];
+  let mut occ_by_slug: HashMap<String, u32> = HashMap::new();
+  let mut fences = Fences::default();
+  for (i, line) in markdown.lines().enumerate() {
+    fences.toggles(line);
+    if !fences.inside() {
+      if let Some((_, text)) = parse_heading(line) {
+        let slug = slugify_heading(&text);
+        let occ = occ_by_slug.entry(slug.clone()).or_insert(0);
+        *occ += 1;
+        sections.push(Section {
+          anchor: format!("heading:{}:{}", slug, occ),
+          heading_line: Some(line.trim().to_string()),
+          lines: vec![(i, line.to_string())],
+        });
+        continue;
+      }
+    }
+    if let Some(cur) = sections.last_mut() {
+      cur.lines.push((i, line.to_string()));
+    }
+  }
+  sections
+}
+
+/// `heading:<slug>:<occurrence>` anchors of the note's headings, with their 0-based line.
+pub fn heading_anchors(markdown: &str) -> Vec<(String, usize)> {
+  split_sections(markdown)
+    .into_iter()
+    .filter(|s| s.heading_line.is_some())
+    .filter_map(|s| Some((s.anchor, s.lines.first()?.0)))
+    .collect()
+}
+
+/// Groups section lines into paragraphs separated by blank lines, never splitting inside a
+/// code fence. With `fences_apart`, a fence is also a paragraph of its own.
+fn split_paragraphs(lines: &[(usize, String)], fences_apart: bool) -> Vec<Vec<(usize, String)>> {
+  let mut out: Vec<Vec<(usize, String)>> = Vec::new();
+  let mut cur: Vec<(usize, String)> = Vec::new();
+  let mut fences = Fences::default();
+  for (i, line) in lines {
+    if fences.toggles(line) {
+      let in_fence = fences.inside();
+      if fences_apart && in_fence && !cur.is_empty() {
+        out.push(std::mem::take(&mut cur));
+      }
+      if fences_apart && !in_fence {
+        cur.push((*i, line.clone()));
+        out.push(std::mem::take(&mut cur));
+        continue;
+      }
+    }
+    if !fences.inside() && line.trim().is_empty() {
+      if !cur.is_empty() {
+        out.push(std::mem::take(&mut cur));
+      }
+      continue;
+    }
+    cur.push((*i, line.clone()));
+  }
+  if !cur.is_empty() {
+    out.push(cur);
+  }
+  out
+}
+
+fn overlap_tail(text: &str, overlap_chars: usize) -> String {
+  if overlap_chars == 0 {
+    return String::new();
+  }
+  let chars: Vec<char> = text.chars().collect();
+  if chars.len() <= overlap_chars {
+    return text.to_string();
+  }
+  let tail: String = chars[chars.len() - overlap_chars..].iter().collect();
+  // Start the overlap on a word boundary so chunks don't begin mid-word.
+  match tail.find(char::is_whitespace) {
+    Some(idx) => tail[idx..].trim_start().to_string(),
+    None => tail,
+  }
+}
+
+struct Piece {
+  text: String,
+  line_start: usize,
+  line_end: usize,
+}
+
+fn pack_paragraphs(paragraphs: Vec<Vec<(usize, String)>>, opts: &ChunkOptions) -> Vec<Piece> {
+  let max_chars = opts.max_chars.max(MIN_MAX_CHARS);
+  let overlap = opts.overlap_chars.min(max_chars / 2);
+  let mut out: Vec<Piece> = Vec::new();
+  let mut buf = String::new();
+  let mut start: Option<usize> = None;
+  let mut end = 0usize;
+
+  for para in paragraphs {
+    let text = para.iter().map(|(_, l)| l.as_str()).collect::<Vec<_>>().join("\n");
+    let p_start = para.first().map(|(i, _)| *i).unwrap_or(0);
+    let p_end = para.last().map(|(i, _)| *i).unwrap_or(p_start);
+    let code = opts.code_blocks && text.lines().next().and_then(fence_marker).is_some();
+    let alone = code || opts.strategy == ChunkStrategy::Paragraph;
+    let fits = buf.chars().count() + text.chars().count() + 2 <= max_chars;
+    if !buf.is_empty() && (alone || !fits) {
+      // Overlap only carries between pieces of the same run of prose.
+      let carry = if alone { String::new() } else { overlap_tail(&buf, overlap) };
+      out.push(Piece {
+        text: std::mem::take(&mut buf),
+        line_start: start.unwrap_or(p_start),
+        line_end: end,
+      });
+      buf = carry;
+      start = None;
+    }
+    if !buf.is_empty() {
+      buf.push_str("\n\n");
+    }
+    buf.push_str(&text);
+    start.get_or_insert(p_start);
+    end = p_end;
+    if code {
+      out.push(Piece {
+        text: std::mem::take(&mut buf),
+        line_start: p_start,
+        line_end: p_end,
+      });
+      start = None;
+      continue;
+    }
+
+    // A single oversized paragraph is hard-split on character boundaries.
+    while buf.chars().count() > max_chars {
+      let head: String = buf.chars().take(max_chars).collect();
+      let rest: String = buf.chars().skip(max_chars).collect();
+      let carry = overlap_tail(&head, overlap);
+      out.push(Piece {
+        text: head,
+        line_start: start.unwrap_or(p_start),
+        line_end: p_end,
+      });
+      buf = format!("{}{}", carry, rest);
+      start = Some(p_start);
+    }
+    if opts.strategy == ChunkStrategy::Paragraph && !buf.trim().is_empty() {
+      out.push(Piece {
+        text: std::mem::take(&mut buf),
+        line_start: start.unwrap_or(p_start),
+        line_end: p_end,
+      });
+      start = None;
+    }
+  }
+  if !buf.trim().is_empty() {
+    out.push(Piece {
+      text: buf,
+      line_start: start.unwrap_or(0),
+      line_end: end,
+    });
+  }
+  out
+}
+
+/// Runs of whole lines for the `fixed` strategy: each holds about `max_tokens` words and starts
+/// with the lines of the last `overlap_tokens` words of the one before. With `code_blocks`, code
+/// fences are runs of their own and the windows flow around them.
+fn fixed_windows(markdown: &str, opts: &ChunkOptions) -> Vec<Piece> {
+  let max = opts.max_tokens.max(MIN_MAX_TOKENS);
+  let overlap = opts.overlap_tokens.min(max / 2);
+  let lines: Vec<&str> = markdown.lines().collect();
+  let words: Vec<usize> = lines.iter().map(|l| l.split_whitespace().count()).collect();
+
+  // (first line, end, is a code fence)
+  let mut segments: Vec<(usize, usize, bool)> = Vec::new();
+  let mut from = 0;
+  let mut fence: Option<usize> = None;
+  if opts.code_blocks {
+    let mut fences = Fences::default();
+    for (i, line) in lines.iter().enumerate() {
+      if !fences.toggles(line) {
+        continue;
+      }
+      match fence.take() {
+        None => {
+          segments.push((from, i, false));
+          fence = Some(i);
+        }
+        Some(open) => {
+          segments.push((open, i + 1, true));
+          from = i + 1;
+        }
+      }
+    }
+  }
+  // An unclosed fence is just text.
+  segments.push((fence.unwrap_or(from), lines.len(), false));
+
+  let piece = |a: usize, b: usize| Piece {
+    text: lines[a..b].join("\n"),
+    line_start: a,
+    line_end: b.saturating_sub(1).max(a),
+  };
+  let mut out = Vec::new();
+  for (a, b, code) in segments {
+    if code {
+      out.push(piece(a, b));
+      continue;
+    }
+    let mut start = a;
+    loop {
+      while start < b && lines[start].trim().is_empty() {
+        start += 1;
+      }
+      if start >= b {
+        break;
+      }
+      let mut end = start;
+      let mut n = 0;
+      while end < b && (end == start || n < max) {
+        n += words[end];
+        end += 1;
+      }
+      let last = (start + 1..=end).rev().find(|e| !lines[e - 1].trim().is_empty()).unwrap_or(end);
+      out.push(piece(start, last));
+      if end >= b {
+        break;
+      }
+      let mut next = end;
+      let mut carried = 0;
+      while next > start + 1 && carried < overlap {
+        next -= 1;
+        carried += words[next];
+      }
+      start = next;
+    }
+  }
+  out
+}
+
+/// Chunks a single markdown document. `owner_key` is the remote id (or a local
+/// placeholder) used to build stable chunk ids.
+pub fn chunk_markdown(
+  markdown: &str,
+  owner_key: &str,
+  rel_path: &str,
+  file_kind: &str,
+  opts: &ChunkOptions,
+) -> Vec<LocalChunk> {
+  let mut out: Vec<LocalChunk> = Vec::new();
+  if opts.strategy == ChunkStrategy::Fixed {
+    for (n, piece) in fixed_windows(markdown, opts).into_iter().enumerate() {
+      let text = piece.text.trim().to_string();
+      if text.is_empty() {
+        continue;
+      }
+      let anchor = format!("window:{}", n + 1);
+      out.push(LocalChunk {
+        record_type: "chunk".to_string(),
+        id: format!("chunk:{}:{}", owner_key, anchor),
+        file_id: None,
+        resource_id: None,
+        file_kind: file_kind.to_string(),
+        anchor,
+        hash: sha256_hex(text.as_bytes()),
+        text,
+        rel_path: rel_path.to_string(),
+        line_start: piece.line_start,
+        line_end: piece.line_end,
+      });
+    }
+    return out;
+  }
+  for sec in split_sections(markdown) {
+    let body_lines: Vec<(usize, String)> = if sec.heading_line.is_some() {
+      sec.lines.iter().skip(1).cloned().collect()
+    } else {
+      sec.lines.clone()
+    };
+    let mut pieces = pack_paragraphs(split_paragraphs(&body_lines, opts.code_blocks), opts);
+    if pieces.is_empty() {
+      // Heading-only sections still get a chunk so the heading is searchable.
+      match (sec.heading_line.as_ref(), sec.lines.first()) {
+        (Some(_), Some((i, _))) => pieces.push(Piece {
+          text: String::new(),
+          line_start: *i,
+          line_end: *i,
+        }),
+        _ => continue,
+      }
+    }
+    let multi = pieces.len() > 1;
+    for (n, piece) in pieces.into_iter().enumerate() {
+      let anchor = if multi {
+        format!("{}:chunk:{}", sec.anchor, n + 1)
+      } else {
+        sec.anchor.clone()
+      };
+      let text = match sec.heading_line.as_ref() {
+        Some(h) if piece.text.trim().is_empty() => h.clone(),
+        Some(h) => format!("{}\n\n{}", h, piece.text.trim()),
+        None => piece.text.trim().to_string(),
+      };
+      if text.trim().is_empty() {
+        continue;
+      }
+      let line_start = if n == 0 {
+        sec.lines.first().map(|(i, _)| *i).unwrap_or(piece.line_start)
+      } else {
+        piece.line_start
+      };
+      out.push(LocalChunk {
+        record_type: "chunk".to_string(),
+        id: format!("chunk:{}:{}", owner_key, anchor),
+        file_id: None,
+        resource_id: None,
+        file_kind: file_kind.to_string(),
+        anchor,
+        hash: sha256_hex(text.as_bytes()),
+        text,
+        rel_path: rel_path.to_string(),
+        line_start,
+        line_end: piece.line_end.max(line_start),
+      });
+    }
+  }
+  out
+}
+
+pub fn chunk_vault(vault_path: &str, opts: &ChunkOptions) -> Result<(Vec<LocalChunk>, u32), String> {
+  let root = Path::new(vault_path);
+  if !root.exists() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let mapping = read_mapping(vault_path)?;
+  let mut chunks: Vec<LocalChunk> = Vec::new();
+  let mut files: u32 = 0;
+
+  for entry in WalkDir::new(root)
+    .follow_links(false)
+    .sort_by_file_name()
+    .into_iter()
+    .filter_map(Result::ok)
+  {
+    let p = entry.path();
+    if !entry.file_type().is_file() {
+      continue;
+    }
+    if p.components().any(|c| c.as_os_str() == ".diregram") {
+      continue;
+    }
+    let rel = match to_rel_posix(root, p) {
+      Some(r) => r,
+      None => continue,
+    };
+    let is_resource = rel.starts_with("resources/");
+    if is_ignored_rel(&rel) && !is_resource {
+      continue;
+    }
+    if !is_markdown_path(p) && !is_extensionless_path(p) {
+      continue;
+    }
+    let bytes = fs::read(p).map_err(|e| e.to_string())?;
+    if !looks_like_text_utf8(&bytes) {
+      continue;
+    }
+    let content = String::from_utf8_lossy(&bytes).to_string();
+
+    let (owner_key, file_id, resource_id, kind) = if is_resource {
+      let rid = mapping
+        .as_ref()
+        .and_then(|m| m.resources.get(&rel))
+        .map(|r| r.resource_id.clone());
+      let key = match rid.as_ref() {
+        Some(id) => format!("resource:{}", id),
+        None => format!("local:{}", rel),
+      };
+      (key, None, rid, "resource".to_string())
+    } else {
+      let fid = mapping.as_ref().and_then(|m| m.files.get(&rel)).map(|f| f.file_id.clone());
+      let key = fid.clone().unwrap_or_else(|| format!("local:{}", rel));
+      (key, fid, None, detect_kind(&content))
+    };
+
+    let mut file_chunks = chunk_markdown(&content, &owner_key, &rel, &kind, opts);
+    for c in file_chunks.iter_mut() {
+      c.file_id = file_id.clone();
+      c.resource_id = resource_id.clone();
+    }
+    files += 1;
+    chunks.append(&mut file_chunks);
+  }
+
+  Ok((chunks, files))
+}
+
+pub fn find_chunk(vault_path: &str, chunk_id: &str) -> Option<ChunkRef> {
+  [Path::new(vault_path).join("rag").join("rag_chunks.jsonl"), chunks_path(vault_path)]
+    .iter()
+    .filter_map(|p| fs::read_to_string(p).ok())
+    .find_map(|text| {
+      text
+        .lines()
+        .filter(|l| l.contains(chunk_id))
+        .filter_map(|l| serde_json::from_str::<ChunkRef>(l).ok())
+        .find(|c| c.id == chunk_id)
+    })
+}
+
+/// 0-based, inclusive line range of `chunk` in `content`, and how it was found.
+pub fn locate(content: &str, rel_path: &str, chunk: &ChunkRef, opts: &ChunkOptions) -> Option<(usize, usize, &'static str)> {
+  let anchor = chunk.anchor.as_deref().unwrap_or("");
+  // Unchanged: chunking the note again yields the same text, preferably under the same anchor.
+  let fresh = chunk_markdown(content, "", rel_path, "", opts);
+  let same = |c: &&LocalChunk| c.text.trim() == chunk.text.trim();
+  if let Some(c) = fresh.iter().filter(same).find(|c| c.anchor == anchor).or_else(|| fresh.iter().find(same)) {
+    return Some((c.line_start, c.line_end, "exact"));
+  }
+
+  // Edited or moved: the run of non-blank lines sharing the most lines with the chunk, if that is
+  // at least half of them.
+  let lines: Vec<&str> = content.lines().collect();
+  let want: Vec<&str> = chunk.text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+  let have: Vec<usize> = (0..lines.len()).filter(|i| !lines[*i].trim().is_empty()).collect();
+  let mut best: Option<(usize, usize, usize)> = None;
+  if !want.is_empty() {
+    for window in have.windows(want.len().min(have.len()).max(1)) {
+      let mut left: HashMap<&str, usize> = HashMap::new();
+      for l in &want {
+        *left.entry(l).or_default() += 1;
+      }
+      let matched: Vec<usize> = window
+        .iter()
+        .copied()
+        .filter(|i| match left.get_mut(lines[*i].trim()) {
+          Some(n) if *n > 0 => {
+            *n -= 1;
+            true
+          }
+          _ => false,
+        })
+        .collect();
+      if let (Some(first), Some(last)) = (matched.first(), matched.last()) {
+        if best.map(|b| matched.len() > b.0).unwrap_or(true) {
+          best = Some((matched.len(), *first, *last));
+        }
+      }
+    }
+  }
+  if let Some((score, first, last)) = best {
+    if score * 2 >= want.len() {
+      return Some((first, last, "fuzzy"));
+    }
+  }
+
+  // Rewritten: the section the chunk came from, if its heading is still there.
+  let section = anchor.split(":chunk:").next().unwrap_or(anchor);
+  let headings = heading_anchors(content);
+  let start = if section == "intro" {
+    0
+  } else {
+    headings.iter().find(|(a, _)| a == section)?.1
+  };
+  let next = headings.iter().map(|(_, l)| *l).find(|l| *l > start).unwrap_or(lines.len());
+  let end = (start..next).rev().find(|i| !lines[*i].trim().is_empty()).unwrap_or(start);
+  (start < lines.len()).then_some((start, end, "section"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{fence_marker, heading_anchors, Fences};
+
+  fn inside_per_line(markdown: &str) -> Vec<bool> {
+    let mut fences = Fences::default();
+    markdown
+      .lines()
+      .map(|line| fences.toggles(line) || fences.inside())
+      .collect()
+  }
+
+  #[test]
+  fn tilde_fences_open_and_close() {
+    assert_eq!(fence_marker("~~~"), Some(('~', 3)));
+    assert_eq!(fence_marker("  ~~~~ text"), Some(('~', 4)));
+    assert_eq!(inside_per_line("a\n~~~\n# code\n~~~\nb"), [false, true, true, true, false]);
+  }
+
+  #[test]
+  fn a_longer_closing_run_closes_a_fence_a_shorter_one_does_not() {
+    assert_eq!(inside_per_line("```\nx\n`````\ny"), [true, true, true, false]);
+    assert_eq!(inside_per_line("````\nx\n```\ny\n````\nz"), [true, true, true, true, true, false]);
+  }
+
+  #[test]
+  fn an_info_string_line_inside_a_fence_is_code() {
+    // Only a bare marker closes; "```rust" inside a fence doesn't, nor does the other character.
+    assert_eq!(inside_per_line("```\n```rust\n~~~\n```\nafter"), [true, true, true, true, false]);
+    assert_eq!(fence_marker("``` a`b"), None);
+  }
+
+  #[test]
+  fn headings_inside_fences_are_not_sections() {
+    let md = "# One\n~~~~\n# not a heading\n```\n# still code\n~~~~\n# Two\n";
+    let anchors: Vec<String> = heading_anchors(md).into_iter().map(|(a, _)| a).collect();
+    assert_eq!(anchors, ["heading:one:1", "heading:two:1"]);
+  }
+}
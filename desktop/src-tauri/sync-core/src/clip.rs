@@ -0,0 +1,100 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{read_config, ClipConfig};
+use crate::sync::{append_event, now_iso, write_atomic, SyncEvent};
+
+/// A page or selection captured by the browser clipper, or pasted from the clipboard.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Clip {
+  pub title: String,
+  pub markdown: String,
+  #[serde(default)]
+  pub source_url: Option<String>,
+  #[serde(default)]
+  pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ClipResult {
+  /// Vault-relative path of the new note.
+  pub path: String,
+  /// `false` when the vault has no sync session; the next push picks the note up.
+  pub pushed: bool,
+  pub ingest_started: bool,
+}
+
+/// Double-quoted YAML scalar.
+fn yaml_string(s: &str) -> String {
+  format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " "))
+}
+
+fn render(clip: &Clip, title: &str) -> String {
+  let mut out = format!("---\ntitle: {}\n", yaml_string(title));
+  if let Some(url) = clip.source_url.as_deref().filter(|u| !u.trim().is_empty()) {
+    out.push_str(&format!("source: {}\n", yaml_string(url.trim())));
+  }
+  out.push_str(&format!("clipped: {}\n", now_iso()));
+  let tags: Vec<String> = clip
+    .tags
+    .iter()
+    .map(|t| t.trim().trim_start_matches('#'))
+    .filter(|t| !t.is_empty())
+    .map(yaml_string)
+    .collect();
+  if !tags.is_empty() {
+    out.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+  }
+  out.push_str("---\n\n");
+  out.push_str(clip.markdown.trim_end());
+  out.push('\n');
+  out
+}
+
+/// The configured folder, if notes may be written there (see `notes::note_rel`).
+pub fn clip_folder(cfg: &ClipConfig) -> Option<String> {
+  let folder = crate::normalize::safe_rel_path(&cfg.folder)?;
+  crate::notes::note_rel(&format!("{}/clip.md", folder)).map(|_| folder)
+}
+
+/// Writes the clip as a new note in the configured folder, named after its title (with ` 2`, ` 3`,
+/// ... when taken). Returns the note's vault-relative path.
+pub fn write(vault_path: &str, clip: &Clip) -> Result<String, String> {
+  if !Path::new(vault_path).is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let folder = clip_folder(&read_config(vault_path)?.clip).ok_or("clip.folder must be a visible folder inside the vault, outside resources/ and rag/")?;
+  let title = clip.title.trim();
+  let title = if title.is_empty() { "Clip" } else { title };
+  let stem = crate::names::local_name(title, false);
+  let mut rel = format!("{}/{}.md", folder, stem);
+  let mut n = 2;
+  while Path::new(vault_path).join(&rel).exists() {
+    rel = format!("{}/{} {}.md", folder, stem, n);
+    n += 1;
+  }
+  let abs = Path::new(vault_path).join(&rel);
+  if let Some(parent) = abs.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  write_atomic(&abs, render(clip, title).as_bytes()).map_err(|e| e.to_string())?;
+  let source = clip
+    .source_url
+    .as_deref()
+    .filter(|u| !u.trim().is_empty())
+    .map(|u| format!(" from {}", u.trim()))
+    .unwrap_or_default();
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "clip".to_string(),
+      path: rel.clone(),
+      detail: format!("Clipped{}.", source),
+    },
+  );
+  Ok(rel)
+}
+
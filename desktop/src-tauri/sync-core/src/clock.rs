@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+
+use crate::sync::{append_event, now_iso, SupabaseAuth, SyncEvent};
+
+/// Skew beyond this is reported to the user; timestamp-based conflict checks get unreliable.
+pub const MAX_CLOCK_SKEW_SECS: i64 = 60;
+/// The `Date` header only has whole seconds; anything smaller is measurement noise.
+const MIN_CORRECTED_SKEW_MS: i64 = 2_000;
+/// Polling syncs reuse a measurement for this long.
+const REMEASURE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Supabase URL -> (local clock minus server clock in ms, when it was measured).
+static SKEW: Lazy<Mutex<HashMap<String, (i64, Instant)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Local clock minus server clock, in seconds, from a response's `Date` header.
+pub fn skew_from(res: &reqwest::Response) -> Option<i64> {
+  skew_ms_from(res).map(|ms| ms / 1000)
+}
+
+fn skew_ms_from(res: &reqwest::Response) -> Option<i64> {
+  let server = res
+    .headers()
+    .get(reqwest::header::DATE)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| DateTime::parse_from_rfc2822(v).ok())?;
+  Some(Utc::now().signed_duration_since(server).num_milliseconds())
+}
+
+fn cached_ms(auth: &SupabaseAuth) -> Option<i64> {
+  SKEW.lock().ok()?.get(&auth.supabase_url).map(|(ms, _)| *ms)
+}
+
+/// Measures the clock skew against the server at the start of a sync, warning the user (event +
+/// notification) when it is too large. Best effort: an unreachable server keeps the last value.
+pub async fn measure(client: &reqwest::Client, auth: &SupabaseAuth, vault_path: &str) {
+  let fresh = SKEW
+    .lock()
+    .ok()
+    .and_then(|g| g.get(&auth.supabase_url).map(|(_, at)| at.elapsed() < REMEASURE_AFTER))
+    .unwrap_or(false);
+  if fresh {
+    return;
+  }
+  let url = format!("{}/auth/v1/health", auth.supabase_url.trim_end_matches('/'));
+  let Ok(res) = client.get(url).header("apikey", &auth.supabase_anon_key).send().await else {
+    return;
+  };
+  let Some(ms) = skew_ms_from(&res) else { return };
+  let ms = if ms.abs() < MIN_CORRECTED_SKEW_MS { 0 } else { ms };
+  let previous = cached_ms(auth);
+  if let Ok(mut g) = SKEW.lock() {
+    g.insert(auth.supabase_url.clone(), (ms, Instant::now()));
+  }
+
+  let secs = ms / 1000;
+  let was_skewed = previous.map(|p| (p / 1000).abs() > MAX_CLOCK_SKEW_SECS).unwrap_or(false);
+  if secs.abs() <= MAX_CLOCK_SKEW_SECS || was_skewed {
+    return;
+  }
+  let detail = format!(
+    "This computer's clock is {}s {} the server. Sync compensates, but fix the system time.",
+    secs.abs(),
+    if secs > 0 { "ahead of" } else { "behind" }
+  );
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "clock_skew".to_string(),
+      path: String::new(),
+      detail: detail.clone(),
+    },
+  );
+  crate::notify::notify(vault_path, crate::notify::NotifyKind::ClockSkew, "System clock is off", &detail, None);
+}
+
+/// The current time by the server's clock, as far as it was last measured. Use for timestamps
+/// that are sent to the server or compared with ones it produced.
+pub fn server_now_iso(auth: &SupabaseAuth) -> String {
+  let ms = cached_ms(auth).unwrap_or(0);
+  (Utc::now() - chrono::Duration::milliseconds(ms)).to_rfc3339()
+}
+
+/// `a` is strictly later than `b`. Compares instants, so offsets and precision don't matter;
+/// falls back to string order for unparseable values.
+pub fn is_after(a: &str, b: &str) -> bool {
+  match (DateTime::parse_from_rfc3339(a), DateTime::parse_from_rfc3339(b)) {
+    (Ok(a), Ok(b)) => a > b,
+    _ => a > b,
+  }
+}
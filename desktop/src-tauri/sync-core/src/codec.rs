@@ -0,0 +1,66 @@
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::config::read_config;
+
+/// Compressed bodies are marked in `content` the same way encrypted ones are, so rows written by
+/// older clients (plain text) keep decoding unchanged.
+const GZIP_PREFIX: &str = "diregram-gz:v1:";
+
+/// `content` as stored remotely, plus how much smaller it is than the plaintext.
+pub struct Encoded {
+  pub content: String,
+  pub bytes_saved: u64,
+}
+
+fn gzip(plaintext: &str) -> Result<String, String> {
+  let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+  enc.write_all(plaintext.as_bytes()).map_err(|e| e.to_string())?;
+  let bytes = enc.finish().map_err(|e| e.to_string())?;
+  Ok(format!("{}{}", GZIP_PREFIX, STANDARD.encode(bytes)))
+}
+
+fn gunzip(body: &str) -> Result<String, String> {
+  let bytes = STANDARD.decode(body).map_err(|e| format!("corrupt compressed content: {}", e))?;
+  let mut out = String::new();
+  GzDecoder::new(bytes.as_slice())
+    .read_to_string(&mut out)
+    .map_err(|e| format!("corrupt compressed content: {}", e))?;
+  Ok(out)
+}
+
+/// Compresses above the vault's threshold (when that actually wins after base64), then encrypts
+/// if E2EE is on. Compression must come first: ciphertext doesn't compress.
+pub fn encode(vault_path: &str, project_folder_id: &str, plaintext: &str) -> Result<Encoded, String> {
+  let cfg = read_config(vault_path)?.compression;
+  let mut body = plaintext.to_string();
+  if cfg.enabled && plaintext.len() as u64 >= cfg.threshold_bytes {
+    let packed = gzip(plaintext)?;
+    if packed.len() < plaintext.len() {
+      body = packed;
+    }
+  }
+  let bytes_saved = plaintext.len().saturating_sub(body.len()) as u64;
+  Ok(Encoded {
+    content: crate::e2ee::seal(vault_path, project_folder_id, &body)?,
+    bytes_saved,
+  })
+}
+
+/// Inverse of `encode` for pulled content; returns the plaintext and the bytes compression saved.
+pub fn decode(project_folder_id: &str, content: &str) -> Result<(String, u64), String> {
+  let opened = crate::e2ee::open(project_folder_id, content)?;
+  match opened.strip_prefix(GZIP_PREFIX) {
+    Some(body) => {
+      let plain = gunzip(body)?;
+      let saved = plain.len().saturating_sub(opened.len()) as u64;
+      Ok((plain, saved))
+    }
+    None => Ok((opened, 0)),
+  }
+}
@@ -0,0 +1,809 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::chunk::ChunkOptions;
+use crate::sync::{append_event, diregram_dir, now_iso, sha256_hex, SyncEvent};
+
+/// sha256 of each vault's config file as last applied, to tell hand edits from our own writes.
+static APPLIED: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Per-vault settings stored next to the sync mapping in `.diregram/config.json`.
+/// Every section defaults so older files (or a missing file) keep working.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct VaultConfigV1 {
+  pub version: u32,
+  pub mode: SyncMode,
+  /// Vault-relative folder -> mode for the files in it, overriding `mode`; the deepest match wins.
+  pub folder_modes: BTreeMap<String, SyncMode>,
+  pub backup: BackupConfig,
+  pub trash: TrashConfig,
+  pub notifications: NotificationConfig,
+  pub e2ee: E2eeConfig,
+  pub compression: CompressionConfig,
+  pub delta: DeltaConfig,
+  pub revisions: RevisionConfig,
+  pub symlinks: SymlinkConfig,
+  pub tempfiles: TempFileConfig,
+  pub daily_note: DailyNoteConfig,
+  pub mass_delete_guard: MassDeleteGuardConfig,
+  pub watch: WatchConfig,
+  pub links: LinkConfig,
+  pub attachments: AttachmentConfig,
+  pub git: GitConfig,
+  pub mirror: MirrorConfig,
+  pub clip: ClipConfig,
+  pub rag: RagConfig,
+  pub tags: TagConfig,
+  /// Saved searches by name, which the app shows as folders.
+  pub smart_folders: BTreeMap<String, SmartFolderQuery>,
+  pub routing: RoutingConfig,
+  pub shred: ShredConfig,
+}
+
+impl Default for VaultConfigV1 {
+  fn default() -> Self {
+    Self {
+      version: 1,
+      mode: SyncMode::default(),
+      folder_modes: BTreeMap::new(),
+      backup: BackupConfig::default(),
+      trash: TrashConfig::default(),
+      notifications: NotificationConfig::default(),
+      e2ee: E2eeConfig::default(),
+      compression: CompressionConfig::default(),
+      delta: DeltaConfig::default(),
+      revisions: RevisionConfig::default(),
+      symlinks: SymlinkConfig::default(),
+      tempfiles: TempFileConfig::default(),
+      daily_note: DailyNoteConfig::default(),
+      mass_delete_guard: MassDeleteGuardConfig::default(),
+      watch: WatchConfig::default(),
+      links: LinkConfig::default(),
+      attachments: AttachmentConfig::default(),
+      git: GitConfig::default(),
+      mirror: MirrorConfig::default(),
+      clip: ClipConfig::default(),
+      rag: RagConfig::default(),
+      tags: TagConfig::default(),
+      smart_folders: BTreeMap::new(),
+      routing: RoutingConfig::default(),
+      shred: ShredConfig::default(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+  #[default]
+  Bidirectional,
+  /// Pulls only. Local edits and deletions are reported as `read_only_edit` events, never pushed.
+  #[serde(alias = "pull_only")]
+  ReadOnly,
+  /// Pushes only. Remote edits and deletions never touch the vault; pushes overwrite them.
+  PushOnly,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+  pub enabled: bool,
+  /// Directory the rotating snapshots are written to. Must live outside the vault.
+  pub backup_dir: Option<String>,
+  pub interval_minutes: u64,
+  /// Number of snapshots kept; older ones are deleted after each run.
+  pub retention: u32,
+  pub include_rag: bool,
+}
+
+impl Default for BackupConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      backup_dir: None,
+      interval_minutes: 360,
+      retention: 10,
+      include_rag: false,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TrashConfig {
+  /// Trash batches older than this are purged. `None` keeps them forever.
+  pub max_age_days: Option<u32>,
+  /// Oldest batches are purged until the trash fits under this size. `None` means unlimited.
+  pub max_bytes: Option<u64>,
+}
+
+impl Default for TrashConfig {
+  fn default() -> Self {
+    Self {
+      max_age_days: Some(30),
+      max_bytes: Some(512 * 1024 * 1024),
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NotificationConfig {
+  pub enabled: bool,
+  pub conflicts: bool,
+  pub push_failures: bool,
+  pub auth_expiry: bool,
+  /// Consecutive failed background pushes before the user is notified.
+  pub push_failure_threshold: u32,
+}
+
+impl Default for NotificationConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      conflicts: true,
+      push_failures: true,
+      auth_expiry: true,
+      push_failure_threshold: 3,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct E2eeConfig {
+  /// Encrypt note content before it is pushed. The key itself lives in the keyring, never here.
+  pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CompressionConfig {
+  pub enabled: bool,
+  /// Note bodies at least this large are gzip-compressed before upload.
+  pub threshold_bytes: u64,
+}
+
+impl Default for CompressionConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      threshold_bytes: 64 * 1024,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DeltaConfig {
+  pub enabled: bool,
+  /// Notes at least this large push only their changes since the last sync. They are stored
+  /// uncompressed remotely, since patches apply to plain text.
+  pub min_bytes: u64,
+}
+
+impl Default for DeltaConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      min_bytes: 256 * 1024,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RevisionConfig {
+  /// Keep a local copy of each note after every successful push and pull.
+  pub enabled: bool,
+  /// Oldest revisions beyond this are deleted, per note.
+  pub max_per_file: u32,
+}
+
+impl Default for RevisionConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      max_per_file: 20,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+  /// Symlinks are never synced, and pulls never write through them.
+  Ignore,
+  /// Symlinks are synced as the file or folder they point to, as long as that is inside the vault.
+  #[default]
+  FollowWithinVault,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SymlinkConfig {
+  pub policy: SymlinkPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TempFileConfig {
+  /// File-name patterns (`*`, `?`) never pushed, on top of the built-in editor temp/lock files.
+  pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DailyNoteConfig {
+  pub enabled: bool,
+  /// Local time (`HH:MM`) after which today's note is created.
+  pub time: String,
+  /// Vault-relative folder the notes go in.
+  pub folder: String,
+  /// chrono format of the file name, without `.md`.
+  pub filename_format: String,
+  /// Vault-relative template file; `{{date}}`, `{{title}}` and `{{weekday}}` are filled in.
+  pub template: Option<String>,
+  pub notify: bool,
+}
+
+impl Default for DailyNoteConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      time: "08:00".to_string(),
+      folder: "Daily".to_string(),
+      filename_format: "%Y-%m-%d".to_string(),
+      template: None,
+      notify: true,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MassDeleteGuardConfig {
+  pub enabled: bool,
+  /// A pull that would archive more than this share of the mapped notes waits for confirmation.
+  pub max_percent: u32,
+  /// Deletions below this count always go through, so small vaults aren't held up.
+  pub min_files: u32,
+}
+
+impl Default for MassDeleteGuardConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      max_percent: 50,
+      min_files: 5,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchStrategy {
+  /// Filesystem events, or periodic scans when the vault is on a network drive.
+  #[default]
+  Auto,
+  Events,
+  /// Periodic scans only, for mounts that deliver no (or unreliable) events.
+  Scan,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WatchConfig {
+  pub mode: WatchStrategy,
+  /// Seconds between scans in scan mode.
+  pub scan_interval_secs: u64,
+}
+
+impl Default for WatchConfig {
+  fn default() -> Self {
+    Self {
+      mode: WatchStrategy::Auto,
+      scan_interval_secs: 30,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LinkConfig {
+  /// Push relative links between notes as `nexus://file/<id>` references and pull them back.
+  pub rewrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AttachmentConfig {
+  pub enabled: bool,
+  /// Extensions (lowercase, without the dot) pushed as attachments.
+  pub extensions: Vec<String>,
+  /// Larger files are left local.
+  pub max_bytes: u64,
+  /// Where the objects are stored remotely.
+  pub backend: AttachmentBackend,
+  /// Required when `backend` is `s3`.
+  pub s3: Option<S3Config>,
+}
+
+impl Default for AttachmentConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      extensions: ["png", "jpg", "jpeg", "gif", "webp", "svg", "pdf"].iter().map(|e| e.to_string()).collect(),
+      max_bytes: 50 * 1024 * 1024,
+      backend: AttachmentBackend::Supabase,
+      s3: None,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentBackend {
+  /// The project's Supabase Storage.
+  #[default]
+  Supabase,
+  /// Any S3-compatible endpoint (AWS, MinIO, R2).
+  S3,
+}
+
+/// The secret key is kept in secure storage (see `attachments_set_s3_secret`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct S3Config {
+  /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://nas.local:9000`.
+  pub endpoint: String,
+  /// `auto` for R2.
+  pub region: String,
+  pub bucket: String,
+  /// Objects are stored as `<prefix>/<project_folder_id>/<sha256>`.
+  pub prefix: String,
+  pub access_key_id: String,
+  /// Address the bucket as `<endpoint>/<bucket>` instead of `<bucket>.<endpoint host>`; MinIO
+  /// needs this.
+  pub path_style: bool,
+}
+
+impl Default for S3Config {
+  fn default() -> Self {
+    Self {
+      endpoint: String::new(),
+      region: "us-east-1".to_string(),
+      bucket: String::new(),
+      prefix: String::new(),
+      access_key_id: String::new(),
+      path_style: true,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct GitConfig {
+  /// Commit the vault to a git repository in it after each successful pull or import.
+  pub enabled: bool,
+  /// Remote the snapshots are pushed to; they stay local when unset.
+  pub push_remote: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct MirrorConfig {
+  /// Secondary copies each successful sync brings up to date.
+  pub targets: Vec<MirrorTargetConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MirrorTargetConfig {
+  /// The password, if any, is kept in secure storage (see `mirror_set_webdav_password`).
+  Webdav { url: String, username: Option<String> },
+  /// Authenticates with keys only: `identity_file`, the SSH agent or `~/.ssh/config`.
+  Sftp {
+    host: String,
+    port: Option<u16>,
+    user: String,
+    remote_dir: String,
+    identity_file: Option<String>,
+  },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClipConfig {
+  /// Vault-relative folder web clips are written to.
+  pub folder: String,
+  /// Index each clip for RAG right after it is pushed (needs `rag.api_base_url`).
+  pub ingest: bool,
+}
+
+impl Default for ClipConfig {
+  fn default() -> Self {
+    Self {
+      folder: "inbox".to_string(),
+      ingest: false,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RagConfig {
+  /// Base URL of the Diregram web app whose ingest endpoint background ingests use.
+  pub api_base_url: Option<String>,
+  /// Re-index the notes the watcher pushes, once pushes have been quiet for
+  /// `ingest_debounce_seconds`.
+  pub ingest_on_push: bool,
+  pub ingest_debounce_seconds: u64,
+  /// Re-index the whole project after `auto_ingest_after_changes` pushed notes, or once pushes
+  /// have been quiet for `auto_ingest_quiet_minutes`.
+  pub auto_ingest: bool,
+  pub auto_ingest_after_changes: u32,
+  pub auto_ingest_quiet_minutes: u64,
+  /// How the local chunker cuts notes; ingests pass it to the server as a hint.
+  pub chunking: ChunkOptions,
+}
+
+impl Default for RagConfig {
+  fn default() -> Self {
+    Self {
+      api_base_url: None,
+      ingest_on_push: false,
+      ingest_debounce_seconds: 60,
+      auto_ingest: false,
+      auto_ingest_after_changes: 25,
+      auto_ingest_quiet_minutes: 15,
+      chunking: ChunkOptions::default(),
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TagConfig {
+  /// Write each synced note's tags to the remote `file_tags` table after pushes, so the web app
+  /// sees the same tags.
+  pub sync_remote: bool,
+}
+
+/// What a smart folder holds: vault files matching every condition that is set.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SmartFolderQuery {
+  /// Tags a note must all carry (or tags nested under them, `tag/...`).
+  pub tags: Vec<String>,
+  /// Globs, any of which the path must match: `*` and `?` stay within a folder, `**` spans
+  /// folders, and a pattern without `/` is matched against the file name.
+  pub paths: Vec<String>,
+  pub exclude_paths: Vec<String>,
+  /// Kinds the file must be one of: a note kind (`note`, `diagram`, `grid`, ...), `resource` or
+  /// `attachment`.
+  pub kinds: Vec<String>,
+  /// Modification time bounds, RFC 3339 or `YYYY-MM-DD` (UTC); after is inclusive, before is not.
+  pub modified_after: Option<String>,
+  pub modified_before: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RoutingConfig {
+  /// Kind (`diagram`, `board`, ...) -> vault-relative folder pulled files of that kind go under.
+  pub kinds: BTreeMap<String, String>,
+}
+
+/// Secure wipe: files it covers are overwritten and removed instead of archived to the trash, and
+/// their local revisions are purged with them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShredConfig {
+  /// Shred every file in the vault.
+  pub enabled: bool,
+  /// Vault-relative globs (`secret/**`, `*.key.md`) shredded even when `enabled` is off.
+  pub globs: Vec<String>,
+  /// Overwrite passes before removal.
+  pub passes: u32,
+}
+
+impl Default for ShredConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      globs: Vec::new(),
+      passes: 1,
+    }
+  }
+}
+
+pub fn config_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("config.json")
+}
+
+pub fn read_config(vault_path: &str) -> Result<VaultConfigV1, String> {
+  let p = config_path(vault_path);
+  if !p.exists() {
+    return Ok(VaultConfigV1::default());
+  }
+  let text = fs::read_to_string(&p).map_err(|e| e.to_string())?;
+  serde_json::from_str(&text).map_err(|e| format!("invalid .diregram/config.json: {}", e))
+}
+
+/// Writes the vault's config; running watchers and the UI pick the change up right away.
+pub fn write_config(vault_path: &str, config: &VaultConfigV1) -> Result<(), String> {
+  fs::create_dir_all(diregram_dir(vault_path)).map_err(|e| e.to_string())?;
+  let text = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+  fs::write(config_path(vault_path), text).map_err(|e| e.to_string())?;
+  remember(vault_path);
+  changed(vault_path);
+  Ok(())
+}
+
+/// A setting the schema or its checks reject.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConfigIssue {
+  /// Dotted path of the setting (`watch.scan_interval_secs`); empty for the file as a whole.
+  pub path: String,
+  pub message: String,
+}
+
+/// Emitted as `config://changed` once the vault's config was written, from the app or by hand.
+#[derive(Debug, Serialize, Clone)]
+pub struct ConfigChanged {
+  pub vault_path: String,
+}
+
+pub fn issue(path: &str, message: impl Into<String>) -> ConfigIssue {
+  ConfigIssue {
+    path: path.to_string(),
+    message: message.into(),
+  }
+}
+
+pub fn describe(issues: &[ConfigIssue]) -> String {
+  let parts: Vec<String> = issues
+    .iter()
+    .map(|i| if i.path.is_empty() { i.message.clone() } else { format!("{}: {}", i.path, i.message) })
+    .collect();
+  format!("invalid .diregram/config.json: {}", parts.join("; "))
+}
+
+/// Keys of `given` that parsing dropped: typos, or settings this version doesn't know.
+pub fn unknown_keys(given: &Value, parsed: &Value, path: &str, out: &mut Vec<ConfigIssue>) {
+  match (given, parsed) {
+    (Value::Object(given), Value::Object(parsed)) => {
+      for (key, value) in given {
+        let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        match parsed.get(key) {
+          Some(p) => unknown_keys(value, p, &child, out),
+          None => out.push(issue(&child, "unknown setting")),
+        }
+      }
+    }
+    (Value::Array(given), Value::Array(parsed)) => {
+      for (i, (g, p)) in given.iter().zip(parsed).enumerate() {
+        unknown_keys(g, p, &format!("{}[{}]", path, i), out);
+      }
+    }
+    _ => {}
+  }
+}
+
+/// Parses a whole config strictly: a wrong type or an unknown key is an issue, not ignored.
+pub fn parse(value: &Value) -> Result<VaultConfigV1, Vec<ConfigIssue>> {
+  if !value.is_object() {
+    return Err(vec![issue("", "must be a JSON object")]);
+  }
+  let config: VaultConfigV1 = serde_json::from_value(value.clone()).map_err(|e| vec![issue("", e.to_string())])?;
+  let parsed = serde_json::to_value(&config).map_err(|e| vec![issue("", e.to_string())])?;
+  let mut issues = Vec::new();
+  unknown_keys(value, &parsed, "", &mut issues);
+  if issues.is_empty() {
+    Ok(config)
+  } else {
+    Err(issues)
+  }
+}
+
+/// Whether `dir` lies in `vault`, resolving links as far as `dir` exists; a backup folder may not
+/// have been created yet.
+fn inside(dir: &Path, vault: &Path) -> bool {
+  let mut existing = dir;
+  let mut rest = Vec::new();
+  while !existing.exists() {
+    let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else { break };
+    rest.push(name);
+    existing = parent;
+  }
+  let mut resolved = fs::canonicalize(existing).unwrap_or_else(|_| existing.to_path_buf());
+  resolved.extend(rest.into_iter().rev());
+  resolved.starts_with(vault)
+}
+
+/// The checks the per-section configure commands make, over the whole config. Nothing is created
+/// or changed on disk.
+pub fn validate(vault_path: &str, cfg: &VaultConfigV1) -> Vec<ConfigIssue> {
+  let mut out = Vec::new();
+  if cfg.version != 1 {
+    out.push(issue("version", format!("unsupported version {}; this app reads version 1", cfg.version)));
+  }
+  for folder in cfg.folder_modes.keys() {
+    if crate::normalize::safe_rel_path(folder).is_none() {
+      out.push(issue(&format!("folder_modes.{}", folder), "must be a folder inside the vault"));
+    }
+  }
+
+  if cfg.backup.interval_minutes == 0 {
+    out.push(issue("backup.interval_minutes", "must be at least 1"));
+  }
+  if cfg.backup.retention == 0 {
+    out.push(issue("backup.retention", "must be at least 1"));
+  }
+  match cfg.backup.backup_dir.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+    Some(dir) => {
+      let vault = fs::canonicalize(vault_path).unwrap_or_else(|_| PathBuf::from(vault_path));
+      if !Path::new(dir).is_absolute() {
+        out.push(issue("backup.backup_dir", "must be an absolute path"));
+      } else if inside(Path::new(dir), &vault) {
+        out.push(issue("backup.backup_dir", "must be outside the vault"));
+      }
+    }
+    None if cfg.backup.enabled => out.push(issue("backup.backup_dir", "is required when backups are enabled")),
+    None => {}
+  }
+
+  if cfg.notifications.push_failure_threshold == 0 {
+    out.push(issue("notifications.push_failure_threshold", "must be at least 1"));
+  }
+  if cfg.revisions.enabled && cfg.revisions.max_per_file == 0 {
+    out.push(issue("revisions.max_per_file", "must be at least 1 while revisions are enabled"));
+  }
+  if let Err(e) = crate::daily::validate(&cfg.daily_note) {
+    out.push(issue("daily_note", e));
+  }
+  if !(1..=100).contains(&cfg.mass_delete_guard.max_percent) {
+    out.push(issue("mass_delete_guard.max_percent", "must be between 1 and 100"));
+  }
+  let min_scan = crate::scan::MIN_SCAN_INTERVAL.as_secs();
+  if cfg.watch.scan_interval_secs < min_scan {
+    out.push(issue("watch.scan_interval_secs", format!("must be at least {}", min_scan)));
+  }
+
+  if cfg.attachments.backend == AttachmentBackend::S3 {
+    match &cfg.attachments.s3 {
+      Some(s3) if !s3.endpoint.trim().is_empty() && !s3.bucket.trim().is_empty() => {
+        if reqwest::Url::parse(&s3.endpoint).is_err() {
+          out.push(issue("attachments.s3.endpoint", "must be a URL"));
+        }
+      }
+      _ => out.push(issue("attachments.s3", "endpoint and bucket are required when backend is s3")),
+    }
+  }
+  for (i, target) in cfg.mirror.targets.iter().enumerate() {
+    let path = format!("mirror.targets[{}]", i);
+    match target {
+      MirrorTargetConfig::Webdav { url, .. } => {
+        if !reqwest::Url::parse(url).map(|u| matches!(u.scheme(), "http" | "https")).unwrap_or(false) {
+          out.push(issue(&format!("{}.url", path), "must be an http(s) URL"));
+        }
+      }
+      MirrorTargetConfig::Sftp { host, user, identity_file, .. } => {
+        if host.trim().is_empty() || user.trim().is_empty() {
+          out.push(issue(&path, "host and user are required"));
+        }
+        let values = [("host", Some(host)), ("user", Some(user)), ("identity_file", identity_file.as_ref())];
+        for (name, value) in values {
+          if let Some(problem) = value.and_then(|v| crate::mirror::sftp_arg_issue(v)) {
+            out.push(issue(&format!("{}.{}", path, name), problem));
+          }
+        }
+      }
+    }
+  }
+  if crate::clip::clip_folder(&cfg.clip).is_none() {
+    out.push(issue("clip.folder", "must be a visible folder inside the vault, outside resources/ and rag/"));
+  }
+  let background_ingest = cfg.rag.ingest_on_push || cfg.rag.auto_ingest || cfg.clip.ingest;
+  if background_ingest && cfg.rag.api_base_url.as_deref().map(str::trim).unwrap_or("").is_empty() {
+    out.push(issue("rag.api_base_url", "is required for background ingests"));
+  }
+  for (name, query) in &cfg.smart_folders {
+    if let Err(e) = crate::smartfolders::normalize(query.clone()) {
+      out.push(issue(&format!("smart_folders.{}", name), e));
+    }
+  }
+  for (kind, dir) in &cfg.routing.kinds {
+    if crate::routing::route_folder(dir).is_none() {
+      out.push(issue(
+        &format!("routing.kinds.{}", kind),
+        "must be a visible folder inside the vault, outside resources/ and rag/",
+      ));
+    }
+  }
+  if !(1..=crate::shred::MAX_PASSES).contains(&cfg.shred.passes) {
+    out.push(issue("shred.passes", format!("must be between 1 and {}", crate::shred::MAX_PASSES)));
+  }
+  if let Some(g) = cfg.shred.globs.iter().find(|g| g.split('/').any(|part| part == "..")) {
+    out.push(issue("shred.globs", format!("{} must stay inside the vault", g)));
+  }
+  out
+}
+
+/// Applies `patch` to `target` the way JSON merge patch does: objects merge key by key, `null`
+/// drops a key (back to its default), anything else replaces.
+pub fn merge(target: &mut Value, patch: Value) {
+  let Value::Object(patch) = patch else {
+    *target = patch;
+    return;
+  };
+  if !target.is_object() {
+    *target = Value::Object(Default::default());
+  }
+  let Value::Object(map) = target else { return };
+  for (key, value) in patch {
+    if value.is_null() {
+      map.remove(&key);
+    } else {
+      merge(map.entry(key).or_insert(Value::Null), value);
+    }
+  }
+}
+
+fn file_hash(vault_path: &str) -> String {
+  fs::read(config_path(vault_path)).map(|b| sha256_hex(&b)).unwrap_or_default()
+}
+
+/// Records the config file as applied. Returns whether it differs from what was recorded before.
+pub fn remember(vault_path: &str) -> bool {
+  let hash = file_hash(vault_path);
+  let Ok(mut guard) = APPLIED.lock() else { return false };
+  guard.insert(vault_path.to_string(), hash.clone()).is_some_and(|before| before != hash)
+}
+
+/// Tells the running app the vault's config changed: watchers re-read their settings and the UI
+/// refreshes. Everything else reads the config on each use already.
+fn changed(vault_path: &str) {
+  crate::host::reload_config(vault_path);
+  crate::host::emit(
+    "config://changed",
+    ConfigChanged {
+      vault_path: vault_path.to_string(),
+    },
+  );
+}
+
+/// Called when the watcher sees the config file change. Edits made outside the app apply as if
+/// they had been saved from it; problems are logged as `config_error`, and running watchers keep
+/// their settings while the file doesn't parse.
+pub fn reload_if_edited(vault_path: &str) {
+  if !remember(vault_path) {
+    return;
+  }
+  let (kind, detail) = match read_config(vault_path) {
+    Ok(cfg) => match validate(vault_path, &cfg) {
+      issues if issues.is_empty() => ("config_reload", "Applied .diregram/config.json, edited outside the app.".to_string()),
+      issues => ("config_error", format!("Applied .diregram/config.json with problems: {}", describe(&issues))),
+    },
+    Err(e) => ("config_error", e),
+  };
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: kind.to_string(),
+      path: String::new(),
+      detail,
+    },
+  );
+  changed(vault_path);
+}
+
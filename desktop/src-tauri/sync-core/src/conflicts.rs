@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::sync::{
+  to_rel_posix, CONFLICT_MARKER, CONFLICT_TS_FORMAT,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConflictEntry {
+  /// The note the conflict belongs to.
+  pub path: String,
+  /// The copy holding the other side, passed back to `conflict_resolve`.
+  pub conflict_path: String,
+  pub created_at: Option<String>,
+  /// False when the note was since moved or deleted, leaving the copy orphaned.
+  pub original_exists: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+  /// Keep the note as it is locally and overwrite remote with it.
+  TakeLocal,
+  /// Replace the note with the saved remote copy.
+  TakeRemote,
+  /// Replace the note with a hand-merged version.
+  MergedContent(String),
+}
+
+/// `(original rel path, conflict timestamp)` if `rel` names a conflict copy.
+pub fn parse_conflict_path(rel: &str) -> Option<(String, String)> {
+  let (dir, name) = match rel.rsplit_once('/') {
+    Some((dir, name)) => (Some(dir), name),
+    None => (None, rel),
+  };
+  let (stem, rest) = name.split_once(CONFLICT_MARKER)?;
+  let (ts, ext) = rest.rsplit_once(").")?;
+  let original = format!("{}.{}", stem, ext);
+  let original = match dir {
+    Some(dir) => format!("{}/{}", dir, original),
+    None => original,
+  };
+  Some((original, ts.to_string()))
+}
+
+pub fn list(vault_path: &str) -> Vec<ConflictEntry> {
+  let root = Path::new(vault_path);
+  let mut out = Vec::new();
+  for entry in WalkDir::new(root)
+    .follow_links(false)
+    .into_iter()
+    .filter_entry(|e| e.file_name() != ".diregram")
+    .filter_map(Result::ok)
+  {
+    if !entry.file_type().is_file() {
+      continue;
+    }
+    let Some(rel) = to_rel_posix(root, entry.path()) else { continue };
+    let Some((original, ts)) = parse_conflict_path(&rel) else { continue };
+    out.push(ConflictEntry {
+      original_exists: crate::normalize::local_path(root, &original).exists(),
+      created_at: NaiveDateTime::parse_from_str(&ts, CONFLICT_TS_FORMAT)
+        .ok()
+        .map(|t| t.and_utc().to_rfc3339()),
+      path: original,
+      conflict_path: rel,
+    });
+  }
+  out.sort_by(|a, b| a.path.cmp(&b.path).then(a.conflict_path.cmp(&b.conflict_path)));
+  out
+}
+
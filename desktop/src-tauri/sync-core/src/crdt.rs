@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::PathBuf;
+
+use automerge::transaction::Transactable;
+use automerge::{AutoCommit, ObjId, ObjType, ReadDoc, TextEncoding, ROOT};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{diregram_dir, rest_base, send_with_refresh, SupabaseAuth};
+
+/// Notes whose nexus-doc header says `"kind": "collab"` are merged through a shared change log
+/// instead of producing conflict files.
+pub const COLLAB_KIND: &str = "collab";
+/// Same name the web editor gives its shared text.
+const TEXT_KEY: &str = "nexus";
+
+/// Local replica of one collab note: the merged document as of the last sync, and the last
+/// `file_crdt_changes.id` folded into it.
+#[derive(Debug, Serialize, Deserialize)]
+struct CrdtStateV1 {
+  version: u32,
+  doc: String,
+  cursor: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChangeRow {
+  id: i64,
+  changes: String,
+}
+
+pub struct MergeInput<'a> {
+  pub file_id: &'a str,
+  /// Current remote `content`, decoded.
+  pub remote_text: &'a str,
+  /// `content` was last written by something that doesn't use the change log (the web editor),
+  /// so its edits have to be folded in from the text itself.
+  pub remote_external: bool,
+  /// Local file content, when it changed since the last sync.
+  pub local_edit: Option<&'a str>,
+}
+
+fn state_path(vault_path: &str, file_id: &str) -> PathBuf {
+  diregram_dir(vault_path).join("crdt").join(format!("{}.json", file_id))
+}
+
+fn read_state(vault_path: &str, file_id: &str) -> Result<Option<CrdtStateV1>, String> {
+  match fs::read_to_string(state_path(vault_path, file_id)) {
+    Ok(text) => serde_json::from_str(&text).map(Some).map_err(|e| e.to_string()),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+fn write_state(vault_path: &str, file_id: &str, state: &CrdtStateV1) -> Result<(), String> {
+  let p = state_path(vault_path, file_id);
+  if let Some(parent) = p.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  let text = serde_json::to_string(state).map_err(|e| e.to_string())?;
+  fs::write(p, text).map_err(|e| e.to_string())
+}
+
+fn text_obj(doc: &AutoCommit) -> Result<Option<ObjId>, String> {
+  Ok(doc.get(ROOT, TEXT_KEY).map_err(|e| e.to_string())?.map(|(_, id)| id))
+}
+
+fn read_text(doc: &AutoCommit, obj: &ObjId) -> Result<String, String> {
+  doc.text(obj).map_err(|e| e.to_string())
+}
+
+/// Edits the text into `target` with one splice over the part that differs, so concurrent edits
+/// elsewhere in the note are untouched by the merge.
+fn splice_to(doc: &mut AutoCommit, obj: &ObjId, target: &str) -> Result<(), String> {
+  let current: Vec<char> = read_text(doc, obj)?.chars().collect();
+  let wanted: Vec<char> = target.chars().collect();
+  if current == wanted {
+    return Ok(());
+  }
+  let prefix = current.iter().zip(&wanted).take_while(|(a, b)| a == b).count();
+  let max_suffix = current.len().min(wanted.len()) - prefix;
+  let suffix = current
+    .iter()
+    .rev()
+    .zip(wanted.iter().rev())
+    .take(max_suffix)
+    .take_while(|(a, b)| a == b)
+    .count();
+  let deleted = current.len() - prefix - suffix;
+  let inserted: String = wanted[prefix..wanted.len() - suffix].iter().collect();
+  doc
+    .splice_text(obj, prefix, deleted as isize, &inserted)
+    .map_err(|e| e.to_string())
+}
+
+async fn fetch_changes(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  file_id: &str,
+  after: i64,
+) -> Result<Vec<ChangeRow>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/file_crdt_changes", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "id,changes");
+    q.append_pair("file_id", &format!("eq.{}", file_id));
+    q.append_pair("id", &format!("gt.{}", after));
+    q.append_pair("order", "id.asc");
+  }
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("change log fetch failed: HTTP {}", res.status()));
+        }
+        res.json::<Vec<ChangeRow>>().await.map_err(|e| e.to_string())
+      })
+    },
+  )
+  .await
+}
+
+async fn append_changes(client: &reqwest::Client, auth: &mut SupabaseAuth, file_id: &str, changes: String) -> Result<(), String> {
+  let url = format!("{}/file_crdt_changes", rest_base(auth));
+  let body = serde_json::json!({ "file_id": file_id, "changes": changes });
+  send_with_refresh(
+    client,
+    auth,
+    || client.post(url.clone()).json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("change log append failed: HTTP {}", res.status()));
+        }
+        Ok(())
+      })
+    },
+  )
+  .await
+}
+
+/// Brings the local replica up to date with the change log, folds in local and web edits, publishes
+/// whatever this device added, and returns the merged text to write on both sides.
+pub async fn merge(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &str,
+  input: MergeInput<'_>,
+) -> Result<String, String> {
+  let state = read_state(vault_path, input.file_id)?;
+  let cursor = state.as_ref().map(|s| s.cursor).unwrap_or(0);
+  let mut doc = match &state {
+    Some(s) => {
+      let bytes = STANDARD.decode(&s.doc).map_err(|e| e.to_string())?;
+      AutoCommit::load(&bytes).map_err(|e| e.to_string())?
+    }
+    None => AutoCommit::new_with_encoding(TextEncoding::UnicodeCodePoint),
+  };
+
+  // Local edits were made against the last synced text, so they are recorded on a fork taken
+  // before any remote changes are applied and merged back afterwards.
+  let mut local = text_obj(&doc)?.map(|_| doc.fork());
+
+  let rows = fetch_changes(client, auth, input.file_id, cursor).await?;
+  let mut next_cursor = cursor;
+  for row in rows {
+    let changes = crate::e2ee::open(project_folder_id, &row.changes)?;
+    let bytes = STANDARD.decode(changes.trim()).map_err(|e| e.to_string())?;
+    doc.load_incremental(&bytes).map_err(|e| e.to_string())?;
+    next_cursor = next_cursor.max(row.id);
+  }
+  let remote_heads = doc.get_heads();
+
+  let obj = match text_obj(&doc)? {
+    Some(obj) => {
+      if input.remote_external && read_text(&doc, &obj)? != input.remote_text {
+        splice_to(&mut doc, &obj, input.remote_text)?;
+      }
+      obj
+    }
+    None => {
+      // Nothing in the log yet: the current remote text becomes the shared starting point.
+      let obj = doc.put_object(ROOT, TEXT_KEY, ObjType::Text).map_err(|e| e.to_string())?;
+      splice_to(&mut doc, &obj, input.remote_text)?;
+      obj
+    }
+  };
+
+  if let Some(edit) = input.local_edit {
+    match local.as_mut() {
+      Some(fork) => {
+        let fork_obj = text_obj(fork)?.ok_or_else(|| "collab document lost its text".to_string())?;
+        splice_to(fork, &fork_obj, edit)?;
+        doc.merge(fork).map_err(|e| e.to_string())?;
+      }
+      None => splice_to(&mut doc, &obj, edit)?,
+    }
+  }
+
+  if doc.get_heads() != remote_heads {
+    let delta = STANDARD.encode(doc.save_after(&remote_heads));
+    let sealed = crate::e2ee::seal(vault_path, project_folder_id, &delta)?;
+    // The cursor isn't moved past our own row: others may have appended before it. Reading our
+    // changes back next time is harmless, as already-known changes are ignored.
+    append_changes(client, auth, input.file_id, sealed).await?;
+  }
+
+  let merged = read_text(&doc, &obj)?;
+  write_state(
+    vault_path,
+    input.file_id,
+    &CrdtStateV1 {
+      version: 1,
+      doc: STANDARD.encode(doc.save()),
+      cursor: next_cursor,
+    },
+  )?;
+  Ok(merged)
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate, NaiveTime};
+use once_cell::sync::Lazy;
+use tokio::sync::oneshot;
+
+use crate::config::DailyNoteConfig;
+use crate::normalize::safe_rel_path;
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+/// How often the scheduler checks whether today's note is due.
+pub const DAILY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub static DAILY_STATE: Lazy<Mutex<HashMap<String, DailyState>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub struct DailyState {
+  pub stop_tx: oneshot::Sender<()>,
+}
+
+pub fn parse_time(time: &str) -> Result<NaiveTime, String> {
+  NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| format!("invalid time {:?} (expected HH:MM)", time))
+}
+
+pub fn validate(cfg: &DailyNoteConfig) -> Result<(), String> {
+  parse_time(&cfg.time)?;
+  if !cfg.folder.trim().is_empty() && safe_rel_path(&cfg.folder).is_none() {
+    return Err(format!("invalid folder: {}", cfg.folder));
+  }
+  if let Some(t) = cfg.template.as_deref().filter(|t| !t.trim().is_empty()) {
+    safe_rel_path(t).ok_or_else(|| format!("invalid template path: {}", t))?;
+  }
+  let name = Local::now().format(&cfg.filename_format).to_string();
+  if name.trim().is_empty() || name.contains('/') || name.contains('\\') {
+    return Err(format!("invalid filename_format: {}", cfg.filename_format));
+  }
+  Ok(())
+}
+
+pub fn note_rel(cfg: &DailyNoteConfig, date: NaiveDate) -> String {
+  let name = format!("{}.md", date.format(&cfg.filename_format));
+  match safe_rel_path(&cfg.folder) {
+    Some(folder) if !folder.is_empty() => format!("{}/{}", folder, name),
+    _ => name,
+  }
+}
+
+pub fn render(vault_path: &str, cfg: &DailyNoteConfig, date: NaiveDate, title: &str) -> String {
+  let template = cfg
+    .template
+    .as_deref()
+    .and_then(safe_rel_path)
+    .and_then(|t| fs::read_to_string(Path::new(vault_path).join(t)).ok())
+    .unwrap_or_else(|| "# {{title}}\n\n".to_string());
+  template
+    .replace("{{date}}", &date.format("%Y-%m-%d").to_string())
+    .replace("{{weekday}}", &date.format("%A").to_string())
+    .replace("{{title}}", title)
+}
+
+/// Creates the note for `date` unless it exists. Returns its path and whether it was created.
+pub fn ensure_note(vault_path: &str, cfg: &DailyNoteConfig, date: NaiveDate) -> Result<(String, bool), String> {
+  let rel = note_rel(cfg, date);
+  let abs = Path::new(vault_path).join(&rel);
+  if abs.exists() {
+    return Ok((rel, false));
+  }
+  if let Some(parent) = abs.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  let title = date.format(&cfg.filename_format).to_string();
+  fs::write(&abs, render(vault_path, cfg, date, &title)).map_err(|e| e.to_string())?;
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "daily_note".to_string(),
+      path: rel.clone(),
+      detail: "Created today's daily note.".to_string(),
+    },
+  );
+  Ok((rel, true))
+}
+
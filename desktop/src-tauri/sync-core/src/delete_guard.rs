@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::read_config;
+use crate::sync::{
+  append_event, archive_remote_deleted, now_iso, read_mapping, write_mapping, SyncEvent, SyncMappingV1, SyncSummary,
+};
+
+/// Remote deletions a pull found but did not apply, because they covered too much of the vault.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PendingMassDeleteV1 {
+  pub detected_at: String,
+  /// Relative path -> remote file id at the time of the pull.
+  pub files: HashMap<String, String>,
+  /// Mapped notes at the time of the pull.
+  pub mapped_files: usize,
+}
+
+/// Whether the remote deletions in `rels` must wait for `sync_mass_delete_resolve`. Records them
+/// as pending (and tells the user) if so; clears a stale pending set otherwise.
+pub fn hold(vault_path: &str, mapping: &mut SyncMappingV1, rels: &[String]) -> bool {
+  let cfg = read_config(vault_path).map(|c| c.mass_delete_guard).unwrap_or_default();
+  let mapped = mapping.files.len();
+  let trips = cfg.enabled
+    && rels.len() >= cfg.min_files.max(1) as usize
+    && rels.len() * 100 > mapped * cfg.max_percent as usize;
+  if !trips {
+    mapping.pending_mass_delete = None;
+    return false;
+  }
+
+  let files: HashMap<String, String> = rels
+    .iter()
+    .filter_map(|rel| mapping.files.get(rel).map(|fm| (rel.clone(), fm.file_id.clone())))
+    .collect();
+  let unchanged = mapping.pending_mass_delete.as_ref().map(|p| p.files == files).unwrap_or(false);
+  if !unchanged {
+    mapping.pending_mass_delete = Some(PendingMassDeleteV1 {
+      detected_at: now_iso(),
+      files,
+      mapped_files: mapped,
+    });
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "mass_delete_guard".to_string(),
+        path: String::new(),
+        detail: format!(
+          "Remote deleted {} of {} synced notes. Local copies are kept until the deletion is confirmed.",
+          rels.len(),
+          mapped
+        ),
+      },
+    );
+  }
+  crate::notify::notify(
+    vault_path,
+    crate::notify::NotifyKind::MassDelete,
+    "Large remote deletion on hold",
+    &format!(
+      "{} of {} notes were deleted in Diregram. Confirm before they are moved to the trash.",
+      rels.len(),
+      mapped
+    ),
+    None,
+  );
+  true
+}
+
+pub fn resolve(vault_path: &str, confirm: bool) -> Result<SyncSummary, String> {
+  let mut mapping = read_mapping(vault_path)?.ok_or_else(|| "vault is not linked (missing .diregram/sync.json)".to_string())?;
+  let pending = mapping
+    .pending_mass_delete
+    .take()
+    .ok_or_else(|| "no remote deletion is on hold".to_string())?;
+  // Skip notes re-linked or re-created since the pull that held them.
+  let rels: Vec<String> = pending
+    .files
+    .iter()
+    .filter(|(rel, id)| mapping.files.get(*rel).map(|fm| &fm.file_id == *id).unwrap_or(false))
+    .map(|(rel, _)| rel.clone())
+    .collect();
+
+  let count = rels.len();
+  let mut summary = SyncSummary::default();
+  if confirm {
+    archive_remote_deleted(vault_path, &mut mapping, rels, &mut summary);
+  } else {
+    for rel in &rels {
+      mapping.files.remove(rel);
+    }
+  }
+  mapping.updated_at = now_iso();
+  write_mapping(vault_path, &mapping)?;
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: if confirm { "mass_delete_confirmed" } else { "mass_delete_kept" }.to_string(),
+      path: String::new(),
+      detail: if confirm {
+        format!("Archived {} notes deleted remotely.", count)
+      } else {
+        format!("Kept {} notes deleted remotely; they will be pushed again.", count)
+      },
+    },
+  );
+  Ok(summary)
+}
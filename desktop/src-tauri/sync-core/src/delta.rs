@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use similar::{DiffTag, TextDiff};
+
+use crate::config::read_config;
+
+/// A multi-megabyte note shouldn't stall a push; past this the diff settles for coarser hunks.
+pub const DIFF_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// One splice applied by the `apply_file_patch` RPC: replace `delete` characters at `start`
+/// (counted in the base text, in Unicode scalar values as Postgres does) with `insert`.
+#[derive(Debug, Serialize, Clone)]
+pub struct Edit {
+  pub start: usize,
+  pub delete: usize,
+  pub insert: String,
+}
+
+/// Whether pushes of a note this size go through patches. Patches are applied to plain text on the
+/// server, so such notes are stored uncompressed, and never when E2EE is on.
+pub fn eligible(vault_path: &str, len: usize) -> bool {
+  match read_config(vault_path) {
+    Ok(cfg) => cfg.delta.enabled && !cfg.e2ee.enabled && len as u64 >= cfg.delta.min_bytes,
+    Err(_) => false,
+  }
+}
+
+/// Line diff of `base` -> `new`, expressed as splices against `base`.
+pub fn edits(base: &str, new: &str) -> Vec<Edit> {
+  let diff = TextDiff::configure().timeout(DIFF_TIMEOUT).diff_lines(base, new);
+  let old_lines = diff.old_slices();
+  let new_lines = diff.new_slices();
+
+  let mut line_starts = Vec::with_capacity(old_lines.len() + 1);
+  let mut offset = 0usize;
+  line_starts.push(0);
+  for line in old_lines {
+    offset += line.chars().count();
+    line_starts.push(offset);
+  }
+
+  diff
+    .ops()
+    .iter()
+    .filter_map(|op| {
+      let (tag, old, new) = op.as_tag_tuple();
+      if tag == DiffTag::Equal {
+        return None;
+      }
+      let start = line_starts[old.start];
+      Some(Edit {
+        start,
+        delete: line_starts[old.end] - start,
+        insert: new_lines[new].concat(),
+      })
+    })
+    .collect()
+}
+
+/// The text the server last held for a note, by the mapping's `base_hash`, if it was kept.
+pub fn base(vault_path: &str, hash: &str) -> Option<String> {
+  crate::blobs::get(vault_path, hash)
+}
+
+/// Keeps `text`, exactly as it was sent to or received from the server, as the base for the next
+/// patch (for eligible notes) and drops `previous`, the base it replaces. Returns the key to store
+/// as the mapping's `base_hash`. Best effort: a missing base only means the next push uploads the
+/// full content.
+pub fn remember_base(vault_path: &str, previous: Option<&str>, text: &str) -> Option<String> {
+  let kept = if eligible(vault_path, text.len()) {
+    crate::blobs::put(vault_path, text).ok()
+  } else {
+    None
+  };
+  if let Some(prev) = previous {
+    if kept.as_deref() != Some(prev) {
+      crate::blobs::remove(vault_path, prev);
+    }
+  }
+  kept
+}
+
+/// Drops a base that no longer matches the server, e.g. after a full upload or a collab merge.
+pub fn forget_base(vault_path: &str, previous: Option<&str>) {
+  if let Some(prev) = previous {
+    crate::blobs::remove(vault_path, prev);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{edits, Edit};
+
+  /// What `apply_file_patch` does: splices highest offset first, offsets in characters.
+  fn apply(base: &str, edits: &[Edit]) -> String {
+    let mut chars: Vec<char> = base.chars().collect();
+    let mut sorted: Vec<&Edit> = edits.iter().collect();
+    sorted.sort_by_key(|e| std::cmp::Reverse(e.start));
+    for e in sorted {
+      chars.splice(e.start..e.start + e.delete, e.insert.chars());
+    }
+    chars.into_iter().collect()
+  }
+
+  #[test]
+  fn edits_turn_the_base_into_the_new_text() {
+    let base = "# Notes\n\none\ntwo\nthree\nfour\n";
+    let cases = [
+      "# Notes\n\none\n2\nthree\nfour\n",
+      "# Notes\n\nzero\none\ntwo\nthree\nfour\nfive\n",
+      "# Notes\n\nthree\n",
+      "",
+      "four\nthree\ntwo\none\n",
+    ];
+    for new in cases {
+      assert_eq!(apply(base, &edits(base, new)), new, "{:?}", new);
+    }
+  }
+
+  #[test]
+  fn offsets_count_characters_not_bytes() {
+    let base = "caf\u{e9} \u{1f600}\nsecond\nthird\n";
+    let new = "caf\u{e9} \u{1f600}\nSECOND\nthird\n";
+    let edits = edits(base, new);
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].start, 7);
+    assert_eq!(edits[0].delete, "second\n".len());
+    assert_eq!(apply(base, &edits), new);
+  }
+
+  #[test]
+  fn unchanged_text_needs_no_edits() {
+    assert!(edits("same\ntext\n", "same\ntext\n").is_empty());
+  }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{append_event, now_iso, rest_base, send_with_refresh, write_atomic, SupabaseAuth, SyncEvent};
+
+const FILE_NAME: &str = "device.json";
+/// Secure-storage key holding the id, so it survives the app data folder being cleared.
+const SECRET_KEY: &str = "device.id";
+/// Sent with every Supabase request; the database stamps written rows with it.
+pub const DEVICE_HEADER: &str = "x-diregram-device";
+
+static DIR: OnceCell<PathBuf> = OnceCell::new();
+static ID: OnceCell<String> = OnceCell::new();
+/// `supabase_url|owner_id` pairs this process has registered the device with.
+static REGISTERED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// `<app config>/device.json`: identifies this install in event logs and on the server.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct DeviceFileV1 {
+  version: u32,
+  device_id: String,
+  created_at: String,
+}
+
+/// A row of the remote device registry.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceInfo {
+  pub device_id: String,
+  #[serde(default)]
+  pub name: Option<String>,
+  #[serde(default)]
+  pub platform: Option<String>,
+  #[serde(default)]
+  pub app_version: Option<String>,
+  #[serde(default)]
+  pub first_seen_at: Option<String>,
+  #[serde(default)]
+  pub last_seen_at: Option<String>,
+  #[serde(default)]
+  pub revoked_at: Option<String>,
+  /// This install.
+  #[serde(default)]
+  pub current: bool,
+}
+
+/// Sets where the device file lives. Call once, before anything logs events.
+pub fn set_dir(dir: PathBuf) {
+  let _ = DIR.set(dir);
+}
+
+/// `set_dir` for the CLI, which has no app handle: the same folder Tauri's `app_config_dir`
+/// resolves to (the platform config folder plus the bundle identifier), so the CLI and the app on
+/// one machine are one device.
+pub fn install_cli() {
+  let base = if cfg!(windows) {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+  } else if cfg!(target_os = "macos") {
+    std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library").join("Application Support"))
+  } else {
+    std::env::var_os("XDG_CONFIG_HOME")
+      .map(PathBuf::from)
+      .filter(|p| p.is_absolute())
+      .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+  };
+  // The keychain service is the bundle identifier.
+  if let Some(base) = base {
+    set_dir(base.join(crate::KEYCHAIN_SERVICE));
+  }
+}
+
+fn random_id() -> String {
+  let mut b = [0u8; 16];
+  let _ = getrandom::getrandom(&mut b);
+  // RFC 4122 version 4, variant 1.
+  b[6] = (b[6] & 0x0f) | 0x40;
+  b[8] = (b[8] & 0x3f) | 0x80;
+  let hex: String = b.iter().map(|x| format!("{:02x}", x)).collect();
+  format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn read_file(path: &Path) -> Option<String> {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|t| serde_json::from_str::<DeviceFileV1>(&t).ok())
+    .map(|f| f.device_id)
+    .filter(|id| !id.is_empty())
+}
+
+fn write_file(path: &Path, device_id: &str) {
+  let file = DeviceFileV1 {
+    version: 1,
+    device_id: device_id.to_string(),
+    created_at: now_iso(),
+  };
+  if let Some(dir) = path.parent() {
+    let _ = fs::create_dir_all(dir);
+  }
+  if let Ok(text) = serde_json::to_string_pretty(&file) {
+    if let Err(e) = write_atomic(path, text) {
+      tracing::warn!(error = %e, "could not save the device id");
+    }
+  }
+}
+
+/// Secure storage first, then the app data file; whichever has it restores the other.
+fn load_or_create() -> String {
+  // Lets a CLI run act as a given device, e.g. one of several jobs on a server.
+  if let Some(id) = std::env::var("DIREGRAM_DEVICE_ID").ok().filter(|id| !id.trim().is_empty()) {
+    return id;
+  }
+  // Only without any config folder to keep it in.
+  let Some(dir) = DIR.get() else { return random_id() };
+  let path = dir.join(FILE_NAME);
+  let stored = crate::secrets::get(None, SECRET_KEY).ok().flatten().filter(|id| !id.trim().is_empty());
+  let from_file = read_file(&path);
+  let id = stored.clone().or(from_file.clone()).unwrap_or_else(random_id);
+  if stored.as_deref() != Some(id.as_str()) {
+    if let Err(e) = crate::secrets::set(None, SECRET_KEY, &id) {
+      tracing::warn!(error = %e, "could not save the device id to secure storage");
+    }
+  }
+  if from_file.as_deref() != Some(id.as_str()) {
+    write_file(&path, &id);
+  }
+  id
+}
+
+/// This install's id, created on first use.
+pub fn id() -> String {
+  ID.get_or_init(load_or_create).clone()
+}
+
+/// Human-readable name shown in the device list.
+pub fn name() -> String {
+  crate::lock::hostname()
+}
+
+/// Records this device in the account's registry, once per process and account. Best effort:
+/// databases without the registry just don't list devices.
+pub async fn register(client: &reqwest::Client, auth: &mut SupabaseAuth, vault_path: &str) {
+  let key = format!("{}|{}", auth.supabase_url, auth.owner_id);
+  if REGISTERED.lock().map(|g| g.contains(&key)).unwrap_or(true) {
+    return;
+  }
+  let url = format!("{}/rpc/register_device", rest_base(auth));
+  let body = serde_json::json!({
+    "p_device_id": id(),
+    "p_name": name(),
+    "p_platform": std::env::consts::OS,
+    "p_app_version": env!("CARGO_PKG_VERSION"),
+  });
+  let res = send_with_refresh(
+    client,
+    auth,
+    || client.post(url.clone()).json(&body),
+    |res| {
+      Box::pin(async move {
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+          return Ok(false);
+        }
+        if !res.status().is_success() {
+          return Err(format!("device registration failed: HTTP {}", res.status()));
+        }
+        Ok(true)
+      })
+    },
+  )
+  .await;
+  let first = match res {
+    Ok(registered) => {
+      let Ok(mut g) = REGISTERED.lock() else { return };
+      g.insert(key) && registered
+    }
+    Err(e) => {
+      tracing::debug!(error = %e, "device registration skipped");
+      false
+    }
+  };
+  if first {
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "device_registered".to_string(),
+        path: String::new(),
+        detail: format!("Registered this device ({}, {}) with the account.", name(), id()),
+      },
+    );
+  }
+}
+
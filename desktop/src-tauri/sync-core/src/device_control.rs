@@ -0,0 +1,246 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{
+  append_event, diregram_dir, mapping_path, now_iso, read_mapping, rest_base, send_with_refresh, sync_key, SupabaseAuth,
+  SyncEvent,
+};
+
+/// How long an unlink waits for the account's running pushes and pulls to end before giving up on
+/// removing content.
+const STOP_WAIT: Duration = Duration::from_secs(60);
+
+/// A pending `device_controls` row: the account asked this device to unlink.
+#[derive(Debug, Deserialize, Clone)]
+struct DeviceControlRow {
+  id: String,
+  #[serde(default)]
+  requested_at: Option<String>,
+  /// Also delete what sync brought into the vault, not just stop syncing it.
+  #[serde(default)]
+  remove_content: bool,
+}
+
+/// Emitted as `device://unlinked` once a remote unlink has been carried out.
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceUnlinked {
+  pub vault_path: String,
+  pub content_removed: bool,
+  pub files_removed: u32,
+}
+
+fn event(vault_path: &str, kind: &str, detail: String) {
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: kind.to_string(),
+      path: String::new(),
+      detail,
+    },
+  );
+}
+
+async fn pending(client: &reqwest::Client, auth: &mut SupabaseAuth) -> Result<Option<DeviceControlRow>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/device_controls", rest_base(auth))).map_err(|e| e.to_string())?;
+  url
+    .query_pairs_mut()
+    .append_pair("select", "id,requested_at,remove_content")
+    .append_pair("device_id", &format!("eq.{}", crate::device::id()))
+    .append_pair("acknowledged_at", "is.null")
+    .append_pair("limit", "1");
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        // Databases without the table can't ask for an unlink.
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+          return Ok(None);
+        }
+        if !res.status().is_success() {
+          return Err(format!("device control check failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<DeviceControlRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next())
+      })
+    },
+  )
+  .await
+}
+
+async fn acknowledge(client: &reqwest::Client, auth: &mut SupabaseAuth, id: &str) -> Result<(), String> {
+  let mut url = reqwest::Url::parse(&format!("{}/device_controls", rest_base(auth))).map_err(|e| e.to_string())?;
+  url.query_pairs_mut().append_pair("id", &format!("eq.{}", id));
+  let body = serde_json::json!({ "acknowledged_at": now_iso() });
+  send_with_refresh(
+    client,
+    auth,
+    || client.patch(url.clone()).json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("device control acknowledge failed: HTTP {}", res.status()));
+        }
+        Ok(())
+      })
+    },
+  )
+  .await
+}
+
+/// Deletes the sync mapping, then the synced notes and resources (not local-only files), local
+/// history and trash. Shredded where the vault asks for it; never archived, which would keep a copy.
+fn remove_content(vault_path: &str) -> Result<u32, String> {
+  let Some(mapping) = read_mapping(vault_path)? else { return Ok(0) };
+  // First: a push that still saw the mapping would take the missing files for local deletions and
+  // delete them remotely too.
+  fs::remove_file(mapping_path(vault_path)).map_err(|e| format!("could not remove the sync mapping: {}", e))?;
+  let root = Path::new(vault_path);
+  let mut removed = 0;
+  for rel in mapping.files.keys().chain(mapping.resources.keys()) {
+    let abs = crate::normalize::local_path(root, rel);
+    if !abs.is_file() {
+      continue;
+    }
+    let res = match crate::shred::covering(vault_path, rel) {
+      Some(cfg) => crate::shred::shred_file(&abs, cfg.passes),
+      None => fs::remove_file(&abs).map_err(|e| e.to_string()),
+    };
+    match res {
+      Ok(()) => removed += 1,
+      Err(e) => tracing::warn!(vault = vault_path, path = %rel, error = %e, "could not remove synced file"),
+    }
+  }
+  for dir in ["revisions", "trash"] {
+    let _ = fs::remove_dir_all(diregram_dir(vault_path).join(dir));
+  }
+  Ok(removed)
+}
+
+/// Stops the account's watchers and pollers, forgets them so they don't resume at the next launch,
+/// and waits for their in-flight runs, except `own_key`: the pull this is called from. Returns the
+/// vaults they ran for, and whether everything stopped within `STOP_WAIT`.
+async fn stop_account_sync(auth: &SupabaseAuth, own_key: &str) -> (Vec<String>, bool) {
+  crate::autosync::forget_account(&auth.supabase_url, &auth.owner_id);
+  let Some(host) = crate::host::get() else { return (Vec::new(), true) };
+  let stopping = host
+    .stop_account(auth.supabase_url.clone(), auth.owner_id.clone(), own_key.to_string())
+    .await;
+  // Without a scheduler nothing else runs.
+  let Ok((vaults, done)) = stopping else { return (Vec::new(), true) };
+  let stopped = tokio::time::timeout(STOP_WAIT, done).await.is_ok();
+  for vault in &vaults {
+    crate::lock::release(vault);
+  }
+  (vaults, stopped)
+}
+
+/// Checked at the start of every pull. When the account has asked this device to unlink, it stops
+/// the account's sync, acknowledges, purges the stored session and, if asked, removes the pulled
+/// content, logging each step; the pull then fails.
+pub async fn enforce(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &str,
+) -> Result<(), String> {
+  let control = match pending(client, auth).await {
+    Ok(Some(c)) => c,
+    Ok(None) => return Ok(()),
+    Err(e) => {
+      tracing::debug!(error = %e, "device control check skipped");
+      return Ok(());
+    }
+  };
+  event(
+    vault_path,
+    "remote_unlink",
+    format!(
+      "This device was unlinked from the account{}; stopping sync.",
+      control.requested_at.as_deref().map(|t| format!(" at {}", t)).unwrap_or_default()
+    ),
+  );
+
+  let (stopped_vaults, stopped) = stop_account_sync(auth, &sync_key(vault_path, project_folder_id)).await;
+  if stopped {
+    event(
+      vault_path,
+      "sync_stopped",
+      "Stopped the account's watchers and pollers; they won't resume at launch.".to_string(),
+    );
+  } else {
+    event(
+      vault_path,
+      "sync_stopped",
+      format!(
+        "Stopped the account's watchers and pollers, but runs in flight didn't finish within {}s.",
+        STOP_WAIT.as_secs()
+      ),
+    );
+  }
+
+  // While the session still works; a failed acknowledgement just repeats the unlink next time.
+  if let Err(e) = acknowledge(client, auth, &control.id).await {
+    tracing::warn!(error = %e, "could not acknowledge the device control");
+  }
+
+  crate::auth::forget(auth);
+  match crate::sync::forget_auth_tokens(auth.profile.as_deref()) {
+    Ok(()) => event(vault_path, "tokens_purged", "Removed the stored sign-in session from secure storage.".to_string()),
+    Err(e) => event(vault_path, "tokens_purge_error", format!("Could not remove the stored sign-in session: {}", e)),
+  }
+
+  let files_removed = if control.remove_content {
+    // Every vault this account syncs on the device, not just the one that noticed.
+    let mut vaults: Vec<String> = crate::vaults::registered()
+      .into_iter()
+      .filter(|v| v.account.owner_id == auth.owner_id && v.account.supabase_url == auth.supabase_url)
+      .map(|v| v.vault_path)
+      .chain(stopped_vaults)
+      .chain(std::iter::once(vault_path.to_string()))
+      .collect();
+    vaults.sort();
+    vaults.dedup();
+    let mut total = 0;
+    for vault in vaults {
+      // A push still running could turn the removal into remote deletions; leave the files.
+      if !stopped {
+        event(
+          &vault,
+          "remote_wipe_error",
+          "Sync didn't stop in time, so synced files were left in place; delete them by hand.".to_string(),
+        );
+        continue;
+      }
+      match remove_content(&vault) {
+        Ok(n) => {
+          event(
+            &vault,
+            "remote_wipe",
+            format!("Removed the sync mapping, {} synced file(s), local history and trash.", n),
+          );
+          total += n;
+        }
+        Err(e) => event(&vault, "remote_wipe_error", format!("Synced files were left in place: {}", e)),
+      }
+    }
+    total
+  } else {
+    0
+  };
+
+  crate::host::emit(
+    "device://unlinked",
+    DeviceUnlinked {
+      vault_path: vault_path.to_string(),
+      content_removed: control.remove_content && stopped,
+      files_removed,
+    },
+  );
+  Err("this device was unlinked from the account; sign in again to resume sync".to_string())
+}
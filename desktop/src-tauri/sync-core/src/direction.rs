@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::config::{read_config, SyncMode};
+use crate::sync::{append_event, now_iso, SyncEvent, SyncSummary};
+
+/// Hash of each held-back local change already reported, per vault and path, so a watcher that
+/// pushes on every event doesn't repeat the warning until the file changes again.
+static REPORTED: Lazy<Mutex<HashMap<(String, String), String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Which way changes flow in a vault, per its `mode` and `folder_modes` in the vault config.
+pub struct Direction {
+  mode: SyncMode,
+  /// Deepest folders first, so the first match is the one that applies.
+  folders: Vec<(String, SyncMode)>,
+}
+
+impl Direction {
+  pub fn load(vault_path: &str) -> Self {
+    let cfg = read_config(vault_path).unwrap_or_default();
+    let mut folders: Vec<(String, SyncMode)> = cfg.folder_modes.into_iter().collect();
+    folders.sort_by_key(|f| std::cmp::Reverse(f.0.len()));
+    Self { mode: cfg.mode, folders }
+  }
+
+  fn mode_of(&self, rel: &str) -> SyncMode {
+    self
+      .folders
+      .iter()
+      .find(|(dir, _)| rel == dir || rel.starts_with(&format!("{}/", dir)))
+      .map(|(_, mode)| *mode)
+      .unwrap_or(self.mode)
+  }
+
+  /// Whether local changes to `rel` (edits, new files, deletions) are written to the remote.
+  pub fn pushes(&self, rel: &str) -> bool {
+    self.mode_of(rel) != SyncMode::ReadOnly
+  }
+
+  /// Whether remote changes to `rel` (edits, renames, deletions) are written to the vault.
+  pub fn pulls(&self, rel: &str) -> bool {
+    self.mode_of(rel) != SyncMode::PushOnly
+  }
+}
+
+/// Records a local change to `rel` that was kept from the remote. `hash` is the file's content
+/// hash, empty for a deletion.
+pub fn hold(vault_path: &str, rel: &str, hash: &str, summary: &mut SyncSummary) {
+  let key = (vault_path.to_string(), rel.to_string());
+  if let Ok(mut reported) = REPORTED.lock() {
+    if reported.get(&key).map(String::as_str) == Some(hash) {
+      return;
+    }
+    reported.insert(key, hash.to_string());
+  }
+  let what = if hash.is_empty() { "deleted" } else { "edited" };
+  summary.warnings.push(format!("{} was {} locally but is read-only; not pushed.", rel, what));
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "read_only_edit".to_string(),
+      path: rel.to_string(),
+      detail: format!("Read-only file {} locally; the remote copy is unchanged.", what),
+    },
+  );
+}
+
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config::read_config;
+use crate::sync::{read_mapping, sha256_hex};
+
+/// Encrypted rows carry their marker in `content` itself, so any client (and the server) can tell
+/// them apart without a schema change: `diregram-e2ee:v1:<key id>:<nonce>:<ciphertext>`.
+const ENVELOPE_PREFIX: &str = "diregram-e2ee:v1:";
+const KEY_STORAGE_PREFIX: &str = "diregram.e2ee.key.";
+
+/// project_folder_id -> key, so the keyring isn't hit for every file.
+static KEYS: Lazy<Mutex<HashMap<String, [u8; 32]>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct E2eeStatus {
+  pub enabled: bool,
+  pub has_key: bool,
+  /// Short fingerprint of the key; devices sharing a project must show the same one.
+  pub key_id: Option<String>,
+}
+
+fn key_id(key: &[u8; 32]) -> String {
+  let mut material = b"diregram-e2ee-key-id:".to_vec();
+  material.extend_from_slice(key);
+  sha256_hex(&material)[..8].to_string()
+}
+
+/// The project id is the salt, so every device derives the same key from the same passphrase.
+pub fn derive_key(passphrase: &str, project_folder_id: &str) -> Result<[u8; 32], String> {
+  let salt = format!("diregram-e2ee:{}", project_folder_id);
+  let mut key = [0u8; 32];
+  Argon2::default()
+    .hash_password_into(passphrase.as_bytes(), salt.as_bytes(), &mut key)
+    .map_err(|e| e.to_string())?;
+  Ok(key)
+}
+
+fn storage_key(project_folder_id: &str) -> String {
+  format!("{}{}", KEY_STORAGE_PREFIX, project_folder_id)
+}
+
+pub fn load_key(project_folder_id: &str) -> Result<Option<[u8; 32]>, String> {
+  if let Some(k) = KEYS.lock().ok().and_then(|g| g.get(project_folder_id).copied()) {
+    return Ok(Some(k));
+  }
+  let profile = crate::auth::current_profile();
+  let Some(encoded) = crate::secrets::get(profile.as_deref(), &storage_key(project_folder_id))? else {
+    return Ok(None);
+  };
+  let bytes = STANDARD.decode(encoded.trim()).map_err(|e| e.to_string())?;
+  let key: [u8; 32] = bytes.try_into().map_err(|_| "stored vault key has the wrong length".to_string())?;
+  if let Ok(mut g) = KEYS.lock() {
+    g.insert(project_folder_id.to_string(), key);
+  }
+  Ok(Some(key))
+}
+
+pub fn store_key(project_folder_id: &str, key: [u8; 32]) -> Result<(), String> {
+  let profile = crate::auth::current_profile();
+  crate::secrets::set(profile.as_deref(), &storage_key(project_folder_id), &STANDARD.encode(key))?;
+  if let Ok(mut g) = KEYS.lock() {
+    g.insert(project_folder_id.to_string(), key);
+  }
+  Ok(())
+}
+
+/// Content to send for a file in this vault: encrypted when E2EE is on, unchanged otherwise.
+pub fn seal(vault_path: &str, project_folder_id: &str, plaintext: &str) -> Result<String, String> {
+  if !read_config(vault_path)?.e2ee.enabled {
+    return Ok(plaintext.to_string());
+  }
+  let key = load_key(project_folder_id)?
+    .ok_or_else(|| "end-to-end encryption is on but this device has no vault key; unlock it with the passphrase".to_string())?;
+  let mut nonce = [0u8; 24];
+  getrandom::getrandom(&mut nonce).map_err(|e| e.to_string())?;
+  let ciphertext = XChaCha20Poly1305::new(&key.into())
+    .encrypt(XNonce::from_slice(&nonce), plaintext.as_bytes())
+    .map_err(|_| "failed to encrypt note content".to_string())?;
+  Ok(format!(
+    "{}{}:{}:{}",
+    ENVELOPE_PREFIX,
+    key_id(&key),
+    STANDARD.encode(nonce),
+    STANDARD.encode(ciphertext)
+  ))
+}
+
+/// Plaintext for pulled content. Unencrypted rows pass through; encrypted rows that can't be
+/// opened are an error, never written to disk as ciphertext.
+pub fn open(project_folder_id: &str, content: &str) -> Result<String, String> {
+  let Some(rest) = content.strip_prefix(ENVELOPE_PREFIX) else {
+    return Ok(content.to_string());
+  };
+  let mut parts = rest.splitn(3, ':');
+  let (Some(id), Some(nonce), Some(ciphertext)) = (parts.next(), parts.next(), parts.next()) else {
+    return Err("malformed encrypted content".to_string());
+  };
+  let key = load_key(project_folder_id)?
+    .ok_or_else(|| "content is end-to-end encrypted and this device has no vault key".to_string())?;
+  if key_id(&key) != id {
+    return Err(format!(
+      "content was encrypted with a different passphrase (key {}, this device has {})",
+      id,
+      key_id(&key)
+    ));
+  }
+  let nonce = STANDARD.decode(nonce).map_err(|e| e.to_string())?;
+  let ciphertext = STANDARD.decode(ciphertext).map_err(|e| e.to_string())?;
+  if nonce.len() != 24 {
+    return Err("malformed encrypted content".to_string());
+  }
+  let plain = XChaCha20Poly1305::new(&key.into())
+    .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+    .map_err(|_| "encrypted content failed authentication (corrupted or tampered)".to_string())?;
+  String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+pub fn project_of(vault_path: &str) -> Result<String, String> {
+  read_mapping(vault_path)?
+    .map(|m| m.project_folder_id)
+    .ok_or_else(|| "vault is not initialized for sync".to_string())
+}
+
+pub fn status_for(vault_path: &str, project_folder_id: &str) -> Result<E2eeStatus, String> {
+  let key = load_key(project_folder_id)?;
+  Ok(E2eeStatus {
+    enabled: read_config(vault_path)?.e2ee.enabled,
+    has_key: key.is_some(),
+    key_id: key.as_ref().map(key_id),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{derive_key, open, seal, ENVELOPE_PREFIX, KEYS};
+  use crate::config::{write_config, E2eeConfig, VaultConfigV1};
+
+  /// A vault with E2EE on (or off), and `project` unlocked with `passphrase` without the keyring.
+  fn vault(label: &str, enabled: bool, project: &str, passphrase: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("diregram-e2ee-{}-{}", label, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cfg = VaultConfigV1 {
+      e2ee: E2eeConfig { enabled },
+      ..Default::default()
+    };
+    write_config(dir.to_str().unwrap(), &cfg).unwrap();
+    KEYS.lock().unwrap().insert(project.to_string(), derive_key(passphrase, project).unwrap());
+    dir
+  }
+
+  #[test]
+  fn sealed_content_opens_to_the_original() {
+    let dir = vault("roundtrip", true, "p-roundtrip", "correct horse");
+    let note = "# Plan\n\nUnicode too: \u{e9}t\u{e9} \u{1f600}\n";
+    let sealed = seal(dir.to_str().unwrap(), "p-roundtrip", note).unwrap();
+    assert!(sealed.starts_with(ENVELOPE_PREFIX));
+    assert!(!sealed.contains("Plan"));
+    // A fresh nonce per push: the same note never seals to the same text.
+    assert_ne!(seal(dir.to_str().unwrap(), "p-roundtrip", note).unwrap(), sealed);
+    assert_eq!(open("p-roundtrip", &sealed).unwrap(), note);
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn tampered_or_foreign_content_does_not_open() {
+    let dir = vault("tamper", true, "p-tamper", "correct horse");
+    let sealed = seal(dir.to_str().unwrap(), "p-tamper", "secret").unwrap();
+    let (head, body) = sealed.rsplit_once(':').unwrap();
+    let flipped = if body.starts_with('A') { body.replacen('A', "B", 1) } else { format!("A{}", &body[1..]) };
+    assert!(open("p-tamper", &format!("{}:{}", head, flipped)).is_err());
+    // Another passphrase gives another key id.
+    KEYS.lock().unwrap().insert("p-tamper".to_string(), derive_key("wrong", "p-tamper").unwrap());
+    assert!(open("p-tamper", &sealed).unwrap_err().contains("different passphrase"));
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn plain_content_passes_through() {
+    let dir = vault("off", false, "p-off", "correct horse");
+    assert_eq!(seal(dir.to_str().unwrap(), "p-off", "hello").unwrap(), "hello");
+    assert_eq!(open("p-off", "hello").unwrap(), "hello");
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use crate::sync::sha256_hex;
+
+/// Long enough for the watcher to report a write, short enough that a real edit made right after
+/// a pull (which changes the hash anyway) is never mistaken for one.
+const ECHO_TTL: Duration = Duration::from_secs(10);
+
+/// Files sync itself just wrote, with the hash of what it wrote.
+static RECENT: Lazy<Mutex<HashMap<PathBuf, (String, Instant)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Records that sync wrote `contents` to `path`.
+pub fn note(path: &Path, contents: &[u8]) {
+  if let Ok(mut recent) = RECENT.lock() {
+    let now = Instant::now();
+    recent.retain(|_, (_, at)| now.duration_since(*at) < ECHO_TTL);
+    recent.insert(path.to_path_buf(), (sha256_hex(contents), now));
+  }
+}
+
+/// Whether a watcher event for `path` is just our own recent write: the file still holds exactly
+/// what sync wrote there.
+pub fn is_echo(path: &Path) -> bool {
+  let expected = match RECENT.lock() {
+    Ok(recent) => match recent.get(path) {
+      Some((hash, at)) if at.elapsed() < ECHO_TTL => hash.clone(),
+      _ => return false,
+    },
+    Err(_) => return false,
+  };
+  fs::read(path).map(|b| sha256_hex(&b) == expected).unwrap_or(false)
+}
@@ -0,0 +1,36 @@
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+
+/// Decodes a local note. UTF-8 (with or without BOM) is the norm; a UTF-16 BOM is honoured, and
+/// anything else that isn't valid UTF-8 is read as Windows-1252, the superset of Latin-1 that
+/// older Windows and Mac tools wrote. Returns the encoding name when the file wasn't UTF-8.
+pub fn decode(bytes: &[u8]) -> (String, Option<&'static str>) {
+  if let Some((enc, bom_len)) = Encoding::for_bom(bytes) {
+    if enc != UTF_8 {
+      let (text, _) = enc.decode_without_bom_handling(&bytes[bom_len..]);
+      return (text.into_owned(), Some(enc.name()));
+    }
+  }
+  match std::str::from_utf8(bytes) {
+    Ok(text) => (text.to_string(), None),
+    Err(_) => {
+      let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+      (text.into_owned(), Some(WINDOWS_1252.name()))
+    }
+  }
+}
+
+/// Encodes pulled text back into a note's original encoding. `None` when the encoding is unknown
+/// or can't represent the text (or is UTF-16, which encoding_rs only decodes), so the caller
+/// writes UTF-8 instead.
+pub fn encode(text: &str, name: &str) -> Option<Vec<u8>> {
+  let enc = Encoding::for_label(name.as_bytes())?;
+  if enc.output_encoding() != enc {
+    return None;
+  }
+  let (bytes, _, unmappable) = enc.encode(text);
+  if unmappable {
+    None
+  } else {
+    Some(bytes.into_owned())
+  }
+}
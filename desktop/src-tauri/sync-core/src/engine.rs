@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use tokio::sync::broadcast;
+
+use crate::sync::{SupabaseAuth, SyncEvent, SyncSummary};
+
+/// One vault synced with one project, usable without a running app: no app handle, window or
+/// managed state is needed. The CLI drives sync through this; the app's commands wrap the same
+/// functions, so the two behave alike. Keep new engine entry points here rather than in command
+/// signatures.
+pub struct VaultSync {
+  vault_path: String,
+  project_folder_id: String,
+  auth: SupabaseAuth,
+}
+
+/// Events of one vault as they are logged (the same ones `.diregram/events.jsonl` receives).
+pub struct EventStream {
+  vault_path: String,
+  rx: broadcast::Receiver<(String, SyncEvent)>,
+}
+
+impl EventStream {
+  /// The next event, or `None` once no more can arrive. Events missed because this stream fell
+  /// behind are skipped; the log file still has them.
+  pub async fn next(&mut self) -> Option<SyncEvent> {
+    loop {
+      match self.rx.recv().await {
+        Ok((vault_path, ev)) if vault_path == self.vault_path => return Some(ev),
+        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+        Err(broadcast::error::RecvError::Closed) => return None,
+      }
+    }
+  }
+
+  /// An event that has already arrived, without waiting.
+  pub fn try_next(&mut self) -> Option<SyncEvent> {
+    loop {
+      match self.rx.try_recv() {
+        Ok((vault_path, ev)) if vault_path == self.vault_path => return Some(ev),
+        Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+        Err(_) => return None,
+      }
+    }
+  }
+}
+
+impl VaultSync {
+  /// `auth` becomes the session the token refresh keeps current.
+  pub fn new(vault_path: impl Into<String>, project_folder_id: impl Into<String>, auth: SupabaseAuth) -> Self {
+    crate::auth::remember(&auth);
+    Self {
+      vault_path: vault_path.into(),
+      project_folder_id: project_folder_id.into(),
+      auth,
+    }
+  }
+
+  /// The session with the freshest tokens; store it if the process outlives this run.
+  pub fn auth(&self) -> SupabaseAuth {
+    crate::auth::latest(&self.auth)
+  }
+
+  /// Downloads remote changes once. `force` allows linking a vault that looks unsafe or already
+  /// holds another project (see `safety`).
+  pub async fn pull_once(&self, force: bool) -> Result<SyncSummary, String> {
+    crate::sync::sync_pull_once(self.vault_path.clone(), self.project_folder_id.clone(), self.auth(), Some(force)).await
+  }
+
+  /// Downloads the whole project into an empty folder, creating it if needed.
+  pub async fn bootstrap(&self, options: &crate::bootstrap::BootstrapOptions) -> Result<SyncSummary, String> {
+    crate::bootstrap::bootstrap(&self.vault_path, &self.project_folder_id, &self.auth(), options).await
+  }
+
+  /// Uploads every local change once.
+  pub async fn push_all(&self) -> Result<SyncSummary, String> {
+    crate::sync::sync_initial_import(self.vault_path.clone(), self.project_folder_id.clone(), self.auth()).await
+  }
+
+  /// Uploads local changes after `path` changed, skipping editor temp files.
+  pub async fn push_path(&self, path: &Path) -> Result<(), String> {
+    crate::sync::sync_one_path(&self.vault_path, &self.project_folder_id, &self.auth(), path).await
+  }
+
+  /// Exports the project's knowledge base into the vault.
+  pub async fn rag_export(&self) -> Result<(), String> {
+    crate::sync::rag_export_once(self.vault_path.clone(), self.project_folder_id.clone(), self.auth()).await
+  }
+
+  /// Events this vault logs from now on.
+  pub fn events(&self) -> EventStream {
+    EventStream {
+      vault_path: self.vault_path.clone(),
+      rx: crate::events::subscribe(),
+    }
+  }
+}
@@ -0,0 +1,456 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::sync::{diregram_dir, events_path, sha256_hex, SyncEvent};
+
+/// The active log is rotated into `events-YYYY-MM.jsonl` once it passes this size
+/// or when the month of its first event is over.
+const EVENTS_ROTATE_BYTES: u64 = 2 * 1024 * 1024;
+const EVENTS_INDEX_VERSION: u32 = 1;
+const ACTIVE_SEGMENT: &str = "events.jsonl";
+const REVERSE_READ_CHUNK: u64 = 64 * 1024;
+
+/// Appends come from the watcher, poller and command threads at once; rotation must not interleave.
+static EVENTS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+/// Every appended event with its vault, for in-process listeners (see `engine::VaultSync::events`).
+static LIVE: Lazy<broadcast::Sender<(String, SyncEvent)>> = Lazy::new(|| broadcast::channel(256).0);
+
+/// Who wrote an entry: this install of the app.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Actor {
+  pub device_id: String,
+  pub app_version: String,
+}
+
+impl Actor {
+  pub fn current() -> Self {
+    Actor {
+      device_id: crate::device::id(),
+      app_version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+  }
+}
+
+/// An event as stored: chained to the entry before it by `prev`, so editing, dropping or reordering
+/// lines breaks every hash after them. Readers that only want the event ignore the extra fields.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+  #[serde(flatten)]
+  pub ev: SyncEvent,
+  #[serde(default)]
+  pub actor: Option<Actor>,
+  /// Hash of the previous entry; empty for the first entry of a log (or the first after entries
+  /// written before chaining existed).
+  #[serde(default)]
+  pub prev: Option<String>,
+  #[serde(default)]
+  pub hash: Option<String>,
+}
+
+/// `sha256` over the JSON array `[prev, ts, kind, path, detail, device_id, app_version]`.
+pub fn entry_hash(prev: &str, ev: &SyncEvent, actor: &Actor) -> String {
+  let fields = [
+    prev,
+    ev.ts.as_str(),
+    ev.kind.as_str(),
+    ev.path.as_str(),
+    ev.detail.as_str(),
+    actor.device_id.as_str(),
+    actor.app_version.as_str(),
+  ];
+  sha256_hex(serde_json::to_string(&fields).unwrap_or_default().as_bytes())
+}
+
+/// `.diregram/events.index.json`: time range of every rotated segment, so queries can skip
+/// whole months and tail reads only touch the active file.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct EventsIndexV1 {
+  version: u32,
+  active_first_ts: Option<String>,
+  segments: Vec<EventSegment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct EventSegment {
+  name: String,
+  first_ts: String,
+  last_ts: String,
+  bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EventQuery {
+  pub kind: Option<String>,
+  pub path_prefix: Option<String>,
+  /// Inclusive RFC 3339 lower bound.
+  pub since: Option<String>,
+  /// Exclusive RFC 3339 upper bound.
+  pub until: Option<String>,
+  /// `next_cursor` from a previous page; returns events older than that page.
+  pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EventPage {
+  /// Oldest first, like the log itself.
+  pub events: Vec<SyncEvent>,
+  pub next_cursor: Option<String>,
+}
+
+fn index_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("events.index.json")
+}
+
+fn parse_ts(ts: &str) -> Option<DateTime<Utc>> {
+  DateTime::parse_from_rfc3339(ts).ok().map(|d| d.with_timezone(&Utc))
+}
+
+fn month_of(ts: &str) -> Option<String> {
+  parse_ts(ts).map(|d| d.format("%Y-%m").to_string())
+}
+
+fn first_event_ts(path: &Path) -> Option<String> {
+  let text = fs::read_to_string(path).ok()?;
+  text
+    .lines()
+    .find_map(|l| serde_json::from_str::<SyncEvent>(l).ok())
+    .map(|ev| ev.ts)
+}
+
+fn segment_stats(path: &Path) -> Option<EventSegment> {
+  let text = fs::read_to_string(path).ok()?;
+  let mut first: Option<String> = None;
+  let mut last: Option<String> = None;
+  for ev in text.lines().filter_map(|l| serde_json::from_str::<SyncEvent>(l).ok()) {
+    if first.is_none() {
+      first = Some(ev.ts.clone());
+    }
+    last = Some(ev.ts);
+  }
+  Some(EventSegment {
+    name: path.file_name()?.to_string_lossy().to_string(),
+    first_ts: first?,
+    last_ts: last?,
+    bytes: text.len() as u64,
+  })
+}
+
+/// Rebuilds the index from disk; used when it is missing (older vaults) or unreadable.
+fn rebuild_index(vault_path: &str) -> EventsIndexV1 {
+  let mut segments: Vec<EventSegment> = fs::read_dir(diregram_dir(vault_path))
+    .map(|rd| {
+      rd.filter_map(Result::ok)
+        .filter(|e| {
+          let name = e.file_name().to_string_lossy().to_string();
+          name.starts_with("events-") && name.ends_with(".jsonl")
+        })
+        .filter_map(|e| segment_stats(&e.path()))
+        .collect()
+    })
+    .unwrap_or_default();
+  segments.sort_by(|a, b| a.name.cmp(&b.name));
+  EventsIndexV1 {
+    version: EVENTS_INDEX_VERSION,
+    active_first_ts: first_event_ts(&events_path(vault_path)),
+    segments,
+  }
+}
+
+fn read_index(vault_path: &str) -> EventsIndexV1 {
+  fs::read_to_string(index_path(vault_path))
+    .ok()
+    .and_then(|text| serde_json::from_str::<EventsIndexV1>(&text).ok())
+    .filter(|idx| idx.version == EVENTS_INDEX_VERSION)
+    .unwrap_or_else(|| rebuild_index(vault_path))
+}
+
+fn write_index(vault_path: &str, idx: &EventsIndexV1) -> Result<(), String> {
+  let text = serde_json::to_string_pretty(idx).map_err(|e| e.to_string())?;
+  fs::write(index_path(vault_path), text).map_err(|e| e.to_string())
+}
+
+/// Moves the active log onto the end of its month's segment and starts a fresh one.
+fn rotate(vault_path: &str, idx: &mut EventsIndexV1) -> Result<(), String> {
+  let active = events_path(vault_path);
+  let text = fs::read_to_string(&active).map_err(|e| e.to_string())?;
+  let month = idx
+    .active_first_ts
+    .as_deref()
+    .and_then(month_of)
+    .unwrap_or_else(|| Utc::now().format("%Y-%m").to_string());
+  let name = format!("events-{}.jsonl", month);
+  let seg_path = diregram_dir(vault_path).join(&name);
+  let mut f = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(&seg_path)
+    .map_err(|e| e.to_string())?;
+  f.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+  f.sync_all().map_err(|e| e.to_string())?;
+  fs::remove_file(&active).map_err(|e| e.to_string())?;
+
+  idx.segments.retain(|s| s.name != name);
+  if let Some(stats) = segment_stats(&seg_path) {
+    idx.segments.push(stats);
+  }
+  idx.segments.sort_by(|a, b| a.name.cmp(&b.name));
+  idx.active_first_ts = None;
+  Ok(())
+}
+
+/// Events appended from now on, for every vault. Slow receivers miss events rather than hold
+/// up syncing.
+pub fn subscribe() -> broadcast::Receiver<(String, SyncEvent)> {
+  LIVE.subscribe()
+}
+
+/// Hash of the newest chained entry on disk: the end of the active log, or of the newest segment
+/// right after a rotation.
+fn newest_hash(vault_path: &str, idx: &EventsIndexV1) -> String {
+  let mut paths = vec![events_path(vault_path)];
+  paths.extend(idx.segments.iter().rev().map(|s| diregram_dir(vault_path).join(&s.name)));
+  for path in paths {
+    let mut found: Option<String> = None;
+    let mut any = false;
+    let _ = read_lines_rev(&path, None, |_, line| {
+      let Ok(entry) = serde_json::from_slice::<AuditEntry>(line) else { return true };
+      any = true;
+      found = entry.hash;
+      false
+    });
+    if any {
+      return found.unwrap_or_default();
+    }
+  }
+  String::new()
+}
+
+pub fn append(vault_path: &str, ev: &SyncEvent) -> Result<(), String> {
+  // No receivers is the common case, and not an error.
+  let _ = LIVE.send((vault_path.to_string(), ev.clone()));
+  let _guard = EVENTS_LOCK.lock().map_err(|_| "events lock poisoned".to_string())?;
+  fs::create_dir_all(diregram_dir(vault_path)).map_err(|e| e.to_string())?;
+  let p = events_path(vault_path);
+  let mut idx = read_index(vault_path);
+  let mut index_dirty = !index_path(vault_path).exists();
+  // Read back rather than cached: the CLI and the app may both append to the same vault.
+  let prev = newest_hash(vault_path, &idx);
+
+  let size = fs::metadata(&p).map(|m| m.len()).unwrap_or(0);
+  if size > 0 {
+    let month_over = match (idx.active_first_ts.as_deref().and_then(month_of), month_of(&ev.ts)) {
+      (Some(a), Some(b)) => a != b,
+      _ => false,
+    };
+    if size >= EVENTS_ROTATE_BYTES || month_over {
+      rotate(vault_path, &mut idx)?;
+      index_dirty = true;
+    }
+  }
+  if idx.active_first_ts.is_none() {
+    idx.active_first_ts = Some(ev.ts.clone());
+    index_dirty = true;
+  }
+
+  let mut f = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(&p)
+    .map_err(|e| e.to_string())?;
+  let actor = Actor::current();
+  let hash = entry_hash(&prev, ev, &actor);
+  let entry = AuditEntry {
+    ev: ev.clone(),
+    actor: Some(actor),
+    prev: Some(prev),
+    hash: Some(hash),
+  };
+  let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+  writeln!(f, "{}", line).map_err(|e| e.to_string())?;
+  if index_dirty {
+    write_index(vault_path, &idx)?;
+  }
+  Ok(())
+}
+
+/// Every stored entry, oldest first: rotated segments in order, then the active log. Segments that
+/// end before `since` are skipped without reading them.
+pub fn read_entries(vault_path: &str, since: Option<&str>) -> Result<Vec<AuditEntry>, String> {
+  let since = since.and_then(parse_ts);
+  let idx = {
+    let _guard = EVENTS_LOCK.lock().map_err(|_| "events lock poisoned".to_string())?;
+    read_index(vault_path)
+  };
+  let mut names: Vec<String> = idx
+    .segments
+    .iter()
+    .filter(|s| match (since, parse_ts(&s.last_ts)) {
+      (Some(since), Some(last)) => last >= since,
+      _ => true,
+    })
+    .map(|s| s.name.clone())
+    .collect();
+  names.push(ACTIVE_SEGMENT.to_string());
+  let mut out = Vec::new();
+  for name in names {
+    let text = match fs::read_to_string(diregram_dir(vault_path).join(&name)) {
+      Ok(t) => t,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+      Err(e) => return Err(e.to_string()),
+    };
+    out.extend(text.lines().filter_map(|l| serde_json::from_str::<AuditEntry>(l).ok()));
+  }
+  Ok(out)
+}
+
+/// Walks complete lines backwards from `before` (or the end of the file). `visit` receives each
+/// line's starting byte offset and returns `false` to stop.
+fn read_lines_rev(path: &Path, before: Option<u64>, mut visit: impl FnMut(u64, &[u8]) -> bool) -> Result<(), String> {
+  let mut f = match File::open(path) {
+    Ok(f) => f,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+    Err(e) => return Err(e.to_string()),
+  };
+  let len = f.metadata().map_err(|e| e.to_string())?.len();
+  let mut pos = before.unwrap_or(len).min(len);
+  let mut carry: Vec<u8> = Vec::new();
+  while pos > 0 {
+    let start = pos.saturating_sub(REVERSE_READ_CHUNK);
+    let mut data = vec![0u8; (pos - start) as usize];
+    f.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+    f.read_exact(&mut data).map_err(|e| e.to_string())?;
+    data.extend_from_slice(&carry);
+
+    let mut line_end = data.len();
+    for i in (0..data.len()).rev() {
+      if data[i] != b'\n' {
+        continue;
+      }
+      if i + 1 < line_end && !visit(start + i as u64 + 1, &data[i + 1..line_end]) {
+        return Ok(());
+      }
+      line_end = i;
+    }
+    carry = data[..line_end].to_vec();
+    pos = start;
+  }
+  if !carry.is_empty() {
+    visit(0, &carry);
+  }
+  Ok(())
+}
+
+fn matches(ev: &SyncEvent, q: &EventQuery, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> bool {
+  if let Some(kind) = q.kind.as_deref().filter(|k| !k.is_empty()) {
+    if ev.kind != kind {
+      return false;
+    }
+  }
+  if let Some(prefix) = q.path_prefix.as_deref().filter(|p| !p.is_empty()) {
+    if !ev.path.starts_with(prefix) {
+      return false;
+    }
+  }
+  if since.is_some() || until.is_some() {
+    let Some(ts) = parse_ts(&ev.ts) else { return false };
+    if since.map(|s| ts < s).unwrap_or(false) || until.map(|u| ts >= u).unwrap_or(false) {
+      return false;
+    }
+  }
+  true
+}
+
+fn parse_cursor(cursor: &str) -> Result<(String, u64), String> {
+  let (name, offset) = cursor.rsplit_once('@').ok_or_else(|| "invalid events cursor".to_string())?;
+  let offset = offset.parse::<u64>().map_err(|_| "invalid events cursor".to_string())?;
+  Ok((name.to_string(), offset))
+}
+
+/// Newest-first scan over the active log and then rotated segments, skipping segments whose
+/// time range cannot match.
+pub fn query(vault_path: &str, q: &EventQuery, limit: usize) -> Result<EventPage, String> {
+  let since = match q.since.as_deref().filter(|s| !s.is_empty()) {
+    Some(s) => Some(parse_ts(s).ok_or_else(|| format!("invalid since timestamp: {}", s))?),
+    None => None,
+  };
+  let until = match q.until.as_deref().filter(|s| !s.is_empty()) {
+    Some(s) => Some(parse_ts(s).ok_or_else(|| format!("invalid until timestamp: {}", s))?),
+    None => None,
+  };
+  let resume = match q.cursor.as_deref().filter(|c| !c.is_empty()) {
+    Some(c) => Some(parse_cursor(c)?),
+    None => None,
+  };
+
+  let idx = {
+    let _guard = EVENTS_LOCK.lock().map_err(|_| "events lock poisoned".to_string())?;
+    read_index(vault_path)
+  };
+  let mut segments: Vec<(String, Option<&EventSegment>)> = vec![(ACTIVE_SEGMENT.to_string(), None)];
+  segments.extend(idx.segments.iter().rev().map(|s| (s.name.clone(), Some(s))));
+  if let Some((name, _)) = &resume {
+    match segments.iter().position(|(n, _)| n == name) {
+      Some(i) => {
+        segments.drain(..i);
+      }
+      None => return Err("events cursor no longer valid (log was rotated)".to_string()),
+    }
+  }
+
+  let limit = limit.max(1);
+  let mut out: Vec<SyncEvent> = Vec::new();
+  let mut next_cursor: Option<String> = None;
+  for (name, seg) in segments {
+    if let Some(seg) = seg {
+      if let (Some(s), Some(last)) = (since, parse_ts(&seg.last_ts)) {
+        if last < s {
+          break;
+        }
+      }
+      if let (Some(u), Some(first)) = (until, parse_ts(&seg.first_ts)) {
+        if first >= u {
+          continue;
+        }
+      }
+    }
+    let before = resume.as_ref().filter(|(n, _)| *n == name).map(|(_, off)| *off);
+    let path = diregram_dir(vault_path).join(&name);
+    let mut stop = false;
+    read_lines_rev(&path, before, |offset, line| {
+      let Ok(ev) = serde_json::from_slice::<SyncEvent>(line) else { return true };
+      if let (Some(s), Some(ts)) = (since, parse_ts(&ev.ts)) {
+        // Logs are append-only, so once we are past `since` everything older is too.
+        if ts < s {
+          stop = true;
+          return false;
+        }
+      }
+      if !matches(&ev, q, since, until) {
+        return true;
+      }
+      if out.len() == limit {
+        next_cursor = Some(format!("{}@{}", name, offset + line.len() as u64 + 1));
+        stop = true;
+        return false;
+      }
+      out.push(ev);
+      true
+    })?;
+    if stop {
+      break;
+    }
+  }
+  out.reverse();
+  Ok(EventPage {
+    events: out,
+    next_cursor,
+  })
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{diregram_dir, fetch_all_folders, rest_base, send_with_refresh, write_atomic, FolderNode, SupabaseAuth};
+
+/// Same cap the full fetch has always used.
+const FOLDER_LIMIT: &str = "10000";
+
+/// `get_rows` error for a missing table or function (PostgREST answers 404).
+const MISSING: &str = "folders fetch failed: not found";
+
+/// Supabase URLs whose database has no `folders_subtree` function; they get account-wide fetches.
+static NO_SUBTREE_RPC: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Where folder rows come from: one project's subtree (server-side), or every visible folder.
+#[derive(Clone, Copy)]
+enum Source<'a> {
+  Subtree(&'a str),
+  All,
+}
+
+impl Source<'_> {
+  fn url(&self, auth: &SupabaseAuth) -> Result<reqwest::Url, String> {
+    let (path, root) = match self {
+      Source::Subtree(root) => ("rpc/folders_subtree", Some(*root)),
+      Source::All => ("folders", None),
+    };
+    let mut url = reqwest::Url::parse(&format!("{}/{}", rest_base(auth), path)).map_err(|e| e.to_string())?;
+    if let Some(root) = root {
+      url.query_pairs_mut().append_pair("p_root", root);
+    }
+    Ok(url)
+  }
+
+  fn scope(&self) -> String {
+    match self {
+      Source::Subtree(root) => root.to_string(),
+      Source::All => String::new(),
+    }
+  }
+}
+
+/// `.diregram/folders.json`: the folders of `scope` (a project id, or empty for the whole
+/// account) as of `watermark`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct FolderCacheV1 {
+  version: u32,
+  supabase_url: String,
+  owner_id: String,
+  scope: String,
+  /// Newest folder `updated_at` seen (server time).
+  watermark: Option<String>,
+  folders: Vec<FolderNode>,
+}
+
+fn cache_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("folders.json")
+}
+
+fn load(vault_path: &str, auth: &SupabaseAuth, source: Source) -> Option<FolderCacheV1> {
+  let text = fs::read_to_string(cache_path(vault_path)).ok()?;
+  let cache: FolderCacheV1 = serde_json::from_str(&text).ok()?;
+  (cache.supabase_url == auth.supabase_url
+    && cache.owner_id == auth.owner_id
+    && cache.scope == source.scope()
+    && cache.watermark.is_some())
+  .then_some(cache)
+}
+
+fn save(vault_path: &str, auth: &SupabaseAuth, source: Source, folders: &[FolderNode]) {
+  let cache = FolderCacheV1 {
+    version: 1,
+    supabase_url: auth.supabase_url.clone(),
+    owner_id: auth.owner_id.clone(),
+    scope: source.scope(),
+    watermark: folders
+      .iter()
+      .filter_map(|f| f.updated_at.clone())
+      .reduce(|a, b| if crate::clock::is_after(&b, &a) { b } else { a }),
+    folders: folders.to_vec(),
+  };
+  if let Ok(text) = serde_json::to_string(&cache) {
+    let _ = write_atomic(&cache_path(vault_path), text);
+  }
+}
+
+async fn get_rows<T: for<'de> Deserialize<'de>>(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  source: Source<'_>,
+  query: &[(&str, &str)],
+) -> Result<Vec<T>, String> {
+  let mut url = source.url(auth)?;
+  {
+    let mut q = url.query_pairs_mut();
+    for (k, v) in query {
+      q.append_pair(k, v);
+    }
+    q.append_pair("limit", FOLDER_LIMIT);
+  }
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+          return Err(MISSING.to_string());
+        }
+        if !res.status().is_success() {
+          return Err(format!("folders fetch failed: HTTP {}", res.status()));
+        }
+        res.json::<Vec<T>>().await.map_err(|e| e.to_string())
+      })
+    },
+  )
+  .await
+}
+
+/// Number of folders in `source`, from PostgREST's `Content-Range` total.
+async fn remote_count(client: &reqwest::Client, auth: &mut SupabaseAuth, source: Source<'_>) -> Option<usize> {
+  let mut url = source.url(auth).ok()?;
+  url.query_pairs_mut().append_pair("select", "id").append_pair("limit", "1");
+  send_with_refresh(
+    client,
+    auth,
+    || client.head(url.clone()).header("Prefer", "count=exact"),
+    |res| Box::pin(async move { Ok(crate::paging::total_from(&res).map(|n| n as usize)) }),
+  )
+  .await
+  .ok()
+  .flatten()
+}
+
+async fn fetch_full(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  source: Source<'_>,
+) -> Result<Vec<FolderNode>, String> {
+  match get_rows::<FolderNode>(client, auth, source, &[("select", "id,parent_id,name,updated_at")]).await {
+    Ok(rows) => {
+      save(vault_path, auth, source, &rows);
+      Ok(rows)
+    }
+    Err(e) if matches!(source, Source::Subtree(_)) => Err(e),
+    Err(_) => {
+      let _ = fs::remove_file(cache_path(vault_path));
+      fetch_all_folders(client, auth).await
+    }
+  }
+}
+
+async fn fetch_cached(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  source: Source<'_>,
+) -> Result<Vec<FolderNode>, String> {
+  let Some(cache) = load(vault_path, auth, source) else {
+    return fetch_full(client, auth, vault_path, source).await;
+  };
+
+  let watermark = cache.watermark.clone().unwrap_or_default();
+  let since = format!("gte.{}", watermark);
+  let changed = get_rows::<FolderNode>(
+    client,
+    auth,
+    source,
+    &[("select", "id,parent_id,name,updated_at"), ("updated_at", since.as_str())],
+  )
+  .await?;
+  let mut by_id: HashMap<String, FolderNode> = cache.folders.into_iter().map(|f| (f.id.clone(), f)).collect();
+  for f in changed {
+    by_id.insert(f.id.clone(), f);
+  }
+
+  if remote_count(client, auth, source).await != Some(by_id.len()) {
+    // Subtrees change shape without every member being touched (a folder moved in brings its
+    // children along); re-reading one project is cheap, so don't try to patch it up.
+    if matches!(source, Source::Subtree(_)) {
+      return fetch_full(client, auth, vault_path, source).await;
+    }
+    #[derive(Deserialize)]
+    struct IdRow {
+      id: String,
+    }
+    let live: HashSet<String> = get_rows::<IdRow>(client, auth, source, &[("select", "id")])
+      .await?
+      .into_iter()
+      .map(|r| r.id)
+      .collect();
+    by_id.retain(|id, _| live.contains(id));
+    // Still short: a folder committed with an `updated_at` below the watermark. Start over.
+    if by_id.len() != live.len() {
+      return fetch_full(client, auth, vault_path, source).await;
+    }
+  }
+
+  let folders: Vec<FolderNode> = by_id.into_values().collect();
+  save(vault_path, auth, source, &folders);
+  Ok(folders)
+}
+
+fn subtree_rpc_missing(auth: &SupabaseAuth) -> bool {
+  NO_SUBTREE_RPC.lock().map(|g| g.contains(&auth.supabase_url)).unwrap_or(false)
+}
+
+fn mark_subtree_rpc_missing(auth: &SupabaseAuth) {
+  if let Ok(mut g) = NO_SUBTREE_RPC.lock() {
+    g.insert(auth.supabase_url.clone());
+  }
+}
+
+/// The folders of a project (itself included), downloading only folders changed since the vault's
+/// last pull. Changes are found through `updated_at` and the server's folder count. Databases
+/// without `folders_subtree` fall back to the account-wide tree, and without `folders.updated_at`
+/// to a full fetch every time.
+pub async fn fetch_folders(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &str,
+) -> Result<Vec<FolderNode>, String> {
+  if !subtree_rpc_missing(auth) {
+    match fetch_cached(client, auth, vault_path, Source::Subtree(project_folder_id)).await {
+      Err(e) if e == MISSING => mark_subtree_rpc_missing(auth),
+      res => return res,
+    }
+  }
+  fetch_cached(client, auth, vault_path, Source::All).await
+}
+
+/// A project's folders without touching any vault cache; no project row when it is gone.
+pub async fn fetch_subtree(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+) -> Result<Vec<FolderNode>, String> {
+  if !subtree_rpc_missing(auth) {
+    let source = Source::Subtree(project_folder_id);
+    match get_rows::<FolderNode>(client, auth, source, &[("select", "id,parent_id,name")]).await {
+      Err(e) if e == MISSING => mark_subtree_rpc_missing(auth),
+      res => return res,
+    }
+  }
+  fetch_all_folders(client, auth).await
+}
+
+/// Every folder the account can see, with `updated_at` where the database has that column.
+pub async fn fetch_account(client: &reqwest::Client, auth: &mut SupabaseAuth) -> Result<Vec<FolderNode>, String> {
+  match get_rows::<FolderNode>(client, auth, Source::All, &[("select", "id,parent_id,name,updated_at")]).await {
+    Ok(rows) => Ok(rows),
+    Err(_) => fetch_all_folders(client, auth).await,
+  }
+}
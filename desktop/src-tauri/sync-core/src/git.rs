@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use serde::Serialize;
+
+use crate::config::read_config;
+use crate::sync::{append_event, now_iso, SyncEvent, SyncSummary};
+
+/// Sync internals never belong in a snapshot.
+pub const EXCLUDE: &str = ":(exclude).diregram";
+
+/// Used when git has no identity configured, so snapshots never fail for want of one.
+const FALLBACK_NAME: &str = "Diregram";
+const FALLBACK_EMAIL: &str = "sync@diregram.local";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GitChange {
+  /// The two porcelain status letters, e.g. ` M`, `??` or `R `.
+  pub status: String,
+  /// Relative to the repository root.
+  pub path: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct GitStatus {
+  /// `false` when the vault is not inside a git repository; nothing else is set then.
+  pub repository: bool,
+  pub branch: Option<String>,
+  pub changes: Vec<GitChange>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GitCommit {
+  pub hash: String,
+  pub author: String,
+  pub date: String,
+  pub subject: String,
+}
+
+/// Output of `git -C <vault> <args>`, or its stderr as the error.
+pub fn git(vault_path: &str, args: &[&str]) -> Result<String, String> {
+  let mut command = Command::new("git");
+  command.arg("-C").arg(vault_path).args(args);
+  #[cfg(target_os = "windows")]
+  {
+    // CREATE_NO_WINDOW: snapshots run after background syncs.
+    use std::os::windows::process::CommandExt;
+    command.creation_flags(0x0800_0000);
+  }
+  let out = command.output().map_err(|e| format!("git: {}", e))?;
+  if !out.status.success() {
+    let err = String::from_utf8_lossy(&out.stderr).trim().to_string();
+    return Err(format!("git {}: {}", args.first().copied().unwrap_or(""), err));
+  }
+  Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+pub fn is_repository(vault_path: &str) -> bool {
+  git(vault_path, &["rev-parse", "--is-inside-work-tree"]).map(|s| s.trim() == "true").unwrap_or(false)
+}
+
+/// Ignores `.diregram/` in the repository's local exclude file rather than through a `.gitignore`
+/// the sync would push as a note's sibling.
+fn ignore_internals(vault_path: &str) -> Result<(), String> {
+  let exclude = git(vault_path, &["rev-parse", "--git-path", "info/exclude"])?;
+  let exclude = Path::new(vault_path).join(exclude.trim());
+  let existing = fs::read_to_string(&exclude).unwrap_or_default();
+  if !existing.lines().any(|l| l.trim() == ".diregram/") {
+    if let Some(parent) = exclude.parent() {
+      fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&exclude, format!("{}.diregram/\n", existing)).map_err(|e| e.to_string())?;
+  }
+  Ok(())
+}
+
+/// `Diregram pull: 2 created, 1 updated, 0 deleted` followed by one `key: value` line per
+/// non-zero count of the summary, so history can be grepped by field.
+fn commit_message(operation: &str, summary: &SyncSummary) -> String {
+  let mut message = format!(
+    "Diregram {}: {} created, {} updated, {} deleted\n\n",
+    operation, summary.files_created, summary.files_updated, summary.files_deleted
+  );
+  let fields = serde_json::to_value(summary).ok();
+  for (key, value) in fields.as_ref().and_then(|v| v.as_object()).into_iter().flatten() {
+    let n = match value {
+      serde_json::Value::Number(n) => n.as_u64().unwrap_or(0),
+      serde_json::Value::Array(a) => a.len() as u64,
+      _ => 0,
+    };
+    if n > 0 {
+      message.push_str(&format!("{}: {}\n", key, n));
+    }
+  }
+  for e in &summary.errors {
+    message.push_str(&format!("error: {}\n", e));
+  }
+  message
+}
+
+/// Commits the vault as it is after a sync, creating the repository on first use. Returns the new
+/// commit's hash, or `None` when snapshots are off or nothing changed. Pushes only when a remote
+/// is configured.
+pub fn snapshot(vault_path: &str, operation: &str, summary: &SyncSummary) -> Result<Option<String>, String> {
+  let cfg = read_config(vault_path)?.git;
+  if !cfg.enabled {
+    return Ok(None);
+  }
+  if !is_repository(vault_path) {
+    git(vault_path, &["init", "--quiet"])?;
+  }
+  ignore_internals(vault_path)?;
+  // Pathspecs keep a vault inside a larger repository from committing anything outside it.
+  git(vault_path, &["add", "--all", "--", "."])?;
+  if git(vault_path, &["diff", "--cached", "--quiet", "--", ".", EXCLUDE]).is_ok() {
+    return Ok(None);
+  }
+  let message = commit_message(operation, summary);
+  let mut args: Vec<String> = Vec::new();
+  if git(vault_path, &["config", "user.email"]).is_err() {
+    args.extend(["-c".to_string(), format!("user.name={}", FALLBACK_NAME)]);
+    args.extend(["-c".to_string(), format!("user.email={}", FALLBACK_EMAIL)]);
+  }
+  args.extend(["commit", "--quiet", "-m", &message, "--", ".", EXCLUDE].map(String::from));
+  git(vault_path, &args.iter().map(String::as_str).collect::<Vec<_>>())?;
+  let hash = git(vault_path, &["rev-parse", "HEAD"])?.trim().to_string();
+  if let Some(remote) = cfg.push_remote.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+    // Only a remote the repository defines; anything else (e.g. `--receive-pack=<cmd>`) is refused.
+    let known = git(vault_path, &["remote"])?;
+    if remote.starts_with('-') || !known.lines().any(|r| r.trim() == remote) {
+      return Err(format!("git.push_remote {:?} is not a remote of the vault's repository", remote));
+    }
+    git(vault_path, &["push", "--quiet", "--", remote, "HEAD"])?;
+  }
+  Ok(Some(hash))
+}
+
+/// Snapshots the vault after a successful pull or import, logging the outcome. A failed snapshot
+/// never fails the sync.
+pub async fn after_sync(vault_path: &str, operation: &'static str, summary: &SyncSummary) {
+  let vp = vault_path.to_string();
+  let s = summary.clone();
+  let res = tokio::task::spawn_blocking(move || snapshot(&vp, operation, &s))
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+  let (kind, detail) = match res {
+    Ok(Some(hash)) => ("git_commit", format!("Committed the vault after {} as {}", operation, hash)),
+    Ok(None) => return,
+    Err(e) => ("git_error", format!("Snapshot after {} failed: {}", operation, e)),
+  };
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: kind.to_string(),
+      path: String::new(),
+      detail,
+    },
+  );
+}
+
@@ -0,0 +1,80 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::sync::{append_event, now_iso, rest_base, send_with_refresh, SupabaseAuth, SyncEvent};
+
+/// An idle vault logs one `pull_idle` event per this many skipped pulls (10 minutes at the
+/// default 5s interval) rather than one per pull.
+const IDLE_HEARTBEAT_EVERY: u32 = 120;
+
+/// Supabase URLs whose database has no `project_sync_head` function.
+static NO_HEAD_RPC: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+/// Vault -> pulls skipped in a row.
+static IDLE: Lazy<Mutex<HashMap<String, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Summary of the project's remote state (counts and newest `updated_at` of its folders, files and
+/// resources). Equal values mean a pull would find nothing new. `None` when the server can't tell.
+pub async fn fetch(client: &reqwest::Client, auth: &mut SupabaseAuth, project_folder_id: &str) -> Option<String> {
+  if NO_HEAD_RPC.lock().map(|g| g.contains(&auth.supabase_url)).unwrap_or(false) {
+    return None;
+  }
+  let mut url = reqwest::Url::parse(&format!("{}/rpc/project_sync_head", rest_base(auth))).ok()?;
+  url.query_pairs_mut().append_pair("p_root", project_folder_id);
+  let res = send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+          return Ok(None);
+        }
+        if !res.status().is_success() {
+          return Err(format!("sync head fetch failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<serde_json::Value> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(Some(rows.into_iter().next().map(|r| r.to_string()).unwrap_or_default()))
+      })
+    },
+  )
+  .await;
+  match res {
+    Ok(Some(head)) => Some(head),
+    Ok(None) => {
+      if let Ok(mut g) = NO_HEAD_RPC.lock() {
+        g.insert(auth.supabase_url.clone());
+      }
+      None
+    }
+    Err(_) => None,
+  }
+}
+
+/// Records a skipped pull, logging a heartbeat on the first and every `IDLE_HEARTBEAT_EVERY`th.
+pub fn note_idle(vault_path: &str) {
+  let count = {
+    let Ok(mut g) = IDLE.lock() else { return };
+    let n = g.entry(vault_path.to_string()).or_insert(0);
+    *n += 1;
+    *n
+  };
+  if count == 1 || count % IDLE_HEARTBEAT_EVERY == 0 {
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "pull_idle".to_string(),
+        path: String::new(),
+        detail: format!("No remote changes ({} pulls skipped).", count),
+      },
+    );
+  }
+}
+
+pub fn note_active(vault_path: &str) {
+  if let Ok(mut g) = IDLE.lock() {
+    g.remove(vault_path);
+  }
+}
@@ -0,0 +1,72 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+static HOST: OnceCell<Box<dyn Host>> = OnceCell::new();
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The vaults whose background sync was stopped, and a receiver that fires once their in-flight
+/// runs are done.
+pub type Stopped = (Vec<String>, oneshot::Receiver<()>);
+
+/// What the engine asks of the app it runs in: a window to show progress in, notifications, and
+/// the background jobs it starts. The desktop app installs one at startup. Without one (the CLI,
+/// tests, other tools) window events and notifications are dropped, and the event log and
+/// [`crate::VaultSync::events`] are the way to follow a sync.
+pub trait Host: Send + Sync {
+  /// Sends `payload` to the app's windows as `event`, e.g. `sync://fetch_progress`.
+  fn emit(&self, event: &str, payload: serde_json::Value);
+
+  /// Shows a desktop notification.
+  fn notify(&self, title: &str, body: &str);
+
+  /// A note was pushed, so the app can queue it for ingest.
+  fn pushed(&self, _vault_path: &str, _rel_path: &str) {}
+
+  /// The vault's config changed; running jobs should re-read it.
+  fn reload_config(&self, _vault_path: &str) {}
+
+  /// Stops the watchers and pollers of an account, except the run keyed `except`. An error when
+  /// nothing runs in the background.
+  fn stop_account(
+    &self,
+    _supabase_url: String,
+    _owner_id: String,
+    _except: String,
+  ) -> BoxFuture<'_, Result<Stopped, String>> {
+    Box::pin(async { Err("no background sync runs".to_string()) })
+  }
+}
+
+/// Installs the app's host. Only the first call counts.
+pub fn install(host: impl Host + 'static) {
+  let _ = HOST.set(Box::new(host));
+}
+
+pub fn get() -> Option<&'static dyn Host> {
+  HOST.get().map(|h| h.as_ref())
+}
+
+pub fn emit(event: &str, payload: impl Serialize) {
+  let Some(host) = get() else { return };
+  match serde_json::to_value(payload) {
+    Ok(value) => host.emit(event, value),
+    Err(e) => tracing::warn!(error = %e, event, "could not serialize event"),
+  }
+}
+
+pub fn pushed(vault_path: &str, rel_path: &str) {
+  if let Some(host) = get() {
+    host.pushed(vault_path, rel_path);
+  }
+}
+
+pub fn reload_config(vault_path: &str) {
+  if let Some(host) = get() {
+    host.reload_config(vault_path);
+  }
+}
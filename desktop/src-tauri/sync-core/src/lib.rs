@@ -0,0 +1,74 @@
+//! The Diregram vault sync engine: pushes and pulls a folder of notes against a Diregram project.
+//!
+//! [`VaultSync`] is the entry point for tools built on the engine. The desktop app uses the modules
+//! directly and installs a [`Host`] to receive progress events and notifications; without one the
+//! engine runs headless.
+
+pub mod activity;
+pub mod attachments;
+pub mod auth;
+pub mod autosync;
+pub mod blobs;
+pub mod bootstrap;
+pub mod chunk;
+pub mod clip;
+pub mod clock;
+pub mod codec;
+pub mod config;
+pub mod conflicts;
+pub mod crdt;
+pub mod daily;
+pub mod delete_guard;
+pub mod delta;
+pub mod device;
+pub mod device_control;
+pub mod direction;
+pub mod e2ee;
+pub mod echo;
+pub mod encoding;
+pub mod engine;
+pub mod events;
+pub mod folders;
+pub mod git;
+pub mod head;
+pub mod host;
+pub mod links;
+pub mod lock;
+pub mod metrics;
+pub mod mirror;
+pub mod names;
+pub mod nexusdoc;
+pub mod normalize;
+pub mod notes;
+pub mod notify;
+pub mod open_files;
+pub mod paging;
+pub mod policy;
+pub mod rag;
+pub mod revisions;
+pub mod routing;
+pub mod s3;
+pub mod safety;
+pub mod scan;
+pub mod secret_file;
+pub mod secrets;
+pub mod sharing;
+pub mod shred;
+pub mod site;
+pub mod smartfolders;
+pub mod status;
+pub mod symlinks;
+pub mod sync;
+pub mod tags;
+pub mod tempfiles;
+pub mod throttle;
+pub mod trash;
+pub mod vaults;
+pub mod volume;
+
+pub use engine::{EventStream, VaultSync};
+pub use host::Host;
+pub use sync::{SupabaseAuth, SyncEvent, SyncSummary};
+
+/// Keychain service name, and the bundle identifier of the desktop app.
+pub const KEYCHAIN_SERVICE: &str = "com.diregram.sync";
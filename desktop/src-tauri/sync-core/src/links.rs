@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::chunk::Fences;
+use crate::config::read_config;
+use crate::sync::FileMappingV1;
+
+/// How the web app links to a file.
+pub const SCHEME: &str = "nexus://file/";
+
+pub fn enabled(vault_path: &str) -> bool {
+  read_config(vault_path).map(|c| c.links.rewrite).unwrap_or(false)
+}
+
+/// Folder of a vault-relative path, as segments.
+pub fn dir_of(rel: &str) -> Vec<&str> {
+  let mut segs: Vec<&str> = rel.split('/').filter(|s| !s.is_empty()).collect();
+  segs.pop();
+  segs
+}
+
+/// `target` resolved against the folder `dir`; `None` if it climbs out of the vault.
+pub fn resolve(dir: &[&str], target: &str) -> Option<String> {
+  let mut out: Vec<&str> = dir.to_vec();
+  for seg in target.split('/') {
+    match seg {
+      "" | "." => {}
+      ".." => {
+        out.pop()?;
+      }
+      s => out.push(s),
+    }
+  }
+  Some(out.join("/"))
+}
+
+/// Path to `rel` from the folder `dir`.
+pub fn relative(dir: &[&str], rel: &str) -> String {
+  let to: Vec<&str> = rel.split('/').collect();
+  let common = dir.iter().zip(&to).take_while(|(a, b)| a == b).count();
+  let mut parts: Vec<&str> = vec![".."; dir.len() - common];
+  parts.extend(&to[common..]);
+  parts.join("/")
+}
+
+pub fn percent_decode(s: &str) -> String {
+  let b = s.as_bytes();
+  let mut out = Vec::with_capacity(b.len());
+  let mut i = 0;
+  while i < b.len() {
+    let hex = s.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok());
+    match hex {
+      Some(v) if b[i] == b'%' => {
+        out.push(v);
+        i += 3;
+      }
+      _ => {
+        out.push(b[i]);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&out).to_string()
+}
+
+/// Escapes what would end or confuse a link target.
+pub fn percent_encode(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      ' ' | '%' | '(' | ')' | '<' | '>' | '#' => out.push_str(&format!("%{:02X}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+/// Calls `f` with the target of every inline link `[text](target "title")` outside code blocks
+/// and code spans, and puts back what it returns (`None` keeps the target).
+pub fn rewrite_targets(markdown: &str, mut f: impl FnMut(&str) -> Option<String>) -> String {
+  let mut out = String::with_capacity(markdown.len());
+  let mut fences = Fences::default();
+  for line in markdown.split_inclusive('\n') {
+    if fences.toggles(line) || fences.inside() {
+      out.push_str(line);
+    } else {
+      rewrite_line(line, &mut out, &mut f);
+    }
+  }
+  out
+}
+
+fn rewrite_line(line: &str, out: &mut String, f: &mut impl FnMut(&str) -> Option<String>) {
+  let b = line.as_bytes();
+  let run_at = |i: usize| b[i..].iter().take_while(|&&c| c == b'`').count();
+  // Start of the text not yet copied to `out`.
+  let mut copied = 0;
+  let mut i = 0;
+  while i < b.len() {
+    if b[i] == b'`' {
+      // Skip the code span, if this run of backticks is closed by one of the same length.
+      let run = run_at(i);
+      let mut j = i + run;
+      i = j;
+      while let Some(k) = line[j..].find('`') {
+        let len = run_at(j + k);
+        if len == run {
+          i = j + k + len;
+          break;
+        }
+        j += k + len;
+      }
+      continue;
+    }
+    if !(b[i] == b']' && b.get(i + 1) == Some(&b'(')) {
+      i += 1;
+      continue;
+    }
+    let start = i + 2;
+    let (from, to, next) = if b.get(start) == Some(&b'<') {
+      match line[start..].find('>') {
+        Some(k) => (start + 1, start + k, start + k + 1),
+        None => {
+          i += 1;
+          continue;
+        }
+      }
+    } else {
+      let mut depth = 0;
+      let mut j = start;
+      while j < b.len() {
+        match b[j] {
+          b'(' => depth += 1,
+          b')' if depth == 0 => break,
+          b')' => depth -= 1,
+          c if c.is_ascii_whitespace() => break,
+          _ => {}
+        }
+        j += 1;
+      }
+      (start, j, j)
+    };
+    if let Some(new) = f(&line[from..to]) {
+      out.push_str(&line[copied..from]);
+      out.push_str(&new);
+      copied = to;
+    }
+    i = next.max(i + 1);
+  }
+  out.push_str(&line[copied..]);
+}
+
+/// Links from the note at `note_rel` to other synced notes, as `nexus://file/<id>` references
+/// the web app can follow. Links to notes not pushed yet stay relative until the note changes
+/// again.
+pub fn to_remote(markdown: &str, note_rel: &str, files: &HashMap<String, FileMappingV1>) -> String {
+  let dir = dir_of(note_rel);
+  rewrite_targets(markdown, |target| {
+    // URLs (`https:`, `mailto:`), in-page anchors and absolute paths aren't vault links.
+    if target.is_empty() || target.starts_with('#') || target.starts_with('/') {
+      return None;
+    }
+    if target.split('/').next().map(|s| s.contains(':')).unwrap_or(false) {
+      return None;
+    }
+    let (path, anchor) = match target.find('#') {
+      Some(i) => target.split_at(i),
+      None => (target, ""),
+    };
+    let rel = crate::normalize::nfc(&resolve(&dir, &percent_decode(path))?);
+    let file = files.get(&rel).or_else(|| files.get(&format!("{}.md", rel)))?;
+    Some(format!("{}{}{}", SCHEME, file.file_id, anchor))
+  })
+}
+
+/// `nexus://file/<id>` references in the note at `note_rel` as relative links, for files in
+/// `targets` (id -> vault-relative path).
+pub fn to_local(markdown: &str, note_rel: &str, targets: &HashMap<String, String>) -> String {
+  if !markdown.contains(SCHEME) {
+    return markdown.to_string();
+  }
+  let dir = dir_of(note_rel);
+  rewrite_targets(markdown, |target| {
+    let rest = target.strip_prefix(SCHEME)?;
+    let (id, anchor) = match rest.find('#') {
+      Some(i) => rest.split_at(i),
+      None => (rest, ""),
+    };
+    let rel = targets.get(id)?;
+    Some(format!("{}{}", percent_encode(&relative(&dir, rel)), anchor))
+  })
+}
+
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{append_event, diregram_dir, now_iso, sha256_hex, SyncEvent};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// A lock whose heartbeat is older than this is considered abandoned.
+const STALE_AFTER_SECS: i64 = 120;
+
+/// Distinguishes this process from an earlier run that reused the same PID.
+static INSTANCE_ID: Lazy<String> = Lazy::new(|| {
+  sha256_hex(format!("{}:{}", std::process::id(), now_iso()).as_bytes())[..16].to_string()
+});
+/// Vault path -> number of background tasks (watcher, poller) holding its lock.
+static HELD: Lazy<Mutex<HashMap<String, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static HEARTBEAT: Once = Once::new();
+/// Vault path -> lock held by whoever in this process is updating its `.diregram/sync.json`.
+static MAPPING: Lazy<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VaultLockV1 {
+  pub pid: u32,
+  pub hostname: String,
+  pub instance_id: String,
+  pub app_version: String,
+  pub acquired_at: String,
+  pub heartbeat_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VaultLockStatus {
+  pub lock: Option<VaultLockV1>,
+  pub held_by_us: bool,
+  pub stale: bool,
+}
+
+pub fn lock_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("lock")
+}
+
+/// This machine's name, for lock holders and the device list.
+pub fn hostname() -> String {
+  std::env::var("COMPUTERNAME")
+    .or_else(|_| std::env::var("HOSTNAME"))
+    .ok()
+    .or_else(|| fs::read_to_string("/etc/hostname").ok())
+    .or_else(|| {
+      std::process::Command::new("hostname")
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+    })
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .unwrap_or_else(|| "unknown".to_string())
+}
+
+pub fn read_lock(vault_path: &str) -> Option<VaultLockV1> {
+  let text = fs::read_to_string(lock_path(vault_path)).ok()?;
+  serde_json::from_str(&text).ok()
+}
+
+pub fn is_ours(lock: &VaultLockV1) -> bool {
+  lock.instance_id == *INSTANCE_ID
+}
+
+pub fn is_stale(lock: &VaultLockV1) -> bool {
+  let age = DateTime::parse_from_rfc3339(&lock.heartbeat_at)
+    .map(|t| (Utc::now() - t.with_timezone(&Utc)).num_seconds())
+    .unwrap_or(i64::MAX);
+  if age > STALE_AFTER_SECS {
+    return true;
+  }
+  // Same machine: a dead PID means the holder crashed, no need to wait for the heartbeat.
+  #[cfg(target_os = "linux")]
+  if lock.hostname == hostname() && !std::path::Path::new(&format!("/proc/{}", lock.pid)).exists() {
+    return true;
+  }
+  false
+}
+
+fn write_lock(vault_path: &str, acquired_at: &str, create_new: bool) -> Result<(), String> {
+  fs::create_dir_all(diregram_dir(vault_path)).map_err(|e| e.to_string())?;
+  let lock = VaultLockV1 {
+    pid: std::process::id(),
+    hostname: hostname(),
+    instance_id: INSTANCE_ID.clone(),
+    app_version: env!("CARGO_PKG_VERSION").to_string(),
+    acquired_at: acquired_at.to_string(),
+    heartbeat_at: now_iso(),
+  };
+  let text = serde_json::to_string_pretty(&lock).map_err(|e| e.to_string())?;
+  let mut opts = OpenOptions::new();
+  opts.write(true);
+  if create_new {
+    opts.create_new(true);
+  } else {
+    opts.create(true).truncate(true);
+  }
+  let mut f = opts.open(lock_path(vault_path)).map_err(|e| e.to_string())?;
+  f.write_all(text.as_bytes()).map_err(|e| e.to_string())
+}
+
+fn held_error(lock: &VaultLockV1) -> String {
+  format!(
+    "Vault is in use by another Diregram sync (pid {} on {}, last heartbeat {}). \
+     If that process is no longer running, use \"Break lock\" (vault_lock_break) to take over.",
+    lock.pid, lock.hostname, lock.heartbeat_at
+  )
+}
+
+fn start_heartbeat() {
+  HEARTBEAT.call_once(|| {
+    std::thread::spawn(heartbeat_loop);
+  });
+}
+
+fn heartbeat_loop() {
+  loop {
+    std::thread::sleep(HEARTBEAT_INTERVAL);
+    let vaults: Vec<String> = HELD.lock().map(|g| g.keys().cloned().collect()).unwrap_or_default();
+    for vp in vaults {
+      match read_lock(&vp) {
+        Some(lock) if is_ours(&lock) => {
+          if let Err(e) = write_lock(&vp, &lock.acquired_at, false) {
+            tracing::warn!(vault = %vp, error = %e, "could not refresh vault lock");
+          }
+        }
+        _ => {
+          // Someone broke our lock; stop claiming it rather than fighting over the file.
+          if let Ok(mut g) = HELD.lock() {
+            g.remove(&vp);
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Takes (or re-enters) the advisory lock for a vault. Each successful call must be paired
+/// with `release`.
+pub fn acquire(vault_path: &str) -> Result<(), String> {
+  let mut held = HELD.lock().map_err(|_| "vault lock state poisoned".to_string())?;
+  match read_lock(vault_path) {
+    Some(lock) if is_ours(&lock) => {}
+    Some(lock) if !is_stale(&lock) => return Err(held_error(&lock)),
+    Some(lock) => {
+      write_lock(vault_path, &now_iso(), false)?;
+      let _ = append_event(
+        vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "lock_takeover".to_string(),
+          path: ".diregram/lock".to_string(),
+          detail: format!("Took over stale lock from pid {} on {}", lock.pid, lock.hostname),
+        },
+      );
+    }
+    None => {
+      if let Err(e) = write_lock(vault_path, &now_iso(), true) {
+        // Lost a race with another process creating the lock at the same moment.
+        return Err(read_lock(vault_path).map(|l| held_error(&l)).unwrap_or(e));
+      }
+    }
+  }
+  *held.entry(vault_path.to_string()).or_insert(0) += 1;
+  drop(held);
+  start_heartbeat();
+  Ok(())
+}
+
+/// Waits until nothing else in this process is updating the vault's mapping, and holds that off
+/// until the guard drops. Pulls, pushes, trash restores and relinks each read the mapping, work,
+/// then write it back; two at once would lose one's changes. Not re-entrant: take it at the
+/// command or job that starts the work, never inside a pull or push.
+pub async fn mapping(vault_path: &str) -> tokio::sync::OwnedMutexGuard<()> {
+  let lock = {
+    let mut locks = MAPPING.lock().unwrap_or_else(|e| e.into_inner());
+    locks.entry(vault_path.to_string()).or_default().clone()
+  };
+  lock.lock_owned().await
+}
+
+pub fn release(vault_path: &str) {
+  let Ok(mut held) = HELD.lock() else { return };
+  let Some(n) = held.get_mut(vault_path) else { return };
+  *n = n.saturating_sub(1);
+  if *n > 0 {
+    return;
+  }
+  held.remove(vault_path);
+  if read_lock(vault_path).map(|l| is_ours(&l)).unwrap_or(false) {
+    if let Err(e) = fs::remove_file(lock_path(vault_path)) {
+      tracing::warn!(vault = vault_path, error = %e, "could not remove vault lock");
+    }
+  }
+}
+
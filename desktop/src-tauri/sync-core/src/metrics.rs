@@ -0,0 +1,229 @@
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::sync::{now_iso, SyncSummary};
+
+/// `host:port` to serve `/metrics` on, e.g. `127.0.0.1:9464`. Loopback addresses only.
+pub const ADDR_ENV: &str = "DIREGRAM_METRICS_ADDR";
+/// Upper bounds of the run duration histogram buckets, in seconds.
+const DURATION_BUCKETS: [f64; 9] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+static METRICS: Lazy<Mutex<SyncMetrics>> = Lazy::new(|| {
+  Mutex::new(SyncMetrics {
+    started_at: now_iso(),
+    ..Default::default()
+  })
+});
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DurationHistogram {
+  /// Cumulative count per bound of `bounds_secs`, as Prometheus buckets are.
+  pub buckets: Vec<u64>,
+  pub bounds_secs: Vec<f64>,
+  pub sum_secs: f64,
+  pub count: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RunMetrics {
+  pub runs: u64,
+  pub failures: u64,
+  pub files_created: u64,
+  pub files_updated: u64,
+  pub files_deleted: u64,
+  pub duration: DurationHistogram,
+}
+
+/// Process-wide sync counters since launch, over all vaults.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SyncMetrics {
+  pub started_at: String,
+  pub pushes: RunMetrics,
+  pub pulls: RunMetrics,
+  /// Request and response body bytes of sync and RAG traffic.
+  pub bytes_uploaded: u64,
+  pub bytes_downloaded: u64,
+  /// Bytes not transferred thanks to compression and delta pushes.
+  pub bytes_saved: u64,
+  /// Requests repeated after a token refresh or a checksum mismatch.
+  pub retries: u64,
+  pub conflicts: u64,
+  /// Watched vaults with local changes waiting for their debounced push.
+  pub queue_depth: u64,
+  /// Background pulls and pushes running right now.
+  pub in_flight: u64,
+}
+
+fn with(f: impl FnOnce(&mut SyncMetrics)) {
+  if let Ok(mut m) = METRICS.lock() {
+    f(&mut m);
+  }
+}
+
+/// Records a finished pull or push. `op` is `"pull"` or `"push"`, as for `status::finish`.
+pub fn record(op: &str, started: Instant, res: Result<&SyncSummary, &String>) {
+  let secs = started.elapsed().as_secs_f64();
+  with(|m| {
+    let run = if op == "pull" { &mut m.pulls } else { &mut m.pushes };
+    run.runs += 1;
+    let h = &mut run.duration;
+    if h.buckets.is_empty() {
+      h.bounds_secs = DURATION_BUCKETS.to_vec();
+      h.buckets = vec![0; DURATION_BUCKETS.len()];
+    }
+    for (i, bound) in DURATION_BUCKETS.iter().enumerate() {
+      if secs <= *bound {
+        h.buckets[i] += 1;
+      }
+    }
+    h.sum_secs += secs;
+    h.count += 1;
+    match res {
+      Ok(s) => {
+        run.files_created += s.files_created as u64;
+        run.files_updated += s.files_updated as u64;
+        run.files_deleted += s.files_deleted as u64;
+        m.bytes_saved += s.bytes_saved;
+      }
+      Err(_) => run.failures += 1,
+    }
+  });
+}
+
+pub fn add_transfer(uploaded: u64, downloaded: u64) {
+  with(|m| {
+    m.bytes_uploaded += uploaded;
+    m.bytes_downloaded += downloaded;
+  });
+}
+
+pub fn note_retry() {
+  with(|m| m.retries += 1);
+}
+
+pub fn note_conflict() {
+  with(|m| m.conflicts += 1);
+}
+
+pub fn set_queue(queue_depth: usize, in_flight: usize) {
+  with(|m| {
+    m.queue_depth = queue_depth as u64;
+    m.in_flight = in_flight as u64;
+  });
+}
+
+pub fn snapshot() -> SyncMetrics {
+  METRICS.lock().map(|m| m.clone()).unwrap_or_default()
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+  let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+}
+
+/// The metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+  let m = snapshot();
+  let runs = [("push", &m.pushes), ("pull", &m.pulls)];
+  let mut out = String::new();
+
+  metric(&mut out, "diregram_sync_runs_total", "counter", "Pull and push runs.");
+  for (op, r) in runs {
+    let _ = writeln!(out, "diregram_sync_runs_total{{op=\"{}\"}} {}", op, r.runs);
+  }
+  metric(&mut out, "diregram_sync_run_failures_total", "counter", "Pull and push runs that failed.");
+  for (op, r) in runs {
+    let _ = writeln!(out, "diregram_sync_run_failures_total{{op=\"{}\"}} {}", op, r.failures);
+  }
+  metric(&mut out, "diregram_sync_files_total", "counter", "Files changed by pulls and pushes.");
+  for (op, r) in runs {
+    for (change, n) in [("created", r.files_created), ("updated", r.files_updated), ("deleted", r.files_deleted)] {
+      let _ = writeln!(out, "diregram_sync_files_total{{op=\"{}\",change=\"{}\"}} {}", op, change, n);
+    }
+  }
+  metric(&mut out, "diregram_sync_run_duration_seconds", "histogram", "Duration of pull and push runs.");
+  for (op, r) in runs {
+    let h = &r.duration;
+    for (i, bound) in DURATION_BUCKETS.iter().enumerate() {
+      let n = h.buckets.get(i).copied().unwrap_or(0);
+      let _ = writeln!(out, "diregram_sync_run_duration_seconds_bucket{{op=\"{}\",le=\"{}\"}} {}", op, bound, n);
+    }
+    let _ = writeln!(out, "diregram_sync_run_duration_seconds_bucket{{op=\"{}\",le=\"+Inf\"}} {}", op, h.count);
+    let _ = writeln!(out, "diregram_sync_run_duration_seconds_sum{{op=\"{}\"}} {}", op, h.sum_secs);
+    let _ = writeln!(out, "diregram_sync_run_duration_seconds_count{{op=\"{}\"}} {}", op, h.count);
+  }
+  metric(&mut out, "diregram_sync_bytes_total", "counter", "Body bytes sent and received.");
+  let _ = writeln!(out, "diregram_sync_bytes_total{{direction=\"up\"}} {}", m.bytes_uploaded);
+  let _ = writeln!(out, "diregram_sync_bytes_total{{direction=\"down\"}} {}", m.bytes_downloaded);
+  let counters = [
+    ("diregram_sync_bytes_saved_total", "Bytes not transferred thanks to compression and deltas.", m.bytes_saved),
+    ("diregram_sync_retries_total", "Requests repeated after a token refresh or checksum mismatch.", m.retries),
+    ("diregram_sync_conflicts_total", "Conflicts written as conflict copies.", m.conflicts),
+  ];
+  for (name, help, value) in counters {
+    metric(&mut out, name, "counter", help);
+    let _ = writeln!(out, "{} {}", name, value);
+  }
+  let gauges = [
+    ("diregram_sync_queue_depth", "Watched vaults waiting for a debounced push.", m.queue_depth),
+    ("diregram_sync_in_flight", "Background pulls and pushes running.", m.in_flight),
+  ];
+  for (name, help, value) in gauges {
+    metric(&mut out, name, "gauge", help);
+    let _ = writeln!(out, "{} {}", name, value);
+  }
+  out
+}
+
+/// Serves `GET /metrics` on the address in `DIREGRAM_METRICS_ADDR`, if set, for scraping by
+/// Prometheus (e.g. when running headless with `--background`). Off by default.
+pub fn serve_from_env() {
+  let Ok(raw) = std::env::var(ADDR_ENV) else { return };
+  let addr: SocketAddr = match raw.parse() {
+    Ok(a) => a,
+    Err(e) => {
+      tracing::warn!(addr = %raw, error = %e, "ignoring invalid {}", ADDR_ENV);
+      return;
+    }
+  };
+  // Metrics name vault activity; keep them off the network.
+  if !addr.ip().is_loopback() {
+    tracing::warn!(%addr, "ignoring {}: only loopback addresses are allowed", ADDR_ENV);
+    return;
+  }
+  let listener = match TcpListener::bind(addr) {
+    Ok(l) => l,
+    Err(e) => {
+      tracing::warn!(%addr, error = %e, "could not serve metrics");
+      return;
+    }
+  };
+  tracing::info!(%addr, "serving metrics");
+  std::thread::spawn(move || {
+    for mut stream in listener.incoming().flatten() {
+      let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+      let mut buf = [0u8; 4096];
+      let n = stream.read(&mut buf).unwrap_or(0);
+      let head = String::from_utf8_lossy(&buf[..n]);
+      let target = head.lines().next().and_then(|l| l.split_whitespace().nth(1)).unwrap_or("");
+      let (status, body) = if target == "/metrics" {
+        ("200 OK", render())
+      } else {
+        ("404 Not Found", String::new())
+      };
+      let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+      );
+    }
+  });
+}
+
@@ -0,0 +1,367 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{read_config, MirrorTargetConfig};
+use crate::sync::{append_event, diregram_dir, now_iso, read_mapping, SyncEvent, SyncMappingV1};
+
+const MANIFEST_VERSION: u32 = 1;
+/// Secure storage key prefix of WebDAV passwords, followed by the target's URL.
+pub const PASSWORD_PREFIX: &str = "mirror-webdav:";
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Somewhere synced files are copied to after each sync, e.g. a NAS.
+pub trait MirrorTarget: Send + Sync {
+  /// Names the target in events and keys what the manifest remembers for it.
+  fn label(&self) -> String;
+  /// Copies `put` (vault-relative) from `root` to the target and removes `delete` there.
+  fn apply<'a>(&'a self, root: &'a Path, put: &'a [String], delete: &'a [String]) -> BoxFuture<'a, Result<(), String>>;
+}
+
+/// `.diregram/mirrors.json`: per target label, the hash of every file as it was last mirrored.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct MirrorManifestV1 {
+  version: u32,
+  targets: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MirrorOutcome {
+  pub target: String,
+  pub uploaded: u32,
+  pub deleted: u32,
+  pub error: Option<String>,
+}
+
+fn manifest_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("mirrors.json")
+}
+
+fn load_manifest(vault_path: &str) -> MirrorManifestV1 {
+  fs::read_to_string(manifest_path(vault_path))
+    .ok()
+    .and_then(|t| serde_json::from_str(&t).ok())
+    .unwrap_or_default()
+}
+
+fn save_manifest(vault_path: &str, manifest: &MirrorManifestV1) -> Result<(), String> {
+  let text = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+  fs::write(manifest_path(vault_path), text).map_err(|e| e.to_string())
+}
+
+/// Every file the last sync left in step with the project, with its hash: notes, resources and
+/// attachments.
+fn synced_files(mapping: &SyncMappingV1) -> HashMap<String, String> {
+  let notes = mapping.files.iter().map(|(rel, f)| (rel.clone(), f.local_hash.clone()));
+  let resources = mapping.resources.iter().map(|(rel, r)| (rel.clone(), r.local_hash.clone()));
+  let attachments = mapping.attachments.iter().map(|(rel, a)| (rel.clone(), a.sha256.clone()));
+  notes.chain(resources).chain(attachments).collect()
+}
+
+fn target(cfg: &MirrorTargetConfig) -> Box<dyn MirrorTarget> {
+  match cfg {
+    MirrorTargetConfig::Webdav { url, username } => {
+      let url = url.trim_end_matches('/').to_string();
+      // Without a stored password the server's 401 tells the user what is missing.
+      let profile = crate::auth::current_profile();
+      let password = crate::secrets::get(profile.as_deref(), &format!("{}{}", PASSWORD_PREFIX, url)).ok().flatten();
+      Box::new(WebDav {
+        client: reqwest::Client::new(),
+        url,
+        username: username.clone(),
+        password,
+      })
+    }
+    MirrorTargetConfig::Sftp {
+      host,
+      port,
+      user,
+      remote_dir,
+      identity_file,
+    } => Box::new(Sftp {
+      host: host.clone(),
+      port: port.unwrap_or(22),
+      user: user.clone(),
+      remote_dir: remote_dir.trim_end_matches('/').to_string(),
+      identity_file: identity_file.clone(),
+    }),
+  }
+}
+
+/// Brings every configured target up to date with what the sync last left in the vault. Targets
+/// fail independently; a failed one is retried in full by the next run.
+pub async fn run(vault_path: &str) -> Result<Vec<MirrorOutcome>, String> {
+  let cfg = read_config(vault_path)?.mirror;
+  if cfg.targets.is_empty() {
+    return Ok(Vec::new());
+  }
+  let Some(mapping) = read_mapping(vault_path)? else {
+    return Ok(Vec::new());
+  };
+  let root = Path::new(vault_path);
+  let current = synced_files(&mapping);
+  let mut manifest = load_manifest(vault_path);
+  manifest.version = MANIFEST_VERSION;
+  let mut outcomes = Vec::new();
+  for target_cfg in &cfg.targets {
+    let target = target(target_cfg);
+    let label = target.label();
+    let mirrored = manifest.targets.get(&label).cloned().unwrap_or_default();
+    let mut put: Vec<String> = current
+      .iter()
+      .filter(|(rel, hash)| mirrored.get(*rel) != Some(*hash) && root.join(rel).is_file())
+      .map(|(rel, _)| rel.clone())
+      .collect();
+    let mut delete: Vec<String> = mirrored.keys().filter(|rel| !current.contains_key(*rel)).cloned().collect();
+    if put.is_empty() && delete.is_empty() {
+      continue;
+    }
+    put.sort();
+    delete.sort();
+    let error = target.apply(root, &put, &delete).await.err();
+    if error.is_none() {
+      let mut now = mirrored;
+      now.retain(|rel, _| current.contains_key(rel));
+      for rel in &put {
+        now.insert(rel.clone(), current[rel].clone());
+      }
+      manifest.targets.insert(label.clone(), now);
+    }
+    outcomes.push(MirrorOutcome {
+      target: label,
+      uploaded: put.len() as u32,
+      deleted: delete.len() as u32,
+      error,
+    });
+  }
+  if !outcomes.is_empty() {
+    save_manifest(vault_path, &manifest)?;
+  }
+  Ok(outcomes)
+}
+
+/// Mirrors after a successful sync, logging each target's outcome. Never fails the sync.
+pub async fn after_sync(vault_path: &str) {
+  let outcomes = match run(vault_path).await {
+    Ok(o) => o,
+    Err(e) => vec![MirrorOutcome {
+      target: String::new(),
+      uploaded: 0,
+      deleted: 0,
+      error: Some(e),
+    }],
+  };
+  for o in outcomes {
+    let (kind, detail) = match &o.error {
+      None => ("mirror", format!("Mirrored {} files to {} and removed {}.", o.uploaded, o.target, o.deleted)),
+      Some(e) => ("mirror_error", format!("Mirroring to {} failed: {}", o.target, e)),
+    };
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: kind.to_string(),
+        path: String::new(),
+        detail,
+      },
+    );
+  }
+}
+
+/// Percent-encodes everything but unreserved characters, for one segment of a URL path.
+pub fn encode_segment(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  for b in s.bytes() {
+    match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(b as char),
+      b => out.push_str(&format!("%{:02X}", b)),
+    }
+  }
+  out
+}
+
+struct WebDav {
+  client: reqwest::Client,
+  url: String,
+  username: Option<String>,
+  password: Option<String>,
+}
+
+impl WebDav {
+  fn url_of(&self, rel: &str) -> String {
+    let segs: Vec<String> = rel.split('/').map(encode_segment).collect();
+    format!("{}/{}", self.url, segs.join("/"))
+  }
+
+  async fn send(&self, method: &str, rel: &str, body: Option<Vec<u8>>) -> Result<reqwest::StatusCode, String> {
+    let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+    let mut req = self.client.request(method, self.url_of(rel));
+    if let Some(user) = &self.username {
+      req = req.basic_auth(user, self.password.as_deref());
+    }
+    if let Some(body) = body {
+      req = req.body(body);
+    }
+    let res = crate::throttle::send(req).await.map_err(|e| e.to_string())?;
+    Ok(res.status())
+  }
+}
+
+impl MirrorTarget for WebDav {
+  fn label(&self) -> String {
+    format!("webdav:{}", self.url)
+  }
+
+  fn apply<'a>(&'a self, root: &'a Path, put: &'a [String], delete: &'a [String]) -> BoxFuture<'a, Result<(), String>> {
+    Box::pin(async move {
+      let mut collections: HashSet<String> = HashSet::new();
+      for rel in put {
+        let segs: Vec<&str> = rel.split('/').collect();
+        for depth in 1..segs.len() {
+          let dir = segs[..depth].join("/");
+          if !collections.insert(dir.clone()) {
+            continue;
+          }
+          // 405 is how WebDAV reports a collection that already exists.
+          let status = self.send("MKCOL", &dir, None).await?;
+          if !status.is_success() && status != reqwest::StatusCode::METHOD_NOT_ALLOWED {
+            return Err(format!("MKCOL {}: HTTP {}", dir, status));
+          }
+        }
+        let bytes = fs::read(root.join(rel)).map_err(|e| format!("{}: {}", rel, e))?;
+        let status = self.send("PUT", rel, Some(bytes)).await?;
+        if !status.is_success() {
+          return Err(format!("PUT {}: HTTP {}", rel, status));
+        }
+      }
+      for rel in delete {
+        let status = self.send("DELETE", rel, None).await?;
+        if !status.is_success() && status != reqwest::StatusCode::NOT_FOUND {
+          return Err(format!("DELETE {}: HTTP {}", rel, status));
+        }
+      }
+      Ok(())
+    })
+  }
+}
+
+/// Runs the system `sftp` client in batch mode, so keys, agents and `~/.ssh/config` work as they
+/// do in a terminal. Password logins are not supported.
+#[derive(Clone)]
+struct Sftp {
+  host: String,
+  port: u16,
+  user: String,
+  remote_dir: String,
+  identity_file: Option<String>,
+}
+
+/// Quotes an argument of an sftp batch command.
+fn sftp_quote(s: &str) -> String {
+  format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Why `value` can't go on the `sftp` command line, if it can't: a leading `-` would be read as an
+/// option (e.g. `-oProxyCommand=...` runs a command), and whitespace or control characters have
+/// no place in a host, user or key path.
+pub fn sftp_arg_issue(value: &str) -> Option<&'static str> {
+  if value.starts_with('-') {
+    Some("must not start with '-'")
+  } else if value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+    Some("must not contain spaces or control characters")
+  } else {
+    None
+  }
+}
+
+impl Sftp {
+  fn script(&self, root: &Path, put: &[String], delete: &[String]) -> String {
+    let remote = |rel: &str| sftp_quote(&format!("{}/{}", self.remote_dir, rel));
+    let mut dirs: Vec<String> = Vec::new();
+    for rel in put {
+      let segs: Vec<&str> = rel.split('/').collect();
+      for depth in 1..segs.len() {
+        let dir = segs[..depth].join("/");
+        if !dirs.contains(&dir) {
+          dirs.push(dir);
+        }
+      }
+    }
+    // A leading `-` lets the batch go on when the folder exists already.
+    let mut script = String::new();
+    for dir in dirs {
+      script.push_str(&format!("-mkdir {}\n", remote(&dir)));
+    }
+    for rel in put {
+      let local = root.join(rel);
+      script.push_str(&format!("put {} {}\n", sftp_quote(&local.to_string_lossy()), remote(rel)));
+    }
+    for rel in delete {
+      script.push_str(&format!("-rm {}\n", remote(rel)));
+    }
+    script
+  }
+
+  fn run_batch(&self, script: &str) -> Result<(), String> {
+    let values = [
+      ("host", Some(&self.host)),
+      ("user", Some(&self.user)),
+      ("identity_file", self.identity_file.as_ref()),
+    ];
+    for (name, value) in values {
+      if let Some(issue) = value.and_then(|v| sftp_arg_issue(v)) {
+        return Err(format!("sftp: {} {}", name, issue));
+      }
+    }
+    let mut command = Command::new("sftp");
+    command
+      .args(["-b", "-", "-o", "BatchMode=yes", "-P", &self.port.to_string()])
+      .stdin(Stdio::piped())
+      .stdout(Stdio::null())
+      .stderr(Stdio::piped());
+    if let Some(identity) = &self.identity_file {
+      command.args(["-i", identity]);
+    }
+    command.arg("--").arg(format!("{}@{}", self.user, self.host));
+    #[cfg(target_os = "windows")]
+    {
+      // CREATE_NO_WINDOW: mirroring runs after background syncs.
+      use std::os::windows::process::CommandExt;
+      command.creation_flags(0x0800_0000);
+    }
+    let mut child = command.spawn().map_err(|e| format!("sftp: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+      stdin.write_all(script.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    let out = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !out.status.success() {
+      return Err(format!("sftp: {}", String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    Ok(())
+  }
+}
+
+impl MirrorTarget for Sftp {
+  fn label(&self) -> String {
+    format!("sftp:{}@{}:{}{}", self.user, self.host, self.port, self.remote_dir)
+  }
+
+  fn apply<'a>(&'a self, root: &'a Path, put: &'a [String], delete: &'a [String]) -> BoxFuture<'a, Result<(), String>> {
+    let script = self.script(root, put, delete);
+    Box::pin(async move {
+      let this = self.clone();
+      tokio::task::spawn_blocking(move || this.run_batch(&script))
+        .await
+        .map_err(|e| e.to_string())?
+    })
+  }
+}
+
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use crate::normalize::nfc;
+use crate::sync::{sha256_hex, FolderNode, SyncMappingV1};
+
+/// Characters Windows, macOS or Linux refuse in a name. They are replaced on every OS, so a vault
+/// copied to another machine finds the same names.
+const UNSAFE_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+/// Device names Windows won't create as files, with or without an extension.
+const WINDOWS_RESERVED: &[&str] = &[
+  "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+  "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+/// Vault-root names the sync keeps for itself.
+const RESERVED_AT_ROOT: &[&str] = &[".diregram", "resources", "rag"];
+/// Filesystems allow 255 bytes per name; the rest is room for conflict-copy and collision suffixes.
+const MAX_NAME_BYTES: usize = 200;
+
+/// `name` split before its extension, if it has one that isn't the whole name.
+fn split_ext(name: &str) -> (&str, &str) {
+  match name.rfind('.') {
+    Some(i) if i > 0 && name.len() - i <= 16 => name.split_at(i),
+    _ => (name, ""),
+  }
+}
+
+fn truncate(name: String) -> String {
+  if name.len() <= MAX_NAME_BYTES {
+    return name;
+  }
+  let (stem, ext) = split_ext(&name);
+  let mut cut = MAX_NAME_BYTES - ext.len();
+  while !stem.is_char_boundary(cut) {
+    cut -= 1;
+  }
+  format!("{}{}", &stem[..cut], ext)
+}
+
+/// A remote file or folder name as a name every OS accepts. Unsafe characters become `_`,
+/// trailing dots and spaces are dropped, reserved names get a `_`, and overlong names are cut
+/// (keeping the extension). Anything else, emoji included, is kept as is.
+pub fn local_name(remote: &str, at_root: bool) -> String {
+  let replaced: String = nfc(remote)
+    .chars()
+    .map(|c| if c.is_control() || UNSAFE_CHARS.contains(&c) { '_' } else { c })
+    .collect();
+  let mut out = replaced.trim_end_matches(['.', ' ']).to_string();
+  if out.is_empty() {
+    out.push('_');
+  }
+  let stem_len = out.split('.').next().map(str::len).unwrap_or(0);
+  let reserved = WINDOWS_RESERVED.iter().any(|r| r.eq_ignore_ascii_case(&out[..stem_len]))
+    || (at_root && RESERVED_AT_ROOT.iter().any(|r| r.eq_ignore_ascii_case(&out)));
+  if reserved {
+    out.insert(stem_len, '_');
+  }
+  truncate(out)
+}
+
+/// `name` with a tag derived from `id` before its extension, e.g. `a_b ~3f2a9c.md`.
+fn disambiguate(name: &str, id: &str) -> String {
+  let (stem, ext) = split_ext(name);
+  let tag = &sha256_hex(id.as_bytes())[..6];
+  let stem = truncate(stem.to_string());
+  let mut cut = stem.len().min(MAX_NAME_BYTES - ext.len() - tag.len() - 2);
+  while !stem.is_char_boundary(cut) {
+    cut -= 1;
+  }
+  format!("{} ~{}{}", &stem[..cut], tag, ext)
+}
+
+/// Local name of remote item `id` called `name`, among `siblings` (id, remote name) in the same
+/// folder. A name that had to change and then matches a sibling's name gets a tag from its id, so
+/// every device picks the same names whatever order it sees the items in.
+pub fn resolve<'a>(
+  id: &str,
+  name: &str,
+  at_root: bool,
+  siblings: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> String {
+  let local = local_name(name, at_root);
+  if local == nfc(name) {
+    return local;
+  }
+  let taken = siblings
+    .into_iter()
+    .any(|(sid, sname)| sid != id && (nfc(sname) == local || local_name(sname, at_root) == local));
+  if taken {
+    disambiguate(&local, id)
+  } else {
+    local
+  }
+}
+
+/// Local path of a folder and each of its ancestors below the project, as (rel, id, remote name),
+/// outermost first. `None` when the folder isn't in the project's tree.
+pub fn folder_chain(
+  project_folder_id: &str,
+  folder_id: &str,
+  folders_by_id: &HashMap<String, FolderNode>,
+) -> Option<Vec<(String, String, String)>> {
+  let mut nodes: Vec<&FolderNode> = Vec::new();
+  let mut cur = folder_id;
+  while cur != project_folder_id {
+    // Guards against cycles in a broken tree.
+    if nodes.len() >= 64 {
+      return None;
+    }
+    let node = folders_by_id.get(cur)?;
+    nodes.push(node);
+    cur = node.parent_id.as_deref()?;
+  }
+  let mut chain = Vec::with_capacity(nodes.len());
+  let mut rel = String::new();
+  for node in nodes.into_iter().rev() {
+    let siblings = folders_by_id
+      .values()
+      .filter(|f| f.parent_id == node.parent_id)
+      .map(|f| (f.id.as_str(), f.name.as_str()));
+    let name = resolve(&node.id, &node.name, rel.is_empty(), siblings);
+    rel = if rel.is_empty() { name } else { format!("{}/{}", rel, name) };
+    chain.push((rel.clone(), node.id.clone(), node.name.clone()));
+  }
+  Some(chain)
+}
+
+/// Records the remote name of the item at `rel` when it differs from the local one.
+pub fn remember(mapping: &mut SyncMappingV1, rel: &str, remote_name: &str) {
+  let local = rel.rsplit('/').next().unwrap_or(rel);
+  if local == nfc(remote_name) {
+    mapping.remote_names.remove(rel);
+  } else {
+    mapping.remote_names.insert(rel.to_string(), remote_name.to_string());
+  }
+}
+
+/// Local path of a remote folder, remembering the remote names of it and its ancestors.
+pub fn local_folder(
+  mapping: &mut SyncMappingV1,
+  project_folder_id: &str,
+  folder_id: &str,
+  folders_by_id: &HashMap<String, FolderNode>,
+) -> Option<String> {
+  let chain = folder_chain(project_folder_id, folder_id, folders_by_id)?;
+  for (rel, _, name) in &chain {
+    remember(mapping, rel, name);
+  }
+  Some(chain.last().map(|(rel, _, _)| rel.clone()).unwrap_or_default())
+}
+
+/// Name to give the item at `rel` remotely: the one it was pulled with, else its local name.
+pub fn remote_name(mapping: &SyncMappingV1, rel: &str) -> String {
+  match mapping.remote_names.get(rel) {
+    Some(name) => name.clone(),
+    None => rel.rsplit('/').next().unwrap_or(rel).to_string(),
+  }
+}
+
+/// Drops remote names of paths the mapping no longer has (folders count while they hold a file).
+pub fn prune(mapping: &mut SyncMappingV1) {
+  let SyncMappingV1 {
+    remote_names,
+    files,
+    resources,
+    folders,
+    ..
+  } = mapping;
+  remote_names.retain(|rel, _| {
+    let dir = format!("{}/", rel);
+    files.contains_key(rel)
+      || resources.contains_key(rel)
+      || folders.contains_key(rel)
+      || files.keys().any(|f| f.starts_with(&dir))
+  });
+}
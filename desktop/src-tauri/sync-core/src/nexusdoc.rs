@@ -0,0 +1,80 @@
+use serde_json::Value;
+
+const FENCE: &str = "```nexus-doc";
+/// Kinds whose whole document is structured data described by the header; a broken header turns
+/// them into unreadable notes in the web app.
+const STRUCTURED_KINDS: [&str; 2] = ["diagram", "board"];
+
+/// The first nexus-doc block: byte range of the whole fence and its JSON text.
+struct Block<'a> {
+  start: usize,
+  end: usize,
+  json: &'a str,
+}
+
+fn block(markdown: &str) -> Option<Block<'_>> {
+  let start = markdown.find(FENCE)?;
+  let body_start = start + FENCE.len();
+  let close = markdown[body_start..].find("\n```")?;
+  Some(Block {
+    start,
+    end: body_start + close + "\n```".len(),
+    json: &markdown[body_start..body_start + close],
+  })
+}
+
+/// The document around its first nexus-doc block: text before it, its JSON and text after it.
+pub fn split(markdown: &str) -> Option<(&str, &str, &str)> {
+  let b = block(markdown)?;
+  Some((&markdown[..b.start], b.json.trim(), &markdown[b.end..]))
+}
+
+/// `kind` from the document's nexus-doc header, when it has a valid one.
+pub fn header_kind(markdown: &str) -> Option<String> {
+  let v: Value = serde_json::from_str(block(markdown)?.json.trim()).ok()?;
+  v.get("kind").and_then(|k| k.as_str()).map(str::to_string)
+}
+
+fn is_structured(kind: &str) -> bool {
+  STRUCTURED_KINDS.contains(&kind)
+}
+
+/// Checks the nexus-doc header of diagram and board documents before they are pushed. Headers of
+/// other documents are left to the editor, which treats a broken one as plain text.
+pub fn validate(markdown: &str) -> Result<(), String> {
+  let Some(b) = block(markdown) else { return Ok(()) };
+  let v: Value = match serde_json::from_str(b.json.trim()) {
+    Ok(v) => v,
+    Err(e) => {
+      // Unparseable, so go by what the header seems to say.
+      if !STRUCTURED_KINDS.iter().any(|k| b.json.contains(&format!("\"{}\"", k))) {
+        return Ok(());
+      }
+      let json_start = b.start + FENCE.len() + (b.json.len() - b.json.trim_start().len());
+      let line = markdown[..json_start].matches('\n').count() + e.line();
+      let msg = e.to_string();
+      let msg = msg.split(" at line ").next().unwrap_or(&msg);
+      return Err(format!("nexus-doc block is not valid JSON at line {}, column {}: {}", line, e.column(), msg));
+    }
+  };
+  let Some(kind) = v.get("kind").and_then(|k| k.as_str()) else { return Ok(()) };
+  if !is_structured(kind) {
+    return Ok(());
+  }
+  if !v.get("version").map(Value::is_u64).unwrap_or(false) {
+    return Err(format!("nexus-doc block of this {} has no numeric \"version\"", kind));
+  }
+  Ok(())
+}
+
+/// Diagram and board documents with their nexus-doc header pretty-printed the same way every
+/// time, so pulls don't turn formatting differences into diffs. Other documents come back as is.
+pub fn normalize(markdown: &str) -> String {
+  let Some(b) = block(markdown) else { return markdown.to_string() };
+  let Ok(v) = serde_json::from_str::<Value>(b.json.trim()) else { return markdown.to_string() };
+  if !v.get("kind").and_then(|k| k.as_str()).map(is_structured).unwrap_or(false) {
+    return markdown.to_string();
+  }
+  let Ok(pretty) = serde_json::to_string_pretty(&v) else { return markdown.to_string() };
+  format!("{}{}\n{}\n```{}", &markdown[..b.start], FENCE, pretty, &markdown[b.end..])
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+use crate::sync::{append_event, now_iso, FileMappingV1, SyncEvent, SyncMappingV1};
+
+/// Mapping keys and remote names use NFC, as the server and Linux do. macOS hands out NFD names,
+/// so without this the same note shows up under two keys (and as two remote files).
+pub fn nfc(s: &str) -> String {
+  if is_nfc(s) {
+    s.to_string()
+  } else {
+    s.nfc().collect()
+  }
+}
+
+/// On-disk path of a (NFC) vault-relative path. Existing files and folders keep whatever
+/// normalization they have on disk; only components that don't exist yet are created as NFC.
+pub fn local_path(root: &Path, rel: &str) -> PathBuf {
+  let direct = root.join(rel);
+  if direct.exists() {
+    return direct;
+  }
+  let mut out = root.to_path_buf();
+  let mut segs = rel.split('/').filter(|s| !s.is_empty());
+  for seg in segs.by_ref() {
+    let exact = out.join(seg);
+    if exact.exists() {
+      out = exact;
+      continue;
+    }
+    let wanted = nfc(seg);
+    let found = fs::read_dir(&out).ok().and_then(|rd| {
+      rd.filter_map(Result::ok)
+        .find(|e| e.file_name().to_str().map(|n| nfc(n) == wanted).unwrap_or(false))
+        .map(|e| e.path())
+    });
+    match found {
+      Some(p) => out = p,
+      None => {
+        out = exact;
+        break;
+      }
+    }
+  }
+  for seg in segs {
+    out = out.join(seg);
+  }
+  out
+}
+
+/// Paths can come from links on any web page or from config files, so only plain relative paths inside the vault are accepted.
+pub fn safe_rel_path(rel: &str) -> Option<String> {
+  let rel = rel.trim().trim_start_matches('/').replace('\\', "/");
+  let ok = !rel.is_empty()
+    && Path::new(&rel).components().all(|c| matches!(c, Component::Normal(_)));
+  ok.then_some(rel)
+}
+
+/// Re-keys a mapping to NFC. Mappings written before normalization may hold the same note under
+/// both forms; the entry already in NFC (else the most recently synced one) is kept. Returns
+/// whether anything changed.
+pub fn normalize_mapping(vault_path: &str, mapping: &mut SyncMappingV1) -> bool {
+  let needs = mapping.files.keys().any(|k| !is_nfc(k))
+    || mapping.folders.keys().any(|k| !is_nfc(k))
+    || mapping.resources.keys().any(|k| !is_nfc(k));
+  if !needs {
+    return false;
+  }
+
+  let mut files: HashMap<String, FileMappingV1> = HashMap::with_capacity(mapping.files.len());
+  let mut entries: Vec<_> = std::mem::take(&mut mapping.files).into_iter().collect();
+  // Preferred entries first: already NFC, then newest remote version.
+  entries.sort_by(|(ka, a), (kb, b)| {
+    is_nfc(kb)
+      .cmp(&is_nfc(ka))
+      .then(b.remote_rev.cmp(&a.remote_rev))
+      .then(b.remote_updated_at.cmp(&a.remote_updated_at))
+  });
+  for (key, m) in entries {
+    let norm = nfc(&key);
+    if let Some(kept) = files.get(&norm) {
+      if kept.file_id != m.file_id {
+        let _ = append_event(
+          vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "path_normalized".to_string(),
+            path: norm.clone(),
+            detail: format!(
+              "Dropped duplicate mapping {:?} (remote file {}) that differed only by Unicode normalization; \
+               the duplicate remote file can be deleted in Diregram.",
+              key, m.file_id
+            ),
+          },
+        );
+      }
+      continue;
+    }
+    files.insert(norm, m);
+  }
+  mapping.files = files;
+
+  let mut folders: Vec<_> = std::mem::take(&mut mapping.folders).into_iter().collect();
+  folders.sort_by_key(|(k, _)| std::cmp::Reverse(is_nfc(k)));
+  for (key, id) in folders {
+    mapping.folders.entry(nfc(&key)).or_insert(id);
+  }
+
+  let mut resources: Vec<_> = std::mem::take(&mut mapping.resources).into_iter().collect();
+  resources.sort_by(|(ka, a), (kb, b)| is_nfc(kb).cmp(&is_nfc(ka)).then(b.remote_updated_at.cmp(&a.remote_updated_at)));
+  for (key, r) in resources {
+    mapping.resources.entry(nfc(&key)).or_insert(r);
+  }
+  true
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::chunk::Fences;
+use crate::normalize::safe_rel_path;
+use crate::links::{dir_of, percent_decode, resolve, rewrite_targets, SCHEME};
+use crate::sync::{is_ignored_rel, is_markdown_path, read_mapping};
+
+/// A line of a note that matched a search.
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchHit {
+  pub path: String,
+  /// 1-based.
+  pub line: usize,
+  pub text: String,
+}
+
+/// A link from one note, as written and, if it points at a note of the vault, where to.
+#[derive(Debug, Serialize, Clone)]
+pub struct NoteLink {
+  pub raw: String,
+  pub target: Option<String>,
+}
+
+/// The vault's notes (markdown outside hidden folders, `resources/` and `rag/`), sorted.
+pub fn list(vault_path: &str) -> Vec<(PathBuf, String)> {
+  crate::site::collect(Path::new(vault_path), vault_path)
+    .into_iter()
+    .filter(|(abs, rel)| is_markdown_path(abs) && !rel.starts_with("resources/"))
+    .collect()
+}
+
+/// `rel` if it names a note that may be read or written from outside the app: markdown, inside
+/// the vault, and not in a hidden folder, `resources/` or `rag/`.
+pub fn note_rel(rel: &str) -> Option<String> {
+  let rel = safe_rel_path(rel)?;
+  let ok = is_markdown_path(Path::new(&rel)) && !rel.split('/').any(|s| s.starts_with('.')) && !is_ignored_rel(&rel);
+  ok.then_some(rel)
+}
+
+fn read(abs: &Path) -> Option<String> {
+  fs::read(abs).ok().map(|b| crate::encoding::decode(&b).0)
+}
+
+/// Lines containing `query` (case-insensitive), in path order, at most `limit`.
+pub fn search(vault_path: &str, query: &str, limit: usize) -> Vec<SearchHit> {
+  let needle = query.trim().to_lowercase();
+  let mut hits = Vec::new();
+  if needle.is_empty() {
+    return hits;
+  }
+  for (abs, rel) in list(vault_path) {
+    let Some(text) = read(&abs) else { continue };
+    for (i, line) in text.lines().enumerate() {
+      if line.to_lowercase().contains(&needle) {
+        hits.push(SearchHit {
+          path: rel.clone(),
+          line: i + 1,
+          text: line.trim().to_string(),
+        });
+        if hits.len() >= limit {
+          return hits;
+        }
+      }
+    }
+  }
+  hits
+}
+
+/// Inner text of every `[[...]]` (and `![[...]]`) outside code blocks and code spans.
+pub fn wiki_links(markdown: &str) -> Vec<String> {
+  let mut out = Vec::new();
+  let mut fences = Fences::default();
+  for line in markdown.lines() {
+    if fences.toggles(line) || fences.inside() {
+      continue;
+    }
+    // Code spans are dropped by splitting on backticks and keeping the even parts.
+    for (i, part) in line.split('`').enumerate() {
+      if i % 2 == 1 {
+        continue;
+      }
+      let mut rest = part;
+      while let Some(start) = rest.find("[[") {
+        let Some(end) = rest[start + 2..].find("]]") else { break };
+        out.push(rest[start + 2..start + 2 + end].to_string());
+        rest = &rest[start + 2 + end + 2..];
+      }
+    }
+  }
+  out
+}
+
+/// Links between the vault's notes: relative markdown links, `nexus://file/<id>` links and
+/// wiki-links, resolved the way the HTML export resolves them.
+pub struct LinkIndex {
+  /// Note path -> its links, in order of appearance.
+  pub outgoing: HashMap<String, Vec<NoteLink>>,
+}
+
+impl LinkIndex {
+  pub fn build(vault_path: &str) -> Self {
+    let notes = list(vault_path);
+    let paths: Vec<&String> = notes.iter().map(|(_, rel)| rel).collect();
+    // Lowercased path and name, both without `.md`; the first (sorted) note wins a shared name.
+    let mut wiki: HashMap<String, String> = HashMap::new();
+    for rel in &paths {
+      let stem = rel.strip_suffix(".md").or_else(|| rel.strip_suffix(".markdown")).unwrap_or(rel);
+      wiki.insert(stem.to_lowercase(), rel.to_string());
+      let name = stem.rsplit('/').next().unwrap_or(stem).to_lowercase();
+      wiki.entry(name).or_insert_with(|| rel.to_string());
+    }
+    let by_id: HashMap<String, String> = read_mapping(vault_path)
+      .ok()
+      .flatten()
+      .map(|m| m.files.into_iter().map(|(rel, f)| (f.file_id, rel)).collect())
+      .unwrap_or_default();
+    let is_note = |rel: &str| paths.iter().any(|p| p.as_str() == rel);
+
+    let mut outgoing = HashMap::new();
+    for (abs, rel) in &notes {
+      let Some(text) = read(abs) else { continue };
+      let dir = dir_of(rel);
+      let mut links = Vec::new();
+      rewrite_targets(&text, |raw| {
+        let path = raw.split('#').next().unwrap_or("");
+        let first = raw.split('/').next().unwrap_or("");
+        let target = if let Some(id) = raw.strip_prefix(SCHEME) {
+          by_id.get(id.split('#').next().unwrap_or(id)).cloned()
+        } else if path.is_empty() || raw.starts_with('/') || first.contains(':') {
+          // In-page anchors, absolute paths and URLs aren't links between notes.
+          return None;
+        } else {
+          resolve(&dir, &percent_decode(path))
+            .map(|r| crate::normalize::nfc(&r))
+            .and_then(|r| if is_note(&r) { Some(r) } else { Some(format!("{}.md", r)).filter(|r| is_note(r)) })
+        };
+        links.push(NoteLink {
+          raw: raw.to_string(),
+          target,
+        });
+        None
+      });
+      for inner in wiki_links(&text) {
+        let name = inner.split('|').next().unwrap_or("").split('#').next().unwrap_or("");
+        if name.trim().is_empty() {
+          continue;
+        }
+        links.push(NoteLink {
+          raw: format!("[[{}]]", inner),
+          target: wiki.get(&name.trim().trim_end_matches(".md").to_lowercase()).cloned(),
+        });
+      }
+      outgoing.insert(rel.clone(), links);
+    }
+    Self { outgoing }
+  }
+
+  /// Notes linking to `rel`, sorted.
+  pub fn backlinks(&self, rel: &str) -> Vec<String> {
+    let mut out: Vec<String> = self
+      .outgoing
+      .iter()
+      .filter(|(from, links)| from.as_str() != rel && links.iter().any(|l| l.target.as_deref() == Some(rel)))
+      .map(|(from, _)| from.clone())
+      .collect();
+    out.sort();
+    out
+  }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{read_config, NotificationConfig};
+
+static LAST_SENT: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static PUSH_FAILURES: Lazy<Mutex<HashMap<String, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static PENDING_FOCUS: Lazy<Mutex<Option<(FocusTarget, Instant)>>> = Lazy::new(|| Mutex::new(None));
+
+/// Same vault + kind + path is not re-notified within this window (the poller retries every few seconds).
+const RENOTIFY_AFTER: Duration = Duration::from_secs(10 * 60);
+/// A click on a notification only counts if the window is focused shortly after it was shown.
+const FOCUS_TARGET_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyKind {
+  Conflict,
+  PushFailure,
+  AuthExpired,
+  DailyNote,
+  MassDelete,
+  ClockSkew,
+  Crash,
+  VaultUnavailable,
+}
+
+impl NotifyKind {
+  fn as_str(self) -> &'static str {
+    match self {
+      NotifyKind::Conflict => "conflict",
+      NotifyKind::PushFailure => "push_failure",
+      NotifyKind::AuthExpired => "auth_expired",
+      NotifyKind::DailyNote => "daily_note",
+      NotifyKind::MassDelete => "mass_delete",
+      NotifyKind::ClockSkew => "clock_skew",
+      NotifyKind::Crash => "crash",
+      NotifyKind::VaultUnavailable => "vault_unavailable",
+    }
+  }
+
+  fn allowed(self, cfg: &NotificationConfig) -> bool {
+    cfg.enabled
+      && match self {
+        NotifyKind::Conflict => cfg.conflicts,
+        NotifyKind::PushFailure => cfg.push_failures,
+        NotifyKind::AuthExpired => cfg.auth_expiry,
+        // Opted into separately, through `daily_note.notify`.
+        NotifyKind::DailyNote => true,
+        // A held pull needs the user's decision; never muted separately.
+        NotifyKind::MassDelete => true,
+        NotifyKind::ClockSkew => true,
+        // Sync stopped working until the restart; always worth knowing.
+        NotifyKind::Crash => true,
+        // The vault's own notification settings can't be read while it is gone.
+        NotifyKind::VaultUnavailable => true,
+      }
+  }
+}
+
+/// Emitted as `notify://focus_file` when the window is focused after a notification, so the UI
+/// can open the file the notification was about.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FocusTarget {
+  pub vault_path: String,
+  pub path: String,
+  pub kind: String,
+}
+
+pub fn notify(vault_path: &str, kind: NotifyKind, title: &str, body: &str, rel_path: Option<&str>) {
+  let Some(host) = crate::host::get() else { return };
+  let cfg = read_config(vault_path).map(|c| c.notifications).unwrap_or_default();
+  if !kind.allowed(&cfg) {
+    return;
+  }
+  let key = format!("{}|{}|{}", vault_path, kind.as_str(), rel_path.unwrap_or(""));
+  {
+    let Ok(mut guard) = LAST_SENT.lock() else { return };
+    if guard.get(&key).map(|t| t.elapsed() < RENOTIFY_AFTER).unwrap_or(false) {
+      return;
+    }
+    guard.insert(key, Instant::now());
+  }
+  if let Ok(mut pending) = PENDING_FOCUS.lock() {
+    *pending = Some((
+      FocusTarget {
+        vault_path: vault_path.to_string(),
+        path: rel_path.unwrap_or("").to_string(),
+        kind: kind.as_str().to_string(),
+      },
+      Instant::now(),
+    ));
+  }
+  host.notify(title, body);
+}
+
+fn is_auth_error(err: &str) -> bool {
+  err.contains("token refresh failed") || err.contains("missing refresh_token")
+}
+
+/// Feeds the outcome of a background push/pull into the failure counters. Auth expiry is
+/// reported immediately; other push failures only after the configured number in a row.
+pub fn report_background_result(vault_path: &str, op: &str, res: Result<(), &str>) {
+  let err = match res {
+    Ok(()) => {
+      if op == "push" {
+        if let Ok(mut guard) = PUSH_FAILURES.lock() {
+          guard.remove(vault_path);
+        }
+      }
+      return;
+    }
+    Err(e) => e,
+  };
+  if is_auth_error(err) {
+    notify(
+      vault_path,
+      NotifyKind::AuthExpired,
+      "Diregram sync signed out",
+      "Your session expired. Open Diregram and sign in again to resume syncing.",
+      None,
+    );
+    return;
+  }
+  if op != "push" {
+    return;
+  }
+  let count = {
+    let Ok(mut guard) = PUSH_FAILURES.lock() else { return };
+    let n = guard.entry(vault_path.to_string()).or_insert(0);
+    *n += 1;
+    *n
+  };
+  let threshold = read_config(vault_path)
+    .map(|c| c.notifications.push_failure_threshold)
+    .unwrap_or(3)
+    .max(1);
+  if count == threshold {
+    notify(
+      vault_path,
+      NotifyKind::PushFailure,
+      "Diregram sync is failing",
+      &format!("{} pushes in a row failed: {}", count, err),
+      None,
+    );
+  }
+}
+
+/// The file the last notification was about, if it was shown recently. Taken once.
+pub fn take_focus_target() -> Option<FocusTarget> {
+  let (target, shown_at) = PENDING_FOCUS.lock().ok()?.take()?;
+  (shown_at.elapsed() <= FOCUS_TARGET_TTL).then_some(target)
+}
+
@@ -0,0 +1,64 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::to_rel_posix;
+
+/// Vault path -> vault-relative paths the editor currently has open.
+pub static OPEN: Lazy<Mutex<HashMap<String, HashSet<String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteChange {
+  /// The file was rewritten with the remote version.
+  Updated,
+  /// Local and remote edits collided; a conflict copy was written or local edits were kept.
+  Conflict,
+  /// The remote file is gone; the local copy was moved to the trash (or shredded).
+  Deleted,
+}
+
+/// Emitted as `sync://remote_changed` when a pull touches a file the editor has open, so it can
+/// offer to reload right away.
+#[derive(Debug, Serialize, Clone)]
+pub struct RemoteChanged {
+  pub vault_path: String,
+  pub path: String,
+  pub change: RemoteChange,
+  pub detail: String,
+}
+
+pub fn normalize(vault_path: &str, path: &str) -> String {
+  let p = Path::new(path);
+  if p.is_absolute() {
+    if let Some(rel) = to_rel_posix(Path::new(vault_path), p) {
+      return rel;
+    }
+  }
+  path.replace('\\', "/").trim_start_matches("./").to_string()
+}
+
+/// Reports a pulled change to `rel_path`, if the editor has it open.
+pub fn note(vault_path: &str, rel_path: &str, change: RemoteChange, detail: &str) {
+  let open = OPEN
+    .lock()
+    .ok()
+    .map(|g| g.get(vault_path).is_some_and(|s| s.contains(rel_path)))
+    .unwrap_or(false);
+  if !open {
+    return;
+  }
+  crate::host::emit(
+    "sync://remote_changed",
+    RemoteChanged {
+      vault_path: vault_path.to_string(),
+      path: rel_path.to_string(),
+      change,
+      detail: detail.to_string(),
+    },
+  );
+}
+
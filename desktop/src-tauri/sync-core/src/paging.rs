@@ -0,0 +1,138 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::sync::{rest_base, send_with_refresh, SupabaseAuth};
+
+const PAGE_SIZE: usize = 1000;
+/// Guards against a server that keeps answering with full pages (e.g. a filter it ignores).
+const MAX_PAGES: usize = 1000;
+
+/// A row that can be paged by its key columns. Values come back in the order of `KEYS`, which
+/// must be unique together and are what the pages are ordered by.
+pub trait KeysetRow {
+  const KEYS: &'static [&'static str];
+  fn key_values(&self) -> Vec<&str>;
+}
+
+/// Emitted as `sync://fetch_progress` after each page of a long listing.
+#[derive(Debug, Serialize, Clone)]
+pub struct FetchProgress {
+  pub table: String,
+  pub fetched: usize,
+  /// Rows the server reported for the whole listing, when it did.
+  pub total: Option<u64>,
+}
+
+/// PostgREST value quoting for `or=(...)` filters, where ids may contain `,`, `(` or `.`.
+fn quoted(v: &str) -> String {
+  format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Filter selecting rows strictly after `last` in `(k1, k2, ...)` order.
+fn after_filter(keys: &[&str], last: &[&str]) -> (String, String) {
+  if let ([key], [value]) = (keys, last) {
+    return (key.to_string(), format!("gt.{}", value));
+  }
+  let mut branches = Vec::new();
+  for i in 0..keys.len() {
+    let mut parts: Vec<String> = (0..i).map(|j| format!("{}.eq.{}", keys[j], quoted(last[j]))).collect();
+    parts.push(format!("{}.gt.{}", keys[i], quoted(last[i])));
+    branches.push(if parts.len() == 1 {
+      parts.remove(0)
+    } else {
+      format!("and({})", parts.join(","))
+    });
+  }
+  ("or".to_string(), format!("({})", branches.join(",")))
+}
+
+/// Total row count from a `Prefer: count=exact` response's `Content-Range`.
+pub fn total_from(res: &reqwest::Response) -> Option<u64> {
+  res
+    .headers()
+    .get(reqwest::header::CONTENT_RANGE)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.rsplit_once('/'))
+    .and_then(|(_, n)| n.parse::<u64>().ok())
+}
+
+/// Every row of `table` matching `filters`, paged by key (`order=<keys>` and "after the last row
+/// seen") rather than by offset, which stays fast and doesn't skip or repeat rows when the table
+/// changes mid-listing. The first page asks for an exact count so progress can show a total.
+pub async fn fetch_all<T: DeserializeOwned + KeysetRow>(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  table: &str,
+  select: &str,
+  filters: &[(&str, String)],
+) -> Result<Vec<T>, String> {
+  let base = rest_base(auth);
+  let mut out: Vec<T> = Vec::new();
+  let mut total: Option<u64> = None;
+  let mut after: Option<(String, String)> = None;
+  for page in 0..MAX_PAGES {
+    let mut url = reqwest::Url::parse(&format!("{}/{}", base, table)).map_err(|e| e.to_string())?;
+    {
+      let mut q = url.query_pairs_mut();
+      q.append_pair("select", select);
+      for (k, v) in filters {
+        q.append_pair(k, v);
+      }
+      if let Some((k, v)) = &after {
+        q.append_pair(k, v);
+      }
+      q.append_pair("order", &T::KEYS.iter().map(|k| format!("{}.asc", k)).collect::<Vec<_>>().join(","));
+      q.append_pair("limit", &PAGE_SIZE.to_string());
+    }
+    let table_name = table.to_string();
+    let (rows, page_total): (Vec<T>, Option<u64>) = send_with_refresh(
+      client,
+      auth,
+      || {
+        let req = client.get(url.clone());
+        if page == 0 {
+          req.header("Prefer", "count=exact")
+        } else {
+          req
+        }
+      },
+      |res| {
+        let table_name = table_name.clone();
+        Box::pin(async move {
+          if !res.status().is_success() {
+            return Err(format!("{} fetch failed: HTTP {}", table_name, res.status()));
+          }
+          let total = total_from(&res);
+          let rows: Vec<T> = res.json().await.map_err(|e| e.to_string())?;
+          Ok((rows, total))
+        })
+      },
+    )
+    .await?;
+    if page == 0 {
+      total = page_total;
+    }
+
+    let n = rows.len();
+    if let Some(last) = rows.last() {
+      after = Some(after_filter(T::KEYS, &last.key_values()));
+    }
+    out.extend(rows);
+    // Single-page listings (most polls) finish too quickly to be worth reporting.
+    let long = total.map(|t| t > PAGE_SIZE as u64).unwrap_or(page > 0);
+    if long {
+      crate::host::emit(
+        "sync://fetch_progress",
+        FetchProgress {
+          table: table.to_string(),
+          fetched: out.len(),
+          total,
+        },
+      );
+    }
+    if n < PAGE_SIZE {
+      break;
+    }
+  }
+  Ok(out)
+}
@@ -0,0 +1,146 @@
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::now_iso;
+
+/// OS power/network state is re-checked this often.
+pub const POLICY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub static POLICY: Lazy<Mutex<PolicyDecision>> = Lazy::new(|| Mutex::new(PolicyDecision::default()));
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncPolicyMode {
+  #[default]
+  Normal,
+  /// No watcher pushes and no polling; explicit pulls/pushes from the UI still run.
+  ManualPullOnly,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PolicyDecision {
+  pub mode: SyncPolicyMode,
+  /// `None` when the platform gives no reliable answer.
+  pub metered: Option<bool>,
+  pub power_saver: Option<bool>,
+  /// User chose to keep background sync running regardless.
+  pub overridden: bool,
+  pub reason: String,
+  pub checked_at: String,
+}
+
+pub fn run(cmd: &str, args: &[&str]) -> Option<String> {
+  let mut command = Command::new(cmd);
+  command.args(args);
+  #[cfg(target_os = "windows")]
+  {
+    // CREATE_NO_WINDOW: keep the periodic check from flashing a console.
+    use std::os::windows::process::CommandExt;
+    command.creation_flags(0x0800_0000);
+  }
+  let out = command.output().ok()?;
+  if !out.status.success() {
+    return None;
+  }
+  Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+pub fn detect() -> (Option<bool>, Option<bool>) {
+  // NetworkManager reports "yes", "yes (guessed)", "no" or "unknown" per device.
+  let metered = run("nmcli", &["-t", "-g", "GENERAL.METERED", "dev", "show"]).map(|s| s.lines().any(|l| l.starts_with("yes")));
+  let power_saver = run("powerprofilesctl", &["get"]).map(|s| s == "power-saver");
+  (metered, power_saver)
+}
+
+#[cfg(target_os = "macos")]
+fn detect() -> (Option<bool>, Option<bool>) {
+  // macOS has no CLI for Low Data Mode, so only Low Power Mode is detected.
+  let power_saver = run("pmset", &["-g"]).map(|s| {
+    s.lines()
+      .any(|l| l.split_whitespace().collect::<Vec<_>>() == ["lowpowermode", "1"])
+  });
+  (None, power_saver)
+}
+
+#[cfg(target_os = "windows")]
+fn detect() -> (Option<bool>, Option<bool>) {
+  let script = "$c=[Windows.Networking.Connectivity.NetworkInformation,Windows.Networking.Connectivity,ContentType=WindowsRuntime]::GetInternetConnectionProfile();\
+    if($c){$c.GetConnectionCost().NetworkCostType}else{'Unknown'};\
+    [Windows.System.Power.PowerManager,Windows.System.Power,ContentType=WindowsRuntime]::EnergySaverStatus";
+  let Some(out) = run("powershell", &["-NoProfile", "-NonInteractive", "-Command", script]) else {
+    return (None, None);
+  };
+  let mut lines = out.lines().map(|l| l.trim());
+  let metered = match lines.next() {
+    Some("Fixed") | Some("Variable") => Some(true),
+    Some("Unrestricted") => Some(false),
+    _ => None,
+  };
+  let power_saver = match lines.next() {
+    Some("On") => Some(true),
+    Some("Off") | Some("Disabled") => Some(false),
+    _ => None,
+  };
+  (metered, power_saver)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn detect() -> (Option<bool>, Option<bool>) {
+  (None, None)
+}
+
+pub fn decide(metered: Option<bool>, power_saver: Option<bool>, overridden: bool) -> PolicyDecision {
+  let mut reasons: Vec<&str> = Vec::new();
+  if metered == Some(true) {
+    reasons.push("metered connection");
+  }
+  if power_saver == Some(true) {
+    reasons.push("battery saver");
+  }
+  let mode = if reasons.is_empty() || overridden {
+    SyncPolicyMode::Normal
+  } else {
+    SyncPolicyMode::ManualPullOnly
+  };
+  let reason = match (reasons.is_empty(), overridden) {
+    (true, _) => String::new(),
+    (false, true) => format!("{} (overridden)", reasons.join(", ")),
+    (false, false) => reasons.join(", "),
+  };
+  PolicyDecision {
+    mode,
+    metered,
+    power_saver,
+    overridden,
+    reason,
+    checked_at: now_iso(),
+  }
+}
+
+pub fn refresh() {
+  let (metered, power_saver) = detect();
+  let changed = {
+    let Ok(mut guard) = POLICY.lock() else { return };
+    let next = decide(metered, power_saver, guard.overridden);
+    let changed = next.mode != guard.mode || next.reason != guard.reason;
+    *guard = next;
+    changed
+  };
+  if changed {
+    crate::status::notify_changed();
+  }
+}
+
+pub fn current() -> PolicyDecision {
+  POLICY.lock().map(|g| g.clone()).unwrap_or_default()
+}
+
+pub fn manual_only() -> bool {
+  current().mode == SyncPolicyMode::ManualPullOnly
+}
+
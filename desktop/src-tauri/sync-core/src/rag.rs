@@ -0,0 +1,820 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::ChunkOptions;
+use crate::config::read_config;
+use crate::sync::{diregram_dir, now_iso, read_mapping, sha256_hex, write_atomic, write_mapping, RagIngestRunV1};
+
+const DEFAULT_CHUNK_LIMIT: u32 = 48;
+const MIN_CHUNK_LIMIT: u32 = 8;
+const MAX_CHUNK_LIMIT: u32 = 96;
+const MAX_CONCURRENCY: u32 = 4;
+const MAX_BACKOFF_ATTEMPTS: u32 = 5;
+const INGEST_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+static INGEST_STATE: Lazy<Mutex<HashMap<String, IngestState>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct IngestState {
+  job_id: Option<String>,
+  cancel: Arc<AtomicBool>,
+  running: RagIngestRunning,
+}
+
+/// An ingest in flight, as `rag_ingest_status` reports it.
+#[derive(Debug, Serialize, Clone)]
+pub struct RagIngestRunning {
+  pub started_at: String,
+  /// Files being re-indexed; 0 for the whole project.
+  pub files: usize,
+  /// Latest progress reported, once the server has answered.
+  pub progress: Option<RagIngestProgress>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RagIngestRequest {
+  pub project_folder_id: String,
+  pub access_token: String,
+  pub api_base_url: String,
+  pub openai_api_key: Option<String>,
+  /// Vault linked to the project; when set, the ingest cursor is persisted in its mapping.
+  #[serde(default)]
+  pub vault_path: Option<String>,
+  /// Continue from the last persisted cursor instead of starting over.
+  #[serde(default)]
+  pub resume: bool,
+  /// Starting chunk limit per batch; shrinks automatically on 413/429/timeouts.
+  #[serde(default)]
+  pub chunk_limit: Option<u32>,
+  /// Batches kept in flight when the server reports idempotent cursors (1-4).
+  #[serde(default)]
+  pub concurrency: Option<u32>,
+  /// Re-index only these files (remote file ids) instead of the whole project.
+  #[serde(default)]
+  pub file_ids: Vec<String>,
+  /// Like `file_ids`, as vault-relative paths resolved through the mapping of `vault_path`.
+  #[serde(default)]
+  pub rel_paths: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RagIngestCancelRequest {
+  pub project_folder_id: String,
+  pub access_token: String,
+  pub api_base_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct RagIngestBody {
+  #[serde(rename = "projectFolderId")]
+  project_folder_id: String,
+  #[serde(rename = "chunkLimit")]
+  chunk_limit: u32,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  cursor: Option<u64>,
+  #[serde(rename = "fileIds", default, skip_serializing_if = "Vec::is_empty")]
+  file_ids: Vec<String>,
+  /// The vault's chunking settings (see `chunking_hints`); servers that can't honour them ignore
+  /// them.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  chunking: Option<serde_json::Value>,
+}
+
+fn chunking_hints(opts: &ChunkOptions) -> serde_json::Value {
+  serde_json::json!({
+    "strategy": opts.strategy,
+    "maxChars": opts.max_chars,
+    "overlapChars": opts.overlap_chars,
+    "maxTokens": opts.max_tokens,
+    "overlapTokens": opts.overlap_tokens,
+    "codeBlocks": opts.code_blocks,
+  })
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RagIngestProgress {
+  pub project_folder_id: String,
+  pub job_id: String,
+  pub status: String,
+  pub step: String,
+  pub progress_pct: u32,
+  pub cursor: u64,
+  pub total_chunks: u64,
+}
+
+fn ingest_cursor_from(poll_json: &serde_json::Value) -> (u64, u64) {
+  let state = poll_json.get("job").and_then(|j| j.get("state"));
+  let ingest = poll_json.get("result").and_then(|r| r.get("ingest"));
+  let cursor = state
+    .and_then(|s| s.get("cursor"))
+    .or_else(|| ingest.and_then(|i| i.get("nextCursor")))
+    .and_then(|v| v.as_u64())
+    .unwrap_or(0);
+  let total = state
+    .and_then(|s| s.get("totalChunks"))
+    .or_else(|| ingest.and_then(|i| i.get("totalChunks")))
+    .and_then(|v| v.as_u64())
+    .unwrap_or(0);
+  (cursor, total)
+}
+
+/// `.diregram/rag_cursor.json`: how far the last whole-project ingest got, to resume it. Kept out
+/// of sync.json so progress never lands on (or is lost under) a mapping a sync is writing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct RagIngestCursorV1 {
+  project_folder_id: String,
+  /// What the cursor counts (see `job_key`); a cursor of another job is ignored.
+  job: String,
+  /// The server's async job, empty for batch ingests.
+  job_id: String,
+  cursor: u64,
+  total_chunks: u64,
+  updated_at: String,
+}
+
+fn cursor_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("rag_cursor.json")
+}
+
+/// Chunk cursors only line up between ingests that split the project the same way.
+fn job_key(scope: &RagIngestBody) -> String {
+  sha256_hex(scope.chunking.as_ref().map(|c| c.to_string()).unwrap_or_default().as_bytes())
+}
+
+fn load_cursor(vault_path: &str) -> Option<RagIngestCursorV1> {
+  serde_json::from_str(&fs::read_to_string(cursor_path(vault_path)).ok()?).ok()
+}
+
+fn load_resume_cursor(vault_path: Option<&str>, scope: &RagIngestBody) -> Option<u64> {
+  let saved = load_cursor(vault_path?)?;
+  if saved.project_folder_id != scope.project_folder_id || saved.job != job_key(scope) {
+    return None;
+  }
+  if saved.cursor == 0 || (saved.total_chunks > 0 && saved.cursor >= saved.total_chunks) {
+    return None;
+  }
+  Some(saved.cursor)
+}
+
+fn persist_cursor(vault_path: Option<&str>, scope: &RagIngestBody, job_id: &str, cursor: u64, total_chunks: u64) {
+  let Some(vault_path) = vault_path else { return };
+  let mut next = RagIngestCursorV1 {
+    project_folder_id: scope.project_folder_id.clone(),
+    job: job_key(scope),
+    job_id: job_id.to_string(),
+    cursor,
+    total_chunks,
+    updated_at: String::new(),
+  };
+  if let Some(saved) = load_cursor(vault_path) {
+    if (RagIngestCursorV1 { updated_at: String::new(), ..saved }) == next {
+      return;
+    }
+  }
+  next.updated_at = now_iso();
+  let Ok(text) = serde_json::to_string_pretty(&next) else { return };
+  if fs::create_dir_all(diregram_dir(vault_path)).is_ok() {
+    let _ = write_atomic(&cursor_path(vault_path), text);
+  }
+}
+
+/// Forgets the project's cursor once an ingest has finished, so a later resume starts over
+/// rather than from a point the finished ingest already passed.
+fn clear_cursor(vault_path: Option<&str>, scope: &RagIngestBody) {
+  let Some(vault_path) = vault_path else { return };
+  if load_cursor(vault_path).is_some_and(|saved| saved.project_folder_id == scope.project_folder_id) {
+    let _ = fs::remove_file(cursor_path(vault_path));
+  }
+}
+
+/// `file_ids` of the request plus those of its `rel_paths`, which must all have been pushed.
+fn selected_files(req: &RagIngestRequest) -> Result<Vec<String>, String> {
+  let mut ids = req.file_ids.clone();
+  if req.rel_paths.is_empty() {
+    return Ok(ids);
+  }
+  let vault_path = req
+    .vault_path
+    .as_deref()
+    .filter(|v| !v.trim().is_empty())
+    .ok_or("rel_paths need vault_path")?;
+  let mapping = read_mapping(vault_path)?.ok_or("the vault is not linked to a project")?;
+  for rel in &req.rel_paths {
+    let rel = crate::normalize::nfc(rel.trim().trim_start_matches('/'));
+    let file = mapping.files.get(&rel).ok_or_else(|| format!("{} has not been pushed yet", rel))?;
+    if !ids.contains(&file.file_id) {
+      ids.push(file.file_id.clone());
+    }
+  }
+  Ok(ids)
+}
+
+pub async fn rag_ingest_jwt(req: RagIngestRequest) -> Result<serde_json::Value, String> {
+  let base = req.api_base_url.trim().trim_end_matches('/').to_string();
+  if base.is_empty() {
+    return Err("api_base_url is required".to_string());
+  }
+  if req.project_folder_id.trim().is_empty() {
+    return Err("project_folder_id is required".to_string());
+  }
+  if req.access_token.trim().is_empty() {
+    return Err("access_token is required".to_string());
+  }
+  let req = RagIngestRequest {
+    file_ids: selected_files(&req)?,
+    rel_paths: Vec::new(),
+    ..req
+  };
+
+  let key = req.project_folder_id.trim().to_string();
+  let cancel = Arc::new(AtomicBool::new(false));
+  {
+    let mut guard = INGEST_STATE.lock().map_err(|_| "ingest state lock poisoned".to_string())?;
+    if guard.contains_key(&key) {
+      return Err("rag ingest already running for this project".to_string());
+    }
+    guard.insert(
+      key.clone(),
+      IngestState {
+        job_id: None,
+        cancel: cancel.clone(),
+        running: RagIngestRunning {
+          started_at: now_iso(),
+          files: req.file_ids.len(),
+          progress: None,
+        },
+      },
+    );
+  }
+
+  let out = run_ingest(&req, &base, &cancel).await;
+
+  let started = INGEST_STATE.lock().ok().and_then(|mut g| g.remove(&key)).map(|st| st.running.started_at);
+  let vault_path = req.vault_path.as_deref().filter(|v| !v.trim().is_empty());
+  if let (Some(vault_path), Some(started_at), true) = (vault_path, started, req.file_ids.is_empty()) {
+    let _mapping = crate::lock::mapping(vault_path).await;
+    record_run(vault_path, started_at, out.as_ref().err().cloned());
+  }
+  out
+}
+
+fn record_run(vault_path: &str, started_at: String, error: Option<String>) {
+  let Ok(Some(mut mapping)) = read_mapping(vault_path) else { return };
+  mapping.last_rag_ingest = Some(RagIngestRunV1 {
+    started_at,
+    finished_at: now_iso(),
+    error,
+  });
+  mapping.updated_at = now_iso();
+  let _ = write_mapping(vault_path, &mapping);
+}
+
+/// Keeps the progress for `rag_ingest_status` and sends it to the UI.
+fn report(progress: RagIngestProgress) {
+  if let Ok(mut guard) = INGEST_STATE.lock() {
+    if let Some(st) = guard.get_mut(&progress.project_folder_id) {
+      st.running.progress = Some(progress.clone());
+    }
+  }
+  crate::host::emit("rag://ingest_progress", progress);
+}
+
+/// The project's ingest in flight, if any.
+pub fn running(project_folder_id: &str) -> Option<RagIngestRunning> {
+  INGEST_STATE.lock().ok()?.get(project_folder_id.trim()).map(|st| st.running.clone())
+}
+
+/// Chunk limit that shrinks when the server pushes back (413/429/timeouts) and
+/// slowly grows again after successful batches.
+struct AdaptiveChunkLimit {
+  current: u32,
+  ceiling: u32,
+}
+
+impl AdaptiveChunkLimit {
+  fn new(requested: u32) -> Self {
+    let ceiling = requested.clamp(MIN_CHUNK_LIMIT, MAX_CHUNK_LIMIT);
+    Self { current: ceiling, ceiling }
+  }
+
+  fn get(&self) -> u32 {
+    self.current
+  }
+
+  fn back_off(&mut self) {
+    self.current = (self.current / 2).max(MIN_CHUNK_LIMIT);
+  }
+
+  fn on_success(&mut self) {
+    self.current = (self.current + (self.current / 4).max(1)).min(self.ceiling);
+  }
+}
+
+enum IngestPostError {
+  /// Server is overloaded or the batch is too large; retry with a smaller chunk limit.
+  Backoff(String),
+  Fatal(String),
+}
+
+impl IngestPostError {
+  fn into_message(self) -> String {
+    match self {
+      IngestPostError::Backoff(m) | IngestPostError::Fatal(m) => m,
+    }
+  }
+}
+
+async fn post_ingest(
+  client: &reqwest::Client,
+  url: &str,
+  access_token: &str,
+  openai_key: &str,
+  body: &RagIngestBody,
+) -> Result<serde_json::Value, IngestPostError> {
+  let mut reqb = client
+    .post(url)
+    .timeout(INGEST_REQUEST_TIMEOUT)
+    .header("content-type", "application/json")
+    .header("authorization", format!("Bearer {}", access_token));
+  if !openai_key.is_empty() {
+    reqb = reqb.header("x-openai-api-key", openai_key);
+  }
+  let res = crate::throttle::send(reqb.json(body)).await.map_err(|e| {
+    if e.is_timeout() {
+      IngestPostError::Backoff(format!("request timed out: {}", e))
+    } else {
+      IngestPostError::Fatal(format!("request failed: {}", e))
+    }
+  })?;
+
+  let status = res.status();
+  let vercel_id = res
+    .headers()
+    .get("x-vercel-id")
+    .and_then(|v| v.to_str().ok())
+    .map(|s| s.to_string());
+  let text = res.text().await.map_err(|e| IngestPostError::Fatal(e.to_string()))?;
+  if !status.is_success() {
+    let msg = if text.trim().is_empty() {
+      format!(
+        "HTTP {}: (empty response body){}",
+        status.as_u16(),
+        vercel_id.map(|id| format!(" [x-vercel-id: {}]", id)).unwrap_or_default()
+      )
+    } else if let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) {
+      let msg = v
+        .get("error")
+        .and_then(|x| x.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| v.to_string());
+      format!(
+        "HTTP {}: {}{}",
+        status.as_u16(),
+        msg,
+        vercel_id.map(|id| format!(" [x-vercel-id: {}]", id)).unwrap_or_default()
+      )
+    } else {
+      format!(
+        "HTTP {}: {}{}",
+        status.as_u16(),
+        text,
+        vercel_id.map(|id| format!(" [x-vercel-id: {}]", id)).unwrap_or_default()
+      )
+    };
+    let backoff = status == reqwest::StatusCode::PAYLOAD_TOO_LARGE
+      || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+      || status == reqwest::StatusCode::GATEWAY_TIMEOUT;
+    return Err(if backoff {
+      IngestPostError::Backoff(msg)
+    } else {
+      IngestPostError::Fatal(msg)
+    });
+  }
+
+  serde_json::from_str::<serde_json::Value>(&text)
+    .map_err(|e| IngestPostError::Fatal(format!("bad JSON: {}: {}", e, text)))
+}
+
+/// Posts one ingest request, halving the chunk limit and retrying when the server pushes back.
+async fn post_ingest_adaptive(
+  client: &reqwest::Client,
+  url: &str,
+  access_token: &str,
+  openai_key: &str,
+  scope: &RagIngestBody,
+  limit: &mut AdaptiveChunkLimit,
+) -> Result<serde_json::Value, String> {
+  let mut attempt: u32 = 0;
+  loop {
+    let body = RagIngestBody {
+      chunk_limit: limit.get(),
+      ..scope.clone()
+    };
+    match post_ingest(client, url, access_token, openai_key, &body).await {
+      Ok(v) => {
+        limit.on_success();
+        return Ok(v);
+      }
+      Err(IngestPostError::Backoff(_)) if attempt < MAX_BACKOFF_ATTEMPTS => {
+        limit.back_off();
+        attempt += 1;
+        tokio::time::sleep(std::time::Duration::from_millis(1000 * (1u64 << attempt))).await;
+      }
+      Err(e) => return Err(e.into_message()),
+    }
+  }
+}
+
+fn ingest_batch_state(json: &serde_json::Value) -> Option<(u64, u64, bool, bool)> {
+  let ingest = json.get("ingest")?;
+  let next = ingest.get("nextCursor").and_then(|v| v.as_u64())?;
+  let total = ingest.get("totalChunks").and_then(|v| v.as_u64()).unwrap_or(0);
+  let done = ingest.get("done").and_then(|v| v.as_bool()).unwrap_or(false);
+  let idempotent = ingest.get("idempotent").and_then(|v| v.as_bool()).unwrap_or(false);
+  Some((next, total, done, idempotent))
+}
+
+async fn run_ingest(
+  req: &RagIngestRequest,
+  base: &str,
+  cancel: &AtomicBool,
+) -> Result<serde_json::Value, String> {
+  let url = format!("{}/api/rag/ingest-jwt", base);
+  let client = reqwest::Client::new();
+  let mut limit = AdaptiveChunkLimit::new(req.chunk_limit.unwrap_or(DEFAULT_CHUNK_LIMIT));
+  let concurrency = req.concurrency.unwrap_or(1).clamp(1, MAX_CONCURRENCY);
+  let project_folder_id = req.project_folder_id.trim().to_string();
+  // A partial ingest's cursor counts other chunks, so it never replaces the project's.
+  let vault_path = req
+    .vault_path
+    .as_deref()
+    .map(|s| s.trim())
+    .filter(|s| !s.is_empty() && req.file_ids.is_empty());
+  let access_token = req.access_token.trim().to_string();
+  let openai_key = req
+    .openai_api_key
+    .clone()
+    .unwrap_or_default()
+    .trim()
+    .to_string();
+
+  let mut scope = RagIngestBody {
+    project_folder_id: project_folder_id.clone(),
+    chunk_limit: limit.get(),
+    cursor: None,
+    file_ids: req.file_ids.clone(),
+    chunking: req
+      .vault_path
+      .as_deref()
+      .and_then(|v| read_config(v).ok())
+      .map(|c| chunking_hints(&c.rag.chunking)),
+  };
+  let resume_cursor = if req.resume { load_resume_cursor(vault_path, &scope) } else { None };
+  scope.cursor = resume_cursor;
+  let json = post_ingest_adaptive(&client, &url, &access_token, &openai_key, &scope, &mut limit).await?;
+  let async_enabled = json.get("async").and_then(|v| v.as_bool()).unwrap_or(false);
+  let job_id = json
+    .get("jobId")
+    .and_then(|v| v.as_str())
+    .unwrap_or("")
+    .trim()
+    .to_string();
+  if !async_enabled || job_id.is_empty() {
+    return drive_batches(
+      &client,
+      &url,
+      &access_token,
+      &openai_key,
+      &scope,
+      vault_path,
+      json,
+      &mut limit,
+      concurrency,
+      cancel,
+    )
+    .await;
+  }
+
+  if let Ok(mut guard) = INGEST_STATE.lock() {
+    if let Some(st) = guard.get_mut(&project_folder_id) {
+      st.job_id = Some(job_id.clone());
+    }
+  }
+
+  let poll_url = json
+    .get("pollUrl")
+    .and_then(|v| v.as_str())
+    .map(|s| s.to_string())
+    .unwrap_or_else(|| format!("{}/api/async-jobs/{}", base, job_id));
+
+  let mut last_cursor = resume_cursor.unwrap_or(0);
+  let mut last_total: u64 = 0;
+  for _ in 0..10_000u32 {
+    if cancel.load(Ordering::SeqCst) {
+      return Err("Async ingest cancelled".to_string());
+    }
+
+    let poll_res = crate::throttle::send(
+      client
+        .get(&poll_url)
+        .header("authorization", format!("Bearer {}", access_token)),
+    )
+    .await
+    .map_err(|e| format!("poll request failed: {}", e))?;
+    let poll_status = poll_res.status();
+    let poll_text = poll_res.text().await.map_err(|e| e.to_string())?;
+    if !poll_status.is_success() {
+      if let Ok(v) = serde_json::from_str::<serde_json::Value>(&poll_text) {
+        let msg = v
+          .get("error")
+          .and_then(|x| x.as_str())
+          .map(|s| s.to_string())
+          .unwrap_or_else(|| v.to_string());
+        return Err(format!("Poll HTTP {}: {}", poll_status.as_u16(), msg));
+      }
+      return Err(format!("Poll HTTP {}: {}", poll_status.as_u16(), poll_text));
+    }
+
+    let poll_json = serde_json::from_str::<serde_json::Value>(&poll_text)
+      .map_err(|e| format!("bad poll JSON: {}: {}", e, poll_text))?;
+    let job = poll_json.get("job");
+    let state = job
+      .and_then(|j| j.get("status"))
+      .and_then(|v| v.as_str())
+      .unwrap_or("");
+
+    let (cursor, total) = ingest_cursor_from(&poll_json);
+    last_cursor = last_cursor.max(cursor);
+    if total > 0 {
+      last_total = total;
+    }
+    persist_cursor(vault_path, &scope, &job_id, last_cursor, last_total);
+    report(RagIngestProgress {
+      project_folder_id: project_folder_id.clone(),
+      job_id: job_id.clone(),
+      status: state.to_string(),
+      step: job
+        .and_then(|j| j.get("step"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string(),
+      progress_pct: job
+        .and_then(|j| j.get("progressPct"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as u32,
+      cursor: last_cursor,
+      total_chunks: last_total,
+    });
+
+    if state == "succeeded" {
+      clear_cursor(vault_path, &scope);
+      return Ok(
+        poll_json
+          .get("result")
+          .cloned()
+          .unwrap_or_else(|| serde_json::json!({ "ok": true, "jobId": job_id })),
+      );
+    }
+    if state == "failed" || state == "cancelled" {
+      let msg = job
+        .and_then(|j| j.get("error"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("Async ingest failed");
+      return Err(msg.to_string());
+    }
+
+    tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
+  }
+
+  Err("Async ingest timed out".to_string())
+}
+
+/// Where the next round of windows starts, given this round's `(start, next_cursor, done)` replies
+/// in start order, each `lim` chunks wide. Only the contiguous prefix counts: a window the server
+/// cut short leaves a gap before the next one, so the round resumes from its `next_cursor` and the
+/// windows after it are sent again. Also returns whether that prefix finished the ingest and how
+/// many windows it spans.
+fn advance(cursor: u64, lim: u64, windows: &[(u64, u64, bool)]) -> (u64, bool, usize) {
+  let mut cursor = cursor;
+  for (used, &(start, next, done)) in windows.iter().enumerate() {
+    if start > cursor {
+      return (cursor, false, used);
+    }
+    cursor = cursor.max(next);
+    if done {
+      return (cursor, true, used + 1);
+    }
+    if next < start + lim {
+      return (cursor, false, used + 1);
+    }
+  }
+  (cursor, false, windows.len())
+}
+
+/// Drives a synchronous (per-batch) ingest endpoint until the server reports `done`,
+/// keeping up to `concurrency` windows in flight when the server declares its cursors idempotent.
+#[allow(clippy::too_many_arguments)]
+async fn drive_batches(
+  client: &reqwest::Client,
+  url: &str,
+  access_token: &str,
+  openai_key: &str,
+  scope: &RagIngestBody,
+  vault_path: Option<&str>,
+  first: serde_json::Value,
+  limit: &mut AdaptiveChunkLimit,
+  concurrency: u32,
+  cancel: &AtomicBool,
+) -> Result<serde_json::Value, String> {
+  let Some((mut cursor, mut total, mut done, idempotent)) = ingest_batch_state(&first) else {
+    clear_cursor(vault_path, scope);
+    return Ok(first);
+  };
+  let width = if idempotent { concurrency } else { 1 };
+  let mut last = first;
+  let mut attempt: u32 = 0;
+
+  while !done {
+    if cancel.load(Ordering::SeqCst) {
+      return Err("Ingest cancelled".to_string());
+    }
+    persist_cursor(vault_path, scope, "", cursor, total);
+    report(RagIngestProgress {
+      project_folder_id: scope.project_folder_id.clone(),
+      job_id: String::new(),
+      status: "running".to_string(),
+      step: "embedding_chunks".to_string(),
+      progress_pct: (cursor.min(total) * 100).checked_div(total).unwrap_or(0) as u32,
+      cursor,
+      total_chunks: total,
+    });
+
+    let lim = limit.get();
+    let mut set = tokio::task::JoinSet::new();
+    for i in 0..width as u64 {
+      let start = cursor + i * lim as u64;
+      if i > 0 && total > 0 && start >= total {
+        break;
+      }
+      let client = client.clone();
+      let url = url.to_string();
+      let access_token = access_token.to_string();
+      let openai_key = openai_key.to_string();
+      let body = RagIngestBody {
+        chunk_limit: lim,
+        cursor: Some(start),
+        ..scope.clone()
+      };
+      set.spawn(async move {
+        let res = post_ingest(&client, &url, &access_token, &openai_key, &body).await;
+        (start, res)
+      });
+    }
+
+    let mut results: Vec<(u64, Result<serde_json::Value, IngestPostError>)> = Vec::new();
+    while let Some(joined) = set.join_next().await {
+      results.push(joined.map_err(|e| format!("ingest batch task failed: {}", e))?);
+    }
+    results.sort_by_key(|(start, _)| *start);
+
+    // Anything after a failed window is re-sent next round (safe because windows are idempotent).
+    let mut backoff: Option<String> = None;
+    let mut windows = Vec::new();
+    let mut replies = Vec::new();
+    for (start, res) in results {
+      match res {
+        Ok(v) => {
+          let (next, d) = match ingest_batch_state(&v) {
+            Some((next, t, d, _)) => {
+              if t > 0 {
+                total = t;
+              }
+              (next, d)
+            }
+            None => (start, false),
+          };
+          windows.push((start, next, d));
+          replies.push(v);
+        }
+        Err(IngestPostError::Backoff(msg)) => {
+          backoff = Some(msg);
+          break;
+        }
+        Err(IngestPostError::Fatal(msg)) => return Err(msg),
+      }
+    }
+    let (next, finished, used) = advance(cursor, lim as u64, &windows);
+    cursor = next;
+    done = finished;
+    if let Some(v) = used.checked_sub(1).and_then(|i| replies.into_iter().nth(i)) {
+      last = v;
+    }
+
+    match backoff {
+      Some(msg) => {
+        if attempt >= MAX_BACKOFF_ATTEMPTS {
+          return Err(msg);
+        }
+        limit.back_off();
+        attempt += 1;
+        tokio::time::sleep(std::time::Duration::from_millis(1000 * (1u64 << attempt))).await;
+      }
+      None => {
+        limit.on_success();
+        attempt = 0;
+      }
+    }
+    if total > 0 && cursor >= total {
+      done = true;
+    }
+  }
+
+  clear_cursor(vault_path, scope);
+  Ok(last)
+}
+
+pub async fn rag_ingest_cancel(req: RagIngestCancelRequest) -> Result<bool, String> {
+  let key = req.project_folder_id.trim().to_string();
+  let job_id = {
+    let guard = INGEST_STATE.lock().map_err(|_| "ingest state lock poisoned".to_string())?;
+    let Some(st) = guard.get(&key) else { return Ok(false) };
+    st.cancel.store(true, Ordering::SeqCst);
+    st.job_id.clone()
+  };
+
+  // Also ask the server to stop so the job does not keep embedding in the background.
+  if let Some(job_id) = job_id {
+    let base = req.api_base_url.trim().trim_end_matches('/').to_string();
+    if !base.is_empty() && !req.access_token.trim().is_empty() {
+      let res = reqwest::Client::new()
+        .post(format!("{}/api/async-jobs/{}/cancel", base, job_id))
+        .header("authorization", format!("Bearer {}", req.access_token.trim()))
+        .send()
+        .await
+        .map_err(|e| format!("cancel request failed: {}", e))?;
+      if !res.status().is_success() {
+        return Err(format!("cancel failed: HTTP {}", res.status()));
+      }
+    }
+  }
+  Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{advance, clear_cursor, cursor_path, load_resume_cursor, persist_cursor, RagIngestBody};
+
+  fn scope(project_folder_id: &str, chunking: Option<serde_json::Value>) -> RagIngestBody {
+    RagIngestBody {
+      project_folder_id: project_folder_id.to_string(),
+      chunk_limit: 50,
+      cursor: None,
+      file_ids: Vec::new(),
+      chunking,
+    }
+  }
+
+  #[test]
+  fn cursor_resumes_only_the_same_project_and_job() {
+    let vault = std::env::temp_dir().join(format!("diregram-rag-cursor-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&vault);
+    let vp = vault.to_str();
+    let project = scope("p1", None);
+    persist_cursor(vp, &project, "job-1", 120, 400);
+    assert_eq!(load_resume_cursor(vp, &project), Some(120));
+    // Kept in its own file; a sync rewriting the mapping can't lose it.
+    assert!(cursor_path(vp.unwrap()).exists());
+    assert!(!crate::sync::mapping_path(vp.unwrap()).exists());
+    assert_eq!(load_resume_cursor(vp, &scope("p2", None)), None);
+    assert_eq!(load_resume_cursor(vp, &scope("p1", Some(serde_json::json!({ "max_chars": 800 })))), None);
+    // A finished ingest leaves nothing to resume.
+    clear_cursor(vp, &project);
+    assert_eq!(load_resume_cursor(vp, &project), None);
+    assert!(!cursor_path(vp.unwrap()).exists());
+    let _ = std::fs::remove_dir_all(&vault);
+  }
+
+  #[test]
+  fn advance_stops_at_a_short_window() {
+    // The second window only got through 150..170; 200..250 must be sent again.
+    let windows = [(100, 150, false), (150, 170, false), (200, 250, false)];
+    assert_eq!(advance(100, 50, &windows), (170, false, 2));
+  }
+
+  #[test]
+  fn advance_spans_full_windows() {
+    let windows = [(0, 50, false), (50, 100, false), (100, 130, true)];
+    assert_eq!(advance(0, 50, &windows), (130, true, 3));
+  }
+
+  #[test]
+  fn advance_ignores_windows_after_a_gap() {
+    // The first window failed, so nothing after it counts.
+    assert_eq!(advance(0, 50, &[(50, 100, false)]), (0, false, 0));
+  }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::config::read_config;
+use crate::normalize::safe_rel_path;
+use crate::sync::{diregram_dir, read_mapping, rest_base, send_with_refresh, SupabaseAuth};
+
+/// Revision files are named `<ts>.md` with this format; it sorts chronologically.
+pub const REVISION_FORMAT: &str = "%Y-%m-%dT%H%M%S%3fZ";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevisionEntry {
+  /// Identifier passed back to `vault_restore_revision`.
+  pub ts: String,
+  pub created_at: String,
+  pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteRevisionEntry {
+  pub rev: i64,
+  pub updated_at: Option<String>,
+  /// Size of the stored `content` (compressed or encrypted bodies count as stored).
+  pub bytes: u64,
+  /// The row's current content rather than an entry in `file_revisions`.
+  pub current: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionRow {
+  pub rev: i64,
+  pub updated_at: Option<String>,
+  #[serde(default)]
+  pub bytes: Option<u64>,
+  #[serde(default)]
+  pub content: Option<String>,
+}
+
+fn revisions_dir(vault_path: &str, rel_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("revisions").join(rel_path)
+}
+
+/// Oldest first.
+pub fn list(vault_path: &str, rel_path: &str) -> Vec<(String, PathBuf)> {
+  let Ok(rd) = fs::read_dir(revisions_dir(vault_path, rel_path)) else {
+    return Vec::new();
+  };
+  let mut out: Vec<(String, PathBuf)> = rd
+    .filter_map(Result::ok)
+    .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+    .filter_map(|e| {
+      let p = e.path();
+      let ts = p.file_name()?.to_str()?.strip_suffix(".md")?.to_string();
+      Some((ts, p))
+    })
+    .collect();
+  out.sort();
+  out
+}
+
+/// Records `content` as the newest revision of `rel_path` after a successful push or pull, unless it
+/// matches the newest one, then trims to the vault's cap. Best effort: history never fails a sync.
+pub fn record(vault_path: &str, rel_path: &str, content: &[u8]) {
+  let Ok(cfg) = read_config(vault_path).map(|c| c.revisions) else { return };
+  if !cfg.enabled || cfg.max_per_file == 0 {
+    return;
+  }
+  let mut existing = list(vault_path, rel_path);
+  if let Some((_, newest)) = existing.last() {
+    if fs::read(newest).map(|b| b == content).unwrap_or(false) {
+      return;
+    }
+  }
+  let dir = revisions_dir(vault_path, rel_path);
+  if fs::create_dir_all(&dir).is_err() {
+    return;
+  }
+  let ts = Utc::now().format(REVISION_FORMAT).to_string();
+  let p = dir.join(format!("{}.md", ts));
+  if fs::write(&p, content).is_err() {
+    return;
+  }
+  existing.push((ts, p));
+  let excess = existing.len().saturating_sub(cfg.max_per_file as usize);
+  for (_, old) in existing.iter().take(excess) {
+    let _ = fs::remove_file(old);
+  }
+}
+
+/// Shreds every local revision of `rel_path`; returns how many were removed.
+pub fn purge(vault_path: &str, rel_path: &str, passes: u32) -> u32 {
+  let mut purged = 0;
+  for (_, p) in list(vault_path, rel_path) {
+    if crate::shred::shred_file(&p, passes).is_ok() {
+      purged += 1;
+    }
+  }
+  let _ = fs::remove_dir(revisions_dir(vault_path, rel_path));
+  purged
+}
+
+pub fn checked_rel(path: &str) -> Result<String, String> {
+  safe_rel_path(path).ok_or_else(|| format!("invalid vault path: {}", path))
+}
+
+pub fn read_local_revision(vault_path: &str, rel_path: &str, ts: &str) -> Result<String, String> {
+  let (_, p) = list(vault_path, rel_path)
+    .into_iter()
+    .find(|(t, _)| t == ts)
+    .ok_or_else(|| format!("no revision {} for {}", ts, rel_path))?;
+  fs::read_to_string(p).map_err(|e| e.to_string())
+}
+
+/// `(project_folder_id, file_id)` of a synced file.
+pub fn remote_ids(vault_path: &str, rel_path: &str) -> Result<(String, String), String> {
+  let mapping = read_mapping(vault_path)?.ok_or_else(|| "vault is not linked (missing .diregram/sync.json)".to_string())?;
+  let file_id = mapping
+    .files
+    .get(rel_path)
+    .map(|m| m.file_id.clone())
+    .ok_or_else(|| format!("{} has not been synced", rel_path))?;
+  Ok((mapping.project_folder_id, file_id))
+}
+
+pub async fn revision_rows(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  table: &str,
+  select: &str,
+  file_id: &str,
+  rev: Option<i64>,
+) -> Result<Vec<RevisionRow>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/{}", rest_base(auth), table)).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", select);
+    q.append_pair(if table == "files" { "id" } else { "file_id" }, &format!("eq.{}", file_id));
+    if let Some(rev) = rev {
+      q.append_pair("rev", &format!("eq.{}", rev));
+    }
+    q.append_pair("order", "rev.desc");
+  }
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("revision fetch failed: HTTP {}", res.status()));
+        }
+        res.json::<Vec<RevisionRow>>().await.map_err(|e| e.to_string())
+      })
+    },
+  )
+  .await
+}
+
+/// Decoded remote text of `rel_path` at `rev`, or as it is now when `rev` is `None`.
+pub async fn fetch_remote_text(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  rel_path: &str,
+  rev: Option<i64>,
+) -> Result<String, String> {
+  let (project_folder_id, file_id) = remote_ids(vault_path, rel_path)?;
+  let mut rows = revision_rows(client, auth, "files", "rev,updated_at,content", &file_id, rev).await?;
+  if rows.is_empty() && rev.is_some() {
+    rows = revision_rows(client, auth, "file_revisions", "rev,updated_at,content", &file_id, rev).await?;
+  }
+  let row = rows
+    .into_iter()
+    .next()
+    .ok_or_else(|| format!("no remote revision {} for {}", rev.map(|r| r.to_string()).unwrap_or_default(), rel_path))?;
+  let (plain, _) = crate::codec::decode(&project_folder_id, row.content.as_deref().unwrap_or(""))?;
+  Ok(plain)
+}
+
@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+use crate::config::read_config;
+use crate::sync::SyncMappingV1;
+
+/// Where pulled files of some kinds go instead of where the remote folder tree puts them
+/// (`routing.kinds` in the vault config). A routed file keeps the remote tree's path under its
+/// kind's folder, e.g. a diagram at `Projects/flow.md` lands at `diagrams/Projects/flow.md`.
+pub struct Routes {
+  kinds: BTreeMap<String, String>,
+}
+
+impl Routes {
+  pub fn load(vault_path: &str) -> Self {
+    let kinds = read_config(vault_path).map(|c| c.routing.kinds).unwrap_or_default();
+    Self {
+      kinds: kinds.into_iter().filter_map(|(kind, dir)| Some((kind, route_folder(&dir)?))).collect(),
+    }
+  }
+
+  /// Local path of a `kind` file the remote tree puts at `rel`. Files already inside their
+  /// kind's folder stay where they are.
+  pub fn apply(&self, kind: &str, rel: &str) -> String {
+    match self.kinds.get(kind) {
+      Some(dir) if !rel.starts_with(&format!("{}/", dir)) => format!("{}/{}", dir, rel),
+      _ => rel.to_string(),
+    }
+  }
+
+  /// Where the file at local `rel` sits in the remote tree: where it was pulled from, or for a
+  /// new file in a routing folder, the same path without that folder.
+  pub fn remote_rel(&self, mapping: &SyncMappingV1, rel: &str) -> String {
+    if let Some(remote) = mapping.routed.get(rel) {
+      return remote.clone();
+    }
+    self
+      .kinds
+      .values()
+      .find_map(|dir| rel.strip_prefix(&format!("{}/", dir)))
+      .unwrap_or(rel)
+      .to_string()
+  }
+
+  /// Whether `rel` is a routing folder or inside one. Pushes don't create remote folders for
+  /// these; the files in them belong to the folders they were pulled from.
+  pub fn owns_dir(&self, rel: &str) -> bool {
+    self.kinds.values().any(|dir| rel == dir || rel.starts_with(&format!("{}/", dir)))
+  }
+}
+
+/// `dir` if it is a visible folder inside the vault, outside `resources/` and `rag/`.
+pub fn route_folder(dir: &str) -> Option<String> {
+  let dir = crate::normalize::safe_rel_path(dir)?;
+  let dir = dir.trim_end_matches('/').to_string();
+  crate::notes::note_rel(&format!("{}/note.md", dir)).map(|_| dir)
+}
+
+/// Records that the file at `local_rel` sits at `remote_rel` in the remote tree (or forgets it
+/// when the two agree).
+pub fn remember(mapping: &mut SyncMappingV1, local_rel: &str, remote_rel: &str) {
+  if local_rel == remote_rel {
+    mapping.routed.remove(local_rel);
+  } else {
+    mapping.routed.insert(local_rel.to_string(), remote_rel.to_string());
+  }
+}
+
+/// Drops routing records of files that are no longer mapped.
+pub fn prune(mapping: &mut SyncMappingV1) {
+  let SyncMappingV1 { routed, files, .. } = mapping;
+  routed.retain(|rel, _| files.contains_key(rel));
+}
+
@@ -0,0 +1,168 @@
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+use crate::attachments::ObjectStore;
+use crate::config::S3Config;
+use crate::mirror::{encode_segment, BoxFuture};
+
+/// Secure storage key prefix of S3 secret keys, followed by the access key id.
+pub const SECRET_PREFIX: &str = "s3-secret:";
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+  let mut block = [0u8; 64];
+  if key.len() > 64 {
+    block[..32].copy_from_slice(&Sha256::digest(key));
+  } else {
+    block[..key.len()].copy_from_slice(key);
+  }
+  let mut inner = Sha256::new();
+  inner.update(block.map(|b| b ^ 0x36));
+  inner.update(data);
+  let mut outer = Sha256::new();
+  outer.update(block.map(|b| b ^ 0x5c));
+  outer.update(inner.finalize());
+  outer.finalize().into()
+}
+
+/// The AWS Signature Version 4 `Authorization` header of a request without query parameters that
+/// signs `host`, `x-amz-content-sha256` and `x-amz-date` (`YYYYMMDDTHHMMSSZ`).
+#[allow(clippy::too_many_arguments)]
+fn authorization(
+  access_key_id: &str,
+  secret: &str,
+  region: &str,
+  method: &str,
+  host: &str,
+  uri: &str,
+  payload_sha256: &str,
+  amz_date: &str,
+) -> String {
+  let date = &amz_date[..8];
+  let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+  let canonical = format!(
+    "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+    method, uri, host, payload_sha256, amz_date, signed_headers, payload_sha256
+  );
+  let scope = format!("{}/{}/s3/aws4_request", date, region);
+  let to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&Sha256::digest(canonical.as_bytes())));
+  let mut key = hmac(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+  for part in [region, "s3", "aws4_request"] {
+    key = hmac(&key, part.as_bytes());
+  }
+  format!(
+    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+    access_key_id,
+    scope,
+    signed_headers,
+    hex(&hmac(&key, to_sign.as_bytes()))
+  )
+}
+
+/// Attachment objects in a bucket of an S3-compatible service.
+pub struct S3Store {
+  client: reqwest::Client,
+  cfg: S3Config,
+  secret: String,
+  project_folder_id: String,
+}
+
+impl S3Store {
+  pub fn open(cfg: &S3Config, project_folder_id: &str) -> Result<Self, String> {
+    if cfg.endpoint.trim().is_empty() || cfg.bucket.trim().is_empty() || cfg.access_key_id.trim().is_empty() {
+      return Err("attachments.s3 needs an endpoint, bucket and access_key_id".to_string());
+    }
+    let profile = crate::auth::current_profile();
+    let secret = crate::secrets::get(profile.as_deref(), &format!("{}{}", SECRET_PREFIX, cfg.access_key_id))?
+      .ok_or_else(|| format!("no secret key stored for S3 access key {}", cfg.access_key_id))?;
+    Ok(Self {
+      client: reqwest::Client::new(),
+      cfg: cfg.clone(),
+      secret,
+      project_folder_id: project_folder_id.to_string(),
+    })
+  }
+
+  fn key(&self, sha256: &str) -> String {
+    let prefix = self.cfg.prefix.trim_matches('/');
+    if prefix.is_empty() {
+      format!("{}/{}", self.project_folder_id, sha256)
+    } else {
+      format!("{}/{}/{}", prefix, self.project_folder_id, sha256)
+    }
+  }
+
+  /// The signed request for the object of `sha256`.
+  fn request(&self, method: reqwest::Method, sha256: &str, body: Vec<u8>) -> Result<reqwest::RequestBuilder, String> {
+    let endpoint = reqwest::Url::parse(self.cfg.endpoint.trim()).map_err(|e| format!("attachments.s3.endpoint: {}", e))?;
+    let mut host = endpoint.host_str().ok_or("attachments.s3.endpoint has no host")?.to_string();
+    if let Some(port) = endpoint.port() {
+      host = format!("{}:{}", host, port);
+    }
+    let key: Vec<String> = self.key(sha256).split('/').map(encode_segment).collect();
+    let uri = if self.cfg.path_style {
+      format!("/{}/{}", encode_segment(&self.cfg.bucket), key.join("/"))
+    } else {
+      host = format!("{}.{}", self.cfg.bucket, host);
+      format!("/{}", key.join("/"))
+    };
+    let url = format!("{}://{}{}", endpoint.scheme(), host, uri);
+    let payload = hex(&Sha256::digest(&body));
+    let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let auth = authorization(
+      &self.cfg.access_key_id,
+      &self.secret,
+      &self.cfg.region,
+      method.as_str(),
+      &host,
+      &uri,
+      &payload,
+      &amz_date,
+    );
+    Ok(
+      self
+        .client
+        .request(method, url)
+        .header("x-amz-content-sha256", payload)
+        .header("x-amz-date", amz_date)
+        .header("Authorization", auth)
+        .body(body),
+    )
+  }
+}
+
+impl ObjectStore for S3Store {
+  fn label(&self) -> String {
+    format!("s3:{}/{}", self.cfg.endpoint.trim_end_matches('/'), self.cfg.bucket)
+  }
+
+  fn put<'a>(&'a mut self, sha256: &'a str, bytes: Vec<u8>) -> BoxFuture<'a, Result<(), String>> {
+    Box::pin(async move {
+      let res = crate::throttle::send(self.request(reqwest::Method::PUT, sha256, bytes)?)
+        .await
+        .map_err(|e| e.to_string())?;
+      // Keys are content hashes, so overwriting an existing object changes nothing.
+      if !res.status().is_success() {
+        return Err(format!("S3 upload failed: HTTP {}", res.status()));
+      }
+      Ok(())
+    })
+  }
+
+  fn delete<'a>(&'a mut self, sha256: &'a str) -> BoxFuture<'a, Result<(), String>> {
+    Box::pin(async move {
+      let res = crate::throttle::send(self.request(reqwest::Method::DELETE, sha256, Vec::new())?)
+        .await
+        .map_err(|e| e.to_string())?;
+      if !res.status().is_success() && res.status() != reqwest::StatusCode::NOT_FOUND {
+        return Err(format!("S3 delete failed: HTTP {}", res.status()));
+      }
+      Ok(())
+    })
+  }
+}
+
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::sync::SyncMappingV1;
+
+fn home_dir() -> Option<PathBuf> {
+  let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok()?;
+  if home.is_empty() {
+    return None;
+  }
+  fs::canonicalize(&home).ok().or_else(|| Some(PathBuf::from(home)))
+}
+
+/// Why `vault_path` is too broad to sync into (pulls create, move and trash files anywhere below
+/// it), or `None` when it's an ordinary folder.
+pub fn dangerous_root(vault_path: &str) -> Option<&'static str> {
+  let p = fs::canonicalize(vault_path).unwrap_or_else(|_| PathBuf::from(vault_path));
+  if p.parent().is_none() {
+    return Some("the filesystem root");
+  }
+  let home = home_dir()?;
+  if p == home {
+    Some("your home folder")
+  } else if home.starts_with(&p) {
+    Some("a folder that contains your home folder")
+  } else {
+    None
+  }
+}
+
+fn has_user_content(vault_path: &str) -> bool {
+  fs::read_dir(Path::new(vault_path))
+    .map(|rd| rd.filter_map(Result::ok).any(|e| e.file_name() != ".diregram"))
+    .unwrap_or(false)
+}
+
+/// Gate for linking a folder that has no sync mapping yet. `allow_existing` is for explicit
+/// imports, where existing files are the point; pulls only initialize empty folders unless forced.
+pub fn check_new_vault(vault_path: &str, allow_existing: bool, force: bool) -> Result<(), String> {
+  if force {
+    return Ok(());
+  }
+  if let Some(reason) = dangerous_root(vault_path) {
+    return Err(format!(
+      "Refusing to sync into {} ({}). Choose a dedicated folder, or pass force: true.",
+      reason, vault_path
+    ));
+  }
+  if !allow_existing && has_user_content(vault_path) {
+    return Err(format!(
+      "{} already contains files but isn't a Diregram vault (no .diregram/sync.json). \
+       Import it first, or pass force: true to pull into it.",
+      vault_path
+    ));
+  }
+  Ok(())
+}
+
+/// Gate for pulls into an already-linked vault: a dangerous root must have been forced once,
+/// which is remembered on the mapping.
+pub fn check_linked_vault(vault_path: &str, mapping: &mut SyncMappingV1, force: bool) -> Result<(), String> {
+  let Some(reason) = dangerous_root(vault_path) else {
+    return Ok(());
+  };
+  if force {
+    mapping.allow_unsafe_root = true;
+  }
+  if mapping.allow_unsafe_root {
+    return Ok(());
+  }
+  Err(format!(
+    "Refusing to pull into {} ({}). Move the vault to a dedicated folder, or pass force: true.",
+    reason, vault_path
+  ))
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use once_cell::sync::Lazy;
+use walkdir::WalkDir;
+
+use crate::config::WatchConfig;
+use crate::sync::{
+  is_extensionless_path, is_ignored_rel, is_markdown_path, looks_like_text_utf8, read_mapping, sha256_hex, to_rel_posix,
+  SyncMappingV1,
+};
+use crate::vaults::SyncScope;
+
+/// Filesystem types that report no (or only local) change events.
+const NETWORK_FS: [&str; 12] = [
+  "nfs", "nfs4", "cifs", "smb", "smb2", "smb3", "smbfs", "afpfs", "webdav", "davfs", "9p", "vboxsf",
+];
+/// Shortest scan interval accepted from the config.
+pub const MIN_SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Size and mtime of every candidate file at the last scan that found nothing to push, per vault.
+static LAST_SCAN: Lazy<Mutex<HashMap<String, HashMap<String, FileStat>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStat {
+  len: u64,
+  modified: Option<SystemTime>,
+}
+
+pub fn scan_interval(config: &WatchConfig) -> Duration {
+  Duration::from_secs(config.scan_interval_secs).max(MIN_SCAN_INTERVAL)
+}
+
+/// The files a push would consider, with their stats. Mirrors the push walk: notes in the
+/// selective sync outside `resources/` and `rag/`, resources under `resources/`.
+fn candidates(vault_path: &str, mapping: &SyncMappingV1, scope: &SyncScope) -> HashMap<String, (PathBuf, FileStat)> {
+  let root = Path::new(vault_path);
+  let tempfiles = crate::tempfiles::TempFileFilter::load(vault_path);
+  let mut out = HashMap::new();
+  for entry in WalkDir::new(root)
+    .follow_links(crate::symlinks::follow_links(vault_path))
+    .into_iter()
+    .filter_entry(|e| e.file_name() != ".diregram" && crate::symlinks::admit(vault_path, e))
+    .filter_map(Result::ok)
+  {
+    let p = entry.path();
+    if entry.file_type().is_dir() {
+      continue;
+    }
+    let Some(rel) = to_rel_posix(root, p) else { continue };
+    let mapped = if rel.starts_with("resources/") {
+      mapping.resources.contains_key(&rel)
+    } else if is_ignored_rel(&rel) || !scope.admits(&rel) {
+      continue;
+    } else {
+      mapping.files.contains_key(&rel)
+    };
+    if tempfiles.matches(p) && !mapped {
+      continue;
+    }
+    if !mapped && !is_markdown_path(p) {
+      // Extensionless files are only pushed when they hold text.
+      if !is_extensionless_path(p) || !fs::read(p).map(|b| looks_like_text_utf8(&b)).unwrap_or(false) {
+        continue;
+      }
+    }
+    let Ok(meta) = entry.metadata() else { continue };
+    let stat = FileStat {
+      len: meta.len(),
+      modified: meta.modified().ok(),
+    };
+    out.insert(rel, (p.to_path_buf(), stat));
+  }
+  out
+}
+
+/// Whether the vault has local changes a push would send: files added, removed, or with content
+/// other than what was last synced. Files whose size and mtime are unchanged since the previous
+/// quiet scan aren't read again. A vault that was never pushed always counts as changed.
+pub fn detect_changes(vault_path: &str) -> bool {
+  let mapping = match read_mapping(vault_path) {
+    Ok(Some(m)) => m,
+    Ok(None) => return true,
+    Err(e) => {
+      tracing::warn!(vault = %vault_path, error = %e, "scan could not read the mapping");
+      return true;
+    }
+  };
+  let scope = SyncScope::load(vault_path);
+  let current = candidates(vault_path, &mapping, &scope);
+  let previous = LAST_SCAN.lock().ok().and_then(|mut m| m.remove(vault_path));
+
+  let removed = mapping
+    .files
+    .keys()
+    .filter(|rel| scope.admits(rel))
+    .chain(mapping.resources.keys())
+    .any(|rel| !current.contains_key(rel));
+  let changed = removed
+    || current.iter().any(|(rel, (path, stat))| {
+      if previous.as_ref().and_then(|p| p.get(rel)) == Some(stat) {
+        return false;
+      }
+      let synced = match mapping.files.get(rel) {
+        Some(f) => Some(&f.local_hash),
+        None => mapping.resources.get(rel).map(|r| &r.local_hash),
+      };
+      match (synced, fs::read(path)) {
+        (Some(hash), Ok(bytes)) => sha256_hex(&bytes) != *hash,
+        _ => true,
+      }
+    });
+
+  // After a change the next scan compares against the mapping again, so a failed push is retried.
+  if !changed {
+    if let Ok(mut m) = LAST_SCAN.lock() {
+      m.insert(vault_path.to_string(), current.into_iter().map(|(rel, (_, stat))| (rel, stat)).collect());
+    }
+  }
+  changed
+}
+
+/// The network filesystem the vault lives on (e.g. `cifs`, `nfs4`, `fuse.rclone`), if any.
+/// Filesystem watchers only see changes made through the local machine there.
+pub fn network_filesystem(vault_path: &str) -> Option<String> {
+  let path = fs::canonicalize(vault_path).unwrap_or_else(|_| PathBuf::from(vault_path));
+  mount_fs_type(&path).filter(|fs| is_network_fs(fs))
+}
+
+fn is_network_fs(fs_type: &str) -> bool {
+  let fs_type = fs_type.to_ascii_lowercase();
+  NETWORK_FS.contains(&fs_type.as_str())
+    // FUSE mounts of remote storage (sshfs, rclone, Google Drive); `fuseblk` is a local disk.
+    || fs_type.starts_with("fuse.")
+    || fs_type.contains("macfuse")
+    || fs_type.contains("osxfuse")
+}
+
+/// Type of the filesystem `path` is mounted from, picked by the longest matching mount point.
+#[cfg(target_os = "linux")]
+fn mount_fs_type(path: &Path) -> Option<String> {
+  let mounts = fs::read_to_string("/proc/mounts").ok()?;
+  let entries = mounts.lines().filter_map(|line| {
+    let mut fields = line.split_whitespace();
+    let (_, point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+    Some((unescape_mount_point(point), fs_type.to_string()))
+  });
+  longest_mount(path, entries)
+}
+
+/// `/proc/mounts` writes spaces and other separators in mount points as octal escapes.
+#[cfg(target_os = "linux")]
+fn unescape_mount_point(raw: &str) -> String {
+  let bytes = raw.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let code = raw.get(i + 1..i + 4).and_then(|o| u8::from_str_radix(o, 8).ok());
+    match code {
+      Some(b) if bytes[i] == b'\\' => {
+        out.push(b);
+        i += 4;
+      }
+      _ => {
+        out.push(bytes[i]);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&out).to_string()
+}
+
+/// Parses `mount` output: `//user@server/share on /Volumes/share (smbfs, nodev, ...)`.
+#[cfg(target_os = "macos")]
+fn mount_fs_type(path: &Path) -> Option<String> {
+  let out = std::process::Command::new("mount").output().ok()?;
+  let text = String::from_utf8_lossy(&out.stdout).to_string();
+  let entries = text.lines().filter_map(|line| {
+    let (_, rest) = line.split_once(" on ")?;
+    let (point, opts) = rest.rsplit_once(" (")?;
+    let fs_type = opts.split([',', ')']).next()?.trim();
+    Some((point.to_string(), fs_type.to_string()))
+  });
+  longest_mount(path, entries)
+}
+
+/// UNC paths (`\\server\share`) are network shares. Mapped drive letters aren't detected; set
+/// `watch.mode` to `scan` for those.
+#[cfg(target_os = "windows")]
+fn mount_fs_type(path: &Path) -> Option<String> {
+  let s = path.to_string_lossy();
+  let unc = s.starts_with(r"\\?\UNC\") || (s.starts_with(r"\\") && !s.starts_with(r"\\?\"));
+  unc.then(|| "smb".to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn mount_fs_type(_path: &Path) -> Option<String> {
+  None
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn longest_mount(path: &Path, entries: impl Iterator<Item = (String, String)>) -> Option<String> {
+  entries
+    .filter(|(point, _)| path.starts_with(point))
+    .max_by_key(|(point, _)| point.len())
+    .map(|(_, fs_type)| fs_type)
+}
+
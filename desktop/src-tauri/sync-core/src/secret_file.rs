@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::KEYCHAIN_SERVICE;
+
+const FILE_NAME: &str = "secrets.enc.json";
+
+static DIR: OnceCell<PathBuf> = OnceCell::new();
+/// Held in memory only; the file never stores it.
+static PASSPHRASE: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+/// Serializes read-modify-write cycles on the file.
+static FILE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+/// The last key derived, so Argon2 (slow on purpose) runs once per process rather than per secret.
+static KEY: Lazy<Mutex<Option<CachedKey>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum KdfMode {
+  /// Key derived from machine id + OS user: protects against copying the file elsewhere, not
+  /// against other software running as the same user.
+  Machine,
+  Passphrase,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SecretFileV1 {
+  version: u32,
+  kdf: KdfMode,
+  salt: String,
+  nonce: String,
+  ciphertext: String,
+}
+
+/// service -> key -> value, mirroring keyring entries.
+type Secrets = BTreeMap<String, BTreeMap<String, String>>;
+
+/// A derived file key and what it was derived for; wiped when replaced or dropped.
+struct CachedKey {
+  mode: KdfMode,
+  salt: Vec<u8>,
+  key: [u8; 32],
+}
+
+impl Drop for CachedKey {
+  fn drop(&mut self) {
+    self.key.zeroize();
+  }
+}
+
+/// Sets where the fallback secret file lives. Call once, at startup.
+pub fn set_dir(dir: PathBuf) {
+  let _ = DIR.set(dir);
+}
+
+fn file_path() -> Result<PathBuf, String> {
+  DIR
+    .get()
+    .map(|d| d.join(FILE_NAME))
+    .ok_or_else(|| "secure storage fallback is not initialized".to_string())
+}
+
+fn machine_secret() -> String {
+  let machine_id = ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+    .iter()
+    .find_map(|p| fs::read_to_string(p).ok())
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .unwrap_or_default();
+  let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default();
+  let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).unwrap_or_default();
+  format!("{}|{}|{}|{}", KEYCHAIN_SERVICE, machine_id, user, home)
+}
+
+fn derive_key(mode: KdfMode, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, String> {
+  let mut cache = KEY.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+  if let Some(cached) = cache.as_ref().filter(|c| c.mode == mode && c.salt == salt) {
+    return Ok(Zeroizing::new(cached.key));
+  }
+  let material = Zeroizing::new(match mode {
+    KdfMode::Machine => machine_secret(),
+    KdfMode::Passphrase => PASSPHRASE
+      .lock()
+      .map_err(|_| "secure storage lock poisoned".to_string())?
+      .clone()
+      .ok_or_else(|| "secure storage is locked: enter your passphrase".to_string())?,
+  });
+  let mut key = Zeroizing::new([0u8; 32]);
+  Argon2::default()
+    .hash_password_into(material.as_bytes(), salt, &mut *key)
+    .map_err(|e| e.to_string())?;
+  *cache = Some(CachedKey {
+    mode,
+    salt: salt.to_vec(),
+    key: *key,
+  });
+  Ok(key)
+}
+
+/// Salt of the cached key when it is for `mode`, so a save can reuse the key (the nonce is what
+/// must be fresh).
+fn cached_salt(mode: KdfMode) -> Option<Vec<u8>> {
+  KEY.lock().ok()?.as_ref().filter(|c| c.mode == mode).map(|c| c.salt.clone())
+}
+
+/// Drops (and wipes) the cached key; call whenever the passphrase changes.
+fn forget_key() {
+  if let Ok(mut g) = KEY.lock() {
+    *g = None;
+  }
+}
+
+fn read_file() -> Result<Option<SecretFileV1>, String> {
+  match fs::read_to_string(file_path()?) {
+    Ok(text) => serde_json::from_str(&text).map(Some).map_err(|e| e.to_string()),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e.to_string()),
+  }
+}
+
+fn decrypt(file: &SecretFileV1) -> Result<Secrets, String> {
+  let salt = STANDARD.decode(&file.salt).map_err(|e| e.to_string())?;
+  let nonce = STANDARD.decode(&file.nonce).map_err(|e| e.to_string())?;
+  let ciphertext = STANDARD.decode(&file.ciphertext).map_err(|e| e.to_string())?;
+  if nonce.len() != 24 {
+    return Err("secure storage file is corrupt".to_string());
+  }
+  let key = derive_key(file.kdf, &salt)?;
+  let cipher = XChaCha20Poly1305::new(&(*key).into());
+  let plain = Zeroizing::new(cipher.decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref()).map_err(|_| {
+    match file.kdf {
+      KdfMode::Passphrase => "wrong passphrase for secure storage".to_string(),
+      KdfMode::Machine => "secure storage file was created on another machine or account".to_string(),
+    }
+  })?);
+  serde_json::from_slice(&plain).map_err(|e| e.to_string())
+}
+
+/// Current mode (passphrase if one is set in memory and no file exists yet) and contents.
+fn load() -> Result<(KdfMode, Secrets), String> {
+  match read_file()? {
+    Some(file) => Ok((file.kdf, decrypt(&file)?)),
+    None => {
+      let has_pass = PASSPHRASE.lock().map(|g| g.is_some()).unwrap_or(false);
+      Ok((if has_pass { KdfMode::Passphrase } else { KdfMode::Machine }, Secrets::new()))
+    }
+  }
+}
+
+fn save(mode: KdfMode, secrets: &Secrets) -> Result<(), String> {
+  let salt = match cached_salt(mode) {
+    Some(salt) => salt,
+    None => {
+      let mut salt = vec![0u8; 16];
+      getrandom::getrandom(&mut salt).map_err(|e| e.to_string())?;
+      salt
+    }
+  };
+  let mut nonce = [0u8; 24];
+  getrandom::getrandom(&mut nonce).map_err(|e| e.to_string())?;
+  let key = derive_key(mode, &salt)?;
+  let plain = Zeroizing::new(serde_json::to_vec(secrets).map_err(|e| e.to_string())?);
+  let ciphertext = XChaCha20Poly1305::new(&(*key).into())
+    .encrypt(XNonce::from_slice(&nonce), plain.as_ref())
+    .map_err(|_| "failed to encrypt secure storage".to_string())?;
+  let file = SecretFileV1 {
+    version: 1,
+    kdf: mode,
+    salt: STANDARD.encode(salt),
+    nonce: STANDARD.encode(nonce),
+    ciphertext: STANDARD.encode(ciphertext),
+  };
+
+  let path = file_path()?;
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  let tmp = path.with_extension("json.tmp");
+  let text = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+  fs::write(&tmp, text).map_err(|e| e.to_string())?;
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600));
+  }
+  fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+pub fn get(service: &str, key: &str) -> Result<Option<String>, String> {
+  let _guard = FILE_LOCK.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+  let (_, secrets) = load()?;
+  Ok(secrets.get(service).and_then(|m| m.get(key)).cloned())
+}
+
+pub fn set(service: &str, key: &str, value: &str) -> Result<(), String> {
+  let _guard = FILE_LOCK.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+  let (mode, mut secrets) = load()?;
+  secrets
+    .entry(service.to_string())
+    .or_default()
+    .insert(key.to_string(), value.to_string());
+  save(mode, &secrets)
+}
+
+pub fn remove(service: &str, key: &str) -> Result<(), String> {
+  let _guard = FILE_LOCK.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+  let (mode, mut secrets) = load()?;
+  let Some(entries) = secrets.get_mut(service) else { return Ok(()) };
+  if entries.remove(key).is_none() {
+    return Ok(());
+  }
+  if entries.is_empty() {
+    secrets.remove(service);
+  }
+  save(mode, &secrets)
+}
+
+pub fn passphrase_protected() -> bool {
+  matches!(read_file(), Ok(Some(f)) if f.kdf == KdfMode::Passphrase)
+}
+
+pub fn locked() -> bool {
+  passphrase_protected() && PASSPHRASE.lock().map(|g| g.is_none()).unwrap_or(true)
+}
+
+/// Keeps `passphrase` in memory if it opens the file (or if there is no file yet).
+pub fn unlock(passphrase: &str) -> Result<(), String> {
+  let _guard = FILE_LOCK.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+  let previous = {
+    let mut g = PASSPHRASE.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+    g.replace(passphrase.to_string())
+  };
+  forget_key();
+  let res = match read_file()? {
+    Some(file) if file.kdf == KdfMode::Passphrase => decrypt(&file).map(|_| ()),
+    _ => Ok(()),
+  };
+  if res.is_err() {
+    if let Ok(mut g) = PASSPHRASE.lock() {
+      *g = previous;
+    }
+    forget_key();
+  }
+  res
+}
+
+/// Re-encrypts the file under a passphrase, or back under the machine key when `None`.
+pub fn set_passphrase(passphrase: Option<&str>) -> Result<(), String> {
+  let _guard = FILE_LOCK.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+  let (_, secrets) = load()?;
+  let mode = {
+    let mut g = PASSPHRASE.lock().map_err(|_| "secure storage lock poisoned".to_string())?;
+    *g = passphrase.filter(|p| !p.is_empty()).map(|p| p.to_string());
+    if g.is_some() { KdfMode::Passphrase } else { KdfMode::Machine }
+  };
+  forget_key();
+  save(mode, &secrets)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{cached_salt, derive_key, forget_key, CachedKey, KdfMode, KEY, PASSPHRASE};
+
+  #[test]
+  fn key_is_cached_per_mode_and_salt_until_forgotten() {
+    *PASSPHRASE.lock().unwrap() = Some("first".to_string());
+    forget_key();
+    let salt = [1u8; 16];
+    let first = derive_key(KdfMode::Passphrase, &salt).unwrap();
+    assert_eq!(cached_salt(KdfMode::Passphrase), Some(salt.to_vec()));
+    assert_eq!(cached_salt(KdfMode::Machine), None);
+
+    // Served from the cache: the passphrase it was derived from no longer matters.
+    *PASSPHRASE.lock().unwrap() = Some("second".to_string());
+    assert_eq!(*derive_key(KdfMode::Passphrase, &salt).unwrap(), *first);
+    // Another salt is derived afresh.
+    assert_ne!(*derive_key(KdfMode::Passphrase, &[2u8; 16]).unwrap(), *first);
+
+    forget_key();
+    assert!(KEY.lock().unwrap().is_none());
+    assert_ne!(*derive_key(KdfMode::Passphrase, &salt).unwrap(), *first);
+    forget_key();
+    *PASSPHRASE.lock().unwrap() = None;
+  }
+
+  #[test]
+  fn dropping_a_cached_key_wipes_it() {
+    let mut cached = std::mem::ManuallyDrop::new(CachedKey {
+      mode: KdfMode::Machine,
+      salt: Vec::new(),
+      key: [7u8; 32],
+    });
+    // SAFETY: dropped once; afterwards only the plain key bytes are read, and the empty salt
+    // owns no allocation.
+    unsafe { std::mem::ManuallyDrop::drop(&mut cached) };
+    assert_eq!(cached.key, [0u8; 32]);
+  }
+}
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{secret_file, KEYCHAIN_SERVICE};
+
+/// Secrets saved before profiles existed live here, under the bare service name.
+pub const DEFAULT_PROFILE: &str = "default";
+/// Keyring backends can't enumerate entries, so each profile keeps a list of its keys.
+pub const INDEX_KEY: &str = "__diregram.keys__";
+/// Registry of non-default profiles, stored under the default service.
+pub const PROFILES_KEY: &str = "__diregram.profiles__";
+
+/// Set once the OS keyring fails (e.g. no Secret Service on Linux); from then on every secret goes
+/// to the encrypted file store for the rest of the session.
+pub static USE_FILE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SecureStorageStatus {
+  /// `"keyring"` or `"file"`.
+  pub backend: String,
+  pub passphrase_protected: bool,
+  pub locked: bool,
+}
+
+/// Errors that mean the keyring backend itself is missing, as opposed to a bad key or value.
+fn switch_to_file(e: &keyring::Error) -> bool {
+  let unavailable = matches!(e, keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_));
+  if unavailable {
+    USE_FILE.store(true, Ordering::SeqCst);
+  }
+  unavailable
+}
+
+pub fn normalize_profile(profile: Option<&str>) -> Result<String, String> {
+  let p = profile.map(|s| s.trim()).filter(|s| !s.is_empty()).unwrap_or(DEFAULT_PROFILE);
+  let valid = p.len() <= 64 && p.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@'));
+  if !valid {
+    return Err(format!("invalid profile name: {}", p));
+  }
+  Ok(p.to_string())
+}
+
+pub fn service_for(profile: &str) -> String {
+  if profile == DEFAULT_PROFILE {
+    KEYCHAIN_SERVICE.to_string()
+  } else {
+    format!("{}.profile.{}", KEYCHAIN_SERVICE, profile)
+  }
+}
+
+fn raw_get(service: &str, key: &str) -> Result<Option<String>, String> {
+  if !USE_FILE.load(Ordering::SeqCst) {
+    match keyring::Entry::new(service, key).and_then(|e| e.get_password()) {
+      Ok(v) => return Ok(Some(v)),
+      Err(keyring::Error::NoEntry) => return Ok(None),
+      Err(e) if !switch_to_file(&e) => return Err(e.to_string()),
+      Err(_) => {}
+    }
+  }
+  secret_file::get(service, key)
+}
+
+fn raw_set(service: &str, key: &str, value: &str) -> Result<(), String> {
+  if !USE_FILE.load(Ordering::SeqCst) {
+    match keyring::Entry::new(service, key).and_then(|e| e.set_password(value)) {
+      Ok(()) => return Ok(()),
+      Err(e) if !switch_to_file(&e) => return Err(e.to_string()),
+      Err(_) => {}
+    }
+  }
+  secret_file::set(service, key, value)
+}
+
+pub fn raw_remove(service: &str, key: &str) -> Result<(), String> {
+  if !USE_FILE.load(Ordering::SeqCst) {
+    match keyring::Entry::new(service, key).and_then(|e| e.delete_credential()) {
+      Ok(()) | Err(keyring::Error::NoEntry) => return Ok(()),
+      Err(e) if !switch_to_file(&e) => return Err(e.to_string()),
+      Err(_) => {}
+    }
+  }
+  secret_file::remove(service, key)
+}
+
+pub fn read_list(service: &str, key: &str) -> Result<Vec<String>, String> {
+  match raw_get(service, key)? {
+    Some(text) => serde_json::from_str(&text).map_err(|e| e.to_string()),
+    None => Ok(Vec::new()),
+  }
+}
+
+pub fn write_list(service: &str, key: &str, list: &[String]) -> Result<(), String> {
+  if list.is_empty() {
+    return raw_remove(service, key);
+  }
+  let text = serde_json::to_string(list).map_err(|e| e.to_string())?;
+  raw_set(service, key, &text)
+}
+
+fn is_reserved(key: &str) -> bool {
+  key == INDEX_KEY || key == PROFILES_KEY
+}
+
+pub fn get(profile: Option<&str>, key: &str) -> Result<Option<String>, String> {
+  let profile = normalize_profile(profile)?;
+  raw_get(&service_for(&profile), key)
+}
+
+pub fn set(profile: Option<&str>, key: &str, value: &str) -> Result<(), String> {
+  if is_reserved(key) {
+    return Err(format!("reserved key: {}", key));
+  }
+  let profile = normalize_profile(profile)?;
+  let service = service_for(&profile);
+  raw_set(&service, key, value)?;
+
+  let mut keys = read_list(&service, INDEX_KEY)?;
+  if !keys.iter().any(|k| k == key) {
+    keys.push(key.to_string());
+    write_list(&service, INDEX_KEY, &keys)?;
+  }
+  if profile != DEFAULT_PROFILE {
+    let mut profiles = read_list(KEYCHAIN_SERVICE, PROFILES_KEY)?;
+    if !profiles.contains(&profile) {
+      profiles.push(profile);
+      write_list(KEYCHAIN_SERVICE, PROFILES_KEY, &profiles)?;
+    }
+  }
+  Ok(())
+}
+
+pub fn remove(profile: Option<&str>, key: &str) -> Result<(), String> {
+  if is_reserved(key) {
+    return Err(format!("reserved key: {}", key));
+  }
+  let profile = normalize_profile(profile)?;
+  let service = service_for(&profile);
+  raw_remove(&service, key)?;
+  let mut keys = read_list(&service, INDEX_KEY)?;
+  let before = keys.len();
+  keys.retain(|k| k != key);
+  if keys.len() != before {
+    write_list(&service, INDEX_KEY, &keys)?;
+  }
+  Ok(())
+}
+
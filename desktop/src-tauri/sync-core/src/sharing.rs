@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{append_event, now_iso, rest_base, send_with_refresh, SupabaseAuth, SyncEvent, SyncMappingV1, SyncSummary};
+
+#[derive(Debug, Deserialize)]
+struct OwnerRow {
+  owner_id: String,
+}
+
+/// A synced file's owner and whether this account may only view it.
+#[derive(Debug, Serialize, Clone)]
+pub struct FileAccess {
+  pub path: String,
+  pub owner_id: Option<String>,
+  /// The server refused the last push of this file: the project is shared with this account as a
+  /// viewer.
+  pub read_only: bool,
+}
+
+/// Owner of the project's root folder, which rows created in it belong to; on shared projects this
+/// isn't the signed-in member. Looked up once per vault; falls back to the signed-in account.
+pub async fn project_owner(client: &reqwest::Client, auth: &mut SupabaseAuth, mapping: &mut SyncMappingV1) -> String {
+  if let Some(owner) = &mapping.project_owner_id {
+    return owner.clone();
+  }
+  let url = format!(
+    "{}/folders?select=owner_id&id=eq.{}&limit=1",
+    rest_base(auth),
+    mapping.project_folder_id
+  );
+  let owner = send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("project owner lookup failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<OwnerRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next().map(|r| r.owner_id))
+      })
+    },
+  )
+  .await;
+  match owner {
+    Ok(Some(owner)) => {
+      mapping.project_owner_id = Some(owner.clone());
+      owner
+    }
+    _ => auth.owner_id.clone(),
+  }
+}
+
+/// Whether a write failed because row-level security refused it rather than for another reason.
+pub fn is_denied(err: &str) -> bool {
+  err.contains("HTTP 403")
+}
+
+/// Marks `rel` as view-only for this account after the server refused a push of it. The local
+/// edit stays; the file is pushed again once its content changes.
+pub fn mark_read_only(vault_path: &str, mapping: &mut SyncMappingV1, rel: &str, summary: &mut SyncSummary) {
+  summary
+    .warnings
+    .push(format!("{} is shared with you as a viewer; the local edit was not pushed.", rel));
+  let Some(m) = mapping.files.get_mut(rel) else { return };
+  if m.read_only {
+    return;
+  }
+  m.read_only = true;
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "read_only".to_string(),
+      path: rel.to_string(),
+      detail: "Read-only because you're a viewer of this shared project; local edits stay local.".to_string(),
+    },
+  );
+}
+
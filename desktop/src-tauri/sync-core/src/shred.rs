@@ -0,0 +1,71 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::config::{read_config, ShredConfig};
+use crate::sync::{append_event, now_iso, SyncEvent};
+
+const CHUNK: usize = 64 * 1024;
+/// Keeps a typo from turning a delete into minutes of disk churn.
+pub const MAX_PASSES: u32 = 7;
+
+/// Overwrites `path` with random bytes `passes` times, flushing each pass to disk, then removes it.
+/// On copy-on-write or journaling file systems old blocks may survive; this is best effort.
+pub fn shred_file(path: &Path, passes: u32) -> Result<(), String> {
+  let len = fs::metadata(path).map_err(|e| e.to_string())?.len();
+  let mut f = OpenOptions::new().write(true).open(path).map_err(|e| e.to_string())?;
+  let mut buf = vec![0u8; CHUNK];
+  for _ in 0..passes.clamp(1, MAX_PASSES) {
+    f.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+    let mut left = len;
+    while left > 0 {
+      let n = left.min(CHUNK as u64) as usize;
+      getrandom::getrandom(&mut buf[..n]).map_err(|e| e.to_string())?;
+      f.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+      left -= n as u64;
+    }
+    f.sync_all().map_err(|e| e.to_string())?;
+  }
+  f.set_len(0).map_err(|e| e.to_string())?;
+  drop(f);
+  fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+/// The vault's shred settings, if they cover `rel_path`.
+pub fn covering(vault_path: &str, rel_path: &str) -> Option<ShredConfig> {
+  let cfg = read_config(vault_path).ok()?.shred;
+  let covered = cfg.enabled || cfg.globs.iter().any(|g| crate::smartfolders::path_matches(g, rel_path));
+  covered.then_some(cfg)
+}
+
+/// Shreds the vault file at `rel_path` and its local revisions, recording the outcome in the event
+/// log either way. Used in place of archiving to the trash.
+pub fn shred(vault_path: &str, rel_path: &str, cfg: &ShredConfig) -> Result<(), String> {
+  let abs = crate::normalize::local_path(Path::new(vault_path), rel_path);
+  let res = shred_file(&abs, cfg.passes);
+  let (kind, detail) = match &res {
+    Ok(()) => {
+      let revisions = crate::revisions::purge(vault_path, rel_path, cfg.passes);
+      (
+        "shred",
+        format!(
+          "Securely wiped the local copy ({} overwrite pass(es)), skipped the trash and purged {} local revision(s).",
+          cfg.passes.clamp(1, MAX_PASSES),
+          revisions
+        ),
+      )
+    }
+    Err(e) => ("shred_error", format!("Secure wipe failed; the file was left in place: {}", e)),
+  };
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: kind.to_string(),
+      path: rel_path.to_string(),
+      detail,
+    },
+  );
+  res
+}
+
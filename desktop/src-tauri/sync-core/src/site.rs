@@ -0,0 +1,767 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::chunk::{fence_marker, Fences};
+use crate::links::{dir_of, percent_decode, percent_encode, relative, resolve, SCHEME};
+use crate::sync::{is_markdown_path, read_mapping, to_rel_posix};
+
+/// Left in the site folder so a later export knows it may replace it.
+const SITE_MARKER: &str = ".diregram-site";
+const SEARCH_INDEX: &str = "search-index.js";
+/// Per page, enough text for search without making the index as large as the site.
+const SEARCH_TEXT_BYTES: usize = 4000;
+
+const STYLE: &str = "body{font:16px/1.6 system-ui,sans-serif;max-width:46em;margin:2em auto;padding:0 1em;color:#222}\
+a{color:#0b63c5}pre{background:#f4f4f4;padding:.8em;overflow:auto}code{background:#f4f4f4;padding:0 .2em}\
+pre code{padding:0}blockquote{border-left:3px solid #ccc;margin-left:0;padding-left:1em;color:#555}\
+table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:.3em .6em}img{max-width:100%}\
+.broken{color:#b00;text-decoration:underline dotted}nav{font-size:.9em;margin-bottom:2em}\
+#q{width:100%;font-size:1em;padding:.4em}";
+
+const SEARCH_SCRIPT: &str = "(function(){var q=document.getElementById('q'),r=document.getElementById('results'),\
+t=document.getElementById('tree');q.addEventListener('input',function(){var terms=q.value.toLowerCase().split(/\\s+/)\
+.filter(Boolean);r.innerHTML='';t.style.display=terms.length?'none':'';if(!terms.length)return;\
+SEARCH_INDEX.filter(function(p){var s=(p.title+' '+p.text).toLowerCase();return terms.every(function(w){\
+return s.indexOf(w)>=0})}).slice(0,50).forEach(function(p){var li=document.createElement('li'),\
+a=document.createElement('a');a.href=p.path;a.textContent=p.title;li.appendChild(a);r.appendChild(li)})})})();";
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct HtmlExportOptions {
+  /// Shown on the index page; defaults to the vault folder's name.
+  pub title: Option<String>,
+  /// Copy images and other files that aren't notes, so pages can show and link them.
+  pub include_assets: bool,
+}
+
+impl Default for HtmlExportOptions {
+  fn default() -> Self {
+    Self {
+      title: None,
+      include_assets: true,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct HtmlExportSummary {
+  pub pages: u32,
+  pub assets: u32,
+  /// Wiki-links whose note isn't in the vault; they are shown but lead nowhere.
+  pub broken_links: u32,
+}
+
+#[derive(Serialize)]
+struct SearchEntry {
+  title: String,
+  path: String,
+  text: String,
+}
+
+/// What links in a page can point to.
+struct Site {
+  /// Vault-relative note path -> page path in the site.
+  pages: HashMap<String, String>,
+  /// Lowercased note path and name, both without `.md` -> note path, for wiki-links.
+  wiki: HashMap<String, String>,
+  /// Remote file id -> note path, for `nexus://file/<id>` links.
+  by_id: HashMap<String, String>,
+}
+
+impl Site {
+  fn wiki_target(&self, name: &str) -> Option<&String> {
+    let key = name.trim().trim_end_matches(".md").to_lowercase();
+    self.wiki.get(&key).and_then(|rel| self.pages.get(rel))
+  }
+}
+
+/// Renders one note, resolving links relative to its own folder.
+struct Page<'a> {
+  site: &'a Site,
+  dir: Vec<&'a str>,
+  broken_links: u32,
+}
+
+impl Page<'_> {
+  fn href(&self, page: &str, anchor: &str) -> String {
+    format!("{}{}", percent_encode(&relative(&self.dir, page)), anchor)
+  }
+
+  /// Link targets to other notes point at their pages; anything else is left as written.
+  fn link(&self, target: &str) -> String {
+    if let Some(rest) = target.strip_prefix(SCHEME) {
+      let (id, anchor) = rest.split_at(rest.find('#').unwrap_or(rest.len()));
+      if let Some(page) = self.site.by_id.get(id).and_then(|rel| self.site.pages.get(rel)) {
+        return self.href(page, anchor);
+      }
+      return target.to_string();
+    }
+    let first = target.split('/').next().unwrap_or("");
+    if target.is_empty() || target.starts_with('#') || target.starts_with('/') || first.contains(':') {
+      return target.to_string();
+    }
+    let (path, anchor) = target.split_at(target.find('#').unwrap_or(target.len()));
+    let Some(rel) = resolve(&self.dir, &percent_decode(path)) else { return target.to_string() };
+    let rel = crate::normalize::nfc(&rel);
+    match self.site.pages.get(&rel).or_else(|| self.site.pages.get(&format!("{}.md", rel))) {
+      Some(page) => self.href(page, anchor),
+      None => target.to_string(),
+    }
+  }
+
+  /// `[[Note]]`, `[[Note#Heading]]` and `[[Note|label]]`.
+  fn wiki_link(&mut self, inner: &str) -> String {
+    let (target, label) = match inner.split_once('|') {
+      Some((t, l)) => (t, l.trim()),
+      None => (inner, inner.trim()),
+    };
+    let (name, heading) = target.split_at(target.find('#').unwrap_or(target.len()));
+    let anchor = heading.strip_prefix('#').map(|h| format!("#{}", slug(h))).unwrap_or_default();
+    if name.trim().is_empty() {
+      return format!("<a href=\"{}\">{}</a>", escape(&anchor), escape(label));
+    }
+    match self.site.wiki_target(name) {
+      Some(page) => format!("<a href=\"{}\">{}</a>", escape(&self.href(page, &anchor)), escape(label)),
+      None => {
+        self.broken_links += 1;
+        format!("<span class=\"broken\">{}</span>", escape(label))
+      }
+    }
+  }
+
+  /// `![[image.png]]`: images are shown, notes are linked.
+  fn embed(&mut self, inner: &str) -> String {
+    let name = inner.split('|').next().unwrap_or(inner).trim();
+    let is_image = ["png", "jpg", "jpeg", "gif", "webp", "svg"]
+      .iter()
+      .any(|e| name.to_lowercase().ends_with(&format!(".{}", e)));
+    if is_image {
+      format!("<img src=\"{}\" alt=\"{}\">", escape(&self.link(name)), escape(name))
+    } else {
+      self.wiki_link(inner)
+    }
+  }
+
+  fn inline(&mut self, text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+      let rest = &text[i..];
+      let c = rest.chars().next().unwrap_or(' ');
+      if c == '\\' {
+        if let Some(n) = rest[1..].chars().next().filter(|n| n.is_ascii_punctuation()) {
+          out.push_str(&escape(&n.to_string()));
+          i += 2;
+          continue;
+        }
+      }
+      if c == '`' {
+        let run = rest.bytes().take_while(|&b| b == b'`').count();
+        let fence = &rest[..run];
+        if let Some(end) = rest[run..].find(fence) {
+          out.push_str(&format!("<code>{}</code>", escape(rest[run..run + end].trim())));
+          i += run + end + run;
+        } else {
+          out.push_str(fence);
+          i += run;
+        }
+        continue;
+      }
+      if let Some(inner) = rest.strip_prefix("![[").and_then(|r| r.find("]]").map(|e| &r[..e])) {
+        out.push_str(&self.embed(inner));
+        i += inner.len() + 5;
+        continue;
+      }
+      if let Some(inner) = rest.strip_prefix("[[").and_then(|r| r.find("]]").map(|e| &r[..e])) {
+        out.push_str(&self.wiki_link(inner));
+        i += inner.len() + 4;
+        continue;
+      }
+      let image = rest.starts_with("![");
+      if image || c == '[' {
+        let open = if image { 1 } else { 0 };
+        if let Some((label, target, len)) = parse_link(&rest[open..]) {
+          let href = escape(&self.link(target));
+          if image {
+            out.push_str(&format!("<img src=\"{}\" alt=\"{}\">", href, escape(label)));
+          } else {
+            out.push_str(&format!("<a href=\"{}\">{}</a>", href, self.inline(label)));
+          }
+          i += open + len;
+          continue;
+        }
+      }
+      if c == '<' {
+        if let Some(end) = rest.find('>') {
+          let url = &rest[1..end];
+          if (url.starts_with("http://") || url.starts_with("https://")) && !url.contains(' ') {
+            out.push_str(&format!("<a href=\"{0}\">{0}</a>", escape(url)));
+            i += end + 1;
+            continue;
+          }
+        }
+      }
+      let emphasis = [("**", "strong"), ("__", "strong"), ("~~", "del"), ("*", "em"), ("_", "em")];
+      if let Some((delim, tag, inner)) = emphasis.iter().find_map(|(d, tag)| {
+        let body = rest.strip_prefix(d)?;
+        // `_` inside words (snake_case) isn't emphasis.
+        if d.starts_with('_') && text[..i].chars().last().map(char::is_alphanumeric).unwrap_or(false) {
+          return None;
+        }
+        let end = body.find(d)?;
+        (end > 0 && !body.starts_with(' ')).then(|| (*d, *tag, &body[..end]))
+      }) {
+        out.push_str(&format!("<{0}>{1}</{0}>", tag, self.inline(inner)));
+        i += delim.len() * 2 + inner.len();
+        continue;
+      }
+      out.push_str(&escape(&c.to_string()));
+      i += c.len_utf8();
+    }
+    out
+  }
+
+  fn blocks(&mut self, lines: &[&str]) -> String {
+    let mut out = String::new();
+    let mut para: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+      let line = lines[i];
+      let trimmed = line.trim_start();
+      let starts_block = trimmed.is_empty()
+        || fence_marker(line).is_some()
+        || heading(trimmed).is_some()
+        || is_rule(trimmed)
+        || trimmed.starts_with('>')
+        || list_marker(line).is_some()
+        || (trimmed.contains('|') && lines.get(i + 1).map(|l| is_table_rule(l)).unwrap_or(false));
+      if !starts_block {
+        para.push(line);
+        i += 1;
+        continue;
+      }
+      if !para.is_empty() {
+        out.push_str(&self.paragraph(&para));
+        para.clear();
+      }
+      if trimmed.is_empty() {
+        i += 1;
+      } else if fence_marker(line).is_some() {
+        let mut fences = Fences::default();
+        fences.toggles(line);
+        let lang = trimmed.trim_start_matches(['`', '~']).trim();
+        let mut code = String::new();
+        i += 1;
+        while i < lines.len() && !fences.toggles(lines[i]) {
+          code.push_str(lines[i]);
+          code.push('\n');
+          i += 1;
+        }
+        i += 1;
+        let class = if lang.is_empty() { String::new() } else { format!(" class=\"language-{}\"", escape(lang)) };
+        out.push_str(&format!("<pre><code{}>{}</code></pre>\n", class, escape(&code)));
+      } else if let Some((level, text)) = heading(trimmed) {
+        let text = text.trim_end().trim_end_matches('#').trim_end();
+        out.push_str(&format!("<h{0} id=\"{1}\">{2}</h{0}>\n", level, slug(text), self.inline(text)));
+        i += 1;
+      } else if is_rule(trimmed) {
+        out.push_str("<hr>\n");
+        i += 1;
+      } else if trimmed.starts_with('>') {
+        let mut quoted = Vec::new();
+        while i < lines.len() && lines[i].trim_start().starts_with('>') {
+          let l = &lines[i].trim_start()[1..];
+          quoted.push(l.strip_prefix(' ').unwrap_or(l));
+          i += 1;
+        }
+        out.push_str(&format!("<blockquote>\n{}</blockquote>\n", self.blocks(&quoted)));
+      } else if list_marker(line).is_some() {
+        i = self.list(lines, i, &mut out);
+      } else {
+        i = self.table(lines, i, &mut out);
+      }
+    }
+    if !para.is_empty() {
+      out.push_str(&self.paragraph(&para));
+    }
+    out
+  }
+
+  fn paragraph(&mut self, lines: &[&str]) -> String {
+    let parts: Vec<String> = lines
+      .iter()
+      .map(|l| {
+        let hard_break = l.ends_with("  ");
+        let text = self.inline(l.trim());
+        if hard_break {
+          format!("{}<br>", text)
+        } else {
+          text
+        }
+      })
+      .collect();
+    format!("<p>{}</p>\n", parts.join("\n"))
+  }
+
+  /// A list starting at `lines[start]`, with items nested by indentation. Returns the line after it.
+  fn list(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+    let Some((indent, ordered, _)) = list_marker(lines[start]) else { return start + 1 };
+    let tag = if ordered { "ol" } else { "ul" };
+    out.push_str(&format!("<{}>\n", tag));
+    let mut i = start;
+    while let Some((item_indent, item_ordered, content)) = lines.get(i).and_then(|l| list_marker(l)) {
+      if item_indent != indent || item_ordered != ordered {
+        break;
+      }
+      // The item's other lines: anything indented past its marker, blank lines between them included.
+      let mut body: Vec<&str> = Vec::new();
+      i += 1;
+      while i < lines.len() {
+        let l = lines[i];
+        let deeper = l.len() - l.trim_start().len() > indent;
+        let next_deeper = lines.get(i + 1).map(|n| n.len() - n.trim_start().len() > indent).unwrap_or(false);
+        if !(deeper || (l.trim().is_empty() && next_deeper)) {
+          break;
+        }
+        body.push(l);
+        i += 1;
+      }
+      let (check, content) = match content.get(..4) {
+        Some("[ ] ") => ("<input type=\"checkbox\" disabled> ", &content[4..]),
+        Some("[x] ") | Some("[X] ") => ("<input type=\"checkbox\" disabled checked> ", &content[4..]),
+        _ => ("", content),
+      };
+      out.push_str(&format!("<li>{}{}", check, self.inline(content.trim())));
+      if !body.is_empty() {
+        let width = body
+          .iter()
+          .filter(|l| !l.trim().is_empty())
+          .map(|l| l.len() - l.trim_start().len())
+          .min()
+          .unwrap_or(0);
+        let dedented: Vec<&str> = body.iter().map(|l| l.get(width..).unwrap_or("")).collect();
+        out.push('\n');
+        out.push_str(&self.blocks(&dedented));
+      }
+      out.push_str("</li>\n");
+      while lines.get(i).map(|l| l.trim().is_empty()).unwrap_or(false)
+        && lines.get(i + 1).and_then(|l| list_marker(l)).map(|(n, o, _)| n == indent && o == ordered).unwrap_or(false)
+      {
+        i += 1;
+      }
+    }
+    out.push_str(&format!("</{}>\n", tag));
+    i
+  }
+
+  /// A pipe table whose header is `lines[start]`. Returns the line after it.
+  fn table(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+    out.push_str("<table>\n<thead><tr>");
+    for cell in cells(lines[start]) {
+      out.push_str(&format!("<th>{}</th>", self.inline(cell)));
+    }
+    out.push_str("</tr></thead>\n<tbody>\n");
+    let mut i = start + 2;
+    while i < lines.len() && lines[i].contains('|') && !lines[i].trim().is_empty() {
+      out.push_str("<tr>");
+      for cell in cells(lines[i]) {
+        out.push_str(&format!("<td>{}</td>", self.inline(cell)));
+      }
+      out.push_str("</tr>\n");
+      i += 1;
+    }
+    out.push_str("</tbody>\n</table>\n");
+    i
+  }
+}
+
+fn escape(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+/// Heading anchor as most markdown renderers make it: lowercase words joined by `-`.
+fn slug(text: &str) -> String {
+  let mut out = String::new();
+  for c in text.trim().to_lowercase().chars() {
+    if c.is_alphanumeric() || c == '-' || c == '_' {
+      out.push(c);
+    } else if c.is_whitespace() {
+      out.push('-');
+    }
+  }
+  out
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+  let level = line.bytes().take_while(|&b| b == b'#').count();
+  let rest = &line[level..];
+  ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))).then(|| (level, rest.trim_start()))
+}
+
+fn is_rule(line: &str) -> bool {
+  let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+  compact.len() >= 3 && ["-", "*", "_"].iter().any(|m| compact.chars().all(|c| c.to_string() == *m))
+}
+
+fn is_table_rule(line: &str) -> bool {
+  let t = line.trim();
+  t.contains('-') && t.contains('|') && t.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+/// Indentation, whether it's numbered, and the text after the marker.
+fn list_marker(line: &str) -> Option<(usize, bool, &str)> {
+  let t = line.trim_start();
+  let indent = line.len() - t.len();
+  if let Some(rest) = t.strip_prefix("- ").or_else(|| t.strip_prefix("* ")).or_else(|| t.strip_prefix("+ ")) {
+    return Some((indent, false, rest));
+  }
+  let digits = t.bytes().take_while(u8::is_ascii_digit).count();
+  let rest = &t[digits..];
+  if (1..=9).contains(&digits) && (rest.starts_with(". ") || rest.starts_with(") ")) {
+    return Some((indent, true, &rest[2..]));
+  }
+  None
+}
+
+fn cells(line: &str) -> Vec<&str> {
+  let t = line.trim();
+  let t = t.strip_prefix('|').unwrap_or(t);
+  let t = t.strip_suffix('|').unwrap_or(t);
+  t.split('|').map(str::trim).collect()
+}
+
+/// `[label](target "title")` at the start of `s`: label, target and the length consumed.
+fn parse_link(s: &str) -> Option<(&str, &str, usize)> {
+  let mut depth = 0;
+  let close = s.char_indices().find_map(|(i, c)| {
+    match c {
+      '[' => depth += 1,
+      ']' if depth == 1 => return Some(i),
+      ']' => depth -= 1,
+      _ => {}
+    }
+    None
+  })?;
+  let after = s[close + 1..].strip_prefix('(')?;
+  let start = close + 2;
+  let (target, end) = if let Some(angled) = after.strip_prefix('<') {
+    let e = angled.find('>')?;
+    (&angled[..e], start + 1 + e + 1)
+  } else {
+    let mut depth = 0;
+    let e = after
+      .char_indices()
+      .find(|&(_, c)| {
+        match c {
+          '(' => depth += 1,
+          ')' if depth == 0 => return true,
+          ')' => depth -= 1,
+          c if c.is_whitespace() => return depth == 0,
+          _ => {}
+        }
+        false
+      })
+      .map(|(i, _)| i)?;
+    (&after[..e], start + e)
+  };
+  // Skip an optional title up to the closing parenthesis.
+  let close_paren = s[end..].find(')')?;
+  Some((&s[1..close], target, end + close_paren + 1))
+}
+
+/// The note without its YAML front matter.
+pub fn body(markdown: &str) -> &str {
+  if let Some(rest) = markdown.strip_prefix("---\n") {
+    if let Some(end) = rest.find("\n---\n") {
+      return &rest[end + 5..];
+    }
+  }
+  markdown
+}
+
+fn title_of(markdown: &str, rel: &str) -> String {
+  body(markdown)
+    .lines()
+    .find_map(|l| l.strip_prefix("# ").map(|t| t.trim().to_string()))
+    .filter(|t| !t.is_empty())
+    .unwrap_or_else(|| {
+      let name = rel.rsplit('/').next().unwrap_or(rel);
+      name.strip_suffix(".md").or_else(|| name.strip_suffix(".markdown")).unwrap_or(name).to_string()
+    })
+}
+
+fn page_html(site_title: &str, title: &str, dir: &[&str], content: &str) -> String {
+  let home = percent_encode(&relative(dir, "index.html"));
+  format!(
+    "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{} – {}</title>\n<style>{}</style>\n</head>\n\
+<body>\n<nav><a href=\"{}\">{}</a></nav>\n<main>\n{}</main>\n</body>\n</html>\n",
+    escape(title),
+    escape(site_title),
+    STYLE,
+    home,
+    escape(site_title),
+    content
+  )
+}
+
+/// The index: a search box and the notes listed folder by folder.
+fn index_html(site_title: &str, entries: &[SearchEntry]) -> String {
+  let mut by_folder: BTreeMap<&str, Vec<&SearchEntry>> = BTreeMap::new();
+  for e in entries {
+    let folder = e.path.rfind('/').map(|i| &e.path[..i]).unwrap_or("");
+    by_folder.entry(folder).or_default().push(e);
+  }
+  let mut tree = String::new();
+  for (folder, pages) in by_folder {
+    if !folder.is_empty() {
+      tree.push_str(&format!("<h2>{}</h2>\n", escape(folder)));
+    }
+    tree.push_str("<ul>\n");
+    for p in pages {
+      tree.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", escape(&percent_encode(&p.path)), escape(&p.title)));
+    }
+    tree.push_str("</ul>\n");
+  }
+  format!(
+    "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>{1}</style>\n</head>\n<body>\n\
+<h1>{0}</h1>\n<input id=\"q\" type=\"search\" placeholder=\"Search\" autofocus>\n<ul id=\"results\"></ul>\n\
+<div id=\"tree\">\n{2}</div>\n<script src=\"{3}\"></script>\n<script>{4}</script>\n</body>\n</html>\n",
+    escape(site_title),
+    STYLE,
+    tree,
+    SEARCH_INDEX,
+    SEARCH_SCRIPT
+  )
+}
+
+/// Notes and other files of the vault worth exporting, sorted: no sync internals, hidden folders,
+/// RAG exports or editor temp files.
+pub fn collect(root: &Path, vault_path: &str) -> Vec<(PathBuf, String)> {
+  let tempfiles = crate::tempfiles::TempFileFilter::load(vault_path);
+  WalkDir::new(root)
+    .sort_by_file_name()
+    .into_iter()
+    .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+    .filter_map(Result::ok)
+    .filter(|e| e.file_type().is_file() && !tempfiles.matches(e.path()))
+    .filter_map(|e| to_rel_posix(root, e.path()).map(|rel| (e.path().to_path_buf(), rel)))
+    .filter(|(_, rel)| rel != "rag" && !rel.starts_with("rag/"))
+    .collect()
+}
+
+/// Site path of a note: its path with `.html` for the extension. The site's own index keeps
+/// `index.html`, so a root `index.md` becomes `index.note.html`.
+fn page_path(rel: &str) -> String {
+  let stem = rel.strip_suffix(".md").or_else(|| rel.strip_suffix(".markdown")).unwrap_or(rel);
+  if stem == "index" {
+    "index.note.html".to_string()
+  } else {
+    format!("{}.html", stem)
+  }
+}
+
+/// Empties `dest` when it holds an earlier export (left with `marker`); any other non-empty folder
+/// is refused.
+pub fn prepare_dest(dest: &Path, marker: &str) -> Result<(), String> {
+  let has_files = fs::read_dir(dest).map(|mut d| d.next().is_some()).unwrap_or(false);
+  if has_files {
+    if !dest.join(marker).exists() {
+      return Err(format!("{} is not empty", dest.display()));
+    }
+    fs::remove_dir_all(dest).map_err(|e| e.to_string())?;
+  }
+  fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+  fs::write(dest.join(marker), "").map_err(|e| e.to_string())
+}
+
+pub fn write(dest: &Path, rel: &str, content: &[u8]) -> Result<(), String> {
+  let p = crate::normalize::local_path(dest, rel);
+  if let Some(parent) = p.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  fs::write(&p, content).map_err(|e| format!("{}: {}", rel, e))
+}
+
+pub fn export(vault_path: &str, dest_dir: &Path, options: &HtmlExportOptions) -> Result<HtmlExportSummary, String> {
+  let root = Path::new(vault_path);
+  if !root.is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let dest = dest_dir.to_path_buf();
+  if dest.starts_with(root) {
+    return Err("dest_dir must be outside the vault, or the site would be synced as notes".to_string());
+  }
+  let site_title = options
+    .title
+    .clone()
+    .filter(|t| !t.trim().is_empty())
+    .or_else(|| root.file_name().map(|n| n.to_string_lossy().to_string()))
+    .unwrap_or_else(|| "Vault".to_string());
+
+  let files = collect(root, vault_path);
+  let mut site = Site {
+    pages: HashMap::new(),
+    wiki: HashMap::new(),
+    by_id: HashMap::new(),
+  };
+  for (abs, rel) in &files {
+    if !is_markdown_path(abs) {
+      continue;
+    }
+    site.pages.insert(rel.clone(), page_path(rel));
+    let stem = rel.strip_suffix(".md").or_else(|| rel.strip_suffix(".markdown")).unwrap_or(rel);
+    site.wiki.insert(stem.to_lowercase(), rel.clone());
+    // By name too, the first (sorted) note winning when several share one.
+    let name = stem.rsplit('/').next().unwrap_or(stem).to_lowercase();
+    site.wiki.entry(name).or_insert_with(|| rel.clone());
+  }
+  if let Ok(Some(mapping)) = read_mapping(vault_path) {
+    site.by_id = mapping.files.iter().map(|(rel, fm)| (fm.file_id.clone(), rel.clone())).collect();
+  }
+
+  prepare_dest(&dest, SITE_MARKER)?;
+  let mut summary = HtmlExportSummary::default();
+  let mut entries = Vec::new();
+  for (abs, rel) in &files {
+    if !is_markdown_path(abs) {
+      if options.include_assets {
+        let bytes = fs::read(abs).map_err(|e| format!("{}: {}", rel, e))?;
+        write(&dest, rel, &bytes)?;
+        summary.assets += 1;
+      }
+      continue;
+    }
+    let bytes = fs::read(abs).map_err(|e| format!("{}: {}", rel, e))?;
+    let (markdown, _) = crate::encoding::decode(&bytes);
+    let markdown = markdown.replace("\r\n", "\n");
+    let lines: Vec<&str> = body(&markdown).lines().collect();
+    let path = page_path(rel);
+    let mut page = Page {
+      site: &site,
+      dir: dir_of(&path),
+      broken_links: 0,
+    };
+    let content = page.blocks(&lines);
+    summary.broken_links += page.broken_links;
+    let title = title_of(&markdown, rel);
+    write(&dest, &path, page_html(&site_title, &title, &page.dir, &content).as_bytes())?;
+    summary.pages += 1;
+
+    let mut text = body(&markdown).to_string();
+    if text.len() > SEARCH_TEXT_BYTES {
+      let mut cut = SEARCH_TEXT_BYTES;
+      while !text.is_char_boundary(cut) {
+        cut -= 1;
+      }
+      text.truncate(cut);
+    }
+    entries.push(SearchEntry { title, path, text });
+  }
+
+  let index = serde_json::to_string(&entries).map_err(|e| e.to_string())?;
+  write(&dest, SEARCH_INDEX, format!("var SEARCH_INDEX = {};\n", index).as_bytes())?;
+  write(&dest, "index.html", index_html(&site_title, &entries).as_bytes())?;
+  Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+  use std::fs;
+
+  use super::{export, page_path, HtmlExportOptions, Page, Site};
+  use crate::links::dir_of;
+
+  fn site() -> Site {
+    let pages = [("notes/a.md", "notes/a.html"), ("b.md", "b.html")];
+    let wiki = [("notes/a", "notes/a.md"), ("a", "notes/a.md"), ("b", "b.md")];
+    Site {
+      pages: pages.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+      wiki: wiki.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+      by_id: HashMap::from([("f-b".to_string(), "b.md".to_string())]),
+    }
+  }
+
+  /// The page body for `markdown` at `page`, and how many broken links it had.
+  fn render(page: &str, markdown: &str) -> (String, u32) {
+    let site = site();
+    let mut page = Page {
+      site: &site,
+      dir: dir_of(page),
+      broken_links: 0,
+    };
+    let lines: Vec<&str> = markdown.lines().collect();
+    let html = page.blocks(&lines);
+    (html, page.broken_links)
+  }
+
+  #[test]
+  fn links_point_between_pages_relative_to_the_page() {
+    let (html, broken) = render(
+      "notes/a.html",
+      "See [[b]], [[B#Some Heading|the end]], [[missing]], [next](../b.md#x) and [id](nexus://file/f-b).",
+    );
+    assert!(html.contains("<a href=\"../b.html\">b</a>"), "{}", html);
+    assert!(html.contains("<a href=\"../b.html#some-heading\">the end</a>"), "{}", html);
+    assert!(html.contains("<span class=\"broken\">missing</span>"), "{}", html);
+    assert!(html.contains("<a href=\"../b.html#x\">next</a>"), "{}", html);
+    assert!(html.contains("<a href=\"../b.html\">id</a>"), "{}", html);
+    assert_eq!(broken, 1);
+  }
+
+  #[test]
+  fn fences_close_only_on_their_own_marker() {
+    let (html, _) = render("b.html", "~~~~ rust\n```\n<b>\n~~~~\nafter");
+    assert_eq!(html, "<pre><code class=\"language-rust\">```\n&lt;b&gt;\n</code></pre>\n<p>after</p>\n");
+  }
+
+  #[test]
+  fn a_root_index_note_does_not_replace_the_site_index() {
+    assert_eq!(page_path("index.md"), "index.note.html");
+    assert_eq!(page_path("sub/index.md"), "sub/index.html");
+    assert_eq!(page_path("notes/a.markdown"), "notes/a.html");
+  }
+
+  #[test]
+  fn export_writes_pages_assets_and_index() {
+    let base = std::env::temp_dir().join(format!("diregram-site-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&base);
+    let (vault, dest) = (base.join("vault"), base.join("site"));
+    fs::create_dir_all(vault.join("sub")).unwrap();
+    fs::write(vault.join("index.md"), "# Home\n\nSee [[Other]].\n").unwrap();
+    fs::write(vault.join("sub/Other.md"), "# Other\n").unwrap();
+    fs::write(vault.join("img.png"), [0x89, b'P', b'N', b'G']).unwrap();
+    let vp = vault.to_str().unwrap();
+
+    let summary = export(vp, &dest, &HtmlExportOptions::default()).unwrap();
+    assert_eq!((summary.pages, summary.assets, summary.broken_links), (2, 1, 0));
+    let home = fs::read_to_string(dest.join("index.note.html")).unwrap();
+    assert!(home.contains("<a href=\"sub/Other.html\">Other</a>"), "{}", home);
+    for file in ["index.html", "search-index.js", "sub/Other.html", "img.png"] {
+      assert!(dest.join(file).exists(), "{}", file);
+    }
+
+    // An earlier export is replaced; anything else, or a folder inside the vault, is refused.
+    assert!(export(vp, &dest, &HtmlExportOptions::default()).is_ok());
+    let other = base.join("other");
+    fs::create_dir_all(&other).unwrap();
+    fs::write(other.join("keep.txt"), "mine").unwrap();
+    assert!(export(vp, &other, &HtmlExportOptions::default()).unwrap_err().contains("not empty"));
+    assert!(export(vp, &vault.join("site"), &HtmlExportOptions::default()).is_err());
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::config::SmartFolderQuery;
+use crate::sync::{detect_kind, is_markdown_path};
+
+/// A bound of `modified_after` / `modified_before`: an RFC 3339 time, or midnight UTC of a date.
+fn parse_bound(s: &str) -> Result<DateTime<Utc>, String> {
+  let s = s.trim();
+  DateTime::parse_from_rfc3339(s)
+    .map(|t| t.with_timezone(&Utc))
+    .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()))
+    .map_err(|_| format!("not a date or RFC 3339 time: {}", s))
+}
+
+fn glob(p: &[char], s: &[char]) -> bool {
+  match p.first() {
+    None => s.is_empty(),
+    Some('*') if p.get(1) == Some(&'*') => {
+      let rest = &p[2..];
+      // `**/` may also stand for no folder at all.
+      (rest.first() == Some(&'/') && glob(&rest[1..], s)) || (0..=s.len()).any(|i| glob(rest, &s[i..]))
+    }
+    Some('*') => (0..=s.len()).take_while(|i| *i == 0 || s[i - 1] != '/').any(|i| glob(&p[1..], &s[i..])),
+    Some('?') => s.first().map(|c| *c != '/').unwrap_or(false) && glob(&p[1..], &s[1..]),
+    Some(c) => s.first() == Some(c) && glob(&p[1..], &s[1..]),
+  }
+}
+
+/// Whether `rel` matches `pattern`; patterns without a `/` are matched against the file name.
+pub fn path_matches(pattern: &str, rel: &str) -> bool {
+  let pattern = pattern.trim().trim_start_matches('/');
+  let target = if pattern.contains('/') { rel } else { rel.rsplit('/').next().unwrap_or(rel) };
+  glob(&pattern.chars().collect::<Vec<_>>(), &target.chars().collect::<Vec<_>>())
+}
+
+/// `query` with its tags normalized, its dates checked and blank entries dropped.
+pub fn normalize(mut query: SmartFolderQuery) -> Result<SmartFolderQuery, String> {
+  query.tags = query
+    .tags
+    .iter()
+    .filter(|t| !t.trim().is_empty())
+    .map(|t| crate::tags::normalize(t).ok_or_else(|| format!("not a valid tag: {}", t)))
+    .collect::<Result<_, _>>()?;
+  for list in [&mut query.paths, &mut query.exclude_paths, &mut query.kinds] {
+    list.retain(|p| !p.trim().is_empty());
+  }
+  for bound in [&query.modified_after, &query.modified_before].into_iter().flatten() {
+    parse_bound(bound)?;
+  }
+  Ok(query)
+}
+
+/// Vault files matching `query`, sorted. Cheap conditions go first; notes are only read when
+/// the query asks for kinds.
+pub fn evaluate(vault_path: &str, query: &SmartFolderQuery) -> Result<Vec<String>, String> {
+  let root = Path::new(vault_path);
+  if !root.is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let after = query.modified_after.as_deref().map(parse_bound).transpose()?;
+  let before = query.modified_before.as_deref().map(parse_bound).transpose()?;
+  let tags: BTreeMap<String, Vec<String>> = if query.tags.is_empty() { BTreeMap::new() } else { crate::tags::by_file(vault_path)? };
+
+  let mut out = Vec::new();
+  for (abs, rel) in crate::site::collect(root, vault_path) {
+    if !query.paths.is_empty() && !query.paths.iter().any(|p| path_matches(p, &rel)) {
+      continue;
+    }
+    if query.exclude_paths.iter().any(|p| path_matches(p, &rel)) {
+      continue;
+    }
+    if after.is_some() || before.is_some() {
+      let Some(modified) = fs::metadata(&abs).and_then(|m| m.modified()).ok().map(DateTime::<Utc>::from) else { continue };
+      if after.map(|a| modified < a).unwrap_or(false) || before.map(|b| modified >= b).unwrap_or(false) {
+        continue;
+      }
+    }
+    if !query.tags.is_empty() {
+      let have = tags.get(&rel).map(Vec::as_slice).unwrap_or_default();
+      if !query.tags.iter().all(|want| have.iter().any(|t| crate::tags::under(t, want))) {
+        continue;
+      }
+    }
+    if !query.kinds.is_empty() {
+      let kind = if rel.starts_with("resources/") {
+        "resource".to_string()
+      } else if is_markdown_path(&abs) {
+        let Ok(bytes) = fs::read(&abs) else { continue };
+        detect_kind(&crate::encoding::decode(&bytes).0)
+      } else {
+        "attachment".to_string()
+      };
+      if !query.kinds.iter().any(|k| k.trim().eq_ignore_ascii_case(&kind)) {
+        continue;
+      }
+    }
+    out.push(rel);
+  }
+  out.sort();
+  Ok(out)
+}
+
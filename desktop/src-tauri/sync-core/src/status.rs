@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::{now_iso, read_mapping, SupabaseAuth};
+use crate::policy::PolicyDecision;
+use crate::throttle::ThroughputReport;
+
+static STATUS: Lazy<Mutex<HashMap<String, VaultSyncStatus>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static SESSIONS: Lazy<Mutex<HashMap<String, SyncSession>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// Global pause: watchers keep running but their pushes and the pull pollers are skipped.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+/// Pinged on every status change; the tray thread is the only subscriber.
+static LISTENER: Lazy<Mutex<Option<mpsc::Sender<()>>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncState {
+  Idle,
+  Syncing,
+  Error,
+  Offline,
+  Paused,
+  ManualOnly,
+  /// The vault folder is gone, e.g. its drive was unplugged; sync resumes when it is back.
+  Unavailable,
+}
+
+impl SyncState {
+  pub fn label(self) -> &'static str {
+    match self {
+      SyncState::Idle => "Idle",
+      SyncState::Syncing => "Syncing…",
+      SyncState::Error => "Error",
+      SyncState::Offline => "Offline",
+      SyncState::Paused => "Paused",
+      SyncState::ManualOnly => "Manual pull only",
+      SyncState::Unavailable => "Vault unavailable",
+    }
+  }
+}
+
+/// How a watched vault's local changes are noticed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchMode {
+  /// Filesystem events.
+  Events,
+  /// Periodic rescans of the whole vault, when events can't be had.
+  Scan,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VaultSyncStatus {
+  pub vault_path: String,
+  pub project_folder_id: Option<String>,
+  pub state: SyncState,
+  pub watching: bool,
+  pub last_pull_at: Option<String>,
+  pub last_push_at: Option<String>,
+  pub last_error: Option<String>,
+  /// `None` when local changes aren't being watched.
+  #[serde(default)]
+  pub watch_mode: Option<WatchMode>,
+  /// Why watching is degraded (e.g. rescanning because the watcher kept failing).
+  #[serde(default)]
+  pub degraded: Option<String>,
+  pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncStatusReport {
+  pub paused: bool,
+  pub vaults: Vec<VaultSyncStatus>,
+  pub throughput: ThroughputReport,
+  pub policy: PolicyDecision,
+}
+
+/// Everything needed to run a sync for a vault outside a frontend command (tray "Sync now").
+#[derive(Clone)]
+pub struct SyncSession {
+  pub vault_path: String,
+  pub project_folder_id: String,
+  pub auth: SupabaseAuth,
+}
+
+pub fn subscribe() -> mpsc::Receiver<()> {
+  let (tx, rx) = mpsc::channel::<()>();
+  if let Ok(mut guard) = LISTENER.lock() {
+    *guard = Some(tx);
+  }
+  rx
+}
+
+pub fn notify_changed() {
+  if let Ok(guard) = LISTENER.lock() {
+    if let Some(tx) = guard.as_ref() {
+      let _ = tx.send(());
+    }
+  }
+}
+
+fn is_offline_error(err: &str) -> bool {
+  let e = err.to_ascii_lowercase();
+  e.contains("error sending request") || e.contains("dns error") || e.contains("connection refused") || e.contains("network is unreachable")
+}
+
+fn update(vault_path: &str, f: impl FnOnce(&mut VaultSyncStatus)) {
+  {
+    let Ok(mut guard) = STATUS.lock() else { return };
+    let st = guard.entry(vault_path.to_string()).or_insert_with(|| {
+      let mapping = read_mapping(vault_path).ok().flatten();
+      VaultSyncStatus {
+        vault_path: vault_path.to_string(),
+        project_folder_id: mapping.as_ref().map(|m| m.project_folder_id.clone()),
+        state: SyncState::Idle,
+        watching: false,
+        last_pull_at: mapping.map(|m| m.last_pull_at).filter(|s| !s.is_empty()),
+        last_push_at: None,
+        last_error: None,
+        watch_mode: None,
+        degraded: None,
+        updated_at: now_iso(),
+      }
+    });
+    f(st);
+    st.updated_at = now_iso();
+  }
+  notify_changed();
+}
+
+pub fn is_paused() -> bool {
+  PAUSED.load(Ordering::SeqCst)
+}
+
+pub fn set_paused(paused: bool) {
+  PAUSED.store(paused, Ordering::SeqCst);
+  notify_changed();
+}
+
+pub fn begin(vault_path: &str) {
+  update(vault_path, |st| st.state = SyncState::Syncing);
+}
+
+/// Records the outcome of a pull or push. `op` is `"pull"` or `"push"`.
+pub fn finish(vault_path: &str, op: &str, res: Result<(), &str>) {
+  update(vault_path, |st| match res {
+    Ok(()) => {
+      st.state = SyncState::Idle;
+      st.last_error = None;
+      if op == "pull" {
+        st.last_pull_at = Some(now_iso());
+      } else {
+        st.last_push_at = Some(now_iso());
+      }
+    }
+    Err(e) => {
+      st.state = if is_offline_error(e) { SyncState::Offline } else { SyncState::Error };
+      st.last_error = Some(e.to_string());
+    }
+  });
+}
+
+pub fn register_session(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth) {
+  if let Ok(mut guard) = SESSIONS.lock() {
+    guard.insert(
+      vault_path.to_string(),
+      SyncSession {
+        vault_path: vault_path.to_string(),
+        project_folder_id: project_folder_id.to_string(),
+        auth: auth.clone(),
+      },
+    );
+  }
+  update(vault_path, |st| {
+    st.project_folder_id = Some(project_folder_id.to_string());
+    st.watching = true;
+  });
+}
+
+/// A fresh sign-in (e.g. via an auth deep link) replaces the tokens tray-triggered syncs use.
+pub fn update_session_tokens(auth: &SupabaseAuth) {
+  if let Ok(mut guard) = SESSIONS.lock() {
+    for s in guard.values_mut().filter(|s| crate::auth::same_account(&s.auth, auth)) {
+      s.auth.access_token = auth.access_token.clone();
+      s.auth.refresh_token = auth.refresh_token.clone();
+    }
+  }
+}
+
+pub fn clear_sessions() {
+  let vaults: Vec<String> = SESSIONS.lock().map(|g| g.keys().cloned().collect()).unwrap_or_default();
+  for vp in vaults {
+    end_session(&vp);
+  }
+}
+
+/// The vault's background sync was stopped.
+pub fn end_session(vault_path: &str) {
+  if let Ok(mut guard) = SESSIONS.lock() {
+    guard.remove(vault_path);
+  }
+  update(vault_path, |st| {
+    st.watching = false;
+    st.watch_mode = None;
+    st.degraded = None;
+  });
+}
+
+pub fn set_watch_mode(vault_path: &str, mode: Option<WatchMode>, degraded: Option<String>) {
+  update(vault_path, |st| {
+    st.watch_mode = mode;
+    st.degraded = degraded;
+  });
+}
+
+pub fn set_unavailable(vault_path: &str, unavailable: bool) {
+  update(vault_path, |st| {
+    if unavailable {
+      st.state = SyncState::Unavailable;
+      st.last_error = Some("vault folder is unavailable".to_string());
+    } else if st.state == SyncState::Unavailable {
+      st.state = SyncState::Idle;
+      st.last_error = None;
+    }
+  });
+}
+
+pub fn sessions() -> Vec<SyncSession> {
+  SESSIONS.lock().map(|g| g.values().cloned().collect()).unwrap_or_default()
+}
+
+pub fn snapshot() -> Vec<VaultSyncStatus> {
+  let mut out: Vec<VaultSyncStatus> = STATUS.lock().map(|g| g.values().cloned().collect()).unwrap_or_default();
+  out.sort_by(|a, b| a.vault_path.cmp(&b.vault_path));
+  out
+}
+
+/// Worst state across vaults (error > unavailable > offline > syncing > idle), or paused / manual-only, and the
+/// most recent pull.
+pub fn aggregate() -> (SyncState, Option<String>) {
+  let all = snapshot();
+  let state = if is_paused() {
+    SyncState::Paused
+  } else if crate::policy::manual_only() {
+    SyncState::ManualOnly
+  } else {
+    [SyncState::Error, SyncState::Unavailable, SyncState::Offline, SyncState::Syncing]
+      .into_iter()
+      .find(|s| all.iter().any(|v| v.state == *s))
+      .unwrap_or(SyncState::Idle)
+  };
+  let last_pull = all.iter().filter_map(|v| v.last_pull_at.clone()).max();
+  (state, last_pull)
+}
+
@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use walkdir::DirEntry;
+
+use crate::config::{read_config, SymlinkPolicy};
+use crate::sync::{append_event, now_iso, to_rel_posix, SyncEvent};
+
+/// `vault_path\0rel` of links already logged, so a skipped link is reported once per session
+/// rather than on every push and pull.
+static REPORTED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+pub fn policy(vault_path: &str) -> SymlinkPolicy {
+  read_config(vault_path).map(|c| c.symlinks.policy).unwrap_or_default()
+}
+
+/// Whether vault walks should descend into symlinked folders (each link is still vetted by `admit`).
+pub fn follow_links(vault_path: &str) -> bool {
+  policy(vault_path) == SymlinkPolicy::FollowWithinVault
+}
+
+fn check_link(root: &Path, policy: SymlinkPolicy, link: &Path) -> Result<(), String> {
+  if policy == SymlinkPolicy::Ignore {
+    return Err("symlinks are ignored by this vault's symlink policy".to_string());
+  }
+  let target = fs::canonicalize(link).map_err(|_| "symlink target does not exist".to_string())?;
+  let root = fs::canonicalize(root).map_err(|e| e.to_string())?;
+  if target.starts_with(&root) {
+    Ok(())
+  } else {
+    Err(format!("symlink points outside the vault ({})", target.display()))
+  }
+}
+
+fn report(vault_path: &str, path: &Path, reason: &str) {
+  let rel = to_rel_posix(Path::new(vault_path), path).unwrap_or_else(|| path.display().to_string());
+  let fresh = REPORTED
+    .lock()
+    .map(|mut r| r.insert(format!("{}\0{}", vault_path, rel)))
+    .unwrap_or(true);
+  if fresh {
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "symlink_skipped".to_string(),
+        path: rel,
+        detail: format!("Skipped: {}.", reason),
+      },
+    );
+  }
+}
+
+/// Walk filter: rejects (and logs) symlinks the policy doesn't allow, pruning linked folders.
+pub fn admit(vault_path: &str, entry: &DirEntry) -> bool {
+  if !entry.path_is_symlink() {
+    return true;
+  }
+  match check_link(Path::new(vault_path), policy(vault_path), entry.path()) {
+    Ok(()) => true,
+    Err(reason) => {
+      report(vault_path, entry.path(), &reason);
+      false
+    }
+  }
+}
+
+/// Checks that writing `abs_path` wouldn't go through a disallowed symlink (the file itself or any
+/// folder above it inside the vault). Logs and returns the reason when it would.
+pub fn check_write(vault_path: &str, abs_path: &Path) -> Result<(), String> {
+  let root = Path::new(vault_path);
+  let Ok(rel) = abs_path.strip_prefix(root) else {
+    return Err(format!("{} is outside the vault", abs_path.display()));
+  };
+  let policy = policy(vault_path);
+  let mut cur = root.to_path_buf();
+  for c in rel.components() {
+    let Component::Normal(seg) = c else { continue };
+    cur.push(seg);
+    let Ok(meta) = fs::symlink_metadata(&cur) else { break };
+    if meta.file_type().is_symlink() {
+      if let Err(reason) = check_link(root, policy, &cur) {
+        report(vault_path, &cur, &reason);
+        return Err(format!("{}: {}", cur.display(), reason));
+      }
+    }
+  }
+  Ok(())
+}
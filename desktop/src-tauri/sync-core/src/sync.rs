@@ -0,0 +1,3500 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::Utc;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+const AUTH_SESSION_KEY: &str = "diregram.sync.auth.session.v1";
+
+/// Background pushes and polling stop while the user paused sync or the OS policy (metered
+/// network, battery saver) asks for manual-only mode.
+pub fn background_sync_suspended() -> bool {
+  crate::status::is_paused() || crate::policy::manual_only()
+}
+
+pub fn sync_key(vault_path: &str, project_folder_id: &str) -> String {
+  format!("{}|{}", vault_path, project_folder_id)
+}
+
+fn persist_auth_session(auth: &SupabaseAuth) -> Result<(), String> {
+  persist_auth_tokens(auth.profile.as_deref(), &auth.access_token, auth.refresh_token.as_deref().unwrap_or(""))
+}
+
+/// Stores the session in the keychain slot the frontend restores from on launch.
+pub fn persist_auth_tokens(profile: Option<&str>, access: &str, refresh: &str) -> Result<(), String> {
+  let refresh = refresh.trim();
+  if refresh.is_empty() {
+    return Err("missing refresh_token (cannot persist session)".to_string());
+  }
+  let access = access.trim();
+  if access.is_empty() {
+    return Err("missing access_token (cannot persist session)".to_string());
+  }
+
+  let payload = serde_json::json!({
+    "version": 1,
+    "accessToken": access,
+    "refreshToken": refresh,
+  });
+  crate::secrets::set(profile, AUTH_SESSION_KEY, &payload.to_string())
+}
+
+/// Access and refresh token last stored by `persist_auth_tokens`.
+pub fn load_auth_tokens(profile: Option<&str>) -> Result<Option<(String, String)>, String> {
+  let Some(text) = crate::secrets::get(profile, AUTH_SESSION_KEY)? else {
+    return Ok(None);
+  };
+  let v: serde_json::Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+  let access = v.get("accessToken").and_then(|t| t.as_str()).unwrap_or("");
+  let refresh = v.get("refreshToken").and_then(|t| t.as_str()).unwrap_or("");
+  if access.is_empty() || refresh.is_empty() {
+    return Ok(None);
+  }
+  Ok(Some((access.to_string(), refresh.to_string())))
+}
+
+/// Removes the stored session, so the app can't resume it at the next launch.
+pub fn forget_auth_tokens(profile: Option<&str>) -> Result<(), String> {
+  crate::secrets::remove(profile, AUTH_SESSION_KEY)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncMappingV1 {
+  pub version: u32,
+  pub vault_path: String,
+  pub project_folder_id: String,
+  pub created_at: String,
+  pub updated_at: String,
+  #[serde(default)]
+  pub last_pull_at: String,
+  #[serde(default)]
+  pub last_rag_export_at: String,
+  /// The last whole-project ingest started from this device (see `rag_queue`).
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub last_rag_ingest: Option<RagIngestRunV1>,
+  /// Relative folder path (posix-style) -> supabase folder UUID.
+  pub folders: HashMap<String, String>,
+  /// Relative file path (posix-style) -> remote mapping.
+  pub files: HashMap<String, FileMappingV1>,
+  /// Relative resource path (posix-style) -> remote mapping.
+  #[serde(default)]
+  pub resources: HashMap<String, ResourceMappingV1>,
+  /// Trash path (relative to `.diregram/trash/`) -> mapping the file had before a remote delete
+  /// archived it. Lets a restore re-use the original remote id.
+  #[serde(default)]
+  pub trashed: HashMap<String, FileMappingV1>,
+  /// The user forced syncing into a filesystem root or home folder (see `safety`).
+  #[serde(default)]
+  pub allow_unsafe_root: bool,
+  /// Remote deletions held back by the mass-delete guard until the user decides (see `delete_guard`).
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub pending_mass_delete: Option<crate::delete_guard::PendingMassDeleteV1>,
+  /// `head::fetch` as of the last pull that completed without errors.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub remote_head: Option<String>,
+  /// Relative path -> remote name, for files, resources and folders whose remote name isn't a
+  /// safe local one (see `names`). Pushes use it so the remote name stays as it was.
+  #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+  pub remote_names: HashMap<String, String>,
+  /// Relative path -> stored object of each synced attachment (see `attachments`).
+  #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+  pub attachments: HashMap<String, crate::attachments::AttachmentMappingV1>,
+  /// sha256 -> reference count of each attachment object in `.diregram/objects`.
+  #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+  pub objects: HashMap<String, crate::attachments::ObjectRefV1>,
+  /// The store `objects` were uploaded to; `None` is the project's Supabase Storage.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub attachment_store: Option<String>,
+  /// Relative path -> path in the remote tree, for pulled files kind routing put elsewhere (see
+  /// `routing`). Pushes use it so the file stays in its remote folder.
+  #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+  pub routed: HashMap<String, String>,
+  /// Owner of the project's root folder, which new folders, files and resources are created
+  /// for (see `sharing`).
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub project_owner_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileMappingV1 {
+  pub file_id: String,
+  pub folder_id: String,
+  pub kind: String,
+  pub local_hash: String,
+  pub remote_updated_at: String,
+  /// Bumped each time this device writes a new version of the file, in either direction.
+  #[serde(default)]
+  pub local_rev: u64,
+  /// Latest `files.rev` seen. The server bumps it on every content change, so unlike
+  /// `remote_updated_at` it can be compared across machines whose clocks disagree.
+  #[serde(default)]
+  pub remote_rev: i64,
+  /// `files.rev` the local file last matched; lags `remote_rev` while a conflict is unresolved.
+  #[serde(default)]
+  pub base_rev: i64,
+  /// Encoding of the local file when it isn't UTF-8. Remote always holds UTF-8; pulls write the
+  /// note back in this encoding.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub encoding: Option<String>,
+  /// Owner of the remote row, when known. On shared projects it may be another member.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub owner_id: Option<String>,
+  /// The server refused the last push of this file: this account may only view it.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub read_only: bool,
+  /// Blob key (sha256) of the text the server last held, exactly as sent or received, kept as the
+  /// base of the next delta push. Set only for notes that push through patches.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub base_hash: Option<String>,
+}
+
+impl FileMappingV1 {
+  /// A file that matches remote version `rev`. The rest starts out unset; callers fill in what
+  /// they know with `..FileMappingV1::synced(..)`.
+  pub fn synced(
+    file_id: String,
+    folder_id: String,
+    kind: String,
+    local_hash: String,
+    remote_updated_at: String,
+    rev: i64,
+  ) -> Self {
+    Self {
+      file_id,
+      folder_id,
+      kind,
+      local_hash,
+      remote_updated_at,
+      local_rev: 0,
+      remote_rev: rev,
+      base_rev: rev,
+      encoding: None,
+      owner_id: None,
+      read_only: false,
+      base_hash: None,
+    }
+  }
+
+  /// Version a push of this file must still find remotely. Mappings from before revs existed
+  /// (rev 0) fall back to the timestamp.
+  fn expected_version(&self) -> ExpectedVersion<'_> {
+    if self.remote_rev > 0 {
+      ExpectedVersion::Rev(self.remote_rev)
+    } else {
+      ExpectedVersion::UpdatedAt(&self.remote_updated_at)
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResourceMappingV1 {
+  pub resource_id: String,
+  pub local_hash: String,
+  pub remote_updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RagIngestRunV1 {
+  pub started_at: String,
+  pub finished_at: String,
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SupabaseAuth {
+  pub supabase_url: String,
+  pub supabase_anon_key: String,
+  pub access_token: String,
+  pub refresh_token: Option<String>,
+  pub owner_id: String,
+  /// Secure-storage profile the session belongs to; `None` is the default profile.
+  #[serde(default)]
+  pub profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SyncSummary {
+  pub folders_created: u32,
+  pub folders_reused: u32,
+  pub files_created: u32,
+  pub files_updated: u32,
+  pub files_deleted: u32,
+  pub files_skipped: u32,
+  pub resources_deleted: u32,
+  pub errors: Vec<String>,
+  /// Non-fatal notices, e.g. notes converted from a legacy encoding.
+  #[serde(default)]
+  pub warnings: Vec<String>,
+  /// Bytes not transferred thanks to content compression (pushed and pulled).
+  #[serde(default)]
+  pub bytes_saved: u64,
+  #[serde(default)]
+  pub attachments_uploaded: u32,
+  /// Attachments whose content was already stored, so nothing was uploaded for them.
+  #[serde(default)]
+  pub attachments_deduped: u32,
+}
+
+pub fn now_iso() -> String {
+  Utc::now().to_rfc3339()
+}
+
+pub fn diregram_dir(vault_path: &str) -> PathBuf {
+  Path::new(vault_path).join(".diregram")
+}
+
+pub fn mapping_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("sync.json")
+}
+
+pub fn events_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("events.jsonl")
+}
+
+pub fn trash_dir(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("trash")
+}
+
+pub fn archive_file_to_trash(vault_path: &str, rel_path: &str) -> Result<Option<PathBuf>, String> {
+  let src = crate::normalize::local_path(Path::new(vault_path), rel_path);
+  if !src.exists() {
+    return Ok(None);
+  }
+  let meta = fs::metadata(&src).map_err(|e| e.to_string())?;
+  if !meta.is_file() {
+    return Ok(None);
+  }
+  if let Some(cfg) = crate::shred::covering(vault_path, rel_path) {
+    crate::shred::shred(vault_path, rel_path, &cfg)?;
+    return Ok(None);
+  }
+
+  let ts = Utc::now().format("%Y-%m-%dT%H%M%SZ").to_string();
+  let dst = trash_dir(vault_path).join(&ts).join(rel_path);
+  if let Some(parent) = dst.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  fs::copy(&src, &dst).map_err(|e| e.to_string())?;
+  fs::remove_file(&src).map_err(|e| e.to_string())?;
+  Ok(Some(dst))
+}
+
+/// For callers that carry on either way; a failed archive leaves the file where it was.
+fn archive_or_log(vault_path: &str, rel_path: &str) {
+  if let Err(e) = archive_file_to_trash(vault_path, rel_path) {
+    tracing::warn!(vault = vault_path, path = rel_path, error = %e, "could not archive file to trash");
+  }
+}
+
+/// Archives notes whose remote file is gone and moves their mapping under `trashed`.
+pub fn archive_remote_deleted(
+  vault_path: &str,
+  mapping: &mut SyncMappingV1,
+  rels: Vec<String>,
+  summary: &mut SyncSummary,
+) {
+  for rel in rels {
+    let shredded = crate::shred::covering(vault_path, &rel).is_some();
+    let archived = archive_file_to_trash(vault_path, &rel);
+    if let Err(e) = &archived {
+      tracing::warn!(vault = vault_path, path = %rel, error = %e, "could not archive remotely deleted file");
+    }
+    let removed = mapping.files.remove(&rel);
+    if let (Ok(Some(dst)), Some(fm)) = (archived, removed) {
+      if let Some(trash_rel) = to_rel_posix(&trash_dir(vault_path), &dst) {
+        mapping.trashed.insert(trash_rel, fm);
+      }
+    }
+    summary.files_deleted += 1;
+    crate::open_files::note(
+      vault_path,
+      &rel,
+      crate::open_files::RemoteChange::Deleted,
+      if shredded {
+        "Deleted remotely; the local copy was securely wiped."
+      } else {
+        "Deleted remotely; the local copy was moved to the trash."
+      },
+    );
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "pull_delete".to_string(),
+        path: rel.clone(),
+        detail: if shredded {
+          "Remote file was deleted; securely wiped the local copy.".to_string()
+        } else {
+          "Remote file was deleted; archived local copy to .diregram/trash/".to_string()
+        },
+      },
+    );
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncEvent {
+  pub ts: String,
+  pub kind: String,
+  pub path: String,
+  pub detail: String,
+}
+
+/// Logs a sync event to the vault's event log and the app log.
+pub fn append_event(vault_path: &str, ev: &SyncEvent) -> Result<(), String> {
+  if ev.kind.contains("error") || matches!(ev.kind.as_str(), "conflict" | "corruption" | "panic") {
+    tracing::warn!(vault = vault_path, kind = %ev.kind, path = %ev.path, "{}", ev.detail);
+  } else {
+    tracing::info!(vault = vault_path, kind = %ev.kind, path = %ev.path, "{}", ev.detail);
+  }
+  let res = crate::events::append(vault_path, ev);
+  if let Err(e) = &res {
+    tracing::warn!(vault = vault_path, error = %e, "could not write to the event log");
+  }
+  res
+}
+
+pub fn read_mapping(vault_path: &str) -> Result<Option<SyncMappingV1>, String> {
+  let p = mapping_path(vault_path);
+  if !p.exists() {
+    return Ok(None);
+  }
+  let text = fs::read_to_string(&p).map_err(|e| e.to_string())?;
+  let mut m: SyncMappingV1 = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+  if crate::normalize::normalize_mapping(vault_path, &mut m) {
+    write_mapping(vault_path, &m)?;
+  }
+  Ok(Some(m))
+}
+
+pub fn write_mapping(vault_path: &str, mapping: &SyncMappingV1) -> Result<(), String> {
+  let dir = diregram_dir(vault_path);
+  fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  let p = mapping_path(vault_path);
+  let text = serde_json::to_string_pretty(mapping).map_err(|e| e.to_string())?;
+  // A crash mid-write must not leave a truncated mapping: every file would look unmapped.
+  write_atomic(&p, text).map_err(|e| e.to_string())?;
+  crate::activity::flush(vault_path);
+  Ok(())
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+  let mut hasher = Sha256::new();
+  hasher.update(bytes);
+  let out = hasher.finalize();
+  format!("{:x}", out)
+}
+
+/// Suffix of the temp files `write_atomic` renames into place.
+pub const ATOMIC_TMP_SUFFIX: &str = ".diregram-tmp";
+
+pub fn is_atomic_tmp(p: &Path) -> bool {
+  p.file_name()
+    .and_then(|n| n.to_str())
+    .map(|n| n.ends_with(ATOMIC_TMP_SUFFIX))
+    .unwrap_or(false)
+}
+
+/// Replaces `path` with `contents` via a synced temp file in the same folder and a rename, so a
+/// crash leaves either the old note or the new one, and editors never read a half-written file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+  let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("note");
+  let tmp = path.with_file_name(format!(".{}{}", name, ATOMIC_TMP_SUFFIX));
+  let res = (|| {
+    let mut f = fs::File::create(&tmp)?;
+    f.write_all(contents.as_ref())?;
+    f.sync_all()?;
+    drop(f);
+    fs::rename(&tmp, path)
+  })();
+  if res.is_err() {
+    let _ = fs::remove_file(&tmp);
+  }
+  res
+}
+
+/// `write_atomic` for content that came from the server (or other sync output): the watcher is
+/// told to ignore the resulting event instead of pushing the same bytes straight back.
+pub fn write_synced(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+  let contents = contents.as_ref();
+  // Noted before the rename: the watcher can report it before `write_atomic` returns. An echo only
+  // matches while the file holds these bytes, so a failed write leaves nothing to wrongly skip.
+  crate::echo::note(path, contents);
+  write_atomic(path, contents)
+}
+
+fn move_file_with_fallback(src: &Path, dst: &Path) -> Result<(), String> {
+  if src == dst {
+    return Ok(());
+  }
+  if let Some(parent) = dst.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  match fs::rename(src, dst) {
+    Ok(()) => Ok(()),
+    Err(_) => {
+      fs::copy(src, dst).map_err(|e| e.to_string())?;
+      fs::remove_file(src).map_err(|e| e.to_string())?;
+      Ok(())
+    }
+  }
+}
+
+pub fn to_rel_posix(root: &Path, p: &Path) -> Option<String> {
+  let rel = p.strip_prefix(root).ok()?;
+  let s = rel
+    .components()
+    .map(|c| c.as_os_str().to_string_lossy().to_string())
+    .collect::<Vec<_>>()
+    .join("/");
+  Some(crate::normalize::nfc(&s))
+}
+
+pub fn is_ignored_rel(rel: &str) -> bool {
+  rel == "resources" || rel.starts_with("resources/") || rel == "rag" || rel.starts_with("rag/") || is_git_rel(rel)
+}
+
+/// A git repository's own files, e.g. the one vault snapshots are committed to.
+pub fn is_git_rel(rel: &str) -> bool {
+  rel == ".git" || rel.starts_with(".git/")
+}
+
+pub fn is_markdown_path(path: &Path) -> bool {
+  let ext = match path.extension().and_then(|e| e.to_str()) {
+    Some(v) => v.trim().to_ascii_lowercase(),
+    None => return false,
+  };
+  ext == "md" || ext == "markdown"
+}
+
+pub fn is_extensionless_path(path: &Path) -> bool {
+  path.extension().is_none()
+}
+
+pub fn looks_like_text_utf8(bytes: &[u8]) -> bool {
+  if bytes.is_empty() {
+    return true;
+  }
+  if bytes.contains(&0u8) {
+    return false;
+  }
+  std::str::from_utf8(bytes).is_ok()
+}
+
+fn has_separator_outside_fences(markdown: &str) -> bool {
+  let mut fences = crate::chunk::Fences::default();
+  for line in markdown.lines() {
+    if fences.toggles(line) {
+      continue;
+    }
+    if !fences.inside() && line.trim() == "---" {
+      return true;
+    }
+  }
+  false
+}
+
+pub fn detect_kind(markdown: &str) -> String {
+  // If a nexus-doc header exists, honor its `kind` field.
+  if let Some(kind) = crate::nexusdoc::header_kind(markdown) {
+    return kind;
+  }
+
+  let lower = markdown.to_ascii_lowercase();
+
+  // Explicit structured payloads should override fallback.
+  if lower.contains("```gridjson") || lower.contains("```tablejson") {
+    return "grid".to_string();
+  }
+  if lower.contains("```visionjson") {
+    return "vision".to_string();
+  }
+  if lower.contains("```testjson") {
+    return "test".to_string();
+  }
+
+  // Heuristics for classic Diregram diagram markdown (no nexus-doc header).
+  let has_diag_fence = lower.contains("```expanded-states")
+    || lower.contains("```tag-store")
+    || lower.contains("```flowtab-process-references")
+    || lower.contains("```custom-connections")
+    || lower.contains("```layout-direction")
+    || lower.contains("```flow-branch-order")
+    || lower.contains("```expanded-grid-")
+    || lower.contains("```flowtab-swimlane-")
+    || lower.contains("```systemflow-");
+  let has_diag_markers = lower.contains("#flowtab#")
+    || lower.contains("#systemflow#")
+    || lower.contains("#flow#")
+    || lower.contains("#common#")
+    || lower.contains("<!-- rn:")
+    || lower.contains("<!-- expid:")
+    || lower.contains("<!-- expanded:")
+    || lower.contains("<!-- fid:")
+    || lower.contains("<!-- sfid:")
+    || lower.contains("<!-- hubnote:");
+  if has_diag_fence || (has_diag_markers && has_separator_outside_fences(markdown)) {
+    return "diagram".to_string();
+  }
+
+  // Default: note (portable).
+  "note".to_string()
+}
+
+fn supabase_headers(auth: &SupabaseAuth) -> Result<HeaderMap, String> {
+  let mut h = HeaderMap::new();
+  h.insert("apikey", HeaderValue::from_str(&auth.supabase_anon_key).map_err(|e| e.to_string())?);
+  h.insert(
+    "Authorization",
+    HeaderValue::from_str(&format!("Bearer {}", auth.access_token)).map_err(|e| e.to_string())?,
+  );
+  h.insert(
+    crate::device::DEVICE_HEADER,
+    HeaderValue::from_str(&crate::device::id()).map_err(|e| e.to_string())?,
+  );
+  Ok(h)
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshTokenResponse {
+  access_token: String,
+  refresh_token: Option<String>,
+}
+
+pub async fn refresh_access_token(client: &reqwest::Client, auth: &mut SupabaseAuth) -> Result<(), String> {
+  let refresh = auth
+    .refresh_token
+    .clone()
+    .ok_or_else(|| "missing refresh_token (cannot refresh)".to_string())?;
+
+  let url = format!("{}/auth/v1/token?grant_type=refresh_token", auth.supabase_url.trim_end_matches('/'));
+  let res = client
+    .post(url)
+    .header("apikey", auth.supabase_anon_key.clone())
+    .header("content-type", "application/json")
+    .json(&serde_json::json!({ "refresh_token": refresh }))
+    .send()
+    .await
+    .map_err(|e| e.to_string())?;
+
+  if !res.status().is_success() {
+    return Err(format!("token refresh failed: HTTP {}", res.status()));
+  }
+  let json: RefreshTokenResponse = res.json().await.map_err(|e| e.to_string())?;
+  auth.access_token = json.access_token;
+  if let Some(rt) = json.refresh_token {
+    auth.refresh_token = Some(rt);
+  }
+  if let Err(e) = persist_auth_session(auth) {
+    tracing::warn!(error = %e, "could not save refreshed session");
+  }
+  crate::auth::store_tokens(auth);
+  Ok(())
+}
+
+pub async fn send_with_refresh<T>(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  make_req: impl Fn() -> reqwest::RequestBuilder,
+  parse: impl Fn(reqwest::Response) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T, String>> + Send>>,
+) -> Result<T, String> {
+  let res = crate::throttle::send(make_req().headers(supabase_headers(auth)?))
+    .await
+    .map_err(|e| e.to_string())?;
+
+  if res.status() == reqwest::StatusCode::UNAUTHORIZED {
+    refresh_access_token(client, auth).await?;
+    crate::metrics::note_retry();
+    let res2 = crate::throttle::send(make_req().headers(supabase_headers(auth)?))
+      .await
+      .map_err(|e| e.to_string())?;
+    return parse(res2).await;
+  }
+
+  parse(res).await
+}
+
+pub fn rest_base(auth: &SupabaseAuth) -> String {
+  format!("{}/rest/v1", auth.supabase_url.trim_end_matches('/'))
+}
+
+#[derive(Debug, Deserialize)]
+struct FolderRow {
+  id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileRow {
+  id: String,
+  updated_at: Option<String>,
+  #[serde(default)]
+  rev: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResourceRow {
+  id: String,
+  updated_at: Option<String>,
+}
+
+async fn find_folder_id(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  parent_id: Option<&str>,
+  name: &str,
+) -> Result<Option<String>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/folders", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "id");
+    q.append_pair("name", &format!("eq.{}", name));
+    match parent_id {
+      Some(pid) => q.append_pair("parent_id", &format!("eq.{}", pid)),
+      None => q.append_pair("parent_id", "is.null"),
+    };
+    q.append_pair("limit", "1");
+  }
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("folder lookup failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FolderRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next().map(|r| r.id))
+      })
+    },
+  )
+  .await
+}
+
+async fn create_folder(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  owner_id: &str,
+  parent_id: Option<&str>,
+  name: &str,
+) -> Result<String, String> {
+  let url = format!("{}/folders", rest_base(auth));
+  let body = serde_json::json!({
+    "name": name,
+    "owner_id": owner_id,
+    "parent_id": parent_id
+  });
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.post(url.clone()).header("Prefer", "return=representation").json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("folder create failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FolderRow> = res.json().await.map_err(|e| e.to_string())?;
+        rows
+          .into_iter()
+          .next()
+          .map(|r| r.id)
+          .ok_or_else(|| "folder create: empty response".to_string())
+      })
+    },
+  )
+  .await
+}
+
+async fn ensure_folder_path(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  mapping: &mut SyncMappingV1,
+  summary: &mut SyncSummary,
+  rel_folder_path: &str,
+) -> Result<String, String> {
+  if rel_folder_path.trim().is_empty() {
+    return Ok(mapping.project_folder_id.clone());
+  }
+
+  let mut parent_id = mapping.project_folder_id.clone();
+  let mut current_rel = String::new();
+
+  for seg in rel_folder_path.split('/').filter(|s| !s.is_empty()) {
+    let next_rel = if current_rel.is_empty() {
+      seg.to_string()
+    } else {
+      format!("{}/{}", current_rel, seg)
+    };
+
+    if let Some(id) = mapping.folders.get(&next_rel) {
+      parent_id = id.clone();
+      current_rel = next_rel;
+      continue;
+    }
+
+    let name = crate::names::remote_name(mapping, &next_rel);
+    if let Some(found) = find_folder_id(client, auth, Some(&parent_id), &name).await? {
+      mapping.folders.insert(next_rel.clone(), found.clone());
+      summary.folders_reused += 1;
+      parent_id = found;
+      current_rel = next_rel;
+      continue;
+    }
+
+    let owner = crate::sharing::project_owner(client, auth, mapping).await;
+    let created = create_folder(client, auth, &owner, Some(&parent_id), &name).await?;
+    mapping.folders.insert(next_rel.clone(), created.clone());
+    summary.folders_created += 1;
+    parent_id = created;
+    current_rel = next_rel;
+  }
+
+  Ok(parent_id)
+}
+
+async fn find_file_id(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  folder_id: &str,
+  name: &str,
+) -> Result<Option<String>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/files", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "id");
+    q.append_pair("folder_id", &format!("eq.{}", folder_id));
+    q.append_pair("name", &format!("eq.{}", name));
+    q.append_pair("limit", "1");
+  }
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("file lookup failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FileRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next().map(|r| r.id))
+      })
+    },
+  )
+  .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn create_file(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  owner_id: &str,
+  folder_id: &str,
+  name: &str,
+  kind: &str,
+  content: &str,
+  updated_at: &str,
+) -> Result<FileRow, String> {
+  let body = serde_json::json!({
+    "name": name,
+    "folder_id": folder_id,
+    "owner_id": owner_id,
+    "kind": kind,
+    "content": content,
+    "content_sha256": sha256_hex(content.as_bytes()),
+    "updated_at": updated_at
+  });
+  insert_file_row(client, auth, body).await
+}
+
+async fn insert_file_row(client: &reqwest::Client, auth: &mut SupabaseAuth, body: serde_json::Value) -> Result<FileRow, String> {
+  let url = format!("{}/files", rest_base(auth));
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.post(url.clone()).header("Prefer", "return=representation").json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("file create failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FileRow> = res.json().await.map_err(|e| e.to_string())?;
+        rows
+          .into_iter()
+          .next()
+          .ok_or_else(|| "file create: empty response".to_string())
+      })
+    },
+  )
+  .await
+}
+
+/// What a conditional update expects the row to still be at.
+#[derive(Clone, Copy)]
+enum ExpectedVersion<'a> {
+  Any,
+  Rev(i64),
+  UpdatedAt(&'a str),
+}
+
+impl ExpectedVersion<'_> {
+  fn matches(&self, row: &RemoteFileRow) -> bool {
+    match *self {
+      ExpectedVersion::Any => true,
+      ExpectedVersion::Rev(rev) => row.rev == Some(rev),
+      ExpectedVersion::UpdatedAt(ts) => ts.is_empty() || row.updated_at.as_deref() == Some(ts),
+    }
+  }
+}
+
+/// Whether an update was refused by row-level security rather than lost to a newer version:
+/// either the server said so, or it wrote nothing although the row is still at `expected`.
+async fn update_denied(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  file_id: &str,
+  expected: ExpectedVersion<'_>,
+  res: &Result<Option<FileRow>, String>,
+) -> bool {
+  match res {
+    Ok(Some(_)) => false,
+    Err(e) => crate::sharing::is_denied(e),
+    Ok(None) => matches!(refetch_remote_file(client, auth, file_id).await, Ok(Some(row)) if expected.matches(&row)),
+  }
+}
+
+/// PATCHes a file row. Unless `expected` is `Any`, the write only applies if the row is still at
+/// that version; `Ok(None)` means someone else changed it first and nothing was written.
+async fn update_file(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  file_id: &str,
+  kind: &str,
+  content: &str,
+  updated_at: &str,
+  expected: ExpectedVersion<'_>,
+) -> Result<Option<FileRow>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/files", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("id", &format!("eq.{}", file_id));
+    match expected {
+      ExpectedVersion::Rev(rev) => {
+        q.append_pair("rev", &format!("eq.{}", rev));
+      }
+      ExpectedVersion::UpdatedAt(ts) if !ts.is_empty() => {
+        q.append_pair("updated_at", &format!("eq.{}", ts));
+      }
+      _ => {}
+    }
+  }
+
+  let body = serde_json::json!({
+    "kind": kind,
+    "content": content,
+    "content_sha256": sha256_hex(content.as_bytes()),
+    "updated_at": updated_at
+  });
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.patch(url.clone()).header("Prefer", "return=representation").json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("file update failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FileRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next())
+      })
+    },
+  )
+  .await
+}
+
+/// Conflict copies are named `<stem> (conflict from Diregram <ts>).<ext>`.
+pub const CONFLICT_MARKER: &str = " (conflict from Diregram ";
+pub const CONFLICT_TS_FORMAT: &str = "%Y-%m-%dT%H%M%SZ";
+
+/// Sibling path the remote side of a conflict is written to.
+pub fn conflict_copy_path(abs_path: &Path, fallback_stem: &str) -> PathBuf {
+  let ts = Utc::now().format(CONFLICT_TS_FORMAT).to_string();
+  let stem = abs_path.file_stem().and_then(|s| s.to_str()).unwrap_or(fallback_stem);
+  let ext = abs_path.extension().and_then(|e| e.to_str()).unwrap_or("md");
+  abs_path.with_file_name(format!("{stem}{CONFLICT_MARKER}{ts}).{ext}"))
+}
+
+/// Handles a version-checked update that lost the race: the local file stays, the current remote
+/// version is written beside it (as a pull conflict would), and the remote row is returned so the
+/// mapping can adopt its version. The next push of this file then overwrites remote knowingly.
+async fn save_remote_conflict(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &str,
+  abs_path: &Path,
+  rel_path: &str,
+  file_id: &str,
+) -> Result<Option<RemoteFileRow>, String> {
+  let Some(row) = refetch_remote_file(client, auth, file_id).await? else {
+    return Ok(None);
+  };
+  let (remote_content, _) = crate::codec::decode(project_folder_id, row.content.as_deref().unwrap_or(""))?;
+  let conflict_path = conflict_copy_path(abs_path, "conflict");
+  write_synced(&conflict_path, &remote_content).map_err(|e| e.to_string())?;
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "conflict".to_string(),
+      path: rel_path.to_string(),
+      detail: format!("Remote changed before local edits were pushed. Wrote {}", conflict_path.display()),
+    },
+  );
+  crate::activity::note_conflict(vault_path, rel_path, "Remote changed before local edits were pushed.");
+  crate::open_files::note(
+    vault_path,
+    rel_path,
+    crate::open_files::RemoteChange::Conflict,
+    "Remote changed before local edits were pushed; its version was saved alongside.",
+  );
+  crate::notify::notify(
+    vault_path,
+    crate::notify::NotifyKind::Conflict,
+    "Sync conflict",
+    &format!("{} changed both here and in Diregram. The remote version was saved alongside it.", rel_path),
+    Some(rel_path),
+  );
+  Ok(Some(row))
+}
+
+/// Pushes only the edits since the last-synced base. `None` means a full upload is needed: no base
+/// was kept, the patch isn't much smaller, or the server no longer holds that base (changed
+/// remotely, or stored compressed), in which case the full upload's version check takes over.
+async fn push_patch(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  prev: &FileMappingV1,
+  kind: &str,
+  plain: &str,
+  updated_at: &str,
+) -> Option<(FileRow, u64)> {
+  let base = crate::delta::base(vault_path, prev.base_hash.as_deref()?)?;
+  let edits = crate::delta::edits(&base, plain);
+  let payload = serde_json::to_string(&edits).ok()?;
+  if payload.len() * 2 > plain.len() {
+    return None;
+  }
+  let url = format!("{}/rpc/apply_file_patch", rest_base(auth));
+  let body = serde_json::json!({
+    "p_file_id": prev.file_id,
+    "p_base_sha256": sha256_hex(base.as_bytes()),
+    "p_edits": edits,
+    "p_new_sha256": sha256_hex(plain.as_bytes()),
+    "p_kind": kind,
+    "p_updated_at": updated_at
+  });
+  let row = send_with_refresh(
+    client,
+    auth,
+    || client.post(url.clone()).json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("file patch failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FileRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next())
+      })
+    },
+  )
+  .await
+  .ok()??;
+  Some((row, plain.len().saturating_sub(payload.len()) as u64))
+}
+
+/// A merged collab note: the text both sides now hold, and the remote row version after any push.
+struct CollabMerge {
+  text: String,
+  remote_updated_at: Option<String>,
+  remote_rev: Option<i64>,
+}
+
+/// Merges a `kind: collab` note through its change log, then stores the merged text as the row's
+/// snapshot if it differs from `remote_text`. Never produces a conflict file.
+async fn merge_collab_file(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &str,
+  remote: &RemoteFileRow,
+  remote_text: &str,
+  local_edit: Option<&str>,
+) -> Result<CollabMerge, String> {
+  // The DB clears the checksum whenever something other than this app (the web editor) rewrites
+  // `content`, which is exactly when the snapshot holds edits the log doesn't.
+  let remote_external = remote.content_sha256.as_deref().is_none_or(str::is_empty);
+  let input = crate::crdt::MergeInput {
+    file_id: &remote.id,
+    remote_text,
+    remote_external,
+    local_edit,
+  };
+  let text = crate::crdt::merge(client, auth, vault_path, project_folder_id, input).await?;
+  let mut merged = CollabMerge {
+    text,
+    remote_updated_at: remote.updated_at.clone(),
+    remote_rev: remote.rev,
+  };
+  if merged.text != remote_text {
+    let encoded = crate::codec::encode(vault_path, project_folder_id, &merged.text)?;
+    let kind = detect_kind(&merged.text);
+    let row = update_file(client, auth, &remote.id, &kind, &encoded.content, &now_iso(), ExpectedVersion::Any)
+      .await?
+      .ok_or_else(|| "file update: empty response".to_string())?;
+    merged.remote_updated_at = row.updated_at;
+    merged.remote_rev = row.rev;
+  }
+  Ok(merged)
+}
+
+async fn delete_file(client: &reqwest::Client, auth: &mut SupabaseAuth, file_id: &str) -> Result<(), String> {
+  let mut url = reqwest::Url::parse(&format!("{}/files", rest_base(auth))).map_err(|e| e.to_string())?;
+  url
+    .query_pairs_mut()
+    .append_pair("id", &format!("eq.{}", file_id));
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.delete(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("file delete failed: HTTP {}", res.status()));
+        }
+        Ok(())
+      })
+    },
+  )
+  .await
+}
+
+async fn find_project_resource_id(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+  name: &str,
+) -> Result<Option<String>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/project_resources", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "id");
+    q.append_pair("project_folder_id", &format!("eq.{}", project_folder_id));
+    q.append_pair("name", &format!("eq.{}", name));
+    q.append_pair("limit", "1");
+  }
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("project resource lookup failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<RemoteIdRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next().map(|r| r.id))
+      })
+    },
+  )
+  .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn create_project_resource(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  owner_id: &str,
+  project_folder_id: &str,
+  name: &str,
+  markdown: &str,
+  source: Option<&serde_json::Value>,
+  updated_at: &str,
+) -> Result<ResourceRow, String> {
+  let url = format!("{}/project_resources", rest_base(auth));
+  let body = serde_json::json!({
+    "owner_id": owner_id,
+    "project_folder_id": project_folder_id,
+    "name": name,
+    "kind": "markdown",
+    "markdown": markdown,
+    "source": source.cloned().unwrap_or(serde_json::Value::Null),
+    "updated_at": updated_at,
+  });
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.post(url.clone()).header("Prefer", "return=representation").json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("project resource create failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<ResourceRow> = res.json().await.map_err(|e| e.to_string())?;
+        rows
+          .into_iter()
+          .next()
+          .ok_or_else(|| "project resource create: empty response".to_string())
+      })
+    },
+  )
+  .await
+}
+
+async fn update_project_resource(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  resource_id: &str,
+  name: &str,
+  markdown: &str,
+  source: Option<&serde_json::Value>,
+  updated_at: &str,
+) -> Result<ResourceRow, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/project_resources", rest_base(auth))).map_err(|e| e.to_string())?;
+  url
+    .query_pairs_mut()
+    .append_pair("id", &format!("eq.{}", resource_id));
+
+  let body = serde_json::json!({
+    "name": name,
+    "kind": "markdown",
+    "markdown": markdown,
+    "source": source.cloned().unwrap_or(serde_json::Value::Null),
+    "updated_at": updated_at,
+  });
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.patch(url.clone()).header("Prefer", "return=representation").json(&body),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("project resource update failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<ResourceRow> = res.json().await.map_err(|e| e.to_string())?;
+        rows
+          .into_iter()
+          .next()
+          .ok_or_else(|| "project resource update: empty response".to_string())
+      })
+    },
+  )
+  .await
+}
+
+async fn delete_project_resource(client: &reqwest::Client, auth: &mut SupabaseAuth, resource_id: &str) -> Result<(), String> {
+  let mut url = reqwest::Url::parse(&format!("{}/project_resources", rest_base(auth))).map_err(|e| e.to_string())?;
+  url
+    .query_pairs_mut()
+    .append_pair("id", &format!("eq.{}", resource_id));
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.delete(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("project resource delete failed: HTTP {}", res.status()));
+        }
+        Ok(())
+      })
+    },
+  )
+  .await
+}
+
+/// Links `vault_path` to a project, creating `.diregram/sync.json` unless it already exists. New
+/// vaults go through `safety::check_new_vault` first.
+pub fn init_mapping(vault_path: &str, project_folder_id: &str, allow_existing: bool, force: bool) -> Result<SyncMappingV1, String> {
+  let (vault_path, project_folder_id) = (vault_path.to_string(), project_folder_id.to_string());
+  if vault_path.trim().is_empty() {
+    return Err("vault_path is required".to_string());
+  }
+  if project_folder_id.trim().is_empty() {
+    return Err("project_folder_id is required".to_string());
+  }
+
+  if let Some(existing) = read_mapping(&vault_path)? {
+    if existing.project_folder_id != project_folder_id {
+      return Err("This vault is already linked to a different Diregram project. Relink it to switch projects.".to_string());
+    }
+    return Ok(existing);
+  }
+  crate::safety::check_new_vault(&vault_path, allow_existing, force)?;
+
+  let now = now_iso();
+  let mut folders = HashMap::new();
+  folders.insert("".to_string(), project_folder_id.clone());
+  let mapping = SyncMappingV1 {
+    version: 1,
+    vault_path: vault_path.clone(),
+    project_folder_id,
+    created_at: now.clone(),
+    updated_at: now,
+    last_pull_at: String::new(),
+    last_rag_export_at: String::new(),
+    last_rag_ingest: None,
+    trashed: HashMap::new(),
+    folders,
+    files: HashMap::new(),
+    resources: HashMap::new(),
+    allow_unsafe_root: force && crate::safety::dangerous_root(&vault_path).is_some(),
+    pending_mass_delete: None,
+    remote_head: None,
+    remote_names: HashMap::new(),
+    attachments: HashMap::new(),
+    objects: HashMap::new(),
+    attachment_store: None,
+    routed: HashMap::new(),
+    project_owner_id: None,
+  };
+
+  write_mapping(&vault_path, &mapping)?;
+  Ok(mapping)
+}
+
+#[tracing::instrument(name = "push", skip_all, fields(vault = vault_path), err)]
+async fn sync_push_once_internal(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth) -> Result<SyncSummary, String> {
+  #[derive(Clone)]
+  struct LocalResourceInput {
+    name: String,
+    markdown: String,
+    local_hash: String,
+    source: Option<serde_json::Value>,
+  }
+
+  let root = Path::new(vault_path);
+  if !root.exists() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let _mapping = crate::lock::mapping(vault_path).await;
+
+  let mut auth = auth.clone();
+  let mut mapping = match read_mapping(vault_path)? {
+    Some(m) => m,
+    None => init_mapping(vault_path, project_folder_id, true, false)?,
+  };
+  if mapping.project_folder_id != project_folder_id {
+    return Err("mapping project_folder_id mismatch".to_string());
+  }
+
+  let client = reqwest::Client::new();
+  crate::clock::measure(&client, &auth, vault_path).await;
+  crate::device::register(&client, &mut auth, vault_path).await;
+  let mut summary = SyncSummary::default();
+  let updated_at = crate::clock::server_now_iso(&auth);
+  let mut local_files: HashSet<String> = HashSet::new();
+  let mut local_resources: HashMap<String, LocalResourceInput> = HashMap::new();
+  let tempfiles = crate::tempfiles::TempFileFilter::load(vault_path);
+  let scope = crate::vaults::SyncScope::load(vault_path);
+  // Encoding of each note pushed this run, recorded on its mapping once the walk is done.
+  let mut encodings: HashMap<String, Option<&'static str>> = HashMap::new();
+  let rewrite_links = crate::links::enabled(vault_path);
+  let attachment_filter = crate::attachments::AttachmentFilter::load(vault_path);
+  let mut local_attachments: HashMap<String, PathBuf> = HashMap::new();
+  let routes = crate::routing::Routes::load(vault_path);
+  let direction = crate::direction::Direction::load(vault_path);
+
+  // Ensure root mapping exists.
+  mapping.folders.insert("".to_string(), project_folder_id.to_string());
+
+  for entry in WalkDir::new(root)
+    .follow_links(crate::symlinks::follow_links(vault_path))
+    .into_iter()
+    .filter_entry(|e| crate::symlinks::admit(vault_path, e))
+    .filter_map(Result::ok)
+  {
+    let p = entry.path();
+    if p == root {
+      continue;
+    }
+
+    // Ignore internal folder
+    if p.components().any(|c| c.as_os_str() == ".diregram") {
+      continue;
+    }
+
+    let rel = match to_rel_posix(root, p) {
+      Some(r) => r,
+      None => continue,
+    };
+    if is_ignored_rel(&rel) {
+      continue;
+    }
+
+    if entry.file_type().is_dir() {
+      if scope.admits_dir(&rel) && !routes.owns_dir(&rel) && direction.pushes(&rel) {
+        let _ = ensure_folder_path(&client, &mut auth, &mut mapping, &mut summary, &rel).await?;
+      }
+      continue;
+    }
+    if !scope.admits(&rel) {
+      continue;
+    }
+    // Already-synced files stay synced, so a new pattern can't turn into a remote delete.
+    if tempfiles.matches(p) && !mapping.files.contains_key(&rel) {
+      continue;
+    }
+
+    if attachment_filter.matches(p) && !mapping.files.contains_key(&rel) {
+      local_attachments.insert(rel.clone(), p.to_path_buf());
+      continue;
+    }
+
+    let is_mapped_file = mapping.files.contains_key(&rel);
+    let is_markdown = is_markdown_path(p);
+    let is_extensionless = is_extensionless_path(p);
+    if !is_markdown && !is_mapped_file && !is_extensionless {
+      continue;
+    }
+    let bytes = fs::read(p).map_err(|e| e.to_string())?;
+    if !is_markdown && !is_mapped_file && !looks_like_text_utf8(&bytes) {
+      continue;
+    }
+    local_files.insert(rel.clone());
+    let local_hash = sha256_hex(&bytes);
+    if !direction.pushes(&rel) {
+      if mapping.files.get(&rel).map(|prev| prev.local_hash != local_hash).unwrap_or(true) {
+        crate::direction::hold(vault_path, &rel, &local_hash, &mut summary);
+      }
+      continue;
+    }
+    let (content, encoding) = crate::encoding::decode(&bytes);
+    let kind = detect_kind(&content);
+
+    // Determine remote folder id.
+    let remote_rel = routes.remote_rel(&mapping, &rel);
+    crate::routing::remember(&mut mapping, &rel, &remote_rel);
+    let parent_rel = Path::new(&remote_rel)
+      .parent()
+      .and_then(|p| p.to_str())
+      .unwrap_or("")
+      .to_string();
+    let parent_rel = if parent_rel == "." { "".to_string() } else { parent_rel };
+    let folder_id = ensure_folder_path(&client, &mut auth, &mut mapping, &mut summary, &parent_rel).await?;
+
+    if mapping.files.get(&rel).map(|prev| prev.local_hash == local_hash).unwrap_or(false) {
+      summary.files_skipped += 1;
+      continue;
+    }
+    if let Err(e) = crate::nexusdoc::validate(&content) {
+      // Left unsynced (and its remote copy intact) until the block is fixed.
+      summary.errors.push(format!("Not pushing {}: {}", rel, e));
+      let _ = append_event(
+        vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "invalid_doc".to_string(),
+          path: rel.clone(),
+          detail: e,
+        },
+      );
+      continue;
+    }
+    if let Some(enc) = encoding {
+      summary.warnings.push(format!("{} is encoded as {}; converted to UTF-8 for upload.", rel, enc));
+    }
+    encodings.insert(rel.clone(), encoding);
+    if kind == crate::crdt::COLLAB_KIND {
+      if let Some(prev) = mapping.files.get(&rel).cloned() {
+        let Some(remote) = refetch_remote_file(&client, &mut auth, &prev.file_id).await? else {
+          // Deleted remotely; forget the mapping so the next push recreates it.
+          mapping.files.remove(&rel);
+          continue;
+        };
+        let (remote_text, _) = crate::codec::decode(project_folder_id, remote.content.as_deref().unwrap_or(""))?;
+        let merged =
+          merge_collab_file(&client, &mut auth, vault_path, project_folder_id, &remote, &remote_text, Some(&content)).await?;
+        if merged.text != content {
+          write_synced(p, &merged.text).map_err(|e| e.to_string())?;
+        }
+        crate::revisions::record(vault_path, &rel, merged.text.as_bytes());
+        crate::activity::note_push(vault_path, &rel);
+        // Collab notes merge through the change log, never through patches.
+        crate::delta::forget_base(vault_path, prev.base_hash.as_deref());
+        let rev = merged.remote_rev.unwrap_or(prev.remote_rev);
+        mapping.files.insert(
+          rel.clone(),
+          FileMappingV1 {
+            local_rev: prev.local_rev + 1,
+            owner_id: prev.owner_id.clone(),
+            ..FileMappingV1::synced(
+              prev.file_id.clone(),
+              folder_id.clone(),
+              kind,
+              sha256_hex(merged.text.as_bytes()),
+              merged.remote_updated_at.unwrap_or_else(|| updated_at.clone()),
+              rev,
+            )
+          },
+        );
+        summary.files_updated += 1;
+        continue;
+      }
+    }
+
+    // Revisions keep the note as it is on disk, with local links, not the form sent to the server.
+    let local = content;
+    let plain = if rewrite_links && kind != crate::crdt::COLLAB_KIND {
+      crate::links::to_remote(&local, &rel, &mapping.files)
+    } else {
+      local.clone()
+    };
+    let delta = crate::delta::eligible(vault_path, plain.len());
+    let encoded = if delta {
+      crate::codec::Encoded {
+        content: plain.clone(),
+        bytes_saved: 0,
+      }
+    } else {
+      crate::codec::encode(vault_path, project_folder_id, &plain)?
+    };
+    summary.bytes_saved += encoded.bytes_saved;
+    let content = encoded.content;
+
+    if let Some(prev) = mapping.files.get(&rel).cloned() {
+      let patched = if delta {
+        push_patch(&client, &mut auth, vault_path, &prev, &kind, &plain, &updated_at).await
+      } else {
+        None
+      };
+      let row = if let Some((row, saved)) = patched {
+        summary.bytes_saved += saved;
+        row
+      } else {
+        // Remote edits to files that aren't pulled are never seen, so they are overwritten.
+        let expected = if direction.pulls(&rel) {
+          prev.expected_version()
+        } else {
+          ExpectedVersion::Any
+        };
+        let updated = update_file(&client, &mut auth, &prev.file_id, &kind, &content, &updated_at, expected).await;
+        if update_denied(&client, &mut auth, &prev.file_id, expected, &updated).await {
+          crate::sharing::mark_read_only(vault_path, &mut mapping, &rel, &mut summary);
+          continue;
+        }
+        let Some(row) = updated? else {
+          match save_remote_conflict(&client, &mut auth, vault_path, project_folder_id, p, &rel, &prev.file_id).await {
+            Ok(Some(remote)) => {
+              let seen = FileMappingV1 {
+                remote_updated_at: remote.updated_at.unwrap_or_else(|| prev.remote_updated_at.clone()),
+                remote_rev: remote.rev.unwrap_or(prev.remote_rev),
+                ..prev
+              };
+              mapping.files.insert(rel.clone(), seen);
+            }
+            // Deleted remotely; forget the mapping so the next push recreates it.
+            Ok(None) => {
+              mapping.files.remove(&rel);
+            }
+            Err(e) => summary.errors.push(format!("Conflict on {}: {}", rel, e)),
+          }
+          continue;
+        };
+        row
+      };
+      let base_hash = crate::delta::remember_base(vault_path, prev.base_hash.as_deref(), &plain);
+      crate::revisions::record(vault_path, &rel, local.as_bytes());
+      crate::activity::note_push(vault_path, &rel);
+      let rev = row.rev.unwrap_or(0);
+      mapping.files.insert(
+        rel.clone(),
+        FileMappingV1 {
+          local_rev: prev.local_rev + 1,
+          owner_id: prev.owner_id.clone(),
+          base_hash,
+          ..FileMappingV1::synced(
+            prev.file_id.clone(),
+            folder_id.clone(),
+            kind,
+            local_hash,
+            row.updated_at.unwrap_or_else(|| updated_at.clone()),
+            rev,
+          )
+        },
+      );
+      summary.files_updated += 1;
+      continue;
+    }
+
+    // Try reuse an existing remote row with same name in the same folder.
+    let name = crate::names::remote_name(&mapping, &rel);
+    let name = name.as_str();
+    let file_id = match find_file_id(&client, &mut auth, &folder_id, name).await? {
+      Some(id) => id,
+      None => {
+        let owner = crate::sharing::project_owner(&client, &mut auth, &mut mapping).await;
+        let row = match create_file(&client, &mut auth, &owner, &folder_id, name, &kind, &content, &updated_at).await {
+          Ok(row) => row,
+          // Viewers can't add files to a shared project; the new note stays local.
+          Err(e) if crate::sharing::is_denied(&e) => {
+            summary
+              .warnings
+              .push(format!("{} was not pushed: this shared project is read-only for you.", rel));
+            continue;
+          }
+          Err(e) => return Err(e),
+        };
+        summary.files_created += 1;
+        let base_hash = crate::delta::remember_base(vault_path, None, &plain);
+        crate::revisions::record(vault_path, &rel, local.as_bytes());
+        crate::activity::note_push(vault_path, &rel);
+        let rev = row.rev.unwrap_or(0);
+        mapping.files.insert(
+          rel.clone(),
+          FileMappingV1 {
+            local_rev: 1,
+            owner_id: Some(owner),
+            base_hash,
+            ..FileMappingV1::synced(
+              row.id.clone(),
+              folder_id.clone(),
+              kind,
+              local_hash,
+              row.updated_at.unwrap_or_else(|| updated_at.clone()),
+              rev,
+            )
+          },
+        );
+        continue;
+      }
+    };
+
+    let row = update_file(&client, &mut auth, &file_id, &kind, &content, &updated_at, ExpectedVersion::Any)
+      .await?
+      .ok_or_else(|| "file update: empty response".to_string())?;
+    summary.files_updated += 1;
+    let base_hash = crate::delta::remember_base(vault_path, None, &plain);
+    crate::revisions::record(vault_path, &rel, local.as_bytes());
+    crate::activity::note_push(vault_path, &rel);
+    let rev = row.rev.unwrap_or(0);
+    mapping.files.insert(
+      rel.clone(),
+      FileMappingV1 {
+        local_rev: 1,
+        base_hash,
+        ..FileMappingV1::synced(
+          file_id.clone(),
+          folder_id.clone(),
+          kind,
+          local_hash,
+          row.updated_at.unwrap_or_else(|| updated_at.clone()),
+          rev,
+        )
+      },
+    );
+  }
+  for (rel, encoding) in encodings {
+    if let Some(m) = mapping.files.get_mut(&rel) {
+      m.encoding = encoding.map(str::to_string);
+    }
+  }
+
+  // Scan local additional resources (`resources/**/*.md`) and sync into `project_resources`.
+  let resources_root = root.join("resources");
+  if resources_root.exists() {
+    for entry in WalkDir::new(&resources_root)
+      .follow_links(crate::symlinks::follow_links(vault_path))
+      .into_iter()
+      .filter_entry(|e| crate::symlinks::admit(vault_path, e))
+      .filter_map(Result::ok)
+    {
+      let p = entry.path();
+      if entry.file_type().is_dir() {
+        continue;
+      }
+      let rel = match to_rel_posix(root, p) {
+        Some(r) => r,
+        None => continue,
+      };
+      let is_mapped_resource = mapping.resources.contains_key(&rel);
+      if tempfiles.matches(p) && !is_mapped_resource {
+        continue;
+      }
+      if attachment_filter.matches(p) && !is_mapped_resource {
+        local_attachments.insert(rel.clone(), p.to_path_buf());
+        continue;
+      }
+      let is_markdown = is_markdown_path(p);
+      let is_extensionless = is_extensionless_path(p);
+      if !is_markdown && !is_mapped_resource && !is_extensionless {
+        continue;
+      }
+      let bytes = fs::read(p).map_err(|e| e.to_string())?;
+      if !is_markdown && !is_mapped_resource && !looks_like_text_utf8(&bytes) {
+        continue;
+      }
+      let (markdown, _) = crate::encoding::decode(&bytes);
+      let local_hash = sha256_hex(&bytes);
+      let name = crate::names::remote_name(&mapping, &rel);
+      let source = if rel.starts_with("resources/docling/") {
+        Some(serde_json::json!({
+          "type": "docling",
+          "importedFrom": "filesystem"
+        }))
+      } else {
+        None
+      };
+      local_resources.insert(
+        rel.clone(),
+        LocalResourceInput {
+          name,
+          markdown,
+          local_hash,
+          source,
+        },
+      );
+    }
+  }
+
+  // Upsert local resources to remote.
+  for (rel, lr) in &local_resources {
+    if !direction.pushes(rel) {
+      if mapping.resources.get(rel).map(|prev| prev.local_hash != lr.local_hash).unwrap_or(true) {
+        crate::direction::hold(vault_path, rel, &lr.local_hash, &mut summary);
+      }
+      continue;
+    }
+    if let Some(prev) = mapping.resources.get(rel) {
+      if prev.local_hash == lr.local_hash {
+        continue;
+      }
+      let row = update_project_resource(
+        &client,
+        &mut auth,
+        &prev.resource_id,
+        &lr.name,
+        &lr.markdown,
+        lr.source.as_ref(),
+        &updated_at,
+      )
+      .await?;
+      mapping.resources.insert(
+        rel.clone(),
+        ResourceMappingV1 {
+          resource_id: prev.resource_id.clone(),
+          local_hash: lr.local_hash.clone(),
+          remote_updated_at: row.updated_at.unwrap_or_else(|| updated_at.clone()),
+        },
+      );
+      continue;
+    }
+
+    let resource_id = match find_project_resource_id(&client, &mut auth, project_folder_id, &lr.name).await? {
+      Some(existing_id) => {
+        let row = update_project_resource(
+          &client,
+          &mut auth,
+          &existing_id,
+          &lr.name,
+          &lr.markdown,
+          lr.source.as_ref(),
+          &updated_at,
+        )
+        .await?;
+        mapping.resources.insert(
+          rel.clone(),
+          ResourceMappingV1 {
+            resource_id: row.id.clone(),
+            local_hash: lr.local_hash.clone(),
+            remote_updated_at: row.updated_at.unwrap_or_else(|| updated_at.clone()),
+          },
+        );
+        row.id
+      }
+      None => {
+        let owner = crate::sharing::project_owner(&client, &mut auth, &mut mapping).await;
+        let row = create_project_resource(
+          &client,
+          &mut auth,
+          &owner,
+          project_folder_id,
+          &lr.name,
+          &lr.markdown,
+          lr.source.as_ref(),
+          &updated_at,
+        )
+        .await?;
+        mapping.resources.insert(
+          rel.clone(),
+          ResourceMappingV1 {
+            resource_id: row.id.clone(),
+            local_hash: lr.local_hash.clone(),
+            remote_updated_at: row.updated_at.unwrap_or_else(|| updated_at.clone()),
+          },
+        );
+        row.id
+      }
+    };
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "resource_push".to_string(),
+        path: rel.clone(),
+        detail: format!("Synced local resource to remote resource_id={}", resource_id),
+      },
+    );
+  }
+
+  // A folder that vanished during the walk would look like every file was deleted.
+  crate::volume::ensure_available(vault_path)?;
+
+  // Reconcile local deletions / moves.
+  let to_remove: Vec<(String, String)> = mapping
+    .files
+    .iter()
+    .filter_map(|(rel, fm)| {
+      // Notes outside the selective sync weren't walked; they aren't gone.
+      if local_files.contains(rel) || !scope.admits(rel) {
+        None
+      } else {
+        Some((rel.clone(), fm.file_id.clone()))
+      }
+    })
+    .collect();
+
+  for (rel, file_id) in to_remove {
+    if !direction.pushes(&rel) {
+      crate::direction::hold(vault_path, &rel, "", &mut summary);
+      continue;
+    }
+    match delete_file(&client, &mut auth, &file_id).await {
+      Ok(()) => {
+        mapping.files.remove(&rel);
+        summary.files_deleted += 1;
+        let _ = append_event(
+          vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "delete".to_string(),
+            path: rel,
+            detail: format!("Deleted remote file_id={}", file_id),
+          },
+        );
+      }
+      Err(e) => {
+        summary.errors.push(format!("Delete failed for {} ({}): {}", rel, file_id, e));
+      }
+    }
+  }
+
+  // Reconcile local resource deletions / moves.
+  let to_remove_resources: Vec<(String, String)> = mapping
+    .resources
+    .iter()
+    .filter_map(|(rel, rm)| {
+      if local_resources.contains_key(rel) {
+        None
+      } else {
+        Some((rel.clone(), rm.resource_id.clone()))
+      }
+    })
+    .collect();
+
+  for (rel, resource_id) in to_remove_resources {
+    if !direction.pushes(&rel) {
+      crate::direction::hold(vault_path, &rel, "", &mut summary);
+      continue;
+    }
+    match delete_project_resource(&client, &mut auth, &resource_id).await {
+      Ok(()) => {
+        mapping.resources.remove(&rel);
+        let _ = append_event(
+          vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "resource_delete".to_string(),
+            path: rel.clone(),
+            detail: format!("Deleted remote project_resource id={}", resource_id),
+          },
+        );
+      }
+      Err(e) => {
+        summary
+          .errors
+          .push(format!("Delete failed for resource {} ({}): {}", rel, resource_id, e));
+      }
+    }
+  }
+
+  if attachment_filter.enabled() {
+    local_attachments.retain(|rel, _| direction.pushes(rel));
+    crate::attachments::push(
+      &client,
+      &mut auth,
+      vault_path,
+      project_folder_id,
+      &mut mapping,
+      &local_attachments,
+      |rel| scope.admits(rel) && direction.pushes(rel),
+      &mut summary,
+    )
+    .await?;
+  }
+
+  crate::names::prune(&mut mapping);
+  crate::routing::prune(&mut mapping);
+  mapping.updated_at = now_iso();
+  write_mapping(vault_path, &mapping)?;
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "push".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Pushed. Files created: {}, updated: {}, deleted: {}, skipped: {}. Errors: {}.",
+        summary.files_created,
+        summary.files_updated,
+        summary.files_deleted,
+        summary.files_skipped,
+        summary.errors.len()
+      ),
+    },
+  );
+  Ok(summary)
+}
+
+pub async fn sync_initial_import(vault_path: String, project_folder_id: String, auth: SupabaseAuth) -> Result<SyncSummary, String> {
+  crate::status::begin(&vault_path);
+  let started = Instant::now();
+  let res = sync_push_once_internal(&vault_path, &project_folder_id, &auth).await;
+  crate::status::finish(&vault_path, "push", res.as_ref().map(|_| ()).map_err(|e| e.as_str()));
+  crate::metrics::record("push", started, res.as_ref());
+  if let Ok(summary) = &res {
+    crate::git::after_sync(&vault_path, "import", summary).await;
+    crate::mirror::after_sync(&vault_path).await;
+    crate::tags::after_push(&vault_path, &project_folder_id, &auth).await;
+  }
+  res
+}
+
+pub async fn sync_one_path(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth, abs_path: &Path) -> Result<(), String> {
+  // Editor temp/lock churn never needs a push (the walk skips these files too).
+  if crate::tempfiles::TempFileFilter::load(vault_path).matches(abs_path) {
+    return Ok(());
+  }
+  // Nor do git's own files or changes outside the vault's selective sync.
+  if let Some(rel) = to_rel_posix(Path::new(vault_path), abs_path) {
+    if is_git_rel(&rel) || !crate::vaults::SyncScope::load(vault_path).admits_dir(&rel) {
+      return Ok(());
+    }
+  }
+  crate::status::begin(vault_path);
+  let started = Instant::now();
+  let res = sync_push_once_internal(vault_path, project_folder_id, auth).await;
+  crate::status::finish(vault_path, "push", res.as_ref().map(|_| ()).map_err(|e| e.as_str()));
+  crate::metrics::record("push", started, res.as_ref());
+  let _ = res?;
+  crate::mirror::after_sync(vault_path).await;
+  crate::tags::after_push(vault_path, project_folder_id, auth).await;
+  Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FolderNode {
+  pub id: String,
+  pub parent_id: Option<String>,
+  pub name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RemoteFileRow {
+  pub id: String,
+  pub name: String,
+  pub folder_id: Option<String>,
+  pub content: Option<String>,
+  pub updated_at: Option<String>,
+  pub kind: Option<String>,
+  /// Written by this app on push; cleared by a DB trigger when something else edits `content`.
+  #[serde(default)]
+  pub content_sha256: Option<String>,
+  #[serde(default)]
+  pub rev: Option<i64>,
+  #[serde(default)]
+  pub owner_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RemoteFileMetaRow {
+  pub id: String,
+  pub name: String,
+  pub folder_id: Option<String>,
+  pub updated_at: Option<String>,
+}
+
+impl crate::paging::KeysetRow for RemoteFileMetaRow {
+  const KEYS: &'static [&'static str] = &["id"];
+  fn key_values(&self) -> Vec<&str> {
+    vec![&self.id]
+  }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct RemoteIdRow {
+  id: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct RemoteResourceRow {
+  id: String,
+  name: String,
+  markdown: String,
+  updated_at: Option<String>,
+  source: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct RemoteResourceMetaRow {
+  id: String,
+  name: String,
+  updated_at: Option<String>,
+  source: Option<serde_json::Value>,
+}
+
+impl crate::paging::KeysetRow for RemoteResourceMetaRow {
+  const KEYS: &'static [&'static str] = &["id"];
+  fn key_values(&self) -> Vec<&str> {
+    vec![&self.id]
+  }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct RagProjectRow {
+  owner_id: String,
+  project_folder_id: String,
+  pub public_id: String,
+  updated_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+struct KgEntityRow {
+  owner_id: String,
+  id: String,
+  project_folder_id: Option<String>,
+  entity_type: String,
+  file_id: Option<String>,
+  data: serde_json::Value,
+  updated_at: Option<String>,
+}
+
+impl crate::paging::KeysetRow for KgEntityRow {
+  const KEYS: &'static [&'static str] = &["id", "owner_id"];
+  fn key_values(&self) -> Vec<&str> {
+    vec![&self.id, &self.owner_id]
+  }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+struct KgEdgeRow {
+  owner_id: String,
+  id: String,
+  project_folder_id: Option<String>,
+  edge_type: String,
+  src: String,
+  dst: String,
+  data: serde_json::Value,
+  updated_at: Option<String>,
+}
+
+impl crate::paging::KeysetRow for KgEdgeRow {
+  const KEYS: &'static [&'static str] = &["id", "owner_id"];
+  fn key_values(&self) -> Vec<&str> {
+    vec![&self.id, &self.owner_id]
+  }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+struct RagChunkRowLite {
+  owner_id: String,
+  id: String,
+  project_folder_id: Option<String>,
+  file_id: Option<String>,
+  resource_id: Option<String>,
+  file_kind: Option<String>,
+  anchor: Option<String>,
+  text: String,
+  metadata: Option<serde_json::Value>,
+  updated_at: Option<String>,
+}
+
+impl crate::paging::KeysetRow for RagChunkRowLite {
+  const KEYS: &'static [&'static str] = &["id", "owner_id"];
+  fn key_values(&self) -> Vec<&str> {
+    vec![&self.id, &self.owner_id]
+  }
+}
+
+pub async fn fetch_all_folders(client: &reqwest::Client, auth: &mut SupabaseAuth) -> Result<Vec<FolderNode>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/folders", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "id,parent_id,name");
+    q.append_pair("limit", "10000");
+  }
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("folders fetch failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FolderNode> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows)
+      })
+    },
+  )
+  .await
+}
+
+pub fn compute_subtree_folder_ids(project_folder_id: &str, folders: &[FolderNode]) -> Vec<String> {
+  let mut children: HashMap<String, Vec<String>> = HashMap::new();
+  for f in folders {
+    if let Some(pid) = &f.parent_id {
+      children.entry(pid.clone()).or_default().push(f.id.clone());
+    }
+  }
+
+  let mut out = vec![project_folder_id.to_string()];
+  let mut i = 0usize;
+  while i < out.len() {
+    let cur = out[i].clone();
+    if let Some(ch) = children.get(&cur) {
+      for id in ch {
+        out.push(id.clone());
+      }
+    }
+    i += 1;
+  }
+  out
+}
+
+fn reverse_file_map(mapping: &SyncMappingV1) -> HashMap<String, String> {
+  let mut out = HashMap::new();
+  for (rel, fm) in &mapping.files {
+    out.insert(fm.file_id.clone(), rel.clone());
+  }
+  out
+}
+
+const REMOTE_FILE_COLUMNS: &str = "id,name,folder_id,content,updated_at,kind,content_sha256,rev,owner_id";
+
+/// Re-downloads one file row, used to retry a transfer whose checksum didn't match.
+async fn refetch_remote_file(client: &reqwest::Client, auth: &mut SupabaseAuth, file_id: &str) -> Result<Option<RemoteFileRow>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/files", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", REMOTE_FILE_COLUMNS);
+    q.append_pair("id", &format!("eq.{}", file_id));
+    q.append_pair("limit", "1");
+  }
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("file fetch failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<RemoteFileRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next())
+      })
+    },
+  )
+  .await
+}
+
+pub fn checksum_matches(row: &RemoteFileRow) -> bool {
+  match row.content_sha256.as_deref().filter(|s| !s.is_empty()) {
+    Some(expected) => sha256_hex(row.content.as_deref().unwrap_or("").as_bytes()).eq_ignore_ascii_case(expected),
+    None => true,
+  }
+}
+
+pub async fn fetch_files_updated_since(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  folder_ids: &[String],
+  since_iso: &str,
+) -> Result<Vec<RemoteFileRow>, String> {
+  let mut out: Vec<RemoteFileRow> = Vec::new();
+  let chunk_size = 40usize;
+  let base = rest_base(auth);
+
+  for chunk in folder_ids.chunks(chunk_size) {
+    let list = chunk.join(",");
+    let mut url = reqwest::Url::parse(&format!("{}/files", base)).map_err(|e| e.to_string())?;
+    {
+      let mut q = url.query_pairs_mut();
+      q.append_pair("select", REMOTE_FILE_COLUMNS);
+      q.append_pair("folder_id", &format!("in.({})", list));
+      q.append_pair("updated_at", &format!("gt.{}", since_iso));
+      q.append_pair("limit", "10000");
+    }
+    let mut rows = send_with_refresh(
+      client,
+      auth,
+      || client.get(url.clone()),
+      |res| {
+        Box::pin(async move {
+          if !res.status().is_success() {
+            return Err(format!("files fetch failed: HTTP {}", res.status()));
+          }
+          let rows: Vec<RemoteFileRow> = res.json().await.map_err(|e| e.to_string())?;
+          Ok(rows)
+        })
+      },
+    )
+    .await?;
+    out.append(&mut rows);
+  }
+  Ok(out)
+}
+
+pub async fn fetch_file_meta_in_folders(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  folder_ids: &[String],
+) -> Result<Vec<RemoteFileMetaRow>, String> {
+  let mut out: Vec<RemoteFileMetaRow> = Vec::new();
+  let chunk_size = 40usize;
+
+  for chunk in folder_ids.chunks(chunk_size) {
+    let list = chunk.join(",");
+    let mut rows = crate::paging::fetch_all(
+      client,
+      auth,
+      "files",
+      "id,name,folder_id,updated_at",
+      &[("folder_id", format!("in.({})", list))],
+    )
+    .await?;
+    out.append(&mut rows);
+  }
+
+  Ok(out)
+}
+
+async fn fetch_resources_updated_since(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+  since_iso: &str,
+) -> Result<Vec<RemoteResourceRow>, String> {
+  let base = rest_base(auth);
+  let mut url = reqwest::Url::parse(&format!("{}/project_resources", base)).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "id,name,markdown,updated_at,source");
+    q.append_pair("project_folder_id", &format!("eq.{}", project_folder_id));
+    q.append_pair("updated_at", &format!("gt.{}", since_iso));
+    q.append_pair("limit", "10000");
+  }
+
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("project_resources fetch failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<RemoteResourceRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows)
+      })
+    },
+  )
+  .await
+}
+
+async fn fetch_resource_meta_for_project(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+) -> Result<Vec<RemoteResourceMetaRow>, String> {
+  crate::paging::fetch_all(
+    client,
+    auth,
+    "project_resources",
+    "id,name,updated_at,source",
+    &[("project_folder_id", format!("eq.{}", project_folder_id))],
+  )
+  .await
+}
+
+/// Remote file and resource ids currently under the project, or `None` when the project
+/// folder itself no longer exists.
+pub async fn fetch_project_remote_ids(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+) -> Result<Option<(HashSet<String>, HashSet<String>)>, String> {
+  let folders = crate::folders::fetch_subtree(client, auth, project_folder_id).await?;
+  if !folders.iter().any(|f| f.id == project_folder_id) {
+    return Ok(None);
+  }
+  let folder_ids = compute_subtree_folder_ids(project_folder_id, &folders);
+  let file_ids: HashSet<String> = fetch_file_meta_in_folders(client, auth, &folder_ids)
+    .await?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+  let resource_ids: HashSet<String> = fetch_resource_meta_for_project(client, auth, project_folder_id)
+    .await?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+  Ok(Some((file_ids, resource_ids)))
+}
+
+async fn fetch_file_row(client: &reqwest::Client, auth: &mut SupabaseAuth, file_id: &str) -> Result<Option<FileRow>, String> {
+  let mut url = reqwest::Url::parse(&format!("{}/files", rest_base(auth))).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "id,updated_at");
+    q.append_pair("id", &format!("eq.{}", file_id));
+    q.append_pair("limit", "1");
+  }
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("file lookup failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<FileRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next())
+      })
+    },
+  )
+  .await
+}
+
+/// Pushes a file that is about to be restored from trash. Prefers the remote row it had before
+/// (patched if it still exists, re-inserted under the same id if not) and only falls back to a new
+/// row when the server refuses the old id. Returns the mapping entry and whether the id was kept.
+pub async fn relink_restored_file(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  mapping: &mut SyncMappingV1,
+  rel: &str,
+  bytes: &[u8],
+  prior: Option<&FileMappingV1>,
+) -> Result<(FileMappingV1, bool), String> {
+  let (content, encoding) = crate::encoding::decode(bytes);
+  let kind = detect_kind(&content);
+  let content = crate::codec::encode(&mapping.vault_path, &mapping.project_folder_id, &content)?.content;
+  let local_hash = sha256_hex(bytes);
+  let updated_at = crate::clock::server_now_iso(auth);
+  let remote_rel = crate::routing::Routes::load(&mapping.vault_path).remote_rel(mapping, rel);
+  let parent_rel = match remote_rel.rsplit_once('/') {
+    Some((dir, _)) => dir.to_string(),
+    None => String::new(),
+  };
+  let name = crate::names::remote_name(mapping, rel);
+  let name = name.as_str();
+  let mut summary = SyncSummary::default();
+  let folder_id = ensure_folder_path(client, auth, mapping, &mut summary, &parent_rel).await?;
+  let owner = match prior.and_then(|m| m.owner_id.clone()) {
+    Some(owner) => owner,
+    None => crate::sharing::project_owner(client, auth, mapping).await,
+  };
+
+  let mut reused: Option<FileRow> = None;
+  if let Some(prev) = prior {
+    reused = match fetch_file_row(client, auth, &prev.file_id).await? {
+      // A restore is a deliberate overwrite, so no version check.
+      Some(_) => update_file(client, auth, &prev.file_id, &kind, &content, &updated_at, ExpectedVersion::Any).await?,
+      None => {
+        let body = serde_json::json!({
+          "id": prev.file_id,
+          "name": name,
+          "folder_id": folder_id,
+          "owner_id": owner,
+          "kind": kind,
+          "content": content,
+          "content_sha256": sha256_hex(content.as_bytes()),
+          "updated_at": updated_at
+        });
+        insert_file_row(client, auth, body).await.ok()
+      }
+    };
+  }
+  let kept = reused.is_some();
+  let row = match reused {
+    Some(row) => row,
+    None => create_file(client, auth, &owner, &folder_id, name, &kind, &content, &updated_at).await?,
+  };
+  let rev = row.rev.unwrap_or(0);
+  Ok((
+    FileMappingV1 {
+      local_rev: prior.map(|m| m.local_rev).unwrap_or(0) + 1,
+      encoding: encoding.map(str::to_string),
+      owner_id: Some(owner),
+      ..FileMappingV1::synced(row.id, folder_id, kind, local_hash, row.updated_at.unwrap_or(updated_at), rev)
+    },
+    kept,
+  ))
+}
+
+pub async fn fetch_one_rag_project(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  project_folder_id: &str,
+) -> Result<Option<RagProjectRow>, String> {
+  let base = rest_base(auth);
+  let mut url = reqwest::Url::parse(&format!("{}/rag_projects", base)).map_err(|e| e.to_string())?;
+  {
+    let mut q = url.query_pairs_mut();
+    q.append_pair("select", "owner_id,project_folder_id,public_id,updated_at");
+    q.append_pair("project_folder_id", &format!("eq.{}", project_folder_id));
+    q.append_pair("limit", "1");
+  }
+  send_with_refresh(
+    client,
+    auth,
+    || client.get(url.clone()),
+    |res| {
+      Box::pin(async move {
+        if !res.status().is_success() {
+          return Err(format!("rag_projects fetch failed: HTTP {}", res.status()));
+        }
+        let rows: Vec<RagProjectRow> = res.json().await.map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().next())
+      })
+    },
+  )
+  .await
+}
+
+async fn fetch_paginated<T: for<'de> Deserialize<'de> + crate::paging::KeysetRow>(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  table: &str,
+  select: &str,
+  project_folder_id: &str,
+) -> Result<Vec<T>, String> {
+  crate::paging::fetch_all(client, auth, table, select, &[("project_folder_id", format!("eq.{}", project_folder_id))]).await
+}
+
+pub fn write_jsonl<T: Serialize>(path: &Path, rows: &[T]) -> Result<(), String> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  let mut buf = Vec::new();
+  for r in rows {
+    let line = serde_json::to_string(r).map_err(|e| e.to_string())?;
+    writeln!(buf, "{}", line).map_err(|e| e.to_string())?;
+  }
+  write_synced(path, buf).map_err(|e| e.to_string())
+}
+
+fn write_json(path: &Path, v: &serde_json::Value) -> Result<(), String> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  let text = serde_json::to_string_pretty(v).map_err(|e| e.to_string())?;
+  write_synced(path, text).map_err(|e| e.to_string())
+}
+
+async fn rag_export_into_vault(
+  client: &reqwest::Client,
+  auth: &mut SupabaseAuth,
+  vault_path: &str,
+  project_folder_id: &str,
+  last_export_at: &str,
+) -> Result<Option<String>, String> {
+  let rag_project = fetch_one_rag_project(client, auth, project_folder_id).await?;
+  let Some(rp) = rag_project else { return Ok(None); };
+  let updated_at = rp.updated_at.clone().unwrap_or_else(now_iso);
+  if !last_export_at.trim().is_empty() && updated_at.as_str() <= last_export_at.trim() {
+    return Ok(None);
+  }
+
+  let ents: Vec<KgEntityRow> = fetch_paginated(client, auth, "kg_entities", "owner_id,id,project_folder_id,entity_type,file_id,data,updated_at", project_folder_id).await?;
+  let edges: Vec<KgEdgeRow> = fetch_paginated(client, auth, "kg_edges", "owner_id,id,project_folder_id,edge_type,src,dst,data,updated_at", project_folder_id).await?;
+  let chunks: Vec<RagChunkRowLite> = fetch_paginated(
+    client,
+    auth,
+    "rag_chunks",
+    // Exclude `embedding` (too large/noisy for filesystem sync).
+    "owner_id,id,project_folder_id,file_id,resource_id,file_kind,anchor,text,metadata,updated_at",
+    project_folder_id,
+  )
+  .await?;
+
+  let rag_dir = Path::new(vault_path).join("rag");
+  write_json(&rag_dir.join("project.json"), &serde_json::to_value(&rp).map_err(|e| e.to_string())?)?;
+  write_jsonl(&rag_dir.join("kg_entities.jsonl"), &ents)?;
+  write_jsonl(&rag_dir.join("kg_edges.jsonl"), &edges)?;
+  write_jsonl(&rag_dir.join("rag_chunks.jsonl"), &chunks)?;
+
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "rag_export".to_string(),
+      path: "rag/".to_string(),
+      detail: format!(
+        "Exported RAG/KG. Entities: {}, edges: {}, chunks: {}.",
+        ents.len(),
+        edges.len(),
+        chunks.len()
+      ),
+    },
+  );
+
+  Ok(Some(updated_at))
+}
+
+pub async fn sync_pull_once(
+  vault_path: String,
+  project_folder_id: String,
+  auth: SupabaseAuth,
+  force: Option<bool>,
+) -> Result<SyncSummary, String> {
+  crate::status::begin(&vault_path);
+  let started = Instant::now();
+  let res = sync_pull_once_internal(vault_path.clone(), project_folder_id, auth, force.unwrap_or(false)).await;
+  crate::status::finish(&vault_path, "pull", res.as_ref().map(|_| ()).map_err(|e| e.as_str()));
+  crate::metrics::record("pull", started, res.as_ref());
+  if let Ok(summary) = &res {
+    crate::git::after_sync(&vault_path, "pull", summary).await;
+    crate::mirror::after_sync(&vault_path).await;
+  }
+  res
+}
+
+/// Local path of a remote folder: the one it is mapped to, else one built from the remote tree.
+pub fn local_folder_rel(
+  mapping: &mut SyncMappingV1,
+  project_folder_id: &str,
+  folder_id: &str,
+  folders_by_id: &HashMap<String, FolderNode>,
+) -> String {
+  let mapped = mapping
+    .folders
+    .iter()
+    .find_map(|(rel, id)| if id == folder_id { Some(rel.clone()) } else { None });
+  mapped
+    .or_else(|| crate::names::local_folder(mapping, project_folder_id, folder_id, folders_by_id))
+    .unwrap_or_default()
+}
+
+/// Local path of remote file `id` called `name`, inside the folder at `folder_rel`.
+pub fn local_file_rel(
+  folder_rel: &str,
+  folder_id: &str,
+  id: &str,
+  name: &str,
+  project_folder_id: &str,
+  remote_file_meta: &[RemoteFileMetaRow],
+) -> String {
+  let siblings = remote_file_meta
+    .iter()
+    .filter(|m| m.folder_id.as_deref().unwrap_or(project_folder_id) == folder_id)
+    .map(|m| (m.id.as_str(), m.name.as_str()));
+  let name = crate::names::resolve(id, name, folder_rel.is_empty(), siblings);
+  if folder_rel.is_empty() {
+    name
+  } else {
+    format!("{}/{}", folder_rel, name)
+  }
+}
+
+/// Local path of a remote resource; docling imports go under `resources/docling/`.
+fn local_resource_rel(
+  id: &str,
+  name: &str,
+  source: Option<&serde_json::Value>,
+  remote_resource_meta: &[RemoteResourceMetaRow],
+) -> String {
+  let siblings = remote_resource_meta.iter().map(|m| (m.id.as_str(), m.name.as_str()));
+  let name = crate::names::resolve(id, name, false, siblings);
+  if source.and_then(|s| s.get("type")).and_then(|v| v.as_str()) == Some("docling") {
+    format!("resources/docling/{}", name)
+  } else {
+    format!("resources/{}", name)
+  }
+}
+
+#[tracing::instrument(name = "pull", skip_all, fields(vault = %vault_path), err)]
+async fn sync_pull_once_internal(
+  vault_path: String,
+  project_folder_id: String,
+  auth: SupabaseAuth,
+  force: bool,
+) -> Result<SyncSummary, String> {
+  let root = Path::new(&vault_path);
+  if !root.exists() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let _mapping = crate::lock::mapping(&vault_path).await;
+
+  let client = reqwest::Client::new();
+  let mut auth = auth;
+  let mut mapping = match read_mapping(&vault_path)? {
+    Some(m) => m,
+    None => init_mapping(&vault_path, &project_folder_id, false, force)?,
+  };
+  if mapping.project_folder_id != project_folder_id {
+    return Err("mapping project_folder_id mismatch".to_string());
+  }
+  crate::safety::check_linked_vault(&vault_path, &mut mapping, force)?;
+  crate::clock::measure(&client, &auth, &vault_path).await;
+  crate::device::register(&client, &mut auth, &vault_path).await;
+  crate::device_control::enforce(&client, &mut auth, &vault_path, &project_folder_id).await?;
+
+  // Taken before listing, so changes made while this pull runs still show up next time.
+  let head = crate::head::fetch(&client, &mut auth, &project_folder_id).await;
+  if head.is_some() && head == mapping.remote_head && !mapping.last_pull_at.trim().is_empty() {
+    crate::head::note_idle(&vault_path);
+    if let Ok(Some(rag_updated_at)) =
+      rag_export_into_vault(&client, &mut auth, &vault_path, &project_folder_id, &mapping.last_rag_export_at).await
+    {
+      mapping.last_rag_export_at = rag_updated_at;
+      write_mapping(&vault_path, &mapping)?;
+    }
+    return Ok(SyncSummary::default());
+  }
+  crate::head::note_active(&vault_path);
+
+  let since = if mapping.last_pull_at.trim().is_empty() {
+    "1970-01-01T00:00:00Z".to_string()
+  } else {
+    mapping.last_pull_at.clone()
+  };
+
+  let folders = crate::folders::fetch_folders(&client, &mut auth, &vault_path, &project_folder_id).await?;
+  let folders_by_id: HashMap<String, FolderNode> = folders.into_iter().map(|f| (f.id.clone(), f)).collect();
+  let folder_vec: Vec<FolderNode> = folders_by_id.values().cloned().collect();
+  let folder_ids = compute_subtree_folder_ids(&project_folder_id, &folder_vec);
+  let remote_file_meta = fetch_file_meta_in_folders(&client, &mut auth, &folder_ids).await?;
+  let remote_file_ids: HashSet<String> = remote_file_meta.iter().map(|r| r.id.clone()).collect();
+  let remote_files = fetch_files_updated_since(&client, &mut auth, &folder_ids, &since).await?;
+  let remote_resource_meta = fetch_resource_meta_for_project(&client, &mut auth, &project_folder_id).await?;
+  let remote_resource_ids: HashSet<String> = remote_resource_meta.iter().map(|r| r.id.clone()).collect();
+  let remote_resources = fetch_resources_updated_since(&client, &mut auth, &project_folder_id, &since).await?;
+  // The drive may have gone while fetching; don't recreate the vault on the bare mount point.
+  crate::volume::ensure_available(&vault_path)?;
+
+  let mut summary = SyncSummary::default();
+  let mut conflicts: u32 = 0;
+  let scope = crate::vaults::SyncScope::load(&vault_path);
+  let conflict_policy = crate::vaults::settings(&vault_path).conflict_policy;
+  let routes = crate::routing::Routes::load(&vault_path);
+  let direction = crate::direction::Direction::load(&vault_path);
+
+  // Reconcile remote file renames/moves by ID, even if `updated_at` did not change.
+  let file_meta_by_id: HashMap<String, RemoteFileMetaRow> = remote_file_meta
+    .iter()
+    .map(|r| (r.id.clone(), r.clone()))
+    .collect();
+  let mapped_files_snapshot: Vec<(String, FileMappingV1)> = mapping
+    .files
+    .iter()
+    .map(|(rel, fm)| (rel.clone(), fm.clone()))
+    .collect();
+  for (old_rel_path, fm) in mapped_files_snapshot {
+    let Some(meta) = file_meta_by_id.get(&fm.file_id) else { continue };
+    let folder_id = meta.folder_id.clone().unwrap_or(project_folder_id.clone());
+    let folder_rel = local_folder_rel(&mut mapping, &project_folder_id, &folder_id, &folders_by_id);
+    let remote_rel = local_file_rel(&folder_rel, &folder_id, &meta.id, &meta.name, &project_folder_id, &remote_file_meta);
+    let desired_rel_path = routes.apply(&fm.kind, &remote_rel);
+    if desired_rel_path == old_rel_path {
+      if let Some(cur) = mapping.files.get_mut(&old_rel_path) {
+        cur.folder_id = folder_id;
+        if let Some(u) = meta.updated_at.clone() {
+          cur.remote_updated_at = u;
+        }
+      }
+      crate::routing::remember(&mut mapping, &old_rel_path, &remote_rel);
+      continue;
+    }
+    // Moved out of the selective sync (or out of pulls): the local copy stays where it is.
+    if !scope.admits(&desired_rel_path) || !direction.pulls(&old_rel_path) || !direction.pulls(&desired_rel_path) {
+      continue;
+    }
+    if let Some(existing) = mapping.files.get(&desired_rel_path) {
+      if existing.file_id != fm.file_id {
+        summary.errors.push(format!(
+          "Cannot remap file {} to {} (path already mapped to a different file id).",
+          old_rel_path, desired_rel_path
+        ));
+        continue;
+      }
+    }
+    let old_abs = crate::normalize::local_path(root, &old_rel_path);
+    let new_abs = crate::normalize::local_path(root, &desired_rel_path);
+    if old_abs.exists() && old_abs != new_abs {
+      if new_abs.exists() {
+        archive_or_log(&vault_path, &old_rel_path);
+      } else if let Err(e) = move_file_with_fallback(&old_abs, &new_abs) {
+        summary.errors.push(format!(
+          "Failed to move renamed file {} -> {}: {}",
+          old_rel_path, desired_rel_path, e
+        ));
+      }
+    }
+    if let Some(mut moved) = mapping.files.remove(&old_rel_path) {
+      moved.folder_id = folder_id;
+      if let Some(u) = meta.updated_at.clone() {
+        moved.remote_updated_at = u;
+      }
+      mapping.files.insert(desired_rel_path.clone(), moved);
+      crate::names::remember(&mut mapping, &desired_rel_path, &meta.name);
+      crate::routing::remember(&mut mapping, &desired_rel_path, &remote_rel);
+      let _ = append_event(
+        &vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "pull_rename".to_string(),
+          path: desired_rel_path.clone(),
+          detail: format!("Renamed from {} by remote metadata sync.", old_rel_path),
+        },
+      );
+    }
+  }
+
+  // Reconcile remote resource renames by ID, even if `updated_at` did not change.
+  let resource_meta_by_id: HashMap<String, RemoteResourceMetaRow> = remote_resource_meta
+    .iter()
+    .map(|r| (r.id.clone(), r.clone()))
+    .collect();
+  let mapped_resources_snapshot: Vec<(String, ResourceMappingV1)> = mapping
+    .resources
+    .iter()
+    .map(|(rel, rm)| (rel.clone(), rm.clone()))
+    .collect();
+  for (old_rel_path, rm) in mapped_resources_snapshot {
+    let Some(meta) = resource_meta_by_id.get(&rm.resource_id) else { continue };
+    let desired_rel_path = local_resource_rel(&meta.id, &meta.name, meta.source.as_ref(), &remote_resource_meta);
+    if !direction.pulls(&old_rel_path) || !direction.pulls(&desired_rel_path) {
+      continue;
+    }
+    if desired_rel_path == old_rel_path {
+      if let Some(cur) = mapping.resources.get_mut(&old_rel_path) {
+        if let Some(u) = meta.updated_at.clone() {
+          cur.remote_updated_at = u;
+        }
+      }
+      continue;
+    }
+    if let Some(existing) = mapping.resources.get(&desired_rel_path) {
+      if existing.resource_id != rm.resource_id {
+        summary.errors.push(format!(
+          "Cannot remap resource {} to {} (path already mapped to a different resource id).",
+          old_rel_path, desired_rel_path
+        ));
+        continue;
+      }
+    }
+    let old_abs = crate::normalize::local_path(root, &old_rel_path);
+    let new_abs = crate::normalize::local_path(root, &desired_rel_path);
+    if old_abs.exists() && old_abs != new_abs {
+      if new_abs.exists() {
+        archive_or_log(&vault_path, &old_rel_path);
+      } else if let Err(e) = move_file_with_fallback(&old_abs, &new_abs) {
+        summary.errors.push(format!(
+          "Failed to move renamed resource {} -> {}: {}",
+          old_rel_path, desired_rel_path, e
+        ));
+      }
+    }
+    if let Some(mut moved) = mapping.resources.remove(&old_rel_path) {
+      if let Some(u) = meta.updated_at.clone() {
+        moved.remote_updated_at = u;
+      }
+      mapping.resources.insert(desired_rel_path.clone(), moved);
+      crate::names::remember(&mut mapping, &desired_rel_path, &meta.name);
+      let _ = append_event(
+        &vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "pull_rename".to_string(),
+          path: desired_rel_path.clone(),
+          detail: format!("Resource renamed from {} by remote metadata sync.", old_rel_path),
+        },
+      );
+    }
+  }
+
+  let mut by_file_id = reverse_file_map(&mapping);
+  let mut by_resource_id: HashMap<String, String> = mapping
+    .resources
+    .iter()
+    .map(|(rel, rm)| (rm.resource_id.clone(), rel.clone()))
+    .collect();
+
+  // Where every file of the project lives (or will) locally, for turning `nexus://file/<id>`
+  // links back into relative ones.
+  let link_targets: Option<HashMap<String, String>> = crate::links::enabled(&vault_path).then(|| {
+    let mut targets = reverse_file_map(&mapping);
+    let kinds: HashMap<&str, &str> = remote_files
+      .iter()
+      .map(|rf| (rf.id.as_str(), rf.kind.as_deref().unwrap_or("note")))
+      .collect();
+    for meta in &remote_file_meta {
+      if targets.contains_key(&meta.id) {
+        continue;
+      }
+      let folder_id = meta.folder_id.clone().unwrap_or(project_folder_id.clone());
+      let folder_rel = local_folder_rel(&mut mapping, &project_folder_id, &folder_id, &folders_by_id);
+      let rel = local_file_rel(&folder_rel, &folder_id, &meta.id, &meta.name, &project_folder_id, &remote_file_meta);
+      let kind = kinds.get(meta.id.as_str()).copied().unwrap_or("note");
+      targets.insert(meta.id.clone(), routes.apply(kind, &rel));
+    }
+    targets
+  });
+
+  let total_files = remote_files.len();
+  for (i, rf) in remote_files.into_iter().enumerate() {
+    crate::bootstrap::progress(&vault_path, "files", i + 1, total_files, &rf.name);
+    let remote_updated_at = rf.updated_at.clone().unwrap_or_else(|| crate::clock::server_now_iso(&auth));
+    let remote_rev = rf.rev.unwrap_or(0);
+    let remote_content = rf.content.clone().unwrap_or_default();
+    let remote_kind = rf.kind.clone().unwrap_or_else(|| "note".to_string());
+
+    let folder_id = rf.folder_id.clone().unwrap_or(project_folder_id.clone());
+    let folder_rel = local_folder_rel(&mut mapping, &project_folder_id, &folder_id, &folders_by_id);
+    let remote_rel = local_file_rel(&folder_rel, &folder_id, &rf.id, &rf.name, &project_folder_id, &remote_file_meta);
+    let desired_rel_path = routes.apply(&remote_kind, &remote_rel);
+    if !scope.admits(&desired_rel_path) || !direction.pulls(&desired_rel_path) {
+      continue;
+    }
+
+    let dir_rel = desired_rel_path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+    let target_dir = crate::normalize::local_path(root, dir_rel);
+    if let Err(e) = fs::create_dir_all(&target_dir) {
+      summary.errors.push(e.to_string());
+      continue;
+    }
+    let mut prev_from_old_rel: Option<FileMappingV1> = None;
+    if let Some(old_rel_path) = by_file_id.get(&rf.id).cloned() {
+      if old_rel_path != desired_rel_path {
+        let old_abs = crate::normalize::local_path(root, &old_rel_path);
+        let new_abs = crate::normalize::local_path(root, &desired_rel_path);
+        if old_abs.exists() && old_abs != new_abs {
+          if new_abs.exists() {
+            archive_or_log(&vault_path, &old_rel_path);
+          } else if let Err(e) = move_file_with_fallback(&old_abs, &new_abs) {
+            summary.errors.push(format!(
+              "Failed to move renamed file {} -> {}: {}",
+              old_rel_path, desired_rel_path, e
+            ));
+          }
+        }
+        prev_from_old_rel = mapping.files.remove(&old_rel_path);
+      } else {
+        prev_from_old_rel = mapping.files.get(&old_rel_path).cloned();
+      }
+    }
+    by_file_id.insert(rf.id.clone(), desired_rel_path.clone());
+    crate::names::remember(&mut mapping, &desired_rel_path, &rf.name);
+    crate::routing::remember(&mut mapping, &desired_rel_path, &remote_rel);
+    let rel_path = desired_rel_path;
+
+    let remote_content = if checksum_matches(&rf) {
+      remote_content
+    } else {
+      // Usually a truncated transfer, so one fresh download settles it.
+      crate::metrics::note_retry();
+      match refetch_remote_file(&client, &mut auth, &rf.id).await {
+        Ok(Some(row)) if checksum_matches(&row) => row.content.unwrap_or_default(),
+        other => {
+          let detail = match other {
+            Err(e) => format!("Checksum mismatch; re-download failed: {}", e),
+            _ => "Downloaded content does not match content_sha256, even after a retry.".to_string(),
+          };
+          summary.errors.push(format!("Skipped corrupted download of {}: {}", rel_path, detail));
+          let _ = append_event(
+            &vault_path,
+            &SyncEvent {
+              ts: now_iso(),
+              kind: "corruption".to_string(),
+              path: rel_path.clone(),
+              detail,
+            },
+          );
+          continue;
+        }
+      }
+    };
+
+    // `wire_content` is the text as the server holds it, the base of later delta pushes;
+    // `remote_content` is what the vault gets.
+    let (wire_content, remote_content) = match crate::codec::decode(&project_folder_id, &remote_content) {
+      Ok((plain, saved)) => {
+        summary.bytes_saved += saved;
+        let local = crate::nexusdoc::normalize(&plain);
+        let local = match &link_targets {
+          Some(targets) if remote_kind != crate::crdt::COLLAB_KIND => crate::links::to_local(&local, &rel_path, targets),
+          _ => local,
+        };
+        (plain, local)
+      }
+      Err(e) => {
+        // Never write ciphertext (or nothing) over the local file; surface it instead.
+        summary.errors.push(format!("Cannot decrypt {}: {}", rel_path, e));
+        let _ = append_event(
+          &vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "decrypt_error".to_string(),
+            path: rel_path.clone(),
+            detail: e,
+          },
+        );
+        continue;
+      }
+    };
+
+    let abs_path = crate::normalize::local_path(root, &rel_path);
+    if crate::symlinks::check_write(&vault_path, &abs_path).is_err() {
+      // Logged as `symlink_skipped`.
+      summary.files_skipped += 1;
+      continue;
+    }
+    let local_bytes = fs::read(&abs_path).ok();
+    let local_hash = local_bytes.as_ref().map(|b| sha256_hex(b)).unwrap_or_default();
+
+    let prev = mapping.files.get(&rel_path).cloned().or(prev_from_old_rel);
+    let prev_local_hash = prev.as_ref().map(|m| m.local_hash.clone()).unwrap_or_default();
+    let prev_remote_updated = prev.as_ref().map(|m| m.remote_updated_at.clone()).unwrap_or_default();
+    let prev_remote_rev = prev.as_ref().map(|m| m.remote_rev).unwrap_or(0);
+    let prev_local_rev = prev.as_ref().map(|m| m.local_rev).unwrap_or(0);
+    let prev_base = prev.as_ref().and_then(|m| m.base_hash.clone());
+
+    let local_modified = !prev_local_hash.is_empty() && local_hash != prev_local_hash;
+    // Revs come from the server, so they order correctly regardless of device clocks; timestamps
+    // are only the fallback for rows or mappings from before revs existed.
+    let remote_newer = if remote_rev > 0 && prev_remote_rev > 0 {
+      remote_rev > prev_remote_rev
+    } else {
+      !prev_remote_updated.is_empty() && crate::clock::is_after(&remote_updated_at, &prev_remote_updated)
+    };
+    let remote_hash = sha256_hex(remote_content.as_bytes());
+
+    if remote_kind == crate::crdt::COLLAB_KIND {
+      let local_text = local_bytes.as_ref().map(|b| crate::encoding::decode(b).0);
+      let pushes = direction.pushes(&rel_path);
+      if local_modified && !pushes {
+        // The merge result replaces the held-back edit, so it is kept beside the file.
+        let conflict_path = conflict_copy_path(&abs_path, "conflict");
+        if let Err(e) = local_bytes.as_ref().map_or(Ok(()), |b| write_synced(&conflict_path, b)) {
+          summary.errors.push(e.to_string());
+          continue;
+        }
+        crate::direction::hold(&vault_path, &rel_path, &local_hash, &mut summary);
+      }
+      let local_edit = local_text.as_deref().filter(|_| local_modified && pushes);
+      let merged =
+        match merge_collab_file(&client, &mut auth, &vault_path, &project_folder_id, &rf, &remote_content, local_edit).await {
+          Ok(m) => m,
+          Err(e) => {
+            summary.errors.push(format!("Collab merge failed for {}: {}", rel_path, e));
+            continue;
+          }
+        };
+      let merged_hash = sha256_hex(merged.text.as_bytes());
+      let wrote = merged_hash != local_hash;
+      if wrote {
+        if let Err(e) = write_synced(&abs_path, &merged.text) {
+          summary.errors.push(e.to_string());
+          continue;
+        }
+        crate::revisions::record(&vault_path, &rel_path, merged.text.as_bytes());
+        crate::activity::note_pull(&vault_path, &rel_path);
+        crate::open_files::note(&vault_path, &rel_path, crate::open_files::RemoteChange::Updated, "Merged remote edits.");
+        if prev.is_some() {
+          summary.files_updated += 1;
+        } else {
+          summary.files_created += 1;
+        }
+      }
+      if local_modified && remote_newer {
+        let _ = append_event(
+          &vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "collab_merge".to_string(),
+            path: rel_path.clone(),
+            detail: "Merged local and remote edits through the change log.".to_string(),
+          },
+        );
+      }
+      crate::delta::forget_base(&vault_path, prev_base.as_deref());
+      let rev = merged.remote_rev.unwrap_or(remote_rev);
+      mapping.files.insert(
+        rel_path.clone(),
+        FileMappingV1 {
+          local_rev: prev_local_rev + u64::from(wrote),
+          owner_id: rf.owner_id.clone(),
+          ..FileMappingV1::synced(
+            rf.id.clone(),
+            folder_id.clone(),
+            remote_kind,
+            merged_hash,
+            merged.remote_updated_at.unwrap_or(remote_updated_at),
+            rev,
+          )
+        },
+      );
+      continue;
+    }
+
+    // Local edits that can't be pushed lose to remote updates, like under prefer_remote.
+    let conflict_policy = if direction.pushes(&rel_path) {
+      conflict_policy
+    } else {
+      crate::vaults::ConflictPolicy::PreferRemote
+    };
+    if local_modified && !remote_newer && !direction.pushes(&rel_path) {
+      crate::direction::hold(&vault_path, &rel_path, &local_hash, &mut summary);
+      continue;
+    }
+    if local_modified && !remote_newer {
+      // Local changed since last sync and remote is not newer.
+      // Keep local as source-of-truth and push it upstream so next pulls converge.
+      if let Some(bytes) = local_bytes.as_ref() {
+        let (local_content, local_encoding) = crate::encoding::decode(bytes);
+        let local_kind = detect_kind(&local_content);
+        let local_content = if link_targets.is_some() && local_kind != crate::crdt::COLLAB_KIND {
+          crate::links::to_remote(&local_content, &rel_path, &mapping.files)
+        } else {
+          local_content
+        };
+        let pushed_at = crate::clock::server_now_iso(&auth);
+        let encoded = crate::nexusdoc::validate(&local_content)
+          .and_then(|_| crate::codec::encode(&vault_path, &project_folder_id, &local_content));
+        let expected = if remote_rev > 0 {
+          ExpectedVersion::Rev(remote_rev)
+        } else {
+          ExpectedVersion::UpdatedAt(rf.updated_at.as_deref().unwrap_or(""))
+        };
+        let pushed = match encoded {
+          Ok(encoded) => {
+            summary.bytes_saved += encoded.bytes_saved;
+            update_file(&client, &mut auth, &rf.id, &local_kind, &encoded.content, &pushed_at, expected).await
+          }
+          Err(e) => Err(e),
+        };
+        if update_denied(&client, &mut auth, &rf.id, expected, &pushed).await {
+          if let Some(prev) = prev.clone() {
+            mapping.files.entry(rel_path.clone()).or_insert(prev);
+          }
+          crate::sharing::mark_read_only(&vault_path, &mut mapping, &rel_path, &mut summary);
+          continue;
+        }
+        match pushed {
+          Ok(None) => {
+            // Changed again since this pull fetched it; the fresh remote copy goes beside the file.
+            match save_remote_conflict(&client, &mut auth, &vault_path, &project_folder_id, &abs_path, &rel_path, &rf.id).await {
+              Ok(remote) => {
+                if let (Some(remote), Some(prev)) = (remote, prev.clone()) {
+                  let seen = FileMappingV1 {
+                    remote_updated_at: remote.updated_at.unwrap_or(prev.remote_updated_at.clone()),
+                    remote_rev: remote.rev.unwrap_or(prev.remote_rev),
+                    ..prev
+                  };
+                  mapping.files.insert(rel_path.clone(), seen);
+                }
+                conflicts += 1;
+              }
+              Err(e) => summary.errors.push(e),
+            }
+            continue;
+          }
+          Ok(Some(row)) => {
+            // Pushed as a full upload, possibly compressed; the next push starts from that.
+            crate::delta::forget_base(&vault_path, prev_base.as_deref());
+            let rev = row.rev.unwrap_or(0);
+            mapping.files.insert(
+              rel_path.clone(),
+              FileMappingV1 {
+                local_rev: prev_local_rev + 1,
+                encoding: local_encoding.map(str::to_string),
+                owner_id: rf.owner_id.clone(),
+                ..FileMappingV1::synced(
+                  rf.id.clone(),
+                  folder_id.clone(),
+                  local_kind,
+                  local_hash.clone(),
+                  row.updated_at.unwrap_or(pushed_at),
+                  rev,
+                )
+              },
+            );
+            summary.files_updated += 1;
+            crate::revisions::record(&vault_path, &rel_path, bytes);
+            crate::activity::note_push(&vault_path, &rel_path);
+            let _ = append_event(
+              &vault_path,
+              &SyncEvent {
+                ts: now_iso(),
+                kind: "push_resolve".to_string(),
+                path: rel_path.clone(),
+                detail: "Kept newer local edit and pushed it to remote.".to_string(),
+              },
+            );
+            continue;
+          }
+          Err(e) => {
+            summary
+              .errors
+              .push(format!("Failed to push newer local file {} to remote: {}", rel_path, e));
+            // Fall back to conflict handling below to avoid silent data loss.
+          }
+        }
+      }
+    }
+
+    if local_modified && remote_newer && conflict_policy == crate::vaults::ConflictPolicy::PreferRemote {
+      // The remote version is written below; the local edits go to a sibling conflict file.
+      let conflict_path = conflict_copy_path(&abs_path, "conflict");
+      if let Err(e) = local_bytes.as_ref().map_or(Ok(()), |b| write_synced(&conflict_path, b)) {
+        summary.errors.push(e.to_string());
+        continue;
+      }
+      let _ = append_event(
+        &vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "conflict".to_string(),
+          path: rel_path.clone(),
+          detail: format!("Took the remote update over local edits (prefer_remote). Wrote {}", conflict_path.display()),
+        },
+      );
+      crate::activity::note_conflict(&vault_path, &rel_path, "Remote update replaced local edits.");
+      crate::open_files::note(
+        &vault_path,
+        &rel_path,
+        crate::open_files::RemoteChange::Conflict,
+        "Remote update replaced local edits; they were saved alongside.",
+      );
+      crate::notify::notify(
+        &vault_path,
+        crate::notify::NotifyKind::Conflict,
+        "Sync conflict",
+        &format!("{} changed both here and in Diregram. Your edits were saved alongside the remote version.", rel_path),
+        Some(&rel_path),
+      );
+      conflicts += 1;
+    } else if local_modified && remote_newer {
+      if conflict_policy == crate::vaults::ConflictPolicy::PreferLocal {
+        let _ = append_event(
+          &vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "conflict".to_string(),
+            path: rel_path.clone(),
+            detail: "Kept local edits over a remote update (prefer_local); the next push overwrites it.".to_string(),
+          },
+        );
+        crate::activity::note_conflict(&vault_path, &rel_path, "Local edits kept over a remote update.");
+        crate::open_files::note(
+          &vault_path,
+          &rel_path,
+          crate::open_files::RemoteChange::Conflict,
+          "Local edits kept over a remote update.",
+        );
+      } else {
+        // Conflict: write remote to a sibling conflict file.
+        let conflict_path = conflict_copy_path(&abs_path, "conflict");
+        if let Err(e) = write_synced(&conflict_path, &remote_content) {
+          summary.errors.push(e.to_string());
+        }
+        let _ = append_event(
+          &vault_path,
+          &SyncEvent {
+            ts: now_iso(),
+            kind: "conflict".to_string(),
+            path: rel_path.clone(),
+            detail: format!("Remote update would overwrite local edits. Wrote {}", conflict_path.display()),
+          },
+        );
+        crate::activity::note_conflict(&vault_path, &rel_path, "Remote update would overwrite local edits.");
+        crate::open_files::note(
+          &vault_path,
+          &rel_path,
+          crate::open_files::RemoteChange::Conflict,
+          "Remote update would overwrite local edits; it was saved alongside.",
+        );
+        crate::notify::notify(
+          &vault_path,
+          crate::notify::NotifyKind::Conflict,
+          "Sync conflict",
+          &format!("{} changed both here and in Diregram. The remote version was saved alongside it.", rel_path),
+          Some(&rel_path),
+        );
+      }
+      // Remember the remote version was seen (but not merged: `base_rev` stays), so the next push of
+      // the local edit is a knowing overwrite rather than another conflict.
+      if let Some(prev) = prev.clone() {
+        let seen = FileMappingV1 {
+          remote_updated_at: remote_updated_at.clone(),
+          remote_rev,
+          ..prev
+        };
+        mapping.files.insert(rel_path.clone(), seen);
+      }
+      conflicts += 1;
+      continue;
+    }
+
+    if local_hash == remote_hash {
+      // Content already matches remote; refresh mapping state without rewriting the file.
+      let base_hash = crate::delta::remember_base(&vault_path, prev_base.as_deref(), &wire_content);
+      mapping.files.insert(
+        rel_path.clone(),
+        FileMappingV1 {
+          local_rev: prev_local_rev,
+          owner_id: rf.owner_id.clone(),
+          read_only: prev.as_ref().is_some_and(|m| m.read_only),
+          base_hash,
+          ..FileMappingV1::synced(rf.id.clone(), folder_id.clone(), remote_kind, local_hash, remote_updated_at, remote_rev)
+        },
+      );
+      continue;
+    }
+
+    // Notes kept in a legacy encoding are written back in it, unless the new text no longer fits.
+    let prev_encoding = prev.as_ref().and_then(|m| m.encoding.clone());
+    let encoded = prev_encoding.as_deref().and_then(|enc| crate::encoding::encode(&remote_content, enc));
+    if let (Some(enc), None) = (prev_encoding.as_deref(), encoded.as_ref()) {
+      summary.warnings.push(format!("{} can no longer be stored as {}; it was written as UTF-8.", rel_path, enc));
+    }
+    let encoding = if encoded.is_some() { prev_encoding } else { None };
+    let out_bytes = encoded.unwrap_or_else(|| remote_content.clone().into_bytes());
+    if let Err(e) = write_synced(&abs_path, &out_bytes) {
+      summary.errors.push(e.to_string());
+      continue;
+    }
+
+    let next_hash = sha256_hex(&out_bytes);
+    let base_hash = crate::delta::remember_base(&vault_path, prev_base.as_deref(), &wire_content);
+    crate::revisions::record(&vault_path, &rel_path, remote_content.as_bytes());
+    crate::activity::note_pull(&vault_path, &rel_path);
+    crate::open_files::note(&vault_path, &rel_path, crate::open_files::RemoteChange::Updated, "Pulled a remote update.");
+    if prev.is_some() {
+      summary.files_updated += 1;
+    } else {
+      summary.files_created += 1;
+    }
+    mapping.files.insert(
+      rel_path.clone(),
+      FileMappingV1 {
+        local_rev: prev_local_rev + 1,
+        encoding,
+        owner_id: rf.owner_id.clone(),
+        read_only: prev.as_ref().is_some_and(|m| m.read_only),
+        base_hash,
+        ..FileMappingV1::synced(rf.id.clone(), folder_id.clone(), remote_kind, next_hash, remote_updated_at, remote_rev)
+      },
+    );
+  }
+
+  let total_resources = remote_resources.len();
+  for (i, rr) in remote_resources.into_iter().enumerate() {
+    crate::bootstrap::progress(&vault_path, "resources", i + 1, total_resources, &rr.name);
+    let remote_updated_at = rr.updated_at.clone().unwrap_or_else(|| crate::clock::server_now_iso(&auth));
+    let desired_rel_path = local_resource_rel(&rr.id, &rr.name, rr.source.as_ref(), &remote_resource_meta);
+    if !direction.pulls(&desired_rel_path) {
+      continue;
+    }
+    crate::names::remember(&mut mapping, &desired_rel_path, &rr.name);
+    let mut prev_from_old_rel: Option<ResourceMappingV1> = None;
+    if let Some(old_rel_path) = by_resource_id.get(&rr.id).cloned() {
+      if old_rel_path != desired_rel_path {
+        let old_abs = crate::normalize::local_path(root, &old_rel_path);
+        let new_abs = crate::normalize::local_path(root, &desired_rel_path);
+        if old_abs.exists() && old_abs != new_abs {
+          if new_abs.exists() {
+            archive_or_log(&vault_path, &old_rel_path);
+          } else if let Err(e) = move_file_with_fallback(&old_abs, &new_abs) {
+            summary.errors.push(format!(
+              "Failed to move renamed resource {} -> {}: {}",
+              old_rel_path, desired_rel_path, e
+            ));
+          }
+        }
+        prev_from_old_rel = mapping.resources.remove(&old_rel_path);
+      } else {
+        prev_from_old_rel = mapping.resources.get(&old_rel_path).cloned();
+      }
+    }
+    by_resource_id.insert(rr.id.clone(), desired_rel_path.clone());
+    let rel_path = desired_rel_path;
+
+    let abs_path = crate::normalize::local_path(root, &rel_path);
+    if crate::symlinks::check_write(&vault_path, &abs_path).is_err() {
+      summary.files_skipped += 1;
+      continue;
+    }
+    if let Some(parent) = abs_path.parent() {
+      if let Err(e) = fs::create_dir_all(parent) {
+        summary.errors.push(e.to_string());
+        continue;
+      }
+    }
+
+    let local_bytes = fs::read(&abs_path).ok();
+    let local_hash = local_bytes.as_ref().map(|b| sha256_hex(b)).unwrap_or_default();
+    let content_hash = sha256_hex(rr.markdown.as_bytes());
+
+    let prev = mapping.resources.get(&rel_path).cloned().or(prev_from_old_rel);
+    let prev_local_hash = prev.as_ref().map(|m| m.local_hash.clone()).unwrap_or_default();
+    let prev_remote_updated = prev.as_ref().map(|m| m.remote_updated_at.clone()).unwrap_or_default();
+
+    let local_modified = !prev_local_hash.is_empty() && local_hash != prev_local_hash;
+    let remote_newer =
+      !prev_remote_updated.is_empty() && crate::clock::is_after(&remote_updated_at, &prev_remote_updated);
+    let remote_hash = content_hash.clone();
+
+    if local_modified && !remote_newer && !direction.pushes(&rel_path) {
+      crate::direction::hold(&vault_path, &rel_path, &local_hash, &mut summary);
+      continue;
+    }
+    if local_modified && !remote_newer {
+      // Local changed since last sync and remote is not newer.
+      // Keep local content and push it upstream.
+      if let Some(bytes) = local_bytes.as_ref() {
+        let (local_markdown, _) = crate::encoding::decode(bytes);
+        let pushed_at = crate::clock::server_now_iso(&auth);
+        match update_project_resource(
+          &client,
+          &mut auth,
+          &rr.id,
+          &rr.name,
+          &local_markdown,
+          rr.source.as_ref(),
+          &pushed_at,
+        )
+        .await
+        {
+          Ok(row) => {
+            mapping.resources.insert(
+              rel_path.clone(),
+              ResourceMappingV1 {
+                resource_id: rr.id.clone(),
+                local_hash: local_hash.clone(),
+                remote_updated_at: row.updated_at.unwrap_or(pushed_at),
+              },
+            );
+            let _ = append_event(
+              &vault_path,
+              &SyncEvent {
+                ts: now_iso(),
+                kind: "push_resolve".to_string(),
+                path: rel_path.clone(),
+                detail: "Kept newer local resource edit and pushed it to remote.".to_string(),
+              },
+            );
+            continue;
+          }
+          Err(e) => {
+            summary
+              .errors
+              .push(format!("Failed to push newer local resource {} to remote: {}", rel_path, e));
+            // Fall back to conflict handling below to avoid silent data loss.
+          }
+        }
+      }
+    }
+
+    if local_modified && remote_newer {
+      let conflict_path = conflict_copy_path(&abs_path, "resource");
+      if let Err(e) = write_synced(&conflict_path, rr.markdown.as_bytes()) {
+        summary.errors.push(e.to_string());
+      }
+      let _ = append_event(
+        &vault_path,
+        &SyncEvent {
+          ts: now_iso(),
+          kind: "conflict".to_string(),
+          path: rel_path.clone(),
+          detail: format!("Remote resource update would overwrite local edits. Wrote {}", conflict_path.display()),
+        },
+      );
+      crate::activity::note_conflict(&vault_path, &rel_path, "Remote resource update would overwrite local edits.");
+      crate::notify::notify(
+        &vault_path,
+        crate::notify::NotifyKind::Conflict,
+        "Sync conflict",
+        &format!("{} changed both here and in Diregram. The remote version was saved alongside it.", rel_path),
+        Some(&rel_path),
+      );
+      conflicts += 1;
+      continue;
+    }
+
+    if local_hash == remote_hash {
+      mapping.resources.insert(
+        rel_path.clone(),
+        ResourceMappingV1 {
+          resource_id: rr.id.clone(),
+          local_hash,
+          remote_updated_at,
+        },
+      );
+      continue;
+    }
+
+    if let Err(e) = write_synced(&abs_path, rr.markdown.as_bytes()) {
+      summary.errors.push(e.to_string());
+      continue;
+    }
+
+    mapping.resources.insert(
+      rel_path.clone(),
+      ResourceMappingV1 {
+        resource_id: rr.id.clone(),
+        local_hash: content_hash,
+        remote_updated_at,
+      },
+    );
+  }
+
+  // Reconcile remote deletions (safe: archive local to `.diregram/trash/...`).
+  crate::volume::ensure_available(&vault_path)?;
+  // Files that aren't pulled keep their local copy and are recreated remotely by the next push.
+  mapping
+    .files
+    .retain(|rel, fm| remote_file_ids.contains(&fm.file_id) || direction.pulls(rel));
+  mapping
+    .resources
+    .retain(|rel, rm| remote_resource_ids.contains(&rm.resource_id) || direction.pulls(rel));
+  let mut to_remove_files: Vec<String> = Vec::new();
+  for (rel, fm) in &mapping.files {
+    if !remote_file_ids.contains(&fm.file_id) && scope.admits(rel) {
+      to_remove_files.push(rel.clone());
+    }
+  }
+  if crate::delete_guard::hold(&vault_path, &mut mapping, &to_remove_files) {
+    summary.warnings.push(format!(
+      "{} remote deletions are on hold until confirmed (mass-delete guard).",
+      to_remove_files.len()
+    ));
+  } else {
+    archive_remote_deleted(&vault_path, &mut mapping, to_remove_files, &mut summary);
+  }
+
+  let mut to_remove_resources: Vec<String> = Vec::new();
+  for (rel, rm) in &mapping.resources {
+    if !remote_resource_ids.contains(&rm.resource_id) {
+      to_remove_resources.push(rel.clone());
+    }
+  }
+  for rel in to_remove_resources {
+    archive_or_log(&vault_path, &rel);
+    mapping.resources.remove(&rel);
+    summary.resources_deleted += 1;
+    let _ = append_event(
+      &vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "pull_delete".to_string(),
+        path: rel.clone(),
+        detail: "Remote resource was deleted; archived local copy to .diregram/trash/".to_string(),
+      },
+    );
+  }
+
+  // Export RAG/KG into vault if KB updated since last export.
+  // This keeps `rag/` in sync even if the KB was rebuilt from the web app.
+  if let Ok(Some(rag_updated_at)) =
+    rag_export_into_vault(&client, &mut auth, &vault_path, &project_folder_id, &mapping.last_rag_export_at).await
+  {
+    mapping.last_rag_export_at = rag_updated_at;
+  }
+
+  // Filters the next pull's `updated_at`, so it has to be in server time.
+  mapping.last_pull_at = crate::clock::server_now_iso(&auth);
+  // A pull with errors runs in full again next time instead of being skipped.
+  mapping.remote_head = head.filter(|_| summary.errors.is_empty());
+  crate::names::prune(&mut mapping);
+  crate::routing::prune(&mut mapping);
+  mapping.updated_at = now_iso();
+  write_mapping(&vault_path, &mapping)?;
+  crate::trash::maybe_enforce_retention(&vault_path);
+  let _ = append_event(
+    &vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "pull".to_string(),
+      path: String::new(),
+      detail: format!(
+        "Pulled. Files created: {}, updated: {}, deleted: {}. Resources deleted: {}. Conflicts: {}. Errors: {}.",
+        summary.files_created,
+        summary.files_updated,
+        summary.files_deleted,
+        summary.resources_deleted,
+        conflicts,
+        summary.errors.len()
+      ),
+    },
+  );
+  Ok(summary)
+}
+
+#[tracing::instrument(name = "rag_export", skip_all, fields(vault = %vault_path), err)]
+pub async fn rag_export_once(vault_path: String, project_folder_id: String, auth: SupabaseAuth) -> Result<(), String> {
+  let root = Path::new(&vault_path);
+  if !root.exists() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let _mapping = crate::lock::mapping(&vault_path).await;
+  let client = reqwest::Client::new();
+  let mut auth = auth;
+  let mut mapping = match read_mapping(&vault_path)? {
+    Some(m) => m,
+    None => init_mapping(&vault_path, &project_folder_id, true, false)?,
+  };
+  if mapping.project_folder_id != project_folder_id {
+    return Err("mapping project_folder_id mismatch".to_string());
+  }
+  if let Some(rag_updated_at) =
+    rag_export_into_vault(&client, &mut auth, &vault_path, &project_folder_id, &mapping.last_rag_export_at).await?
+  {
+    mapping.last_rag_export_at = rag_updated_at;
+    mapping.updated_at = now_iso();
+    write_mapping(&vault_path, &mapping)?;
+  }
+  Ok(())
+}
+
@@ -0,0 +1,300 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Fences;
+use crate::config::read_config;
+use crate::sync::{append_event, diregram_dir, now_iso, read_mapping, rest_base, send_with_refresh, write_atomic, SupabaseAuth, SyncEvent};
+
+const REMOTE_TABLE: &str = "file_tags";
+/// File ids per delete request, to keep its URL short.
+const DELETE_BATCH_SIZE: usize = 50;
+const INSERT_BATCH_SIZE: usize = 500;
+
+/// A note's tags, and the size and mtime they were read at.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct FileTags {
+  mtime_ms: u64,
+  size: u64,
+  pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TagIndexV1 {
+  version: u32,
+  pub files: BTreeMap<String, FileTags>,
+  /// Project and tags (by file id) last written to the remote `file_tags` table.
+  remote_project_folder_id: Option<String>,
+  remote: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TagCount {
+  pub tag: String,
+  /// Notes carrying the tag itself (not counting nested `tag/...` ones).
+  pub files: u32,
+}
+
+fn index_path(vault_path: &str) -> PathBuf {
+  diregram_dir(vault_path).join("tags.json")
+}
+
+fn load_index(vault_path: &str) -> TagIndexV1 {
+  fs::read_to_string(index_path(vault_path))
+    .ok()
+    .and_then(|t| serde_json::from_str(&t).ok())
+    .unwrap_or_default()
+}
+
+fn save_index(vault_path: &str, index: &TagIndexV1) -> Result<(), String> {
+  fs::create_dir_all(diregram_dir(vault_path)).map_err(|e| e.to_string())?;
+  let text = serde_json::to_string_pretty(index).map_err(|e| e.to_string())?;
+  write_atomic(&index_path(vault_path), text).map_err(|e| e.to_string())
+}
+
+/// `#` dropped, lowercased and without stray slashes; `None` for what isn't a tag (empty, or
+/// only digits like `#123`).
+pub fn normalize(raw: &str) -> Option<String> {
+  let tag = raw.trim().trim_matches(|c| c == '"' || c == '\'').trim_start_matches('#').trim_matches('/').to_lowercase();
+  let valid = !tag.is_empty()
+    && tag.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+    && tag.chars().any(|c| !c.is_ascii_digit());
+  valid.then_some(tag)
+}
+
+/// Tags listed under `tags:` (or `tag:`) in YAML frontmatter: an inline `[a, b]` list, a
+/// comma- or space-separated scalar, or a block list of `- a` items.
+fn frontmatter_tags(yaml: &str, out: &mut BTreeSet<String>) {
+  let mut lines = yaml.lines().peekable();
+  while let Some(line) = lines.next() {
+    let Some((key, value)) = line.split_once(':') else { continue };
+    if !matches!(key.trim(), "tags" | "tag") || line.starts_with(char::is_whitespace) {
+      continue;
+    }
+    let value = value.trim();
+    if value.is_empty() {
+      while let Some(item) = lines.peek().and_then(|l| l.trim_start().strip_prefix('-')) {
+        out.extend(normalize(item));
+        lines.next();
+      }
+    } else {
+      let value = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(value);
+      out.extend(value.split(|c: char| c == ',' || c.is_whitespace()).filter_map(normalize));
+    }
+  }
+}
+
+/// Frontmatter tags and inline `#tags` of a note, sorted. An inline tag starts a line or follows
+/// whitespace; tags in code blocks and code spans don't count.
+pub fn parse(markdown: &str) -> Vec<String> {
+  let mut out = BTreeSet::new();
+  let mut body = markdown;
+  if let Some(rest) = markdown.strip_prefix("---\n") {
+    if let Some(end) = rest.find("\n---") {
+      frontmatter_tags(&rest[..end], &mut out);
+      body = rest[end + 4..].split_once('\n').map(|(_, b)| b).unwrap_or("");
+    }
+  }
+
+  let mut fences = Fences::default();
+  for line in body.lines() {
+    if fences.toggles(line) || fences.inside() {
+      continue;
+    }
+    for (i, part) in line.split('`').enumerate() {
+      if i % 2 == 1 {
+        continue;
+      }
+      let mut prev = ' ';
+      for (at, c) in part.char_indices() {
+        if c == '#' && prev.is_whitespace() {
+          let rest = &part[at + 1..];
+          let end = rest.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))).unwrap_or(rest.len());
+          out.extend(normalize(&rest[..end]));
+        }
+        prev = c;
+      }
+    }
+  }
+  out.into_iter().collect()
+}
+
+fn stamp(abs: &Path) -> Option<(u64, u64)> {
+  let meta = fs::metadata(abs).ok()?;
+  let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+  Some((mtime, meta.len()))
+}
+
+/// The vault's tag index, re-reading only notes whose size or mtime changed since it was saved.
+pub fn refresh(vault_path: &str) -> Result<TagIndexV1, String> {
+  if !Path::new(vault_path).is_dir() {
+    return Err("vault_path does not exist".to_string());
+  }
+  let mut index = load_index(vault_path);
+  let mut dirty = index.version != 1;
+  let mut files = BTreeMap::new();
+  for (abs, rel) in crate::notes::list(vault_path) {
+    let Some((mtime_ms, size)) = stamp(&abs) else { continue };
+    let known = index.files.remove(&rel).filter(|f| f.mtime_ms == mtime_ms && f.size == size);
+    let entry = match known {
+      Some(f) => f,
+      None => {
+        let Ok(bytes) = fs::read(&abs) else { continue };
+        dirty = true;
+        FileTags {
+          mtime_ms,
+          size,
+          tags: parse(&crate::encoding::decode(&bytes).0),
+        }
+      }
+    };
+    files.insert(rel, entry);
+  }
+  // Notes left over were deleted.
+  dirty |= !index.files.is_empty();
+  index.version = 1;
+  index.files = files;
+  if dirty {
+    save_index(vault_path, &index)?;
+  }
+  Ok(index)
+}
+
+/// Each note's tags, from the refreshed index.
+pub fn by_file(vault_path: &str) -> Result<BTreeMap<String, Vec<String>>, String> {
+  Ok(refresh(vault_path)?.files.into_iter().map(|(rel, f)| (rel, f.tags)).collect())
+}
+
+/// Whether `tag` is `wanted` or nested under it (`wanted/...`).
+pub fn under(tag: &str, wanted: &str) -> bool {
+  tag.strip_prefix(wanted).map(|rest| rest.is_empty() || rest.starts_with('/')).unwrap_or(false)
+}
+
+async fn delete_rows(client: &reqwest::Client, auth: &mut SupabaseAuth, project_folder_id: &str, file_ids: &[&String]) -> Result<(), String> {
+  for batch in file_ids.chunks(DELETE_BATCH_SIZE) {
+    let list = batch.iter().map(|id| format!("\"{}\"", id)).collect::<Vec<_>>().join(",");
+    let mut url = reqwest::Url::parse(&format!("{}/{}", rest_base(auth), REMOTE_TABLE)).map_err(|e| e.to_string())?;
+    {
+      let mut q = url.query_pairs_mut();
+      q.append_pair("owner_id", &format!("eq.{}", auth.owner_id));
+      q.append_pair("project_folder_id", &format!("eq.{}", project_folder_id));
+      q.append_pair("file_id", &format!("in.({})", list));
+    }
+    send_with_refresh(
+      client,
+      auth,
+      || client.delete(url.clone()),
+      |res| {
+        Box::pin(async move {
+          if !res.status().is_success() {
+            return Err(format!("{} delete failed: HTTP {}", REMOTE_TABLE, res.status()));
+          }
+          Ok(())
+        })
+      },
+    )
+    .await?;
+  }
+  Ok(())
+}
+
+async fn insert_rows(client: &reqwest::Client, auth: &mut SupabaseAuth, rows: &[serde_json::Value]) -> Result<(), String> {
+  let url = format!("{}/{}", rest_base(auth), REMOTE_TABLE);
+  for batch in rows.chunks(INSERT_BATCH_SIZE) {
+    send_with_refresh(
+      client,
+      auth,
+      || client.post(&url).header("Prefer", "return=minimal").json(&batch),
+      |res| {
+        Box::pin(async move {
+          if !res.status().is_success() {
+            let status = res.status();
+            let text = res.text().await.unwrap_or_default();
+            return Err(format!("{} insert failed: HTTP {}: {}", REMOTE_TABLE, status, text));
+          }
+          Ok(())
+        })
+      },
+    )
+    .await?;
+  }
+  Ok(())
+}
+
+/// Brings the project's rows in `file_tags` in line with the index: the rows of each synced note
+/// whose tags changed since the last run are replaced. Returns how many notes that was.
+async fn sync_remote(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth) -> Result<u32, String> {
+  let mut index = refresh(vault_path)?;
+  let mapping = read_mapping(vault_path)?.ok_or("the vault has not been synced yet")?;
+  let wanted: BTreeMap<String, Vec<String>> = index
+    .files
+    .iter()
+    .filter(|(_, f)| !f.tags.is_empty())
+    .filter_map(|(rel, f)| mapping.files.get(rel).map(|m| (m.file_id.clone(), f.tags.clone())))
+    .collect();
+  // Another project's rows are not ours to replace; start over for this one.
+  if index.remote_project_folder_id.as_deref() != Some(project_folder_id) {
+    index.remote.clear();
+  }
+  let changed: Vec<&String> = wanted
+    .keys()
+    .chain(index.remote.keys())
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .filter(|id| wanted.get(*id) != index.remote.get(*id))
+    .collect();
+  if changed.is_empty() && index.remote_project_folder_id.as_deref() == Some(project_folder_id) {
+    return Ok(0);
+  }
+
+  let client = reqwest::Client::new();
+  let mut auth = auth.clone();
+  delete_rows(&client, &mut auth, project_folder_id, &changed).await?;
+  let mut rows = Vec::new();
+  for id in &changed {
+    for tag in wanted.get(*id).into_iter().flatten() {
+      rows.push(serde_json::json!({
+        "owner_id": auth.owner_id,
+        "project_folder_id": project_folder_id,
+        "file_id": id,
+        "tag": tag,
+      }));
+    }
+  }
+  insert_rows(&client, &mut auth, &rows).await?;
+
+  let count = changed.len() as u32;
+  index.remote = wanted;
+  index.remote_project_folder_id = Some(project_folder_id.to_string());
+  save_index(vault_path, &index)?;
+  Ok(count)
+}
+
+/// Refreshes the index after a successful push and, with `tags.sync_remote`, updates the remote
+/// `file_tags` table, logging how it went. Never fails the sync.
+pub async fn after_push(vault_path: &str, project_folder_id: &str, auth: &SupabaseAuth) {
+  let enabled = read_config(vault_path).map(|c| c.tags.sync_remote).unwrap_or(false);
+  if !enabled {
+    let _ = refresh(vault_path);
+    return;
+  }
+  let (kind, detail) = match sync_remote(vault_path, project_folder_id, auth).await {
+    Ok(0) => return,
+    Ok(n) => ("tags", format!("Synced the tags of {} notes.", n)),
+    Err(e) => ("tags_error", format!("Syncing tags failed: {}", e)),
+  };
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: kind.to_string(),
+      path: String::new(),
+      detail,
+    },
+  );
+}
+
@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::Path;
+
+use crate::config::read_config;
+
+/// Vault-root file with more patterns, one per line; blank lines and `#` comments are skipped.
+/// The file itself is never pushed.
+pub const IGNORE_FILE: &str = ".diregramignore";
+/// The ignore file's name before the rename; still read (and never pushed) in vaults that have it.
+pub const LEGACY_IGNORE_FILE: &str = ".nexusmapignore";
+
+/// File-name patterns of editor swap/lock files and partial downloads, which are never notes.
+/// `*` matches any run of characters and `?` a single one.
+const BUILTIN_PATTERNS: &[&str] = &[
+  // Vim swap and write-test files.
+  "*.swp",
+  "*.swo",
+  "*.swx",
+  "4913",
+  // Emacs lock and autosave files, and generic backups.
+  ".#*",
+  "#*#",
+  "*~",
+  // Office and LibreOffice lock files.
+  "~$*",
+  ".~lock.*#",
+  // JetBrains safe-write files.
+  "*___jb_tmp___",
+  "*___jb_old___",
+  // Partial downloads.
+  "*.tmp",
+  "*.temp",
+  "*.part",
+  "*.partial",
+  "*.crdownload",
+  "*.download",
+  "*.部分",
+  // OS metadata.
+  ".DS_Store",
+  "Thumbs.db",
+  "desktop.ini",
+];
+
+/// Built-in patterns plus the vault's `tempfiles.patterns` and `.diregramignore`, loaded once per
+/// push.
+pub struct TempFileFilter {
+  extra: Vec<String>,
+}
+
+impl TempFileFilter {
+  pub fn load(vault_path: &str) -> Self {
+    let mut extra = read_config(vault_path).map(|c| c.tempfiles.patterns).unwrap_or_default();
+    for file in [IGNORE_FILE, LEGACY_IGNORE_FILE] {
+      let Ok(text) = fs::read_to_string(Path::new(vault_path).join(file)) else { continue };
+      extra.extend(
+        text
+          .lines()
+          .map(str::trim)
+          .filter(|l| !l.is_empty() && !l.starts_with('#'))
+          .map(str::to_string),
+      );
+    }
+    Self { extra }
+  }
+
+  pub fn matches(&self, path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+      return false;
+    };
+    name == IGNORE_FILE
+      || name == LEGACY_IGNORE_FILE
+      || BUILTIN_PATTERNS.iter().any(|p| wildcard_match(p, name))
+      || self.extra.iter().any(|p| wildcard_match(p, name))
+  }
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+  let p: Vec<char> = pattern.chars().collect();
+  let n: Vec<char> = name.chars().collect();
+  let (mut pi, mut ni) = (0, 0);
+  // Position of the last `*` and the name index it was tried at, for backtracking.
+  let mut star: Option<(usize, usize)> = None;
+  while ni < n.len() {
+    if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+      pi += 1;
+      ni += 1;
+    } else if pi < p.len() && p[pi] == '*' {
+      star = Some((pi, ni));
+      pi += 1;
+    } else if let Some((sp, sn)) = star {
+      pi = sp + 1;
+      ni = sn + 1;
+      star = Some((sp, sn + 1));
+    } else {
+      return false;
+    }
+  }
+  p[pi..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+/// Unused allowance carries over for at most this long, so short bursts go out at full speed.
+const MAX_BURST: Duration = Duration::from_secs(1);
+/// Throughput is averaged over this trailing window.
+const METER_WINDOW: Duration = Duration::from_secs(5);
+
+pub static UPLOAD: Lazy<Mutex<Direction>> = Lazy::new(|| Mutex::new(Direction::default()));
+pub static DOWNLOAD: Lazy<Mutex<Direction>> = Lazy::new(|| Mutex::new(Direction::default()));
+
+#[derive(Default)]
+pub struct Direction {
+  pub limit_bytes_per_sec: Option<u64>,
+  pub next_free: Option<Instant>,
+  samples: VecDeque<(Instant, u64)>,
+}
+
+impl Direction {
+  /// Books `bytes` against the limit and returns how long the caller must wait.
+  fn reserve(&mut self, bytes: u64) -> Duration {
+    let now = Instant::now();
+    self.samples.push_back((now, bytes));
+    while self.samples.front().map(|(t, _)| now.duration_since(*t) > METER_WINDOW).unwrap_or(false) {
+      self.samples.pop_front();
+    }
+    let Some(rate) = self.limit_bytes_per_sec.filter(|r| *r > 0) else {
+      self.next_free = None;
+      return Duration::ZERO;
+    };
+    let floor = now.checked_sub(MAX_BURST).unwrap_or(now);
+    let start = self.next_free.map(|t| t.max(floor)).unwrap_or(now);
+    let done = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+    self.next_free = Some(done);
+    done.saturating_duration_since(now)
+  }
+
+  fn bytes_per_sec(&self) -> u64 {
+    let now = Instant::now();
+    let total: u64 = self
+      .samples
+      .iter()
+      .filter(|(t, _)| now.duration_since(*t) <= METER_WINDOW)
+      .map(|(_, b)| *b)
+      .sum();
+    total / METER_WINDOW.as_secs()
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BandwidthLimit {
+  /// `None` or 0 means unlimited.
+  pub upload_kbps: Option<u64>,
+  pub download_kbps: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ThroughputReport {
+  pub upload_bytes_per_sec: u64,
+  pub download_bytes_per_sec: u64,
+  pub limit: BandwidthLimit,
+}
+
+async fn pace(dir: &Lazy<Mutex<Direction>>, bytes: u64) {
+  let wait = dir.lock().map(|mut d| d.reserve(bytes)).unwrap_or(Duration::ZERO);
+  if !wait.is_zero() {
+    tokio::time::sleep(wait).await;
+  }
+}
+
+fn download_limited() -> bool {
+  DOWNLOAD.lock().map(|d| d.limit_bytes_per_sec.unwrap_or(0) > 0).unwrap_or(false)
+}
+
+/// Drop-in for `RequestBuilder::send` used by all sync and RAG traffic. Paces the request body
+/// against the upload cap and, when a download cap is set, reads the response body in paced
+/// chunks before handing back an equivalent buffered response.
+pub async fn send(builder: reqwest::RequestBuilder) -> Result<reqwest::Response, reqwest::Error> {
+  let (client, req) = builder.build_split();
+  let req = req?;
+  let up = req.body().and_then(|b| b.as_bytes()).map(|b| b.len() as u64).unwrap_or(0);
+  pace(&UPLOAD, up).await;
+
+  let mut res = client.execute(req).await?;
+  if !download_limited() {
+    let down = res.content_length().unwrap_or(0);
+    crate::metrics::add_transfer(up, down);
+    pace(&DOWNLOAD, down).await;
+    return Ok(res);
+  }
+
+  let status = res.status();
+  let version = res.version();
+  let headers = res.headers().clone();
+  let mut body: Vec<u8> = Vec::new();
+  while let Some(chunk) = res.chunk().await? {
+    pace(&DOWNLOAD, chunk.len() as u64).await;
+    body.extend_from_slice(&chunk);
+  }
+  crate::metrics::add_transfer(up, body.len() as u64);
+  let mut buffered = http::Response::new(body);
+  *buffered.status_mut() = status;
+  *buffered.version_mut() = version;
+  *buffered.headers_mut() = headers;
+  Ok(reqwest::Response::from(buffered))
+}
+
+pub fn report() -> ThroughputReport {
+  let (up_bps, up_limit) = UPLOAD
+    .lock()
+    .map(|d| (d.bytes_per_sec(), d.limit_bytes_per_sec))
+    .unwrap_or((0, None));
+  let (down_bps, down_limit) = DOWNLOAD
+    .lock()
+    .map(|d| (d.bytes_per_sec(), d.limit_bytes_per_sec))
+    .unwrap_or((0, None));
+  ThroughputReport {
+    upload_bytes_per_sec: up_bps,
+    download_bytes_per_sec: down_bps,
+    limit: BandwidthLimit {
+      upload_kbps: up_limit.map(|b| b / 1024),
+      download_kbps: down_limit.map(|b| b / 1024),
+    },
+  }
+}
+
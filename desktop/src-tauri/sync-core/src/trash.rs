@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::config::{read_config, TrashConfig};
+use crate::sync::{
+  append_event, now_iso, read_mapping, to_rel_posix, trash_dir, write_mapping,
+  SyncEvent,
+};
+
+/// Retention is enforced from the pull loop, which runs every few seconds; sweep at most hourly.
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+static LAST_SWEEP: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Trash batch directories are named by `archive_file_to_trash` with this format.
+const TRASH_BATCH_FORMAT: &str = "%Y-%m-%dT%H%M%SZ";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashEntry {
+  /// Path relative to `.diregram/trash/`, e.g. `2024-05-01T101500Z/notes/a.md`.
+  pub trash_rel: String,
+  /// Original vault-relative path.
+  pub rel_path: String,
+  pub archived_at: String,
+  pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashRelinkResult {
+  pub rel_path: String,
+  pub file_id: String,
+  /// True when the remote row kept its original id (so links and history still resolve).
+  pub reused_file_id: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TrashPurgeSummary {
+  pub files: u32,
+  pub bytes: u64,
+}
+
+pub struct TrashBatch {
+  pub name: String,
+  pub archived: Option<NaiveDateTime>,
+  bytes: u64,
+}
+
+fn batch_time(name: &str) -> Option<NaiveDateTime> {
+  NaiveDateTime::parse_from_str(name, TRASH_BATCH_FORMAT).ok()
+}
+
+pub fn dir_size(dir: &Path) -> (u32, u64) {
+  let mut files = 0u32;
+  let mut bytes = 0u64;
+  for e in WalkDir::new(dir).into_iter().filter_map(Result::ok) {
+    if e.file_type().is_file() {
+      files += 1;
+      bytes += e.metadata().map(|m| m.len()).unwrap_or(0);
+    }
+  }
+  (files, bytes)
+}
+
+/// Oldest first. Unparseable names sort first so stray folders are the first to go.
+pub fn list_batches(vault_path: &str) -> Vec<TrashBatch> {
+  let Ok(rd) = fs::read_dir(trash_dir(vault_path)) else { return Vec::new() };
+  let mut out: Vec<TrashBatch> = rd
+    .filter_map(Result::ok)
+    .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+    .map(|e| {
+      let name = e.file_name().to_string_lossy().to_string();
+      let (_, bytes) = dir_size(&e.path());
+      TrashBatch {
+        archived: batch_time(&name),
+        name,
+        bytes,
+      }
+    })
+    .collect();
+  out.sort_by(|a, b| a.name.cmp(&b.name));
+  out
+}
+
+/// Resolves a `trash_rel` from the UI, refusing anything that escapes the trash folder.
+pub fn resolve_trash_rel(vault_path: &str, trash_rel: &str) -> Result<(PathBuf, String, String), String> {
+  let rel = trash_rel.trim().trim_start_matches('/').replace('\\', "/");
+  if rel.split('/').any(|seg| seg.is_empty() || seg == "." || seg == "..") {
+    return Err(format!("invalid trash path: {}", trash_rel));
+  }
+  let Some((batch, original)) = rel.split_once('/') else {
+    return Err(format!("invalid trash path: {}", trash_rel));
+  };
+  Ok((trash_dir(vault_path).join(&rel), batch.to_string(), original.to_string()))
+}
+
+pub fn remove_batch_if_empty(vault_path: &str, batch: &str) {
+  let dir = trash_dir(vault_path).join(batch);
+  let (files, _) = dir_size(&dir);
+  if files == 0 {
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
+
+/// Picks `name (restored).ext`, `name (restored 2).ext`, ... when the original path is taken again.
+pub fn free_restore_path(vault_path: &str, rel: &str) -> String {
+  let root = Path::new(vault_path);
+  if !root.join(rel).exists() {
+    return rel.to_string();
+  }
+  let (dir, file) = match rel.rsplit_once('/') {
+    Some((d, f)) => (format!("{}/", d), f.to_string()),
+    None => (String::new(), rel.to_string()),
+  };
+  let (stem, ext) = match file.rsplit_once('.') {
+    Some((s, e)) if !s.is_empty() => (s.to_string(), format!(".{}", e)),
+    _ => (file.clone(), String::new()),
+  };
+  let mut n = 1;
+  loop {
+    let label = if n == 1 { "restored".to_string() } else { format!("restored {}", n) };
+    let candidate = format!("{}{} ({}){}", dir, stem, label, ext);
+    if !root.join(&candidate).exists() {
+      return candidate;
+    }
+    n += 1;
+  }
+}
+
+/// Works out where a trashed file will be restored to: `(trash file, batch name, vault-relative target)`.
+pub fn plan_restore(vault_path: &str, trash_rel: &str) -> Result<(PathBuf, String, String), String> {
+  let (src, batch, original) = resolve_trash_rel(vault_path, trash_rel)?;
+  if !src.is_file() {
+    return Err(format!("not found in trash: {}", trash_rel));
+  }
+  let rel = free_restore_path(vault_path, &original);
+  Ok((src, batch, rel))
+}
+
+pub fn move_out_of_trash(vault_path: &str, src: &Path, batch: &str, rel: &str) -> Result<(), String> {
+  let dst = Path::new(vault_path).join(rel);
+  if let Some(parent) = dst.parent() {
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+  }
+  if fs::rename(src, &dst).is_err() {
+    fs::copy(src, &dst).map_err(|e| e.to_string())?;
+    fs::remove_file(src).map_err(|e| e.to_string())?;
+  }
+  remove_batch_if_empty(vault_path, batch);
+  Ok(())
+}
+
+/// Drops remembered remote mappings for trash entries that no longer exist on disk.
+pub fn forget_trashed(vault_path: &str, keep: impl Fn(&str) -> bool) {
+  let Ok(Some(mut mapping)) = read_mapping(vault_path) else { return };
+  let before = mapping.trashed.len();
+  mapping.trashed.retain(|k, _| keep(k));
+  if mapping.trashed.len() != before {
+    if let Err(e) = write_mapping(vault_path, &mapping) {
+      tracing::warn!(vault = vault_path, error = %e, "could not update trashed mappings");
+    }
+  }
+}
+
+pub fn purge_batches(vault_path: &str, names: &[String]) -> TrashPurgeSummary {
+  let mut summary = TrashPurgeSummary::default();
+  for name in names {
+    let dir = trash_dir(vault_path).join(name);
+    let (files, bytes) = dir_size(&dir);
+    if fs::remove_dir_all(&dir).is_ok() {
+      summary.files += files;
+      summary.bytes += bytes;
+    }
+  }
+  if !names.is_empty() {
+    forget_trashed(vault_path, |k| !names.iter().any(|n| k.starts_with(&format!("{}/", n))));
+  }
+  summary
+}
+
+fn enforce_retention(vault_path: &str, policy: &TrashConfig) -> TrashPurgeSummary {
+  let batches = list_batches(vault_path);
+  let mut doomed: Vec<String> = Vec::new();
+  if let Some(days) = policy.max_age_days {
+    let cutoff = Utc::now().naive_utc() - chrono::Duration::days(days as i64);
+    for b in &batches {
+      if b.archived.map(|t| t < cutoff).unwrap_or(false) {
+        doomed.push(b.name.clone());
+      }
+    }
+  }
+  if let Some(max_bytes) = policy.max_bytes {
+    let mut total: u64 = batches.iter().filter(|b| !doomed.contains(&b.name)).map(|b| b.bytes).sum();
+    for b in &batches {
+      if total <= max_bytes {
+        break;
+      }
+      if !doomed.contains(&b.name) {
+        total = total.saturating_sub(b.bytes);
+        doomed.push(b.name.clone());
+      }
+    }
+  }
+  purge_batches(vault_path, &doomed)
+}
+
+/// Called after each pull. Applies the vault's trash policy at most once per sweep interval.
+pub fn maybe_enforce_retention(vault_path: &str) {
+  {
+    let Ok(mut guard) = LAST_SWEEP.lock() else { return };
+    if let Some(last) = guard.get(vault_path) {
+      if last.elapsed() < RETENTION_SWEEP_INTERVAL {
+        return;
+      }
+    }
+    guard.insert(vault_path.to_string(), Instant::now());
+  }
+  let Ok(cfg) = read_config(vault_path) else { return };
+  let summary = enforce_retention(vault_path, &cfg.trash);
+  if summary.files > 0 {
+    let _ = append_event(
+      vault_path,
+      &SyncEvent {
+        ts: now_iso(),
+        kind: "trash_retention".to_string(),
+        path: ".diregram/trash/".to_string(),
+        detail: format!("Purged {} trashed files ({} bytes) per retention policy.", summary.files, summary.bytes),
+      },
+    );
+  }
+}
+
+/// Everything in the trash, newest batch first.
+pub fn entries(vault_path: &str) -> Vec<TrashEntry> {
+  let root = trash_dir(vault_path);
+  let mut out: Vec<TrashEntry> = Vec::new();
+  for b in list_batches(vault_path).into_iter().rev() {
+    let archived_at = b.archived.map(|t| t.and_utc().to_rfc3339()).unwrap_or_else(|| b.name.clone());
+    let batch_dir = root.join(&b.name);
+    for e in WalkDir::new(&batch_dir).sort_by_file_name().into_iter().filter_map(Result::ok) {
+      if !e.file_type().is_file() {
+        continue;
+      }
+      let Some(rel_path) = to_rel_posix(&batch_dir, e.path()) else { continue };
+      out.push(TrashEntry {
+        trash_rel: format!("{}/{}", b.name, rel_path),
+        rel_path,
+        archived_at: archived_at.clone(),
+        bytes: e.metadata().map(|m| m.len()).unwrap_or(0),
+      });
+    }
+  }
+  out
+}
+
@@ -0,0 +1,225 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+
+use crate::status::{SyncState, VaultSyncStatus};
+use crate::sync::{load_auth_tokens, write_atomic, SupabaseAuth};
+
+const FILE_NAME: &str = "vaults.json";
+
+static DIR: OnceCell<PathBuf> = OnceCell::new();
+/// Serializes read-modify-write cycles on the file.
+static FILE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// `<app config>/vaults.json`: every vault the user set up, with how each one is synced. Like
+/// `autosync.json`, it holds the account a vault syncs with but not its tokens.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VaultRegistryV1 {
+  version: u32,
+  pub vaults: Vec<RegisteredVault>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegisteredVault {
+  pub vault_path: String,
+  pub project_folder_id: String,
+  /// Shown in vault pickers; the folder name unless given.
+  pub name: String,
+  pub account: VaultAccount,
+  #[serde(default)]
+  pub settings: VaultSettings,
+  pub registered_at: String,
+}
+
+/// The account a vault syncs with. Its session tokens are read from secure storage.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VaultAccount {
+  pub supabase_url: String,
+  pub supabase_anon_key: String,
+  pub owner_id: String,
+  #[serde(default)]
+  pub profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+  /// Keep the local file and write the remote version beside it.
+  #[default]
+  KeepBoth,
+  /// Keep the local file; the next push overwrites the remote version.
+  PreferLocal,
+  /// Take the remote version; the local edits are written beside it.
+  PreferRemote,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct VaultSettings {
+  /// Push local changes as they happen.
+  pub watch: bool,
+  /// Poll the project for remote changes.
+  pub pull: bool,
+  pub pull_interval_ms: u64,
+  pub max_pull_interval_ms: u64,
+  /// Vault-relative folders to sync notes in; empty syncs the whole vault. `resources/` is
+  /// always synced.
+  pub selective_sync: Vec<String>,
+  pub conflict_policy: ConflictPolicy,
+}
+
+impl Default for VaultSettings {
+  fn default() -> Self {
+    Self {
+      watch: true,
+      pull: true,
+      pull_interval_ms: 5000,
+      max_pull_interval_ms: 60_000,
+      selective_sync: Vec::new(),
+      conflict_policy: ConflictPolicy::KeepBoth,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct VaultListEntry {
+  #[serde(flatten)]
+  pub vault: RegisteredVault,
+  /// `None` until the vault has synced (or tried to) since launch.
+  pub status: Option<VaultSyncStatus>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct VaultList {
+  pub vaults: Vec<VaultListEntry>,
+  /// Worst state across all vaults, as the tray shows it.
+  pub state: SyncState,
+  pub last_pull_at: Option<String>,
+}
+
+/// Outcome of starting or stopping one vault in `vaults_start_all` / `vaults_stop_all`.
+#[derive(Debug, Serialize, Clone)]
+pub struct VaultRunResult {
+  pub vault_path: String,
+  pub error: Option<String>,
+}
+
+/// Which notes of a vault take part in sync, per its `selective_sync` setting.
+pub struct SyncScope {
+  folders: Vec<String>,
+}
+
+impl SyncScope {
+  pub fn load(vault_path: &str) -> Self {
+    Self {
+      folders: settings(vault_path).selective_sync,
+    }
+  }
+
+  pub fn admits(&self, rel: &str) -> bool {
+    self.folders.is_empty() || self.folders.iter().any(|f| rel == f || rel.starts_with(&format!("{}/", f)))
+  }
+
+  /// Folders on the way to a selected one are synced too, so it has somewhere to live.
+  pub fn admits_dir(&self, rel: &str) -> bool {
+    rel.is_empty() || self.admits(rel) || self.folders.iter().any(|f| f.starts_with(&format!("{}/", rel)))
+  }
+}
+
+fn file_path() -> Option<PathBuf> {
+  DIR.get().map(|d| d.join(FILE_NAME))
+}
+
+pub fn load() -> VaultRegistryV1 {
+  file_path()
+    .and_then(|p| fs::read_to_string(p).ok())
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+pub fn update<T>(f: impl FnOnce(&mut VaultRegistryV1) -> Result<T, String>) -> Result<T, String> {
+  let path = file_path().ok_or_else(|| "vault registry is not available".to_string())?;
+  let _guard = FILE_LOCK.lock().map_err(|e| e.to_string())?;
+  let mut registry = load();
+  registry.version = 1;
+  let out = f(&mut registry)?;
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+  }
+  let text = serde_json::to_string_pretty(&registry).map_err(|e| e.to_string())?;
+  write_atomic(&path, text).map_err(|e| e.to_string())?;
+  Ok(out)
+}
+
+/// Sets where the registry lives. Call once, at startup.
+pub fn set_dir(dir: PathBuf) {
+  let _ = DIR.set(dir);
+}
+
+/// The vault's registry settings; defaults for vaults that aren't registered (and in the CLI).
+pub fn settings(vault_path: &str) -> VaultSettings {
+  load()
+    .vaults
+    .into_iter()
+    .find(|v| v.vault_path == vault_path)
+    .map(|v| v.settings)
+    .unwrap_or_default()
+}
+
+/// Every registered vault.
+pub fn registered() -> Vec<RegisteredVault> {
+  load().vaults
+}
+
+/// Follows a vault to the project `sync_relink` linked it to.
+pub fn relinked(vault_path: &str, project_folder_id: &str) {
+  let res = update(|r| {
+    for v in r.vaults.iter_mut().filter(|v| v.vault_path == vault_path) {
+      v.project_folder_id = project_folder_id.to_string();
+    }
+    Ok(())
+  });
+  if let Err(e) = res {
+    tracing::warn!(vault = vault_path, error = %e, "could not update the vault registry");
+  }
+}
+
+pub fn normalize_settings(mut settings: VaultSettings) -> Result<VaultSettings, String> {
+  if settings.pull_interval_ms == 0 {
+    return Err("pull_interval_ms must be greater than 0".to_string());
+  }
+  settings.max_pull_interval_ms = settings.max_pull_interval_ms.max(settings.pull_interval_ms);
+  let mut folders = Vec::new();
+  for raw in &settings.selective_sync {
+    let folder = crate::normalize::nfc(raw.replace('\\', "/").trim_matches('/'));
+    if folder.is_empty() {
+      continue;
+    }
+    if folder.split('/').any(|c| c == ".." || c == ".") {
+      return Err(format!("selective_sync folder must be vault-relative: {}", raw));
+    }
+    folders.push(folder);
+  }
+  folders.sort();
+  folders.dedup();
+  settings.selective_sync = folders;
+  Ok(settings)
+}
+
+pub fn saved_auth(account: &VaultAccount) -> Result<SupabaseAuth, String> {
+  let (access_token, refresh_token) = load_auth_tokens(account.profile.as_deref())?
+    .ok_or_else(|| "no saved session; sign in to start sync".to_string())?;
+  Ok(SupabaseAuth {
+    supabase_url: account.supabase_url.clone(),
+    supabase_anon_key: account.supabase_anon_key.clone(),
+    access_token,
+    refresh_token: Some(refresh_token),
+    owner_id: account.owner_id.clone(),
+    profile: account.profile.clone(),
+  })
+}
+
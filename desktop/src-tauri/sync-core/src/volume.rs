@@ -0,0 +1,101 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::sync::{append_event, mapping_path, now_iso, SyncEvent};
+
+/// Unavailable vaults and when they went away.
+static UNAVAILABLE: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// Vaults seen linked (with a mapping) since launch. If one later shows up without its mapping,
+/// the folder is most likely an empty mount point with the volume unmounted.
+static LINKED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Emitted as `sync://vault_unavailable` and `sync://vault_available`.
+#[derive(Debug, Serialize, Clone)]
+pub struct VaultAvailability {
+  pub vault_path: String,
+  /// When the vault went away.
+  pub since: String,
+}
+
+/// Whether the vault folder is there to sync: it exists and, if it was linked earlier, still
+/// holds its mapping.
+pub fn available(vault_path: &str) -> bool {
+  if !Path::new(vault_path).is_dir() {
+    return false;
+  }
+  let linked = mapping_path(vault_path).is_file();
+  let Ok(mut seen) = LINKED.lock() else { return true };
+  if linked {
+    seen.insert(vault_path.to_string());
+    true
+  } else {
+    !seen.contains(vault_path)
+  }
+}
+
+/// For runs to check before acting on what they found missing locally: a folder that vanished
+/// mid-run would otherwise read as every file having been deleted.
+pub fn ensure_available(vault_path: &str) -> Result<(), String> {
+  if Path::new(vault_path).is_dir() && mapping_path(vault_path).is_file() {
+    return Ok(());
+  }
+  Err("vault folder is unavailable (its drive may have been removed or unmounted)".to_string())
+}
+
+/// Records that the vault went away. Returns false if it was already known to be unavailable.
+/// Nothing is written into the vault until it is back, so an empty mount point stays empty.
+pub fn mark_unavailable(vault_path: &str) -> bool {
+  let since = now_iso();
+  {
+    let Ok(mut guard) = UNAVAILABLE.lock() else { return false };
+    if guard.contains_key(vault_path) {
+      return false;
+    }
+    guard.insert(vault_path.to_string(), since.clone());
+  }
+  tracing::warn!(vault = vault_path, "vault folder is unavailable; pausing its sync");
+  crate::status::set_unavailable(vault_path, true);
+  crate::notify::notify(
+    vault_path,
+    crate::notify::NotifyKind::VaultUnavailable,
+    "Diregram vault unavailable",
+    &format!("{} can't be reached; its sync resumes when it is back.", vault_path),
+    None,
+  );
+  emit("sync://vault_unavailable", vault_path, since);
+  true
+}
+
+/// Records that an unavailable vault is back, logging the outage in its event log. Returns false
+/// if it wasn't unavailable.
+pub fn mark_available(vault_path: &str) -> bool {
+  let Some(since) = UNAVAILABLE.lock().ok().and_then(|mut g| g.remove(vault_path)) else {
+    return false;
+  };
+  let _ = append_event(
+    vault_path,
+    &SyncEvent {
+      ts: now_iso(),
+      kind: "vault_unavailable".to_string(),
+      path: String::new(),
+      detail: format!("Vault folder was unavailable from {}; sync resumed.", since),
+    },
+  );
+  crate::status::set_unavailable(vault_path, false);
+  emit("sync://vault_available", vault_path, since);
+  true
+}
+
+fn emit(event: &str, vault_path: &str, since: String) {
+  crate::host::emit(
+    event,
+    VaultAvailability {
+      vault_path: vault_path.to_string(),
+      since,
+    },
+  );
+}
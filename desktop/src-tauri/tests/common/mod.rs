@@ -0,0 +1,695 @@
+//! Test harness: an in-process stand-in for the Supabase endpoints the sync engine calls, fixture
+//! vaults in temp directories, and a runner for the app binary's `--cli` mode.
+//!
+//! The mock implements the slice of PostgREST the engine relies on (`eq`, `neq`, `gt`, `gte`,
+//! `lt`, `lte`, `is.null` and `in` filters, `select`, `order`, `limit`, `Prefer: count=exact`,
+//! insert/update/delete with `return=representation`) over JSON rows kept in memory. Like the real
+//! schema, file rows get a `rev` that is bumped on every content change. RPCs answer 404, which
+//! the engine treats as an older database and falls back from. Rows marked `viewer_only` stand in
+//! for rows of a shared project the user may only read: updates and deletes skip them, as
+//! row-level security would. File writes are stamped with the `x-diregram-device` request header
+//! in `last_modified_by_device`, like the schema's trigger.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+
+pub const OWNER_ID: &str = "00000000-0000-4000-8000-00000000beef";
+/// Passed to every CLI run as `DIREGRAM_DEVICE_ID`.
+pub const DEVICE_ID: &str = "test-device";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_DIR: AtomicU64 = AtomicU64::new(1);
+
+fn new_id() -> String {
+  format!("00000000-0000-4000-8000-{:012x}", NEXT_ID.fetch_add(1, Ordering::SeqCst))
+}
+
+fn now() -> String {
+  Utc::now().to_rfc3339()
+}
+
+type Db = HashMap<String, Vec<Map<String, Value>>>;
+
+pub struct MockSupabase {
+  url: String,
+  db: Arc<Mutex<Db>>,
+}
+
+impl MockSupabase {
+  pub fn start() -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let db: Arc<Mutex<Db>> = Arc::new(Mutex::new(HashMap::new()));
+    let shared = db.clone();
+    std::thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let db = shared.clone();
+        std::thread::spawn(move || handle_connection(stream, &db));
+      }
+    });
+    Self { url, db }
+  }
+
+  pub fn url(&self) -> &str {
+    &self.url
+  }
+
+  /// Inserts a row as the server would (id, timestamps and `rev` filled in) and returns it.
+  pub fn insert(&self, table: &str, row: Value) -> Value {
+    let mut db = self.db.lock().unwrap();
+    Value::Object(insert_row(&mut db, table, row.as_object().cloned().unwrap_or_default()))
+  }
+
+  /// A project root folder, as the web app creates it.
+  pub fn create_project(&self, name: &str) -> String {
+    let row = self.insert("folders", json!({ "name": name, "parent_id": null, "owner_id": OWNER_ID }));
+    row["id"].as_str().unwrap().to_string()
+  }
+
+  pub fn rows(&self, table: &str) -> Vec<Value> {
+    let db = self.db.lock().unwrap();
+    db.get(table)
+      .map(|rows| rows.iter().cloned().map(Value::Object).collect())
+      .unwrap_or_default()
+  }
+
+  pub fn file_by_name(&self, name: &str) -> Option<Value> {
+    self.rows("files").into_iter().find(|r| r["name"] == name)
+  }
+
+  /// Changes a file's content the way the web editor does: new `rev` and `updated_at`, and the
+  /// checksum cleared (it is only written by the desktop app).
+  pub fn edit_file(&self, id: &str, content: &str) {
+    let mut db = self.db.lock().unwrap();
+    let row = db
+      .get_mut("files")
+      .and_then(|rows| rows.iter_mut().find(|r| r["id"] == id))
+      .expect("file row");
+    let rev = row.get("rev").and_then(Value::as_i64).unwrap_or(0);
+    row.insert("content".into(), json!(content));
+    row.insert("content_sha256".into(), Value::Null);
+    row.insert("rev".into(), json!(rev + 1));
+    row.insert("updated_at".into(), json!(now()));
+  }
+
+  pub fn delete(&self, table: &str, id: &str) {
+    let mut db = self.db.lock().unwrap();
+    if let Some(rows) = db.get_mut(table) {
+      rows.retain(|r| r["id"] != id);
+    }
+  }
+}
+
+fn insert_row(db: &mut Db, table: &str, mut row: Map<String, Value>) -> Map<String, Value> {
+  row.entry("id").or_insert_with(|| json!(new_id()));
+  if !row.get("updated_at").map(Value::is_string).unwrap_or(false) {
+    row.insert("updated_at".into(), json!(now()));
+  }
+  row.entry("created_at").or_insert_with(|| json!(now()));
+  if table == "files" {
+    row.insert("rev".into(), json!(1));
+    row.entry("content_sha256").or_insert(Value::Null);
+  }
+  db.entry(table.to_string()).or_default().push(row.clone());
+  row
+}
+
+struct Request {
+  method: String,
+  path: String,
+  query: Vec<(String, String)>,
+  headers: HashMap<String, String>,
+  body: Vec<u8>,
+}
+
+struct Response {
+  status: u16,
+  body: String,
+  headers: Vec<(String, String)>,
+}
+
+impl Response {
+  fn json(status: u16, body: Value) -> Self {
+    Self {
+      status,
+      body: body.to_string(),
+      headers: Vec::new(),
+    }
+  }
+
+  fn error(status: u16, message: &str) -> Self {
+    Self::json(status, json!({ "message": message }))
+  }
+}
+
+fn url_decode(s: &str) -> String {
+  let bytes = s.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'+' => out.push(b' '),
+      b'%' if i + 2 < bytes.len() => {
+        let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+        match u8::from_str_radix(hex, 16) {
+          Ok(b) => {
+            out.push(b);
+            i += 2;
+          }
+          Err(_) => out.push(b'%'),
+        }
+      }
+      b => out.push(b),
+    }
+    i += 1;
+  }
+  String::from_utf8_lossy(&out).into_owned()
+}
+
+fn read_request(stream: &mut TcpStream) -> Option<Request> {
+  let mut reader = BufReader::new(stream.try_clone().ok()?);
+  let mut line = String::new();
+  reader.read_line(&mut line).ok()?;
+  let mut parts = line.split_whitespace();
+  let method = parts.next()?.to_string();
+  let target = parts.next()?.to_string();
+
+  let mut headers = HashMap::new();
+  loop {
+    let mut h = String::new();
+    reader.read_line(&mut h).ok()?;
+    let h = h.trim_end();
+    if h.is_empty() {
+      break;
+    }
+    if let Some((k, v)) = h.split_once(':') {
+      headers.insert(k.trim().to_ascii_lowercase(), v.trim().to_string());
+    }
+  }
+  let len = headers.get("content-length").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+  let mut body = vec![0u8; len];
+  reader.read_exact(&mut body).ok()?;
+
+  let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+  let query = query
+    .split('&')
+    .filter(|p| !p.is_empty())
+    .map(|p| {
+      let (k, v) = p.split_once('=').unwrap_or((p, ""));
+      (url_decode(k), url_decode(v))
+    })
+    .collect();
+  Some(Request {
+    method,
+    path: path.to_string(),
+    query,
+    headers,
+    body,
+  })
+}
+
+fn handle_connection(mut stream: TcpStream, db: &Mutex<Db>) {
+  let Some(req) = read_request(&mut stream) else { return };
+  let res = route(&req, db);
+  let reason = match res.status {
+    200 => "OK",
+    201 => "Created",
+    204 => "No Content",
+    400 => "Bad Request",
+    _ => "Not Found",
+  };
+  let body = if req.method == "HEAD" || res.status == 204 { "" } else { res.body.as_str() };
+  let mut head = format!(
+    "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+    res.status,
+    reason,
+    body.len()
+  );
+  for (k, v) in &res.headers {
+    head.push_str(&format!("{}: {}\r\n", k, v));
+  }
+  head.push_str("\r\n");
+  let _ = stream.write_all(head.as_bytes());
+  let _ = stream.write_all(body.as_bytes());
+}
+
+fn route(req: &Request, db: &Mutex<Db>) -> Response {
+  if req.path == "/auth/v1/health" {
+    return Response::json(200, json!({}));
+  }
+  if let Some(object) = req.path.strip_prefix("/storage/v1/object/") {
+    return storage(req, db, object);
+  }
+  if let Some(path) = req.path.strip_prefix("/dav/") {
+    return dav(req, db, &url_decode(path));
+  }
+  let Some(table) = req.path.strip_prefix("/rest/v1/") else {
+    return Response::error(404, "not found");
+  };
+  if table.starts_with("rpc/") {
+    return Response::error(404, "function not found");
+  }
+  let mut db = db.lock().unwrap();
+  match req.method.as_str() {
+    "GET" | "HEAD" => select(req, &db, table),
+    "POST" => {
+      let Ok(body) = serde_json::from_slice::<Value>(&req.body) else {
+        return Response::error(400, "bad json");
+      };
+      let mut rows: Vec<Map<String, Value>> = match body {
+        Value::Array(items) => items.into_iter().filter_map(|v| v.as_object().cloned()).collect(),
+        Value::Object(o) => vec![o],
+        _ => return Response::error(400, "bad body"),
+      };
+      if let (Some(device), "files") = (req.headers.get("x-diregram-device"), table) {
+        for r in &mut rows {
+          r.insert("last_modified_by_device".into(), json!(device));
+        }
+      }
+      let out: Vec<Value> = rows.into_iter().map(|r| Value::Object(insert_row(&mut db, table, r))).collect();
+      Response::json(201, Value::Array(out))
+    }
+    "PATCH" => {
+      let Ok(Value::Object(patch)) = serde_json::from_slice::<Value>(&req.body) else {
+        return Response::error(400, "bad body");
+      };
+      let filters = match filters(req) {
+        Ok(f) => f,
+        Err(e) => return Response::error(400, &e),
+      };
+      let mut out = Vec::new();
+      for row in db.entry(table.to_string()).or_default().iter_mut() {
+        if !filters.iter().all(|f| f.matches(row)) || row.get("viewer_only") == Some(&json!(true)) {
+          continue;
+        }
+        let content_changed = patch.get("content").map(|c| Some(c) != row.get("content")).unwrap_or(false);
+        for (k, v) in &patch {
+          row.insert(k.clone(), v.clone());
+        }
+        if !patch.contains_key("updated_at") {
+          row.insert("updated_at".into(), json!(now()));
+        }
+        if let (Some(device), "files") = (req.headers.get("x-diregram-device"), table) {
+          row.insert("last_modified_by_device".into(), json!(device));
+        }
+        if table == "files" && content_changed {
+          let rev = row.get("rev").and_then(Value::as_i64).unwrap_or(0);
+          row.insert("rev".into(), json!(rev + 1));
+        }
+        out.push(Value::Object(row.clone()));
+      }
+      Response::json(200, Value::Array(out))
+    }
+    "DELETE" => {
+      let filters = match filters(req) {
+        Ok(f) => f,
+        Err(e) => return Response::error(400, &e),
+      };
+      if let Some(rows) = db.get_mut(table) {
+        rows.retain(|r| !filters.iter().all(|f| f.matches(r)) || r.get("viewer_only") == Some(&json!(true)));
+      }
+      Response::json(204, Value::Null)
+    }
+    _ => Response::error(400, "unsupported method"),
+  }
+}
+
+/// Storage objects are rows of `storage_objects` holding their path and size.
+fn storage(req: &Request, db: &Mutex<Db>, object: &str) -> Response {
+  let mut db = db.lock().unwrap();
+  let objects = db.entry("storage_objects".to_string()).or_default();
+  let existing = objects.iter().position(|o| o["path"] == json!(object));
+  match (req.method.as_str(), existing) {
+    ("POST", Some(_)) => Response::error(409, "The resource already exists"),
+    ("POST", None) => {
+      let mut row = Map::new();
+      row.insert("path".to_string(), json!(object));
+      row.insert("size".to_string(), json!(req.body.len()));
+      objects.push(row);
+      Response::json(200, json!({ "Key": object }))
+    }
+    ("DELETE", Some(i)) => {
+      objects.remove(i);
+      Response::json(200, json!({}))
+    }
+    _ => Response::error(404, "Object not found"),
+  }
+}
+
+/// A WebDAV share: `dav_files` rows hold a path and its text, `dav_collections` rows a path.
+fn dav(req: &Request, db: &Mutex<Db>, path: &str) -> Response {
+  let mut db = db.lock().unwrap();
+  let mut row = Map::new();
+  row.insert("path".to_string(), json!(path));
+  match req.method.as_str() {
+    "MKCOL" => {
+      let collections = db.entry("dav_collections".to_string()).or_default();
+      if collections.iter().any(|c| c["path"] == json!(path)) {
+        return Response::error(405, "exists");
+      }
+      collections.push(row);
+      Response::json(201, json!({}))
+    }
+    "PUT" => {
+      let files = db.entry("dav_files".to_string()).or_default();
+      files.retain(|f| f["path"] != json!(path));
+      row.insert("content".to_string(), json!(String::from_utf8_lossy(&req.body)));
+      files.push(row);
+      Response::json(201, json!({}))
+    }
+    "DELETE" => {
+      let files = db.entry("dav_files".to_string()).or_default();
+      let before = files.len();
+      files.retain(|f| f["path"] != json!(path));
+      Response::json(if files.len() < before { 204 } else { 404 }, json!({}))
+    }
+    _ => Response::error(400, "unsupported method"),
+  }
+}
+
+fn select(req: &Request, db: &Db, table: &str) -> Response {
+  let filters = match filters(req) {
+    Ok(f) => f,
+    Err(e) => return Response::error(400, &e),
+  };
+  let param = |name: &str| req.query.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+  let mut rows: Vec<&Map<String, Value>> = db
+    .get(table)
+    .map(|rows| rows.iter().filter(|r| filters.iter().all(|f| f.matches(r))).collect())
+    .unwrap_or_default();
+  let total = rows.len();
+
+  if let Some(order) = param("order") {
+    let keys: Vec<&str> = order.split(',').map(|o| o.split('.').next().unwrap_or(o)).collect();
+    rows.sort_by(|a, b| {
+      keys
+        .iter()
+        .map(|k| compare(a.get(*k), b.get(*k)))
+        .find(|o| o.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+    });
+  }
+  if let Some(limit) = param("limit").and_then(|l| l.parse::<usize>().ok()) {
+    rows.truncate(limit);
+  }
+  let columns: Option<Vec<&str>> = param("select").filter(|s| *s != "*").map(|s| s.split(',').collect());
+  let out: Vec<Value> = rows
+    .into_iter()
+    .map(|r| match &columns {
+      Some(cols) => Value::Object(cols.iter().map(|c| (c.to_string(), r.get(*c).cloned().unwrap_or(Value::Null))).collect()),
+      None => Value::Object(r.clone()),
+    })
+    .collect();
+
+  let mut res = Response::json(200, Value::Array(out.clone()));
+  let wants_count = req.headers.get("prefer").map(|p| p.contains("count=exact")).unwrap_or(false);
+  if wants_count {
+    let range = if out.is_empty() {
+      format!("*/{}", total)
+    } else {
+      format!("0-{}/{}", out.len() - 1, total)
+    };
+    res.headers.push(("Content-Range".to_string(), range));
+  }
+  res
+}
+
+enum Op {
+  Eq(String),
+  Neq(String),
+  Gt(String),
+  Gte(String),
+  Lt(String),
+  Lte(String),
+  IsNull,
+  In(Vec<String>),
+}
+
+struct Filter {
+  column: String,
+  op: Op,
+}
+
+fn filters(req: &Request) -> Result<Vec<Filter>, String> {
+  let mut out = Vec::new();
+  for (k, v) in &req.query {
+    if matches!(k.as_str(), "select" | "order" | "limit" | "offset") {
+      continue;
+    }
+    let (op, arg) = v.split_once('.').ok_or_else(|| format!("bad filter {}={}", k, v))?;
+    let op = match op {
+      "eq" => Op::Eq(arg.to_string()),
+      "neq" => Op::Neq(arg.to_string()),
+      "gt" => Op::Gt(arg.to_string()),
+      "gte" => Op::Gte(arg.to_string()),
+      "lt" => Op::Lt(arg.to_string()),
+      "lte" => Op::Lte(arg.to_string()),
+      "is" if arg == "null" => Op::IsNull,
+      "in" => Op::In(
+        arg
+          .trim_start_matches('(')
+          .trim_end_matches(')')
+          .split(',')
+          .map(|s| s.trim_matches('"').to_string())
+          .collect(),
+      ),
+      _ => return Err(format!("unsupported filter {}={}", k, v)),
+    };
+    out.push(Filter { column: k.clone(), op });
+  }
+  Ok(out)
+}
+
+fn text(v: Option<&Value>) -> Option<String> {
+  match v? {
+    Value::Null => None,
+    Value::String(s) => Some(s.clone()),
+    other => Some(other.to_string()),
+  }
+}
+
+/// Numbers, then timestamps (as instants), then plain strings; nulls first.
+fn compare_text(a: &str, b: &str) -> std::cmp::Ordering {
+  if let (Ok(x), Ok(y)) = (a.parse::<f64>(), b.parse::<f64>()) {
+    return x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal);
+  }
+  if let (Ok(x), Ok(y)) = (DateTime::parse_from_rfc3339(a), DateTime::parse_from_rfc3339(b)) {
+    return x.cmp(&y);
+  }
+  a.cmp(b)
+}
+
+fn compare(a: Option<&Value>, b: Option<&Value>) -> std::cmp::Ordering {
+  match (text(a), text(b)) {
+    (Some(a), Some(b)) => compare_text(&a, &b),
+    (a, b) => a.is_some().cmp(&b.is_some()),
+  }
+}
+
+impl Filter {
+  fn matches(&self, row: &Map<String, Value>) -> bool {
+    let value = text(row.get(&self.column));
+    let cmp = |arg: &str| value.as_deref().map(|v| compare_text(v, arg));
+    match &self.op {
+      Op::Eq(arg) => cmp(arg).map(|o| o.is_eq()).unwrap_or(false),
+      Op::Neq(arg) => cmp(arg).map(|o| o.is_ne()).unwrap_or(false),
+      Op::Gt(arg) => cmp(arg).map(|o| o.is_gt()).unwrap_or(false),
+      Op::Gte(arg) => cmp(arg).map(|o| o.is_ge()).unwrap_or(false),
+      Op::Lt(arg) => cmp(arg).map(|o| o.is_lt()).unwrap_or(false),
+      Op::Lte(arg) => cmp(arg).map(|o| o.is_le()).unwrap_or(false),
+      Op::IsNull => value.is_none(),
+      Op::In(list) => value.map(|v| list.contains(&v)).unwrap_or(false),
+    }
+  }
+}
+
+/// A directory under the system temp dir, removed on drop.
+pub struct TempDir {
+  path: PathBuf,
+}
+
+impl TempDir {
+  pub fn new(label: &str) -> Self {
+    let path = std::env::temp_dir().join(format!(
+      "diregram-test-{}-{}-{}",
+      label,
+      std::process::id(),
+      NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).expect("create temp dir");
+    Self { path }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.path);
+  }
+}
+
+fn copy_dir(src: &Path, dst: &Path) {
+  for entry in fs::read_dir(src).expect("read fixture") {
+    let entry = entry.unwrap();
+    let to = dst.join(entry.file_name());
+    if entry.file_type().unwrap().is_dir() {
+      fs::create_dir_all(&to).unwrap();
+      copy_dir(&entry.path(), &to);
+    } else {
+      fs::copy(entry.path(), &to).unwrap();
+    }
+  }
+}
+
+/// A vault folder: empty, or a copy of `tests/fixtures/<name>`.
+pub struct Vault {
+  dir: TempDir,
+}
+
+impl Vault {
+  pub fn empty() -> Self {
+    Self { dir: TempDir::new("vault") }
+  }
+
+  pub fn from_fixture(name: &str) -> Self {
+    let vault = Self::empty();
+    copy_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name), vault.path());
+    vault
+  }
+
+  pub fn path(&self) -> &Path {
+    self.dir.path()
+  }
+
+  pub fn write(&self, rel: &str, text: &str) {
+    let p = self.path().join(rel);
+    fs::create_dir_all(p.parent().unwrap()).unwrap();
+    fs::write(p, text).unwrap();
+  }
+
+  pub fn read(&self, rel: &str) -> Option<String> {
+    fs::read_to_string(self.path().join(rel)).ok()
+  }
+
+  pub fn remove(&self, rel: &str) {
+    fs::remove_file(self.path().join(rel)).unwrap();
+  }
+
+  /// Relative paths of files in `rel_dir` whose name contains `needle`.
+  pub fn find(&self, rel_dir: &str, needle: &str) -> Vec<String> {
+    fs::read_dir(self.path().join(rel_dir))
+      .map(|rd| {
+        rd.filter_map(Result::ok)
+          .filter_map(|e| e.file_name().to_str().map(str::to_string))
+          .filter(|n| n.contains(needle))
+          .collect()
+      })
+      .unwrap_or_default()
+  }
+
+  /// Every file under `.diregram/trash/`, relative to it.
+  pub fn trashed(&self) -> Vec<String> {
+    let root = self.path().join(".diregram/trash");
+    let mut out = Vec::new();
+    let mut stack = vec![root.clone()];
+    while let Some(dir) = stack.pop() {
+      for e in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        if e.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+          stack.push(e.path());
+        } else if let Ok(rel) = e.path().strip_prefix(&root) {
+          out.push(rel.to_string_lossy().replace('\\', "/"));
+        }
+      }
+    }
+    out
+  }
+}
+
+/// Runs the app binary's CLI against `mock` with a session file of its own.
+pub struct Cli {
+  session: TempDir,
+}
+
+impl Cli {
+  pub fn new(mock: &MockSupabase) -> Self {
+    let session = TempDir::new("session");
+    let auth = json!({
+      "supabase_url": mock.url(),
+      "supabase_anon_key": "anon",
+      "access_token": "test-access-token",
+      "refresh_token": "test-refresh-token",
+      "owner_id": OWNER_ID,
+    });
+    fs::write(session.path().join("session.json"), auth.to_string()).unwrap();
+    Self { session }
+  }
+
+  fn command(&self, args: &[&str]) -> Command {
+    let session = self.session.path().join("session.json");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_diregram_sync"));
+    command.arg("--cli").args(args).arg("--session").arg(session);
+    command.env("DIREGRAM_DEVICE_ID", DEVICE_ID);
+    command
+  }
+
+  pub fn run(&self, args: &[&str]) -> Output {
+    self.command(args).output().expect("run diregram_sync")
+  }
+
+  /// Like `run`, with `input` on stdin (closed afterwards).
+  pub fn run_with_input(&self, args: &[&str], input: &str) -> Output {
+    let mut child = self
+      .command(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .expect("run diregram_sync");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().expect("wait for diregram_sync")
+  }
+
+  /// Runs a sync command for `vault` and returns its JSON summary, failing on a non-zero exit.
+  pub fn sync(&self, command: &[&str], vault: &Vault, project: &str) -> Value {
+    let vault_path = vault.path().to_str().unwrap();
+    let mut args = command.to_vec();
+    args.extend(["--vault", vault_path, "--project", project, "--json"]);
+    let out = self.run(&args);
+    assert!(
+      out.status.success(),
+      "{:?} failed: {}{}",
+      command,
+      String::from_utf8_lossy(&out.stdout),
+      String::from_utf8_lossy(&out.stderr)
+    );
+    serde_json::from_slice(&out.stdout).expect("summary json")
+  }
+
+  pub fn status(&self, vault: &Vault) -> Value {
+    let out = self.run(&["status", "--vault", vault.path().to_str().unwrap(), "--json"]);
+    assert!(out.status.success(), "status failed: {}", String::from_utf8_lossy(&out.stderr));
+    serde_json::from_slice::<Value>(&out.stdout).expect("status json")[0].clone()
+  }
+}
+
+/// Content a pushed file row holds, by name.
+pub fn remote_content(mock: &MockSupabase, name: &str) -> Option<String> {
+  mock
+    .file_by_name(name)
+    .and_then(|r| r["content"].as_str().map(str::to_string))
+}
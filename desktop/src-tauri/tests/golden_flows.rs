@@ -0,0 +1,661 @@
+//! End-to-end sync flows through the `--cli` binary against the mock Supabase in `common`.
+
+mod common;
+
+use common::{remote_content, Cli, MockSupabase, Vault, DEVICE_ID};
+use serde_json::{json, Value};
+
+/// A project with the `basic_vault` fixture imported into it.
+fn imported() -> (MockSupabase, Cli, Vault, String) {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Basic");
+  let cli = Cli::new(&mock);
+  let vault = Vault::from_fixture("basic_vault");
+  cli.sync(&["import"], &vault, &project);
+  (mock, cli, vault, project)
+}
+
+#[test]
+fn import_creates_folders_files_and_resources() {
+  let (mock, cli, vault, project) = imported();
+
+  let notes = mock.rows("folders").into_iter().find(|f| f["name"] == "Notes").expect("Notes folder");
+  assert_eq!(notes["parent_id"], json!(project));
+  let meeting = mock.file_by_name("Meeting.md").expect("Meeting.md row");
+  assert_eq!(meeting["folder_id"], notes["id"]);
+  assert_eq!(remote_content(&mock, "Welcome.md").as_deref(), vault.read("Welcome.md").as_deref());
+  assert!(mock.file_by_name("Ideas.md").is_some());
+  assert!(mock.rows("project_resources").iter().any(|r| r["name"] == "Reference.md"));
+
+  let status = cli.status(&vault);
+  assert_eq!(status["project_folder_id"], json!(project));
+  assert_eq!(status["files"], json!(3));
+  assert_eq!(status["resources"], json!(1));
+}
+
+#[test]
+fn second_import_skips_unchanged_files() {
+  let (_mock, cli, vault, project) = imported();
+  let summary = cli.sync(&["import"], &vault, &project);
+  assert_eq!(summary["files_created"], json!(0));
+  assert_eq!(summary["files_updated"], json!(0));
+  assert_eq!(summary["files_skipped"], json!(3));
+}
+
+#[test]
+fn pull_into_empty_vault_writes_remote_files() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let folder = mock.insert("folders", json!({ "name": "Plans", "parent_id": project }));
+  mock.insert("files", json!({ "name": "Top.md", "folder_id": project, "content": "# Top\n", "kind": "note" }));
+  mock.insert("files", json!({ "name": "Q3.md", "folder_id": folder["id"], "content": "# Q3\n", "kind": "note" }));
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  assert_eq!(vault.read("Top.md").as_deref(), Some("# Top\n"));
+  assert_eq!(vault.read("Plans/Q3.md").as_deref(), Some("# Q3\n"));
+  assert_eq!(cli.status(&vault)["files"], json!(2));
+}
+
+#[test]
+fn clone_creates_empty_folders_and_seeds_defaults() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  mock.insert("folders", json!({ "name": "Archive", "parent_id": project }));
+  mock.insert("files", json!({ "name": "Top.md", "folder_id": project, "content": "# Top\n", "kind": "note" }));
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+
+  let summary = cli.sync(&["clone"], &vault, &project);
+
+  assert_eq!(summary["folders_created"], json!(1));
+  assert!(vault.path().join("Archive").is_dir());
+  assert_eq!(vault.read("Top.md").as_deref(), Some("# Top\n"));
+  assert!(vault.read(".diregramignore").is_some());
+  assert!(vault.path().join(".diregram/config.json").is_file());
+  // The seeded ignore file stays local.
+  let summary = cli.sync(&["import"], &vault, &project);
+  assert_eq!(summary["files_created"], json!(0));
+}
+
+#[test]
+fn pull_into_unlinked_vault_with_files_needs_force() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::from_fixture("basic_vault");
+  let path = vault.path().to_str().unwrap().to_string();
+
+  let out = cli.run(&["sync", "pull", "--vault", &path, "--project", &project]);
+  assert_eq!(out.status.code(), Some(1));
+  assert_eq!(vault.read("Welcome.md").as_deref(), Some("# Welcome\n\nStart here.\n"));
+
+  cli.sync(&["sync", "pull", "--force"], &vault, &project);
+}
+
+#[test]
+fn unsafe_remote_names_get_safe_local_names_and_keep_remote_ones() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let folder = mock.insert("folders", json!({ "name": "Q1/Q2", "parent_id": project }));
+  mock.insert("files", json!({ "name": "a:b.md", "folder_id": folder["id"], "content": "# A\n", "kind": "note" }));
+  mock.insert("files", json!({ "name": "a_b.md", "folder_id": folder["id"], "content": "# B\n", "kind": "note" }));
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  assert_eq!(vault.read("Q1_Q2/a_b.md").as_deref(), Some("# B\n"));
+  assert_eq!(vault.find("Q1_Q2", "a_b ~").len(), 1);
+  vault.write("Q1_Q2/New.md", "# New\n");
+  cli.sync(&["import"], &vault, &project);
+  assert_eq!(mock.file_by_name("New.md").unwrap()["folder_id"], folder["id"]);
+  assert_eq!(mock.rows("folders").len(), 2);
+  assert!(mock.file_by_name("a:b.md").is_some());
+}
+
+#[test]
+fn diagram_headers_are_pretty_printed_on_pull_and_checked_on_push() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let compact = "```nexus-doc\n{\"kind\":\"diagram\",\"version\":1}\n```\nA\n---\n";
+  mock.insert("files", json!({ "name": "Flow.md", "folder_id": project, "content": compact, "kind": "diagram" }));
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  let path = vault.path().to_str().unwrap().to_string();
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+  let pretty = "```nexus-doc\n{\n  \"kind\": \"diagram\",\n  \"version\": 1\n}\n```\nA\n---\n";
+  assert_eq!(vault.read("Flow.md").as_deref(), Some(pretty));
+
+  vault.write("Flow.md", "```nexus-doc\n{\"kind\": \"diagram\", \"version\": 1,}\n```\nB\n---\n");
+  let out = cli.run(&["import", "--vault", &path, "--project", &project]);
+  assert_eq!(out.status.code(), Some(1));
+  assert_eq!(remote_content(&mock, "Flow.md").as_deref(), Some(compact));
+}
+
+#[test]
+fn pulled_files_are_routed_by_kind_and_pushed_back_to_their_remote_folder() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let folder = mock.insert("folders", json!({ "name": "Plans", "parent_id": project }));
+  let diagram = "```nexus-doc\n{\n  \"kind\": \"diagram\",\n  \"version\": 1\n}\n```\nA\n---\n";
+  mock.insert("files", json!({ "name": "Flow.md", "folder_id": folder["id"], "content": diagram, "kind": "diagram" }));
+  mock.insert("files", json!({ "name": "Q3.md", "folder_id": folder["id"], "content": "# Q3\n", "kind": "note" }));
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  vault.write(".diregram/config.json", r#"{"routing": {"kinds": {"diagram": "diagrams"}}}"#);
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+  assert_eq!(vault.read("diagrams/Plans/Flow.md").as_deref(), Some(diagram));
+  assert_eq!(vault.read("Plans/Flow.md"), None);
+  assert_eq!(vault.read("Plans/Q3.md").as_deref(), Some("# Q3\n"));
+
+  let edited = diagram.replace("A\n", "B\n");
+  vault.write("diagrams/Plans/Flow.md", &edited);
+  let summary = cli.sync(&["import"], &vault, &project);
+
+  assert_eq!(summary["files_updated"], json!(1));
+  assert_eq!(summary["files_created"], json!(0));
+  assert_eq!(remote_content(&mock, "Flow.md").as_deref(), Some(edited.as_str()));
+  assert_eq!(mock.file_by_name("Flow.md").unwrap()["folder_id"], folder["id"]);
+  assert!(!mock.rows("folders").iter().any(|f| f["name"] == "diagrams"));
+}
+
+#[test]
+fn links_become_remote_ids_on_push_and_relative_again_on_pull() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  vault.write(".diregram/config.json", r#"{"links": {"rewrite": true}}"#);
+  vault.write("Plan B.md", "# Plan B\n");
+  vault.write("Notes/Index.md", "See [plan](../Plan%20B.md#goals), [web](https://x.y) and `[no](../Plan%20B.md)`.\n");
+
+  cli.sync(&["import"], &vault, &project);
+
+  let id = mock.file_by_name("Plan B.md").unwrap()["id"].as_str().unwrap().to_string();
+  let linked = format!("See [plan](nexus://file/{}#goals), [web](https://x.y) and `[no](../Plan%20B.md)`.\n", id);
+  assert_eq!(remote_content(&mock, "Index.md").as_deref(), Some(linked.as_str()));
+
+  let index = mock.file_by_name("Index.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.edit_file(&index, &format!("Moved: [plan](nexus://file/{}).\n", id));
+  cli.sync(&["sync", "pull"], &vault, &project);
+  assert_eq!(vault.read("Notes/Index.md").as_deref(), Some("Moved: [plan](../Plan%20B.md).\n"));
+}
+
+#[test]
+fn identical_attachments_are_uploaded_once_and_collected_when_unused() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  vault.write(".diregram/config.json", r#"{"attachments": {"enabled": true}}"#);
+  vault.write("a.png", "same pixels");
+  vault.write("Notes/b.png", "same pixels");
+
+  let summary = cli.sync(&["import"], &vault, &project);
+
+  assert_eq!(summary["attachments_uploaded"], json!(1));
+  assert_eq!(summary["attachments_deduped"], json!(1));
+  assert_eq!(mock.rows("storage_objects").len(), 1);
+  assert!(mock.file_by_name("a.png").is_none());
+
+  vault.remove("a.png");
+  cli.sync(&["import"], &vault, &project);
+  assert_eq!(mock.rows("storage_objects").len(), 1);
+  vault.remove("Notes/b.png");
+  cli.sync(&["import"], &vault, &project);
+  assert!(mock.rows("storage_objects").is_empty());
+  assert!(vault.find(".diregram/objects", "").is_empty());
+}
+
+#[test]
+fn remote_edit_is_pulled() {
+  let (mock, cli, vault, project) = imported();
+  let id = mock.file_by_name("Ideas.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.edit_file(&id, "# Ideas\n\n- edited on the web\n");
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  assert_eq!(vault.read("Ideas.md").as_deref(), Some("# Ideas\n\n- edited on the web\n"));
+}
+
+#[test]
+fn local_edit_is_pushed() {
+  let (mock, cli, vault, project) = imported();
+  vault.write("Notes/Meeting.md", "# Meeting\n\nDecisions.\n");
+
+  let summary = cli.sync(&["import"], &vault, &project);
+
+  assert_eq!(summary["files_updated"], json!(1));
+  assert_eq!(remote_content(&mock, "Meeting.md").as_deref(), Some("# Meeting\n\nDecisions.\n"));
+  assert_eq!(mock.file_by_name("Meeting.md").unwrap()["rev"], json!(2));
+}
+
+#[test]
+fn concurrent_edits_keep_local_and_write_conflict_copy() {
+  let (mock, cli, vault, project) = imported();
+  let id = mock.file_by_name("Welcome.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.edit_file(&id, "# Welcome\n\nRemote edit.\n");
+  vault.write("Welcome.md", "# Welcome\n\nLocal edit.\n");
+
+  cli.sync(&["import"], &vault, &project);
+
+  assert_eq!(vault.read("Welcome.md").as_deref(), Some("# Welcome\n\nLocal edit.\n"));
+  let copies = vault.find("", " (conflict from Diregram ");
+  assert_eq!(copies.len(), 1, "conflict copies: {:?}", copies);
+  assert_eq!(vault.read(&copies[0]).as_deref(), Some("# Welcome\n\nRemote edit.\n"));
+  // The remote version is now known, so the next push overwrites it deliberately.
+  assert_eq!(remote_content(&mock, "Welcome.md").as_deref(), Some("# Welcome\n\nRemote edit.\n"));
+  cli.sync(&["import"], &vault, &project);
+  assert_eq!(remote_content(&mock, "Welcome.md").as_deref(), Some("# Welcome\n\nLocal edit.\n"));
+}
+
+#[test]
+fn read_only_vault_reports_local_changes_instead_of_pushing_them() {
+  let (mock, cli, vault, project) = imported();
+  vault.write(".diregram/config.json", r#"{"mode": "read_only"}"#);
+  vault.write("Welcome.md", "# Welcome\n\nLocal edit.\n");
+  vault.remove("Ideas.md");
+  let remote_welcome = remote_content(&mock, "Welcome.md");
+
+  let summary = cli.sync(&["import"], &vault, &project);
+
+  assert_eq!(summary["files_updated"], json!(0));
+  assert_eq!(summary["files_deleted"], json!(0));
+  assert_eq!(summary["warnings"].as_array().map(Vec::len), Some(2));
+  assert_eq!(remote_content(&mock, "Welcome.md"), remote_welcome);
+  assert!(mock.file_by_name("Ideas.md").is_some());
+  let events = vault.read(".diregram/events.jsonl").unwrap_or_default();
+  assert_eq!(events.matches("\"read_only_edit\"").count(), 2, "{}", events);
+
+  let id = mock.file_by_name("Meeting.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.edit_file(&id, "# Meeting\n\nFrom the team.\n");
+  cli.sync(&["sync", "pull"], &vault, &project);
+  assert_eq!(vault.read("Notes/Meeting.md").as_deref(), Some("# Meeting\n\nFrom the team.\n"));
+}
+
+#[test]
+fn local_delete_removes_remote_file() {
+  let (mock, cli, vault, project) = imported();
+  vault.remove("Ideas.md");
+
+  cli.sync(&["import"], &vault, &project);
+
+  assert!(mock.file_by_name("Ideas.md").is_none());
+  assert!(mock.file_by_name("Welcome.md").is_some());
+}
+
+#[test]
+fn remote_delete_archives_local_copy() {
+  let (mock, cli, vault, project) = imported();
+  let id = mock.file_by_name("Ideas.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.delete("files", &id);
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  assert_eq!(vault.read("Ideas.md"), None);
+  let trashed = vault.trashed();
+  assert!(trashed.iter().any(|p| p.ends_with("Ideas.md")), "trash: {:?}", trashed);
+  assert_eq!(cli.status(&vault)["files"], json!(2));
+}
+
+#[test]
+fn push_only_vault_ignores_remote_changes_and_overwrites_them() {
+  let (mock, cli, vault, project) = imported();
+  vault.write(".diregram/config.json", r#"{"mode": "push_only"}"#);
+  let welcome = mock.file_by_name("Welcome.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.edit_file(&welcome, "# Welcome\n\nRemote edit.\n");
+  let ideas = mock.file_by_name("Ideas.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.delete("files", &ideas);
+  let local_welcome = vault.read("Welcome.md");
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  assert_eq!(vault.read("Welcome.md"), local_welcome);
+  assert!(vault.read("Ideas.md").is_some());
+  assert!(vault.trashed().is_empty());
+
+  vault.write("Welcome.md", "# Welcome\n\nLocal edit.\n");
+  cli.sync(&["import"], &vault, &project);
+
+  assert_eq!(remote_content(&mock, "Welcome.md").as_deref(), Some("# Welcome\n\nLocal edit.\n"));
+  assert!(vault.find("", " (conflict from Diregram ").is_empty());
+  assert_eq!(remote_content(&mock, "Ideas.md"), vault.read("Ideas.md"));
+}
+
+#[test]
+fn folder_modes_override_the_vault_mode_per_folder() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  vault.write("inbox/Clip.md", "# Clip\n");
+  vault.write("reference/Spec.md", "# Spec\n");
+  vault.write("Plan.md", "# Plan\n");
+  cli.sync(&["import"], &vault, &project);
+  let config = json!({ "folder_modes": { "inbox": "push_only", "reference": "pull_only" } });
+  vault.write(".diregram/config.json", &config.to_string());
+
+  for name in ["Clip.md", "Spec.md", "Plan.md"] {
+    let id = mock.file_by_name(name).unwrap()["id"].as_str().unwrap().to_string();
+    mock.edit_file(&id, "# Remote\n");
+  }
+  cli.sync(&["sync", "pull"], &vault, &project);
+  assert_eq!(vault.read("inbox/Clip.md").as_deref(), Some("# Clip\n"));
+  assert_eq!(vault.read("reference/Spec.md").as_deref(), Some("# Remote\n"));
+  assert_eq!(vault.read("Plan.md").as_deref(), Some("# Remote\n"));
+
+  vault.write("inbox/Clip.md", "# Clip\n\nLocal.\n");
+  vault.write("reference/Spec.md", "# Spec\n\nLocal.\n");
+  let summary = cli.sync(&["import"], &vault, &project);
+  assert_eq!(remote_content(&mock, "Clip.md").as_deref(), Some("# Clip\n\nLocal.\n"));
+  assert_eq!(remote_content(&mock, "Spec.md").as_deref(), Some("# Remote\n"));
+  assert_eq!(summary["warnings"].as_array().map(Vec::len), Some(1));
+}
+
+#[test]
+fn shared_project_rows_keep_their_owner_and_viewer_edits_stay_local() {
+  let mock = MockSupabase::start();
+  let teammate = "00000000-0000-4000-8000-0000000000aa";
+  let project = mock.insert("folders", json!({ "name": "Team", "parent_id": null, "owner_id": teammate }))["id"]
+    .as_str()
+    .unwrap()
+    .to_string();
+  let spec = json!({ "name": "Spec.md", "folder_id": project, "content": "# Spec\n", "kind": "note", "owner_id": teammate, "viewer_only": true });
+  mock.insert("files", spec);
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+  vault.write("Spec.md", "# Spec\n\nMy edit.\n");
+  vault.write("Notes/Mine.md", "# Mine\n");
+  let summary = cli.sync(&["import"], &vault, &project);
+
+  assert_eq!(summary["warnings"].as_array().map(Vec::len), Some(1), "{}", summary);
+  assert_eq!(remote_content(&mock, "Spec.md").as_deref(), Some("# Spec\n"));
+  assert!(vault.find("", " (conflict from Diregram ").is_empty());
+  assert_eq!(mock.file_by_name("Mine.md").unwrap()["owner_id"], json!(teammate));
+  assert!(mock.rows("folders").iter().any(|f| f["name"] == "Notes" && f["owner_id"] == json!(teammate)));
+  let mapping: serde_json::Value = serde_json::from_str(&vault.read(".diregram/sync.json").unwrap()).unwrap();
+  assert_eq!(mapping["files"]["Spec.md"]["owner_id"], json!(teammate));
+  assert_eq!(mapping["files"]["Spec.md"]["read_only"], json!(true));
+}
+
+#[test]
+fn mass_remote_delete_is_held_back() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Many");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  for i in 0..6 {
+    vault.write(&format!("Note {}.md", i), &format!("# Note {}\n", i));
+  }
+  cli.sync(&["import"], &vault, &project);
+  for row in mock.rows("files") {
+    mock.delete("files", row["id"].as_str().unwrap());
+  }
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  for i in 0..6 {
+    assert!(vault.read(&format!("Note {}.md", i)).is_some());
+  }
+  assert!(vault.trashed().is_empty());
+  assert_eq!(cli.status(&vault)["pending_mass_delete"], json!(6));
+}
+
+#[test]
+fn bad_usage_exits_with_2() {
+  let mock = MockSupabase::start();
+  let cli = Cli::new(&mock);
+  assert_eq!(cli.run(&["frobnicate", "--bogus"]).status.code(), Some(2));
+}
+
+#[test]
+fn metrics_file_counts_the_run() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Basic");
+  let cli = Cli::new(&mock);
+  let vault = Vault::from_fixture("basic_vault");
+  let metrics = common::TempDir::new("metrics");
+  let metrics_file = metrics.path().join("diregram.prom");
+
+  cli.sync(&["import", "--metrics-file", metrics_file.to_str().unwrap()], &vault, &project);
+
+  let text = std::fs::read_to_string(&metrics_file).expect("metrics file");
+  assert!(text.contains("diregram_sync_runs_total{op=\"push\"} 1"), "{}", text);
+  assert!(text.contains("diregram_sync_files_total{op=\"push\",change=\"created\"} 3"), "{}", text);
+  assert!(text.contains("# TYPE diregram_sync_run_duration_seconds histogram"), "{}", text);
+}
+
+#[test]
+fn pulls_and_imports_are_committed_to_git_when_enabled() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  vault.write(".diregram/config.json", r#"{"git": {"enabled": true}}"#);
+  vault.write("Plan.md", "# Plan\n");
+
+  cli.sync(&["import"], &vault, &project);
+  let id = mock.file_by_name("Plan.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.edit_file(&id, "# Plan\n\nShipped.\n");
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  let log = std::process::Command::new("git")
+    .arg("-C")
+    .arg(vault.path())
+    .args(["log", "--format=%s%n%b", "--name-only"])
+    .output()
+    .expect("git log");
+  let log = String::from_utf8_lossy(&log.stdout);
+  assert!(log.contains("Diregram pull: 0 created, 1 updated, 0 deleted"), "{}", log);
+  assert!(log.contains("Diregram import: 1 created, 0 updated, 0 deleted"), "{}", log);
+  assert!(log.contains("files_created: 1"), "{}", log);
+  assert!(!log.contains(".diregram"), "{}", log);
+  assert!(mock.file_by_name("HEAD").is_none());
+}
+
+#[test]
+fn synced_files_are_mirrored_to_webdav() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  let config = json!({ "mirror": { "targets": [{ "type": "webdav", "url": format!("{}/dav/vault/", mock.url()) }] } });
+  vault.write(".diregram/config.json", &config.to_string());
+  vault.write("Plan.md", "# Plan\n");
+  vault.write("Notes/Old idea.md", "# Old\n");
+
+  cli.sync(&["import"], &vault, &project);
+
+  let mirrored = |path: &str| {
+    mock
+      .rows("dav_files")
+      .into_iter()
+      .find(|f| f["path"] == json!(path))
+      .map(|f| f["content"].as_str().unwrap().to_string())
+  };
+  assert_eq!(mirrored("vault/Plan.md").as_deref(), Some("# Plan\n"));
+  assert_eq!(mirrored("vault/Notes/Old idea.md").as_deref(), Some("# Old\n"));
+  assert!(mock.rows("dav_collections").iter().any(|c| c["path"] == json!("vault/Notes")));
+
+  vault.remove("Notes/Old idea.md");
+  vault.write("Plan.md", "# Plan\n\nRevised.\n");
+  cli.sync(&["import"], &vault, &project);
+  assert_eq!(mirrored("vault/Plan.md").as_deref(), Some("# Plan\n\nRevised.\n"));
+  assert_eq!(mirrored("vault/Notes/Old idea.md"), None);
+  assert_eq!(mock.rows("dav_files").len(), 1);
+}
+
+#[test]
+fn note_tags_are_synced_to_file_tags_when_enabled() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  vault.write(".diregram/config.json", r#"{"tags": {"sync_remote": true}}"#);
+  vault.write("Plan.md", "---\ntags: [roadmap, Q3]\n---\n# Plan\n\nShip it #launch/beta.\n");
+  vault.write("Notes.md", "# Notes\n\n`#not-a-tag` and #launch\n");
+
+  cli.sync(&["import"], &vault, &project);
+  let tags_of = |name: &str| {
+    let id = mock.file_by_name(name).unwrap()["id"].clone();
+    let mut tags: Vec<String> = mock
+      .rows("file_tags")
+      .into_iter()
+      .filter(|r| r["file_id"] == id && r["project_folder_id"] == json!(project))
+      .map(|r| r["tag"].as_str().unwrap().to_string())
+      .collect();
+    tags.sort();
+    tags
+  };
+  assert_eq!(tags_of("Plan.md"), ["launch/beta", "q3", "roadmap"]);
+  assert_eq!(tags_of("Notes.md"), ["launch"]);
+
+  vault.write("Plan.md", "# Plan\n\n#roadmap only now.\n");
+  cli.sync(&["import"], &vault, &project);
+  assert_eq!(tags_of("Plan.md"), ["roadmap"]);
+  assert_eq!(tags_of("Notes.md"), ["launch"]);
+  assert_eq!(mock.rows("file_tags").len(), 2);
+}
+
+#[test]
+fn mcp_server_answers_tool_calls_over_stdio() {
+  let mock = MockSupabase::start();
+  let project = mock.create_project("Remote");
+  let cli = Cli::new(&mock);
+  let vault = Vault::empty();
+  vault.write("Plan.md", "# Plan\n\nShip the launch checklist.\n");
+  vault.write("Notes/Ideas.md", "Builds on [[Plan]].\n");
+  vault.write("Notes/Other.md", "See [the plan](../Plan.md) and `[[Plan]]`.\n");
+  cli.sync(&["import"], &vault, &project);
+  let plan = mock.file_by_name("Plan.md").unwrap()["id"].as_str().unwrap().to_string();
+  let entities = [
+    json!({ "id": format!("file:{}", plan), "entity_type": "file", "file_id": plan, "data": { "name": "Plan.md" } }),
+    json!({ "id": "heading:plan", "entity_type": "noteHeading", "file_id": plan, "data": { "text": "Plan" } }),
+  ];
+  let edge = json!({ "id": "e1", "edge_type": "file_has_heading", "src": format!("file:{}", plan), "dst": "heading:plan", "data": {} });
+  vault.write("rag/kg_entities.jsonl", &format!("{}\n{}\n", entities[0], entities[1]));
+  vault.write("rag/kg_edges.jsonl", &format!("{}\n", edge));
+
+  let requests = [
+    json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": { "name": "test", "version": "1" } } }),
+    json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+    json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+    json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": { "name": "search_notes", "arguments": { "query": "CHECKLIST" } } }),
+    json!({ "jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": { "name": "get_note", "arguments": { "path": "Plan.md" } } }),
+    json!({ "jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": { "name": "get_kg_neighbors", "arguments": { "entity": "plan.md" } } }),
+    json!({ "jsonrpc": "2.0", "id": 6, "method": "tools/call", "params": { "name": "list_recent_changes", "arguments": {} } }),
+    json!({ "jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": { "name": "get_note", "arguments": { "path": ".diregram/mapping.json" } } }),
+    json!({ "jsonrpc": "2.0", "id": 8, "method": "resources/list" }),
+  ];
+  let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+  let out = cli.run_with_input(&["mcp", "--vault", vault.path().to_str().unwrap()], &input);
+  assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+  let replies: Vec<serde_json::Value> = String::from_utf8_lossy(&out.stdout)
+    .lines()
+    .map(|l| serde_json::from_str(l).unwrap())
+    .collect();
+  assert_eq!(replies.len(), 8, "the notification gets no reply");
+  let tool_json = |i: usize| -> serde_json::Value {
+    serde_json::from_str(replies[i]["result"]["content"][0]["text"].as_str().unwrap()).unwrap()
+  };
+
+  assert_eq!(replies[0]["result"]["protocolVersion"], json!("2024-11-05"));
+  let names: Vec<&str> = replies[1]["result"]["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+  assert_eq!(names, ["search_notes", "get_note", "get_kg_neighbors", "list_recent_changes"]);
+  assert_eq!(tool_json(2), json!([{ "path": "Plan.md", "line": 3, "text": "Ship the launch checklist." }]));
+  let note = tool_json(3);
+  assert_eq!(note["content"], json!("# Plan\n\nShip the launch checklist.\n"));
+  assert_eq!(note["backlinks"], json!(["Notes/Ideas.md", "Notes/Other.md"]));
+  let kg = tool_json(4);
+  assert_eq!(kg["entity"]["path"], json!("Plan.md"));
+  assert_eq!(kg["neighbors"][0]["direction"], json!("out"));
+  assert_eq!(kg["neighbors"][0]["entity"]["label"], json!("Plan"));
+  let recent = tool_json(5);
+  assert_eq!(recent.as_array().unwrap().len(), 3);
+  assert!(recent.as_array().unwrap().iter().all(|r| r["kind"] == json!("push")));
+  assert_eq!(replies[6]["result"]["isError"], json!(true));
+  assert_eq!(replies[7]["error"]["code"], json!(-32601));
+}
+
+#[test]
+fn shredded_files_skip_the_trash_and_lose_their_revisions() {
+  let (mock, cli, vault, project) = imported();
+  assert!(!vault.find(".diregram/revisions/Ideas.md", ".md").is_empty());
+  vault.write(".diregram/config.json", r#"{"shred": {"globs": ["Idea*.md"]}}"#);
+  let ideas = mock.file_by_name("Ideas.md").unwrap()["id"].as_str().unwrap().to_string();
+  let welcome = mock.file_by_name("Welcome.md").unwrap()["id"].as_str().unwrap().to_string();
+  mock.delete("files", &ideas);
+  mock.delete("files", &welcome);
+
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  assert!(vault.read("Ideas.md").is_none());
+  assert!(vault.find(".diregram/revisions/Ideas.md", ".md").is_empty());
+  assert_eq!(vault.trashed().iter().filter(|t| t.ends_with("/Welcome.md")).count(), 1);
+  assert!(!vault.trashed().iter().any(|t| t.ends_with("Ideas.md")));
+  let events = vault.read(".diregram/events.jsonl").unwrap();
+  assert!(events.lines().any(|l| l.contains(r#""kind":"shred""#) && l.contains("Ideas.md")));
+}
+
+#[test]
+fn event_log_entries_are_attributed_and_hash_chained() {
+  let (_mock, cli, vault, project) = imported();
+  cli.sync(&["sync", "pull"], &vault, &project);
+
+  let log = vault.read(".diregram/events.jsonl").unwrap();
+  let entries: Vec<Value> = log.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+  assert!(entries.len() > 1);
+  assert_eq!(entries[0]["prev"], json!(""));
+  for pair in entries.windows(2) {
+    assert_eq!(pair[1]["prev"], pair[0]["hash"]);
+  }
+  assert!(entries.iter().all(|e| e["actor"]["app_version"] == json!(env!("CARGO_PKG_VERSION"))));
+  assert!(entries.iter().all(|e| !e["actor"]["device_id"].as_str().unwrap_or("").is_empty()));
+}
+
+#[test]
+fn written_rows_and_events_carry_the_device_id() {
+  let (mock, _cli, vault, _project) = imported();
+
+  let device = mock.file_by_name("Welcome.md").unwrap()["last_modified_by_device"].clone();
+  assert!(!device.as_str().unwrap_or("").is_empty());
+  assert!(mock.rows("files").iter().all(|f| f["last_modified_by_device"] == device));
+  let log = vault.read(".diregram/events.jsonl").unwrap();
+  let push = log
+    .lines()
+    .map(|l| serde_json::from_str::<Value>(l).unwrap())
+    .find(|e| e["kind"] == json!("push"))
+    .expect("push event");
+  assert_eq!(push["actor"]["device_id"], device);
+}
+
+#[test]
+fn remote_unlink_stops_the_pull_and_removes_synced_content() {
+  let (mock, cli, vault, project) = imported();
+  vault.write("Scratch.md", "# Never synced\n");
+  let control = mock.insert("device_controls", json!({ "device_id": DEVICE_ID, "remove_content": true }));
+  let path = vault.path().to_str().unwrap().to_string();
+
+  let out = cli.run(&["sync", "pull", "--vault", &path, "--project", &project]);
+
+  assert_eq!(out.status.code(), Some(1));
+  assert!(vault.read("Welcome.md").is_none());
+  assert!(vault.read("Notes/Meeting.md").is_none());
+  assert_eq!(vault.read("Scratch.md").as_deref(), Some("# Never synced\n"));
+  assert!(vault.read(".diregram/sync.json").is_none());
+  // Only this device's copy goes; the account's data stays on the server.
+  assert!(mock.file_by_name("Welcome.md").is_some());
+  let row = mock.rows("device_controls").into_iter().find(|r| r["id"] == control["id"]).unwrap();
+  assert!(row["acknowledged_at"].is_string());
+  let events = vault.read(".diregram/events.jsonl").unwrap();
+  for kind in ["remote_unlink", "sync_stopped", "remote_wipe"] {
+    assert!(events.contains(&format!(r#""kind":"{}""#, kind)), "{}", events);
+  }
+}
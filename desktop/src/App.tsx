@@ -1,7 +1,7 @@
 import { useEffect, useMemo, useState } from 'react';
 import { open as openDialog } from '@tauri-apps/plugin-dialog';
-import { getCurrent, onOpenUrl } from '@tauri-apps/plugin-deep-link';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import type { SupabaseClient } from '@supabase/supabase-js';
 import { createSupabaseClient, getSession } from './lib/supabase';
 import { buildAiGuideBundle } from './lib/aiGuideContent';
@@ -30,6 +30,12 @@ import { projectLocalPath } from './lib/localPaths';
 
 type AppStep = 'signedOut' | 'signedIn';
 
+/** Parsed by the backend from `diregram://` links (see src-tauri/src/deeplink.rs). */
+type DeepLinkRoute =
+  | { action: 'open'; file: string; project_folder_id: string | null }
+  | { action: 'sync'; project_folder_id: string }
+  | { action: 'auth_callback'; code: string | null; access_token: string | null; refresh_token: string | null; error: string | null };
+
 export function App() {
   const syncRootFolderName = 'Diregram';
 
@@ -134,12 +140,11 @@ export function App() {
         // ignore resume failures
       }
 
-      const startUrls = await getCurrent();
-      if (startUrls?.length) await handleOpenUrls(sb, startUrls);
-
-      unsub = await onOpenUrl(async (urls) => {
-        await handleOpenUrls(sb, urls);
+      unsub = await listen<DeepLinkRoute>('deeplink://navigate', async (e) => {
+        await handleDeepLink(sb, e.payload);
       });
+      const pending = (await invoke('deeplink_take_pending')) as DeepLinkRoute[];
+      for (const route of pending) await handleDeepLink(sb, route);
     };
 
     void boot();
@@ -152,6 +157,17 @@ export function App() {
     };
   }, []);
 
+  useEffect(() => {
+    // Backend refresh daemon gave up (refresh token revoked/expired): ask for a fresh sign-in.
+    const sub = listen<{ reason: string }>('auth://expired', () => {
+      setStep('signedOut');
+      setStatus('Your session expired. Sign in again to resume syncing.');
+    });
+    return () => {
+      void sub.then((un) => un());
+    };
+  }, []);
+
   useEffect(() => {
     if (!supabase) return;
     const {
@@ -183,30 +199,29 @@ export function App() {
     }
   };
 
-  const handleOpenUrls = async (sb: SupabaseClient, urls: string[]) => {
-    const url = urls[0];
-    if (!url) return;
+  const handleDeepLink = async (sb: SupabaseClient, route: DeepLinkRoute) => {
+    if (route.action === 'open') {
+      setStatus(`Opened from link: ${route.file}`);
+      return;
+    }
+    if (route.action === 'sync') {
+      setStatus('Sync requested from link…');
+      return;
+    }
 
     try {
-      const parsed = new URL(url);
+      if (route.error) throw new Error(route.error);
       setStatus('Completing sign-in…');
 
-      const code = parsed.searchParams.get('code');
-      if (code) {
-        const { error } = await sb.auth.exchangeCodeForSession(code);
+      if (route.code) {
+        const { error } = await sb.auth.exchangeCodeForSession(route.code);
+        if (error) throw error;
+      } else if (route.access_token && route.refresh_token) {
+        // Magic link returned tokens in the fragment.
+        const { error } = await sb.auth.setSession({ access_token: route.access_token, refresh_token: route.refresh_token });
         if (error) throw error;
       } else {
-        // Magic link may return tokens in the fragment.
-        const hash = String(parsed.hash || '').replace(/^#/, '');
-        const params = new URLSearchParams(hash);
-        const access_token = params.get('access_token');
-        const refresh_token = params.get('refresh_token');
-        if (access_token && refresh_token) {
-          const { error } = await sb.auth.setSession({ access_token, refresh_token });
-          if (error) throw error;
-        } else {
-          return;
-        }
+        return;
       }
 
       const session = await getSession(sb);
@@ -85,7 +85,8 @@ export async function loadAllProjectEvents(opts: {
   for (const p of opts.projects) {
     const loc = projectLocalPath(p, opts.rootVault, opts.syncRootFolderName);
     // eslint-disable-next-line no-await-in-loop
-    const evs = (await opts.invoke('sync_read_events', { vaultPath: loc.abs, limit })) as any[];
+    const page = (await opts.invoke('sync_read_events', { vaultPath: loc.abs, limit })) as { events?: any[] } | null;
+    const evs = page?.events;
     if (Array.isArray(evs)) all.push(...(evs as SyncEvent[]));
   }
   all.sort((a, b) => String(b.ts || '').localeCompare(String(a.ts || '')));
@@ -7,19 +7,28 @@ async function safeInvoke<T>(cmd: string, args: Record<string, any>): Promise<T>
 }
 
 export const secureStorage = {
-  async getItem(key: string): Promise<string | null> {
+  async getItem(key: string, profile?: string): Promise<string | null> {
     try {
-      const v = await safeInvoke<string | null>('secure_storage_get', { key });
+      const v = await safeInvoke<string | null>('secure_storage_get', { key, profile });
       return v ?? null;
     } catch {
       return null;
     }
   },
-  async setItem(key: string, value: string): Promise<void> {
-    await safeInvoke<void>('secure_storage_set', { key, value });
+  async setItem(key: string, value: string, profile?: string): Promise<void> {
+    await safeInvoke<void>('secure_storage_set', { key, value, profile });
   },
-  async removeItem(key: string): Promise<void> {
-    await safeInvoke<void>('secure_storage_remove', { key });
+  async removeItem(key: string, profile?: string): Promise<void> {
+    await safeInvoke<void>('secure_storage_remove', { key, profile });
+  },
+  async listKeys(profile?: string): Promise<string[]> {
+    return await safeInvoke<string[]>('secure_storage_list_keys', { profile });
+  },
+  async listProfiles(): Promise<string[]> {
+    return await safeInvoke<string[]>('profile_list', {});
+  },
+  async deleteProfile(profile: string): Promise<void> {
+    await safeInvoke<void>('profile_delete', { profile });
   },
 };
 
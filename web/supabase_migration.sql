@@ -156,6 +156,103 @@ create policy "Users can delete owned or shared(edit) folders" on public.folders
 alter table public.files add column if not exists room_name text;
 alter table public.files add column if not exists last_opened_at timestamptz;
 alter table public.files add column if not exists access jsonb;
+-- sha256 of `content` as written by the desktop sync client, verified on pull.
+alter table public.files add column if not exists content_sha256 text;
+
+-- Writers that don't know about the checksum (web editor, collab server) would otherwise leave a
+-- stale one behind; clear it so clients skip verification for that row.
+create or replace function public.files_clear_stale_checksum()
+returns trigger
+language plpgsql
+as $$
+begin
+  if new.content is distinct from old.content
+     and new.content_sha256 is not distinct from old.content_sha256 then
+    new.content_sha256 := null;
+  end if;
+  return new;
+end;
+$$;
+
+drop trigger if exists files_clear_stale_checksum on public.files;
+create trigger files_clear_stale_checksum
+  before update on public.files
+  for each row execute function public.files_clear_stale_checksum();
+
+-- Content generation counter. Maintained here rather than by clients so it orders writes from
+-- every device correctly no matter how their clocks drift; metadata-only updates leave it alone.
+alter table public.files add column if not exists rev bigint not null default 1;
+
+create or replace function public.files_bump_rev()
+returns trigger
+language plpgsql
+as $$
+begin
+  if new.content is distinct from old.content then
+    new.rev := old.rev + 1;
+  else
+    new.rev := old.rev;
+  end if;
+  return new;
+end;
+$$;
+
+drop trigger if exists files_bump_rev on public.files;
+create trigger files_bump_rev
+  before update on public.files
+  for each row execute function public.files_bump_rev();
+
+-- Applies a desktop delta push: splices `p_edits` ([{start, delete, insert}], character offsets
+-- into the current content) only if the row still holds the client's base, and verifies the result.
+-- Returns no row when the base doesn't match, so the client falls back to a full upload.
+create or replace function public.apply_file_patch(
+  p_file_id uuid,
+  p_base_sha256 text,
+  p_edits jsonb,
+  p_new_sha256 text,
+  p_kind text,
+  p_updated_at timestamptz
+)
+returns table (id uuid, updated_at timestamptz, rev bigint)
+language plpgsql
+security invoker
+set search_path = public
+as $$
+#variable_conflict use_column
+declare
+  v_content text;
+  v_edit jsonb;
+begin
+  select f.content into v_content
+  from public.files f
+  where f.id = p_file_id and f.content_sha256 = p_base_sha256
+  for update;
+  if not found then
+    return;
+  end if;
+
+  -- Highest offset first, so earlier offsets stay valid.
+  for v_edit in
+    select value from jsonb_array_elements(p_edits) order by (value->>'start')::int desc
+  loop
+    v_content := overlay(
+      v_content placing coalesce(v_edit->>'insert', '')
+      from (v_edit->>'start')::int + 1
+      for (v_edit->>'delete')::int
+    );
+  end loop;
+
+  if encode(sha256(convert_to(v_content, 'UTF8')), 'hex') <> p_new_sha256 then
+    raise exception 'patched content does not match the expected checksum';
+  end if;
+
+  return query
+  update public.files f
+  set content = v_content, content_sha256 = p_new_sha256, kind = p_kind, updated_at = p_updated_at
+  where f.id = p_file_id
+  returning f.id, f.updated_at, f.rev;
+end;
+$$;
 
 alter table public.files enable row level security;
 drop policy if exists "Users can view their own files" on public.files;
@@ -503,3 +600,278 @@ $$;
 
 revoke all on function public.claim_async_jobs(text, int, int) from public;
 grant execute on function public.claim_async_jobs(text, int, int) to service_role;
+
+-- 10) CRDT change log for notes marked `kind: collab`. Rows are append-only automerge change chunks
+-- (base64, or an E2EE envelope); clients replay them in id order to merge concurrent edits.
+create table if not exists public.file_crdt_changes (
+  id bigint generated always as identity primary key,
+  file_id uuid references public.files(id) on delete cascade not null,
+  author_id uuid references public.profiles(id) on delete set null default auth.uid(),
+  changes text not null,
+  created_at timestamptz default now()
+);
+
+create index if not exists file_crdt_changes_file_id_idx
+  on public.file_crdt_changes (file_id, id);
+
+alter table public.file_crdt_changes enable row level security;
+drop policy if exists "file_crdt_changes_select_via_file_access" on public.file_crdt_changes;
+drop policy if exists "file_crdt_changes_insert_via_file_edit" on public.file_crdt_changes;
+
+-- Visibility follows the file (the files RLS applies inside the subquery).
+create policy "file_crdt_changes_select_via_file_access" on public.file_crdt_changes
+  for select
+  using (exists (select 1 from public.files fl where fl.id = file_crdt_changes.file_id));
+
+create policy "file_crdt_changes_insert_via_file_edit" on public.file_crdt_changes
+  for insert
+  with check (
+    auth.uid() = author_id
+    and exists (
+      select 1 from public.files fl
+      where fl.id = file_crdt_changes.file_id
+        and (
+          auth.uid() = fl.owner_id
+          or public.access_can_edit(fl.access)
+          or exists (
+            select 1 from public.folders f
+            where f.id = fl.folder_id
+              and (auth.uid() = f.owner_id or public.access_can_edit(f.access))
+          )
+        )
+    )
+  );
+
+-- 11) Server-side note history. Every content change keeps the previous version here (as stored:
+-- compressed or E2EE bodies stay opaque), capped per file, so clients can list and diff revisions.
+create table if not exists public.file_revisions (
+  id bigint generated always as identity primary key,
+  file_id uuid references public.files(id) on delete cascade not null,
+  rev bigint not null,
+  content text,
+  content_sha256 text,
+  bytes int not null default 0,
+  updated_at timestamptz,
+  created_at timestamptz default now(),
+  unique (file_id, rev)
+);
+
+alter table public.file_revisions enable row level security;
+drop policy if exists "file_revisions_select_via_file_access" on public.file_revisions;
+
+-- Read-only for clients; rows are written by the trigger below.
+create policy "file_revisions_select_via_file_access" on public.file_revisions
+  for select
+  using (exists (select 1 from public.files fl where fl.id = file_revisions.file_id));
+
+create or replace function public.files_keep_revision()
+returns trigger
+language plpgsql
+security definer
+set search_path = public
+as $$
+begin
+  if new.content is distinct from old.content then
+    insert into public.file_revisions (file_id, rev, content, content_sha256, bytes, updated_at)
+    values (old.id, old.rev, old.content, old.content_sha256, coalesce(length(old.content), 0), old.updated_at)
+    on conflict (file_id, rev) do nothing;
+    delete from public.file_revisions
+    where file_id = old.id and rev <= old.rev - 50;
+  end if;
+  return null;
+end;
+$$;
+
+drop trigger if exists files_keep_revision on public.files;
+create trigger files_keep_revision
+  after update on public.files
+  for each row execute function public.files_keep_revision();
+
+-- 12) Folder change watermark. Desktop sync caches the folder tree and only asks for folders
+-- touched since its last pull; renames and moves bump `updated_at` here so they are included.
+alter table public.folders add column if not exists updated_at timestamptz default now();
+create index if not exists folders_updated_at_idx on public.folders (updated_at);
+
+create or replace function public.folders_touch_updated_at()
+returns trigger
+language plpgsql
+as $$
+begin
+  new.updated_at := now();
+  return new;
+end;
+$$;
+
+drop trigger if exists folders_touch_updated_at on public.folders;
+create trigger folders_touch_updated_at
+  before update on public.folders
+  for each row execute function public.folders_touch_updated_at();
+
+-- 13) Project subtree lookup. Desktop pulls ask for the synced project's folders only, instead of
+-- every folder the account can see. Runs as the caller, so folders RLS still applies.
+create or replace function public.folders_subtree(p_root uuid)
+returns setof public.folders
+language sql
+stable
+as $$
+  with recursive tree as (
+    select f.* from public.folders f where f.id = p_root
+    union
+    select c.* from public.folders c join tree t on c.parent_id = t.id
+  )
+  select * from tree;
+$$;
+
+-- 14) One-row summary of everything a desktop pull reads for a project. Pulls compare it with the
+-- previous one and skip the full listing when nothing moved; counts catch deletions.
+create or replace function public.project_sync_head(p_root uuid)
+returns table (
+  folders bigint,
+  folders_updated_at timestamptz,
+  files bigint,
+  files_updated_at timestamptz,
+  resources bigint,
+  resources_updated_at timestamptz
+)
+language sql
+stable
+as $$
+  with tree as (select id, updated_at from public.folders_subtree(p_root))
+  select
+    (select count(*) from tree),
+    (select max(t.updated_at) from tree t),
+    (select count(*) from public.files f where f.folder_id in (select id from tree)),
+    (select max(f.updated_at) from public.files f where f.folder_id in (select id from tree)),
+    (select count(*) from public.project_resources r where r.project_folder_id = p_root),
+    (select max(r.updated_at) from public.project_resources r where r.project_folder_id = p_root);
+$$;
+
+-- 15) Device registry. Each desktop install has a stable id, sent with every request as the
+-- `x-diregram-device` header: rows it writes are stamped with it, and `register_device` keeps one
+-- row per install so users can see their machines and revoke a lost one's sessions.
+alter table public.files add column if not exists last_modified_by_device text;
+
+create table if not exists public.devices (
+  owner_id uuid references auth.users(id) on delete cascade not null default auth.uid(),
+  device_id text not null,
+  name text,
+  platform text,
+  app_version text,
+  session_id uuid,
+  first_seen_at timestamptz default now(),
+  last_seen_at timestamptz default now(),
+  revoked_at timestamptz,
+  primary key (owner_id, device_id)
+);
+
+alter table public.devices enable row level security;
+drop policy if exists "devices_select_own" on public.devices;
+-- Read-only for clients; rows are written by the functions below.
+create policy "devices_select_own" on public.devices for select using (auth.uid() = owner_id);
+
+create or replace function public.files_stamp_device()
+returns trigger
+language plpgsql
+as $$
+begin
+  new.last_modified_by_device :=
+    coalesce(nullif(current_setting('request.headers', true), '')::json->>'x-diregram-device', new.last_modified_by_device);
+  return new;
+end;
+$$;
+
+drop trigger if exists files_stamp_device on public.files;
+create trigger files_stamp_device
+  before insert or update on public.files
+  for each row execute function public.files_stamp_device();
+
+create or replace function public.register_device(p_device_id text, p_name text, p_platform text, p_app_version text)
+returns table (revoked_at timestamptz)
+language sql
+security definer
+set search_path = public
+as $$
+  insert into public.devices as d (owner_id, device_id, name, platform, app_version, session_id)
+  values (auth.uid(), p_device_id, p_name, p_platform, p_app_version, (auth.jwt()->>'session_id')::uuid)
+  on conflict (owner_id, device_id) do update
+    set name = excluded.name,
+        platform = excluded.platform,
+        app_version = excluded.app_version,
+        revoked_at = case when d.session_id is distinct from excluded.session_id then null else d.revoked_at end,
+        session_id = excluded.session_id,
+        last_seen_at = now()
+  returning d.revoked_at;
+$$;
+
+-- Ends the device's sign-in session: its refresh token stops working, so it is signed out within
+-- one access-token lifetime. Signing in again on that device clears the revocation.
+create or replace function public.revoke_device(p_device_id text)
+returns void
+language plpgsql
+security definer
+set search_path = public, auth
+as $$
+declare
+  v_session uuid;
+begin
+  update public.devices d
+  set revoked_at = now()
+  where d.owner_id = auth.uid() and d.device_id = p_device_id
+  returning d.session_id into v_session;
+  if not found then
+    raise exception 'unknown device';
+  end if;
+  if v_session is not null then
+    delete from auth.sessions s where s.id = v_session and s.user_id = auth.uid();
+  end if;
+end;
+$$;
+
+-- 16) Remote unlink. A row here asks a device (see `devices`) to stop syncing and forget its
+-- session the next time it pulls, optionally deleting what it synced. The device acknowledges it.
+create table if not exists public.device_controls (
+  id uuid primary key default gen_random_uuid(),
+  owner_id uuid references auth.users(id) on delete cascade not null default auth.uid(),
+  device_id text not null,
+  remove_content boolean not null default false,
+  requested_at timestamptz default now(),
+  acknowledged_at timestamptz
+);
+
+create index if not exists device_controls_pending_idx
+  on public.device_controls (owner_id, device_id) where acknowledged_at is null;
+
+alter table public.device_controls enable row level security;
+drop policy if exists "device_controls_select_own" on public.device_controls;
+drop policy if exists "device_controls_insert_own" on public.device_controls;
+drop policy if exists "device_controls_update_own" on public.device_controls;
+create policy "device_controls_select_own" on public.device_controls for select using (auth.uid() = owner_id);
+create policy "device_controls_insert_own" on public.device_controls for insert with check (auth.uid() = owner_id);
+create policy "device_controls_update_own" on public.device_controls for update using (auth.uid() = owner_id);
+
+-- 17) Note tags written by desktop sync (`tags.sync_remote`), one row per file and tag, so the web
+-- app sees the same tags. Visibility and edits follow the file.
+create table if not exists public.file_tags (
+  owner_id uuid references public.profiles(id) on delete cascade not null default auth.uid(),
+  project_folder_id uuid references public.folders(id) on delete cascade not null,
+  file_id uuid references public.files(id) on delete cascade not null,
+  tag text not null,
+  created_at timestamptz default now(),
+  primary key (file_id, tag)
+);
+
+create index if not exists file_tags_project_tag_idx on public.file_tags (project_folder_id, tag);
+
+alter table public.file_tags enable row level security;
+drop policy if exists "file_tags_select_via_file_access" on public.file_tags;
+drop policy if exists "file_tags_insert_via_file_access" on public.file_tags;
+drop policy if exists "file_tags_delete_via_file_access" on public.file_tags;
+create policy "file_tags_select_via_file_access" on public.file_tags
+  for select
+  using (exists (select 1 from public.files fl where fl.id = file_tags.file_id));
+create policy "file_tags_insert_via_file_access" on public.file_tags
+  for insert
+  with check (auth.uid() = owner_id and exists (select 1 from public.files fl where fl.id = file_tags.file_id));
+create policy "file_tags_delete_via_file_access" on public.file_tags
+  for delete
+  using (exists (select 1 from public.files fl where fl.id = file_tags.file_id));